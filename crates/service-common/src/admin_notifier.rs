@@ -0,0 +1,203 @@
+// /pdf-bookstore/crates/service-common/src/admin_notifier.rs
+
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Tujuan alert admin
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlertSink {
+    /// Hanya di-log level error (default)
+    Log,
+    /// Slack incoming webhook
+    Slack(String),
+    /// Email ke alamat admin lewat AlertMailer service (auth-service: SMTP yang sama dengan email user)
+    Email(String),
+}
+
+/// Pengirim email untuk ADMIN_ALERT_CHANNEL=email, dipasang service yang punya SMTP
+#[async_trait]
+pub trait AlertMailer: Send + Sync {
+    async fn send_alert(&self, to: &str, message: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// Notifikasi admin untuk kondisi kritis (circuit breaker open, lonjakan login gagal, webhook gagal berulang)
+/// Alert dengan key yang sama di-debounce supaya tidak terjadi alert storm
+pub struct AdminNotifier {
+    service: &'static str,
+    sink: AlertSink,
+    client: reqwest::Client,
+    mailer: Option<Arc<dyn AlertMailer>>,
+    debounce: Duration,
+    threshold: usize,
+    window: Duration,
+    state: Mutex<NotifierState>,
+}
+
+#[derive(Default)]
+struct NotifierState {
+    last_sent: HashMap<String, Instant>,
+    events: HashMap<String, Vec<Instant>>,
+}
+
+impl AdminNotifier {
+    /// ADMIN_ALERT_CHANNEL=log|slack|email (default log), ADMIN_ALERT_SLACK_WEBHOOK_URL, ADMIN_ALERT_EMAIL,
+    /// ADMIN_ALERT_DEBOUNCE_SECONDS (default 300), ADMIN_ALERT_THRESHOLD (default `default_threshold`
+    /// kejadian per service) dalam ADMIN_ALERT_WINDOW_SECONDS (default 600)
+    pub fn from_env(service: &'static str, default_threshold: u64) -> Self {
+        let read = |key: &str, default: u64| {
+            env::var(key).ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(default)
+        };
+
+        let sink = match env::var("ADMIN_ALERT_CHANNEL").unwrap_or_default().to_lowercase().as_str() {
+            "slack" => match env::var("ADMIN_ALERT_SLACK_WEBHOOK_URL") {
+                Ok(url) if !url.trim().is_empty() => AlertSink::Slack(url.trim().to_string()),
+                _ => {
+                    tracing::warn!("ADMIN_ALERT_CHANNEL=slack tanpa ADMIN_ALERT_SLACK_WEBHOOK_URL, alert hanya di-log");
+                    AlertSink::Log
+                }
+            },
+            "email" => match env::var("ADMIN_ALERT_EMAIL") {
+                Ok(to) if !to.trim().is_empty() => AlertSink::Email(to.trim().to_string()),
+                _ => {
+                    tracing::warn!("ADMIN_ALERT_CHANNEL=email tanpa ADMIN_ALERT_EMAIL, alert hanya di-log");
+                    AlertSink::Log
+                }
+            },
+            "" | "log" => AlertSink::Log,
+            other => {
+                tracing::warn!("ADMIN_ALERT_CHANNEL '{}' tidak dikenal, alert hanya di-log", other);
+                AlertSink::Log
+            }
+        };
+
+        Self::new(
+            service,
+            sink,
+            Duration::from_secs(read("ADMIN_ALERT_DEBOUNCE_SECONDS", 300)),
+            read("ADMIN_ALERT_THRESHOLD", default_threshold) as usize,
+            Duration::from_secs(read("ADMIN_ALERT_WINDOW_SECONDS", 600)),
+        )
+    }
+
+    fn new(service: &'static str, sink: AlertSink, debounce: Duration, threshold: usize, window: Duration) -> Self {
+        Self {
+            service,
+            sink,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(5))
+                .build()
+                .unwrap_or_default(),
+            mailer: None,
+            debounce,
+            threshold: threshold.max(1),
+            window,
+            state: Mutex::new(NotifierState::default()),
+        }
+    }
+
+    /// Pasang pengirim email untuk channel email, tanpa mailer alert email hanya di-log
+    pub fn with_mailer(mut self, mailer: Arc<dyn AlertMailer>) -> Self {
+        self.mailer = Some(mailer);
+        self
+    }
+
+    /// Kirim alert langsung (misal circuit breaker open), di-skip selama masa debounce key yang sama
+    pub fn notify(&self, key: &str, message: impl Into<String>) {
+        if self.should_send(key, Instant::now()) {
+            self.dispatch(key, message.into());
+        }
+    }
+
+    /// Catat satu kejadian, alert dikirim saat jumlah kejadian dalam window mencapai threshold
+    pub fn record(&self, key: &str, message: impl FnOnce(usize) -> String) {
+        let now = Instant::now();
+        if let Some(count) = self.threshold_crossed(key, now) {
+            if self.should_send(key, now) {
+                self.dispatch(key, message(count));
+            }
+        }
+    }
+
+    fn threshold_crossed(&self, key: &str, now: Instant) -> Option<usize> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let events = state.events.entry(key.to_string()).or_default();
+        events.retain(|at| now.duration_since(*at) < self.window);
+        events.push(now);
+
+        let count = events.len();
+        if count < self.threshold {
+            return None;
+        }
+        events.clear();
+        Some(count)
+    }
+
+    fn should_send(&self, key: &str, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.last_sent.get(key) {
+            Some(sent) if now.duration_since(*sent) < self.debounce => false,
+            _ => {
+                state.last_sent.insert(key.to_string(), now);
+                true
+            }
+        }
+    }
+
+    fn dispatch(&self, key: &str, message: String) {
+        tracing::error!(alert = key, service = self.service, "ADMIN ALERT: {}", message);
+
+        let text = format!("[{}] {}", self.service, message);
+        match &self.sink {
+            AlertSink::Log => {}
+            AlertSink::Slack(url) => {
+                let request = self.client
+                    .post(url)
+                    .json(&serde_json::json!({ "text": text }));
+
+                tokio::spawn(async move {
+                    if let Err(e) = request.send().await.and_then(|r| r.error_for_status()) {
+                        tracing::warn!("Gagal mengirim admin alert ke Slack: {}", e);
+                    }
+                });
+            }
+            AlertSink::Email(to) => {
+                let Some(mailer) = self.mailer.clone() else {
+                    tracing::warn!("{} tidak punya pengirim email, admin alert hanya di-log", self.service);
+                    return;
+                };
+                let to = to.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = mailer.send_alert(&to, &text).await {
+                        tracing::warn!("Gagal mengirim admin alert ke email: {}", e);
+                    }
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threshold_and_debounce() {
+        let notifier = AdminNotifier::new("auth-service", AlertSink::Log, Duration::from_secs(60), 3, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert_eq!(notifier.threshold_crossed("login_failures", now), None);
+        assert_eq!(notifier.threshold_crossed("login_failures", now), None);
+        assert_eq!(notifier.threshold_crossed("login_failures", now), Some(3));
+        assert_eq!(notifier.threshold_crossed("login_failures", now + Duration::from_secs(120)), None);
+
+        assert!(notifier.should_send("circuit_open:book-service", now));
+        assert!(!notifier.should_send("circuit_open:book-service", now + Duration::from_secs(30)));
+        assert!(notifier.should_send("circuit_open:payment-service", now));
+        assert!(notifier.should_send("circuit_open:book-service", now + Duration::from_secs(61)));
+    }
+}
@@ -0,0 +1,157 @@
+// /pdf-bookstore/crates/service-common/src/client_ip.rs
+
+use axum::http::HeaderMap;
+use std::{env, net::IpAddr};
+
+/// Resolve IP client asli dari X-Forwarded-For / X-Real-IP, dipakai untuk rate limit,
+/// audit IP, dedup view, dan allowlist webhook di semua service
+#[derive(Debug, Clone, Default)]
+pub struct ClientIpResolver {
+    trusted_proxies: Vec<(IpAddr, u8)>,
+}
+
+impl ClientIpResolver {
+    /// TRUSTED_PROXIES: CIDR / IP comma-separated, proxy yang boleh mengisi X-Forwarded-For / X-Real-IP
+    pub fn from_env() -> Self {
+        Self { trusted_proxies: parse_cidr_list("TRUSTED_PROXIES", &env::var("TRUSTED_PROXIES").unwrap_or_default()) }
+    }
+
+    pub fn new(trusted_proxies: &[&str]) -> Self {
+        Self { trusted_proxies: trusted_proxies.iter().filter_map(|p| parse_cidr(p)).collect() }
+    }
+
+    pub fn is_trusted_proxy(&self, ip: IpAddr) -> bool {
+        cidr_list_contains(&self.trusted_proxies, ip)
+    }
+
+    /// Header hanya dipercaya kalau peer langsung termasuk TRUSTED_PROXIES
+    pub fn resolve(&self, peer_ip: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.is_trusted_proxy(peer_ip) {
+            return peer_ip;
+        }
+
+        // Ambil hop paling kanan yang bukan proxy terpercaya
+        if let Some(forwarded) = headers.get("x-forwarded-for").and_then(|h| h.to_str().ok()) {
+            let client = forwarded
+                .split(',')
+                .rev()
+                .filter_map(|hop| hop.trim().parse::<IpAddr>().ok())
+                .find(|ip| !self.is_trusted_proxy(*ip));
+
+            if let Some(ip) = client {
+                return ip;
+            }
+        }
+
+        headers.get("x-real-ip")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|ip| ip.trim().parse().ok())
+            .unwrap_or(peer_ip)
+    }
+}
+
+/// Parse daftar CIDR comma-separated dari env `key`, entry tidak valid di-log lalu dilewati
+pub fn parse_cidr_list(key: &str, raw: &str) -> Vec<(IpAddr, u8)> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let parsed = parse_cidr(entry);
+            if parsed.is_none() {
+                tracing::warn!("Invalid {} entry: {}", key, entry);
+            }
+            parsed
+        })
+        .collect()
+}
+
+pub fn cidr_list_contains(list: &[(IpAddr, u8)], ip: IpAddr) -> bool {
+    list.iter().any(|(network, prefix)| cidr_contains(*network, *prefix, ip))
+}
+
+/// Parse "10.0.0.0/8" atau IP tunggal ("172.18.0.5")
+pub fn parse_cidr(entry: &str) -> Option<(IpAddr, u8)> {
+    let (addr, prefix) = match entry.split_once('/') {
+        Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, prefix.parse::<u8>().ok()?),
+        None => {
+            let addr = entry.parse::<IpAddr>().ok()?;
+            (addr, if addr.is_ipv4() { 32 } else { 128 })
+        }
+    };
+
+    let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+    (prefix <= max_prefix).then_some((addr, prefix))
+}
+
+fn cidr_contains(network: IpAddr, prefix: u8, ip: IpAddr) -> bool {
+    match (network, ip) {
+        (IpAddr::V4(net), IpAddr::V4(ip)) => {
+            let mask = if prefix == 0 { 0 } else { u32::MAX << (32 - prefix) };
+            (u32::from(net) & mask) == (u32::from(ip) & mask)
+        }
+        (IpAddr::V6(net), IpAddr::V6(ip)) => {
+            let mask = if prefix == 0 { 0 } else { u128::MAX << (128 - prefix) };
+            (u128::from(net) & mask) == (u128::from(ip) & mask)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn forwarded(xff: Option<&str>, real_ip: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(xff) = xff {
+            headers.insert("x-forwarded-for", xff.parse().unwrap());
+        }
+        if let Some(real_ip) = real_ip {
+            headers.insert("x-real-ip", real_ip.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_resolve_trusts_only_configured_proxies() {
+        let resolver = ClientIpResolver::new(&["10.0.0.0/8", "172.18.0.5"]);
+
+        // Peer bukan proxy terpercaya: header spoof diabaikan
+        let spoofed = forwarded(Some("1.2.3.4"), Some("5.6.7.8"));
+        assert_eq!(resolver.resolve(ip("8.8.8.8"), &spoofed), ip("8.8.8.8"));
+
+        // Multi-hop: hop paling kanan yang bukan proxy, entry palsu di kiri tidak dipakai
+        let chain = forwarded(Some("6.6.6.6, 203.0.113.9, 10.1.2.3, 172.18.0.5"), None);
+        assert_eq!(resolver.resolve(ip("10.0.0.2"), &chain), ip("203.0.113.9"));
+
+        // Semua hop proxy / tidak valid -> X-Real-IP, lalu peer
+        let only_proxies = forwarded(Some("10.9.9.9, bukan-ip"), Some("198.51.100.7"));
+        assert_eq!(resolver.resolve(ip("10.0.0.2"), &only_proxies), ip("198.51.100.7"));
+        assert_eq!(resolver.resolve(ip("10.0.0.2"), &HeaderMap::new()), ip("10.0.0.2"));
+
+        // Tanpa TRUSTED_PROXIES header tidak pernah dipercaya
+        assert_eq!(ClientIpResolver::default().resolve(ip("10.0.0.2"), &chain), ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn test_parse_cidr_rejects_invalid_entries() {
+        assert_eq!(parse_cidr("10.0.0.0/8"), Some((ip("10.0.0.0"), 8)));
+        assert_eq!(parse_cidr("172.18.0.5"), Some((ip("172.18.0.5"), 32)));
+        assert_eq!(parse_cidr("fd00::/8"), Some((ip("fd00::"), 8)));
+        assert_eq!(parse_cidr("::1"), Some((ip("::1"), 128)));
+
+        for invalid in ["", "10.0.0.0/33", "fd00::/129", "10.0.0.0/", "10.0.0/8", "proxy.local", "10.0.0.0/-1"] {
+            assert_eq!(parse_cidr(invalid), None, "{}", invalid);
+        }
+        assert_eq!(parse_cidr_list("TRUSTED_PROXIES", " 10.0.0.0/8, bukan-ip ,, ::1"), vec![(ip("10.0.0.0"), 8), (ip("::1"), 128)]);
+
+        assert!(cidr_contains(ip("10.0.0.0"), 8, ip("10.255.0.1")));
+        assert!(!cidr_contains(ip("10.0.0.0"), 8, ip("11.0.0.1")));
+        assert!(cidr_contains(ip("0.0.0.0"), 0, ip("8.8.8.8")));
+        assert!(!cidr_contains(ip("10.0.0.0"), 8, ip("::ffff:10.0.0.1")));
+    }
+}
@@ -0,0 +1,234 @@
+// /pdf-bookstore/crates/service-common/src/concurrency_limit.rs
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use hyper::body::{Body as HttpBody, Frame, SizeHint};
+use std::{
+    env,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Batas request in-flight per instance: saat penuh, request menunggu sebentar lalu ditolak 503
+/// dengan Retry-After (load shedding) supaya DB pool / memory tidak habis dan timeout tidak merambat
+pub struct ConcurrencyLimit {
+    semaphore: Option<Arc<Semaphore>>,
+    queue_wait: Duration,
+    retry_after_secs: u64,
+}
+
+impl ConcurrencyLimit {
+    /// MAX_IN_FLIGHT_REQUESTS (default 256, 0 = tanpa batas), LOAD_SHED_QUEUE_WAIT_MS (default 100,
+    /// 0 = langsung ditolak saat penuh), OVERLOAD_RETRY_AFTER_SECONDS (default 2)
+    pub fn from_env() -> Self {
+        Self::from_lookup(|key| env::var(key).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let read = |key: &str, default: u64| {
+            lookup(key).and_then(|v| v.parse::<u64>().ok()).unwrap_or(default)
+        };
+
+        Self::new(
+            read("MAX_IN_FLIGHT_REQUESTS", 256) as usize,
+            Duration::from_millis(read("LOAD_SHED_QUEUE_WAIT_MS", 100)),
+            read("OVERLOAD_RETRY_AFTER_SECONDS", 2).max(1),
+        )
+    }
+
+    fn new(max_in_flight: usize, queue_wait: Duration, retry_after_secs: u64) -> Self {
+        Self {
+            semaphore: (max_in_flight > 0).then(|| Arc::new(Semaphore::new(max_in_flight))),
+            queue_wait,
+            retry_after_secs,
+        }
+    }
+
+    fn overloaded(&self) -> Response {
+        let mut response = (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "success": false,
+                "message": "Server sedang sibuk, silakan coba lagi sebentar",
+                "error_code": "SERVICE_OVERLOADED",
+            })),
+        ).into_response();
+
+        response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(self.retry_after_secs));
+        response
+    }
+}
+
+/// Middleware concurrency limit, health check tidak dihitung supaya container tidak di-restart saat beban tinggi
+pub async fn concurrency_limit_middleware(
+    State(limit): State<Arc<ConcurrencyLimit>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(semaphore) = limit.semaphore.clone() else {
+        return next.run(req).await;
+    };
+    if req.uri().path().starts_with("/health") {
+        return next.run(req).await;
+    }
+
+    let permit = match semaphore.clone().try_acquire_owned() {
+        Ok(permit) => Some(permit),
+        Err(_) if limit.queue_wait.is_zero() => None,
+        Err(_) => tokio::time::timeout(limit.queue_wait, semaphore.acquire_owned())
+            .await
+            .ok()
+            .and_then(Result::ok),
+    };
+
+    match permit {
+        Some(permit) => next.run(req).await.map(|body| Body::new(PermitBody { inner: body, _permit: permit })),
+        None => {
+            tracing::warn!("Request {} {} ditolak: batas in-flight tercapai", req.method(), req.uri().path());
+            limit.overloaded()
+        }
+    }
+}
+
+/// Body response yang memegang permit sampai selesai dikirim (atau client putus),
+/// supaya download / stream panjang tetap dihitung sebagai request in-flight
+struct PermitBody {
+    inner: Body,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl HttpBody for PermitBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn app(limit: Arc<ConcurrencyLimit>) -> Router {
+        Router::new()
+            .route("/api/slow", get(|| async { "ok" }))
+            .route("/health", get(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(limit, concurrency_limit_middleware))
+    }
+
+    fn request(path: &str) -> Request {
+        Request::builder().uri(path).body(Body::empty()).unwrap()
+    }
+
+    fn config(vars: &'static [(&'static str, &'static str)]) -> ConcurrencyLimit {
+        ConcurrencyLimit::from_lookup(|key| vars.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string()))
+    }
+
+    #[tokio::test]
+    async fn test_defaults_and_health_bypass() {
+        let default = config(&[]);
+        assert_eq!(default.semaphore.as_ref().unwrap().available_permits(), 256);
+        assert_eq!(default.queue_wait, Duration::from_millis(100));
+        assert_eq!(default.retry_after_secs, 2);
+        assert!(config(&[("MAX_IN_FLIGHT_REQUESTS", "0")]).semaphore.is_none());
+
+        // Health check tetap dijawab saat penuh supaya container tidak di-restart
+        let limit = Arc::new(config(&[("MAX_IN_FLIGHT_REQUESTS", "1"), ("LOAD_SHED_QUEUE_WAIT_MS", "0"), ("OVERLOAD_RETRY_AFTER_SECONDS", "3")]));
+        let app = app(limit.clone());
+        let held = limit.semaphore.clone().unwrap().try_acquire_owned().unwrap();
+
+        let response = app.clone().oneshot(request("/api/slow")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "3");
+
+        let response = app.clone().oneshot(request("/health")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        drop(held);
+        let response = app.oneshot(request("/api/slow")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_unfinished_body_counts_as_in_flight() {
+        // Tanpa batas: semua request diteruskan
+        let unlimited = app(Arc::new(config(&[("MAX_IN_FLIGHT_REQUESTS", "0")])));
+        let first = unlimited.clone().oneshot(request("/api/slow")).await.unwrap();
+        let second = unlimited.oneshot(request("/api/slow")).await.unwrap();
+        assert_eq!((first.status(), second.status()), (StatusCode::OK, StatusCode::OK));
+
+        // Response proxy yang body-nya belum habis (client lambat) tetap memegang permit
+        let limit = Arc::new(config(&[("MAX_IN_FLIGHT_REQUESTS", "1"), ("LOAD_SHED_QUEUE_WAIT_MS", "0")]));
+        let app = app(limit.clone());
+        let pending = app.clone().oneshot(request("/api/slow")).await.unwrap();
+        assert_eq!(limit.semaphore.as_ref().unwrap().available_permits(), 0);
+
+        let shed = app.clone().oneshot(request("/api/slow")).await.unwrap();
+        assert_eq!(shed.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        drop(pending);
+        assert_eq!(limit.semaphore.as_ref().unwrap().available_permits(), 1);
+        let response = app.oneshot(request("/health")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_streamed_body_holds_permit_until_sent() {
+        // Download PDF di-stream: permit baru dilepas setelah body selesai dikirim
+        let app = app(Arc::new(config(&[("MAX_IN_FLIGHT_REQUESTS", "1"), ("LOAD_SHED_QUEUE_WAIT_MS", "0")])));
+
+        let streaming = app.clone().oneshot(request("/api/slow")).await.unwrap();
+        assert_eq!(streaming.status(), StatusCode::OK);
+
+        let shed = app.clone().oneshot(request("/api/slow")).await.unwrap();
+        assert_eq!(shed.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(shed.headers()[header::RETRY_AFTER], "2");
+
+        let body = axum::body::to_bytes(streaming.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"ok");
+
+        let response = app.oneshot(request("/api/slow")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_queue_wait_before_shedding() {
+        // Webhook yang datang saat penuh menunggu sebentar sebelum ditolak
+        let limit = Arc::new(config(&[("MAX_IN_FLIGHT_REQUESTS", "1"), ("LOAD_SHED_QUEUE_WAIT_MS", "500"), ("OVERLOAD_RETRY_AFTER_SECONDS", "0")]));
+        let waiting = app(limit.clone());
+
+        let held = limit.semaphore.clone().unwrap().try_acquire_owned().unwrap();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            drop(held);
+        });
+        let response = waiting.oneshot(request("/api/slow")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        drop(response);
+
+        let short = Arc::new(config(&[("MAX_IN_FLIGHT_REQUESTS", "1"), ("LOAD_SHED_QUEUE_WAIT_MS", "10"), ("OVERLOAD_RETRY_AFTER_SECONDS", "0")]));
+        let _held = short.semaphore.clone().unwrap().try_acquire_owned().unwrap();
+        let response = app(short).oneshot(request("/api/slow")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+    }
+}
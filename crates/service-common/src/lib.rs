@@ -0,0 +1,18 @@
+// /pdf-bookstore/crates/service-common/src/lib.rs
+
+//! Middleware bersama untuk api-gateway, auth-service, book-service dan payment-service.
+//! Konfigurasi dibaca dari env yang sama di semua service; default khusus service
+//! (misal path no-store) dioper oleh masing-masing `main.rs`.
+
+pub mod admin_notifier;
+pub mod client_ip;
+pub mod concurrency_limit;
+pub mod db_connect;
+pub mod public_routes;
+pub mod retention;
+pub mod security_headers;
+pub mod session_cookie;
+pub mod shutdown;
+pub mod token_scopes;
+pub mod trace_sampling;
+pub mod user_directory;
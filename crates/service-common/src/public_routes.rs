@@ -0,0 +1,132 @@
+// /pdf-bookstore/crates/service-common/src/public_routes.rs
+
+use axum::http::Method;
+use serde::Deserialize;
+use std::{env, fs};
+
+/// Policy bawaan, di-embed saat compile dari config/public_routes.json
+const DEFAULT_POLICY: &str = include_str!("../../../config/public_routes.json");
+
+/// Cara pencocokan pattern terhadap path request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchType {
+    Prefix,
+    Contains,
+}
+
+/// Satu aturan route public (tanpa auth)
+/// `methods` kosong = semua method, `except` = substring path yang tetap butuh auth
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PublicRoute {
+    pub pattern: String,
+    #[serde(rename = "match")]
+    pub match_type: MatchType,
+    #[serde(default)]
+    pub methods: Vec<String>,
+    #[serde(default)]
+    pub except: Vec<String>,
+}
+
+impl PublicRoute {
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        let path_matches = match self.match_type {
+            MatchType::Prefix => path.starts_with(&self.pattern),
+            MatchType::Contains => path.contains(&self.pattern),
+        };
+
+        let method_matches = self.methods.is_empty()
+            || self.methods.iter().any(|m| m.eq_ignore_ascii_case(method.as_str()));
+
+        path_matches && method_matches && !self.except.iter().any(|e| path.contains(e.as_str()))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PublicRouteFile {
+    routes: Vec<PublicRoute>,
+}
+
+/// Policy route public untuk api-gateway dan book-service (sumber: config/public_routes.json)
+#[derive(Debug, Clone)]
+pub struct PublicRoutePolicy {
+    routes: Vec<PublicRoute>,
+}
+
+impl PublicRoutePolicy {
+    /// Load dari PUBLIC_ROUTES_FILE jika di-set, fallback ke tabel default
+    pub fn from_env() -> Self {
+        let Ok(path) = env::var("PUBLIC_ROUTES_FILE") else {
+            return Self::default();
+        };
+
+        match fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| Self::from_json(&content))
+        {
+            Ok(policy) => {
+                tracing::info!("Public route policy dimuat dari {} ({} rules)", path, policy.routes.len());
+                policy
+            }
+            Err(e) => {
+                tracing::warn!("Gagal memuat public route policy {}: {}, pakai default", path, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn from_json(content: &str) -> Result<Self, String> {
+        serde_json::from_str::<PublicRouteFile>(content)
+            .map(|file| Self { routes: file.routes })
+            .map_err(|e| e.to_string())
+    }
+
+    /// Cek apakah request boleh lewat tanpa auth
+    pub fn is_public(&self, method: &Method, path: &str) -> bool {
+        self.routes.iter().any(|route| route.matches(method, path))
+    }
+}
+
+impl Default for PublicRoutePolicy {
+    fn default() -> Self {
+        Self::from_json(DEFAULT_POLICY).expect("config/public_routes.json tidak valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct PolicyCase {
+        method: String,
+        path: String,
+        public: bool,
+    }
+
+    #[derive(Deserialize)]
+    struct PolicyCases {
+        cases: Vec<PolicyCase>,
+    }
+
+    #[test]
+    fn test_default_policy_loads_shared_config() {
+        assert_eq!(PublicRoutePolicy::default().routes.len(), 20);
+        assert!(PublicRoutePolicy::from_json("{\"routes\": [{\"pattern\": \"/x\"}]}").is_err());
+    }
+
+    #[test]
+    fn test_shared_route_cases() {
+        let policy = PublicRoutePolicy::default();
+        let cases: PolicyCases = serde_json::from_str(DEFAULT_POLICY).unwrap();
+
+        for case in cases.cases {
+            let method = Method::from_bytes(case.method.as_bytes()).unwrap();
+            assert_eq!(
+                policy.is_public(&method, &case.path),
+                case.public,
+                "{} {} harus public={}", case.method, case.path, case.public
+            );
+        }
+    }
+}
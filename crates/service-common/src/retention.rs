@@ -0,0 +1,215 @@
+// /pdf-bookstore/crates/service-common/src/retention.rs
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{PgPool, Row};
+use std::{env, path::PathBuf};
+use tokio::io::AsyncWriteExt;
+
+pub type RetentionResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Retention window untuk satu tabel log (tabel wajib punya kolom `id` dan `created_at`)
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    pub table: &'static str,
+    pub retention_days: i64,
+}
+
+impl RetentionPolicy {
+    /// RETENTION_<TABLE>_DAYS (misal RETENTION_AUDIT_LOGS_DAYS), 0 = tabel tidak di-purge
+    pub fn from_env(table: &'static str, default_days: i64) -> Self {
+        let retention_days = env::var(format!("RETENTION_{}_DAYS", table.to_uppercase()))
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default_days);
+
+        Self { table, retention_days }
+    }
+}
+
+/// Konfigurasi retention job, daftar tabel ditentukan masing-masing service
+#[derive(Debug, Clone)]
+pub struct RetentionConfig {
+    pub policies: Vec<RetentionPolicy>,
+    pub dry_run: bool,
+    /// Archive JSONL hanya ditulis ke disk lokal, belum ada export ke cold storage (S3/GCS)
+    pub archive_dir: Option<PathBuf>,
+    /// Jumlah row yang di-archive + delete per transaksi
+    pub batch_size: i64,
+}
+
+impl RetentionConfig {
+    /// RETENTION_DRY_RUN (default false), RETENTION_BATCH_SIZE (default 1000),
+    /// RETENTION_ARCHIVE_DIR: direktori archive JSONL per tabel per run. Belum ada upload ke
+    /// cold storage, jadi direktori ini harus volume persisten yang di-sync/backup terpisah;
+    /// tanpa RETENTION_ARCHIVE_DIR row lama langsung dihapus permanen
+    pub fn from_env(policies: Vec<RetentionPolicy>) -> Self {
+        Self {
+            policies,
+            dry_run: env::var("RETENTION_DRY_RUN")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            archive_dir: env::var("RETENTION_ARCHIVE_DIR").ok()
+                .filter(|v| !v.trim().is_empty())
+                .map(PathBuf::from),
+            batch_size: env::var("RETENTION_BATCH_SIZE").ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1000i64)
+                .max(1),
+        }
+    }
+}
+
+/// Jalankan retention untuk semua policy, return ringkasan per tabel
+pub async fn run_retention(pool: &PgPool, config: &RetentionConfig) -> RetentionResult<serde_json::Value> {
+    let mut summary = serde_json::Map::new();
+
+    for policy in config.policies.iter().filter(|p| p.retention_days > 0) {
+        let cutoff = Utc::now() - Duration::days(policy.retention_days);
+
+        let expired: i64 = sqlx::query(&format!(
+            "SELECT COUNT(*) FROM {} WHERE created_at < $1", policy.table
+        ))
+        .bind(cutoff)
+        .fetch_one(pool)
+        .await?
+        .get(0);
+
+        if expired == 0 || config.dry_run {
+            summary.insert(policy.table.to_string(), serde_json::json!({
+                "expired": expired,
+                "archived": 0,
+                "deleted": 0,
+                "dry_run": config.dry_run,
+            }));
+            continue;
+        }
+
+        let (archived, deleted) = purge_expired(pool, policy.table, cutoff, config).await?;
+
+        summary.insert(policy.table.to_string(), serde_json::json!({
+            "expired": expired,
+            "archived": archived,
+            "deleted": deleted,
+            "dry_run": false,
+        }));
+    }
+
+    Ok(serde_json::Value::Object(summary))
+}
+
+/// Archive (jika archive dir di-set) lalu delete rows lama per batch
+/// Tiap batch satu statement DELETE ... RETURNING dalam transaksi yang baru di-commit setelah rows
+/// tertulis ke file, jadi tidak ada row terhapus tanpa ter-archive dan memory hanya berisi satu batch
+async fn purge_expired(
+    pool: &PgPool,
+    table: &str,
+    cutoff: DateTime<Utc>,
+    config: &RetentionConfig,
+) -> RetentionResult<(u64, u64)> {
+    let delete_batch = format!(
+        "DELETE FROM {table} t WHERE t.id IN (
+            SELECT id FROM {table} WHERE created_at < $1 ORDER BY created_at, id LIMIT $2
+        ) RETURNING row_to_json(t)::text"
+    );
+    let mut archive = None;
+    let (mut archived, mut deleted) = (0u64, 0u64);
+
+    loop {
+        let mut tx = pool.begin().await?;
+        let rows = sqlx::query(&delete_batch)
+            .bind(cutoff)
+            .bind(config.batch_size)
+            .fetch_all(&mut *tx)
+            .await?;
+
+        if let Some(ref dir) = config.archive_dir {
+            if archive.is_none() && !rows.is_empty() {
+                tokio::fs::create_dir_all(dir).await?;
+                let path = dir.join(format!("{}_{}.jsonl", table, Utc::now().format("%Y%m%d%H%M%S")));
+                archive = Some((tokio::fs::File::create(&path).await?, path));
+            }
+            if let Some((ref mut file, _)) = archive {
+                for row in &rows {
+                    let line: String = row.get(0);
+                    file.write_all(line.as_bytes()).await?;
+                    file.write_all(b"\n").await?;
+                }
+                file.flush().await?;
+                archived += rows.len() as u64;
+            }
+        }
+
+        tx.commit().await?;
+        deleted += rows.len() as u64;
+
+        if (rows.len() as i64) < config.batch_size {
+            break;
+        }
+    }
+
+    if let Some((_, path)) = archive {
+        tracing::info!("Archived {} rows dari {} ke {}", archived, table, path.display());
+    }
+    Ok((archived, deleted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_connect::test_pool;
+
+    #[tokio::test]
+    async fn test_expired_rows_archived_and_deleted_in_batches() {
+        let pool = test_pool().await;
+
+        // Tabel sementara per test supaya tidak bergantung pada schema service tertentu
+        let marker = format!("retention_test_{}", uuid::Uuid::new_v4().simple());
+        sqlx::query(&format!(
+            "CREATE TABLE {marker} (id BIGSERIAL PRIMARY KEY, note TEXT, created_at TIMESTAMPTZ NOT NULL)"
+        ))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let old = DateTime::parse_from_rfc3339("2000-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        for i in 0..5 {
+            sqlx::query(&format!("INSERT INTO {marker} (note, created_at) VALUES ($1, $2)"))
+                .bind(&marker)
+                .bind(old + Duration::minutes(i))
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        sqlx::query(&format!("INSERT INTO {marker} (note, created_at) VALUES ('baru', NOW())"))
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let archive_dir = env::temp_dir().join(&marker);
+        let config = RetentionConfig {
+            policies: Vec::new(),
+            dry_run: false,
+            archive_dir: Some(archive_dir.clone()),
+            batch_size: 2,
+        };
+        let cutoff = old + Duration::days(1);
+        let (archived, deleted) = purge_expired(&pool, &marker, cutoff, &config).await.unwrap();
+
+        let remaining: i64 = sqlx::query(&format!("SELECT COUNT(*) FROM {marker}"))
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+            .get(0);
+        sqlx::query(&format!("DROP TABLE {marker}")).execute(&pool).await.unwrap();
+        let mut lines = Vec::new();
+        for entry in std::fs::read_dir(&archive_dir).unwrap() {
+            lines.extend(std::fs::read_to_string(entry.unwrap().path()).unwrap().lines().map(String::from));
+        }
+        std::fs::remove_dir_all(&archive_dir).unwrap();
+
+        assert_eq!((archived, deleted), (5, 5));
+        assert_eq!(remaining, 1);
+        assert_eq!(lines.len(), 5);
+        assert!(lines.iter().all(|line| line.contains(&marker)));
+    }
+}
@@ -0,0 +1,216 @@
+// /pdf-bookstore/crates/service-common/src/security_headers.rs
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::{env, sync::Arc};
+
+const DEFAULT_CSP: &str = "default-src 'self'; script-src 'self'; style-src 'self' 'unsafe-inline'; img-src 'self' data: https:; font-src 'self'; connect-src 'self'; frame-ancestors 'none';";
+
+/// Konfigurasi security headers, dibaca sekali saat startup
+/// Header yang sudah di-set handler tidak ditimpa
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    enabled: bool,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    csp: Option<HeaderValue>,
+    hsts: Option<HeaderValue>,
+    no_store_paths: Vec<String>,
+}
+
+impl SecurityHeaders {
+    /// SECURITY_HEADERS_ENABLED (default true), SECURITY_CSP (kosong = tidak dikirim),
+    /// SECURITY_FRAME_OPTIONS (default DENY), SECURITY_REFERRER_POLICY (default strict-origin-when-cross-origin),
+    /// SECURITY_PERMISSIONS_POLICY, HSTS_ENABLED (default true jika ENVIRONMENT=production),
+    /// HSTS_MAX_AGE (default 31536000), HSTS_INCLUDE_SUBDOMAINS (default true), HSTS_PRELOAD (default false),
+    /// SECURITY_NO_STORE_PATHS (prefix path comma-separated untuk Cache-Control no-store,
+    /// default `default_no_store_paths` milik service)
+    pub fn from_env(default_no_store_paths: &str) -> Self {
+        Self::from_lookup(default_no_store_paths, |key| env::var(key).ok())
+    }
+
+    fn from_lookup(default_no_store_paths: &str, lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let flag = |key: &str, default: bool| {
+            lookup(key).map(|v| v == "true" || v == "1").unwrap_or(default)
+        };
+        let value = |key: &str, default: &str| lookup(key).unwrap_or_else(|| default.to_string());
+
+        let mut headers = vec![
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            (header::X_XSS_PROTECTION, "1; mode=block".to_string()),
+        ];
+        for (name, key, default) in [
+            (header::X_FRAME_OPTIONS, "SECURITY_FRAME_OPTIONS", "DENY"),
+            (header::REFERRER_POLICY, "SECURITY_REFERRER_POLICY", "strict-origin-when-cross-origin"),
+            (HeaderName::from_static("permissions-policy"), "SECURITY_PERMISSIONS_POLICY", "geolocation=(), microphone=(), camera=()"),
+        ] {
+            headers.push((name, value(key, default)));
+        }
+
+        let production = lookup("ENVIRONMENT").unwrap_or_default() == "production";
+        let hsts = flag("HSTS_ENABLED", production).then(|| {
+            let mut hsts = format!("max-age={}", value("HSTS_MAX_AGE", "31536000"));
+            if flag("HSTS_INCLUDE_SUBDOMAINS", true) {
+                hsts.push_str("; includeSubDomains");
+            }
+            if flag("HSTS_PRELOAD", false) {
+                hsts.push_str("; preload");
+            }
+            hsts
+        });
+
+        Self {
+            enabled: flag("SECURITY_HEADERS_ENABLED", true),
+            headers: headers.into_iter()
+                .filter_map(|(name, v)| parse_value(&name, &v).map(|v| (name, v)))
+                .collect(),
+            csp: parse_value(&header::CONTENT_SECURITY_POLICY, &value("SECURITY_CSP", DEFAULT_CSP)),
+            hsts: hsts.and_then(|v| parse_value(&header::STRICT_TRANSPORT_SECURITY, &v)),
+            no_store_paths: value("SECURITY_NO_STORE_PATHS", default_no_store_paths)
+                .split(',')
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Tambahkan header ke response tanpa menimpa header yang sudah ada (termasuk Content-Type)
+    fn apply(&self, path: &str, headers: &mut HeaderMap) {
+        // File PDF/gambar tanpa CSP & X-Frame-Options supaya viewer browser dan preview iframe tetap jalan
+        let binary = is_binary_content(headers);
+
+        for (name, value) in &self.headers {
+            if binary && name == header::X_FRAME_OPTIONS {
+                continue;
+            }
+            headers.entry(name).or_insert_with(|| value.clone());
+        }
+
+        if let Some(hsts) = &self.hsts {
+            headers.entry(header::STRICT_TRANSPORT_SECURITY).or_insert_with(|| hsts.clone());
+        }
+
+        if let Some(csp) = self.csp.as_ref().filter(|_| !binary) {
+            headers.entry(header::CONTENT_SECURITY_POLICY).or_insert_with(|| csp.clone());
+        }
+
+        if self.no_store_paths.iter().any(|prefix| path.starts_with(prefix.as_str())) {
+            headers.entry(header::CACHE_CONTROL)
+                .or_insert_with(|| HeaderValue::from_static("no-store, no-cache, must-revalidate, private"));
+        }
+    }
+}
+
+/// Security headers middleware
+pub async fn security_headers_middleware(
+    State(config): State<Arc<SecurityHeaders>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let path = req.uri().path().to_string();
+    let mut response = next.run(req).await;
+
+    if config.enabled {
+        config.apply(&path, response.headers_mut());
+    }
+
+    response
+}
+
+fn parse_value(name: &HeaderName, value: &str) -> Option<HeaderValue> {
+    if value.is_empty() {
+        return None;
+    }
+
+    HeaderValue::from_str(value)
+        .map_err(|_| tracing::warn!("Nilai header {} tidak valid, dilewati", name))
+        .ok()
+}
+
+fn is_binary_content(headers: &HeaderMap) -> bool {
+    headers.get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/pdf") || ct.starts_with("image/") || ct.starts_with("application/octet-stream"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_STORE_PATHS: &str = "/api/auth,/api/orders";
+
+    #[test]
+    fn test_defaults_use_service_no_store_paths() {
+        let config = SecurityHeaders::from_lookup(NO_STORE_PATHS, |_| None);
+        assert!(config.hsts.is_none());
+
+        for path in ["/api/auth/login", "/api/orders/1"] {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            config.apply(path, &mut headers);
+            assert!(headers[header::CACHE_CONTROL].to_str().unwrap().contains("no-store"), "{}", path);
+            assert_eq!(headers[header::X_FRAME_OPTIONS], "DENY");
+            assert!(headers.contains_key(header::CONTENT_SECURITY_POLICY));
+        }
+
+        let mut catalog = HeaderMap::new();
+        config.apply("/api/books", &mut catalog);
+        assert!(catalog.get(header::CACHE_CONTROL).is_none());
+        assert_eq!(catalog[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+
+        // SECURITY_NO_STORE_PATHS mengganti default service
+        let custom = SecurityHeaders::from_lookup(NO_STORE_PATHS, |key| {
+            (key == "SECURITY_NO_STORE_PATHS").then(|| "/api/books".to_string())
+        });
+        let mut headers = HeaderMap::new();
+        custom.apply("/api/orders/1", &mut headers);
+        assert!(headers.get(header::CACHE_CONTROL).is_none());
+    }
+
+    #[test]
+    fn test_binary_and_handler_headers_kept() {
+        let config = SecurityHeaders::from_lookup(NO_STORE_PATHS, |_| None);
+
+        // PDF/gambar tanpa CSP & X-Frame-Options, Cache-Control dari handler tidak ditimpa
+        let mut pdf = HeaderMap::new();
+        pdf.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/pdf"));
+        pdf.insert(header::CACHE_CONTROL, HeaderValue::from_static("private, max-age=300"));
+        config.apply("/api/orders/1/invoice", &mut pdf);
+        assert_eq!(pdf[header::CONTENT_TYPE], "application/pdf");
+        assert_eq!(pdf[header::CACHE_CONTROL], "private, max-age=300");
+        assert_eq!(pdf[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert!(pdf.get(header::X_FRAME_OPTIONS).is_none());
+        assert!(pdf.get(header::CONTENT_SECURITY_POLICY).is_none());
+
+        let mut framed = HeaderMap::new();
+        framed.insert(header::X_FRAME_OPTIONS, HeaderValue::from_static("SAMEORIGIN"));
+        config.apply("/api/books", &mut framed);
+        assert_eq!(framed[header::X_FRAME_OPTIONS], "SAMEORIGIN");
+    }
+
+    #[test]
+    fn test_hsts_and_csp_overrides() {
+        // Production mengaktifkan HSTS secara default
+        let production = SecurityHeaders::from_lookup(NO_STORE_PATHS, |key| {
+            (key == "ENVIRONMENT").then(|| "production".to_string())
+        });
+        let mut headers = HeaderMap::new();
+        production.apply("/api/auth/login", &mut headers);
+        assert_eq!(headers[header::STRICT_TRANSPORT_SECURITY], "max-age=31536000; includeSubDomains");
+
+        // CSP kosong berarti tidak dikirim, HSTS dengan preload
+        let overridden = SecurityHeaders::from_lookup(NO_STORE_PATHS, |key| match key {
+            "SECURITY_CSP" => Some(String::new()),
+            "HSTS_ENABLED" | "HSTS_PRELOAD" => Some("true".to_string()),
+            "HSTS_MAX_AGE" => Some("60".to_string()),
+            _ => None,
+        });
+        let mut headers = HeaderMap::new();
+        overridden.apply("/api/books", &mut headers);
+        assert!(headers.get(header::CONTENT_SECURITY_POLICY).is_none());
+        assert_eq!(headers[header::STRICT_TRANSPORT_SECURITY], "max-age=60; includeSubDomains; preload");
+    }
+}
@@ -0,0 +1,85 @@
+// /pdf-bookstore/crates/service-common/src/session_cookie.rs
+
+use axum::http::{header, HeaderMap, Method};
+use std::env;
+
+pub const ACCESS_COOKIE: &str = "access_token";
+pub const REFRESH_COOKIE: &str = "refresh_token";
+pub const CSRF_COOKIE: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+pub const AUTH_MODE_HEADER: &str = "x-auth-mode";
+
+/// Mode sesi cookie opt-in lewat AUTH_COOKIE_ENABLED (default false), dibaca sama oleh gateway dan auth-service
+pub fn cookie_sessions_enabled() -> bool {
+    cookie_sessions_enabled_from(|key| env::var(key).ok())
+}
+
+fn cookie_sessions_enabled_from(lookup: impl Fn(&str) -> Option<String>) -> bool {
+    lookup("AUTH_COOKIE_ENABLED")
+        .map(|v| v == "true" || v == "1")
+        .unwrap_or(false)
+}
+
+/// Ambil nilai cookie dari header Cookie
+pub fn read_cookie(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers.get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Method yang mengubah state wajib membawa CSRF token di mode cookie
+pub fn requires_csrf(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Double-submit check: header X-CSRF-Token harus sama dengan cookie csrf_token (constant-time)
+pub fn csrf_valid(headers: &HeaderMap) -> bool {
+    let Some(cookie) = read_cookie(headers, CSRF_COOKIE) else {
+        return false;
+    };
+    let Some(header) = headers.get(CSRF_HEADER).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+
+    cookie.len() == header.len()
+        && cookie.bytes().zip(header.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_cookie_flag_is_opt_in() {
+        assert!(!cookie_sessions_enabled_from(|_| None));
+        assert!(cookie_sessions_enabled_from(|_| Some("true".to_string())));
+        assert!(cookie_sessions_enabled_from(|_| Some("1".to_string())));
+        assert!(!cookie_sessions_enabled_from(|_| Some("false".to_string())));
+        assert!(!cookie_sessions_enabled_from(|_| Some("off".to_string())));
+    }
+
+    #[test]
+    fn test_read_cookie_and_csrf_double_submit() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, HeaderValue::from_static("theme=dark; access_token=abc; csrf_token=tok; empty="));
+        assert_eq!(read_cookie(&headers, ACCESS_COOKIE).as_deref(), Some("abc"));
+        assert_eq!(read_cookie(&headers, "empty"), None);
+        assert!(!csrf_valid(&headers));
+
+        headers.insert(CSRF_HEADER, HeaderValue::from_static("tok"));
+        assert!(csrf_valid(&headers));
+        headers.insert(CSRF_HEADER, HeaderValue::from_static("toK"));
+        assert!(!csrf_valid(&headers));
+        headers.insert(CSRF_HEADER, HeaderValue::from_static("tok2"));
+        assert!(!csrf_valid(&headers));
+
+        assert!(requires_csrf(&Method::POST));
+        assert!(!requires_csrf(&Method::GET));
+    }
+}
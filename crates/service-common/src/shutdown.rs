@@ -0,0 +1,142 @@
+// /pdf-bookstore/crates/service-common/src/shutdown.rs
+
+use std::{env, future::Future, time::Duration};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+
+/// Koordinasi shutdown untuk background job
+/// Job cek `token` di antara iterasi, main menunggu semua job selesai sebelum exit
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    pub token: CancellationToken,
+    tasks: TaskTracker,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawn background task yang ikut ditunggu saat shutdown
+    /// Return false (task tidak dijalankan) jika shutdown sudah dimulai
+    pub fn spawn<F>(&self, task: F) -> bool
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        // Daftar ke tracker dulu baru cek token: drain selalu cancel sebelum menunggu tracker,
+        // jadi task yang lolos cek ini pasti ikut ditunggu
+        let tracked = self.tasks.track_future(task);
+        if self.token.is_cancelled() {
+            return false;
+        }
+        tokio::spawn(tracked);
+        true
+    }
+
+    /// Jalankan satu iterasi job terjadwal, skip jika shutdown sudah dimulai
+    pub async fn run_job<F>(&self, job: F)
+    where
+        F: Future<Output = ()>,
+    {
+        let tracked = self.tasks.track_future(job);
+        if self.token.is_cancelled() {
+            return;
+        }
+        tracked.await;
+    }
+
+    /// Tunggu SIGINT/SIGTERM lalu cancel token (dipakai axum graceful shutdown)
+    pub async fn wait_for_signal(self) {
+        let ctrl_c = async {
+            tokio::signal::ctrl_c().await.ok();
+        };
+
+        #[cfg(unix)]
+        let terminate = async {
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(mut signal) => { signal.recv().await; }
+                Err(_) => std::future::pending::<()>().await,
+            }
+        };
+
+        #[cfg(not(unix))]
+        let terminate = std::future::pending::<()>();
+
+        tokio::select! {
+            _ = ctrl_c => {},
+            _ = terminate => {},
+            _ = self.token.cancelled() => {},
+        }
+
+        tracing::info!("🛑 Shutdown signal diterima, menghentikan background jobs");
+        self.token.cancel();
+    }
+
+    /// Cancel semua job lalu tunggu selesai maksimal SHUTDOWN_TIMEOUT_SECONDS
+    pub async fn drain(&self) {
+        self.token.cancel();
+        self.tasks.close();
+
+        let timeout = shutdown_timeout();
+        match tokio::time::timeout(timeout, self.tasks.wait()).await {
+            Ok(_) => tracing::info!("✅ Semua background job selesai"),
+            Err(_) => tracing::warn!(
+                "⚠️ {} background job belum selesai setelah {}s, force exit",
+                self.tasks.len(),
+                timeout.as_secs()
+            ),
+        }
+    }
+}
+
+/// Batas waktu menunggu background job saat shutdown (default 30 detik)
+fn shutdown_timeout() -> Duration {
+    Duration::from_secs(
+        env::var("SHUTDOWN_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+
+    #[tokio::test]
+    async fn test_drain_waits_for_running_job() {
+        let shutdown = Shutdown::new();
+        let finished = Arc::new(AtomicBool::new(false));
+
+        let job_shutdown = shutdown.clone();
+        let job_finished = finished.clone();
+        shutdown.spawn(async move {
+            job_shutdown.run_job(async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                job_finished.store(true, Ordering::SeqCst);
+            }).await;
+        });
+
+        tokio::task::yield_now().await;
+        shutdown.drain().await;
+
+        assert!(finished.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_run_job_and_spawn_skipped_after_cancel() {
+        let shutdown = Shutdown::new();
+        shutdown.drain().await;
+
+        let ran = Arc::new(AtomicBool::new(false));
+        shutdown.run_job(async { ran.store(true, Ordering::SeqCst) }).await;
+
+        let spawned = ran.clone();
+        let started = shutdown.spawn(async move { spawned.store(true, Ordering::SeqCst) });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert!(!started);
+        assert!(!ran.load(Ordering::SeqCst));
+        assert!(shutdown.tasks.is_empty());
+    }
+}
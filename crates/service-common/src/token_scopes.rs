@@ -0,0 +1,98 @@
+// /pdf-bookstore/crates/service-common/src/token_scopes.rs
+
+use axum::http::{HeaderMap, Method};
+
+/// Header scope personal access token dari api-gateway ke service downstream (comma-separated)
+pub const SCOPES_HEADER: &str = "X-Token-Scopes";
+
+/// Scope dari header gateway, None = bukan personal access token
+pub fn scopes_from_headers(headers: &HeaderMap) -> Option<Vec<String>> {
+    headers.get(SCOPES_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .map(|raw| raw.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect())
+}
+
+/// Scope dari respons /api/auth/verify (gateway, atau service yang dipanggil langsung tanpa gateway)
+pub fn scopes_from_verify(verify_data: &serde_json::Value) -> Option<Vec<String>> {
+    verify_data["scopes"].as_array().map(|list| {
+        list.iter().filter_map(|s| s.as_str().map(str::to_string)).collect()
+    })
+}
+
+/// Cek scope personal access token, aturan yang sama dipakai gateway, book-service dan payment-service
+/// Route /api/auth/* dicek sendiri oleh auth-service, path lain yang tidak dikenal ditolak
+pub fn scope_allows(scopes: &[String], method: &Method, path: &str) -> bool {
+    let has = |scope: &str| scopes.iter().any(|s| s == scope);
+    let read_only = matches!(*method, Method::GET | Method::HEAD);
+
+    if path.starts_with("/api/auth") {
+        return true;
+    }
+
+    if path.contains("/admin/") || path.starts_with("/api/upload") {
+        return has("admin:access");
+    }
+
+    if path.starts_with("/api/books") || path.starts_with("/api/categories") || path.starts_with("/storage") {
+        // Tidak ada scope books:write: aksi tulis user (review, progress) butuh profile:write
+        return has("books:read") && (read_only || has("profile:write"));
+    }
+
+    if path.starts_with("/api/orders") || path.starts_with("/api/payments") || path.starts_with("/api/payment-methods") {
+        return has("orders:write") || (read_only && has("orders:read"));
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scopes(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_scoped_pat_refused_outside_scopes() {
+        let books_only = scopes(&["books:read"]);
+        assert!(scope_allows(&books_only, &Method::GET, "/api/books/3f1c"));
+        assert!(!scope_allows(&books_only, &Method::POST, "/api/books/3f1c/reviews"));
+        assert!(!scope_allows(&books_only, &Method::GET, "/api/orders"));
+        assert!(!scope_allows(&books_only, &Method::GET, "/api/books/admin/stats"));
+
+        let orders_read = scopes(&["orders:read"]);
+        assert!(scope_allows(&orders_read, &Method::GET, "/api/orders/42"));
+        assert!(!scope_allows(&orders_read, &Method::POST, "/api/orders"));
+        assert!(scope_allows(&scopes(&["orders:write"]), &Method::POST, "/api/orders"));
+
+        // Role admin tanpa admin:access tetap ditolak di route admin
+        assert!(!scope_allows(&scopes(&["profile:read", "books:read"]), &Method::GET, "/api/payments/admin/summary"));
+        assert!(scope_allows(&scopes(&["admin:access"]), &Method::GET, "/api/payments/admin/summary"));
+        assert!(!scope_allows(&scopes(&["admin:access"]), &Method::GET, "/unknown"));
+    }
+
+    #[test]
+    fn test_scopes_from_gateway_header_and_verify_response() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(scopes_from_headers(&headers), None);
+        headers.insert(SCOPES_HEADER, "books:read, orders:read".parse().unwrap());
+        let list = scopes_from_headers(&headers).unwrap();
+        assert_eq!(list, scopes(&["books:read", "orders:read"]));
+
+        assert!(scope_allows(&list, &Method::GET, "/api/books/my-library"));
+        assert!(scope_allows(&list, &Method::GET, "/storage/books/a.pdf"));
+        assert!(!scope_allows(&list, &Method::PUT, "/api/books/3f1c/progress"));
+        assert!(!scope_allows(&list, &Method::GET, "/api/admin/books/stats"));
+        assert!(!scope_allows(&list, &Method::POST, "/api/upload/cover"));
+
+        let admin = scopes(&["admin:access"]);
+        assert!(scope_allows(&admin, &Method::GET, "/api/admin/books/stats"));
+        assert!(scope_allows(&admin, &Method::POST, "/api/upload/cover"));
+        assert!(!scope_allows(&admin, &Method::GET, "/api/books/my-library"));
+
+        let verify = serde_json::json!({ "user": { "role": "customer" }, "scopes": ["books:read"] });
+        assert_eq!(scopes_from_verify(&verify), Some(scopes(&["books:read"])));
+        assert_eq!(scopes_from_verify(&serde_json::json!({ "user": {} })), None);
+    }
+}
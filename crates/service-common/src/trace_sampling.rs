@@ -0,0 +1,148 @@
+// /pdf-bookstore/crates/service-common/src/trace_sampling.rs
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    env,
+    sync::{atomic::{AtomicU64, Ordering}, Arc},
+    time::Instant,
+};
+use tracing::Instrument;
+
+/// Sampling request tracing: hanya sebagian request sukses yang di-trace penuh (span + log),
+/// request error (5xx, dan 4xx jika TRACE_CLIENT_ERRORS aktif) selalu di-log
+pub struct TraceSampling {
+    rate: f64,
+    client_errors: bool,
+    counter: AtomicU64,
+}
+
+impl TraceSampling {
+    /// TRACE_SAMPLE_RATE (0.0-1.0, default 1.0 = semua request), TRACE_CLIENT_ERRORS (default true)
+    pub fn from_env() -> Self {
+        Self::from_lookup(|key| env::var(key).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let rate = lookup("TRACE_SAMPLE_RATE")
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|r| r.is_finite())
+            .unwrap_or(1.0);
+
+        let client_errors = lookup("TRACE_CLIENT_ERRORS")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(true);
+
+        Self::new(rate, client_errors)
+    }
+
+    fn new(rate: f64, client_errors: bool) -> Self {
+        Self {
+            rate: rate.clamp(0.0, 1.0),
+            client_errors,
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    /// Sampling deterministik: request ke-n diambil jika floor((n+1)*rate) > floor(n*rate),
+    /// jadi rate 0.1 = tepat 1 dari tiap 10 request tanpa butuh RNG
+    fn sample(&self) -> bool {
+        if self.rate >= 1.0 {
+            return true;
+        }
+        if self.rate <= 0.0 {
+            return false;
+        }
+
+        let n = self.counter.fetch_add(1, Ordering::Relaxed) as f64;
+        ((n + 1.0) * self.rate).floor() > (n * self.rate).floor()
+    }
+}
+
+/// Middleware pengganti TraceLayer dengan sampling
+pub async fn trace_sampling_middleware(
+    State(sampling): State<Arc<TraceSampling>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let sampled = sampling.sample();
+    let started = Instant::now();
+
+    let span = if sampled {
+        tracing::info_span!("request", %method, %path)
+    } else {
+        tracing::Span::none()
+    };
+
+    let response = next.run(req).instrument(span.clone()).await;
+    let status = response.status();
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let _entered = span.enter();
+    if status.is_server_error() {
+        tracing::error!(%method, %path, status = status.as_u16(), latency_ms, "request failed");
+    } else if status.is_client_error() && sampling.client_errors {
+        tracing::warn!(%method, %path, status = status.as_u16(), latency_ms, "request rejected");
+    } else if sampled {
+        tracing::debug!(%method, %path, status = status.as_u16(), latency_ms, "request completed");
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_rate(raw: &str) -> TraceSampling {
+        TraceSampling::from_lookup(|key| (key == "TRACE_SAMPLE_RATE").then(|| raw.to_string()))
+    }
+
+    #[test]
+    fn test_sampling_rate_is_exact_and_clamped() {
+        // Default: semua request di-trace, 4xx ikut di-log
+        let default = TraceSampling::from_lookup(|_| None);
+        assert!((0..10).all(|_| default.sample()));
+        assert!(default.client_errors);
+
+        // Rate pecahan diambil tepat tanpa RNG
+        let quarter = with_rate("0.25");
+        assert_eq!((0..1000).filter(|_| quarter.sample()).count(), 250);
+        let tenth = with_rate("0.1");
+        assert_eq!((0..1000).filter(|_| tenth.sample()).count(), 100);
+
+        // Rate 0 / negatif mematikan trace sukses, rate > 1 di-clamp
+        assert!(!(0..10).any(|_| with_rate("0").sample()));
+        let negative = with_rate("-0.5");
+        assert!(!(0..10).any(|_| negative.sample()));
+        assert_eq!(with_rate("5").rate, 1.0);
+    }
+
+    #[test]
+    fn test_invalid_rate_keeps_full_tracing() {
+        // Nilai tidak valid tidak boleh diam-diam mematikan trace
+        for raw in ["abc", "NaN", "inf", ""] {
+            assert_eq!(with_rate(raw).rate, 1.0, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn test_client_error_logging_toggle() {
+        let quiet = TraceSampling::from_lookup(|key| match key {
+            "TRACE_SAMPLE_RATE" => Some("0.1".to_string()),
+            "TRACE_CLIENT_ERRORS" => Some("false".to_string()),
+            _ => None,
+        });
+        assert!(!quiet.client_errors);
+        assert_eq!((0..100).filter(|_| quiet.sample()).count(), 10);
+
+        let enabled = TraceSampling::from_lookup(|key| (key == "TRACE_CLIENT_ERRORS").then(|| "1".to_string()));
+        assert!(enabled.client_errors);
+        assert_eq!(enabled.rate, 1.0);
+    }
+}
@@ -0,0 +1,130 @@
+// /pdf-bookstore/crates/service-common/src/user_directory.rs
+
+use std::{collections::HashMap, env, time::Duration};
+use uuid::Uuid;
+
+/// Batas user per request ke auth-service (sama dengan MAX_BATCH_USERS di auth-service)
+const BATCH_SIZE: usize = 100;
+
+/// Profil publik user dari auth-service
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserProfile {
+    pub email: String,
+    pub full_name: String,
+}
+
+/// Lookup profil user ke auth-service (POST /api/internal/users/batch) untuk enrichment list
+/// (order di payment-service, review di book-service): satu request per 100 user,
+/// bukan satu request per item atau JOIN ke tabel users milik auth-service
+pub struct UserDirectory {
+    auth_service_url: String,
+    service_key: String,
+    timeout: Duration,
+    client: reqwest::Client,
+}
+
+impl UserDirectory {
+    /// AUTH_SERVICE_URL (default http://localhost:3001), INTERNAL_SERVICE_KEY,
+    /// USER_LOOKUP_TIMEOUT_MS (default 2000)
+    pub fn from_env(client: reqwest::Client) -> Self {
+        Self::from_lookup(client, |key| env::var(key).ok())
+    }
+
+    fn from_lookup(client: reqwest::Client, lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let timeout_ms = lookup("USER_LOOKUP_TIMEOUT_MS")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(2000);
+
+        Self {
+            auth_service_url: lookup("AUTH_SERVICE_URL")
+                .unwrap_or_else(|| "http://localhost:3001".to_string()),
+            service_key: lookup("INTERNAL_SERVICE_KEY")
+                .unwrap_or_else(|| "internal-service-key-secret".to_string()),
+            timeout: Duration::from_millis(timeout_ms),
+            client,
+        }
+    }
+
+    /// Profil user yang ditemukan; auth-service down / error = map kosong (list tetap tampil tanpa nama)
+    pub async fn lookup(&self, user_ids: &[Uuid]) -> HashMap<Uuid, UserProfile> {
+        let mut ids = user_ids.to_vec();
+        ids.sort();
+        ids.dedup();
+
+        let mut profiles = HashMap::new();
+        for chunk in ids.chunks(BATCH_SIZE) {
+            let response = self.client
+                .post(format!("{}/api/internal/users/batch", self.auth_service_url))
+                .header("X-Service-Key", &self.service_key)
+                .json(&serde_json::json!({ "user_ids": chunk }))
+                .timeout(self.timeout)
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status());
+
+            match response {
+                Ok(resp) => match resp.json::<serde_json::Value>().await {
+                    Ok(body) => profiles.extend(parse_batch_users(&body)),
+                    Err(e) => tracing::warn!("Response batch user auth-service tidak valid: {}", e),
+                },
+                Err(e) => tracing::warn!("Gagal lookup {} user ke auth-service: {}", chunk.len(), e),
+            }
+        }
+
+        profiles
+    }
+}
+
+/// Parse `{ "users": [{ "id", "email", "full_name" }] }` dari auth-service
+fn parse_batch_users(body: &serde_json::Value) -> HashMap<Uuid, UserProfile> {
+    body["users"].as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|user| {
+            let id = Uuid::parse_str(user["id"].as_str()?).ok()?;
+            Some((id, UserProfile {
+                email: user["email"].as_str()?.to_string(),
+                full_name: user["full_name"].as_str()?.to_string(),
+            }))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_and_timeout_override() {
+        let default = UserDirectory::from_lookup(reqwest::Client::new(), |_| None);
+        assert_eq!(default.auth_service_url, "http://localhost:3001");
+        assert_eq!(default.timeout, Duration::from_millis(2000));
+
+        let custom = UserDirectory::from_lookup(reqwest::Client::new(), |key| match key {
+            "AUTH_SERVICE_URL" => Some("http://auth-service:3001".to_string()),
+            "USER_LOOKUP_TIMEOUT_MS" => Some("500".to_string()),
+            _ => None,
+        });
+        assert_eq!(custom.auth_service_url, "http://auth-service:3001");
+        assert_eq!(custom.timeout, Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_parse_batch_users_skips_malformed_entries() {
+        let id = Uuid::new_v4();
+        let body = serde_json::json!({
+            "success": true,
+            "users": [
+                { "id": id, "email": "a@example.com", "full_name": "Ani", "role": "customer" },
+                { "id": "bukan-uuid", "email": "b@example.com", "full_name": "Budi" },
+                { "id": Uuid::new_v4(), "full_name": "Tanpa Email" },
+            ],
+            "not_found": [Uuid::new_v4()],
+        });
+
+        let profiles = parse_batch_users(&body);
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[&id], UserProfile { email: "a@example.com".to_string(), full_name: "Ani".to_string() });
+        assert!(parse_batch_users(&serde_json::json!({ "success": false })).is_empty());
+    }
+}
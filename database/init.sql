@@ -33,6 +33,28 @@
 \i /docker-entrypoint-initdb.d/migrations/013_create_security_events.sql
 \i /docker-entrypoint-initdb.d/migrations/014_complete_missing_tables.sql
 \i /docker-entrypoint-initdb.d/migrations/015_add_login_otp.sql
+\i /docker-entrypoint-initdb.d/migrations/016_create_reviews_table.sql
+\i /docker-entrypoint-initdb.d/migrations/017_create_personal_access_tokens.sql
+\i /docker-entrypoint-initdb.d/migrations/018_create_email_send_log.sql
+\i /docker-entrypoint-initdb.d/migrations/019_ensure_unique_book_reviews.sql
+\i /docker-entrypoint-initdb.d/migrations/020_create_book_views.sql
+\i /docker-entrypoint-initdb.d/migrations/021_add_order_refunded_amount.sql
+\i /docker-entrypoint-initdb.d/migrations/022_create_reading_progress.sql
+\i /docker-entrypoint-initdb.d/migrations/023_add_audit_actor.sql
+\i /docker-entrypoint-initdb.d/migrations/024_create_notification_preferences.sql
+\i /docker-entrypoint-initdb.d/migrations/025_add_featured_books.sql
+\i /docker-entrypoint-initdb.d/migrations/026_add_order_payment_reference.sql
+\i /docker-entrypoint-initdb.d/migrations/027_create_password_history.sql
+\i /docker-entrypoint-initdb.d/migrations/028_add_users_normalized_email_unique.sql
+\i /docker-entrypoint-initdb.d/migrations/029_create_user_totp_secrets.sql
+\i /docker-entrypoint-initdb.d/migrations/030_add_login_otp_resend_tracking.sql
+\i /docker-entrypoint-initdb.d/migrations/031_create_trusted_devices.sql
+\i /docker-entrypoint-initdb.d/migrations/032_add_refresh_token_family.sql
+\i /docker-entrypoint-initdb.d/migrations/033_add_users_failed_login_reset_at.sql
+\i /docker-entrypoint-initdb.d/migrations/034_add_security_events_read_at.sql
+\i /docker-entrypoint-initdb.d/migrations/035_add_orders_access_revoked_at.sql
+\i /docker-entrypoint-initdb.d/migrations/036_add_book_views_window.sql
+\i /docker-entrypoint-initdb.d/migrations/037_add_login_otp_attempts.sql
 
 
 
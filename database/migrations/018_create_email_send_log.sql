@@ -0,0 +1,13 @@
+-- /pdf-bookstore/database/migrations/018_create_email_send_log.sql
+
+-- Log pengiriman email sensitif (reset password, OTP) untuk rate limit per email
+CREATE TABLE IF NOT EXISTS email_send_log (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    email VARCHAR(255) NOT NULL,
+    purpose VARCHAR(50) NOT NULL,
+    suppressed BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_send_log_lookup
+    ON email_send_log(email, purpose, created_at DESC);
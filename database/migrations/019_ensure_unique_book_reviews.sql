@@ -0,0 +1,21 @@
+-- /pdf-bookstore/database/migrations/019_ensure_unique_book_reviews.sql
+
+-- Merge review duplikat (simpan yang paling baru) lalu pastikan unique constraint ada,
+-- dibutuhkan oleh upsert ON CONFLICT (book_id, user_id) di book-service
+DELETE FROM book_reviews r
+USING book_reviews newer
+WHERE r.book_id = newer.book_id
+  AND r.user_id = newer.user_id
+  AND (r.updated_at, r.id) < (newer.updated_at, newer.id);
+
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_constraint
+        WHERE conname = 'unique_user_book_review'
+          AND conrelid = 'book_reviews'::regclass
+    ) THEN
+        ALTER TABLE book_reviews
+            ADD CONSTRAINT unique_user_book_review UNIQUE (book_id, user_id);
+    END IF;
+END $$;
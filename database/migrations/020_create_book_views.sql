@@ -0,0 +1,14 @@
+-- /pdf-bookstore/database/migrations/020_create_book_views.sql
+
+-- Tracking view detail/preview buku (termasuk guest) untuk analytics konversi
+CREATE TABLE IF NOT EXISTS book_views (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    book_id UUID NOT NULL REFERENCES books(id) ON DELETE CASCADE,
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    viewer_hash VARCHAR(64) NOT NULL,
+    source VARCHAR(20) NOT NULL CHECK (source IN ('detail', 'preview')),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_book_views_book_created ON book_views(book_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_book_views_dedup ON book_views(book_id, viewer_hash, source, created_at DESC);
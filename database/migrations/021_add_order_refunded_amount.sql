@@ -0,0 +1,19 @@
+-- /pdf-bookstore/database/migrations/021_add_order_refunded_amount.sql
+
+-- Akumulasi jumlah yang sudah di-refund per order (mendukung partial refund berulang)
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS refunded_amount DECIMAL(12,2) NOT NULL DEFAULT 0;
+
+-- Backfill order yang sudah full refund sebelum kolom ini ada
+UPDATE orders o
+SET refunded_amount = LEAST(o.amount, COALESCE((SELECT SUM(r.amount) FROM refunds r WHERE r.order_id = o.id), o.amount))
+WHERE o.status = 'refunded' AND o.refunded_amount = 0;
+
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_constraint WHERE conname = 'orders_refunded_amount_check'
+    ) THEN
+        ALTER TABLE orders ADD CONSTRAINT orders_refunded_amount_check
+            CHECK (refunded_amount >= 0 AND refunded_amount <= amount);
+    END IF;
+END $$;
@@ -0,0 +1,13 @@
+-- /pdf-bookstore/database/migrations/022_create_reading_progress.sql
+
+-- Posisi baca terakhir per user per buku (sync "continue reading" antar device)
+CREATE TABLE IF NOT EXISTS reading_progress (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    book_id UUID NOT NULL REFERENCES books(id) ON DELETE CASCADE,
+    current_page INTEGER NOT NULL CHECK (current_page >= 1),
+    position VARCHAR(500),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, book_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_reading_progress_user_updated ON reading_progress(user_id, updated_at DESC);
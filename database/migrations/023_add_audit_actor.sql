@@ -0,0 +1,6 @@
+-- /pdf-bookstore/database/migrations/023_add_audit_actor.sql
+
+-- Admin yang melakukan aksi (terpisah dari user_id yang menyimpan subjek aksi, misal pembeli)
+ALTER TABLE audit_logs ADD COLUMN IF NOT EXISTS actor_user_id UUID REFERENCES users(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_audit_logs_actor_user_id ON audit_logs(actor_user_id) WHERE actor_user_id IS NOT NULL;
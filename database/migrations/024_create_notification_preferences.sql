@@ -0,0 +1,11 @@
+-- /pdf-bookstore/database/migrations/024_create_notification_preferences.sql
+
+-- Preferensi email per user, user tanpa row memakai default (security & receipt aktif, marketing nonaktif)
+-- Email wajib (OTP login, verifikasi, reset password) tidak terpengaruh preferensi ini
+CREATE TABLE IF NOT EXISTS notification_preferences (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    security_alerts BOOLEAN NOT NULL DEFAULT true,
+    order_receipts BOOLEAN NOT NULL DEFAULT true,
+    marketing BOOLEAN NOT NULL DEFAULT false,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
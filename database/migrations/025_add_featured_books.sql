@@ -0,0 +1,8 @@
+-- /pdf-bookstore/database/migrations/025_add_featured_books.sql
+
+-- Kurasi buku unggulan untuk homepage, urutan manual lewat featured_order (NULL = di akhir)
+ALTER TABLE books ADD COLUMN IF NOT EXISTS is_featured BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE books ADD COLUMN IF NOT EXISTS featured_order INTEGER;
+ALTER TABLE books ADD COLUMN IF NOT EXISTS featured_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_books_featured ON books(featured_order, featured_at) WHERE is_featured = true;
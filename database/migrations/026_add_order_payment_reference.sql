@@ -0,0 +1,5 @@
+-- /pdf-bookstore/database/migrations/026_add_order_payment_reference.sql
+
+-- order_id Midtrans yang aktif saat link pembayaran dibuat ulang (NULL = order_number)
+-- Midtrans menolak order_id yang sudah dipakai, jadi link baru memakai order_number + suffix
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS payment_reference VARCHAR(64);
@@ -0,0 +1,12 @@
+-- /pdf-bookstore/database/migrations/027_create_password_history.sql
+
+-- Hash password lama (format argon2 + pepper yang sama dengan users.password_hash)
+-- Dipakai untuk menolak reuse N password terakhir, row di luar N di-prune saat ganti password
+CREATE TABLE IF NOT EXISTS password_history (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    password_hash VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_password_history_user_created ON password_history(user_id, created_at DESC);
@@ -0,0 +1,15 @@
+-- /pdf-bookstore/database/migrations/028_add_users_normalized_email_unique.sql
+
+-- Registrasi mengecek email dengan LOWER(TRIM(email)), constraint users_email_key hanya exact match
+-- Unique index ini membuat registrasi bersamaan dengan email yang sama selalu gagal di salah satu insert
+-- Data lama yang sudah duplikat tidak dihapus otomatis, index dilewati dengan warning sampai dibereskan manual
+DO $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM users GROUP BY LOWER(TRIM(email)) HAVING COUNT(*) > 1
+    ) THEN
+        RAISE WARNING 'Email duplikat (case/whitespace) ditemukan, idx_users_email_normalized tidak dibuat';
+    ELSE
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_users_email_normalized ON users (LOWER(TRIM(email)));
+    END IF;
+END $$;
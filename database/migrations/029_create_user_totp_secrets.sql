@@ -0,0 +1,15 @@
+-- /pdf-bookstore/database/migrations/029_create_user_totp_secrets.sql
+
+-- TOTP (RFC 6238) sebagai alternatif OTP email
+-- totp_enabled baru TRUE setelah enrollment dikonfirmasi lewat /api/auth/2fa/totp/verify
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_enabled BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Secret disimpan terenkripsi (TOTP_ENCRYPTION_KEY), last_used_step mencegah replay kode dalam window yang sama
+CREATE TABLE IF NOT EXISTS user_totp_secrets (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    secret_encrypted TEXT NOT NULL,
+    confirmed_at TIMESTAMP WITH TIME ZONE,
+    last_used_step BIGINT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
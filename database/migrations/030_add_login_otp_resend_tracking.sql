@@ -0,0 +1,7 @@
+-- /pdf-bookstore/database/migrations/030_add_login_otp_resend_tracking.sql
+
+-- Tracking kirim ulang OTP login (POST /api/auth/otp/resend): cooldown dari last_sent_at,
+-- resend_count dihitung per window yang dimulai di resend_window_started_at
+ALTER TABLE login_otps ADD COLUMN IF NOT EXISTS last_sent_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW();
+ALTER TABLE login_otps ADD COLUMN IF NOT EXISTS resend_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE login_otps ADD COLUMN IF NOT EXISTS resend_window_started_at TIMESTAMP WITH TIME ZONE;
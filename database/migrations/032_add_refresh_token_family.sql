@@ -0,0 +1,7 @@
+-- /pdf-bookstore/database/migrations/032_add_refresh_token_family.sql
+
+-- Satu family per login, diwariskan ke setiap refresh token hasil rotasi
+-- Refresh token yang sudah di-revoke dipakai ulang = token bocor, seluruh family di-revoke
+ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS token_family UUID NOT NULL DEFAULT gen_random_uuid();
+
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_family ON refresh_tokens(token_family);
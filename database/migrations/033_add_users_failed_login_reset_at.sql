@@ -0,0 +1,5 @@
+-- /pdf-bookstore/database/migrations/033_add_users_failed_login_reset_at.sql
+
+-- Lockout dihitung dari LOGIN_FAILED di security_events, admin unlock menggeser titik awal hitungan
+-- Percobaan gagal sebelum failed_login_reset_at tidak dihitung lagi
+ALTER TABLE users ADD COLUMN IF NOT EXISTS failed_login_reset_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,9 @@
+-- /pdf-bookstore/database/migrations/034_add_security_events_read_at.sql
+
+-- Status baca event di timeline aktivitas user (NULL = belum dibaca)
+ALTER TABLE security_events ADD COLUMN IF NOT EXISTS read_at TIMESTAMP WITH TIME ZONE;
+
+-- Unread count per user
+CREATE INDEX IF NOT EXISTS idx_security_events_user_unread
+    ON security_events (user_id, created_at DESC)
+    WHERE read_at IS NULL;
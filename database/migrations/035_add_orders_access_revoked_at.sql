@@ -0,0 +1,5 @@
+-- /pdf-bookstore/database/migrations/035_add_orders_access_revoked_at.sql
+
+-- Akses buku dicabut admin: order paid tetap tercatat tapi tidak lagi membuktikan kepemilikan
+-- (fallback PurchaseVerifier book-service melewati order ini)
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS access_revoked_at TIMESTAMP WITH TIME ZONE;
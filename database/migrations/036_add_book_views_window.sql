@@ -0,0 +1,34 @@
+-- /pdf-bookstore/database/migrations/036_add_book_views_window.sql
+
+-- Dedup view per window waktu tetap (BOOK_VIEW_DEDUP_MINUTES) lewat unique constraint,
+-- book-service insert dengan ON CONFLICT DO NOTHING sehingga request paralel tidak dobel
+ALTER TABLE book_views ADD COLUMN IF NOT EXISTS view_window TIMESTAMP WITH TIME ZONE;
+
+-- Row lama: window 30 menit (default), duplikat di window yang sama disisakan yang paling awal
+UPDATE book_views
+SET view_window = to_timestamp(floor(extract(epoch FROM created_at) / 1800) * 1800)
+WHERE view_window IS NULL;
+
+DELETE FROM book_views v
+USING book_views earlier
+WHERE v.book_id = earlier.book_id
+  AND v.viewer_hash = earlier.viewer_hash
+  AND v.source = earlier.source
+  AND v.view_window = earlier.view_window
+  AND (v.created_at, v.id) > (earlier.created_at, earlier.id);
+
+ALTER TABLE book_views ALTER COLUMN view_window SET NOT NULL;
+
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_constraint
+        WHERE conname = 'unique_book_view_window'
+          AND conrelid = 'book_views'::regclass
+    ) THEN
+        ALTER TABLE book_views
+            ADD CONSTRAINT unique_book_view_window UNIQUE (book_id, viewer_hash, source, view_window);
+    END IF;
+END $$;
+
+DROP INDEX IF EXISTS idx_book_views_dedup;
@@ -0,0 +1,4 @@
+-- /pdf-bookstore/database/migrations/037_add_login_otp_attempts.sql
+
+-- Jumlah percobaan verify-otp per challenge login, di-reset saat kode baru dibuat (login / resend)
+ALTER TABLE login_otps ADD COLUMN IF NOT EXISTS attempts INTEGER NOT NULL DEFAULT 0;
@@ -151,11 +151,11 @@ class BookstoreApp {
                     this.books = response.data;
                 }
 
-                // Update pagination info dari PaginationMeta
-                if (response.pagination) {
-                    this.currentPage = response.pagination.current_page;
-                    this.totalPages = response.pagination.total_pages;
-                    this.updatePaginationUI(response.pagination);
+                // Update pagination info dari PaginationMeta (envelope `meta`)
+                if (response.meta) {
+                    this.currentPage = response.meta.current_page;
+                    this.totalPages = response.meta.total_pages;
+                    this.updatePaginationUI(response.meta);
                 }
 
                 this.renderBooks();
@@ -399,10 +399,10 @@ class BookstoreApp {
             } catch (error) {
                 console.warn('Could not check purchase status:', error);
                 // Continue with purchase flow if status check fails
-                purchaseStatus = { has_purchased: false };
+                purchaseStatus = { data: { has_purchased: false } };
             }
 
-            if (purchaseStatus && purchaseStatus.has_purchased) {
+            if (purchaseStatus && purchaseStatus.data && purchaseStatus.data.has_purchased) {
                 Utils.showNotification('You already own this book!', 'info');
                 return;
             }
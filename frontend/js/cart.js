@@ -359,7 +359,7 @@ class CartManager {
             const response = await this.api.checkPurchaseStatus(bookId);
 
 
-            return response.success && response.has_purchased;
+            return response.success && Boolean(response.data && response.data.has_purchased);
 
         } catch (error) {
             console.warn('Failed to check book ownership:', error);
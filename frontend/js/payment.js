@@ -563,7 +563,7 @@ class PaymentManager {
                 return {
                     success: true,
                     orders: response.data,
-                    pagination: response.pagination
+                    pagination: response.meta
                 };
             } else {
                 throw new Error(response.message || 'Failed to get payment history');
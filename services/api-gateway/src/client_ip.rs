@@ -0,0 +1,112 @@
+// /pdf-bookstore/services/api-gateway/src/client_ip.rs
+
+use axum::http::{HeaderMap, HeaderValue};
+use service_common::client_ip::ClientIpResolver;
+use std::net::IpAddr;
+
+/// Header IP client yang dipercaya service downstream jika gateway (dan load balancer di depannya) ada di TRUSTED_PROXIES
+const FORWARDED_FOR: &str = "x-forwarded-for";
+const REAL_IP: &str = "x-real-ip";
+
+/// Header identitas yang dipercaya service downstream, hanya boleh diisi gateway setelah token terverifikasi
+const IDENTITY_HEADERS: [&str; 5] = ["x-gateway-request", "x-user-id", "x-user-role", "x-user-email", "x-token-scopes"];
+
+/// Header untuk downstream. Peer termasuk TRUSTED_PROXIES (load balancer / ingress di depan gateway):
+/// rantai X-Forwarded-For dipertahankan dan peer di-append, X-Real-IP diisi IP client hasil resolve.
+/// Peer lain: X-Forwarded-For / X-Real-IP dari client dibuang lalu diganti IP peer socket gateway,
+/// sehingga hop yang ditulis client tidak pernah terbaca sebagai IP client
+pub fn forwarded_headers(headers: &HeaderMap, peer_ip: IpAddr, resolver: &ClientIpResolver) -> HeaderMap {
+    let mut forwarded = headers.clone();
+    forwarded.remove(FORWARDED_FOR);
+    forwarded.remove(REAL_IP);
+
+    let (chain, client_ip) = if resolver.is_trusted_proxy(peer_ip) {
+        let mut hops = headers.get_all(FORWARDED_FOR)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .filter(|hop| !hop.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        hops.push(peer_ip.to_string());
+        (hops.join(", "), resolver.resolve(peer_ip, headers))
+    } else {
+        (peer_ip.to_string(), peer_ip)
+    };
+
+    if let Ok(chain) = HeaderValue::from_str(&chain) {
+        forwarded.insert(FORWARDED_FOR, chain);
+    }
+    forwarded.insert(REAL_IP, HeaderValue::from_str(&client_ip.to_string()).expect("IP address is a valid header value"));
+    forwarded
+}
+
+/// Buang header identitas kiriman client, termasuk di route public yang tidak melewati verifikasi token
+pub fn strip_identity_headers(headers: &mut HeaderMap) {
+    for name in IDENTITY_HEADERS {
+        headers.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_forged_hops_never_forwarded() {
+        let mut headers = HeaderMap::new();
+        headers.append(FORWARDED_FOR, HeaderValue::from_static("1.2.3.4"));
+        headers.append(FORWARDED_FOR, HeaderValue::from_static("5.6.7.8, 10.0.0.9"));
+        headers.insert(REAL_IP, HeaderValue::from_static("9.9.9.9"));
+        headers.insert("x-correlation-id", HeaderValue::from_static("abc"));
+
+        let peer: IpAddr = "203.0.113.9".parse().unwrap();
+        let forwarded = forwarded_headers(&headers, peer, &ClientIpResolver::default());
+
+        // Downstream mengambil hop paling kanan yang bukan proxy: satu-satunya hop adalah peer
+        let hops = forwarded.get_all(FORWARDED_FOR).iter().collect::<Vec<_>>();
+        assert_eq!(hops, vec!["203.0.113.9"]);
+        assert_eq!(forwarded.get_all(REAL_IP).iter().collect::<Vec<_>>(), vec!["203.0.113.9"]);
+        assert_eq!(forwarded.get("x-correlation-id").unwrap(), "abc");
+    }
+
+    #[test]
+    fn test_trusted_load_balancer_chain_kept() {
+        let resolver = ClientIpResolver::new(&["10.0.0.0/8"]);
+        let mut headers = HeaderMap::new();
+        headers.insert(FORWARDED_FOR, HeaderValue::from_static("198.51.100.23"));
+        headers.insert(REAL_IP, HeaderValue::from_static("198.51.100.23"));
+
+        // Load balancer terpercaya: IP client asli tetap sampai ke downstream
+        let lb: IpAddr = "10.0.0.4".parse().unwrap();
+        let forwarded = forwarded_headers(&headers, lb, &resolver);
+        assert_eq!(forwarded.get_all(FORWARDED_FOR).iter().collect::<Vec<_>>(), vec!["198.51.100.23, 10.0.0.4"]);
+        assert_eq!(forwarded[REAL_IP], "198.51.100.23");
+
+        // Downstream dengan TRUSTED_PROXIES yang sama me-resolve client yang sama lewat gateway
+        let gateway: IpAddr = "10.0.0.9".parse().unwrap();
+        assert_eq!(resolver.resolve(gateway, &forwarded), "198.51.100.23".parse::<IpAddr>().unwrap());
+
+        // Client langsung (bukan proxy terpercaya) tetap ditimpa
+        let direct: IpAddr = "203.0.113.9".parse().unwrap();
+        let forwarded = forwarded_headers(&headers, direct, &resolver);
+        assert_eq!(forwarded[FORWARDED_FOR], "203.0.113.9");
+        assert_eq!(forwarded[REAL_IP], "203.0.113.9");
+    }
+
+    #[test]
+    fn test_client_identity_headers_stripped() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Gateway-Request", HeaderValue::from_static("true"));
+        headers.insert("X-User-Id", HeaderValue::from_static("00000000-0000-0000-0000-000000000001"));
+        headers.insert("X-User-Role", HeaderValue::from_static("admin"));
+        headers.insert("X-User-Email", HeaderValue::from_static("admin@example.com"));
+        headers.insert("X-Token-Scopes", HeaderValue::from_static("admin:access"));
+        headers.insert("x-correlation-id", HeaderValue::from_static("abc"));
+
+        strip_identity_headers(&mut headers);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers.get("x-correlation-id").unwrap(), "abc");
+    }
+}
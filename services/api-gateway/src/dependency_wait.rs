@@ -0,0 +1,78 @@
+// /pdf-bookstore/services/api-gateway/src/dependency_wait.rs
+
+use std::{env, time::Duration};
+
+/// Tunggu dependency (auth-service) sehat sebelum gateway menerima traffic (opt-in)
+pub struct DependencyWait {
+    pub enabled: bool,
+    pub attempts: u32,
+    pub delay: Duration,
+}
+
+impl DependencyWait {
+    /// GATEWAY_WAIT_FOR_AUTH (default false), GATEWAY_WAIT_ATTEMPTS (default 30),
+    /// GATEWAY_WAIT_DELAY_MS (default 1000)
+    pub fn from_env() -> Self {
+        let read = |key: &str, default: u64| {
+            env::var(key).ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(default)
+        };
+
+        Self {
+            enabled: env::var("GATEWAY_WAIT_FOR_AUTH")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            attempts: read("GATEWAY_WAIT_ATTEMPTS", 30).max(1) as u32,
+            delay: Duration::from_millis(read("GATEWAY_WAIT_DELAY_MS", 1000)),
+        }
+    }
+
+    /// Poll `{base_url}/health` sampai 2xx, false jika attempt habis (gateway tetap start,
+    /// request ke service yang belum siap ditangani circuit breaker / fallback)
+    pub async fn wait_for(&self, client: &reqwest::Client, name: &str, base_url: &str) -> bool {
+        if !self.enabled {
+            return true;
+        }
+
+        let health_url = format!("{}/health", base_url.trim_end_matches('/'));
+        for attempt in 1..=self.attempts {
+            match client.get(&health_url).timeout(Duration::from_secs(2)).send().await {
+                Ok(resp) if resp.status().is_success() => {
+                    tracing::info!("✓ {} siap (attempt {}/{})", name, attempt, self.attempts);
+                    return true;
+                }
+                Ok(resp) => tracing::warn!("{} belum siap (attempt {}/{}): status {}", name, attempt, self.attempts, resp.status()),
+                Err(e) => tracing::warn!("{} belum siap (attempt {}/{}): {}", name, attempt, self.attempts, e),
+            }
+
+            if attempt < self.attempts {
+                tokio::time::sleep(self.delay).await;
+            }
+        }
+
+        tracing::warn!("⚠️ {} belum sehat setelah {} attempt, gateway tetap dijalankan", name, self.attempts);
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::get, Router};
+
+    #[tokio::test]
+    async fn test_wait_for_healthy_dependency() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, Router::new().route("/health", get(|| async { "ok" }))).await.unwrap();
+        });
+
+        let client = reqwest::Client::new();
+        let wait = DependencyWait { enabled: true, attempts: 2, delay: Duration::from_millis(10) };
+        assert!(wait.wait_for(&client, "auth-service", &format!("http://{}/", addr)).await);
+        assert!(!wait.wait_for(&client, "auth-service", "http://127.0.0.1:1").await);
+
+        let disabled = DependencyWait { enabled: false, ..wait };
+        assert!(disabled.wait_for(&client, "auth-service", "http://127.0.0.1:1").await);
+    }
+}
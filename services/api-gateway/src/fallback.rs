@@ -0,0 +1,181 @@
+// /pdf-bookstore/services/api-gateway/src/fallback.rs
+
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderValue, StatusCode},
+    response::Response,
+};
+use std::{collections::HashMap, env, time::{Duration, Instant}};
+use tokio::sync::RwLock;
+
+/// Maksimal jumlah response yang disimpan sebagai fallback
+const MAX_FALLBACK_ENTRIES: usize = 500;
+
+/// Snapshot response sukses terakhir untuk satu URL
+#[derive(Clone)]
+struct CachedResponse {
+    body: Bytes,
+    content_type: Option<HeaderValue>,
+    stored_at: Instant,
+}
+
+/// Cache last-known-good response untuk GET route yang aman
+/// Opt-in per route lewat GATEWAY_FALLBACK_ROUTES (prefix path, comma-separated)
+pub struct FallbackCache {
+    routes: Vec<String>,
+    ttl: Duration,
+    entries: RwLock<HashMap<String, CachedResponse>>,
+}
+
+impl FallbackCache {
+    pub fn from_env() -> Self {
+        let routes = env::var("GATEWAY_FALLBACK_ROUTES")
+            .unwrap_or_default()
+            .split(',')
+            .map(|r| r.trim().to_string())
+            .filter(|r| !r.is_empty())
+            .collect::<Vec<_>>();
+
+        let ttl = Duration::from_secs(
+            env::var("GATEWAY_FALLBACK_TTL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300)
+        );
+
+        if !routes.is_empty() {
+            tracing::info!("Gateway fallback cache aktif untuk {:?} (ttl {}s)", routes, ttl.as_secs());
+        }
+
+        Self::new(routes, ttl)
+    }
+
+    fn new(routes: Vec<String>, ttl: Duration) -> Self {
+        Self { routes, ttl, entries: RwLock::new(HashMap::new()) }
+    }
+
+    /// Cek apakah route boleh pakai cached fallback
+    pub fn is_enabled_for(&self, path: &str) -> bool {
+        self.routes.iter().any(|route| path.starts_with(route.as_str()))
+    }
+
+    /// Simpan response sukses sebagai last-known-good
+    pub async fn store(&self, key: &str, body: Bytes, content_type: Option<HeaderValue>) {
+        let mut entries = self.entries.write().await;
+
+        if entries.len() >= MAX_FALLBACK_ENTRIES && !entries.contains_key(key) {
+            let ttl = self.ttl;
+            entries.retain(|_, entry| entry.stored_at.elapsed() < ttl);
+            if entries.len() >= MAX_FALLBACK_ENTRIES {
+                return;
+            }
+        }
+
+        entries.insert(key.to_string(), CachedResponse {
+            body,
+            content_type,
+            stored_at: Instant::now(),
+        });
+    }
+
+    /// Ambil cached response yang belum expired
+    pub async fn get(&self, key: &str, correlation_id: &str) -> Option<Response<Body>> {
+        let entries = self.entries.read().await;
+        let entry = entries.get(key).filter(|e| e.stored_at.elapsed() < self.ttl)?.clone();
+        drop(entries);
+
+        let mut builder = Response::builder()
+            .status(StatusCode::OK)
+            .header("X-Gateway-Fallback", "cached")
+            .header(header::AGE, entry.stored_at.elapsed().as_secs())
+            .header("X-Correlation-Id", correlation_id);
+
+        if let Some(content_type) = entry.content_type {
+            builder = builder.header(header::CONTENT_TYPE, content_type);
+        }
+
+        builder.body(Body::from(entry.body)).ok()
+    }
+}
+
+/// Response JSON terstruktur ketika downstream tidak tersedia (tidak ada instance sehat / circuit open)
+pub fn service_unavailable_response(
+    service_name: &str,
+    retry_after_secs: u64,
+    correlation_id: &str,
+) -> Response<Body> {
+    let body = serde_json::json!({
+        "success": false,
+        "message": format!("Service {} sedang tidak tersedia, silakan coba lagi nanti", service_name),
+        "error_code": "SERVICE_UNAVAILABLE",
+        "service": service_name,
+        "retry_after_seconds": retry_after_secs,
+        "correlation_id": correlation_id,
+    });
+
+    Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::RETRY_AFTER, retry_after_secs)
+        .header("X-Correlation-Id", correlation_id)
+        .body(Body::from(body.to_string()))
+        .unwrap_or_else(|_| Response::new(Body::empty()))
+}
+
+/// Response JSON terstruktur ketika request ke downstream gagal dikirim (koneksi putus, timeout)
+/// Tetap 502 seperti sebelum fallback ada: service terdaftar sehat tapi tidak memberi response
+pub fn bad_gateway_response(service_name: &str, correlation_id: &str) -> Response<Body> {
+    let body = serde_json::json!({
+        "success": false,
+        "message": format!("Service {} tidak memberikan response yang valid", service_name),
+        "error_code": "BAD_GATEWAY",
+        "service": service_name,
+        "correlation_id": correlation_id,
+    });
+
+    Response::builder()
+        .status(StatusCode::BAD_GATEWAY)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header("X-Correlation-Id", correlation_id)
+        .body(Body::from(body.to_string()))
+        .unwrap_or_else(|_| Response::new(Body::empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cached_fallback_and_error_responses() {
+        let cache = FallbackCache::new(vec!["/api/books".to_string()], Duration::from_secs(60));
+        assert!(cache.is_enabled_for("/api/books/featured"));
+        assert!(!cache.is_enabled_for("/api/orders"));
+
+        assert!(cache.get("/api/books?page=1", "corr-1").await.is_none());
+        cache.store("/api/books?page=1", Bytes::from_static(b"{\"success\":true}"), Some(HeaderValue::from_static("application/json"))).await;
+
+        let cached = cache.get("/api/books?page=1", "corr-1").await.unwrap();
+        assert_eq!(cached.status(), StatusCode::OK);
+        assert_eq!(cached.headers()["X-Gateway-Fallback"], "cached");
+        assert_eq!(cached.headers()[header::CONTENT_TYPE], "application/json");
+        assert_eq!(cached.headers()["X-Correlation-Id"], "corr-1");
+        let body = axum::body::to_bytes(cached.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"{\"success\":true}");
+
+        let expired = FallbackCache::new(vec!["/api/books".to_string()], Duration::ZERO);
+        expired.store("/api/books", Bytes::from_static(b"[]"), None).await;
+        assert!(expired.get("/api/books", "corr-2").await.is_none());
+
+        let unavailable = service_unavailable_response("book-service", 30, "corr-3");
+        assert_eq!(unavailable.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(unavailable.headers()[header::RETRY_AFTER], "30");
+
+        let bad_gateway = bad_gateway_response("book-service", "corr-4");
+        assert_eq!(bad_gateway.status(), StatusCode::BAD_GATEWAY);
+        assert!(bad_gateway.headers().get(header::RETRY_AFTER).is_none());
+        let body: serde_json::Value = serde_json::from_slice(
+            &axum::body::to_bytes(bad_gateway.into_body(), usize::MAX).await.unwrap()
+        ).unwrap();
+        assert_eq!(body["error_code"], "BAD_GATEWAY");
+    }
+}
@@ -0,0 +1,240 @@
+// /pdf-bookstore/services/api-gateway/src/jwt_verifier.rs
+
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use std::{collections::HashMap, env, time::{Duration, Instant}};
+use tokio::sync::RwLock;
+
+use crate::{token_revocation::{Revocation, TokenRevocations}, AppState};
+
+/// Hanya access token (umur pendek) yang diverifikasi lokal; refresh token, personal access token,
+/// dan token lama tanpa token_type tetap lewat /api/auth/verify karena perlu cek blacklist / DB
+const LOCAL_TOKEN_TYPE: &str = "access";
+
+#[derive(Debug, Deserialize)]
+struct AccessClaims {
+    sub: String,
+    role: String,
+    token_type: Option<String>,
+    jti: Option<String>,
+    iat: Option<i64>,
+}
+
+/// User hasil verifikasi lokal, dikirim ke downstream sebagai X-User-Id / X-User-Role
+#[derive(Debug, PartialEq, Eq)]
+pub struct VerifiedUser {
+    pub user_id: String,
+    pub role: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LocalVerification {
+    Verified(VerifiedUser),
+    /// Signature / expiry / issuer salah dengan key yang dikenal, langsung 401
+    Rejected,
+    /// Tidak bisa diputuskan lokal (bukan RS256, kid belum di cache, bukan access token,
+    /// atau token user yang baru di-revoke / berubah status)
+    Fallback,
+}
+
+/// Verifikasi JWT RS256 di gateway memakai public key dari JWKS auth-service
+///
+/// Trade-off: token lokal hanya dicek signature/expiry, role diambil dari claim dan status user
+/// (role berubah, nonaktif, terkunci) hanya diketahui lewat invalidasi dari auth-service
+/// (POST /api/internal/cache/invalidate-token). Karena itu default mati: semua token tetap
+/// diverifikasi lewat /api/auth/verify
+pub struct JwtVerifier {
+    enabled: bool,
+    jwks_url: String,
+    pub refresh_interval: Duration,
+    validation: Validation,
+    keys: RwLock<HashMap<String, DecodingKey>>,
+    pub revocations: TokenRevocations,
+}
+
+impl JwtVerifier {
+    /// AUTH_SERVICE_URL (JWKS di `/.well-known/jwks.json`), JWT_ISSUER, JWT_AUDIENCE (sama dengan auth-service),
+    /// GATEWAY_JWKS_REFRESH_SECONDS (default 300), GATEWAY_LOCAL_JWT_VERIFY=true untuk mengaktifkan
+    pub fn from_env() -> Self {
+        let enabled = env::var("GATEWAY_LOCAL_JWT_VERIFY")
+            .map(|v| matches!(v.to_lowercase().as_str(), "true" | "1"))
+            .unwrap_or(false);
+        let auth_service_url = env::var("AUTH_SERVICE_URL").unwrap_or_else(|_| "http://localhost:3001".to_string());
+        let issuer = env::var("JWT_ISSUER").unwrap_or_else(|_| "bookstore-auth-service".to_string());
+        let audience = env::var("JWT_AUDIENCE").unwrap_or_else(|_| "bookstore-app".to_string());
+        let refresh_secs = env::var("GATEWAY_JWKS_REFRESH_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(300);
+
+        let mut verifier = Self::new(
+            format!("{}/.well-known/jwks.json", auth_service_url.trim_end_matches('/')),
+            &issuer,
+            &audience,
+            Duration::from_secs(refresh_secs.max(10)),
+        );
+        verifier.enabled = enabled;
+        verifier.revocations = TokenRevocations::from_env();
+        verifier
+    }
+
+    fn new(jwks_url: String, issuer: &str, audience: &str, refresh_interval: Duration) -> Self {
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_issuer(&[issuer]);
+        validation.set_audience(&[audience]);
+        validation.leeway = 0;
+
+        Self {
+            enabled: true,
+            jwks_url,
+            refresh_interval,
+            validation,
+            keys: RwLock::new(HashMap::new()),
+            revocations: TokenRevocations::new(Duration::from_secs(900)),
+        }
+    }
+
+    /// Ambil ulang JWKS, cache lama dipertahankan jika fetch gagal
+    pub async fn refresh(&self, client: &reqwest::Client) -> Result<usize, String> {
+        let jwks = client.get(&self.jwks_url)
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|e| e.to_string())?
+            .json::<JwkSet>()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(self.load(&jwks).await)
+    }
+
+    /// Ganti isi cache dengan key RS256 yang punya kid
+    async fn load(&self, jwks: &JwkSet) -> usize {
+        let keys: HashMap<String, DecodingKey> = jwks.keys.iter()
+            .filter_map(|jwk| {
+                let kid = jwk.common.key_id.clone()?;
+                DecodingKey::from_jwk(jwk).ok().map(|key| (kid, key))
+            })
+            .collect();
+
+        let count = keys.len();
+        *self.keys.write().await = keys;
+        count
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub async fn verify(&self, token: &str) -> LocalVerification {
+        if !self.enabled {
+            return LocalVerification::Fallback;
+        }
+
+        let kid = match decode_header(token) {
+            Ok(header) if header.alg == Algorithm::RS256 => header.kid,
+            _ => None,
+        };
+        let Some(kid) = kid else {
+            return LocalVerification::Fallback;
+        };
+
+        let keys = self.keys.read().await;
+        let Some(key) = keys.get(&kid) else {
+            return LocalVerification::Fallback;
+        };
+
+        match decode::<AccessClaims>(token, key, &self.validation) {
+            Ok(data) if data.claims.token_type.as_deref() == Some(LOCAL_TOKEN_TYPE) => {
+                let claims = data.claims;
+                match self.revocations.check(claims.jti.as_deref(), &claims.sub, claims.iat, Instant::now()) {
+                    Revocation::Token => LocalVerification::Rejected,
+                    Revocation::User => LocalVerification::Fallback,
+                    Revocation::None => LocalVerification::Verified(VerifiedUser { user_id: claims.sub, role: claims.role }),
+                }
+            }
+            Ok(_) => LocalVerification::Fallback,
+            Err(e) => {
+                tracing::debug!("JWT ditolak verifikasi lokal (kid {}): {}", kid, e);
+                LocalVerification::Rejected
+            }
+        }
+    }
+}
+
+/// Refresh JWKS secara berkala di background (fetch pertama langsung saat start)
+pub fn start_jwks_refresher(state: AppState) {
+    if !state.jwt_verifier.enabled() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(state.jwt_verifier.refresh_interval);
+
+        loop {
+            interval.tick().await;
+            match state.jwt_verifier.refresh(&state.client).await {
+                Ok(count) => tracing::debug!("JWKS di-refresh: {} key", count),
+                Err(e) => tracing::debug!("Gagal refresh JWKS, verifikasi lewat auth-service: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, jwk::Jwk, EncodingKey, Header};
+    use serde_json::json;
+
+    const PRIVATE_KEY: &str = include_str!("../../auth-service/src/core/testdata/jwt_rs256_private.pem");
+
+    #[tokio::test]
+    async fn test_verify_locally_with_cached_jwks() {
+        let encoding_key = EncodingKey::from_rsa_pem(PRIVATE_KEY.as_bytes()).unwrap();
+        let mut jwk = Jwk::from_encoding_key(&encoding_key, Algorithm::RS256).unwrap();
+        jwk.common.key_id = Some("k1".to_string());
+
+        let verifier = JwtVerifier::new(String::new(), "bookstore-auth-service", "bookstore-app", Duration::from_secs(60));
+        let sign = |kid: &str, token_type: &str, exp_offset: i64| {
+            let now = chrono::Utc::now().timestamp();
+            let mut header = Header::new(Algorithm::RS256);
+            header.kid = Some(kid.to_string());
+            let claims = json!({
+                "sub": "user-1", "role": "admin", "token_type": token_type, "jti": format!("jti-{}", exp_offset),
+                "iss": "bookstore-auth-service", "aud": "bookstore-app",
+                "iat": now, "exp": now + exp_offset,
+            });
+            encode(&header, &claims, &encoding_key).unwrap()
+        };
+
+        // Cache kosong (mis. auth-service HS256) -> fallback ke remote
+        assert_eq!(verifier.verify(&sign("k1", "access", 900)).await, LocalVerification::Fallback);
+
+        assert_eq!(verifier.load(&JwkSet { keys: vec![jwk] }).await, 1);
+        assert_eq!(
+            verifier.verify(&sign("k1", "access", 900)).await,
+            LocalVerification::Verified(VerifiedUser { user_id: "user-1".to_string(), role: "admin".to_string() })
+        );
+        assert_eq!(verifier.verify(&sign("k1", "access", -10)).await, LocalVerification::Rejected);
+        assert_eq!(verifier.verify(&sign("k1", "pat", 900)).await, LocalVerification::Fallback);
+        assert_eq!(verifier.verify(&sign("k2", "access", 900)).await, LocalVerification::Fallback);
+        assert_eq!(verifier.verify("not-a-jwt").await, LocalVerification::Fallback);
+
+        let mut tampered = sign("k1", "access", 900);
+        tampered.pop();
+        assert_eq!(verifier.verify(&tampered).await, LocalVerification::Rejected);
+
+        // Token yang di-logout ditolak, token user yang di-revoke dicek ulang di auth-service
+        verifier.revocations.revoke_jti("jti-900", Instant::now());
+        assert_eq!(verifier.verify(&sign("k1", "access", 900)).await, LocalVerification::Rejected);
+        verifier.revocations.revoke_user("user-1", chrono::Utc::now().timestamp() + 60, Instant::now());
+        assert_eq!(verifier.verify(&sign("k1", "access", 600)).await, LocalVerification::Fallback);
+
+        // Default mati: semua token lewat auth-service agar status user di DB tetap dicek
+        let mut disabled = verifier;
+        disabled.enabled = false;
+        assert_eq!(disabled.verify(&sign("k1", "access", 900)).await, LocalVerification::Fallback);
+    }
+}
@@ -2,31 +2,60 @@
 mod circuit_breaker;
 mod service_discovery;  
 mod error;
+mod fallback;
+mod openapi;
+mod dependency_wait;
+mod jwt_verifier;
+mod client_ip;
+mod token_revocation;
 
 use axum::{
     Router,
-    extract::{Request, State},
-    http::{StatusCode, HeaderValue},  
+    extract::{ConnectInfo, Request, State},
+    http::{StatusCode, HeaderName, HeaderValue},  
     response::{Response, Json},
     body::Body,
-    routing::get,
+    routing::{get, post},
     middleware::{self, Next},
 };
-use std::{sync::Arc, time::Duration, env};
+use std::{net::SocketAddr, sync::Arc, time::Duration, env};
 use tower::ServiceBuilder;
 use tower_http::{
     cors::CorsLayer,
-    trace::TraceLayer,
     timeout::TimeoutLayer,
 };
 use service_discovery::ServiceRegistry;  
 use circuit_breaker::CircuitBreakerManager;
+use fallback::{FallbackCache, bad_gateway_response, service_unavailable_response};
+use error::AppError;
+use openapi::{OpenApiAggregator, get_merged_openapi, start_openapi_refresher};
+use service_common::admin_notifier::AdminNotifier;
+use service_common::client_ip::ClientIpResolver;
+use service_common::concurrency_limit::{ConcurrencyLimit, concurrency_limit_middleware};
+use service_common::public_routes::PublicRoutePolicy;
+use service_common::security_headers::{SecurityHeaders, security_headers_middleware};
+use service_common::session_cookie::{cookie_sessions_enabled, csrf_valid, read_cookie, requires_csrf, ACCESS_COOKIE};
+use service_common::token_scopes::{scope_allows, scopes_from_verify, SCOPES_HEADER};
+use service_common::trace_sampling::{TraceSampling, trace_sampling_middleware};
+use dependency_wait::DependencyWait;
+use jwt_verifier::{JwtVerifier, LocalVerification, start_jwks_refresher};
+use client_ip::{forwarded_headers, strip_identity_headers};
+use utoipa_swagger_ui::{Config, SwaggerUi};
+
+/// Path default yang response-nya tidak boleh di-cache (data akun/order/admin)
+const NO_STORE_PATHS: &str = "/api/auth,/api/users,/api/orders,/api/admin";
 
 #[derive(Clone)]
 pub struct AppState {
     pub client: reqwest::Client,
     pub service_registry: Arc<ServiceRegistry>, 
     pub circuit_manager: Arc<CircuitBreakerManager>,  
+    pub fallback_cache: Arc<FallbackCache>,
+    pub public_routes: Arc<PublicRoutePolicy>,
+    pub openapi: Arc<OpenApiAggregator>,
+    pub jwt_verifier: Arc<JwtVerifier>,
+    pub cookie_sessions: bool,
+    pub client_ip: Arc<ClientIpResolver>,
 }
 
 #[tokio::main]
@@ -46,15 +75,26 @@ async fn main() {
     let service_registry = Arc::new(ServiceRegistry::new());
     service_registry.init_default_services().await;
     
-    let circuit_manager = Arc::new(CircuitBreakerManager::new());
+    // Alert admin saat circuit breaker ke downstream OPEN (ADMIN_ALERT_CHANNEL)
+    let notifier = Arc::new(AdminNotifier::from_env("api-gateway", 5));
+    let circuit_manager = Arc::new(CircuitBreakerManager::new().with_notifier(notifier));
     
+    let startup_client = client.clone();
     let state = AppState { 
         client,
         service_registry,
         circuit_manager,
+        fallback_cache: Arc::new(FallbackCache::from_env()),
+        public_routes: Arc::new(PublicRoutePolicy::from_env()),
+        openapi: Arc::new(OpenApiAggregator::from_env()),
+        jwt_verifier: Arc::new(JwtVerifier::from_env()),
+        cookie_sessions: cookie_sessions_enabled(),
+        client_ip: Arc::new(ClientIpResolver::from_env()),
     };
     
     start_health_checker(state.clone());
+    start_openapi_refresher(state.clone());
+    start_jwks_refresher(state.clone());
 
     let cors = CorsLayer::new()
         .allow_origin([
@@ -75,17 +115,36 @@ async fn main() {
             axum::http::header::AUTHORIZATION,
             axum::http::header::ACCEPT,
             axum::http::header::ORIGIN,
+            HeaderName::from_static("x-csrf-token"),
+            HeaderName::from_static("x-auth-mode"),
+            HeaderName::from_static("x-refresh-token"),
         ])
-        .allow_credentials(true);
+        .expose_headers([HeaderName::from_static("x-csrf-token")])
+        .allow_credentials(true)
+        .max_age(cors_max_age());
     
     let app = Router::new()
         .route("/health", get(health_check))
         .route("/api/gateway/status", get(gateway_status))
+        .route("/api/internal/cache/invalidate-token", post(token_revocation::invalidate_token))
+        .route("/api/docs/openapi.json", get(get_merged_openapi))
+        .merge(SwaggerUi::new("/api/docs/ui").config(Config::from("/api/docs/openapi.json")))
         .fallback(proxy_handler)
         .layer(
             ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(TimeoutLayer::new(Duration::from_secs(30)))
+                .layer(middleware::from_fn_with_state(
+                    Arc::new(TraceSampling::from_env()),
+                    trace_sampling_middleware,
+                ))
+                .layer(middleware::from_fn_with_state(
+                    Arc::new(ConcurrencyLimit::from_env()),
+                    concurrency_limit_middleware,
+                ))
+                .layer(middleware::from_fn_with_state(
+                    Arc::new(SecurityHeaders::from_env(NO_STORE_PATHS)),
+                    security_headers_middleware,
+                ))
+                .layer(TimeoutLayer::new(request_timeout()))
                 .layer(cors)
                 .layer(middleware::from_fn_with_state(state.clone(), auth_middleware))
         )
@@ -106,6 +165,7 @@ async fn main() {
     println!("║  🔍 Monitoring:                                       ║");
     println!("║    GET /health              - Gateway health         ║");
     println!("║    GET /api/gateway/status  - All services status    ║");
+    println!("║    GET /api/docs/ui         - Unified Swagger UI     ║");
     println!("╠═══════════════════════════════════════════════════════╣");
     println!("║  ✨ Features:                                         ║");
     println!("║    ✓ Service Discovery                               ║");
@@ -115,10 +175,16 @@ async fn main() {
     println!("║    ✓ Request Proxying                                ║");
     println!("╚═══════════════════════════════════════════════════════╝\n");
     
+    // Opsional: tunggu auth-service sehat dulu, semua request terproteksi butuh verifikasi token
+    let auth_service_url = env::var("AUTH_SERVICE_URL")
+        .unwrap_or_else(|_| "http://localhost:3001".to_string());
+    DependencyWait::from_env().wait_for(&startup_client, "auth-service", &auth_service_url).await;
+
     let listener = tokio::net::TcpListener::bind(addr).await
         .expect("Failed to bind gateway address");
     
-    axum::serve(listener, app).await
+    // ConnectInfo: IP peer socket dipakai untuk X-Forwarded-For ke downstream
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await
         .expect("Failed to start gateway server");
 }
 
@@ -128,59 +194,38 @@ async fn auth_middleware(
     next: Next,
 ) -> Result<Response, StatusCode> {
     let path = req.uri().path().to_string();
+
+    // Identitas hanya berasal dari forward_user, header kiriman client selalu dibuang
+    strip_identity_headers(req.headers_mut());
     
-    let public_paths = [
-        "/health",
-        "/api/gateway/status",
-        "/api/auth/register",
-        "/api/auth/login",
-        "/api/auth/password-reset/request",
-        "/api/auth/password-reset/confirm",
-        "/api/auth/verify-otp",
-        "/api/auth/email/verify",
-        "/storage/",
-    ];
-
-    for public_path in public_paths.iter() {
-        if path.starts_with(public_path) {
-            return Ok(next.run(req).await);
-        }
+    // Internal endpoint diverifikasi via X-Service-Key di handler (tidak pernah di-proxy ke service)
+    if state.public_routes.is_public(req.method(), &path) || path.starts_with("/api/internal/") {
+        return Ok(next.run(req).await);
     }
     
-    if req.method() == axum::http::Method::GET {
-        if path.starts_with("/api/books") 
-            && !path.contains("/download") 
-            && !path.contains("/my-library") {
-            return Ok(next.run(req).await);
-        }
-        
-        if path.starts_with("/api/categories") {
-            return Ok(next.run(req).await);
-        }
-        
-        if path.contains("/preview") || path.contains("/related") {
-            return Ok(next.run(req).await);
+    let token = request_token(&mut req, state.cookie_sessions).inspect_err(|status| {
+        if *status == StatusCode::FORBIDDEN {
+            tracing::warn!("CSRF token invalid for cookie session: {} {}", req.method(), path);
+        } else {
+            tracing::warn!("Missing or invalid Authorization header for path: {}", path);
         }
-        
-        if path.contains("/reviews") {
+    })?;
+    
+    // Access token RS256 diverifikasi lokal pakai JWKS jika GATEWAY_LOCAL_JWT_VERIFY aktif,
+    // sisanya (atau key belum ada) lewat auth-service
+    match state.jwt_verifier.verify(&token).await {
+        LocalVerification::Verified(user) => {
+            forward_user(&mut req, &user.user_id, &user.role, None)?;
+            tracing::debug!("Auth success (local): user_id={}, role={}, path={}", user.user_id, user.role, path);
             return Ok(next.run(req).await);
         }
-    }
-    
-    let auth_header = req.headers()
-        .get(axum::http::header::AUTHORIZATION)
-        .and_then(|h| h.to_str().ok());
-    
-    let token = match auth_header {
-        Some(header) if header.starts_with("Bearer ") => {
-            header.strip_prefix("Bearer ").unwrap()
-        }
-        _ => {
-            tracing::warn!("Missing or invalid Authorization header for path: {}", path);
+        LocalVerification::Rejected => {
+            tracing::warn!("Local JWT verification failed for path: {}", path);
             return Err(StatusCode::UNAUTHORIZED);
         }
-    };
-    
+        LocalVerification::Fallback => {}
+    }
+
     let auth_service_url = env::var("AUTH_SERVICE_URL")
         .unwrap_or_else(|_| "http://localhost:3001".to_string());
     
@@ -198,18 +243,16 @@ async fn auth_middleware(
                     user_data["user"]["id"].as_str(),
                     user_data["user"]["role"].as_str(),
                 ) {
-                    req.headers_mut().insert(
-                        "X-Gateway-Request",
-                        HeaderValue::from_static("true"),
-                    );
-                    req.headers_mut().insert(
-                        "X-User-Id",
-                        HeaderValue::from_str(user_id).unwrap(),
-                    );
-                    req.headers_mut().insert(
-                        "X-User-Role",
-                        HeaderValue::from_str(user_role).unwrap(),
-                    );
+                    // Personal access token: scope dicek di gateway dan diteruskan ke service downstream
+                    let scopes = scopes_from_verify(&user_data);
+                    if let Some(scopes) = &scopes {
+                        if !scope_allows(scopes, req.method(), &path) {
+                            tracing::warn!("PAT for user {} lacks scope for {} {}", user_id, req.method(), path);
+                            return Err(StatusCode::FORBIDDEN);
+                        }
+                    }
+
+                    forward_user(&mut req, user_id, user_role, scopes.as_deref())?;
                     
                     tracing::debug!("Auth success: user_id={}, role={}, path={}", 
                         user_id, user_role, path);
@@ -232,6 +275,42 @@ async fn auth_middleware(
     }
 }
 
+/// Header identitas user untuk service downstream (header kiriman client sudah dibuang di auth_middleware)
+fn forward_user(req: &mut Request, user_id: &str, user_role: &str, scopes: Option<&[String]>) -> Result<(), StatusCode> {
+    let headers = req.headers_mut();
+    headers.insert("X-Gateway-Request", HeaderValue::from_static("true"));
+    headers.insert("X-User-Id", HeaderValue::from_str(user_id).map_err(|_| StatusCode::UNAUTHORIZED)?);
+    headers.insert("X-User-Role", HeaderValue::from_str(user_role).map_err(|_| StatusCode::UNAUTHORIZED)?);
+    if let Some(scopes) = scopes {
+        headers.insert(SCOPES_HEADER, HeaderValue::from_str(&scopes.join(",")).map_err(|_| StatusCode::UNAUTHORIZED)?);
+    }
+    Ok(())
+}
+
+/// Token dari Authorization header, atau cookie access_token jika mode cookie aktif (AUTH_COOKIE_ENABLED,
+/// sama dengan auth-service): method non-GET wajib CSRF double-submit, token diteruskan ke downstream
+/// lewat Authorization header
+fn request_token(req: &mut Request, cookie_sessions: bool) -> Result<String, StatusCode> {
+    if let Some(header) = req.headers().get(axum::http::header::AUTHORIZATION) {
+        return header.to_str().ok()
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .map(str::to_string)
+            .ok_or(StatusCode::UNAUTHORIZED);
+    }
+
+    let token = read_cookie(req.headers(), ACCESS_COOKIE)
+        .filter(|_| cookie_sessions)
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    if requires_csrf(req.method()) && !csrf_valid(req.headers()) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let bearer = HeaderValue::from_str(&format!("Bearer {}", token))
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+    req.headers_mut().insert(axum::http::header::AUTHORIZATION, bearer);
+    Ok(token)
+}
+
 async fn gateway_status(State(state): State<AppState>) -> Json<serde_json::Value> {
     let status = state.service_registry.get_status().await;
     
@@ -253,6 +332,7 @@ async fn gateway_status(State(state): State<AppState>) -> Json<serde_json::Value
 
 async fn proxy_handler(
     State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
     req: Request,
 ) -> Result<Response<Body>, StatusCode> {
     let path = req.uri().path().to_string();
@@ -260,11 +340,17 @@ async fn proxy_handler(
     
     tracing::debug!("Proxying request: {} {}", method, path);
     
-    let service_name = if path.starts_with("/api/auth") {
+    // Introspection hanya untuk service internal, tidak diteruskan dari luar (seperti /api/internal/*)
+    if path == "/api/auth/introspect" {
+        tracing::warn!("Blocked external request to internal-only path: {}", path);
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let service_name = if path.starts_with("/api/auth") || path.starts_with("/.well-known/") {
         "auth-service"
     } else if path.starts_with("/api/books") || path.starts_with("/api/categories") {
         "book-service"
-    } else if path.starts_with("/api/orders") || path.starts_with("/api/payments") {
+    } else if path.starts_with("/api/orders") || path.starts_with("/api/payments") || path.starts_with("/api/payment-methods") {
         "payment-service"
     } else if path.starts_with("/storage") {
         "book-service"
@@ -273,45 +359,75 @@ async fn proxy_handler(
         return Err(StatusCode::NOT_FOUND);
     };
     
-    let service = state.service_registry
-        .get_healthy_instance(service_name)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to get healthy instance for {}: {:?}", service_name, e);
-            StatusCode::SERVICE_UNAVAILABLE
-        })?;
-    
-    let service_url = service.get_url();
-    
+    let correlation_id = req.headers()
+        .get("X-Correlation-Id")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
     let query = req.uri().query()
         .map(|q| format!("?{}", q))
         .unwrap_or_default();
-    let url = format!("{}{}{}", service_url, path, query);
-    
-    tracing::debug!("Forwarding to: {}", url);
-    
+    let path_and_query = format!("{}{}", path, query);
+
+    // Cached fallback hanya untuk GET pada route yang di-opt-in
+    // Request dengan Authorization tidak di-cache supaya data user tidak bocor
+    let fallback_enabled = method == axum::http::Method::GET
+        && !req.headers().contains_key(axum::http::header::AUTHORIZATION)
+        && state.fallback_cache.is_enabled_for(&path);
+
     let (parts, body) = req.into_parts();
     let body_bytes = axum::body::to_bytes(body, usize::MAX).await
         .map_err(|e| {
             tracing::error!("Failed to read request body: {}", e);
             StatusCode::BAD_REQUEST
         })?;
-    
+
+    let service = match state.service_registry.get_healthy_instance(service_name).await {
+        Ok(service) => service,
+        Err(e) => {
+            tracing::error!("Failed to get healthy instance for {}: {:?}", service_name, e);
+            return Ok(degraded_response(&state, service_name, &path_and_query, fallback_enabled, &correlation_id, DownstreamFailure::Unavailable).await);
+        }
+    };
+
+    let url = format!("{}{}", service.get_url(), path_and_query);
+
+    tracing::debug!("Forwarding to: {} (correlation_id={})", url, correlation_id);
+
     let mut req_builder = state.client.request(parts.method.clone(), &url);
-    
-    for (key, value) in parts.headers.iter() {
+
+    for (key, value) in forwarded_headers(&parts.headers, peer.ip(), &state.client_ip).iter() {
         req_builder = req_builder.header(key, value);
     }
-    
-    let response = req_builder
-        .body(body_bytes.to_vec())
-        .send()
-        .await
-        .map_err(|e| {
-            tracing::error!("Proxy error to {} ({}): {}", service_name, url, e);
-            StatusCode::BAD_GATEWAY
-        })?;
-    
+    req_builder = req_builder.header("X-Correlation-Id", &correlation_id);
+
+    // Circuit breaker menghitung kegagalan koneksi ke downstream
+    // `attempted` membedakan circuit open (503) dari request yang gagal terkirim (502)
+    let breaker = state.circuit_manager.get_or_create(service_name).await;
+    let attempted = std::sync::atomic::AtomicBool::new(false);
+    let result = breaker.call(async {
+        attempted.store(true, std::sync::atomic::Ordering::Relaxed);
+        req_builder
+            .body(body_bytes.to_vec())
+            .send()
+            .await
+            .map_err(|e| AppError::ExternalService(format!("Proxy error to {} ({}): {}", service_name, url, e)))
+    }).await;
+
+    let response = match result {
+        Ok(response) => response,
+        Err(e) => {
+            tracing::error!("Downstream {} unavailable: {:?}", service_name, e);
+            let failure = if attempted.load(std::sync::atomic::Ordering::Relaxed) {
+                DownstreamFailure::BadGateway
+            } else {
+                DownstreamFailure::Unavailable
+            };
+            return Ok(degraded_response(&state, service_name, &path_and_query, fallback_enabled, &correlation_id, failure).await);
+        }
+    };
+
     let status = response.status();
     let headers = response.headers().clone();
     let body = response.bytes().await
@@ -319,15 +435,21 @@ async fn proxy_handler(
             tracing::error!("Failed to read response body: {}", e);
             StatusCode::BAD_GATEWAY
         })?;
-    
+
     tracing::debug!("Proxy response: {} from {}", status, service_name);
-    
+
+    if fallback_enabled && status.is_success() {
+        state.fallback_cache
+            .store(&path_and_query, body.clone(), headers.get(axum::http::header::CONTENT_TYPE).cloned())
+            .await;
+    }
+
     let mut builder = Response::builder().status(status);
-    
+
     for (key, value) in headers.iter() {
         builder = builder.header(key, value);
     }
-    
+
     builder
         .body(Body::from(body))
         .map_err(|e| {
@@ -336,6 +458,63 @@ async fn proxy_handler(
         })
 }
 
+/// Penyebab downstream tidak bisa melayani request
+enum DownstreamFailure {
+    /// Tidak ada instance sehat / circuit breaker open (503 + Retry-After)
+    Unavailable,
+    /// Request terkirim tapi gagal (koneksi putus, timeout) (502)
+    BadGateway,
+}
+
+/// Response saat downstream down: cached last-known-good (jika opt-in), atau JSON 503 / 502
+async fn degraded_response(
+    state: &AppState,
+    service_name: &str,
+    path_and_query: &str,
+    fallback_enabled: bool,
+    correlation_id: &str,
+    failure: DownstreamFailure,
+) -> Response<Body> {
+    if fallback_enabled {
+        if let Some(cached) = state.fallback_cache.get(path_and_query, correlation_id).await {
+            tracing::warn!("Serving cached fallback for {} ({} down)", path_and_query, service_name);
+            return cached;
+        }
+    }
+
+    match failure {
+        DownstreamFailure::Unavailable => service_unavailable_response(service_name, retry_after_hint().as_secs(), correlation_id),
+        DownstreamFailure::BadGateway => bad_gateway_response(service_name, correlation_id),
+    }
+}
+
+/// Retry hint untuk client saat service down (GATEWAY_RETRY_AFTER_SECONDS, default 30 detik)
+fn retry_after_hint() -> Duration {
+    let secs = env::var("GATEWAY_RETRY_AFTER_SECONDS")
+        .unwrap_or_else(|_| "30".to_string())
+        .parse()
+        .unwrap_or(30);
+    Duration::from_secs(secs)
+}
+
+/// Timeout global request (REQUEST_TIMEOUT_SECONDS, default 30 detik)
+fn request_timeout() -> Duration {
+    let secs = env::var("REQUEST_TIMEOUT_SECONDS")
+        .unwrap_or_else(|_| "30".to_string())
+        .parse()
+        .unwrap_or(30);
+    Duration::from_secs(secs)
+}
+
+/// Durasi cache preflight CORS (Access-Control-Max-Age)
+fn cors_max_age() -> Duration {
+    let secs = env::var("CORS_MAX_AGE_SECONDS")
+        .unwrap_or_else(|_| "3600".to_string())
+        .parse()
+        .unwrap_or(3600);
+    Duration::from_secs(secs)
+}
+
 fn start_health_checker(state: AppState) {
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(Duration::from_secs(30));
@@ -357,4 +536,37 @@ async fn health_check() -> Json<serde_json::Value> {
         "timestamp": chrono::Utc::now(),
         "version": "1.0.0"
     }))
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Method;
+
+    fn cookie_request(method: Method, csrf_header: Option<&str>) -> Request {
+        let mut builder = Request::builder()
+            .method(method)
+            .uri("/api/orders")
+            .header(axum::http::header::COOKIE, "access_token=jwt; csrf_token=tok");
+        if let Some(csrf) = csrf_header {
+            builder = builder.header("X-CSRF-Token", csrf);
+        }
+        builder.body(axum::body::Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn test_cookie_session_csrf_matches_auth_service() {
+        // CSRF hilang atau beda: ditolak sebelum token diverifikasi
+        assert_eq!(request_token(&mut cookie_request(Method::POST, None), true), Err(StatusCode::FORBIDDEN));
+        assert_eq!(request_token(&mut cookie_request(Method::POST, Some("forged")), true), Err(StatusCode::FORBIDDEN));
+
+        // CSRF valid: token cookie diteruskan sebagai Authorization header
+        let mut valid = cookie_request(Method::POST, Some("tok"));
+        assert_eq!(request_token(&mut valid, true).as_deref(), Ok("jwt"));
+        assert_eq!(valid.headers()[axum::http::header::AUTHORIZATION], "Bearer jwt");
+        assert_eq!(request_token(&mut cookie_request(Method::GET, None), true).as_deref(), Ok("jwt"));
+
+        // Mode cookie tidak aktif (default AUTH_COOKIE_ENABLED): cookie diabaikan seperti di auth-service
+        assert_eq!(request_token(&mut cookie_request(Method::GET, None), false), Err(StatusCode::UNAUTHORIZED));
+        assert_eq!(request_token(&mut cookie_request(Method::POST, Some("tok")), false), Err(StatusCode::UNAUTHORIZED));
+    }
+}
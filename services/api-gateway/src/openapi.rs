@@ -0,0 +1,305 @@
+// /pdf-bookstore/services/api-gateway/src/openapi.rs
+
+use axum::{extract::State, http::StatusCode, response::Json};
+use serde_json::{json, Map, Value};
+use std::{env, time::{Duration, Instant}};
+use tokio::sync::RwLock;
+
+use crate::AppState;
+
+/// Section components yang di-namespace per service (securitySchemes dibagi bersama
+/// karena dirujuk by name dari `security`, bukan lewat $ref)
+const NAMESPACED_COMPONENTS: &[&str] = &[
+    "schemas", "responses", "parameters", "examples", "requestBodies", "headers", "links", "callbacks",
+];
+
+/// Sumber OpenAPI spec satu service
+#[derive(Debug, Clone)]
+struct SpecSource {
+    name: String,
+    url: String,
+}
+
+/// Gabungan OpenAPI spec semua service, di-cache dan di-refresh berkala
+pub struct OpenApiAggregator {
+    sources: Vec<SpecSource>,
+    gateway_url: String,
+    refresh_interval: Duration,
+    merged: RwLock<Option<(Instant, Value)>>,
+}
+
+impl OpenApiAggregator {
+    /// OPENAPI_SOURCES: `nama=url` comma-separated (default auth/books/payments di `/api-docs/openapi.json`
+    /// memakai AUTH_SERVICE_URL, BOOK_SERVICE_URL, PAYMENT_SERVICE_URL)
+    /// GATEWAY_PUBLIC_URL (default http://localhost:8000), OPENAPI_REFRESH_SECONDS (default 300)
+    pub fn from_env() -> Self {
+        let service_url = |key: &str, default: &str| env::var(key).unwrap_or_else(|_| default.to_string());
+
+        let sources = match env::var("OPENAPI_SOURCES") {
+            Ok(list) => list.split(',')
+                .filter_map(|entry| entry.split_once('='))
+                .map(|(name, url)| SpecSource { name: name.trim().to_string(), url: url.trim().to_string() })
+                .filter(|s| !s.name.is_empty() && !s.url.is_empty())
+                .collect(),
+            Err(_) => [
+                ("auth", service_url("AUTH_SERVICE_URL", "http://localhost:3001")),
+                ("books", service_url("BOOK_SERVICE_URL", "http://localhost:3002")),
+                ("payments", service_url("PAYMENT_SERVICE_URL", "http://localhost:3003")),
+            ]
+            .into_iter()
+            .map(|(name, base)| SpecSource {
+                name: name.to_string(),
+                url: format!("{}/api-docs/openapi.json", base.trim_end_matches('/')),
+            })
+            .collect(),
+        };
+
+        let refresh_secs = env::var("OPENAPI_REFRESH_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(300);
+
+        Self {
+            sources,
+            gateway_url: service_url("GATEWAY_PUBLIC_URL", "http://localhost:8000"),
+            refresh_interval: Duration::from_secs(refresh_secs.max(10)),
+            merged: RwLock::new(None),
+        }
+    }
+
+    /// Ambil spec semua service lalu gabungkan, service yang gagal dilewati
+    /// Return None jika tidak ada satu pun spec yang berhasil diambil
+    pub async fn refresh(&self, client: &reqwest::Client) -> Option<Value> {
+        let mut specs = Vec::new();
+
+        for source in &self.sources {
+            let result = client.get(&source.url)
+                .timeout(Duration::from_secs(5))
+                .send()
+                .await;
+
+            match result {
+                Ok(resp) if resp.status().is_success() => match resp.json::<Value>().await {
+                    Ok(spec) => specs.push((source.name.clone(), spec)),
+                    Err(e) => tracing::warn!("OpenAPI spec {} tidak valid: {}", source.name, e),
+                },
+                Ok(resp) => tracing::debug!("OpenAPI spec {} tidak tersedia: {}", source.name, resp.status()),
+                Err(e) => tracing::debug!("Gagal mengambil OpenAPI spec {}: {}", source.name, e),
+            }
+        }
+
+        if specs.is_empty() {
+            return None;
+        }
+
+        let merged = merge_specs(&specs, &self.gateway_url);
+        *self.merged.write().await = Some((Instant::now(), merged.clone()));
+        Some(merged)
+    }
+
+    /// Spec dari cache, refresh jika kadaluarsa (spec lama tetap dipakai jika refresh gagal)
+    pub async fn get(&self, client: &reqwest::Client) -> Option<Value> {
+        let cached = self.merged.read().await.clone();
+
+        match cached {
+            Some((built_at, spec)) if built_at.elapsed() < self.refresh_interval => Some(spec),
+            stale => match self.refresh(client).await {
+                Some(spec) => Some(spec),
+                None => stale.map(|(_, spec)| spec),
+            },
+        }
+    }
+}
+
+/// Refresh spec gabungan secara berkala di background
+pub fn start_openapi_refresher(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(state.openapi.refresh_interval);
+
+        loop {
+            interval.tick().await;
+            if state.openapi.refresh(&state.client).await.is_none() {
+                tracing::debug!("Belum ada OpenAPI spec service yang bisa diambil");
+            }
+        }
+    });
+}
+
+/// Handler spec OpenAPI gabungan semua service
+/// GET /api/docs/openapi.json
+pub async fn get_merged_openapi(State(state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+    state.openapi.get(&state.client).await
+        .map(Json)
+        .ok_or(StatusCode::SERVICE_UNAVAILABLE)
+}
+
+/// Gabungkan spec: server diarahkan ke gateway, paths digabung, components di-namespace `{service}.{Nama}`
+fn merge_specs(specs: &[(String, Value)], gateway_url: &str) -> Value {
+    let mut paths = Map::new();
+    let mut components: Map<String, Value> = Map::new();
+    let mut tags: Vec<Value> = Vec::new();
+    let mut services = Vec::new();
+
+    for (service, spec) in specs {
+        let mut spec = spec.clone();
+        rewrite_refs(&mut spec, service);
+        services.push(service.as_str());
+
+        if let Some(spec_paths) = spec["paths"].as_object() {
+            for (path, item) in spec_paths {
+                let mut item = item.clone();
+                strip_servers(&mut item);
+
+                match paths.get_mut(path).and_then(Value::as_object_mut) {
+                    Some(existing) => {
+                        for (method, operation) in item.as_object().into_iter().flatten() {
+                            if existing.contains_key(method) {
+                                tracing::warn!("Operasi {} {} duplikat di {}, dilewati", method, path, service);
+                            } else {
+                                existing.insert(method.clone(), operation.clone());
+                            }
+                        }
+                    }
+                    None => {
+                        paths.insert(path.clone(), item);
+                    }
+                }
+            }
+        }
+
+        for (section, entries) in spec["components"].as_object().into_iter().flatten() {
+            let target = components.entry(section.clone())
+                .or_insert_with(|| Value::Object(Map::new()))
+                .as_object_mut();
+            let (Some(target), Some(entries)) = (target, entries.as_object()) else {
+                continue;
+            };
+
+            for (name, value) in entries {
+                let key = if NAMESPACED_COMPONENTS.contains(&section.as_str()) {
+                    format!("{}.{}", service, name)
+                } else {
+                    name.clone()
+                };
+                target.entry(key).or_insert_with(|| value.clone());
+            }
+        }
+
+        for tag in spec["tags"].as_array().into_iter().flatten() {
+            if !tags.iter().any(|t| t["name"] == tag["name"]) {
+                tags.push(tag.clone());
+            }
+        }
+    }
+
+    json!({
+        "openapi": specs.first().and_then(|(_, s)| s["openapi"].as_str()).unwrap_or("3.1.0"),
+        "info": {
+            "title": "PDF Bookstore API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": format!("Spec gabungan via API gateway: {}", services.join(", ")),
+        },
+        "servers": [{ "url": gateway_url }],
+        "paths": paths,
+        "components": components,
+        "tags": tags,
+    })
+}
+
+/// Ubah `#/components/{section}/{Nama}` jadi `#/components/{section}/{service}.{Nama}`
+fn rewrite_refs(value: &mut Value, service: &str) {
+    match value {
+        Value::Object(map) => {
+            for (key, inner) in map.iter_mut() {
+                if key == "$ref" {
+                    if let Some(reference) = inner.as_str() {
+                        *inner = Value::String(namespace_ref(reference, service));
+                    }
+                } else {
+                    rewrite_refs(inner, service);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| rewrite_refs(item, service)),
+        _ => {}
+    }
+}
+
+fn namespace_ref(reference: &str, service: &str) -> String {
+    let Some(rest) = reference.strip_prefix("#/components/") else {
+        return reference.to_string();
+    };
+
+    match rest.split_once('/') {
+        Some((section, name)) if NAMESPACED_COMPONENTS.contains(&section) => {
+            format!("#/components/{}/{}.{}", section, service, name)
+        }
+        _ => reference.to_string(),
+    }
+}
+
+/// Server per path/operasi menunjuk host internal service, buang supaya pakai server gateway
+fn strip_servers(item: &mut Value) {
+    if let Some(map) = item.as_object_mut() {
+        map.remove("servers");
+        for operation in map.values_mut() {
+            if let Some(op) = operation.as_object_mut() {
+                op.remove("servers");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_namespaces_components_and_rewrites_servers() {
+        let auth = json!({
+            "openapi": "3.1.0",
+            "servers": [{ "url": "http://auth-service:3001" }],
+            "paths": {
+                "/api/auth/login": { "post": {
+                    "servers": [{ "url": "http://auth-service:3001" }],
+                    "responses": { "200": { "content": { "application/json": {
+                        "schema": { "$ref": "#/components/schemas/AuthResponse" }
+                    }}}}
+                }}
+            },
+            "components": {
+                "schemas": { "AuthResponse": { "type": "object" }, "ErrorResponse": { "type": "object" } },
+                "securitySchemes": { "bearer_auth": { "type": "http", "scheme": "bearer" } }
+            },
+            "tags": [{ "name": "auth" }]
+        });
+        let books = json!({
+            "paths": { "/api/books": { "get": {
+                "responses": { "400": { "$ref": "#/components/responses/BadRequest" } }
+            }}},
+            "components": {
+                "schemas": { "ErrorResponse": { "type": "object" } },
+                "responses": { "BadRequest": { "description": "bad" } },
+                "securitySchemes": { "bearer_auth": { "type": "http" } }
+            }
+        });
+
+        let merged = merge_specs(
+            &[("auth".to_string(), auth), ("books".to_string(), books)],
+            "https://api.example.com",
+        );
+
+        assert_eq!(merged["servers"], json!([{ "url": "https://api.example.com" }]));
+        assert!(merged["paths"]["/api/auth/login"]["post"].get("servers").is_none());
+        assert_eq!(
+            merged["paths"]["/api/auth/login"]["post"]["responses"]["200"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/auth.AuthResponse"
+        );
+        assert_eq!(merged["paths"]["/api/books"]["get"]["responses"]["400"]["$ref"], "#/components/responses/books.BadRequest");
+
+        let schemas = merged["components"]["schemas"].as_object().unwrap();
+        assert!(schemas.contains_key("auth.ErrorResponse") && schemas.contains_key("books.ErrorResponse"));
+        assert_eq!(merged["components"]["securitySchemes"]["bearer_auth"]["scheme"], "bearer");
+        assert_eq!(merged["tags"], json!([{ "name": "auth" }]));
+    }
+}
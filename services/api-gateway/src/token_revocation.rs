@@ -0,0 +1,149 @@
+// /pdf-bookstore/services/api-gateway/src/token_revocation.rs
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::Json,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use crate::AppState;
+
+/// Access token yang dicabut auth-service sebelum expired (logout, revoke-all, user dinonaktifkan)
+/// Hanya dipakai verifikasi lokal JWKS; entry disimpan selama umur access token lalu dibuang
+pub struct TokenRevocations {
+    ttl: Duration,
+    jtis: RwLock<HashMap<String, Instant>>,
+    /// user_id -> (waktu revoke unix, kapan entry dibuang)
+    users: RwLock<HashMap<String, (i64, Instant)>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Revocation {
+    None,
+    /// jti di-blacklist (logout), langsung 401
+    Token,
+    /// Token user diterbitkan sebelum revoke-all / perubahan status, dicek ulang di auth-service
+    User,
+}
+
+impl TokenRevocations {
+    /// GATEWAY_REVOCATION_TTL_SECONDS (default 900 = umur access token di auth-service)
+    pub fn from_env() -> Self {
+        let ttl = env::var("GATEWAY_REVOCATION_TTL_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(900);
+
+        Self::new(Duration::from_secs(ttl))
+    }
+
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, jtis: RwLock::new(HashMap::new()), users: RwLock::new(HashMap::new()) }
+    }
+
+    pub fn revoke_jti(&self, jti: &str, now: Instant) {
+        let mut jtis = self.jtis.write().unwrap_or_else(|e| e.into_inner());
+        jtis.retain(|_, expires| *expires > now);
+        jtis.insert(jti.to_string(), now + self.ttl);
+    }
+
+    /// Semua token user dengan iat <= `revoked_at` (unix detik) dianggap dicabut
+    pub fn revoke_user(&self, user_id: &str, revoked_at: i64, now: Instant) {
+        let mut users = self.users.write().unwrap_or_else(|e| e.into_inner());
+        users.retain(|_, (_, expires)| *expires > now);
+        let entry = users.entry(user_id.to_string()).or_insert((revoked_at, now + self.ttl));
+        *entry = (entry.0.max(revoked_at), now + self.ttl);
+    }
+
+    pub fn check(&self, jti: Option<&str>, user_id: &str, iat: Option<i64>, now: Instant) -> Revocation {
+        let live = |expires: &Instant| *expires > now;
+
+        if let Some(jti) = jti {
+            let jtis = self.jtis.read().unwrap_or_else(|e| e.into_inner());
+            if jtis.get(jti).is_some_and(live) {
+                return Revocation::Token;
+            }
+        }
+
+        let users = self.users.read().unwrap_or_else(|e| e.into_inner());
+        match users.get(user_id) {
+            // Token tanpa iat tidak bisa dibandingkan, ikut dicek ulang
+            Some((revoked_at, expires)) if live(expires) && iat.is_none_or(|iat| iat <= *revoked_at) => Revocation::User,
+            _ => Revocation::None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InvalidateTokenRequest {
+    pub user_id: String,
+    /// jti access token yang di-logout, None = semua token user sampai `revoked_at`
+    pub jti: Option<String>,
+    pub revoked_at: Option<i64>,
+}
+
+/// Invalidasi token dari auth-service (logout, revoke-all, status user berubah)
+/// POST /api/internal/cache/invalidate-token (X-Service-Key)
+pub async fn invalidate_token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<InvalidateTokenRequest>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let service_key = headers.get("X-Service-Key").and_then(|h| h.to_str().ok()).unwrap_or("");
+    let expected_key = env::var("INTERNAL_SERVICE_KEY")
+        .unwrap_or_else(|_| "internal-service-key-secret".to_string());
+    if service_key != expected_key {
+        tracing::warn!("Invalid service key for token invalidation");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let now = Instant::now();
+    let revocations = &state.jwt_verifier.revocations;
+    match &request.jti {
+        Some(jti) => revocations.revoke_jti(jti, now),
+        None => revocations.revoke_user(
+            &request.user_id,
+            request.revoked_at.unwrap_or_else(|| chrono::Utc::now().timestamp()),
+            now,
+        ),
+    }
+
+    tracing::info!("Token invalidated for user {} (jti: {:?})", request.user_id, request.jti);
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_revoked_jti_and_user_expire_with_ttl() {
+        let revocations = TokenRevocations::new(Duration::from_secs(900));
+        let now = Instant::now();
+
+        revocations.revoke_jti("jti-1", now);
+        assert_eq!(revocations.check(Some("jti-1"), "user-1", Some(100), now), Revocation::Token);
+        assert_eq!(revocations.check(Some("jti-2"), "user-1", Some(100), now), Revocation::None);
+
+        // Revoke user: token lama dicek ulang, token yang terbit setelahnya tetap lokal
+        revocations.revoke_user("user-1", 200, now);
+        assert_eq!(revocations.check(Some("jti-2"), "user-1", Some(200), now), Revocation::User);
+        assert_eq!(revocations.check(Some("jti-3"), "user-1", Some(201), now), Revocation::None);
+        assert_eq!(revocations.check(None, "user-1", None, now), Revocation::User);
+        assert_eq!(revocations.check(None, "user-2", Some(100), now), Revocation::None);
+
+        // Revoke lebih lama tidak memundurkan batas
+        revocations.revoke_user("user-1", 150, now);
+        assert_eq!(revocations.check(None, "user-1", Some(180), now), Revocation::User);
+
+        let later = now + Duration::from_secs(901);
+        assert_eq!(revocations.check(Some("jti-1"), "user-1", Some(100), later), Revocation::None);
+    }
+}
@@ -8,11 +8,12 @@ use axum::{
 };
 use uuid::Uuid;
 use std::collections::HashMap;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 
 use crate::{
     AppState,
     models::*,
-    db::{UserRepository, DatabaseError},
+    db::{UserRepository, DatabaseError, SecurityEventFilter, AccountLockState},
     utils::get_pepper, 
 };
 
@@ -22,7 +23,7 @@ pub async fn get_admin_user_stats(
     State(state): State<AppState>,
     Extension(user_role): Extension<String>,
     Extension(user_id): Extension<Uuid>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<Json<ApiResponse<AdminUserStats>>, (StatusCode, Json<ErrorResponse>)> {
     // Cek akses admin
     if user_role != "admin" {
         tracing::warn!("Non-admin user {} attempted to access admin stats", user_id);
@@ -36,11 +37,7 @@ pub async fn get_admin_user_stats(
     
     match user_repository.get_admin_user_stats(&state.db).await {
         Ok(stats) => {
-            Ok(Json(serde_json::json!({
-                "success": true,
-                "message": "Statistik user berhasil diambil",
-                "data": stats
-            })))
+            Ok(Json(ApiResponse::ok("Statistik user berhasil diambil", stats)))
         }
         Err(e) => {
             tracing::error!("Failed to get admin user stats: {}", e);
@@ -58,7 +55,7 @@ pub async fn get_admin_users(
     State(state): State<AppState>,
     Extension(user_role): Extension<String>,
     Query(params): Query<HashMap<String, String>>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<Json<ApiResponse<Vec<AdminUserProfile>, AdminPaginationMeta>>, (StatusCode, Json<ErrorResponse>)> {
     // Cek akses admin
     if user_role != "admin" {
         return Err((
@@ -85,12 +82,7 @@ pub async fn get_admin_users(
                 }
             }
             
-            Ok(Json(serde_json::json!({
-                "success": true,
-                "message": "Users berhasil diambil dengan order data",
-                "data": users,
-                "pagination": pagination
-            })))
+            Ok(Json(ApiResponse::ok("Users berhasil diambil dengan order data", users).with_meta(pagination)))
         }
         Err(e) => {
             Err((
@@ -107,7 +99,7 @@ pub async fn get_admin_activity_feed(
     State(state): State<AppState>,
     Extension(user_role): Extension<String>,
     Query(params): Query<HashMap<String, String>>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<Json<ApiResponse<Vec<UserActivity>>>, (StatusCode, Json<ErrorResponse>)> {
     // Cek akses admin
     if user_role != "admin" {
         return Err((
@@ -123,11 +115,7 @@ pub async fn get_admin_activity_feed(
 
     match user_repository.get_user_activity_feed(&state.db, limit, activity_type).await {
         Ok(activities) => {
-            Ok(Json(serde_json::json!({
-                "success": true,
-                "message": "Feed aktivitas berhasil diambil",
-                "data": activities
-            })))
+            Ok(Json(ApiResponse::ok("Feed aktivitas berhasil diambil", activities)))
         }
         Err(e) => {
             Err((
@@ -139,12 +127,14 @@ pub async fn get_admin_activity_feed(
 }
 
 /// Handler untuk mendapatkan security activity feed (admin only)
+/// Query: severity, event_type, user_id, from, to (RFC3339 / YYYY-MM-DD, default 30 hari terakhir),
+/// sort=severity (severity tertinggi dulu), page, limit
 /// GET /api/admin/security/activity
 pub async fn get_security_activity_feed(
     State(state): State<AppState>,
     Extension(user_role): Extension<String>,
     Query(params): Query<HashMap<String, String>>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<Json<ApiResponse<Vec<UserActivity>, SecurityFeedMeta>>, (StatusCode, Json<ErrorResponse>)> {
     // Cek akses admin
     if user_role != "admin" {
         return Err((
@@ -152,41 +142,68 @@ pub async fn get_security_activity_feed(
             Json(ErrorResponse::new("Akses admin diperlukan", Some("INSUFFICIENT_PRIVILEGES")))
         ));
     }
-    
+
+    let bad_request = |message: &str, code: &str| (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new(message, Some(code)))
+    );
+
+    let page = params.get("page").and_then(|p| p.parse::<u32>().ok()).unwrap_or(1).max(1);
     let limit = params.get("limit")
         .and_then(|l| l.parse::<u32>().ok())
         .unwrap_or(50)
-        .min(100);
-    
-    let severity_filter = params.get("severity").map(|s| s.as_str());
-    
-    let user_filter = params.get("user_id")
-        .and_then(|id| Uuid::parse_str(id).ok());
-    
-    let user_repository = UserRepository::new(get_pepper().as_bytes());
-    
-    match user_repository.get_security_activity_feed(
-        &state.db,
+        .clamp(1, 100);
+
+    let severity = match params.get("severity").filter(|s| !s.is_empty()) {
+        Some(s) => Some(ActivitySeverity::parse(s)
+            .ok_or_else(|| bad_request("Severity harus info, warning, critical, atau security", "INVALID_SEVERITY"))?),
+        None => None,
+    };
+
+    let user_id = match params.get("user_id").filter(|s| !s.is_empty()) {
+        Some(id) => Some(Uuid::parse_str(id)
+            .map_err(|_| bad_request("Format user_id tidak valid", "INVALID_USER_ID"))?),
+        None => None,
+    };
+
+    let to = match params.get("to").filter(|s| !s.is_empty()) {
+        Some(v) => parse_date_param(v, true).ok_or_else(|| bad_request("Format tanggal 'to' tidak valid", "INVALID_DATE"))?,
+        None => Utc::now(),
+    };
+    let from = match params.get("from").filter(|s| !s.is_empty()) {
+        Some(v) => parse_date_param(v, false).ok_or_else(|| bad_request("Format tanggal 'from' tidak valid", "INVALID_DATE"))?,
+        None => to - Duration::days(30),
+    };
+
+    if from >= to {
+        return Err(bad_request("'from' harus sebelum 'to'", "INVALID_DATE_RANGE"));
+    }
+
+    let filter = SecurityEventFilter {
+        severity,
+        event_type: params.get("event_type")
+            .map(|t| t.trim().to_uppercase())
+            .filter(|t| !t.is_empty()),
+        user_id,
+        from,
+        to,
+        severity_first: params.get("sort").is_some_and(|s| s == "severity"),
+        page,
         limit,
-        severity_filter,
-        user_filter
-    ).await {
-        Ok(activities) => {
-            // Group by severity untuk stats
-            let stats = serde_json::json!({
-                "total": activities.len(),
-                "info": activities.iter().filter(|a| matches!(a.severity, ActivitySeverity::Info)).count(),
-                "warning": activities.iter().filter(|a| matches!(a.severity, ActivitySeverity::Warning)).count(),
-                "critical": activities.iter().filter(|a| matches!(a.severity, ActivitySeverity::Critical)).count(),
-                "security": activities.iter().filter(|a| matches!(a.severity, ActivitySeverity::Security)).count(),
-            });
-            
-            Ok(Json(serde_json::json!({
-                "success": true,
-                "message": "Security activities retrieved",
-                "stats": stats,
-                "data": activities
-            })))
+    };
+
+    let user_repository = UserRepository::new(get_pepper().as_bytes());
+
+    match user_repository.get_security_activity_feed(&state.db, &filter).await {
+        Ok((activities, total, severity_counts)) => {
+            let meta = SecurityFeedMeta {
+                pagination: AdminPaginationMeta::new(page, limit, total),
+                severity_counts,
+                from,
+                to,
+            };
+
+            Ok(Json(ApiResponse::ok("Security activities retrieved", activities).with_meta(meta)))
         }
         Err(e) => {
             tracing::error!("Failed to get security activities: {}", e);
@@ -198,6 +215,17 @@ pub async fn get_security_activity_feed(
     }
 }
 
+/// Parse tanggal RFC3339 atau YYYY-MM-DD (awal hari; akhir hari jika `end_of_day`)
+fn parse_date_param(value: &str, end_of_day: bool) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    let date = if end_of_day { date.succ_opt()? } else { date };
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
 /// Handler untuk update status user (admin only)
 /// PUT /api/admin/users/:id/status
 pub async fn admin_update_user_status(
@@ -206,7 +234,7 @@ pub async fn admin_update_user_status(
     Extension(admin_user_id): Extension<Uuid>,
     Path(target_user_id): Path<Uuid>,
     Json(payload): Json<serde_json::Value>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<Json<ApiResponse<serde_json::Value>>, (StatusCode, Json<ErrorResponse>)> {
     let user_repository = UserRepository::new(get_pepper().as_bytes());
     
     // Verify admin access
@@ -250,6 +278,9 @@ pub async fn admin_update_user_status(
     
     match update_result {
         Ok(Some(updated_user)) => {
+            // Token user yang sudah terbit dicek ulang ke auth-service oleh gateway (status baru berlaku)
+            state.service_client.spawn_gateway_invalidation(target_user_id, None, state.clock.now());
+
             // Log admin action untuk audit
             let _ = log_security_event(
                 &state.db,
@@ -264,20 +295,18 @@ pub async fn admin_update_user_status(
                 true
             ).await;
             
-            Ok(Json(serde_json::json!({
-                "success": true,
-                "message": format!(
-                    "User {} berhasil {}",
-                    updated_user.email,
-                    if is_active { "diaktifkan" } else { "dinonaktifkan" }
-                ),
-                "data": {
-                    "id": updated_user.id,
-                    "email": updated_user.email,
-                    "full_name": updated_user.full_name,
-                    "is_active": updated_user.is_active
-                }
-            })))
+            let message = format!(
+                "User {} berhasil {}",
+                updated_user.email,
+                if is_active { "diaktifkan" } else { "dinonaktifkan" }
+            );
+
+            Ok(Json(ApiResponse::ok(message, serde_json::json!({
+                "id": updated_user.id,
+                "email": updated_user.email,
+                "full_name": updated_user.full_name,
+                "is_active": updated_user.is_active
+            }))))
         }
         Ok(None) => {
             Err((
@@ -295,6 +324,74 @@ pub async fn admin_update_user_status(
     }
 }
 
+/// Handler untuk membuka lockout akun (admin only)
+/// POST /api/admin/users/:id/unlock
+/// Idempotent: akun yang tidak terkunci tetap 200, counter percobaan gagal tetap di-reset
+pub async fn admin_unlock_user(
+    State(state): State<AppState>,
+    Extension(admin_user_id): Extension<Uuid>,
+    Path(target_user_id): Path<Uuid>,
+) -> Result<Json<ApiResponse<AccountLockState>>, (StatusCode, Json<ErrorResponse>)> {
+    let user_repository = UserRepository::new(get_pepper().as_bytes()).with_clock(state.clock.clone());
+
+    // Verify admin access
+    if let Err(e) = user_repository.check_user_access(&state.db, admin_user_id, Some("admin")).await {
+        return match e {
+            DatabaseError::AdminAccessDenied => Err((
+                StatusCode::FORBIDDEN,
+                Json(ErrorResponse::new("Admin access required", Some("ADMIN_ACCESS_DENIED")))
+            )),
+            DatabaseError::AccessDenied => Err((
+                StatusCode::FORBIDDEN,
+                Json(ErrorResponse::new("Account tidak aktif", Some("ACCOUNT_INACTIVE")))
+            )),
+            _ => Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new("Access check failed", Some("ACCESS_CHECK_ERROR")))
+            ))
+        };
+    }
+
+    let (before, after) = match user_repository.unlock_account(&state.db, target_user_id).await {
+        Ok(states) => states,
+        Err(DatabaseError::UserNotFound) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::new("User tidak ditemukan", Some("USER_NOT_FOUND")))
+            ));
+        }
+        Err(e) => {
+            tracing::error!("Failed to unlock user {}: {}", target_user_id, e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new("Gagal membuka lockout akun", Some("DATABASE_ERROR")))
+            ));
+        }
+    };
+
+    log_security_event(
+        &state.db,
+        Some(admin_user_id),
+        "ADMIN_UNLOCKED_ACCOUNT",
+        serde_json::json!({
+            "admin_id": admin_user_id,
+            "target_user_id": target_user_id,
+            "target_email": after.email,
+            "was_locked": before.locked,
+            "failed_attempts_cleared": before.failed_attempts
+        }),
+        true
+    ).await;
+
+    let message = if before.locked {
+        format!("Lockout akun {} berhasil dibuka", after.email)
+    } else {
+        format!("Akun {} tidak terkunci, counter percobaan gagal di-reset", after.email)
+    };
+
+    Ok(Json(ApiResponse::ok(message, after)))
+}
+
 // Helper function
 async fn log_security_event(
     pool: &sqlx::PgPool,
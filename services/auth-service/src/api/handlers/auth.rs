@@ -1,9 +1,9 @@
 // /pdf-bookstore/services/auth-service/src/api/handlers/auth.rs
 
 use axum::{
-    extract::{State, ConnectInfo},
+    extract::{State, ConnectInfo, Path},
     http::{StatusCode, HeaderMap},
-    response::Json,
+    response::{IntoResponse, Json, Response},
     Extension,
 };
 use serde_json::json;
@@ -15,9 +15,19 @@ use utoipa;
 
 use crate::{
     AppState,
+    core::{
+        jwt::effective_role,
+        session_cookie::{read_cookie, csrf_valid, ACCESS_COOKIE, REFRESH_COOKIE},
+        totp::{TotpConfig, base32_encode},
+    },
+    middleware::{auth::PatScopes, recent_auth::{require_recent_auth, AuthTime}},
     models::*,
-    db::{UserRepository, DatabaseError, SessionInfo},
-    utils::{hash_token, extract_device_info, contains_suspicious_patterns, get_pepper, EmailService}, 
+    db::{UserRepository, DatabaseError, SessionInfo, RefreshRotation, EMAIL_PURPOSE_PASSWORD_RESET, EMAIL_PURPOSE_LOGIN_OTP, EMAIL_PURPOSE_VERIFICATION},
+    utils::{
+        hash_token, extract_device_info, contains_suspicious_patterns, get_pepper, resolve_client_ip, EmailService,
+        otp_lifetime, otp_max_attempts, otp_resend_cooldown, otp_resend_limit, verification_token_lifetime, password_reset_lifetime,
+        trusted_device_lifetime, login_alerts_enabled,
+    },
 };
 
 /// Handler untuk registrasi user baru
@@ -39,6 +49,8 @@ pub async fn register_user(
     headers: HeaderMap,
     Json(request): Json<RegisterRequest>,
 ) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let client_ip = resolve_client_ip(addr.ip(), &headers);
+
     // Validasi input
     if let Err(errors) = request.validate() {
         return Err((
@@ -63,7 +75,7 @@ pub async fn register_user(
         ));
     }
 
-    let user_repository = UserRepository::new(get_pepper().as_bytes());
+    let user_repository = UserRepository::new(get_pepper().as_bytes()).with_clock(state.clock.clone());
 
     // Create user di database
     match user_repository.create_user(&state.db, request).await {
@@ -75,7 +87,7 @@ pub async fn register_user(
                 "USER_REGISTERED",
                 json!({
                     "email": user.email,
-                    "ip": addr.ip().to_string(),
+                    "ip": client_ip.to_string(),
                     "user_agent": extract_device_info(&headers)
                 }),
                 true
@@ -90,12 +102,13 @@ pub async fn register_user(
             sqlx::query!(
                 r#"
                 INSERT INTO email_verification_tokens (user_id, token_hash, expires_at)
-                VALUES ($1, $2, NOW() + INTERVAL '24 hours')
+                VALUES ($1, $2, $3)
                 ON CONFLICT (user_id) DO UPDATE
-                SET token_hash = $2, expires_at = NOW() + INTERVAL '24 hours'
+                SET token_hash = $2, expires_at = $3
                 "#,
                 user.id,
-                token_hash
+                token_hash,
+                Utc::now() + verification_token_lifetime()
             )
             .execute(&state.db)
             .await
@@ -126,6 +139,8 @@ pub async fn register_user(
                 session_id: None,
                 requires_verification: Some(true),
                 two_factor_required: None,
+                trusted_device_token: None,
+                scopes: None,
             }))
         }
         Err(DatabaseError::EmailExists) => {
@@ -159,6 +174,7 @@ pub async fn register_user(
     responses(
         (status = 200, description = "Login successful", body = AuthResponse),
         (status = 401, description = "Invalid credentials", body = ErrorResponse),
+        (status = 503, description = "OTP email could not be delivered", body = ErrorResponse),
     ),
     tag = "auth"
 )]
@@ -167,7 +183,9 @@ pub async fn login_user(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
     Json(request): Json<LoginRequest>,
-) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<(HeaderMap, Json<AuthResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let client_ip = resolve_client_ip(addr.ip(), &headers);
+
     // Validasi request
     if let Err(errors) = request.validate() {
         return Err((
@@ -176,10 +194,10 @@ pub async fn login_user(
         ));
     }
 
-    let user_repository = UserRepository::new(get_pepper().as_bytes());
+    let user_repository = UserRepository::new(get_pepper().as_bytes()).with_clock(state.clock.clone());
     
     // Get user dari database
-    let user = match user_repository.find_by_email(&state.db, &request.email, Some(addr.ip())).await {
+    let user = match user_repository.find_by_email(&state.db, &request.email, Some(client_ip)).await {
         Ok(user) => user,
         Err(DatabaseError::UserNotFound) => {
             log_security_event(
@@ -188,12 +206,13 @@ pub async fn login_user(
                 "LOGIN_FAILED",
                 json!({
                     "email": request.email,
-                    "ip": addr.ip().to_string(),
+                    "ip": client_ip.to_string(),
                     "reason": "User not found"
                 }),
                 false
             ).await;
             
+            alert_login_failure(&state, &client_ip.to_string());
             tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
             return Err((
                 StatusCode::UNAUTHORIZED,
@@ -237,7 +256,7 @@ pub async fn login_user(
         &state.db,
         user.id,
         &request.password,
-        Some(addr.ip()),
+        Some(client_ip),
     ).await {
         Ok(true) => {
             log_security_event(
@@ -245,7 +264,7 @@ pub async fn login_user(
                 Some(user.id),
                 "PASSWORD_VERIFIED",
                 json!({
-                    "ip": addr.ip().to_string(),
+                    "ip": client_ip.to_string(),
                     "user_agent": extract_device_info(&headers)
                 }),
                 true
@@ -254,55 +273,141 @@ pub async fn login_user(
             track_login_attempt(
                 &state.db,
                 Some(user.id),
-                Some(addr.ip()),
+                Some(client_ip),
                 extract_device_info(&headers),
                 request.device_fingerprint.clone(),
                 "password_success",
                 None
             ).await;
 
-            // ALWAYS SEND OTP (wajib)
-            let otp = format!("{:06}", rand::random::<u32>() % 1000000);
-            let otp_hash = hash_token(&otp);
-
-            // Store OTP di database
-            sqlx::query!(
-                r#"
-                INSERT INTO login_otps (user_id, otp_hash, expires_at)
-                VALUES ($1, $2, NOW() + INTERVAL '5 minutes')
-                ON CONFLICT (user_id) DO UPDATE
-                SET otp_hash = $2, expires_at = NOW() + INTERVAL '5 minutes'
-                "#,
+            // Trusted device: token tambahan dari verify-otp sebelumnya, login langsung tanpa OTP
+            let trusted = is_trusted_device(
+                &state.db,
+                &user_repository,
                 user.id,
-                otp_hash
-            )
-            .execute(&state.db)
-            .await
-            .map_err(|e| {
-                tracing::error!("Failed to store OTP: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ErrorResponse::new("Failed to generate OTP", Some("OTP_ERROR")))
+                request.device_fingerprint.as_deref(),
+                request.trusted_device_token.as_deref(),
+            ).await;
+
+            if trusted {
+                let (cookies, mut response) = issue_login_session(&state, &user_repository, user, LoginSessionContext {
+                    headers: &headers,
+                    client_ip,
+                    remember_me: request.remember_me.unwrap_or(false),
+                    device_fingerprint: request.device_fingerprint.clone(),
+                    use_cookies: request.use_cookies,
+                    event_type: "TRUSTED_DEVICE_LOGIN_SUCCESS",
+                    method: "trusted_device",
+                    new_device_alert: false,
+                }).await?;
+                response.two_factor_required = Some(false);
+
+                return Ok((cookies, Json(response)));
+            }
+
+            // ALWAYS SEND OTP (wajib), kecuali limit email per jam terlampaui.
+            // Password sudah terverifikasi, jadi pesan response boleh jujur soal email yang tidak dikirim
+            let otp_allowed = user_repository
+                .allow_sensitive_email(&state.db, &user.email, EMAIL_PURPOSE_LOGIN_OTP)
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::error!("Email rate limit check failed: {}", e);
+                    true
+                });
+
+            // User dengan TOTP tetap mendapat challenge login walau email tidak terkirim / kena limit,
+            // login_otps juga menandai bahwa password sudah terverifikasi untuk jalur TOTP
+            let totp_enabled = user_repository
+                .is_totp_enabled(&state.db, user.id)
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::error!("TOTP status check failed: {}", e);
+                    false
+                });
+
+            let mut otp_emailed = false;
+            if otp_allowed {
+                let otp = format!("{:06}", rand::random::<u32>() % 1000000);
+                let otp_hash = hash_token(&otp);
+
+                // Store OTP di database
+                sqlx::query!(
+                    r#"
+                    INSERT INTO login_otps (user_id, otp_hash, expires_at)
+                    VALUES ($1, $2, $3)
+                    ON CONFLICT (user_id) DO UPDATE
+                    SET otp_hash = $2, expires_at = $3, last_sent_at = NOW(), used_at = NULL, attempts = 0
+                    "#,
+                    user.id,
+                    otp_hash,
+                    state.clock.now() + otp_lifetime()
                 )
-            })?;
+                .execute(&state.db)
+                .await
+                .map_err(|e| {
+                    tracing::error!("Failed to store OTP: {}", e);
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(ErrorResponse::new("Failed to generate OTP", Some("OTP_ERROR")))
+                    )
+                })?;
 
-            // Send OTP via email
-            let user_email = user.email.clone();
-            match EmailService::new().await {
-                Ok(service) => {
-                    if let Err(e) = service.send_login_otp(&user_email, &otp).await {
-                        tracing::error!("Failed to send OTP: {}", e);
-                    } else {
-                        tracing::info!("OTP sent to {}", user_email);
+                // Send OTP via email, jangan klaim terkirim kalau gagal
+                match send_login_otp_with_retry(&user.email, &otp).await {
+                    Ok(()) => {
+                        otp_emailed = true;
+                        tracing::info!("OTP sent to {}", user.email);
+                    }
+                    Err(e) if totp_enabled => {
+                        // Kode dari authenticator app tetap bisa dipakai, challenge login dipertahankan
+                        tracing::warn!("Failed to deliver OTP to TOTP user {}: {}", user.id, e);
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to deliver OTP to user {}: {}", user.id, e);
+
+                        // OTP yang tidak pernah terkirim tidak perlu disimpan
+                        if let Err(e) = sqlx::query!("DELETE FROM login_otps WHERE user_id = $1", user.id)
+                            .execute(&state.db)
+                            .await
+                        {
+                            tracing::warn!("Failed to remove undelivered OTP: {}", e);
+                        }
+
+                        log_security_event(
+                            &state.db,
+                            Some(user.id),
+                            "OTP_DELIVERY_FAILED",
+                            json!({"ip": client_ip.to_string()}),
+                            false
+                        ).await;
+
+                        return Err((
+                            StatusCode::SERVICE_UNAVAILABLE,
+                            Json(ErrorResponse::new(
+                                "Kode OTP gagal dikirim. Silakan coba login kembali beberapa saat lagi.",
+                                Some("OTP_DELIVERY_FAILED")
+                            ))
+                        ));
                     }
                 }
-                Err(e) => tracing::error!("Email service failed: {}", e)
+            } else if totp_enabled {
+                tracing::warn!("Login OTP email suppressed for TOTP user {} (rate limit)", user.id);
+                reuse_totp_login_challenge(&state, user.id).await?;
+            } else {
+                tracing::warn!("Login OTP email suppressed for user {} (rate limit)", user.id);
             }
 
+            let message = match (otp_emailed, totp_enabled) {
+                (true, true) => "Masukkan kode OTP dari email atau dari aplikasi authenticator Anda.",
+                (true, false) => "Kode OTP telah dikirim ke email Anda. Silakan cek inbox/spam.",
+                (false, true) => "Kode OTP email tidak dikirim kali ini. Masukkan kode dari aplikasi authenticator Anda.",
+                (false, false) => "Batas pengiriman kode OTP tercapai. Gunakan kode OTP terakhir yang dikirim ke email Anda atau coba login kembali nanti.",
+            };
+
             // Return OTP response - NO TOKEN
-            Ok(Json(AuthResponse {
+            Ok((HeaderMap::new(), Json(AuthResponse {
                 success: true,
-                message: "Kode OTP telah dikirim ke email Anda. Silakan cek inbox/spam.".to_string(),
+                message: message.to_string(),
                 user: None,
                 token: None,
                 refresh_token: None,
@@ -310,7 +415,9 @@ pub async fn login_user(
                 session_id: None,
                 requires_verification: None,
                 two_factor_required: Some(true),
-            }))
+                trusted_device_token: None,
+                scopes: None,
+            })))
         }
         Ok(false) => {
             log_security_event(
@@ -318,16 +425,18 @@ pub async fn login_user(
                 Some(user.id),
                 "LOGIN_FAILED",
                 json!({
-                    "ip": addr.ip().to_string(),
+                    "ip": client_ip.to_string(),
                     "reason": "Invalid password"
                 }),
                 false
             ).await;
             
+            alert_login_failure(&state, &client_ip.to_string());
+
             track_login_attempt(
                 &state.db,
                 Some(user.id),
-                Some(addr.ip()),
+                Some(client_ip),
                 extract_device_info(&headers),
                 request.device_fingerprint.clone(),
                 "failed",
@@ -351,24 +460,68 @@ pub async fn login_user(
     }
 }
 
+/// Challenge login TOTP tanpa email (limit email tercapai). Challenge lama yang masih berlaku dipakai
+/// apa adanya, dan jumlah percobaan hanya di-reset setelah challenge sebelumnya berhasil dipakai,
+/// sehingga login berulang tidak menambah jatah tebakan kode authenticator
+async fn reuse_totp_login_challenge(
+    state: &AppState,
+    user_id: Uuid,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let now = state.clock.now();
+
+    // Kode email acak yang tidak pernah dikirim, challenge ini hanya bisa dijawab dengan TOTP
+    let unsent_otp = format!("{:06}", rand::random::<u32>() % 1000000);
+
+    sqlx::query!(
+        r#"
+        INSERT INTO login_otps (user_id, otp_hash, expires_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (user_id) DO UPDATE
+        SET otp_hash = $2, expires_at = $3, used_at = NULL,
+            attempts = CASE WHEN login_otps.used_at IS NULL THEN login_otps.attempts ELSE 0 END
+        WHERE login_otps.used_at IS NOT NULL OR login_otps.expires_at <= $4
+        "#,
+        user_id,
+        hash_token(&unsent_otp),
+        now + otp_lifetime(),
+        now
+    )
+    .execute(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to store TOTP login challenge: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Failed to generate OTP", Some("OTP_ERROR")))
+        )
+    })?;
+
+    Ok(())
+}
+
 /// Handler untuk verifikasi OTP login
 /// POST /api/auth/verify-otp
 pub async fn verify_otp(
     State(state): State<AppState>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,  // <- ADD THIS
-    headers: HeaderMap,  // <- ADD THIS
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(request): Json<VerifyOtpRequest>,
-) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<(HeaderMap, Json<AuthResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let client_ip = resolve_client_ip(addr.ip(), &headers);
+
     let otp_hash = hash_token(&request.otp);
+    let user_repository = UserRepository::new(get_pepper().as_bytes()).with_clock(state.clock.clone());
     
-    // Get user and validate OTP
+    // Get user and validate OTP, user dengan TOTP aktif boleh memakai kode authenticator
+    // untuk challenge login yang sama (challenge hanya dibuat setelah password terverifikasi)
     let result = sqlx::query!(
         r#"
-        SELECT o.user_id, o.expires_at, o.used_at, 
-               u.id, u.email, u.full_name, u.role, u.email_verified
+        SELECT o.user_id, o.otp_hash, o.expires_at, o.used_at,
+               o.otp_hash = $2 AS "otp_matched!",
+               u.totp_enabled
         FROM login_otps o
         JOIN users u ON u.id = o.user_id
-        WHERE u.email = $1 AND o.otp_hash = $2
+        WHERE u.email = $1
         "#,
         request.email.to_lowercase(),
         otp_hash
@@ -383,32 +536,96 @@ pub async fn verify_otp(
         )
     })?;
     
-    let otp_data = result.ok_or((
+    let invalid_otp = || (
         StatusCode::UNAUTHORIZED,
         Json(ErrorResponse::new("Invalid OTP", Some("INVALID_OTP")))
-    ))?;
-    
-    // Check expiry
-    if otp_data.expires_at < Utc::now() {
+    );
+
+    let otp_data = result.ok_or_else(invalid_otp)?;
+
+    // Setiap percobaan dihitung sebelum kode dicocokkan, sehingga tebakan paralel juga kena batas.
+    // Challenge yang habis percobaannya harus diganti lewat login ulang / resend
+    let max_attempts = otp_max_attempts();
+    let attempt = sqlx::query_scalar!(
+        "UPDATE login_otps SET attempts = attempts + 1 WHERE user_id = $1 AND attempts < $2 RETURNING attempts",
+        otp_data.user_id,
+        max_attempts
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to count OTP attempt: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Database error", Some("DB_ERROR")))
+        )
+    })?;
+
+    if attempt.is_none() {
+        log_security_event(
+            &state.db,
+            Some(otp_data.user_id),
+            "OTP_ATTEMPTS_EXCEEDED",
+            json!({"ip": client_ip.to_string(), "max_attempts": max_attempts}),
+            false
+        ).await;
+
         return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse::new("OTP expired", Some("OTP_EXPIRED")))
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ErrorResponse::new(
+                "Terlalu banyak percobaan kode OTP. Silakan login ulang atau minta kode baru.",
+                Some("OTP_ATTEMPTS_EXCEEDED")
+            ))
         ));
     }
+
+    // Bukan OTP email, coba sebagai kode TOTP
+    let totp_step = if otp_data.otp_matched {
+        None
+    } else if otp_data.totp_enabled {
+        Some(match_login_totp(&state, &user_repository, otp_data.user_id, &request.otp).await.ok_or_else(invalid_otp)?)
+    } else {
+        return Err(invalid_otp());
+    };
     
-    // Check if used
-    if otp_data.used_at.is_some() {
-        return Err((
+    check_otp_challenge(otp_data.expires_at, otp_data.used_at, state.clock.now())
+        .map_err(|(message, code)| (
             StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse::new("OTP already used", Some("OTP_USED")))
-        ));
+            Json(ErrorResponse::new(message, Some(code)))
+        ))?;
+    
+    // Kode TOTP hanya boleh dipakai sekali dalam window yang sama
+    if let Some(step) = totp_step {
+        let fresh = user_repository.consume_totp_step(&state.db, otp_data.user_id, step).await
+            .map_err(|e| {
+                tracing::error!("Failed to consume TOTP step: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ErrorResponse::new("Database error", Some("DB_ERROR")))
+                )
+            })?;
+
+        if !fresh {
+            log_security_event(
+                &state.db,
+                Some(otp_data.user_id),
+                "TOTP_REPLAY_BLOCKED",
+                json!({"ip": client_ip.to_string()}),
+                false
+            ).await;
+
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorResponse::new("Kode authenticator sudah dipakai, tunggu kode berikutnya", Some("TOTP_CODE_USED")))
+            ));
+        }
     }
     
     // Mark as used
     sqlx::query!(
         "UPDATE login_otps SET used_at = NOW() WHERE user_id = $1 AND otp_hash = $2",
         otp_data.user_id,
-        otp_hash
+        otp_data.otp_hash
     )
     .execute(&state.db)
     .await
@@ -421,103 +638,180 @@ pub async fn verify_otp(
     })?;
     
     // Get full user
-    let user_repository = UserRepository::new(get_pepper().as_bytes());
     let user = user_repository.find_by_id(&state.db, otp_data.user_id).await
         .map_err(|_| (
             StatusCode::NOT_FOUND,
             Json(ErrorResponse::new("User not found", Some("USER_NOT_FOUND")))
         ))?;
-    
-    // Check remember_me dari request
-    let remember_me = request.remember_me.unwrap_or(false);
-    
-    // Generate tokens dengan durasi berdasarkan remember_me
-    let (token_pair, token_expiry) = if remember_me {
-        let custom_token = state.jwt_service
-            .generate_token_with_duration(&user, Duration::days(30))
-            .map_err(|_| (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse::new("Token generation failed", Some("TOKEN_ERROR")))
-            ))?;
 
+    let trusted_device_token = if request.trust_device.unwrap_or(false) {
+        trust_device(&state.db, &user_repository, user.id, request.device_fingerprint.as_deref(), &headers).await
+    } else {
+        None
+    };
+
+    let (cookies, mut response) = issue_login_session(&state, &user_repository, user, LoginSessionContext {
+        headers: &headers,
+        client_ip,
+        remember_me: request.remember_me.unwrap_or(false),
+        device_fingerprint: request.device_fingerprint.clone(),
+        use_cookies: request.use_cookies,
+        event_type: "OTP_LOGIN_SUCCESS",
+        method: if totp_step.is_some() { "totp" } else { "email_otp" },
+        new_device_alert: true,
+    }).await?;
+    response.trusted_device_token = trusted_device_token;
+
+    Ok((cookies, Json(response)))
+}
+
+/// Handler untuk kirim ulang OTP login yang masih pending (email terlambat / tidak sampai)
+/// POST /api/auth/otp/resend
+#[utoipa::path(
+    post,
+    path = "/api/auth/otp/resend",
+    request_body = ResendOtpRequest,
+    responses(
+        (status = 200, description = "OTP resent"),
+        (status = 400, description = "No pending OTP", body = ErrorResponse),
+        (status = 429, description = "Cooldown active or resend limit reached", body = ErrorResponse),
+        (status = 503, description = "OTP email could not be delivered", body = ErrorResponse),
+    ),
+    tag = "auth"
+)]
+pub async fn resend_login_otp(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(request): Json<ResendOtpRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let client_ip = resolve_client_ip(addr.ip(), &headers);
+
+    if let Err(errors) = request.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::validation_error(errors))
+        ));
+    }
+
+    let db_error = |e: sqlx::Error| {
+        tracing::error!("OTP resend DB error: {}", e);
         (
-            TokenPairResponse {
-                access_token: custom_token.clone(),
-                refresh_token: format!("refresh_{}", custom_token),
-                expires_in: 30 * 24 * 3600,
-                refresh_expires_in: 30 * 24 * 3600,
-            },
-            Duration::days(30)
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Database error", Some("DB_ERROR")))
         )
-    } else {
-        let token_pair = state.jwt_service.generate_token_pair(&user)
-            .map_err(|_| (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse::new("Token generation failed", Some("TOKEN_ERROR")))
-            ))?;
-        (token_pair, Duration::days(7))
     };
-    
-    // STORE REFRESH TOKEN IN DATABASE
-    let token_hash = hash_token(&token_pair.refresh_token);
-    let expires_at = Utc::now() + token_expiry;
-    let device_fingerprint = request.device_fingerprint.clone()
-        .or_else(|| extract_device_info(&headers))
-        .unwrap_or_default();
-    
-    if let Err(e) = sqlx::query!(
+
+    let now = state.clock.now();
+    let pending = sqlx::query!(
         r#"
-        INSERT INTO refresh_tokens (user_id, token_hash, device_fingerprint, expires_at)
-        VALUES ($1, $2, $3, $4)
-        ON CONFLICT (token_hash) DO NOTHING
+        SELECT o.user_id, o.last_sent_at, o.resend_count, o.resend_window_started_at, u.email
+        FROM login_otps o
+        JOIN users u ON u.id = o.user_id
+        WHERE u.email = $1 AND o.used_at IS NULL AND o.expires_at > $2
         "#,
-        user.id,
-        token_hash,
-        Some(device_fingerprint.clone()),
-        expires_at
+        request.email.to_lowercase(),
+        now
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(db_error)?
+    .ok_or((
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new("Tidak ada OTP aktif, silakan login kembali", Some("NO_PENDING_OTP")))
+    ))?;
+
+    // Cooldown antar pengiriman
+    let cooldown_left = (pending.last_sent_at + otp_resend_cooldown() - now).num_seconds();
+    if cooldown_left > 0 {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ErrorResponse::retry_later("Tunggu sebelum meminta kode OTP baru", "OTP_RESEND_COOLDOWN", cooldown_left))
+        ));
+    }
+
+    // Batas resend per window, window baru dimulai saat window lama sudah lewat
+    let (max_resends, window) = otp_resend_limit();
+    let (window_started, resend_count) = match pending.resend_window_started_at {
+        Some(started) if started + window > now => (started, pending.resend_count),
+        _ => (now, 0),
+    };
+    if resend_count >= max_resends {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ErrorResponse::retry_later(
+                "Batas kirim ulang OTP tercapai, coba lagi nanti",
+                "OTP_RESEND_LIMIT",
+                (window_started + window - now).num_seconds(),
+            ))
+        ));
+    }
+
+    let otp = format!("{:06}", rand::random::<u32>() % 1000000);
+
+    // Kondisi last_sent_at mencegah dua resend paralel lolos cooldown bersamaan
+    let updated = sqlx::query!(
+        r#"
+        UPDATE login_otps
+        SET otp_hash = $3, expires_at = $4, last_sent_at = $5,
+            resend_count = $6, resend_window_started_at = $7, attempts = 0
+        WHERE user_id = $1 AND last_sent_at = $2 AND used_at IS NULL
+        "#,
+        pending.user_id,
+        pending.last_sent_at,
+        hash_token(&otp),
+        now + otp_lifetime(),
+        now,
+        resend_count + 1,
+        window_started
     )
     .execute(&state.db)
-    .await {
-        tracing::warn!("Failed to store refresh token: {}", e);
+    .await
+    .map_err(db_error)?;
+
+    if updated.rows_affected() != 1 {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ErrorResponse::retry_later(
+                "Tunggu sebelum meminta kode OTP baru",
+                "OTP_RESEND_COOLDOWN",
+                otp_resend_cooldown().num_seconds(),
+            ))
+        ));
     }
-    
-    // Create session
-    let session_info = SessionInfo {
-        device_info: Some(device_fingerprint),
-        ip_address: Some(addr.ip()),
+
+    let sent = match EmailService::new().await {
+        Ok(service) => service.send_login_otp(&pending.email, &otp).await,
+        Err(e) => Err(e),
     };
-    
-    let session_token = user_repository.update_last_login_with_session(
-        &state.db,
-        user.id,
-        session_info,
-    ).await.unwrap_or_else(|_| Uuid::new_v4().to_string());
-    
-    // Log successful login
+    if let Err(e) = sent {
+        tracing::error!("Failed to resend OTP to user {}: {}", pending.user_id, e);
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse::new(
+                "Kode OTP gagal dikirim. Silakan coba lagi beberapa saat lagi.",
+                Some("OTP_DELIVERY_FAILED")
+            ))
+        ));
+    }
+
     log_security_event(
         &state.db,
-        Some(user.id),
-        "OTP_LOGIN_SUCCESS",
+        Some(pending.user_id),
+        "OTP_RESENT",
         json!({
-            "ip": addr.ip().to_string(),
-            "user_agent": extract_device_info(&headers)
+            "ip": client_ip.to_string(),
+            "resend_count": resend_count + 1
         }),
         true
     ).await;
-    
-    let user_profile = UserProfile::from(user);
-    
-    Ok(Json(AuthResponse {
-        success: true,
-        message: "Login successful".to_string(),
-        user: Some(user_profile),
-        token: Some(token_pair.access_token),
-        refresh_token: Some(token_pair.refresh_token),
-        expires_in: Some(token_pair.expires_in),
-        session_id: Some(session_token),
-        requires_verification: Some(!otp_data.email_verified.unwrap_or(false)),
-        two_factor_required: None,
-    }))
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Kode OTP baru telah dikirim ke email Anda",
+        "retry_after": otp_resend_cooldown().num_seconds(),
+        "resends_remaining": max_resends - resend_count - 1
+    })))
 }
 
 /// Handler untuk verifikasi JWT token
@@ -525,16 +819,18 @@ pub async fn verify_otp(
 pub async fn verify_token(
     State(state): State<AppState>,
     Extension(user_id): Extension<Uuid>,
+    Extension(PatScopes(scopes)): Extension<PatScopes>,
 ) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let user_repository = UserRepository::new(get_pepper().as_bytes());
+    let user_repository = UserRepository::new(get_pepper().as_bytes()).with_clock(state.clock.clone());
     
     match user_repository.find_by_id(&state.db, user_id).await {
         Ok(user) => {
-            let user_profile = UserProfile::from(user);
-            Ok(Json(AuthResponse::with_user(
-                user_profile,
-                "Token valid"
-            )))
+            // PAT: role diturunkan jika tanpa admin:access, scope diteruskan supaya service lain ikut membatasi
+            let mut user_profile = UserProfile::from(user);
+            user_profile.role = effective_role(&user_profile.role, scopes.as_deref());
+            let mut response = AuthResponse::with_user(user_profile, "Token valid");
+            response.scopes = scopes;
+            Ok(Json(response))
         }
         Err(DatabaseError::UserNotFound) => {
             Ok(Json(AuthResponse::error("User tidak ditemukan atau tidak aktif")))
@@ -549,32 +845,487 @@ pub async fn verify_token(
     }
 }
 
-// [Lanjutkan dengan fungsi lainnya: refresh_access_token, logout, validate_session, dll]
+/// Handler untuk re-autentikasi (step-up) sebelum aksi sensitif yang menolak REAUTH_REQUIRED
+/// POST /api/auth/reauthenticate, return access token baru dengan auth_time sekarang
+pub async fn reauthenticate(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(request): Json<ReauthenticateRequest>,
+) -> Result<(HeaderMap, Json<AuthResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let client_ip = resolve_client_ip(addr.ip(), &headers);
+    let user_repository = UserRepository::new(get_pepper().as_bytes()).with_clock(state.clock.clone());
 
-/// Helper untuk track login attempts
-async fn track_login_attempt(
-    pool: &sqlx::PgPool,
-    user_id: Option<Uuid>,
-    ip: Option<std::net::IpAddr>,
-    user_agent: Option<String>,
-    device_fingerprint: Option<String>,
-    status: &str,
-    failure_reason: Option<&str>,
-) {
-    if let Some(uid) = user_id {
-        let ip_str = ip.map(|i| i.to_string());
-        
-        let result = sqlx::query(
-            r#"
-            INSERT INTO login_history (
-                user_id,
-                ip_address,
-                user_agent,
-                device_fingerprint,
-                login_status,
-                failure_reason
-            )
-            VALUES ($1, $2::inet, $3, $4, $5, $6)
+    let user = user_repository.find_by_id(&state.db, user_id).await
+        .map_err(|_| (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new("User tidak ditemukan", Some("USER_NOT_FOUND")))
+        ))?;
+
+    // Percobaan gagal ikut dihitung ke lockout yang sama dengan login
+    match user_repository.verify_password_with_attempts(&state.db, user.id, &request.password, Some(client_ip)).await {
+        Ok(true) => {}
+        Ok(false) => {
+            log_security_event(
+                &state.db,
+                Some(user.id),
+                "REAUTH_FAILED",
+                json!({"ip": client_ip.to_string()}),
+                false
+            ).await;
+
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorResponse::new("Password salah", Some("INVALID_CREDENTIALS")))
+            ));
+        }
+        Err(DatabaseError::AccountLocked) => {
+            return Err((
+                StatusCode::LOCKED,
+                Json(ErrorResponse::new("Akun sementara terkunci", Some("ACCOUNT_LOCKED")))
+            ));
+        }
+        Err(e) => {
+            tracing::error!("Re-authentication error: {}", e);
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new("Autentikasi gagal", Some("AUTH_ERROR")))
+            ));
+        }
+    }
+
+    // Hanya access token yang diganti, refresh token lama tetap dipakai
+    let token_pair = state.jwt_service.generate_token_pair(&user, None)
+        .map_err(|_| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Token generation failed", Some("TOKEN_ERROR")))
+        ))?;
+
+    log_security_event(
+        &state.db,
+        Some(user.id),
+        "REAUTH_SUCCESS",
+        json!({"ip": client_ip.to_string()}),
+        true
+    ).await;
+
+    let (cookies, token) = if state.cookie_session.wants_cookies(request.use_cookies, &headers) {
+        (state.cookie_session.access_headers(&token_pair.access_token, token_pair.expires_in), None)
+    } else {
+        (HeaderMap::new(), Some(token_pair.access_token))
+    };
+
+    let mut response = AuthResponse::success("Re-autentikasi berhasil");
+    response.token = token;
+    response.expires_in = Some(token_pair.expires_in);
+
+    Ok((cookies, Json(response)))
+}
+
+/// Handler untuk mulai enrollment TOTP (authenticator app), butuh autentikasi baru (step-up)
+/// POST /api/auth/2fa/totp/enroll
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Extension(auth_time): Extension<AuthTime>,
+) -> Result<Json<ApiResponse<TotpEnrollment>>, (StatusCode, Json<ErrorResponse>)> {
+    require_recent_auth(auth_time, state.clock.now())?;
+
+    let user_repository = UserRepository::new(get_pepper().as_bytes()).with_clock(state.clock.clone());
+    let user = user_repository.find_by_id(&state.db, user_id).await
+        .map_err(|_| (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new("User tidak ditemukan", Some("USER_NOT_FOUND")))
+        ))?;
+
+    let db_error = |e: DatabaseError| {
+        tracing::error!("TOTP enrollment DB error: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Gagal memulai enrollment TOTP", Some("TOTP_ERROR")))
+        )
+    };
+
+    let secret = TotpConfig::generate_secret();
+    let stored = user_repository
+        .store_pending_totp_secret(&state.db, user_id, &state.totp.encrypt_secret(&secret))
+        .await
+        .map_err(db_error)?;
+
+    if !stored {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse::new("Authenticator app sudah aktif untuk akun ini", Some("TOTP_ALREADY_ENABLED")))
+        ));
+    }
+
+    let otpauth_uri = state.totp.provisioning_uri(&secret, &user.email);
+
+    Ok(Json(ApiResponse::ok(
+        "Scan QR code dengan aplikasi authenticator lalu konfirmasi dengan kode yang muncul",
+        TotpEnrollment {
+            secret: base32_encode(&secret),
+            qr_payload: otpauth_uri.clone(),
+            otpauth_uri,
+        },
+    )))
+}
+
+/// Handler untuk konfirmasi enrollment TOTP dengan kode pertama dari authenticator app
+/// POST /api/auth/2fa/totp/verify
+pub async fn verify_totp_enrollment(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<TotpVerifyRequest>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, (StatusCode, Json<ErrorResponse>)> {
+    if let Err(errors) = request.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::validation_error(errors))
+        ));
+    }
+
+    let user_repository = UserRepository::new(get_pepper().as_bytes()).with_clock(state.clock.clone());
+    let db_error = |e: DatabaseError| {
+        tracing::error!("TOTP verification DB error: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Gagal verifikasi TOTP", Some("TOTP_ERROR")))
+        )
+    };
+
+    let record = user_repository.get_totp_secret(&state.db, user_id).await
+        .map_err(db_error)?
+        .ok_or((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("Enrollment TOTP belum dimulai", Some("TOTP_NOT_ENROLLED")))
+        ))?;
+
+    if record.confirmed {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse::new("Authenticator app sudah aktif untuk akun ini", Some("TOTP_ALREADY_ENABLED")))
+        ));
+    }
+
+    let step = state.totp.decrypt_secret(&record.secret_encrypted)
+        .and_then(|secret| state.totp.verify(&secret, &request.code, state.clock.now().timestamp()))
+        .ok_or((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("Kode authenticator tidak valid", Some("INVALID_TOTP_CODE")))
+        ))?;
+
+    if !user_repository.confirm_totp(&state.db, user_id, step).await.map_err(db_error)? {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new("Kode authenticator sudah dipakai, tunggu kode berikutnya", Some("TOTP_CODE_USED")))
+        ));
+    }
+
+    log_security_event(
+        &state.db,
+        Some(user_id),
+        "TOTP_ENABLED",
+        json!({"user_agent": extract_device_info(&headers)}),
+        true
+    ).await;
+
+    Ok(Json(ApiResponse::ok(
+        "Authenticator app berhasil diaktifkan",
+        json!({"totp_enabled": true}),
+    )))
+}
+
+/// Konteks login yang sudah lolos verifikasi (OTP / TOTP / trusted device)
+struct LoginSessionContext<'a> {
+    headers: &'a HeaderMap,
+    client_ip: std::net::IpAddr,
+    remember_me: bool,
+    device_fingerprint: Option<String>,
+    use_cookies: Option<bool>,
+    event_type: &'static str,
+    method: &'static str,
+    /// Kirim email peringatan jika device_fingerprint belum pernah menyelesaikan login
+    new_device_alert: bool,
+}
+
+/// Terbitkan token pair + refresh token + session setelah login terverifikasi
+async fn issue_login_session(
+    state: &AppState,
+    user_repository: &UserRepository,
+    user: User,
+    ctx: LoginSessionContext<'_>,
+) -> Result<(HeaderMap, AuthResponse), (StatusCode, Json<ErrorResponse>)> {
+    // Generate tokens dengan durasi berdasarkan remember_me
+    let (token_pair, token_expiry) = if ctx.remember_me {
+        let custom_token = state.jwt_service
+            .generate_token_with_duration(&user, Duration::days(30), None)
+            .map_err(|_| (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new("Token generation failed", Some("TOKEN_ERROR")))
+            ))?;
+
+        (
+            TokenPairResponse {
+                access_token: custom_token.clone(),
+                refresh_token: format!("refresh_{}", custom_token),
+                expires_in: 30 * 24 * 3600,
+                refresh_expires_in: 30 * 24 * 3600,
+            },
+            Duration::days(30)
+        )
+    } else {
+        let token_pair = state.jwt_service.generate_token_pair(&user, None)
+            .map_err(|_| (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new("Token generation failed", Some("TOKEN_ERROR")))
+            ))?;
+        (token_pair, Duration::days(7))
+    };
+    
+    // Device baru dicek sebelum login ini tercatat di login_history
+    let new_device_fingerprint = match ctx.device_fingerprint.as_deref().map(str::trim).filter(|f| !f.is_empty()) {
+        Some(fingerprint) if ctx.new_device_alert && login_alerts_enabled() => {
+            match user_repository.is_known_login_device(&state.db, user.id, fingerprint).await {
+                Ok(known) => (!known).then(|| fingerprint.to_string()),
+                Err(e) => {
+                    tracing::warn!("Failed to check login device for user {}: {}", user.id, e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    track_login_attempt(
+        &state.db,
+        Some(user.id),
+        Some(ctx.client_ip),
+        extract_device_info(ctx.headers),
+        ctx.device_fingerprint.clone(),
+        "success",
+        None
+    ).await;
+
+    // STORE REFRESH TOKEN IN DATABASE
+    let token_hash = hash_token(&token_pair.refresh_token);
+    let expires_at = state.clock.now() + token_expiry;
+    let device_fingerprint = ctx.device_fingerprint
+        .or_else(|| extract_device_info(ctx.headers))
+        .unwrap_or_default();
+    
+    if let Err(e) = sqlx::query!(
+        r#"
+        INSERT INTO refresh_tokens (user_id, token_hash, device_fingerprint, expires_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (token_hash) DO NOTHING
+        "#,
+        user.id,
+        token_hash,
+        Some(device_fingerprint.clone()),
+        expires_at
+    )
+    .execute(&state.db)
+    .await {
+        tracing::warn!("Failed to store refresh token: {}", e);
+    }
+    
+    // Create session
+    let session_info = SessionInfo {
+        device_info: Some(device_fingerprint),
+        ip_address: Some(ctx.client_ip),
+    };
+    
+    let session_token = user_repository.update_last_login_with_session(
+        &state.db,
+        user.id,
+        session_info,
+    ).await.unwrap_or_else(|_| Uuid::new_v4().to_string());
+    
+    // Log successful login
+    log_security_event(
+        &state.db,
+        Some(user.id),
+        ctx.event_type,
+        json!({
+            "ip": ctx.client_ip.to_string(),
+            "user_agent": extract_device_info(ctx.headers),
+            "method": ctx.method
+        }),
+        true
+    ).await;
+
+    // Geo-velocity (opsional) di background supaya login tidak tertunda
+    spawn_geo_velocity_check(state, user.id, user.email.clone(), ctx.client_ip);
+
+    if let Some(fingerprint) = new_device_fingerprint {
+        spawn_new_device_alert(state, user.id, user.email.clone(), ctx.client_ip, fingerprint, extract_device_info(ctx.headers));
+    }
+    
+    let requires_verification = !user.email_verified;
+    let user_profile = UserProfile::from(user);
+
+    // Mode cookie: token hanya dikirim lewat cookie HttpOnly, tidak di body
+    let (cookies, token, refresh_token) = if state.cookie_session.wants_cookies(ctx.use_cookies, ctx.headers) {
+        let cookies = state.cookie_session.session_headers(
+            &token_pair.access_token,
+            token_pair.expires_in,
+            &token_pair.refresh_token,
+            token_pair.refresh_expires_in,
+        );
+        (cookies, None, None)
+    } else {
+        (HeaderMap::new(), Some(token_pair.access_token), Some(token_pair.refresh_token))
+    };
+    
+    Ok((cookies, AuthResponse {
+        success: true,
+        message: "Login successful".to_string(),
+        user: Some(user_profile),
+        token,
+        refresh_token,
+        expires_in: Some(token_pair.expires_in),
+        session_id: Some(session_token),
+        requires_verification: Some(requires_verification),
+        two_factor_required: None,
+        trusted_device_token: None,
+        scopes: None,
+    }))
+}
+
+/// Challenge OTP login masih bisa dipakai: belum expired (menurut clock) dan belum dipakai
+/// Err berisi (message, error_code)
+fn check_otp_challenge(
+    expires_at: chrono::DateTime<Utc>,
+    used_at: Option<chrono::DateTime<Utc>>,
+    now: chrono::DateTime<Utc>,
+) -> Result<(), (&'static str, &'static str)> {
+    if expires_at < now {
+        return Err(("OTP expired", "OTP_EXPIRED"));
+    }
+
+    if used_at.is_some() {
+        return Err(("OTP already used", "OTP_USED"));
+    }
+
+    Ok(())
+}
+
+/// Simpan device sebagai trusted device, return token plaintext (hanya dikirim sekali ke client)
+async fn trust_device(
+    db: &sqlx::PgPool,
+    user_repository: &UserRepository,
+    user_id: Uuid,
+    device_fingerprint: Option<&str>,
+    headers: &HeaderMap,
+) -> Option<String> {
+    let Some(fingerprint) = device_fingerprint.map(str::trim).filter(|f| !f.is_empty()) else {
+        tracing::warn!("Trust device requested without device_fingerprint (user {})", user_id);
+        return None;
+    };
+
+    let token = hex::encode(rand::random::<[u8; 32]>());
+    let result = user_repository.add_trusted_device(
+        db,
+        user_id,
+        fingerprint,
+        &hash_token(&token),
+        extract_device_info(headers),
+        trusted_device_lifetime(),
+    ).await;
+
+    match result {
+        Ok(()) => {
+            log_security_event(
+                db,
+                Some(user_id),
+                "TRUSTED_DEVICE_ADDED",
+                json!({"device_fingerprint": fingerprint}),
+                true
+            ).await;
+            Some(token)
+        }
+        Err(e) => {
+            tracing::warn!("Failed to store trusted device for user {}: {}", user_id, e);
+            None
+        }
+    }
+}
+
+/// Login tanpa OTP hanya jika token, device_fingerprint, dan user cocok dengan trusted device yang masih berlaku
+async fn is_trusted_device(
+    db: &sqlx::PgPool,
+    user_repository: &UserRepository,
+    user_id: Uuid,
+    device_fingerprint: Option<&str>,
+    token: Option<&str>,
+) -> bool {
+    let (Some(fingerprint), Some(token)) = (device_fingerprint, token) else {
+        return false;
+    };
+
+    user_repository
+        .use_trusted_device(db, user_id, fingerprint.trim(), &hash_token(token))
+        .await
+        .unwrap_or_else(|e| {
+            tracing::error!("Trusted device check failed: {}", e);
+            false
+        })
+}
+
+/// Cocokkan kode login dengan TOTP user, return step yang cocok
+/// None jika TOTP belum dikonfirmasi, secret tidak bisa didekripsi, atau kode salah
+async fn match_login_totp(
+    state: &AppState,
+    user_repository: &UserRepository,
+    user_id: Uuid,
+    code: &str,
+) -> Option<i64> {
+    let record = match user_repository.get_totp_secret(&state.db, user_id).await {
+        Ok(Some(record)) if record.confirmed => record,
+        Ok(_) => return None,
+        Err(e) => {
+            tracing::error!("Failed to load TOTP secret for user {}: {}", user_id, e);
+            return None;
+        }
+    };
+
+    let Some(secret) = state.totp.decrypt_secret(&record.secret_encrypted) else {
+        tracing::error!("TOTP secret user {} tidak bisa didekripsi (TOTP_ENCRYPTION_KEY berubah?)", user_id);
+        return None;
+    };
+
+    state.totp.verify(&secret, code, state.clock.now().timestamp())
+}
+
+// [Lanjutkan dengan fungsi lainnya: refresh_access_token, logout, validate_session, dll]
+
+/// Helper untuk track login attempts
+async fn track_login_attempt(
+    pool: &sqlx::PgPool,
+    user_id: Option<Uuid>,
+    ip: Option<std::net::IpAddr>,
+    user_agent: Option<String>,
+    device_fingerprint: Option<String>,
+    status: &str,
+    failure_reason: Option<&str>,
+) {
+    if let Some(uid) = user_id {
+        let ip_str = ip.map(|i| i.to_string());
+        
+        let result = sqlx::query(
+            r#"
+            INSERT INTO login_history (
+                user_id,
+                ip_address,
+                user_agent,
+                device_fingerprint,
+                login_status,
+                failure_reason
+            )
+            VALUES ($1, $2::inet, $3, $4, $5, $6)
             "#
         )
         .bind(uid)
@@ -592,6 +1343,107 @@ async fn track_login_attempt(
     }
 }
 
+/// Helper untuk kirim OTP login, retry sekali jika pengiriman pertama gagal
+async fn send_login_otp_with_retry(
+    email: &str,
+    otp: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let service = EmailService::new().await?;
+
+    match service.send_login_otp(email, otp).await {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            tracing::warn!("OTP send failed, retrying once: {}", e);
+            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            service.send_login_otp(email, otp).await
+        }
+    }
+}
+
+/// Cek impossible travel terhadap login_history, jika terpicu catat event + kirim email alert
+fn spawn_geo_velocity_check(state: &AppState, user_id: Uuid, email: String, client_ip: std::net::IpAddr) {
+    let geo_velocity = state.geo_velocity.clone();
+    let pool = state.db.clone();
+    let now = state.clock.now();
+
+    tokio::spawn(async move {
+        let Some(travel) = geo_velocity.check(&pool, user_id, client_ip, now).await else {
+            return;
+        };
+
+        tracing::warn!(
+            "Impossible travel user {}: {} -> {} ({:.0} km, {:.0} km/jam)",
+            user_id, travel.previous_ip, client_ip, travel.distance_km, travel.speed_kmh
+        );
+
+        log_security_event(
+            &pool,
+            Some(user_id),
+            "IMPOSSIBLE_TRAVEL",
+            json!({
+                "ip": client_ip.to_string(),
+                "previous_ip": travel.previous_ip,
+                "previous_login_at": travel.previous_at,
+                "distance_km": travel.distance_km.round(),
+                "speed_kmh": travel.speed_kmh.round(),
+            }),
+            false
+        ).await;
+
+        // Alert pengambilalihan akun tidak mengikuti preferensi security_alerts, sama seperti OTP
+        let sent = match EmailService::new().await {
+            Ok(service) => service.send_security_alert(&email, &client_ip.to_string(), now).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = sent {
+            tracing::warn!("Gagal mengirim security alert ke user {}: {}", user_id, e);
+        }
+    });
+}
+
+/// Email "new device signed in" di background, login tidak menunggu SMTP
+fn spawn_new_device_alert(
+    state: &AppState,
+    user_id: Uuid,
+    email: String,
+    client_ip: std::net::IpAddr,
+    device_fingerprint: String,
+    user_agent: Option<String>,
+) {
+    let pool = state.db.clone();
+    let now = state.clock.now();
+
+    tokio::spawn(async move {
+        log_security_event(
+            &pool,
+            Some(user_id),
+            "NEW_DEVICE_LOGIN",
+            json!({
+                "ip": client_ip.to_string(),
+                "device_fingerprint": device_fingerprint,
+                "user_agent": user_agent,
+            }),
+            true
+        ).await;
+
+        // Login dari device baru selalu diberitahukan, preferensi security_alerts tidak berlaku
+        let sent = match EmailService::new().await {
+            Ok(service) => service.send_new_device_alert(&email, &client_ip.to_string(), now, user_agent.as_deref()).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = sent {
+            tracing::warn!("Gagal mengirim new device alert ke user {}: {}", user_id, e);
+        }
+    });
+}
+
+/// Lonjakan login gagal lintas akun (indikasi credential stuffing) memicu alert admin
+fn alert_login_failure(state: &AppState, client_ip: &str) {
+    state.notifier.record("login_failures", |count| {
+        format!("{} login gagal dalam window alert, terakhir dari IP {}", count, client_ip)
+    });
+}
+
 /// Helper untuk log security event
 async fn log_security_event(
     pool: &sqlx::PgPool,
@@ -632,12 +1484,33 @@ async fn log_security_event(
 )]
 pub async fn refresh_access_token(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(request): Json<RefreshTokenRequest>,
-) -> Result<Json<TokenPairResponse>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    // Refresh token dari body, atau dari cookie (mode cookie, wajib CSRF token)
+    let (refresh_token, from_cookie) = match request.refresh_token.clone() {
+        Some(token) => (token, false),
+        None => {
+            let token = read_cookie(&headers, REFRESH_COOKIE)
+                .filter(|_| state.cookie_session.enabled)
+                .ok_or((
+                    StatusCode::BAD_REQUEST,
+                    Json(ErrorResponse::new("Refresh token wajib diisi", Some("MISSING_REFRESH_TOKEN")))
+                ))?;
+
+            if !csrf_valid(&headers) {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    Json(ErrorResponse::new("CSRF token tidak valid", Some("CSRF_TOKEN_INVALID")))
+                ));
+            }
+            (token, true)
+        }
+    };
     
     // Verify refresh token dengan enhanced claims
     let claims = state.jwt_service
-        .verify_token_with_blacklist(&request.refresh_token, &state.db)
+        .verify_token_with_blacklist(&refresh_token, &state.db)
         .await
         .map_err(|e| {
             tracing::warn!("Invalid refresh token: {}", e);
@@ -655,7 +1528,7 @@ pub async fn refresh_access_token(
         ))?;
     
     // Get user dari database
-    let user_repository = UserRepository::new(get_pepper().as_bytes());
+    let user_repository = UserRepository::new(get_pepper().as_bytes()).with_clock(state.clock.clone());
     let user = user_repository.find_by_id(&state.db, user_id)
         .await
         .map_err(|e| {
@@ -667,14 +1540,16 @@ pub async fn refresh_access_token(
         })?;
     
     // Get original token expiry from database to preserve remember_me duration
-    let old_token_hash = hash_token(&request.refresh_token);
+    // Token yang sudah di-revoke tetap diambil, reuse ditangani saat rotasi
+    let old_token_hash = hash_token(&refresh_token);
     let original_token_data = sqlx::query!(
         r#"
         SELECT expires_at, created_at
         FROM refresh_tokens
-        WHERE token_hash = $1 AND is_revoked = false
+        WHERE token_hash = $1 AND user_id = $2
         "#,
-        old_token_hash
+        old_token_hash,
+        user_id
     )
     .fetch_optional(&state.db)
     .await
@@ -690,15 +1565,18 @@ pub async fn refresh_access_token(
         Json(ErrorResponse::new("Refresh token not found or already revoked", Some("TOKEN_NOT_FOUND")))
     ))?;
 
+    // Waktu login asli dibawa ke token baru, refresh tidak dihitung sebagai autentikasi ulang
+    let auth_time = claims.auth_time.unwrap_or(claims.iat);
+
     // Calculate original token duration
-    let created_at = original_token_data.created_at.unwrap_or_else(|| Utc::now());
+    let created_at = original_token_data.created_at.unwrap_or_else(|| state.clock.now());
     let original_duration = original_token_data.expires_at - created_at;
     let is_remember_me = original_duration.num_days() > 14; 
 
     // Generate new token pair with same duration as original
     let (token_pair, token_expiry) = if is_remember_me {
         let custom_token = state.jwt_service
-            .generate_token_with_duration(&user, Duration::days(30))
+            .generate_token_with_duration(&user, Duration::days(30), Some(auth_time))
             .map_err(|e| {
                 tracing::error!("Failed to generate token: {}", e);
                 (
@@ -717,7 +1595,7 @@ pub async fn refresh_access_token(
             Duration::days(30)
         )
     } else {
-        let token_pair = state.jwt_service.generate_token_pair(&user)
+        let token_pair = state.jwt_service.generate_token_pair(&user, Some(auth_time))
             .map_err(|e| {
                 tracing::error!("Failed to generate token pair: {}", e);
                 (
@@ -730,71 +1608,73 @@ pub async fn refresh_access_token(
 
     // Store new refresh token hash di database with preserved expiry
     let token_hash = hash_token(&token_pair.refresh_token);
-    let expires_at = Utc::now() + token_expiry;
+    let expires_at = state.clock.now() + token_expiry;
     
-    let mut tx = state.db.begin().await
-        .map_err(|e| {
-            tracing::error!("Failed to begin transaction: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse::new("Database error", Some("DB_ERROR")))
-            )
-        })?;
-
-    // Revoke old refresh token (old_token_hash already defined above)
-    sqlx::query!(
-        r#"
-        UPDATE refresh_tokens 
-        SET is_revoked = true, 
-            revoked_at = NOW(), 
-            revoked_reason = 'Token refreshed'
-        WHERE token_hash = $1
-        "#,
-        old_token_hash
-    )
-    .execute(&mut *tx)
-    .await
-    .map_err(|e| {
-        tracing::error!("Failed to revoke old token: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse::new("Failed to revoke old token", Some("DB_ERROR")))
-        )
-    })?;
-    
-    // Store new refresh token
-    sqlx::query!(
-        r#"
-        INSERT INTO refresh_tokens (user_id, token_hash, device_fingerprint, expires_at)
-        VALUES ($1, $2, $3, $4)
-        "#,
+    let rotation = user_repository.rotate_refresh_token(
+        &state.db,
         user_id,
-        token_hash,
-        request.device_fingerprint,
-        expires_at
+        &old_token_hash,
+        &token_hash,
+        request.device_fingerprint.as_deref(),
+        expires_at,
     )
-    .execute(&mut *tx)
     .await
     .map_err(|e| {
-        tracing::error!("Failed to store refresh token: {}", e);
+        tracing::error!("Failed to rotate refresh token: {}", e);
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse::new("Failed to store token", Some("DB_ERROR")))
         )
-    })?;
-    
-    tx.commit().await
-        .map_err(|e| {
-            tracing::error!("Failed to commit transaction: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse::new("Transaction failed", Some("DB_ERROR")))
-            )
-        })?;
+    })?;
+
+    match rotation {
+        RefreshRotation::Rotated => {}
+        RefreshRotation::ReuseDetected { token_family, revoked } => {
+            tracing::warn!("Refresh token reuse for user {}, revoked {} tokens in family {}", user_id, revoked, token_family);
+
+            log_security_event(
+                &state.db,
+                Some(user_id),
+                "TOKEN_REUSE_DETECTED",
+                json!({
+                    "token_family": token_family,
+                    "revoked_count": revoked,
+                    "device_fingerprint": request.device_fingerprint,
+                }),
+                false
+            ).await;
+
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorResponse::new("Refresh token sudah dipakai, silakan login kembali", Some("TOKEN_REUSE_DETECTED")))
+            ));
+        }
+        RefreshRotation::NotFound => {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorResponse::new("Refresh token not found or already revoked", Some("TOKEN_NOT_FOUND")))
+            ));
+        }
+    }
     
     tracing::info!("Token refreshed for user {}", user_id);
+
+    // Refresh lewat cookie tetap di mode cookie, token baru tidak dikirim di body
+    if from_cookie || state.cookie_session.wants_cookies(request.use_cookies, &headers) {
+        let cookies = state.cookie_session.session_headers(
+            &token_pair.access_token,
+            token_pair.expires_in,
+            &token_pair.refresh_token,
+            token_pair.refresh_expires_in,
+        );
+        return Ok((cookies, Json(json!({
+            "success": true,
+            "expires_in": token_pair.expires_in,
+            "refresh_expires_in": token_pair.refresh_expires_in,
+        }))).into_response());
+    }
     
-    Ok(Json(token_pair))
+    Ok(Json(token_pair).into_response())
 }
 
 /// Handler untuk logout
@@ -815,10 +1695,14 @@ pub async fn refresh_access_token(
 pub async fn logout(
     State(state): State<AppState>,
     Extension(user_id): Extension<Uuid>,
+    headers: HeaderMap,
     Json(payload): Json<LogoutRequest>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<(HeaderMap, Json<serde_json::Value>), (StatusCode, Json<ErrorResponse>)> {
+    let refresh_token = payload.refresh_token.clone()
+        .or_else(|| read_cookie(&headers, REFRESH_COOKIE));
+
     // Revoke refresh token jika ada
-    if let Some(refresh_token) = &payload.refresh_token {
+    if let Some(refresh_token) = &refresh_token {
         let token_hash = hash_token(refresh_token);
         
         match sqlx::query!(
@@ -865,6 +1749,11 @@ pub async fn logout(
         }
     }
     
+    // Gateway yang memverifikasi access token lokal ikut menolak token ini
+    if let Some(access_jti) = payload.access_token_jti.clone() {
+        state.service_client.spawn_gateway_invalidation(user_id, Some(access_jti), state.clock.now());
+    }
+
     tracing::info!("User {} logged out successfully", user_id);
 
     log_security_event(
@@ -873,16 +1762,23 @@ pub async fn logout(
         "LOGOUT",
         serde_json::json!({
             "timestamp": Utc::now(),
-            "session_revoked": payload.refresh_token.is_some()
+            "session_revoked": refresh_token.is_some()
         }),
         true
     ).await;
+
+    // Hapus cookie sesi jika client memakai mode cookie
+    let cookies = if read_cookie(&headers, ACCESS_COOKIE).is_some() || read_cookie(&headers, REFRESH_COOKIE).is_some() {
+        state.cookie_session.clear_headers()
+    } else {
+        HeaderMap::new()
+    };
     
-    Ok(Json(serde_json::json!({
+    Ok((cookies, Json(serde_json::json!({
         "success": true,
         "message": "Logout berhasil",
         "user_id": user_id.to_string()
-    })))
+    }))))
 }
 
 /// Handler untuk request password reset
@@ -912,6 +1808,27 @@ pub async fn request_password_reset(
         )
     })?;
     
+    let user_repository = UserRepository::new(get_pepper().as_bytes()).with_clock(state.clock.clone());
+
+    // Rate limit per email: response tetap generic, pengiriman di-suppress
+    let user = match user {
+        Some(user) => {
+            let allowed = user_repository
+                .allow_sensitive_email(&state.db, &user.email, EMAIL_PURPOSE_PASSWORD_RESET)
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::error!("Email rate limit check failed: {}", e);
+                    true
+                });
+
+            if !allowed {
+                tracing::warn!("Password reset email suppressed for user {} (rate limit)", user.id);
+            }
+            allowed.then_some(user)
+        }
+        None => None,
+    };
+
     if let Some(user) = user {
         let reset_token = format!("reset_{}", Uuid::new_v4());
         let token_hash = hash_token(&reset_token);
@@ -919,12 +1836,13 @@ pub async fn request_password_reset(
         sqlx::query!(
             r#"
             INSERT INTO password_reset_tokens (user_id, token_hash, expires_at)
-            VALUES ($1, $2, NOW() + INTERVAL '1 hour')
+            VALUES ($1, $2, $3)
             ON CONFLICT (user_id) DO UPDATE
-            SET token_hash = $2, expires_at = NOW() + INTERVAL '1 hour'
+            SET token_hash = $2, expires_at = $3
             "#,
             user.id,
-            token_hash
+            token_hash,
+            Utc::now() + password_reset_lifetime()
         )
         .execute(&state.db)
         .await
@@ -962,6 +1880,94 @@ pub async fn request_password_reset(
     )))
 }
 
+/// Handler untuk kirim ulang link verifikasi email (tanpa login)
+/// POST /api/auth/email/resend-verification
+pub async fn resend_verification_email(
+    State(state): State<AppState>,
+    Json(request): Json<ResendVerificationRequest>,
+) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if let Err(errors) = request.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::validation_error(errors))
+        ));
+    }
+
+    // Response selalu generic supaya tidak bisa dipakai enumerasi email
+    let generic_response = AuthResponse::success(
+        "Jika email terdaftar dan belum terverifikasi, link verifikasi telah dikirim ke email Anda"
+    );
+
+    let user = sqlx::query!(
+        "SELECT id, email FROM users WHERE email = $1 AND is_active = true AND email_verified = false",
+        request.email.trim().to_lowercase()
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Database error: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Database error", Some("DB_ERROR")))
+        )
+    })?;
+
+    let Some(user) = user else {
+        return Ok(Json(generic_response));
+    };
+
+    let user_repository = UserRepository::new(get_pepper().as_bytes()).with_clock(state.clock.clone());
+    let allowed = user_repository
+        .allow_sensitive_email(&state.db, &user.email, EMAIL_PURPOSE_VERIFICATION)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::error!("Email rate limit check failed: {}", e);
+            true
+        });
+
+    if !allowed {
+        tracing::warn!("Verification email suppressed for user {} (rate limit)", user.id);
+        return Ok(Json(generic_response));
+    }
+
+    let verify_token = format!("verify_{}", Uuid::new_v4());
+    let token_hash = hash_token(&verify_token);
+
+    sqlx::query!(
+        r#"
+        INSERT INTO email_verification_tokens (user_id, token_hash, expires_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (user_id) DO UPDATE
+        SET token_hash = $2, expires_at = $3, verified_at = NULL
+        "#,
+        user.id,
+        token_hash,
+        Utc::now() + verification_token_lifetime()
+    )
+    .execute(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to store verification token: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Failed to create verification token", Some("TOKEN_ERROR")))
+        )
+    })?;
+
+    match EmailService::new().await {
+        Ok(service) => {
+            if let Err(e) = service.send_verification_email(&user.email, &verify_token).await {
+                tracing::error!("Failed to resend verification email: {}", e);
+            } else {
+                tracing::info!("Verification email resent to {}", user.email);
+            }
+        }
+        Err(e) => tracing::error!("Email service failed: {}", e)
+    }
+
+    Ok(Json(generic_response))
+}
+
 /// Handler untuk reset password dengan token
 /// POST /api/auth/password-reset/confirm
 pub async fn reset_password(
@@ -1014,14 +2020,43 @@ pub async fn reset_password(
         ));
     }
     
-    if token_data.expires_at < Utc::now() {
+    if token_data.expires_at < state.clock.now() {
         return Err((
             StatusCode::BAD_REQUEST,
             Json(ErrorResponse::new("Token sudah expired", Some("TOKEN_EXPIRED")))
         ));
     }
     
-    let user_repository = UserRepository::new(get_pepper().as_bytes());
+    let reset_user_id = token_data.user_id.ok_or((
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new("Token tidak valid atau expired", Some("INVALID_TOKEN")))
+    ))?;
+
+    let current_hash = sqlx::query_scalar!(
+        "SELECT password_hash FROM users WHERE id = $1",
+        reset_user_id
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Database error: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Database error", Some("DB_ERROR")))
+        )
+    })?
+    .ok_or((
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new("Token tidak valid atau expired", Some("INVALID_TOKEN")))
+    ))?;
+
+    let user_repository = UserRepository::new(get_pepper().as_bytes()).with_clock(state.clock.clone());
+
+    // Reset tetap menolak reuse password, tapi tanpa jarak minimal (alur pemulihan akun)
+    user_repository.check_password_policy(&state.db, reset_user_id, &current_hash, &request.new_password, false)
+        .await
+        .map_err(super::user::password_policy_error)?;
+
     let new_hash = user_repository.security_service.hash_password(&request.new_password)
         .map_err(|_| (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -1057,6 +2092,16 @@ pub async fn reset_password(
         )
     })?;
     
+    user_repository.record_password_history(&mut tx, reset_user_id, &current_hash)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to record password history: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new("Failed to update password", Some("UPDATE_ERROR")))
+            )
+        })?;
+    
     sqlx::query!(
         "UPDATE password_reset_tokens SET used_at = NOW() WHERE token_hash = $1",
         token_hash
@@ -1070,6 +2115,17 @@ pub async fn reset_password(
             Json(ErrorResponse::new("Database error", Some("DB_ERROR")))
         )
     })?;
+
+    // Password direset = akun mungkin bocor, trusted device lama tidak boleh melewati OTP lagi
+    user_repository.revoke_trusted_devices(&mut *tx, reset_user_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to revoke trusted devices: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new("Database error", Some("DB_ERROR")))
+            )
+        })?;
     
     tx.commit().await.map_err(|e| {
         tracing::error!("Transaction commit error: {}", e);
@@ -1103,6 +2159,15 @@ pub async fn revoke_all_tokens(
         Ok(result) => {
             let revoked_count = result.rows_affected();
             tracing::info!("Revoked {} refresh tokens for user {}", revoked_count, user_id);
+            state.service_client.spawn_gateway_invalidation(user_id, None, state.clock.now());
+
+            // Logout dari semua device juga mencabut trusted device (login berikutnya wajib OTP)
+            if let Err(e) = UserRepository::new(get_pepper().as_bytes())
+                .revoke_trusted_devices(&state.db, user_id)
+                .await
+            {
+                tracing::warn!("Failed to revoke trusted devices for user {}: {}", user_id, e);
+            }
             
             Ok(Json(serde_json::json!({
                 "success": true,
@@ -1120,12 +2185,213 @@ pub async fn revoke_all_tokens(
     }
 }
 
-/// Handler untuk validasi session
+/// Refresh token device yang sedang request: header X-Refresh-Token atau cookie refresh
+fn current_refresh_token_hash(headers: &HeaderMap) -> Option<String> {
+    headers.get("X-Refresh-Token")
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_string)
+        .or_else(|| read_cookie(headers, REFRESH_COOKIE))
+        .map(|token| hash_token(&token))
+}
+
+/// Handler untuk daftar sesi login aktif milik user (satu per login, token hasil rotasi digabung)
+/// GET /api/auth/sessions
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<Vec<ActiveSession>>>, (StatusCode, Json<ErrorResponse>)> {
+    let current_hash = current_refresh_token_hash(&headers);
+    let user_repository = UserRepository::new(get_pepper().as_bytes()).with_clock(state.clock.clone());
+
+    let sessions = user_repository.list_active_sessions(&state.db, user_id, current_hash.as_deref())
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to list sessions for user {}: {}", user_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new("Gagal mengambil daftar sesi", Some("DATABASE_ERROR")))
+            )
+        })?;
+
+    Ok(Json(ApiResponse::ok("Daftar sesi berhasil diambil", sessions)))
+}
+
+/// Handler untuk revoke satu sesi (logout satu device), seluruh token family ikut di-revoke
+/// DELETE /api/auth/sessions/{id}
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Path(session_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let db_error = |e: String| {
+        tracing::error!("Failed to revoke session {}: {}", session_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Gagal revoke sesi", Some("DATABASE_ERROR")))
+        )
+    };
+
+    let session = sqlx::query!(
+        "SELECT user_id, token_family FROM refresh_tokens WHERE id = $1",
+        session_id
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| db_error(e.to_string()))?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse::new("Sesi tidak ditemukan", Some("SESSION_NOT_FOUND")))
+    ))?;
+
+    if session.user_id != user_id {
+        log_security_event(
+            &state.db,
+            Some(user_id),
+            "UNAUTHORIZED_ACCESS",
+            json!({"action": "revoke_session", "session_id": session_id}),
+            false
+        ).await;
+
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::new("Sesi bukan milik Anda", Some("SESSION_FORBIDDEN")))
+        ));
+    }
+
+    let user_repository = UserRepository::new(get_pepper().as_bytes()).with_clock(state.clock.clone());
+    let revoked = user_repository
+        .revoke_token_family(&state.db, user_id, session.token_family, "User revoked session")
+        .await
+        .map_err(|e| db_error(e.to_string()))?;
+
+    let is_current = match current_refresh_token_hash(&headers) {
+        Some(hash) => sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM refresh_tokens WHERE token_hash = $1 AND token_family = $2) AS "exists!""#,
+            hash,
+            session.token_family
+        )
+        .fetch_one(&state.db)
+        .await
+        .map_err(|e| db_error(e.to_string()))?,
+        None => false,
+    };
+    tracing::info!("User {} revoked session {} ({} tokens, current: {})", user_id, session_id, revoked, is_current);
+
+    Ok(Json(json!({
+        "success": true,
+        "message": if revoked > 0 { "Sesi berhasil di-revoke" } else { "Sesi sudah di-revoke sebelumnya" },
+        "session_id": session_id,
+        "is_current": is_current
+    })))
+}
+
+/// Handler untuk daftar trusted device (login tanpa OTP) milik user yang masih berlaku
+/// GET /api/auth/trusted-devices
+pub async fn list_trusted_devices(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<ApiResponse<Vec<TrustedDevice>>>, (StatusCode, Json<ErrorResponse>)> {
+    let devices = sqlx::query_as!(
+        TrustedDevice,
+        r#"
+        SELECT id, device_fingerprint, device_info, created_at, expires_at, last_used_at
+        FROM trusted_devices
+        WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > $2
+        ORDER BY COALESCE(last_used_at, created_at) DESC
+        LIMIT 100
+        "#,
+        user_id,
+        state.clock.now()
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to list trusted devices for user {}: {}", user_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Gagal mengambil daftar trusted device", Some("DATABASE_ERROR")))
+        )
+    })?;
+
+    Ok(Json(ApiResponse::ok("Daftar trusted device berhasil diambil", devices)))
+}
+
+/// Handler untuk mencabut trusted device, login berikutnya dari device tersebut kembali butuh OTP
+/// DELETE /api/auth/trusted-devices/{id}
+pub async fn revoke_trusted_device(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Path(device_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let db_error = |e: sqlx::Error| {
+        tracing::error!("Failed to revoke trusted device {}: {}", device_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Gagal mencabut trusted device", Some("DATABASE_ERROR")))
+        )
+    };
+
+    let device = sqlx::query!(
+        "SELECT user_id, revoked_at FROM trusted_devices WHERE id = $1",
+        device_id
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(db_error)?
+    .ok_or((
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse::new("Trusted device tidak ditemukan", Some("TRUSTED_DEVICE_NOT_FOUND")))
+    ))?;
+
+    if device.user_id != user_id {
+        log_security_event(
+            &state.db,
+            Some(user_id),
+            "UNAUTHORIZED_ACCESS",
+            json!({"action": "revoke_trusted_device", "device_id": device_id}),
+            false
+        ).await;
+
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::new("Trusted device bukan milik Anda", Some("TRUSTED_DEVICE_FORBIDDEN")))
+        ));
+    }
+
+    if device.revoked_at.is_none() {
+        sqlx::query!(
+            "UPDATE trusted_devices SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+            device_id,
+            user_id
+        )
+        .execute(&state.db)
+        .await
+        .map_err(db_error)?;
+
+        log_security_event(
+            &state.db,
+            Some(user_id),
+            "TRUSTED_DEVICE_REVOKED",
+            json!({"device_id": device_id}),
+            true
+        ).await;
+    }
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Trusted device berhasil dicabut",
+        "device_id": device_id
+    })))
+}
+
+/// Handler untuk validasi session, return user id/role dan sisa lifetime (detik)
 /// GET/POST /api/auth/session/validate
 pub async fn validate_session(
     State(state): State<AppState>,
     headers: HeaderMap,
-) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<Json<SessionValidationResponse>, (StatusCode, Json<ErrorResponse>)> {
     let session_token = headers
         .get("X-Session-Token")
         .and_then(|h| h.to_str().ok())
@@ -1134,21 +2400,169 @@ pub async fn validate_session(
             Json(ErrorResponse::new("Missing session token", Some("NO_SESSION")))
         ))?;
     
-    let user_repository = UserRepository::new(get_pepper().as_bytes());
+    let user_repository = UserRepository::new(get_pepper().as_bytes()).with_clock(state.clock.clone());
     
     match user_repository.validate_session_and_get_user(&state.db, session_token).await {
-        Ok(user) => {
+        Ok((user, expires_at)) => {
             let user_profile = UserProfile::from(user);
-            Ok(Json(AuthResponse::with_user(user_profile, "Session valid")))
+            Ok(Json(SessionValidationResponse::valid(user_profile, expires_at, state.clock.now())))
         }
         Err(DatabaseError::InvalidSession) => {
-            Ok(Json(AuthResponse::error("Session invalid atau expired")))
+            Ok(Json(SessionValidationResponse::invalid("Session invalid atau expired")))
         }
         Err(e) => {
             tracing::error!("Session validation error: {}", e);
-            Ok(Json(AuthResponse::error("Gagal validasi session")))
+            Ok(Json(SessionValidationResponse::invalid("Gagal validasi session")))
         }
     }
 }
 
-// Removed: Old send_email function replaced with EmailService
\ No newline at end of file
+// Removed: Old send_email function replaced with EmailService
+/// Handler untuk public key JWT (RS256) supaya service lain bisa verifikasi token secara lokal
+/// GET /.well-known/jwks.json
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    responses(
+        (status = 200, description = "JSON Web Key Set (kosong jika JWT_ALGORITHM=HS256)", body = serde_json::Value),
+    ),
+    tag = "auth"
+)]
+pub async fn get_jwks(State(state): State<AppState>) -> Response {
+    (
+        [("cache-control", "public, max-age=300")],
+        Json(state.jwt_service.jwks().clone()),
+    ).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::clock::MockClock;
+    use crate::db::test_support::{register_request, test_pool};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_login_otp_expires_with_clock() {
+        use crate::core::Clock;
+
+        let clock = MockClock::new(Utc::now());
+        let expires_at = clock.now() + otp_lifetime();
+        let error_code = |used_at| check_otp_challenge(expires_at, used_at, clock.now()).err().map(|(_, code)| code);
+
+        assert_eq!(error_code(None), None);
+        clock.advance(otp_lifetime());
+        assert_eq!(error_code(None), None);
+        clock.advance(Duration::seconds(1));
+        assert_eq!(error_code(None), Some("OTP_EXPIRED"));
+
+        clock.set(expires_at - Duration::minutes(1));
+        assert_eq!(error_code(Some(clock.now())), Some("OTP_USED"));
+    }
+
+    /// Butuh database asli (DATABASE_URL)
+    #[tokio::test]
+    async fn test_repeated_totp_login_does_not_reset_otp_attempts() {
+        let pool = test_pool().await;
+
+        let state = AppState::for_tests(pool.clone());
+        let repository = UserRepository::new(get_pepper().as_bytes());
+        let register = register_request("totp-attempts");
+        let email = register.email.clone();
+        let user = repository.create_user(&pool, register).await.unwrap();
+        sqlx::query!("UPDATE users SET email_verified = true, totp_enabled = true WHERE id = $1", user.id)
+            .execute(&pool).await.unwrap();
+
+        // Limit email OTP login sudah habis: challenge hanya bisa dijawab dengan kode authenticator
+        sqlx::query!(
+            "INSERT INTO email_send_log (email, purpose, suppressed) SELECT $1, $2, false FROM generate_series(1, 50)",
+            email, EMAIL_PURPOSE_LOGIN_OTP
+        ).execute(&pool).await.unwrap();
+
+        let addr: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+        let login = || login_user(State(state.clone()), ConnectInfo(addr), HeaderMap::new(), Json(LoginRequest {
+            email: email.clone(),
+            password: "Rahasia123!".to_string(),
+            remember_me: None,
+            enable_otp: None,
+            device_fingerprint: None,
+            trusted_device_token: None,
+            use_cookies: None,
+        }));
+        let verify = || async {
+            verify_otp(State(state.clone()), ConnectInfo(addr), HeaderMap::new(), Json(VerifyOtpRequest {
+                email: email.clone(),
+                otp: "000000".to_string(),
+                remember_me: None,
+                device_fingerprint: None,
+                use_cookies: None,
+                trust_device: None,
+            })).await.err().map(|(status, _)| status)
+        };
+
+        let first_login = login().await.is_ok();
+        let mut guesses = Vec::new();
+        for _ in 0..otp_max_attempts() + 1 {
+            guesses.push(verify().await);
+        }
+
+        // Login ulang (juga setelah challenge expired) tidak membuka jatah tebakan baru
+        let mut after_relogin = Vec::new();
+        for _ in 0..3 {
+            let relogin = login().await.is_ok();
+            after_relogin.push((relogin, verify().await));
+        }
+        sqlx::query!("UPDATE login_otps SET expires_at = NOW() - INTERVAL '1 minute' WHERE user_id = $1", user.id)
+            .execute(&pool).await.unwrap();
+        let after_expiry = (login().await.is_ok(), verify().await);
+
+        sqlx::query!("DELETE FROM email_send_log WHERE email = $1", email).execute(&pool).await.unwrap();
+        sqlx::query!("DELETE FROM users WHERE id = $1", user.id).execute(&pool).await.unwrap();
+
+        let locked = Some(StatusCode::TOO_MANY_REQUESTS);
+        assert!(first_login);
+        assert!(guesses[..otp_max_attempts() as usize].iter().all(|g| *g == Some(StatusCode::UNAUTHORIZED)));
+        assert_eq!(guesses.last().copied().flatten(), locked);
+        assert!(after_relogin.iter().all(|attempt| *attempt == (true, locked)));
+        assert_eq!(after_expiry, (true, locked));
+    }
+
+    /// Butuh database asli (DATABASE_URL)
+    #[tokio::test]
+    async fn test_trusted_device_skips_otp_only_while_valid() {
+        let pool = test_pool().await;
+
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let repository = UserRepository::new(b"pepper").with_clock(clock.clone());
+        let user = repository.create_user(&pool, register_request("trusted")).await.unwrap();
+        let headers = HeaderMap::new();
+        let trusted = |fingerprint: &'static str, token: Option<String>| {
+            let (pool, repository) = (pool.clone(), &repository);
+            async move { is_trusted_device(&pool, repository, user.id, Some(fingerprint), token.as_deref()).await }
+        };
+
+        // Tanpa fingerprint tidak ada token yang diterbitkan
+        assert_eq!(trust_device(&pool, &repository, user.id, Some("  "), &headers).await, None);
+
+        let token = trust_device(&pool, &repository, user.id, Some("fp-laptop"), &headers).await.expect("token trusted device");
+        let login_skips_otp = trusted("fp-laptop", Some(token.clone())).await;
+        let wrong_fingerprint = trusted("fp-phone", Some(token.clone())).await;
+        let without_token = trusted("fp-laptop", None).await;
+
+        // Dicabut (ganti / reset password, logout semua device)
+        repository.revoke_trusted_devices(&pool, user.id).await.unwrap();
+        let revoked = trusted("fp-laptop", Some(token)).await;
+
+        let token = trust_device(&pool, &repository, user.id, Some("fp-laptop"), &headers).await.unwrap();
+        clock.advance(trusted_device_lifetime() + Duration::minutes(1));
+        let expired = trusted("fp-laptop", Some(token)).await;
+
+        sqlx::query!("DELETE FROM users WHERE id = $1", user.id).execute(&pool).await.unwrap();
+
+        assert!(login_skips_otp);
+        assert!(!wrong_fingerprint);
+        assert!(!without_token);
+        assert!(!revoked);
+        assert!(!expired);
+    }
+}
@@ -1,18 +1,22 @@
 // /pdf-bookstore/services/auth-service/src/api/handlers/internal.rs
 
 use axum::{
-    extract::{State, Path},
+    extract::{State, Path, Query},
     http::{StatusCode, HeaderMap},
     response::Json,
     Extension,
 };
 use uuid::Uuid;
 
+use sqlx::PgPool;
+
 use crate::{
     AppState,
     models::*,
-    db::UserRepository,
-    utils::get_pepper, 
+    core::jwt::{effective_role, JwtService, PAT_TOKEN_TYPE, REFRESH_TOKEN_TYPE},
+    db::{UserRepository, allows_email, get_notification_preferences},
+    services::ServiceClient,
+    utils::{get_pepper, hash_token},
 };
 
 /// Handler untuk verifikasi user dari service lain (internal use)
@@ -54,15 +58,7 @@ pub async fn get_user_for_payment(
     Path(user_id): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
     // Verify internal service call dengan API key
-    let service_key = headers
-        .get("X-Service-Key")
-        .and_then(|h| h.to_str().ok())
-        .unwrap_or("");
-    
-    let expected_key = std::env::var("INTERNAL_SERVICE_KEY")
-        .unwrap_or_else(|_| "internal-service-key-secret".to_string());
-    
-    if service_key != expected_key {
+    if !is_valid_service_key(&headers) {
         return Err((
             StatusCode::UNAUTHORIZED,
             Json(ErrorResponse::new("Unauthorized service call", Some("INVALID_SERVICE_KEY")))
@@ -80,6 +76,7 @@ pub async fn get_user_for_payment(
                     "email": user.email,
                     "name": user.full_name,
                     "phone": None::<String>,
+                    "email_verified": user.email_verified,
                 }
             })))
         }
@@ -92,6 +89,137 @@ pub async fn get_user_for_payment(
     }
 }
 
+/// Batas jumlah user per batch lookup
+const MAX_BATCH_USERS: usize = 100;
+
+/// Handler untuk lookup banyak user sekaligus (enrichment list order/review)
+/// POST /api/internal/users/batch
+pub async fn get_users_batch_internal(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<BatchUserLookupRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    if !is_valid_service_key(&headers) {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse::new("Unauthorized service call", Some("INVALID_SERVICE_KEY")))
+        ));
+    }
+
+    let mut user_ids = request.user_ids;
+    user_ids.sort();
+    user_ids.dedup();
+
+    if user_ids.is_empty() || user_ids.len() > MAX_BATCH_USERS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(
+                &format!("user_ids harus berisi 1-{} ID", MAX_BATCH_USERS),
+                Some("INVALID_BATCH_SIZE")
+            ))
+        ));
+    }
+
+    let users = sqlx::query!(
+        r#"
+        SELECT id, email, full_name, role, is_active, email_verified
+        FROM users
+        WHERE id = ANY($1)
+        "#,
+        &user_ids
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Batch user lookup failed: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Gagal mengambil data user", Some("DATABASE_ERROR")))
+        )
+    })?;
+
+    let not_found: Vec<Uuid> = user_ids.iter()
+        .filter(|id| !users.iter().any(|u| u.id == **id))
+        .copied()
+        .collect();
+
+    let users: Vec<serde_json::Value> = users.into_iter().map(|user| serde_json::json!({
+        "id": user.id,
+        "email": user.email,
+        "full_name": user.full_name,
+        "role": user.role,
+        "is_active": user.is_active,
+        "email_verified": user.email_verified
+    })).collect();
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "users": users,
+        "not_found": not_found
+    })))
+}
+
+/// Helper untuk verifikasi X-Service-Key pada internal endpoint
+fn is_valid_service_key(headers: &HeaderMap) -> bool {
+    let service_key = headers
+        .get("X-Service-Key")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("");
+    
+    let expected_key = std::env::var("INTERNAL_SERVICE_KEY")
+        .unwrap_or_else(|_| "internal-service-key-secret".to_string());
+    
+    service_key == expected_key
+}
+
+/// Handler preferensi notifikasi user untuk service lain sebelum kirim email
+/// GET /api/internal/users/:id/notifications?category=order_receipts
+/// `allowed` diisi jika category dikirim
+pub async fn get_user_notification_preferences_internal(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    if !is_valid_service_key(&headers) {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse::new("Unauthorized service call", Some("INVALID_SERVICE_KEY")))
+        ));
+    }
+
+    let category = match params.get("category") {
+        Some(value) => Some(EmailCategory::parse(value).ok_or((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(
+                "Category harus salah satu dari: mandatory, security_alerts, order_receipts, marketing",
+                Some("INVALID_CATEGORY")
+            ))
+        ))?),
+        None => None,
+    };
+
+    let preferences = get_notification_preferences(&state.db, user_id).await
+        .map_err(|e| {
+            tracing::error!("Failed to get notification preferences for {}: {}", user_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new("Gagal mengambil preferensi notifikasi", Some("DATABASE_ERROR")))
+            )
+        })?;
+
+    let allowed = match category {
+        Some(category) => Some(allows_email(&state.db, user_id, category).await),
+        None => None,
+    };
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "preferences": preferences,
+        "allowed": allowed,
+    })))
+}
+
 /// Handler untuk validasi token internal antar service
 /// POST /api/internal/validate-token
 pub async fn validate_token_internal(
@@ -121,31 +249,99 @@ pub async fn validate_token_internal(
     })))
 }
 
+/// Handler introspection token ala RFC 7662 untuk service lain (mis. payment-service saat retry webhook)
+/// POST /api/auth/introspect, hanya untuk jaringan internal (tidak diteruskan gateway)
+/// Token invalid/expired/revoked tetap 200 dengan `active: false`
+pub async fn introspect_token(
+    State(state): State<AppState>,
+    Json(request): Json<IntrospectRequest>,
+) -> Json<IntrospectionResponse> {
+    let user_repository = UserRepository::new(get_pepper().as_bytes()).with_clock(state.clock.clone());
+
+    Json(introspect(&state.jwt_service, &user_repository, &state.db, request.token.trim()).await)
+}
+
+/// Token aktif jika JWT valid dan tidak di-blacklist, refresh token belum di-revoke,
+/// dan user masih aktif serta tidak terkunci; role diambil dari database, bukan dari claim
+async fn introspect(
+    jwt_service: &JwtService,
+    user_repository: &UserRepository,
+    pool: &PgPool,
+    token: &str,
+) -> IntrospectionResponse {
+    let claims = match jwt_service.verify_token_with_blacklist(token, pool).await {
+        Ok(claims) => claims,
+        Err(e) => {
+            tracing::debug!("Introspection: token tidak aktif: {}", e);
+            return IntrospectionResponse::inactive();
+        }
+    };
+
+    if claims.token_type == REFRESH_TOKEN_TYPE {
+        match user_repository.is_refresh_token_active(pool, &hash_token(token)).await {
+            Ok(true) => {}
+            Ok(false) => return IntrospectionResponse::inactive(),
+            Err(e) => {
+                tracing::warn!("Introspection: gagal cek refresh token: {}", e);
+                return IntrospectionResponse::inactive();
+            }
+        }
+    }
+
+    let Ok(user_id) = Uuid::parse_str(&claims.sub) else {
+        return IntrospectionResponse::inactive();
+    };
+    let user = match user_repository.find_by_id(pool, user_id).await {
+        Ok(user) => user,
+        Err(e) => {
+            tracing::debug!("Introspection: user {} tidak aktif: {}", user_id, e);
+            return IntrospectionResponse::inactive();
+        }
+    };
+
+    let scopes = (claims.token_type == PAT_TOKEN_TYPE).then(|| claims.scopes.clone().unwrap_or_default());
+    let mut response = IntrospectionResponse::from(claims);
+    response.role = Some(effective_role(&user.role, scopes.as_deref()));
+    response
+}
+
 /// Handler untuk cek akses user terhadap buku tertentu
 /// GET /api/users/books/:book_id/access
+/// `partial: true` jika payment/book service tidak tersedia
 pub async fn check_user_book_access(
     State(state): State<AppState>,
     Extension(user_id): Extension<Uuid>,
     Path(book_id): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let mut partial = false;
+
     // Check ownership via payment service
-    let has_access = state.service_client
-        .check_book_ownership(user_id, book_id)
-        .await
-        .unwrap_or(false);
+    let has_access = match state.service_client.check_book_ownership(user_id, book_id).await {
+        Ok(owned) => owned,
+        Err(e) => {
+            tracing::warn!("Ownership check untuk user {} gagal: {}", user_id, e);
+            partial = true;
+            false
+        }
+    };
     
     // Get book details jika user punya akses
     let book_details = if has_access {
         state.service_client
             .get_book_details(book_id)
             .await
-            .ok()
+            .unwrap_or_else(|e| {
+                tracing::warn!("Detail buku {} tidak tersedia: {}", book_id, e);
+                partial = true;
+                None
+            })
     } else {
         None
     };
     
     Ok(Json(serde_json::json!({
         "success": true,
+        "partial": partial,
         "has_access": has_access,
         "user_id": user_id,
         "book_id": book_id,
@@ -168,35 +364,52 @@ pub async fn get_user_complete_profile(
             StatusCode::NOT_FOUND,
             Json(ErrorResponse::new("User tidak ditemukan", Some("USER_NOT_FOUND")))
         ))?;
+
+    let user_fields = serde_json::json!({
+        "id": user.id,
+        "email": user.email,
+        "full_name": user.full_name,
+        "role": user.role,
+        "email_verified": user.email_verified,
+        "created_at": user.created_at,
+    });
     
-    // Get purchases dari payment service
-    let purchases = state.service_client
-        .get_user_purchases(user_id, Some(10))
-        .await
-        .unwrap_or_default();
-    
-    // Get downloaded books dari book service
-    let downloads = state.service_client
-        .get_user_downloaded_books(user_id)
-        .await
-        .unwrap_or_default();
-    
-    // Get order stats
-    let order_stats = state.service_client
-        .get_user_order_stats(user_id)
-        .await
-        .unwrap_or_default();
+    Ok(Json(aggregate_profile(&state.service_client, user_id, user_fields).await))
+}
+
+/// Gabungkan data auth dengan data payment/book service
+/// Downstream yang gagal/circuit open tidak menggagalkan profile, ditandai lewat `partial`
+async fn aggregate_profile(
+    service_client: &ServiceClient,
+    user_id: Uuid,
+    user_fields: serde_json::Value,
+) -> serde_json::Value {
+    let (purchases, downloads, order_stats) = tokio::join!(
+        service_client.get_user_purchases(user_id, Some(10)),
+        service_client.get_user_downloaded_books(user_id),
+        service_client.get_user_order_stats(user_id),
+    );
+
+    let mut unavailable = Vec::new();
+    if purchases.is_err() {
+        unavailable.push("recent_purchases");
+    }
+    if downloads.is_err() {
+        unavailable.push("downloaded_books");
+    }
+    if order_stats.is_err() {
+        unavailable.push("stats");
+    }
+
+    let purchases = purchases.unwrap_or_default();
+    let downloads = downloads.unwrap_or_default();
+    let order_stats = order_stats.unwrap_or_default();
     
-    Ok(Json(serde_json::json!({
+    serde_json::json!({
         "success": true,
-        "user": {
-            "id": user.id,
-            "email": user.email,
-            "full_name": user.full_name,
-            "role": user.role,
-            "email_verified": user.email_verified,
-            "created_at": user.created_at,
-        },
+        "partial": !unavailable.is_empty(),
+        "unavailable_sections": unavailable,
+        "user": user_fields,
         "stats": {
             "total_orders": order_stats.total_orders,
             "total_spent": order_stats.total_spent,
@@ -205,5 +418,122 @@ pub async fn get_user_complete_profile(
         },
         "recent_purchases": purchases,
         "downloaded_books": downloads
-    })))
-}
\ No newline at end of file
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{register_request, test_pool};
+    use crate::services::CircuitBreakerManager;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_profile_partial_when_downstream_down() {
+        // Port 9 (discard) di localhost -> connection refused, simulasi service mati
+        let client = ServiceClient::with_urls(
+            Arc::new(CircuitBreakerManager::new()),
+            "http://127.0.0.1:9".to_string(),
+            "http://127.0.0.1:9".to_string(),
+        );
+        let user_id = Uuid::new_v4();
+
+        let profile = aggregate_profile(
+            &client,
+            user_id,
+            serde_json::json!({ "id": user_id, "email": "reader@example.com" }),
+        ).await;
+
+        assert_eq!(profile["success"], true);
+        assert_eq!(profile["partial"], true);
+        assert_eq!(profile["user"]["email"], "reader@example.com");
+        assert_eq!(profile["user"]["id"], user_id.to_string());
+        assert!(profile["unavailable_sections"].as_array().unwrap().len() == 3);
+        assert_eq!(profile["downloaded_books"], serde_json::json!([]));
+    }
+
+    /// Hapus user test (dan blacklist-nya) saat drop, termasuk ketika assert panic
+    struct TestUsers(Vec<Uuid>);
+
+    impl Drop for TestUsers {
+        fn drop(&mut self) {
+            let user_ids = std::mem::take(&mut self.0);
+
+            // Runtime test sedang ter-block di drop ini, cleanup jalan di thread dengan runtime sendiri
+            let cleanup = std::thread::spawn(move || {
+                let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+                runtime.block_on(async {
+                    let pool = test_pool().await;
+                    sqlx::query!("DELETE FROM token_blacklist WHERE user_id = ANY($1)", &user_ids)
+                        .execute(&pool).await.unwrap();
+                    sqlx::query!("DELETE FROM users WHERE id = ANY($1)", &user_ids)
+                        .execute(&pool).await.unwrap();
+                });
+            });
+            let _ = cleanup.join();
+        }
+    }
+
+    /// Butuh database asli (DATABASE_URL)
+    #[tokio::test]
+    async fn test_introspection_rejects_expired_blacklisted_and_revoked_tokens() {
+        use crate::core::clock::{Clock, MockClock};
+
+        let pool = test_pool().await;
+
+        let clock = Arc::new(MockClock::new(chrono::Utc::now()));
+        let jwt_service = JwtService::for_tests(clock.clone());
+        let repository = UserRepository::new(b"pepper").with_clock(clock.clone());
+        let mut users = TestUsers(Vec::new());
+        let admin = repository.create_user(&pool, register_request("introspect-admin")).await.unwrap();
+        users.0.push(admin.id);
+        let customer = repository.create_user(&pool, register_request("introspect")).await.unwrap();
+        users.0.push(customer.id);
+
+        // Role diambil dari database (admin), bukan dari claim token (customer)
+        let admin_token = jwt_service.generate_token_pair(&admin, None).unwrap().access_token;
+        sqlx::query!("UPDATE users SET role = 'admin' WHERE id = $1", admin.id).execute(&pool).await.unwrap();
+        let promoted = introspect(&jwt_service, &repository, &pool, &admin_token).await;
+        assert!(promoted.active);
+        assert_eq!(promoted.role.as_deref(), Some("admin"));
+
+        let pair = jwt_service.generate_token_pair(&customer, None).unwrap();
+        let active = introspect(&jwt_service, &repository, &pool, &pair.access_token).await;
+        assert!(active.active);
+        assert_eq!(active.role.as_deref(), Some("customer"));
+
+        // Refresh token: aktif selama row belum di-revoke
+        sqlx::query!(
+            "INSERT INTO refresh_tokens (user_id, token_hash, expires_at) VALUES ($1, $2, $3)",
+            customer.id, hash_token(&pair.refresh_token), clock.now() + chrono::Duration::days(7)
+        ).execute(&pool).await.unwrap();
+        assert!(introspect(&jwt_service, &repository, &pool, &pair.refresh_token).await.active);
+        sqlx::query!("UPDATE refresh_tokens SET is_revoked = true WHERE user_id = $1", customer.id)
+            .execute(&pool).await.unwrap();
+        let revoked_refresh = introspect(&jwt_service, &repository, &pool, &pair.refresh_token).await;
+
+        // Access token di-blacklist (logout)
+        sqlx::query!(
+            "INSERT INTO token_blacklist (token_jti, user_id, expires_at, reason) VALUES ($1, $2, NOW() + INTERVAL '1 hour', 'test')",
+            active.jti.clone().unwrap(), customer.id
+        ).execute(&pool).await.unwrap();
+        let blacklisted = introspect(&jwt_service, &repository, &pool, &pair.access_token).await;
+
+        // Token expired milik user aktif
+        let stale = jwt_service.generate_token_pair(&customer, None).unwrap().access_token;
+        clock.advance(chrono::Duration::days(2));
+        let expired = introspect(&jwt_service, &repository, &pool, &stale).await;
+
+        // Token valid milik user nonaktif (customer: admin wajib aktif)
+        let fresh = jwt_service.generate_token_pair(&customer, None).unwrap().access_token;
+        assert!(introspect(&jwt_service, &repository, &pool, &fresh).await.active);
+        sqlx::query!("UPDATE users SET is_active = false WHERE id = $1", customer.id).execute(&pool).await.unwrap();
+        let inactive_user = introspect(&jwt_service, &repository, &pool, &fresh).await;
+
+        assert!(!revoked_refresh.active);
+        assert!(!blacklisted.active);
+        assert!(!inactive_user.active);
+        assert!(!expired.active);
+        assert_eq!(expired.role, None);
+    }
+}
@@ -5,10 +5,12 @@ pub mod user;
 pub mod admin;
 pub mod internal;
 pub mod oauth;
+pub mod tokens;
 
 // Re-export semua handler functions
 pub use auth::*;
 pub use user::*;
 pub use admin::*;
 pub use internal::*;
-pub use oauth::*;
\ No newline at end of file
+pub use oauth::*;
+pub use tokens::*;
\ No newline at end of file
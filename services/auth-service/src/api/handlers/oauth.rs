@@ -159,7 +159,7 @@ pub async fn google_oauth_callback(
 
     // Generate tokens (similar to verify_otp handler)
     let user_repository = UserRepository::new(get_pepper().as_bytes());
-    let token_pair = state.jwt_service.generate_token_pair(&user).map_err(|e| {
+    let token_pair = state.jwt_service.generate_token_pair(&user, None).map_err(|e| {
         tracing::error!("Failed to generate token pair: {}", e);
         (
             StatusCode::INTERNAL_SERVER_ERROR,
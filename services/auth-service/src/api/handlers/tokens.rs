@@ -0,0 +1,246 @@
+// /pdf-bookstore/services/auth-service/src/api/handlers/tokens.rs
+
+use axum::{
+    extract::{State, Path},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use uuid::Uuid;
+use validator::Validate;
+use chrono::Duration;
+
+use crate::{
+    AppState,
+    models::*,
+    core::jwt::PAT_SCOPES,
+    db::UserRepository,
+    middleware::recent_auth::{require_recent_auth, AuthTime},
+    utils::{hash_token, get_pepper},
+};
+
+/// Batas personal access token aktif per user
+const MAX_ACTIVE_TOKENS: i64 = 10;
+
+/// Handler untuk membuat (atau regenerate) personal access token, butuh autentikasi baru (step-up)
+/// POST /api/auth/tokens
+pub async fn create_personal_token(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Extension(auth_time): Extension<AuthTime>,
+    Json(request): Json<CreatePersonalTokenRequest>,
+) -> Result<Json<CreatePersonalTokenResponse>, (StatusCode, Json<ErrorResponse>)> {
+    require_recent_auth(auth_time, state.clock.now())?;
+
+    if let Err(errors) = request.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::validation_error(errors))
+        ));
+    }
+
+    let user_repository = UserRepository::new(get_pepper().as_bytes());
+    let user = user_repository.find_by_id(&state.db, user_id).await
+        .map_err(|e| {
+            tracing::error!("Failed to load user {} for token creation: {}", user_id, e);
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::new("User tidak ditemukan", Some("USER_NOT_FOUND")))
+            )
+        })?;
+
+    // Validasi scope
+    if let Some(invalid) = request.scopes.iter().find(|s| !PAT_SCOPES.contains(&s.as_str())) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(&format!("Scope tidak dikenal: {}", invalid), Some("INVALID_SCOPE")))
+        ));
+    }
+
+    if request.scopes.iter().any(|s| s == "admin:access") && user.role != "admin" {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::new("Scope admin hanya untuk admin", Some("INSUFFICIENT_PRIVILEGES")))
+        ));
+    }
+
+    let name = request.name.trim().to_string();
+    let token_id = Uuid::new_v4();
+    let now = state.clock.now();
+    let expires_at = now + Duration::days(request.expires_in_days.unwrap_or(90));
+
+    let token = state.jwt_service
+        .generate_personal_access_token(&user, token_id, &request.scopes, expires_at)
+        .map_err(|e| {
+            tracing::error!("Failed to sign personal access token: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new("Gagal membuat token", Some("TOKEN_ERROR")))
+            )
+        })?;
+
+    let db_error = |e: sqlx::Error| {
+        tracing::error!("Personal access token DB error: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Gagal menyimpan token", Some("DATABASE_ERROR")))
+        )
+    };
+
+    let mut tx = state.db.begin().await.map_err(db_error)?;
+
+    // Lock per user: request paralel menunggu sampai token sebelumnya ter-commit,
+    // sehingga revoke, hitung, dan insert atomik (MAX_ACTIVE_TOKENS tidak bisa terlewati)
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext('personal_access_tokens'), hashtext($1))")
+        .bind(user_id.to_string())
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    // Token dengan nama sama di-revoke (regenerate)
+    let regenerated = sqlx::query!(
+        r#"
+        UPDATE personal_access_tokens
+        SET revoked_at = $3
+        WHERE user_id = $1 AND name = $2 AND revoked_at IS NULL
+        "#,
+        user_id,
+        name,
+        now
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?
+    .rows_affected() > 0;
+
+    let active_count = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM personal_access_tokens
+        WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > $2
+        "#,
+        user_id,
+        now
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    if active_count >= MAX_ACTIVE_TOKENS {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse::new(
+                &format!("Maksimal {} token aktif, revoke token lama terlebih dahulu", MAX_ACTIVE_TOKENS),
+                Some("TOKEN_LIMIT_REACHED")
+            ))
+        ));
+    }
+
+    let info = sqlx::query_as!(
+        PersonalAccessTokenInfo,
+        r#"
+        INSERT INTO personal_access_tokens (id, user_id, name, token_hash, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, name, scopes, expires_at, last_used_at, created_at
+        "#,
+        token_id,
+        user_id,
+        name,
+        hash_token(&token),
+        &request.scopes,
+        expires_at
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+
+    tracing::info!("Personal access token '{}' created for user {} (regenerated: {})",
+        info.name, user_id, regenerated);
+
+    Ok(Json(CreatePersonalTokenResponse {
+        success: true,
+        message: if regenerated {
+            "Token berhasil di-regenerate, simpan token ini karena tidak akan ditampilkan lagi".to_string()
+        } else {
+            "Token berhasil dibuat, simpan token ini karena tidak akan ditampilkan lagi".to_string()
+        },
+        token,
+        data: info,
+    }))
+}
+
+/// Handler untuk list personal access token aktif milik user
+/// GET /api/auth/tokens
+pub async fn list_personal_tokens(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let tokens = sqlx::query_as!(
+        PersonalAccessTokenInfo,
+        r#"
+        SELECT id, name, scopes, expires_at, last_used_at, created_at
+        FROM personal_access_tokens
+        WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+        ORDER BY created_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to list tokens for user {}: {}", user_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Gagal mengambil token", Some("DATABASE_ERROR")))
+        )
+    })?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "Token berhasil diambil",
+        "data": tokens,
+        "count": tokens.len()
+    })))
+}
+
+/// Handler untuk revoke personal access token
+/// DELETE /api/auth/tokens/{id}
+pub async fn revoke_personal_token(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Path(token_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE personal_access_tokens
+        SET revoked_at = NOW()
+        WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        token_id,
+        user_id
+    )
+    .execute(&state.db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to revoke token {}: {}", token_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Gagal revoke token", Some("DATABASE_ERROR")))
+        )
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new("Token tidak ditemukan", Some("TOKEN_NOT_FOUND")))
+        ));
+    }
+
+    tracing::info!("Personal access token {} revoked by user {}", token_id, user_id);
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "Token berhasil di-revoke"
+    })))
+}
@@ -14,9 +14,10 @@ use utoipa;
 
 use crate::{
     AppState,
+    middleware::recent_auth::{require_recent_auth, AuthTime},
     models::*,
-    db::{UserRepository, DatabaseError},
-    utils::common::{get_pepper, hash_token},
+    db::{UserRepository, DatabaseError, SecurityEventFilter, get_notification_preferences, update_notification_preferences},
+    utils::common::{get_pepper, hash_token, verification_token_lifetime},
 };
 
 /// Handler untuk mendapatkan profile user
@@ -64,6 +65,15 @@ pub async fn update_profile(
     Extension(user_id): Extension<Uuid>,
     Json(request): Json<UpdateProfileRequest>,
 ) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
+    // Batas panjang eksplisit sebelum validasi dan query DB
+    let oversized = request.oversized_fields(&ProfileLengthLimits::from_env());
+    if !oversized.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::input_too_long(&oversized))
+        ));
+    }
+
     // Validasi request
     if let Err(errors) = request.validate() {
         return Err((
@@ -134,16 +144,21 @@ pub async fn update_profile(
         session_id: None,
         requires_verification: None,
         two_factor_required: None,
+        trusted_device_token: None,
+        scopes: None,
     }))
 }
 
-/// Handler untuk ganti password user yang sudah login
+/// Handler untuk ganti password user yang sudah login, butuh autentikasi baru (step-up)
 /// POST /api/auth/password/change
 pub async fn change_password(
     State(state): State<AppState>,
     Extension(user_id): Extension<Uuid>,
+    Extension(auth_time): Extension<AuthTime>,
     Json(request): Json<ChangePasswordRequest>,
 ) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
+    require_recent_auth(auth_time, state.clock.now())?;
+
     // Validasi passwords match
     if request.new_password != request.confirm_password {
         return Err((
@@ -186,6 +201,11 @@ pub async fn change_password(
         ));
     }
     
+    // Tolak reuse password terakhir dan ganti password terlalu sering
+    user_repository.check_password_policy(&state.db, user_id, &user.password_hash, &request.new_password, true)
+        .await
+        .map_err(password_policy_error)?;
+    
     // Hash new password
     let new_hash = user_repository.security_service
         .hash_password(&request.new_password)
@@ -194,7 +214,16 @@ pub async fn change_password(
             Json(ErrorResponse::new("Failed to hash password", Some("HASH_ERROR")))
         ))?;
     
-    // Update password
+    let update_error = |e: &dyn std::fmt::Display| {
+        tracing::error!("Failed to update password: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse::new("Failed to update password", Some("UPDATE_ERROR")))
+        )
+    };
+
+    // Update password + simpan hash lama ke riwayat dalam satu transaksi
+    let mut tx = state.db.begin().await.map_err(|e| update_error(&e))?;
     sqlx::query!(
         r#"
         UPDATE users 
@@ -206,15 +235,17 @@ pub async fn change_password(
         new_hash,
         user_id
     )
-    .execute(&state.db)
+    .execute(&mut *tx)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to update password: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ErrorResponse::new("Failed to update password", Some("UPDATE_ERROR")))
-        )
-    })?;
+    .map_err(|e| update_error(&e))?;
+
+    user_repository.record_password_history(&mut tx, user_id, &user.password_hash)
+        .await
+        .map_err(|e| update_error(&e))?;
+    user_repository.revoke_trusted_devices(&mut *tx, user_id)
+        .await
+        .map_err(|e| update_error(&e))?;
+    tx.commit().await.map_err(|e| update_error(&e))?;
     
     // Revoke all tokens (force re-login)
     sqlx::query!(
@@ -243,6 +274,27 @@ pub async fn change_password(
     Ok(Json(AuthResponse::success("Password berhasil diubah. Silakan login kembali")))
 }
 
+/// Error kebijakan password (PASSWORD_REUSED / PASSWORD_CHANGE_TOO_SOON) untuk change & reset password
+pub(crate) fn password_policy_error(e: DatabaseError) -> (StatusCode, Json<ErrorResponse>) {
+    match e {
+        DatabaseError::PasswordReused => (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(&e.to_string(), Some("PASSWORD_REUSED")))
+        ),
+        DatabaseError::PasswordChangeTooSoon(_) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ErrorResponse::new(&e.to_string(), Some("PASSWORD_CHANGE_TOO_SOON")))
+        ),
+        e => {
+            tracing::error!("Failed to check password policy: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new("Failed to check password policy", Some("PASSWORD_POLICY_ERROR")))
+            )
+        }
+    }
+}
+
 /// Handler untuk mendapatkan login history
 /// GET /api/auth/login-history
 pub async fn get_login_history(
@@ -337,12 +389,13 @@ pub async fn send_verification_email(
     sqlx::query!(
         r#"
         INSERT INTO email_verification_tokens (user_id, token_hash, expires_at)
-        VALUES ($1, $2, NOW() + INTERVAL '24 hours')
+        VALUES ($1, $2, $3)
         ON CONFLICT (user_id) DO UPDATE
-        SET token_hash = $2, expires_at = NOW() + INTERVAL '24 hours'
+        SET token_hash = $2, expires_at = $3
         "#,
         user_id,
-        token_hash
+        token_hash,
+        Utc::now() + verification_token_lifetime()
     )
     .execute(&state.db)
     .await
@@ -453,6 +506,48 @@ pub async fn verify_email(
     Ok(Json(AuthResponse::success("Email berhasil diverifikasi")))
 }
 
+/// Handler untuk preferensi notifikasi email user
+/// GET /api/auth/me/notifications
+pub async fn get_my_notification_preferences(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<ApiResponse<NotificationPreferences>>, (StatusCode, Json<ErrorResponse>)> {
+    let preferences = get_notification_preferences(&state.db, user_id).await
+        .map_err(|e| {
+            tracing::error!("Failed to get notification preferences for {}: {}", user_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new("Gagal mengambil preferensi notifikasi", Some("DATABASE_ERROR")))
+            )
+        })?;
+
+    Ok(Json(ApiResponse::ok("Preferensi notifikasi berhasil diambil", preferences)))
+}
+
+/// Handler untuk update preferensi notifikasi (email wajib seperti OTP/reset password tetap dikirim)
+/// PUT /api/auth/me/notifications
+pub async fn update_my_notification_preferences(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Json(request): Json<UpdateNotificationPreferencesRequest>,
+) -> Result<Json<ApiResponse<NotificationPreferences>>, (StatusCode, Json<ErrorResponse>)> {
+    let preferences = update_notification_preferences(&state.db, user_id, &request).await
+        .map_err(|e| {
+            tracing::error!("Failed to update notification preferences for {}: {}", user_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new("Gagal menyimpan preferensi notifikasi", Some("DATABASE_ERROR")))
+            )
+        })?;
+
+    tracing::info!(
+        "Notification preferences user {} diupdate: security={}, receipts={}, marketing={}",
+        user_id, preferences.security_alerts, preferences.order_receipts, preferences.marketing
+    );
+
+    Ok(Json(ApiResponse::ok("Preferensi notifikasi berhasil disimpan", preferences)))
+}
+
 /// Handler untuk mendapatkan aktivitas user sendiri
 /// GET /api/auth/my-activity
 pub async fn get_my_activity(
@@ -467,17 +562,29 @@ pub async fn get_my_activity(
     
     let user_repository = UserRepository::new(get_pepper().as_bytes());
     
-    match user_repository.get_security_activity_feed(
-        &state.db,
+    let filter = SecurityEventFilter {
+        severity: None,
+        event_type: None,
+        user_id: Some(user_id),
+        from: Utc::now() - chrono::Duration::days(30),
+        to: Utc::now(),
+        severity_first: false,
+        page: 1,
         limit,
-        None,
-        Some(user_id)
-    ).await {
-        Ok(activities) => {
+    };
+
+    let (feed, unread) = tokio::join!(
+        user_repository.get_security_activity_feed(&state.db, &filter),
+        user_repository.count_unread_activity(&state.db, user_id, filter.from),
+    );
+
+    match feed.and_then(|(activities, _, _)| unread.map(|unread| (activities, unread))) {
+        Ok((activities, unread_count)) => {
             Ok(Json(serde_json::json!({
                 "success": true,
                 "message": "Your activities retrieved",
                 "total": activities.len(),
+                "unread_count": unread_count,
                 "data": activities
             })))
         }
@@ -491,6 +598,55 @@ pub async fn get_my_activity(
     }
 }
 
+/// Handler untuk menandai aktivitas sendiri sudah dibaca (semua atau daftar ID)
+/// POST /api/auth/me/activity/mark-read
+pub async fn mark_my_activity_read(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Json(request): Json<MarkActivityReadRequest>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, (StatusCode, Json<ErrorResponse>)> {
+    if let Err(errors) = request.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::validation_error(errors))
+        ));
+    }
+
+    let ids = match (request.all, request.ids.as_deref()) {
+        (true, None) => None,
+        (false, Some(ids)) => Some(ids),
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::new("Kirim salah satu: all=true atau daftar ids", Some("VALIDATION_ERROR")))
+            ));
+        }
+    };
+
+    let user_repository = UserRepository::new(get_pepper().as_bytes()).with_clock(state.clock.clone());
+    let since = state.clock.now() - chrono::Duration::days(30);
+
+    let result = match user_repository.mark_activity_read(&state.db, user_id, ids).await {
+        Ok(marked) => user_repository.count_unread_activity(&state.db, user_id, since).await
+            .map(|unread| (marked, unread)),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok((marked, unread_count)) => Ok(Json(ApiResponse::ok(
+            "Aktivitas ditandai sudah dibaca",
+            serde_json::json!({ "marked": marked, "unread_count": unread_count }),
+        ))),
+        Err(e) => {
+            tracing::error!("Failed to mark activities read for {}: {}", user_id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new("Gagal menandai aktivitas", Some("DATABASE_ERROR")))
+            ))
+        }
+    }
+}
+
 // Helper functions
 fn send_email(to: &str, subject: &str, body: &str) {
     tracing::info!(
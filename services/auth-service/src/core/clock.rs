@@ -0,0 +1,69 @@
+// /pdf-bookstore/services/auth-service/src/core/clock.rs
+
+use chrono::{DateTime, Utc};
+
+/// Sumber waktu untuk logic yang bergantung pada waktu (expiry token, OTP, lockout)
+/// Di-inject supaya test bisa memajukan waktu tanpa sleep
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Clock asli berdasarkan waktu sistem
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Clock manual untuk test, waktu hanya berubah lewat `advance` / `set`
+#[cfg(test)]
+#[derive(Debug)]
+pub struct MockClock {
+    now: std::sync::Mutex<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self { now: std::sync::Mutex::new(start) }
+    }
+
+    pub fn advance(&self, duration: chrono::Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now += duration;
+    }
+
+    pub fn set(&self, time: DateTime<Utc>) {
+        *self.now.lock().unwrap() = time;
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn test_mock_clock_advance_and_set() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let clock = MockClock::new(start);
+
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::minutes(5));
+        assert_eq!(clock.now(), start + Duration::minutes(5));
+
+        clock.set(start);
+        assert_eq!(clock.now(), start);
+    }
+}
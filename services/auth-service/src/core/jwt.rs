@@ -1,12 +1,16 @@
 // /pdf-bookstore/services/auth-service/src/core/jwt.rs
 
 
-use jsonwebtoken::{encode, decode, Header, Validation, EncodingKey, DecodingKey, Algorithm};
+use jsonwebtoken::{
+    encode, decode, Header, Validation, EncodingKey, DecodingKey, Algorithm,
+    jwk::{Jwk, JwkSet, PublicKeyUse, ThumbprintHash},
+};
 use uuid::Uuid;
 use chrono::{Utc, Duration};
-use std::env;
+use std::{env, sync::Arc};
 
 use crate::models::{User, Claims, EnhancedClaims, TokenPairResponse};
+use super::clock::{Clock, SystemClock};
 
 
 /// service untuk generate dan verify jwt token 
@@ -14,43 +18,127 @@ pub struct JwtService {
     encoding_key: EncodingKey,
     decoding_key: DecodingKey,
     validation: Validation,
+    /// Header token (alg + kid untuk RS256)
+    header: Header,
+    /// Public key untuk /.well-known/jwks.json, kosong untuk HS256 (secret tidak pernah dipublikasikan)
+    jwks: JwkSet,
     issuer: String,
     audience: String,
+    clock: Arc<dyn Clock>,
 }
 
 impl JwtService {
-    /// Setup JWT service dengan secret dari environment
+    /// Setup JWT service dari environment
+    /// JWT_ALGORITHM: HS256 (default, JWT_SECRET) atau RS256 (JWT_PRIVATE_KEY_PATH + JWT_PUBLIC_KEY_PATH,
+    /// JWT_KEY_ID opsional, default thumbprint RFC 7638 dari public key)
     pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let secret = env::var("JWT_SECRET")
-            .map_err(|_| "JWT_SECRET environment variable not set")?;
-            
-        if secret.len() < 32 {
-            return Err("JWT_SECRET must be at least 32 characters long".into());
-        }
-
         let issuer = env::var("JWT_ISSUER")
             .unwrap_or_else(|_| "bookstore-auth-service".to_string());
         let audience = env::var("JWT_AUDIENCE")
             .unwrap_or_else(|_| "bookstore-app".to_string());
 
-        let mut validation = Validation::new(Algorithm::HS256);
+        let algorithm = env::var("JWT_ALGORITHM").unwrap_or_else(|_| "HS256".to_string());
+        let (encoding_key, decoding_key, key_id) = match algorithm.to_uppercase().as_str() {
+            "HS256" => {
+                let secret = env::var("JWT_SECRET")
+                    .map_err(|_| "JWT_SECRET environment variable not set")?;
+
+                if secret.len() < 32 {
+                    return Err("JWT_SECRET must be at least 32 characters long".into());
+                }
+
+                (EncodingKey::from_secret(secret.as_bytes()), DecodingKey::from_secret(secret.as_bytes()), None)
+            }
+            "RS256" => {
+                let read_pem = |key: &str| -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+                    let path = env::var(key).map_err(|_| format!("{} environment variable not set", key))?;
+                    std::fs::read(&path).map_err(|e| format!("Failed to read {} ({}): {}", key, path, e).into())
+                };
+
+                (
+                    EncodingKey::from_rsa_pem(&read_pem("JWT_PRIVATE_KEY_PATH")?)?,
+                    DecodingKey::from_rsa_pem(&read_pem("JWT_PUBLIC_KEY_PATH")?)?,
+                    env::var("JWT_KEY_ID").ok().filter(|kid| !kid.trim().is_empty()),
+                )
+            }
+            other => return Err(format!("JWT_ALGORITHM {} tidak didukung (HS256 / RS256)", other).into()),
+        };
+
+        Self::from_keys(encoding_key, decoding_key, key_id, issuer, audience)
+    }
+
+    fn from_keys(
+        encoding_key: EncodingKey,
+        decoding_key: DecodingKey,
+        key_id: Option<String>,
+        issuer: String,
+        audience: String,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let algorithm = match encoding_key.family() {
+            jsonwebtoken::AlgorithmFamily::Rsa => Algorithm::RS256,
+            _ => Algorithm::HS256,
+        };
+
+        let mut validation = Validation::new(algorithm);
         validation.set_issuer(&[&issuer]);
         validation.set_audience(&[&audience]);
-        validation.validate_exp = true;
+        // Expiry dicek manual pakai clock service (lihat verify_*), bukan waktu sistem library
+        validation.validate_exp = false;
         validation.leeway = 60;
 
+        let mut header = Header::new(algorithm);
+        let mut jwks = JwkSet { keys: Vec::new() };
+        if algorithm == Algorithm::RS256 {
+            let mut jwk = Jwk::from_encoding_key(&encoding_key, algorithm)?;
+            let kid = key_id.unwrap_or_else(|| jwk.thumbprint(ThumbprintHash::SHA256));
+            jwk.common.key_id = Some(kid.clone());
+            jwk.common.public_key_use = Some(PublicKeyUse::Signature);
+            header.kid = Some(kid);
+            jwks.keys.push(jwk);
+        }
+
         Ok(Self {
-            encoding_key: EncodingKey::from_secret(secret.as_bytes()),
-            decoding_key: DecodingKey::from_secret(secret.as_bytes()),
+            encoding_key,
+            decoding_key,
             validation,
+            header,
+            jwks,
             issuer,
             audience,
+            clock: Arc::new(SystemClock),
         })
     }
 
+    /// Service HS256 dengan secret tetap untuk test modul lain
+    #[cfg(test)]
+    pub fn for_tests(clock: Arc<dyn Clock>) -> Self {
+        let secret = "test-secret-with-at-least-32-characters!!";
+
+        Self::from_keys(
+            EncodingKey::from_secret(secret.as_bytes()),
+            DecodingKey::from_secret(secret.as_bytes()),
+            None,
+            "bookstore-auth-service".to_string(),
+            "bookstore-app".to_string(),
+        )
+        .unwrap()
+        .with_clock(clock)
+    }
+
+    /// Public key set untuk verifikasi lokal di service lain (kosong jika HS256)
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+
+    /// Ganti sumber waktu (dipakai untuk test expiry secara deterministik)
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Generate JWT token untuk user login
     pub fn generate_token(&self, user: &User) -> Result<String, Box<dyn std::error::Error>> {
-        let now = Utc::now();
+        let now = self.clock.now();
         let exp_hours = env::var("JWT_EXPIRES_IN")
             .unwrap_or_else(|_| "24h".to_string())
             .trim_end_matches('h')
@@ -68,9 +156,12 @@ impl JwtService {
             session_id: Some(Uuid::new_v4().to_string()),
             permissions: Some(get_permissions_for_role(&user.role)),
             ip_address: None,
+            token_type: None,
+            scopes: None,
+            auth_time: Some(now.timestamp() as usize),
         };
 
-        encode(&Header::default(), &claims, &self.encoding_key)
+        encode(&self.header, &claims, &self.encoding_key)
             .map_err(|e| e.into())
     }
     
@@ -78,7 +169,7 @@ impl JwtService {
     pub fn verify_token(&self, token: &str) -> Result<Claims, Box<dyn std::error::Error>> {
         let token_data = decode::<Claims>(token, &self.decoding_key, &self.validation)?;
         
-        let now = Utc::now().timestamp() as usize;
+        let now = self.clock.now().timestamp() as usize;
         if token_data.claims.exp <= now {
             return Err("Token has expired".into());
         }
@@ -91,8 +182,10 @@ impl JwtService {
     }
 
     /// Generate token pair (access + refresh) untuk dual token authentication
-    pub fn generate_token_pair(&self, user: &User) -> Result<TokenPairResponse, Box<dyn std::error::Error>> {
-        let now = Utc::now();
+    /// `auth_time` None = user baru saja login, Some = waktu login asli (refresh token)
+    pub fn generate_token_pair(&self, user: &User, auth_time: Option<usize>) -> Result<TokenPairResponse, Box<dyn std::error::Error>> {
+        let now = self.clock.now();
+        let auth_time = auth_time.unwrap_or(now.timestamp() as usize);
         
         // ACCESS TOKEN - 15 menit
         let access_jti = Uuid::new_v4().to_string();
@@ -108,9 +201,11 @@ impl JwtService {
             aud: self.audience.clone(),
             jti: access_jti,
             token_type: "access".to_string(),
+            scopes: None,
+            auth_time: Some(auth_time),
         };
         
-        let access_token = encode(&Header::default(), &access_claims, &self.encoding_key)?;
+        let access_token = encode(&self.header, &access_claims, &self.encoding_key)?;
         
         // REFRESH TOKEN - 7 hari
         let refresh_jti = Uuid::new_v4().to_string();
@@ -125,10 +220,12 @@ impl JwtService {
             iss: self.issuer.clone(),
             aud: self.audience.clone(),
             jti: refresh_jti,
-            token_type: "refresh".to_string(),
+            token_type: REFRESH_TOKEN_TYPE.to_string(),
+            scopes: None,
+            auth_time: Some(auth_time),
         };
         
-        let refresh_token = encode(&Header::default(), &refresh_claims, &self.encoding_key)?;
+        let refresh_token = encode(&self.header, &refresh_claims, &self.encoding_key)?;
         
         Ok(TokenPairResponse {
             access_token,
@@ -142,9 +239,10 @@ impl JwtService {
     pub fn generate_token_with_duration(
         &self,
         user: &User,
-        duration: Duration
+        duration: Duration,
+        auth_time: Option<usize>,
     ) -> Result<String, Box<dyn std::error::Error>> {
-        let now = Utc::now();
+        let now = self.clock.now();
         let exp = (now + duration).timestamp() as usize;
 
         let claims = Claims {
@@ -158,12 +256,43 @@ impl JwtService {
             session_id: Some(Uuid::new_v4().to_string()),
             permissions: Some(get_permissions_for_role(&user.role)),
             ip_address: None,
+            token_type: None,
+            scopes: None,
+            auth_time: Some(auth_time.unwrap_or(now.timestamp() as usize)),
         };
 
-        encode(&Header::default(), &claims, &self.encoding_key)
+        encode(&self.header, &claims, &self.encoding_key)
             .map_err(|e| e.into())
     }
     
+    /// Generate personal access token (long-lived, scoped, revocable)
+    /// `jti` = id row di tabel personal_access_tokens
+    pub fn generate_personal_access_token(
+        &self,
+        user: &User,
+        token_id: Uuid,
+        scopes: &[String],
+        expires_at: chrono::DateTime<Utc>,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let claims = EnhancedClaims {
+            sub: user.id.to_string(),
+            email: user.email.clone(),
+            role: user.role.clone(),
+            exp: expires_at.timestamp() as usize,
+            iat: self.clock.now().timestamp() as usize,
+            iss: self.issuer.clone(),
+            aud: self.audience.clone(),
+            jti: token_id.to_string(),
+            token_type: PAT_TOKEN_TYPE.to_string(),
+            scopes: Some(scopes.to_vec()),
+            // PAT tidak pernah dianggap autentikasi baru (step-up selalu ditolak)
+            auth_time: None,
+        };
+
+        encode(&self.header, &claims, &self.encoding_key)
+            .map_err(|e| e.into())
+    }
+
     /// Verify token dengan blacklist check
     pub async fn verify_token_with_blacklist(
         &self,
@@ -178,7 +307,7 @@ impl JwtService {
         )?;
         
         // Check expiry
-        let now = Utc::now().timestamp() as usize;
+        let now = self.clock.now().timestamp() as usize;
         if token_data.claims.exp <= now {
             return Err("Token has expired".into());
         }
@@ -194,11 +323,60 @@ impl JwtService {
         if blacklisted.count.unwrap_or(0) > 0 {
             return Err("Token has been revoked".into());
         }
+
+        // Personal access token harus masih aktif di database
+        if token_data.claims.token_type == PAT_TOKEN_TYPE {
+            let token_id = Uuid::parse_str(&token_data.claims.jti)?;
+            let token_hash = crate::utils::hash_token(token);
+
+            let active = sqlx::query!(
+                r#"
+                UPDATE personal_access_tokens
+                SET last_used_at = NOW()
+                WHERE id = $1 AND token_hash = $2
+                  AND revoked_at IS NULL AND expires_at > NOW()
+                RETURNING id
+                "#,
+                token_id,
+                token_hash
+            )
+            .fetch_optional(db)
+            .await?;
+
+            if active.is_none() {
+                return Err("Personal access token has been revoked".into());
+            }
+        }
         
         Ok(token_data.claims)
     }
 }
 
+/// Nilai claim `token_type` untuk personal access token
+pub const PAT_TOKEN_TYPE: &str = "pat";
+
+/// Nilai claim `token_type` untuk refresh token
+pub const REFRESH_TOKEN_TYPE: &str = "refresh";
+
+/// Role efektif token: PAT tanpa scope `admin:access` tidak pernah membawa role admin
+/// (`scopes` None = token login biasa, role dari database dipakai apa adanya)
+pub fn effective_role(role: &str, scopes: Option<&[String]>) -> String {
+    match scopes {
+        Some(scopes) if role == "admin" && !scopes.iter().any(|s| s == "admin:access") => "customer".to_string(),
+        _ => role.to_string(),
+    }
+}
+
+/// Scope yang boleh diberikan ke personal access token
+pub const PAT_SCOPES: &[&str] = &[
+    "profile:read",
+    "profile:write",
+    "books:read",
+    "orders:read",
+    "orders:write",
+    "admin:access",
+];
+
 /// Mendapatkan permissions berdasarkan role user
 fn get_permissions_for_role(role: &str) -> Vec<String> {
     match role {
@@ -219,4 +397,121 @@ fn get_permissions_for_role(role: &str) -> Vec<String> {
         ],
         _ => vec!["books:read".to_string()],
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::clock::MockClock;
+    use chrono::TimeZone;
+
+    fn test_service(clock: Arc<dyn Clock>) -> JwtService {
+        JwtService::for_tests(clock)
+    }
+
+    fn test_user() -> User {
+        let now = Utc::now();
+        User {
+            id: Uuid::new_v4(),
+            email: "reader@example.com".to_string(),
+            password_hash: String::new(),
+            full_name: "Test Reader".to_string(),
+            role: "customer".to_string(),
+            is_active: true,
+            email_verified: true,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn test_token_expires_when_clock_advances() {
+        let clock = Arc::new(MockClock::new(Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap()));
+        let service = test_service(clock.clone());
+
+        let token = service
+            .generate_token_with_duration(&test_user(), Duration::minutes(10), None)
+            .unwrap();
+
+        assert!(service.verify_token(&token).is_ok());
+
+        clock.advance(Duration::minutes(9));
+        assert!(service.verify_token(&token).is_ok());
+
+        clock.advance(Duration::minutes(1));
+        assert!(service.verify_token(&token).is_err());
+    }
+
+    #[test]
+    fn test_token_issued_in_future_is_rejected() {
+        let clock = Arc::new(MockClock::new(Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap()));
+        let service = test_service(clock.clone());
+
+        let token = service.generate_token(&test_user()).unwrap();
+
+        clock.set(Utc.with_ymd_and_hms(2029, 12, 31, 23, 0, 0).unwrap());
+        assert!(service.verify_token(&token).is_err());
+    }
+
+    #[test]
+    fn test_auth_time_preserved_on_refresh() {
+        let clock = Arc::new(MockClock::new(Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap()));
+        let service = test_service(clock.clone());
+        let login_at = clock.now().timestamp() as usize;
+
+        let fresh = service.generate_token_pair(&test_user(), None).unwrap();
+        assert_eq!(service.verify_token(&fresh.access_token).unwrap().auth_time, Some(login_at));
+
+        clock.advance(Duration::minutes(30));
+        let refreshed = service.generate_token_pair(&test_user(), Some(login_at)).unwrap();
+        let claims = service.verify_token(&refreshed.access_token).unwrap();
+        assert_eq!(claims.auth_time, Some(login_at));
+        assert!(claims.iat > login_at);
+    }
+
+    #[test]
+    fn test_rs256_token_has_kid_and_matches_jwks() {
+        let clock = Arc::new(MockClock::new(Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap()));
+        let service = JwtService::from_keys(
+            EncodingKey::from_rsa_pem(include_bytes!("testdata/jwt_rs256_private.pem")).unwrap(),
+            DecodingKey::from_rsa_pem(include_bytes!("testdata/jwt_rs256_public.pem")).unwrap(),
+            None,
+            "bookstore-auth-service".to_string(),
+            "bookstore-app".to_string(),
+        )
+        .unwrap()
+        .with_clock(clock);
+
+        let token = service.generate_token(&test_user()).unwrap();
+        let header = jsonwebtoken::decode_header(&token).unwrap();
+        assert_eq!(header.alg, Algorithm::RS256);
+        assert!(service.verify_token(&token).is_ok());
+
+        let jwks = service.jwks();
+        assert_eq!(jwks.keys.len(), 1);
+        let kid = header.kid.expect("kid di header RS256");
+        let jwk = jwks.find(&kid).expect("kid ada di JWKS");
+
+        // Verifikasi lokal seperti service lain: DecodingKey dari JWKS publik
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_issuer(&["bookstore-auth-service"]);
+        validation.set_audience(&["bookstore-app"]);
+        validation.validate_exp = false;
+        let decoded = decode::<Claims>(&token, &DecodingKey::from_jwk(jwk).unwrap(), &validation).unwrap();
+        assert_eq!(decoded.claims.email, "reader@example.com");
+
+        // Token HS256 tidak lolos verifikasi RS256
+        let hs_token = test_service(Arc::new(SystemClock)).generate_token(&test_user()).unwrap();
+        assert!(service.verify_token(&hs_token).is_err());
+        assert!(test_service(Arc::new(SystemClock)).jwks().keys.is_empty());
+    }
+
+    #[test]
+    fn test_admin_pat_without_admin_scope_is_downgraded() {
+        let scopes = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(effective_role("admin", None), "admin");
+        assert_eq!(effective_role("admin", Some(&scopes(&["books:read", "orders:read"]))), "customer");
+        assert_eq!(effective_role("admin", Some(&scopes(&["admin:access"]))), "admin");
+        assert_eq!(effective_role("customer", Some(&scopes(&["admin:access"]))), "customer");
+    }
+}
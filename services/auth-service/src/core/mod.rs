@@ -1,5 +1,11 @@
 // /pdf-bookstore/services/auth-service/src/core/mod.rs
 
 pub mod jwt;
+pub mod clock;
+pub mod session_cookie;
+pub mod totp;
 
-pub use jwt::JwtService;
\ No newline at end of file
+pub use jwt::JwtService;
+pub use clock::{Clock, SystemClock};
+pub use session_cookie::CookieSessionConfig;
+pub use totp::TotpConfig;
\ No newline at end of file
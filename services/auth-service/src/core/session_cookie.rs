@@ -0,0 +1,174 @@
+// /pdf-bookstore/services/auth-service/src/core/session_cookie.rs
+
+use axum::http::{header, HeaderMap, HeaderValue};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use std::env;
+
+pub use service_common::session_cookie::{
+    cookie_sessions_enabled, csrf_valid, read_cookie, requires_csrf,
+    ACCESS_COOKIE, AUTH_MODE_HEADER, CSRF_COOKIE, CSRF_HEADER, REFRESH_COOKIE,
+};
+
+/// Refresh cookie hanya dikirim ke endpoint auth (refresh/logout)
+const REFRESH_COOKIE_PATH: &str = "/api/auth";
+
+/// Mode sesi cookie (opsional, di samping bearer token)
+/// Access/refresh token di cookie HttpOnly, CSRF pakai double-submit: cookie `csrf_token`
+/// (bisa dibaca JS) harus dikirim ulang di header `X-CSRF-Token` untuk method non-GET
+#[derive(Debug, Clone)]
+pub struct CookieSessionConfig {
+    pub enabled: bool,
+    secure: bool,
+    same_site: String,
+    domain: Option<String>,
+}
+
+impl CookieSessionConfig {
+    /// AUTH_COOKIE_ENABLED (default false, opt-in), AUTH_COOKIE_SECURE (default true jika ENVIRONMENT=production),
+    /// AUTH_COOKIE_SAMESITE (Strict/Lax/None, default Strict), AUTH_COOKIE_DOMAIN (default host-only)
+    pub fn from_env() -> Self {
+        let flag = |key: &str, default: bool| {
+            env::var(key).map(|v| v == "true" || v == "1").unwrap_or(default)
+        };
+
+        let production = env::var("ENVIRONMENT").unwrap_or_default() == "production";
+        let mut secure = flag("AUTH_COOKIE_SECURE", production);
+
+        let same_site = match env::var("AUTH_COOKIE_SAMESITE").unwrap_or_default().to_lowercase().as_str() {
+            "lax" => "Lax",
+            "none" => "None",
+            _ => "Strict",
+        }
+        .to_string();
+
+        // Browser menolak SameSite=None tanpa Secure
+        if same_site == "None" && !secure {
+            tracing::warn!("AUTH_COOKIE_SAMESITE=None membutuhkan Secure, cookie dipaksa Secure");
+            secure = true;
+        }
+
+        Self {
+            enabled: cookie_sessions_enabled(),
+            secure,
+            same_site,
+            domain: env::var("AUTH_COOKIE_DOMAIN").ok().filter(|d| !d.trim().is_empty()),
+        }
+    }
+
+    /// Client minta mode cookie lewat flag `use_cookies` di body atau header `X-Auth-Mode: cookie`
+    pub fn wants_cookies(&self, flag: Option<bool>, headers: &HeaderMap) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        flag.unwrap_or_else(|| {
+            headers.get(AUTH_MODE_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|mode| mode.eq_ignore_ascii_case("cookie"))
+        })
+    }
+
+    /// Set-Cookie untuk access, refresh, dan CSRF token baru (CSRF juga dikembalikan di header)
+    pub fn session_headers(&self, access_token: &str, expires_in: i64, refresh_token: &str, refresh_expires_in: i64) -> HeaderMap {
+        let csrf_token = generate_csrf_token();
+
+        let mut headers = HeaderMap::new();
+        for cookie in [
+            self.build_cookie(ACCESS_COOKIE, access_token, "/", expires_in, true),
+            self.build_cookie(REFRESH_COOKIE, refresh_token, REFRESH_COOKIE_PATH, refresh_expires_in, true),
+            self.build_cookie(CSRF_COOKIE, &csrf_token, "/", refresh_expires_in, false),
+        ] {
+            if let Ok(value) = HeaderValue::from_str(&cookie) {
+                headers.append(header::SET_COOKIE, value);
+            }
+        }
+        if let Ok(value) = HeaderValue::from_str(&csrf_token) {
+            headers.insert(CSRF_HEADER, value);
+        }
+
+        headers
+    }
+
+    /// Set-Cookie untuk access token baru saja (re-autentikasi), refresh & CSRF cookie tetap
+    pub fn access_headers(&self, access_token: &str, expires_in: i64) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Ok(value) = HeaderValue::from_str(&self.build_cookie(ACCESS_COOKIE, access_token, "/", expires_in, true)) {
+            headers.append(header::SET_COOKIE, value);
+        }
+        headers
+    }
+
+    /// Set-Cookie yang menghapus semua cookie sesi (logout)
+    pub fn clear_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, path, http_only) in [
+            (ACCESS_COOKIE, "/", true),
+            (REFRESH_COOKIE, REFRESH_COOKIE_PATH, true),
+            (CSRF_COOKIE, "/", false),
+        ] {
+            if let Ok(value) = HeaderValue::from_str(&self.build_cookie(name, "", path, 0, http_only)) {
+                headers.append(header::SET_COOKIE, value);
+            }
+        }
+
+        headers
+    }
+
+    fn build_cookie(&self, name: &str, value: &str, path: &str, max_age: i64, http_only: bool) -> String {
+        let mut cookie = format!("{}={}; Path={}; Max-Age={}; SameSite={}", name, value, path, max_age.max(0), self.same_site);
+        if let Some(domain) = &self.domain {
+            cookie.push_str(&format!("; Domain={}", domain));
+        }
+        if self.secure {
+            cookie.push_str("; Secure");
+        }
+        if http_only {
+            cookie.push_str("; HttpOnly");
+        }
+        cookie
+    }
+}
+
+fn generate_csrf_token() -> String {
+    URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cookie_session_headers_and_csrf() {
+        let config = CookieSessionConfig {
+            enabled: true,
+            secure: true,
+            same_site: "Strict".to_string(),
+            domain: None,
+        };
+
+        let issued = config.session_headers("access", 900, "refresh", 3600);
+        let cookies: Vec<&str> = issued.get_all(header::SET_COOKIE).iter().map(|v| v.to_str().unwrap()).collect();
+        assert_eq!(cookies.len(), 3);
+        assert!(cookies[0].starts_with("access_token=access; Path=/; Max-Age=900; SameSite=Strict"));
+        assert!(cookies[0].ends_with("; Secure; HttpOnly"));
+        assert!(cookies[1].contains("Path=/api/auth"));
+        assert!(!cookies[2].contains("HttpOnly"));
+
+        let csrf = issued[CSRF_HEADER].to_str().unwrap().to_string();
+        let mut request = HeaderMap::new();
+        request.insert(header::COOKIE, HeaderValue::from_str(&format!("access_token=access; csrf_token={}", csrf)).unwrap());
+        assert_eq!(read_cookie(&request, ACCESS_COOKIE).as_deref(), Some("access"));
+        assert!(!csrf_valid(&request));
+
+        request.insert(CSRF_HEADER, HeaderValue::from_str(&csrf).unwrap());
+        assert!(csrf_valid(&request));
+        request.insert(CSRF_HEADER, HeaderValue::from_static("forged"));
+        assert!(!csrf_valid(&request));
+
+        assert!(config.wants_cookies(None, &HeaderMap::from_iter([(
+            axum::http::HeaderName::from_static(AUTH_MODE_HEADER), HeaderValue::from_static("cookie"),
+        )])));
+        assert!(!config.wants_cookies(Some(false), &HeaderMap::new()));
+        assert!(config.clear_headers().get_all(header::SET_COOKIE).iter().all(|v| v.to_str().unwrap().contains("Max-Age=0")));
+    }
+}
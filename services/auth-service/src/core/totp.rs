@@ -0,0 +1,209 @@
+// /pdf-bookstore/services/auth-service/src/core/totp.rs
+
+use aes_gcm::{aead::Aead, Aes256Gcm, KeyInit, Nonce};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::Sha256;
+use std::env;
+
+type HmacSha1 = Hmac<Sha1>;
+type HmacSha256 = Hmac<Sha256>;
+
+/// Parameter default authenticator app (Google Authenticator, Authy, dll)
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECONDS: i64 = 30;
+const SECRET_BYTES: usize = 20;
+const NONCE_BYTES: usize = 12;
+const DEFAULT_ENCRYPTION_KEY: &str = "default_totp_key_change_in_production";
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// TOTP (RFC 6238, HMAC-SHA1, 6 digit, step 30 detik) sebagai alternatif OTP email
+/// Secret disimpan terenkripsi AES-256-GCM, key diturunkan dari TOTP_ENCRYPTION_KEY
+pub struct TotpConfig {
+    pub issuer: String,
+    /// Toleransi clock drift dalam jumlah step (1 = kode sebelum/sesudah masih diterima)
+    pub allowed_drift_steps: i64,
+    cipher: Aes256Gcm,
+}
+
+impl TotpConfig {
+    /// TOTP_ISSUER (default "PDF Bookstore"), TOTP_ALLOWED_DRIFT_STEPS (default 1, maks 5),
+    /// TOTP_ENCRYPTION_KEY (kunci enkripsi secret, wajib di-set saat ENVIRONMENT=production)
+    pub fn from_env() -> Result<Self, String> {
+        let issuer = env::var("TOTP_ISSUER")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .unwrap_or_else(|| "PDF Bookstore".to_string());
+
+        let allowed_drift_steps = env::var("TOTP_ALLOWED_DRIFT_STEPS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(1)
+            .clamp(0, 5);
+
+        let production = env::var("ENVIRONMENT").unwrap_or_default() == "production";
+        let encryption_key = match env::var("TOTP_ENCRYPTION_KEY").ok().filter(|v| !v.trim().is_empty()) {
+            Some(key) if production && (key == DEFAULT_ENCRYPTION_KEY || key.len() < 32) => {
+                return Err("TOTP_ENCRYPTION_KEY minimal 32 karakter dan bukan nilai default di production".to_string());
+            }
+            Some(key) => key,
+            None if production => return Err("TOTP_ENCRYPTION_KEY wajib di-set di production".to_string()),
+            None => {
+                tracing::warn!("TOTP_ENCRYPTION_KEY tidak di-set, memakai key default (hanya untuk development)");
+                DEFAULT_ENCRYPTION_KEY.to_string()
+            }
+        };
+
+        Ok(Self::new(issuer, allowed_drift_steps, encryption_key.as_bytes()))
+    }
+
+    fn new(issuer: String, allowed_drift_steps: i64, encryption_key: &[u8]) -> Self {
+        // Key AES 256-bit diturunkan dari TOTP_ENCRYPTION_KEY (panjang bebas)
+        let mut derive = <HmacSha256 as Mac>::new_from_slice(encryption_key).expect("HMAC menerima key sepanjang apapun");
+        derive.update(b"totp-secret-aes-gcm");
+        let key = derive.finalize().into_bytes();
+
+        Self { issuer, allowed_drift_steps, cipher: Aes256Gcm::new(&key) }
+    }
+
+    /// Secret acak 160-bit (panjang yang direkomendasikan RFC 4226)
+    pub fn generate_secret() -> Vec<u8> {
+        rand::random::<[u8; SECRET_BYTES]>().to_vec()
+    }
+
+    /// URI otpauth:// untuk di-scan authenticator app (isi QR code)
+    pub fn provisioning_uri(&self, secret: &[u8], account: &str) -> String {
+        format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            urlencoding::encode(&self.issuer),
+            urlencoding::encode(account),
+            base32_encode(secret),
+            urlencoding::encode(&self.issuer),
+            TOTP_DIGITS,
+            TOTP_STEP_SECONDS,
+        )
+    }
+
+    /// Cocokkan kode dengan step di sekitar `unix_time`, return step yang cocok
+    /// Caller wajib menolak step <= step terakhir yang dipakai (anti replay)
+    pub fn verify(&self, secret: &[u8], code: &str, unix_time: i64) -> Option<i64> {
+        let code = code.trim();
+        if code.len() != TOTP_DIGITS || !code.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        let current = unix_time.div_euclid(TOTP_STEP_SECONDS);
+        (-self.allowed_drift_steps..=self.allowed_drift_steps)
+            .map(|offset| current + offset)
+            .filter(|step| *step >= 0)
+            .find(|step| constant_time_eq(code_at(secret, *step as u64).as_bytes(), code.as_bytes()))
+    }
+
+    /// Enkripsi secret untuk disimpan di user_totp_secrets.secret_encrypted (nonce || ciphertext+tag)
+    pub fn encrypt_secret(&self, secret: &[u8]) -> String {
+        let nonce = rand::random::<[u8; NONCE_BYTES]>();
+        let ciphertext = self.cipher
+            .encrypt(&Nonce::from(nonce), secret)
+            .expect("AES-GCM encrypt secret pendek tidak gagal");
+
+        let mut output = nonce.to_vec();
+        output.extend_from_slice(&ciphertext);
+        STANDARD.encode(output)
+    }
+
+    /// None jika data rusak atau kunci enkripsi sudah berubah
+    pub fn decrypt_secret(&self, encoded: &str) -> Option<Vec<u8>> {
+        let data = STANDARD.decode(encoded).ok()?;
+        if data.len() <= NONCE_BYTES {
+            return None;
+        }
+
+        let (nonce, ciphertext) = data.split_at(NONCE_BYTES);
+        let nonce: [u8; NONCE_BYTES] = nonce.try_into().ok()?;
+        self.cipher.decrypt(&Nonce::from(nonce), ciphertext).ok()
+    }
+}
+
+/// Kode HOTP (RFC 4226) untuk counter = step TOTP
+fn code_at(secret: &[u8], step: u64) -> String {
+    let mut mac = <HmacSha1 as Mac>::new_from_slice(secret).expect("HMAC menerima key sepanjang apapun");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([hash[offset], hash[offset + 1], hash[offset + 2], hash[offset + 3]]) & 0x7fff_ffff;
+
+    format!("{:0width$}", binary % 10u32.pow(TOTP_DIGITS as u32), width = TOTP_DIGITS)
+}
+
+/// Base32 (RFC 4648) tanpa padding, format secret yang diterima authenticator app
+pub fn base32_encode(data: &[u8]) -> String {
+    let mut output = String::with_capacity(data.len().div_ceil(5) * 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            output.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        output.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    output
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc6238_vectors_and_drift() {
+        // Test vector RFC 6238 (SHA1), 6 digit terakhir dari kode 8 digit
+        let secret = b"12345678901234567890";
+        assert_eq!(code_at(secret, 59 / 30), "287082");
+        assert_eq!(code_at(secret, 1111111109 / 30), "081804");
+        assert_eq!(code_at(secret, 1234567890 / 30), "005924");
+
+        let config = TotpConfig::new("PDF Bookstore".to_string(), 1, b"test-key");
+        let step = 1111111109 / 30;
+        assert_eq!(config.verify(secret, "081804", 1111111109), Some(step));
+        assert_eq!(config.verify(secret, "081804", 1111111109 + 30), Some(step));
+        assert_eq!(config.verify(secret, "081804", 1111111109 + 60), None);
+        assert_eq!(config.verify(secret, "81804", 1111111109), None);
+
+        let strict = TotpConfig::new("PDF Bookstore".to_string(), 0, b"test-key");
+        assert_eq!(strict.verify(secret, "081804", 1111111109 + 30), None);
+
+        assert_eq!(base32_encode(b"12345678901234567890"), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+    }
+
+    #[test]
+    fn test_secret_encryption_roundtrip() {
+        let config = TotpConfig::new("PDF Bookstore".to_string(), 1, b"test-key");
+        let secret = TotpConfig::generate_secret();
+
+        let encrypted = config.encrypt_secret(&secret);
+        assert_ne!(encrypted, config.encrypt_secret(&secret));
+        assert_eq!(config.decrypt_secret(&encrypted), Some(secret));
+
+        let other_key = TotpConfig::new("PDF Bookstore".to_string(), 1, b"other-key");
+        assert_eq!(other_key.decrypt_secret(&encrypted), None);
+
+        let mut tampered = STANDARD.decode(&encrypted).unwrap();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(config.decrypt_secret(&STANDARD.encode(tampered)), None);
+
+        let uri = config.provisioning_uri(b"12345678901234567890", "user@example.com");
+        assert!(uri.starts_with("otpauth://totp/PDF%20Bookstore:user%40example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"));
+    }
+}
@@ -2,5 +2,13 @@
 
 pub mod user_repository;
 pub mod security_service;
+pub mod notification_service;
+#[cfg(test)]
+pub mod test_support;
 
-pub use user_repository::{UserRepository, DatabaseError, SessionInfo};
\ No newline at end of file
+pub use user_repository::{
+    UserRepository, DatabaseError, SessionInfo, RefreshRotation, AccountLockState,
+    EMAIL_PURPOSE_PASSWORD_RESET, EMAIL_PURPOSE_LOGIN_OTP, EMAIL_PURPOSE_VERIFICATION,
+};
+pub use security_service::SecurityEventFilter;
+pub use notification_service::{allows_email, get_notification_preferences, update_notification_preferences};
@@ -0,0 +1,74 @@
+// /pdf-bookstore/services/auth-service/src/db/notification_service.rs
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::models::{EmailCategory, NotificationPreferences, UpdateNotificationPreferencesRequest};
+use super::DatabaseError;
+
+/// Preferensi notifikasi user, default jika belum pernah diatur
+pub async fn get_notification_preferences(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<NotificationPreferences, DatabaseError> {
+    let preferences = sqlx::query_as!(
+        NotificationPreferences,
+        r#"
+        SELECT security_alerts, order_receipts, marketing, updated_at as "updated_at?"
+        FROM notification_preferences
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(preferences.unwrap_or_default())
+}
+
+/// Upsert preferensi, field yang tidak dikirim memakai nilai tersimpan (atau default)
+pub async fn update_notification_preferences(
+    pool: &PgPool,
+    user_id: Uuid,
+    request: &UpdateNotificationPreferencesRequest,
+) -> Result<NotificationPreferences, DatabaseError> {
+    let current = get_notification_preferences(pool, user_id).await?;
+
+    let preferences = sqlx::query_as!(
+        NotificationPreferences,
+        r#"
+        INSERT INTO notification_preferences (user_id, security_alerts, order_receipts, marketing)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (user_id) DO UPDATE
+        SET security_alerts = EXCLUDED.security_alerts,
+            order_receipts = EXCLUDED.order_receipts,
+            marketing = EXCLUDED.marketing,
+            updated_at = NOW()
+        RETURNING security_alerts, order_receipts, marketing, updated_at as "updated_at?"
+        "#,
+        user_id,
+        request.security_alerts.unwrap_or(current.security_alerts),
+        request.order_receipts.unwrap_or(current.order_receipts),
+        request.marketing.unwrap_or(current.marketing)
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(preferences)
+}
+
+/// Cek sebelum mengirim email opsional; jika preferensi gagal dibaca dipakai default
+/// (security alert & receipt tetap terkirim, marketing tidak)
+pub async fn allows_email(pool: &PgPool, user_id: Uuid, category: EmailCategory) -> bool {
+    if category == EmailCategory::Mandatory {
+        return true;
+    }
+
+    match get_notification_preferences(pool, user_id).await {
+        Ok(preferences) => preferences.allows(category),
+        Err(e) => {
+            tracing::warn!("Gagal membaca preferensi notifikasi user {}: {}", user_id, e);
+            NotificationPreferences::default().allows(category)
+        }
+    }
+}
@@ -1,24 +1,64 @@
 // auth-service/src/db/security_service.rs
 
-use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
 use argon2::password_hash::{rand_core::OsRng, SaltString};
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, QueryBuilder, Row};
+use std::env;
+use uuid::Uuid;
 use super::DatabaseError;
+use crate::models::{ActivitySeverity, SeverityCounts};
 
 /// Layanan keamanan untuk handling password
 pub struct SecurityService {
     argon2: Argon2<'static>,
+    params: Params,
     pub(super) pepper: Vec<u8>,
 }
 
 impl SecurityService {
-    /// Membuat instance baru SecurityService
+    /// Membuat instance baru SecurityService dengan parameter Argon2id dari env
     pub fn new(pepper: &[u8]) -> Self {
+        Self::with_params(pepper, argon2_params_from_env())
+    }
+
+    /// Membuat SecurityService dengan parameter Argon2id tertentu
+    pub fn with_params(pepper: &[u8], params: Params) -> Self {
         Self {
-            argon2: Argon2::default(),
+            argon2: Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone()),
+            params,
             pepper: pepper.to_vec(),
         }
     }
 
+    /// Hash perlu di-upgrade jika algoritma/versi/parameter berbeda dari konfigurasi saat ini
+    /// Hash yang tidak bisa di-parse dianggap tidak perlu rehash (verify sudah gagal duluan)
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        let Ok(parsed) = PasswordHash::new(hash) else {
+            return false;
+        };
+
+        let current_params = Params::try_from(&parsed).ok();
+
+        parsed.algorithm != Algorithm::Argon2id.ident()
+            || parsed.version != Some(Version::V0x13.into())
+            || current_params.is_none_or(|p| {
+                p.m_cost() != self.params.m_cost()
+                    || p.t_cost() != self.params.t_cost()
+                    || p.p_cost() != self.params.p_cost()
+            })
+    }
+
+    /// Hash baru dengan parameter saat ini jika hash lama sudah usang
+    /// Dipanggil hanya setelah password terverifikasi
+    pub fn rehash_if_needed(&self, password: &str, hash: &str) -> Result<Option<String>, DatabaseError> {
+        if self.needs_rehash(hash) {
+            self.hash_password(password).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
     /// Hash password dengan pepper
     pub fn hash_password(&self, password: &str) -> Result<String, DatabaseError> {
         let peppered_password = format!("{}{}", password, String::from_utf8_lossy(&self.pepper));
@@ -48,6 +88,18 @@ impl SecurityService {
         Ok(result.is_ok())
     }
 
+    /// Cek apakah password cocok dengan salah satu hash (riwayat password), hash rusak dilewati
+    /// Semua hash selalu diverifikasi (tanpa short-circuit) supaya waktu respons tidak membocorkan posisi yang cocok
+    pub fn matches_any_hash(&self, password: &str, hashes: &[String]) -> bool {
+        let peppered_password = format!("{}{}", password, String::from_utf8_lossy(&self.pepper));
+
+        hashes.iter()
+            .filter_map(|hash| PasswordHash::new(hash).ok())
+            .fold(false, |matched, parsed| {
+                self.argon2.verify_password(peppered_password.as_bytes(), &parsed).is_ok() | matched
+            })
+    }
+
     /// Validasi kekuatan password
     pub fn validate_password_strength(&self, password: &str) -> bool {
         password.len() >= 8 &&
@@ -56,4 +108,264 @@ impl SecurityService {
         password.chars().any(|c| c.is_numeric()) &&
         password.chars().any(|c| "!@#$%^&*()_+-=[]{}|;:,.<>?".contains(c))
     }
-}
\ No newline at end of file
+}
+
+/// Parameter Argon2id dari env (default = rekomendasi OWASP / default crate argon2)
+/// ARGON2_MEMORY_KIB (19456), ARGON2_ITERATIONS (2), ARGON2_PARALLELISM (1)
+fn argon2_params_from_env() -> Params {
+    let read = |key: &str, default: u32| {
+        env::var(key).ok().and_then(|v| v.parse::<u32>().ok()).unwrap_or(default)
+    };
+
+    let memory_kib = read("ARGON2_MEMORY_KIB", Params::DEFAULT_M_COST);
+    let iterations = read("ARGON2_ITERATIONS", Params::DEFAULT_T_COST);
+    let parallelism = read("ARGON2_PARALLELISM", Params::DEFAULT_P_COST);
+
+    Params::new(memory_kib, iterations, parallelism, None).unwrap_or_else(|e| {
+        tracing::warn!(
+            "Parameter Argon2 tidak valid (m={}, t={}, p={}): {}, memakai default",
+            memory_kib, iterations, parallelism, e
+        );
+        Params::default()
+    })
+}
+
+// ========== SECURITY EVENT FEED ==========
+
+/// Severity per event_type di security_events
+/// Event yang tidak terdaftar: gagal = warning, sukses = info
+const EVENT_SEVERITIES: &[(&str, ActivitySeverity)] = &[
+    ("USER_REGISTERED", ActivitySeverity::Info),
+    ("LOGIN_SUCCESS", ActivitySeverity::Info),
+    ("OTP_LOGIN_SUCCESS", ActivitySeverity::Info),
+    ("GOOGLE_OAUTH_LOGIN_SUCCESS", ActivitySeverity::Info),
+    ("SESSION_CREATED", ActivitySeverity::Info),
+    ("LOGOUT", ActivitySeverity::Info),
+    ("LOGIN_FAILED", ActivitySeverity::Warning),
+    ("LOGIN_ATTEMPT_UNKNOWN_EMAIL", ActivitySeverity::Warning),
+    ("FAILED_LOGIN_ATTEMPT_INCREMENTED", ActivitySeverity::Warning),
+    ("ACCOUNT_LOCKED", ActivitySeverity::Warning),
+    ("PASSWORD_CHANGED", ActivitySeverity::Warning),
+    ("EMAIL_RATE_LIMITED", ActivitySeverity::Warning),
+    ("OTP_DELIVERY_FAILED", ActivitySeverity::Warning),
+    ("USER_DELETED", ActivitySeverity::Critical),
+    ("USER_DEACTIVATED_BY_ADMIN", ActivitySeverity::Critical),
+    ("BRUTE_FORCE_DETECTED", ActivitySeverity::Critical),
+    ("SUSPICIOUS_ACTIVITY", ActivitySeverity::Security),
+    ("UNAUTHORIZED_ACCESS", ActivitySeverity::Security),
+    ("IMPOSSIBLE_TRAVEL", ActivitySeverity::Security),
+];
+
+/// Severity untuk satu event (sinkron dengan ekspresi SQL di `severity_sql`)
+pub fn event_severity(event_type: &str, success: bool) -> ActivitySeverity {
+    EVENT_SEVERITIES.iter()
+        .find(|(event, _)| *event == event_type)
+        .map(|(_, severity)| *severity)
+        .unwrap_or(if success { ActivitySeverity::Info } else { ActivitySeverity::Warning })
+}
+
+/// Ekspresi CASE severity dari EVENT_SEVERITIES (konstanta, aman di-embed ke SQL)
+fn severity_sql() -> String {
+    let mut sql = String::from("CASE");
+    for severity in ActivitySeverity::ALL {
+        let events = EVENT_SEVERITIES.iter()
+            .filter(|(_, s)| *s == severity)
+            .map(|(event, _)| format!("'{}'", event))
+            .collect::<Vec<_>>();
+
+        if !events.is_empty() {
+            sql.push_str(&format!(" WHEN se.event_type IN ({}) THEN '{}'", events.join(", "), severity.as_str()));
+        }
+    }
+    sql.push_str(" WHEN NOT se.success THEN 'warning' ELSE 'info' END");
+    sql
+}
+
+/// Filter feed security event (admin dashboard / aktivitas user)
+#[derive(Debug, Clone)]
+pub struct SecurityEventFilter {
+    pub severity: Option<ActivitySeverity>,
+    pub event_type: Option<String>,
+    pub user_id: Option<Uuid>,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Urutkan severity tertinggi dulu (critical > security > warning > info)
+    pub severity_first: bool,
+    pub page: u32,
+    pub limit: u32,
+}
+
+/// Satu baris security_events + info user
+#[derive(Debug)]
+pub struct SecurityEventRecord {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub user_name: Option<String>,
+    pub user_email: Option<String>,
+    pub event_type: String,
+    pub event_data: Option<serde_json::Value>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub success: bool,
+    pub severity: ActivitySeverity,
+    pub created_at: DateTime<Utc>,
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+/// Hasil query feed: halaman event, total sesuai filter, jumlah per severity
+#[derive(Debug)]
+pub struct SecurityEventPage {
+    pub events: Vec<SecurityEventRecord>,
+    pub total: i64,
+    pub severity_counts: SeverityCounts,
+}
+
+/// Query security_events dengan filter, pagination, dan hitungan per severity
+/// Hitungan severity memakai semua filter kecuali severity sendiri (untuk tab dashboard)
+pub async fn query_security_events(
+    pool: &PgPool,
+    filter: &SecurityEventFilter,
+) -> Result<SecurityEventPage, DatabaseError> {
+    let severity = severity_sql();
+
+    let mut count_builder = QueryBuilder::<Postgres>::new(
+        format!("SELECT {} AS severity, COUNT(*) AS count FROM security_events se", severity)
+    );
+    push_event_filters(&mut count_builder, filter, &severity, false);
+    count_builder.push(" GROUP BY 1");
+
+    let mut severity_counts = SeverityCounts::default();
+    for row in count_builder.build().fetch_all(pool).await? {
+        let name: String = row.get("severity");
+        if let Some(s) = ActivitySeverity::parse(&name) {
+            severity_counts.add(s, row.get("count"));
+        }
+    }
+
+    let total = match filter.severity {
+        Some(s) => severity_counts.get(s),
+        None => severity_counts.total(),
+    };
+
+    let mut query_builder = QueryBuilder::<Postgres>::new(
+        r#"
+        SELECT
+            se.id, se.user_id, u.full_name AS user_name, u.email AS user_email,
+            se.event_type, se.event_data, host(se.ip_address) AS ip_address, se.user_agent,
+            se.success, se.created_at, se.read_at
+        FROM security_events se
+        LEFT JOIN users u ON u.id = se.user_id
+        "#
+    );
+    push_event_filters(&mut query_builder, filter, &severity, true);
+
+    if filter.severity_first {
+        query_builder.push(format!(
+            " ORDER BY CASE {} WHEN 'critical' THEN 4 WHEN 'security' THEN 3 WHEN 'warning' THEN 2 ELSE 1 END DESC, se.created_at DESC",
+            severity
+        ));
+    } else {
+        query_builder.push(" ORDER BY se.created_at DESC");
+    }
+
+    let limit = filter.limit.clamp(1, 100);
+    query_builder.push(" LIMIT ").push_bind(limit as i64);
+    query_builder.push(" OFFSET ").push_bind((filter.page.max(1) - 1) as i64 * limit as i64);
+
+    let events = query_builder.build().fetch_all(pool).await?
+        .into_iter()
+        .map(|row| {
+            let event_type: String = row.get("event_type");
+            let success: bool = row.get("success");
+            SecurityEventRecord {
+                id: row.get("id"),
+                user_id: row.get("user_id"),
+                user_name: row.get("user_name"),
+                user_email: row.get("user_email"),
+                severity: event_severity(&event_type, success),
+                event_type,
+                event_data: row.get("event_data"),
+                ip_address: row.get("ip_address"),
+                user_agent: row.get("user_agent"),
+                success,
+                created_at: row.get("created_at"),
+                read_at: row.get("read_at"),
+            }
+        })
+        .collect();
+
+    Ok(SecurityEventPage { events, total, severity_counts })
+}
+
+fn push_event_filters(
+    builder: &mut QueryBuilder<'_, Postgres>,
+    filter: &SecurityEventFilter,
+    severity_expr: &str,
+    with_severity: bool,
+) {
+    builder.push(" WHERE se.created_at >= ").push_bind(filter.from);
+    builder.push(" AND se.created_at < ").push_bind(filter.to);
+
+    if let Some(event_type) = &filter.event_type {
+        builder.push(" AND se.event_type = ").push_bind(event_type.clone());
+    }
+    if let Some(user_id) = filter.user_id {
+        builder.push(" AND se.user_id = ").push_bind(user_id);
+    }
+    if let (true, Some(severity)) = (with_severity, filter.severity) {
+        builder.push(format!(" AND ({}) = ", severity_expr)).push_bind(severity.as_str());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(m: u32, t: u32) -> Params {
+        Params::new(m, t, 1, None).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_transparent_rehash_upgrades_old_params() {
+        let old_service = SecurityService::with_params(b"pepper", params(8192, 1));
+        let old_hash = old_service.hash_password("Rahasia123!").unwrap();
+
+        let service = SecurityService::with_params(b"pepper", params(16384, 2));
+        assert!(service.verify_password("Rahasia123!", &old_hash).await.unwrap());
+        assert!(service.needs_rehash(&old_hash));
+
+        let new_hash = service.rehash_if_needed("Rahasia123!", &old_hash).unwrap().unwrap();
+        assert!(new_hash.contains("m=16384,t=2,p=1"));
+        assert!(!service.needs_rehash(&new_hash));
+        assert!(service.rehash_if_needed("Rahasia123!", &new_hash).unwrap().is_none());
+        assert!(service.verify_password("Rahasia123!", &new_hash).await.unwrap());
+    }
+
+    #[test]
+    fn test_matches_any_hash_detects_reuse() {
+        let service = SecurityService::with_params(b"pepper", params(8192, 1));
+        let history = vec![
+            service.hash_password("LamaSekali1!").unwrap(),
+            "bukan-hash-argon2".to_string(),
+            service.hash_password("Lama123!").unwrap(),
+        ];
+
+        assert!(service.matches_any_hash("Lama123!", &history));
+        assert!(!service.matches_any_hash("Baru123!", &history));
+        assert!(!service.matches_any_hash("Lama123!", &[]));
+    }
+
+    #[test]
+    fn test_event_severity_classification() {
+        assert_eq!(event_severity("LOGIN_FAILED", false), ActivitySeverity::Warning);
+        assert_eq!(event_severity("ACCOUNT_LOCKED", true), ActivitySeverity::Warning);
+        assert_eq!(event_severity("OTP_LOGIN_SUCCESS", true), ActivitySeverity::Info);
+        assert_eq!(event_severity("SUSPICIOUS_ACTIVITY", true), ActivitySeverity::Security);
+        assert_eq!(event_severity("SOMETHING_NEW", false), ActivitySeverity::Warning);
+        assert_eq!(event_severity("SOMETHING_NEW", true), ActivitySeverity::Info);
+
+        let sql = severity_sql();
+        assert!(sql.contains("'OTP_LOGIN_SUCCESS'"));
+        assert!(sql.ends_with("ELSE 'info' END"));
+    }
+}
@@ -0,0 +1,16 @@
+// /pdf-bookstore/services/auth-service/src/db/test_support.rs
+
+use uuid::Uuid;
+
+use crate::models::RegisterRequest;
+
+pub use service_common::db_connect::test_pool;
+
+/// Register user test dengan email unik `<prefix>-<uuid>@example.com`
+pub fn register_request(prefix: &str) -> RegisterRequest {
+    RegisterRequest {
+        email: format!("{}-{}@example.com", prefix, Uuid::new_v4()),
+        password: "Rahasia123!".to_string(),
+        full_name: format!("{} test", prefix),
+    }
+}
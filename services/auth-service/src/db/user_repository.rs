@@ -2,13 +2,16 @@
 
 use sqlx::{PgPool, Row, Postgres, QueryBuilder};
 use uuid::Uuid;
-use chrono::{Utc, Datelike};
+use chrono::{DateTime, Utc, Datelike};
 use sha2::{Sha256, Digest};
-use std::net::IpAddr;
+use std::{net::IpAddr, sync::Arc};
+use serde::Serialize;
 use thiserror::Error;
 
-use crate::models::{User, RegisterRequest, AdminUserStats, AdminUserProfile, AdminPaginationMeta, UserActivity, ActivitySeverity};
-use super::security_service::SecurityService;
+use crate::core::{Clock, SystemClock};
+
+use crate::models::{User, RegisterRequest, AdminUserStats, AdminUserProfile, AdminPaginationMeta, UserActivity, ActivitySeverity, SeverityCounts, ActiveSession};
+use super::security_service::{query_security_events, SecurityEventFilter, SecurityService};
 
 #[derive(Error, Debug)]
 pub enum DatabaseError {
@@ -34,6 +37,47 @@ pub enum DatabaseError {
     AdminAccessDenied,
     #[error("Invalid query parameters")]
     InvalidQuery,
+    #[error("Password baru pernah dipakai sebelumnya")]
+    PasswordReused,
+    #[error("Password baru saja diubah, coba lagi dalam {0} menit")]
+    PasswordChangeTooSoon(i64),
+}
+
+fn password_env(key: &str, default: i64) -> i64 {
+    std::env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+/// Purpose email untuk rate limit per email
+pub const EMAIL_PURPOSE_PASSWORD_RESET: &str = "password_reset";
+pub const EMAIL_PURPOSE_LOGIN_OTP: &str = "login_otp";
+pub const EMAIL_PURPOSE_VERIFICATION: &str = "email_verification";
+
+/// Secret TOTP tersimpan (lihat core::totp)
+pub struct TotpSecretRecord {
+    pub secret_encrypted: String,
+    pub confirmed: bool,
+}
+
+/// Jumlah LOGIN_FAILED dalam 1 jam yang membuat akun terkunci
+const ACCOUNT_LOCK_THRESHOLD: i64 = 10;
+
+/// Status lockout akun (dihitung dari security_events, bukan kolom counter)
+#[derive(Debug, Serialize)]
+pub struct AccountLockState {
+    pub user_id: Uuid,
+    pub email: String,
+    pub locked: bool,
+    pub failed_attempts: i64,
+    pub failed_login_reset_at: Option<DateTime<Utc>>,
+}
+
+/// Hasil rotasi refresh token
+#[derive(Debug, PartialEq, Eq)]
+pub enum RefreshRotation {
+    Rotated,
+    /// Token lama sudah pernah di-revoke (dipakai ulang), seluruh family ikut di-revoke
+    ReuseDetected { token_family: Uuid, revoked: u64 },
+    NotFound,
 }
 
 /// Informasi sesi untuk tracking login user
@@ -45,6 +89,7 @@ pub struct SessionInfo {
 /// Repository untuk operasi database terkait user
 pub struct UserRepository {
     pub security_service: SecurityService,
+    clock: Arc<dyn Clock>,
 }
 
 impl UserRepository {
@@ -52,9 +97,16 @@ impl UserRepository {
     pub fn new(pepper: &[u8]) -> Self {
         Self {
             security_service: SecurityService::new(pepper),
+            clock: Arc::new(SystemClock),
         }
     }
 
+    /// Ganti sumber waktu untuk window rate limit / lockout
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Membuat user baru di database
     pub async fn create_user(
         &self,
@@ -94,8 +146,9 @@ impl UserRepository {
         // Mulai transaksi database
         let mut tx = pool.begin().await?;
         
-        // Insert user baru
-        let user = sqlx::query(
+        // Insert user baru, unique constraint email jadi penentu akhir saat registrasi bersamaan
+        // (pre-check di atas bisa lolos untuk dua request sekaligus)
+        let inserted = sqlx::query(
             r#"
             INSERT INTO users (email, password_hash, full_name, role)
             VALUES ($1, $2, $3, 'customer')
@@ -107,8 +160,26 @@ impl UserRepository {
         .bind(&password_hash)
         .bind(request.full_name.trim())
         .fetch_one(&mut *tx)
-        .await
-        .map(|row| User {
+        .await;
+
+        let row = match inserted {
+            Ok(row) => row,
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                drop(tx);
+                self.log_security_event(
+                    pool,
+                    None,
+                    "REGISTRATION_ATTEMPT_EXISTING_EMAIL",
+                    serde_json::json!({ "email": normalized_email, "concurrent": true }),
+                    false,
+                ).await?;
+
+                return Err(DatabaseError::EmailExists);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let user = User {
             id: row.get("id"),
             email: row.get("email"),
             password_hash: row.get("password_hash"),
@@ -118,7 +189,7 @@ impl UserRepository {
             email_verified: row.get::<Option<bool>, _>("email_verified").unwrap_or(false),
             created_at: row.get::<Option<chrono::DateTime<Utc>>, _>("created_at").unwrap_or_else(|| Utc::now()),
             updated_at: row.get::<Option<chrono::DateTime<Utc>>, _>("updated_at").unwrap_or_else(|| Utc::now()),
-        })?;
+        };
 
         // Log pembuatan user berhasil
         self.log_security_event(
@@ -268,32 +339,60 @@ impl UserRepository {
             self.increment_failed_login_attempts(pool, user_id).await?;
         } else {
             self.reset_failed_login_attempts(pool, user_id).await?;
+            self.upgrade_password_hash(pool, user_id, password, &user.password_hash).await;
         }
 
         Ok(is_valid)
     }
 
-    /// Validasi session dan get user
+    /// Upgrade hash ke parameter Argon2 terbaru setelah login sukses
+    /// Gagal upgrade tidak menggagalkan login; update bersyarat agar tidak menimpa ganti password paralel
+    async fn upgrade_password_hash(&self, pool: &PgPool, user_id: Uuid, password: &str, current_hash: &str) {
+        let new_hash = match self.security_service.rehash_if_needed(password, current_hash) {
+            Ok(Some(hash)) => hash,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::warn!("Gagal rehash password user {}: {}", user_id, e);
+                return;
+            }
+        };
+
+        match sqlx::query!(
+            "UPDATE users SET password_hash = $1, updated_at = NOW() WHERE id = $2 AND password_hash = $3",
+            new_hash,
+            user_id,
+            current_hash
+        )
+        .execute(pool)
+        .await
+        {
+            Ok(_) => tracing::info!("Password hash user {} di-upgrade ke parameter terbaru", user_id),
+            Err(e) => tracing::warn!("Gagal menyimpan rehash password user {}: {}", user_id, e),
+        }
+    }
+
+    /// Validasi session dan get user beserta waktu expiry session
     pub async fn validate_session_and_get_user(
         &self,
         pool: &PgPool,
         session_token: &str,
-    ) -> Result<User, DatabaseError> {
+    ) -> Result<(User, DateTime<Utc>), DatabaseError> {
         let token_hash = self.hash_session_token(session_token);
         
         let result = sqlx::query!(
             r#"
             SELECT u.id, u.email, u.password_hash, u.full_name, u.role, 
                 u.is_active, u.email_verified, u.created_at, u.updated_at,
-                s.device_info, s.ip_address
+                s.device_info, s.ip_address, s.expires_at
             FROM sessions s  
             JOIN users u ON u.id = s.user_id
             WHERE s.session_token = $1  
-            AND s.expires_at > NOW()
+            AND s.expires_at > $2
             AND s.is_active = true
             AND u.is_active = true
             "#,
-            token_hash
+            token_hash,
+            self.clock.now()
         )
         .fetch_optional(pool)
         .await?;
@@ -307,7 +406,7 @@ impl UserRepository {
                 .execute(pool)
                 .await?;
                 
-                Ok(User {
+                let user = User {
                     id: row.id,
                     email: row.email,
                     password_hash: row.password_hash,
@@ -317,7 +416,9 @@ impl UserRepository {
                     email_verified: row.email_verified.unwrap_or(false),
                     created_at: row.created_at.unwrap_or(Utc::now()),
                     updated_at: row.updated_at.unwrap_or(Utc::now()),
-                })
+                };
+
+                Ok((user, row.expires_at))
             }
             None => Err(DatabaseError::InvalidSession),
         }
@@ -675,6 +776,7 @@ impl UserRepository {
                 metadata: None,
                 severity,
                 location: None,
+                read_at: None,
             };
             activities.push(activity);
         }
@@ -686,105 +788,205 @@ impl UserRepository {
         Ok(activities)
     }
 
+    /// Feed security event dengan filter + pagination (lihat `security_service::query_security_events`)
+    /// Return halaman aktivitas, total sesuai filter, dan jumlah per severity
     pub async fn get_security_activity_feed(
         &self,
         pool: &PgPool,
-        limit: u32,
-        severity_filter: Option<&str>,
-        user_filter: Option<Uuid>,
-    ) -> Result<Vec<UserActivity>, DatabaseError> {
-        let limit = std::cmp::min(limit, 100) as i64;
-        
-        let mut query_str = r#"
-            SELECT 
-                se.id,
-                se.user_id,
-                u.full_name as user_name,
-                u.email as user_email,
-                se.event_type,
-                se.event_data,
-                se.success,
-                se.created_at
-            FROM security_events se
-            LEFT JOIN users u ON u.id = se.user_id
-            WHERE se.created_at >= NOW() - INTERVAL '30 days'
-        "#.to_string();
-        
-        if user_filter.is_some() {
-            query_str.push_str(" AND se.user_id = $2");
+        filter: &SecurityEventFilter,
+    ) -> Result<(Vec<UserActivity>, i64, SeverityCounts), DatabaseError> {
+        let page = query_security_events(pool, filter).await?;
+
+        let activities = page.events.into_iter()
+            .map(|event| {
+                let ip_address = event.event_data.as_ref()
+                    .and_then(|d| d["ip"].as_str())
+                    .map(|s| s.to_string())
+                    .or(event.ip_address);
+
+                let user_agent = event.event_data.as_ref()
+                    .and_then(|d| d["user_agent"].as_str())
+                    .map(|s| s.to_string())
+                    .or(event.user_agent);
+
+                UserActivity {
+                    id: Some(event.id),
+                    user_id: event.user_id.unwrap_or_else(Uuid::nil),
+                    user_name: event.user_name.unwrap_or_else(|| "Unknown".to_string()),
+                    user_email: event.user_email.unwrap_or_else(|| "Unknown".to_string()),
+                    description: self.get_event_description(&event.event_type, event.success),
+                    activity_type: event.event_type,
+                    ip_address,
+                    user_agent,
+                    timestamp: event.created_at,
+                    resource_type: None,
+                    resource_id: None,
+                    session_id: None,
+                    metadata: event.event_data,
+                    severity: event.severity,
+                    location: None,
+                    read_at: event.read_at,
+                }
+            })
+            .collect();
+
+        Ok((activities, page.total, page.severity_counts))
+    }
+
+    /// Cek kebijakan ganti password: reuse PASSWORD_HISTORY_SIZE password terakhir (default 5, termasuk yang aktif)
+    /// dan jarak minimal PASSWORD_MIN_CHANGE_INTERVAL_MINUTES (default 1440) jika `enforce_interval`
+    /// Nilai 0 menonaktifkan masing-masing aturan
+    pub async fn check_password_policy(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        current_hash: &str,
+        new_password: &str,
+        enforce_interval: bool,
+    ) -> Result<(), DatabaseError> {
+        let min_interval = password_env("PASSWORD_MIN_CHANGE_INTERVAL_MINUTES", 1440);
+        if enforce_interval && min_interval > 0 {
+            let last_change = sqlx::query_scalar!(
+                "SELECT last_password_change FROM users WHERE id = $1",
+                user_id
+            )
+            .fetch_optional(pool)
+            .await?
+            .flatten();
+
+            if let Some(last_change) = last_change {
+                let next_allowed = last_change + chrono::Duration::minutes(min_interval);
+                let now = self.clock.now();
+                if now < next_allowed {
+                    let remaining = (next_allowed - now).num_seconds();
+                    return Err(DatabaseError::PasswordChangeTooSoon((remaining + 59) / 60));
+                }
+            }
         }
-        
-        query_str.push_str(" ORDER BY se.created_at DESC LIMIT $1");
-        
-        let rows = if let Some(user_id) = user_filter {
-            sqlx::query(&query_str)
-                .bind(limit)
-                .bind(user_id)
-                .fetch_all(pool)
-                .await?
-        } else {
-            sqlx::query(&query_str)
-                .bind(limit)
-                .fetch_all(pool)
-                .await?
-        };
-        
-        let mut activities = Vec::new();
-        for row in rows {
-            let event_type: String = row.get("event_type");
-            let success: bool = row.get("success");
-            let event_data: Option<serde_json::Value> = row.get("event_data");
-            
-            let severity = match event_type.as_str() {
-                "USER_REGISTERED" | "LOGIN_SUCCESS" | "SESSION_CREATED" => ActivitySeverity::Info,
-                "LOGIN_FAILED" | "PASSWORD_CHANGED" => ActivitySeverity::Warning,
-                "ACCOUNT_LOCKED" | "USER_DELETED" => ActivitySeverity::Critical,
-                "SUSPICIOUS_ACTIVITY" | "UNAUTHORIZED_ACCESS" => ActivitySeverity::Security,
-                _ if !success => ActivitySeverity::Warning,
-                _ => ActivitySeverity::Info,
-            };
-            
-            let description = self.get_event_description(&event_type, success);
-            
-            let ip_address = event_data.as_ref()
-                .and_then(|d| d["ip"].as_str())
-                .map(|s| s.to_string());
-            
-            let user_agent = event_data.as_ref()
-                .and_then(|d| d["user_agent"].as_str())
-                .map(|s| s.to_string());
-            
-            let activity = UserActivity {
-                id: Some(row.get("id")),
-                user_id: row.get("user_id"),
-                user_name: row.try_get("user_name").ok().unwrap_or_else(|| "Unknown".to_string()),
-                user_email: row.try_get("user_email").ok().unwrap_or_else(|| "Unknown".to_string()),
-                activity_type: event_type,
-                description,
-                ip_address,
-                user_agent,
-                timestamp: row.get("created_at"),
-                resource_type: None,
-                resource_id: None,
-                session_id: None,
-                metadata: event_data,
-                severity,
-                location: None,
-            };
-            activities.push(activity);
+
+        let history_size = password_env("PASSWORD_HISTORY_SIZE", 5);
+        if history_size <= 0 {
+            return Ok(());
         }
-        
-        if let Some(severity_filter) = severity_filter {
-            activities.retain(|a| match severity_filter {
-                "info" => matches!(a.severity, ActivitySeverity::Info),
-                "warning" => matches!(a.severity, ActivitySeverity::Warning),
-                "critical" => matches!(a.severity, ActivitySeverity::Critical),
-                "security" => matches!(a.severity, ActivitySeverity::Security),
-                _ => true,
-            });
+
+        let mut hashes = sqlx::query_scalar!(
+            r#"
+            SELECT password_hash FROM password_history
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+            "#,
+            user_id,
+            history_size - 1
+        )
+        .fetch_all(pool)
+        .await?;
+        hashes.insert(0, current_hash.to_string());
+
+        if self.security_service.matches_any_hash(new_password, &hashes) {
+            return Err(DatabaseError::PasswordReused);
         }
-        
-        Ok(activities)
+
+        Ok(())
+    }
+
+    /// Simpan hash password lama ke riwayat lalu prune row di luar PASSWORD_HISTORY_SIZE - 1
+    /// (password aktif sudah tersimpan di users.password_hash)
+    pub async fn record_password_history(
+        &self,
+        conn: &mut sqlx::PgConnection,
+        user_id: Uuid,
+        old_hash: &str,
+    ) -> Result<(), DatabaseError> {
+        let keep = password_env("PASSWORD_HISTORY_SIZE", 5) - 1;
+        if keep > 0 {
+            sqlx::query!(
+                "INSERT INTO password_history (user_id, password_hash) VALUES ($1, $2)",
+                user_id,
+                old_hash
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        sqlx::query!(
+            r#"
+            DELETE FROM password_history
+            WHERE user_id = $1 AND id NOT IN (
+                SELECT id FROM password_history
+                WHERE user_id = $1
+                ORDER BY created_at DESC
+                LIMIT $2
+            )
+            "#,
+            user_id,
+            keep.max(0)
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Cek dan catat pengiriman email sensitif (reset password / login OTP / verifikasi) per email
+    /// Return false jika limit terlampaui, email tidak boleh dikirim
+    pub async fn allow_sensitive_email(
+        &self,
+        pool: &PgPool,
+        email: &str,
+        purpose: &str,
+    ) -> Result<bool, DatabaseError> {
+        let env_i64 = |key: &str, default: i64| {
+            std::env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        };
+
+        let max_sends = match purpose {
+            EMAIL_PURPOSE_LOGIN_OTP => env_i64("EMAIL_RATE_LIMIT_LOGIN_OTP", 5),
+            EMAIL_PURPOSE_VERIFICATION => env_i64("EMAIL_RATE_LIMIT_VERIFICATION", 3),
+            _ => env_i64("EMAIL_RATE_LIMIT_PASSWORD_RESET", 3),
+        };
+        let window_start = self.clock.now()
+            - chrono::Duration::minutes(env_i64("EMAIL_RATE_LIMIT_WINDOW_MINUTES", 60));
+
+        // Lock per email + purpose: request paralel menunggu sampai log sebelumnya ter-commit,
+        // sehingga hitung dan insert berjalan atomik (tidak ada yang lolos melewati limit)
+        let mut tx = pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1), hashtext($2))")
+            .bind(email)
+            .bind(purpose)
+            .execute(&mut *tx)
+            .await?;
+
+        let suppressed = sqlx::query_scalar!(
+            r#"
+            INSERT INTO email_send_log (email, purpose, suppressed)
+            SELECT $1::varchar, $2::varchar, $4::bigint > 0 AND COUNT(*) >= $4::bigint
+            FROM email_send_log
+            WHERE email = $1::varchar AND purpose = $2::varchar AND suppressed = false AND created_at > $3
+            RETURNING suppressed
+            "#,
+            email,
+            purpose,
+            window_start,
+            max_sends
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        let allowed = !suppressed;
+
+        if !allowed {
+            self.log_security_event(
+                pool,
+                None,
+                "EMAIL_RATE_LIMITED",
+                serde_json::json!({"email": email, "purpose": purpose}),
+                false,
+            ).await?;
+        }
+
+        Ok(allowed)
     }
 
     // ========== HELPER METHODS ==========
@@ -795,7 +997,7 @@ impl UserRepository {
         pool: &PgPool,
         identifier: &str,
     ) -> Result<bool, DatabaseError> {
-        let now = Utc::now();
+        let now = self.clock.now();
         let window_start = now - chrono::Duration::minutes(15); // Jendela 15 menit
         
         // Hitung percobaan gagal dalam jendela waktu
@@ -823,25 +1025,65 @@ impl UserRepository {
         pool: &PgPool,
         user_id: Uuid,
     ) -> Result<bool, DatabaseError> {
-        let lock_window = Utc::now() - chrono::Duration::hours(1); // Jendela 1 jam
-        
-        // Hitung percobaan gagal dalam jendela waktu
-        let failed_attempts = sqlx::query!(
+        Ok(self.get_account_lock_state(pool, user_id).await?.locked)
+    }
+
+    /// Status lockout akun: LOGIN_FAILED dalam jendela 1 jam, dihitung sejak reset terakhir oleh admin
+    pub async fn get_account_lock_state(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+    ) -> Result<AccountLockState, DatabaseError> {
+        let lock_window = self.clock.now() - chrono::Duration::hours(1); // Jendela 1 jam
+
+        let row = sqlx::query!(
             r#"
-            SELECT COUNT(*) as count 
-            FROM security_events 
-            WHERE user_id = $1 
-                AND event_type = 'LOGIN_FAILED' 
-                AND created_at > $2
+            SELECT u.id, u.email, u.failed_login_reset_at,
+                   (SELECT COUNT(*) FROM security_events e
+                    WHERE e.user_id = u.id
+                      AND e.event_type = 'LOGIN_FAILED'
+                      AND e.created_at > $2
+                      AND (u.failed_login_reset_at IS NULL OR e.created_at > u.failed_login_reset_at)
+                   ) AS "failed_attempts!"
+            FROM users u
+            WHERE u.id = $1
             "#,
             user_id,
             lock_window
         )
-        .fetch_one(pool)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(DatabaseError::UserNotFound)?;
+
+        Ok(AccountLockState {
+            user_id: row.id,
+            email: row.email,
+            // Kunci akun jika >= 10 percobaan gagal
+            locked: row.failed_attempts >= ACCOUNT_LOCK_THRESHOLD,
+            failed_attempts: row.failed_attempts,
+            failed_login_reset_at: row.failed_login_reset_at,
+        })
+    }
+
+    /// Reset counter percobaan gagal (admin unlock), idempotent untuk akun yang tidak terkunci
+    /// Return (status sebelum unlock, status sesudah unlock)
+    pub async fn unlock_account(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+    ) -> Result<(AccountLockState, AccountLockState), DatabaseError> {
+        let before = self.get_account_lock_state(pool, user_id).await?;
+
+        sqlx::query!(
+            "UPDATE users SET failed_login_reset_at = $2, updated_at = NOW() WHERE id = $1",
+            user_id,
+            self.clock.now()
+        )
+        .execute(pool)
         .await?;
 
-        // Kunci akun jika >= 10 percobaan gagal
-        Ok(failed_attempts.count.unwrap_or(0) >= 10)
+        let after = self.get_account_lock_state(pool, user_id).await?;
+        Ok((before, after))
     }
 
     /// Tambah counter percobaan login gagal
@@ -903,6 +1145,405 @@ impl UserRepository {
         Ok(())
     }
 
+    /// Status TOTP user (users.totp_enabled)
+    pub async fn is_totp_enabled(&self, pool: &PgPool, user_id: Uuid) -> Result<bool, DatabaseError> {
+        let enabled = sqlx::query_scalar!("SELECT totp_enabled FROM users WHERE id = $1", user_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or(DatabaseError::UserNotFound)?;
+
+        Ok(enabled)
+    }
+
+    /// Simpan secret TOTP baru (belum aktif), enrollment yang belum dikonfirmasi boleh diulang
+    /// Return false jika TOTP user sudah aktif
+    pub async fn store_pending_totp_secret(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        secret_encrypted: &str,
+    ) -> Result<bool, DatabaseError> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_totp_secrets (user_id, secret_encrypted)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE
+            SET secret_encrypted = $2, last_used_step = NULL, updated_at = NOW()
+            WHERE user_totp_secrets.confirmed_at IS NULL
+            "#,
+            user_id,
+            secret_encrypted
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Secret TOTP user (terenkripsi) beserta status konfirmasinya
+    pub async fn get_totp_secret(&self, pool: &PgPool, user_id: Uuid) -> Result<Option<TotpSecretRecord>, DatabaseError> {
+        let record = sqlx::query_as!(
+            TotpSecretRecord,
+            r#"
+            SELECT secret_encrypted, confirmed_at IS NOT NULL AS "confirmed!"
+            FROM user_totp_secrets
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// Tandai step TOTP sudah dipakai, false jika step yang sama / lebih baru sudah pernah dipakai (replay)
+    pub async fn consume_totp_step<'e, E>(&self, executor: E, user_id: Uuid, step: i64) -> Result<bool, DatabaseError>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_totp_secrets
+            SET last_used_step = $2, updated_at = NOW()
+            WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+            user_id,
+            step
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Konfirmasi enrollment: aktifkan TOTP user dengan kode pertama yang valid
+    pub async fn confirm_totp(&self, pool: &PgPool, user_id: Uuid, step: i64) -> Result<bool, DatabaseError> {
+        let mut tx = pool.begin().await?;
+
+        if !self.consume_totp_step(&mut *tx, user_id, step).await? {
+            return Ok(false);
+        }
+
+        let confirmed = sqlx::query!(
+            "UPDATE user_totp_secrets SET confirmed_at = NOW() WHERE user_id = $1 AND confirmed_at IS NULL",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        if confirmed.rows_affected() != 1 {
+            return Ok(false);
+        }
+
+        sqlx::query!("UPDATE users SET totp_enabled = TRUE, updated_at = NOW() WHERE id = $1", user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Cek apakah device_fingerprint pernah menyelesaikan login (status 'success') untuk user ini
+    /// Langkah password saja ('password_success') tidak dihitung supaya OTP yang gagal tidak membuat device "dikenal"
+    pub async fn is_known_login_device(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        device_fingerprint: &str,
+    ) -> Result<bool, DatabaseError> {
+        let known = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM login_history
+                WHERE user_id = $1 AND device_fingerprint = $2 AND login_status = 'success'
+            ) AS "known!"
+            "#,
+            user_id,
+            device_fingerprint
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(known)
+    }
+
+    /// Simpan trusted device (hash token) yang berlaku `lifetime` dari sekarang
+    pub async fn add_trusted_device(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        device_fingerprint: &str,
+        token_hash: &str,
+        device_info: Option<String>,
+        lifetime: chrono::Duration,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO trusted_devices (user_id, device_fingerprint, token_hash, device_info, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            user_id,
+            device_fingerprint,
+            token_hash,
+            device_info,
+            self.clock.now() + lifetime
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Pakai trusted device untuk login tanpa OTP: user, fingerprint, dan token harus cocok,
+    /// belum dicabut dan belum expired. last_used_at ikut di-update
+    pub async fn use_trusted_device(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        device_fingerprint: &str,
+        token_hash: &str,
+    ) -> Result<bool, DatabaseError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE trusted_devices
+            SET last_used_at = NOW()
+            WHERE user_id = $1 AND device_fingerprint = $2 AND token_hash = $3
+              AND revoked_at IS NULL AND expires_at > $4
+            "#,
+            user_id,
+            device_fingerprint,
+            token_hash,
+            self.clock.now()
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Cabut semua trusted device user (logout semua device, ganti / reset password)
+    pub async fn revoke_trusted_devices<'e, E>(&self, executor: E, user_id: Uuid) -> Result<u64, DatabaseError>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+    {
+        let result = sqlx::query!(
+            "UPDATE trusted_devices SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+            user_id
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Tandai event timeline sebagai dibaca, `ids` None = semua yang belum dibaca
+    /// Scoped ke user: ID milik user lain atau yang sudah dibaca diabaikan, return jumlah yang berubah
+    pub async fn mark_activity_read(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        ids: Option<&[Uuid]>,
+    ) -> Result<u64, DatabaseError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE security_events SET read_at = $3
+            WHERE user_id = $1 AND read_at IS NULL AND ($2::uuid[] IS NULL OR id = ANY($2))
+            "#,
+            user_id,
+            ids as Option<&[Uuid]>,
+            self.clock.now()
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Jumlah event belum dibaca sejak `since` (jendela yang sama dengan feed my-activity)
+    pub async fn count_unread_activity(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<i64, DatabaseError> {
+        let unread = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!" FROM security_events
+            WHERE user_id = $1 AND read_at IS NULL AND created_at >= $2
+            "#,
+            user_id,
+            since
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(unread)
+    }
+
+    /// Refresh token masih tersimpan, belum di-revoke, dan belum expired
+    pub async fn is_refresh_token_active(&self, pool: &PgPool, token_hash: &str) -> Result<bool, DatabaseError> {
+        let active = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM refresh_tokens
+                WHERE token_hash = $1 AND is_revoked IS NOT TRUE AND expires_at > $2
+            ) AS "active!"
+            "#,
+            token_hash,
+            self.clock.now()
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(active)
+    }
+
+    /// Rotasi refresh token: revoke token lama dan simpan token baru di family yang sama
+    /// Token lama yang sudah di-revoke (replay token bocor / hasil rotasi sebelumnya) me-revoke seluruh family
+    pub async fn rotate_refresh_token(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        old_token_hash: &str,
+        new_token_hash: &str,
+        device_fingerprint: Option<&str>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<RefreshRotation, DatabaseError> {
+        let mut tx = pool.begin().await?;
+
+        // Hanya satu request yang bisa me-revoke token lama, request paralel jatuh ke cek reuse
+        let rotated_family = sqlx::query_scalar!(
+            r#"
+            UPDATE refresh_tokens
+            SET is_revoked = true,
+                revoked_at = NOW(),
+                revoked_reason = 'Token refreshed'
+            WHERE token_hash = $1 AND user_id = $2 AND is_revoked IS NOT TRUE
+            RETURNING token_family
+            "#,
+            old_token_hash,
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(token_family) = rotated_family {
+            sqlx::query!(
+                r#"
+                INSERT INTO refresh_tokens (user_id, token_hash, device_fingerprint, expires_at, token_family)
+                VALUES ($1, $2, $3, $4, $5)
+                "#,
+                user_id,
+                new_token_hash,
+                device_fingerprint,
+                expires_at,
+                token_family
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            tx.commit().await?;
+            return Ok(RefreshRotation::Rotated);
+        }
+
+        let reused_family = sqlx::query_scalar!(
+            "SELECT token_family FROM refresh_tokens WHERE token_hash = $1 AND user_id = $2",
+            old_token_hash,
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(token_family) = reused_family else {
+            return Ok(RefreshRotation::NotFound);
+        };
+
+        let revoked = sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET is_revoked = true,
+                revoked_at = NOW(),
+                revoked_reason = 'Token reuse detected'
+            WHERE token_family = $1 AND is_revoked IS NOT TRUE
+            "#,
+            token_family
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+        Ok(RefreshRotation::ReuseDetected { token_family, revoked })
+    }
+
+    /// Sesi login aktif user: satu entry per token family (satu login), memakai refresh token
+    /// hasil rotasi terakhir; token yang sudah di-revoke atau expired tidak ikut
+    pub async fn list_active_sessions(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        current_token_hash: Option<&str>,
+    ) -> Result<Vec<ActiveSession>, DatabaseError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT * FROM (
+                SELECT DISTINCT ON (token_family)
+                       id, token_hash, token_family, device_fingerprint, expires_at, last_used_at,
+                       (SELECT MIN(f.created_at) FROM refresh_tokens f WHERE f.token_family = t.token_family) AS login_at,
+                       COALESCE(last_used_at, created_at) AS activity_at
+                FROM refresh_tokens t
+                WHERE user_id = $1 AND expires_at > $2 AND is_revoked IS NOT TRUE
+                ORDER BY token_family, created_at DESC NULLS LAST
+            ) sessions
+            ORDER BY activity_at DESC NULLS LAST
+            LIMIT 100
+            "#,
+            user_id,
+            self.clock.now()
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter()
+            .map(|row| ActiveSession {
+                is_current: current_token_hash == Some(row.token_hash.as_str()),
+                id: row.id,
+                device_fingerprint: row.device_fingerprint,
+                created_at: row.login_at,
+                expires_at: row.expires_at,
+                last_used_at: row.last_used_at,
+                is_revoked: false,
+            })
+            .collect())
+    }
+
+    /// Revoke seluruh token family sebuah sesi (token hasil rotasi lama maupun baru)
+    /// Return jumlah token yang di-revoke
+    pub async fn revoke_token_family(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        token_family: Uuid,
+        reason: &str,
+    ) -> Result<u64, DatabaseError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET is_revoked = true,
+                revoked_at = NOW(),
+                revoked_reason = $3
+            WHERE token_family = $1 AND user_id = $2 AND is_revoked IS NOT TRUE
+            "#,
+            token_family,
+            user_id,
+            reason
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     /// Hash token sesi dengan pepper
     fn hash_session_token(&self, token: &str) -> String {
         let mut hasher = Sha256::new();
@@ -927,8 +1568,292 @@ impl UserRepository {
             "SUSPICIOUS_ACTIVITY" => "Aktivitas mencurigakan terdeteksi".to_string(),
             "UNAUTHORIZED_ACCESS" => "Percobaan akses tidak sah".to_string(),
             "BRUTE_FORCE_DETECTED" => "Serangan brute force terdeteksi".to_string(),
+            "IMPOSSIBLE_TRAVEL" => "Login dari lokasi yang mustahil dijangkau sejak login sebelumnya".to_string(),
             _ if !success => format!("{} - Gagal", event_type).to_string(),
             _ => event_type.to_string(),
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_support::{register_request, test_pool};
+
+    /// Butuh database asli (DATABASE_URL)
+    #[tokio::test]
+    async fn test_concurrent_registration_creates_single_account() {
+        let pool = test_pool().await;
+
+        let base = register_request("race");
+        let email = base.email.clone();
+        let request = || RegisterRequest { email: email.to_uppercase(), ..base.clone() };
+
+        let first = UserRepository::new(b"pepper");
+        let second = UserRepository::new(b"pepper");
+        let (a, b) = tokio::join!(first.create_user(&pool, request()), second.create_user(&pool, request()));
+
+        let created = sqlx::query_scalar!("SELECT COUNT(*) FROM users WHERE email = $1", email)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        sqlx::query!("DELETE FROM users WHERE email = $1", email).execute(&pool).await.unwrap();
+
+        assert_eq!(created, Some(1));
+        assert_eq!([a.is_ok(), b.is_ok()].iter().filter(|ok| **ok).count(), 1);
+        assert!(matches!(a.err().or(b.err()), Some(DatabaseError::EmailExists)));
+    }
+
+    /// Butuh database asli (DATABASE_URL)
+    #[tokio::test]
+    async fn test_parallel_sensitive_emails_respect_limit() {
+        let pool = test_pool().await;
+
+        // Limit default verifikasi: 3 email per jam
+        let email = format!("limit-{}@example.com", Uuid::new_v4());
+        let mut attempts = tokio::task::JoinSet::new();
+        for _ in 0..10 {
+            let (pool, email) = (pool.clone(), email.clone());
+            attempts.spawn(async move {
+                UserRepository::new(b"pepper").allow_sensitive_email(&pool, &email, EMAIL_PURPOSE_VERIFICATION).await
+            });
+        }
+        let results = attempts.join_all().await;
+
+        let suppressed = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM email_send_log WHERE email = $1 AND suppressed = true",
+            email
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        sqlx::query!("DELETE FROM email_send_log WHERE email = $1", email).execute(&pool).await.unwrap();
+
+        assert_eq!(results.iter().filter(|r| matches!(r, Ok(true))).count(), 3);
+        assert_eq!(suppressed, Some(7));
+    }
+
+    /// Butuh database asli (DATABASE_URL)
+    #[tokio::test]
+    async fn test_account_lock_expires_with_window() {
+        use crate::core::clock::MockClock;
+
+        let pool = test_pool().await;
+
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let repository = UserRepository::new(b"pepper").with_clock(clock.clone());
+        let user = repository.create_user(&pool, register_request("lockout")).await.unwrap();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO security_events (user_id, event_type, event_data, success)
+            SELECT $1, 'LOGIN_FAILED', '{}'::jsonb, false FROM generate_series(1, $2::int)
+            "#,
+            user.id,
+            ACCOUNT_LOCK_THRESHOLD as i32
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let locked = repository.get_account_lock_state(&pool, user.id).await.unwrap();
+        clock.advance(chrono::Duration::minutes(59));
+        let still_locked = repository.get_account_lock_state(&pool, user.id).await.unwrap();
+        clock.advance(chrono::Duration::minutes(2));
+        let expired = repository.get_account_lock_state(&pool, user.id).await.unwrap();
+
+        sqlx::query!("DELETE FROM users WHERE id = $1", user.id).execute(&pool).await.unwrap();
+
+        assert!(locked.locked);
+        assert!(still_locked.locked);
+        assert!(!expired.locked);
+        assert_eq!(expired.failed_attempts, 0);
+    }
+
+    /// Butuh database asli (DATABASE_URL)
+    #[tokio::test]
+    async fn test_refresh_token_reuse_revokes_family() {
+        let pool = test_pool().await;
+
+        let repository = UserRepository::new(b"pepper");
+        let user = repository.create_user(&pool, register_request("reuse")).await.unwrap();
+
+        let token = |n: u8| format!("reuse-{}-{}", user.id, n);
+        let expires_at = Utc::now() + chrono::Duration::days(7);
+        sqlx::query!(
+            "INSERT INTO refresh_tokens (user_id, token_hash, expires_at) VALUES ($1, $2, $3)",
+            user.id,
+            token(1),
+            expires_at
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let (repository, pool_ref, user_id) = (&repository, &pool, user.id);
+        let rotate = |old: u8, new: u8| {
+            let (old, new) = (token(old), token(new));
+            async move { repository.rotate_refresh_token(pool_ref, user_id, &old, &new, None, expires_at).await }
+        };
+        assert_eq!(rotate(1, 2).await.unwrap(), RefreshRotation::Rotated);
+        assert_eq!(rotate(2, 3).await.unwrap(), RefreshRotation::Rotated);
+
+        // Replay token pertama: token aktif terakhir (3) ikut di-revoke, token baru tidak disimpan
+        let replay = rotate(1, 4).await.unwrap();
+        let tokens = sqlx::query!(
+            r#"SELECT token_hash, COALESCE(is_revoked, false) AS "is_revoked!", token_family FROM refresh_tokens WHERE user_id = $1"#,
+            user.id
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        let unknown = rotate(9, 5).await.unwrap();
+        sqlx::query!("DELETE FROM users WHERE id = $1", user.id).execute(&pool).await.unwrap();
+
+        assert!(matches!(replay, RefreshRotation::ReuseDetected { revoked: 1, .. }));
+        assert_eq!(tokens.len(), 3);
+        assert!(tokens.iter().all(|t| t.is_revoked && t.token_family == tokens[0].token_family));
+        assert_eq!(unknown, RefreshRotation::NotFound);
+    }
+
+    /// Butuh database asli (DATABASE_URL)
+    #[tokio::test]
+    async fn test_revoke_session_after_rotation_revokes_family() {
+        let pool = test_pool().await;
+
+        let repository = UserRepository::new(b"pepper");
+        let user = repository.create_user(&pool, register_request("session")).await.unwrap();
+
+        let token = |n: u8| format!("session-{}-{}", user.id, n);
+        let expires_at = Utc::now() + chrono::Duration::days(7);
+        let first_id = sqlx::query_scalar!(
+            "INSERT INTO refresh_tokens (user_id, token_hash, expires_at) VALUES ($1, $2, $3) RETURNING id",
+            user.id,
+            token(1),
+            expires_at
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        repository.rotate_refresh_token(&pool, user.id, &token(1), &token(2), None, expires_at).await.unwrap();
+
+        // Token lama hasil rotasi tidak tampil sebagai sesi terpisah
+        let sessions = repository.list_active_sessions(&pool, user.id, Some(&token(2))).await.unwrap();
+        let family = sqlx::query_scalar!("SELECT token_family FROM refresh_tokens WHERE id = $1", first_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        // Revoke lewat id sesi lama tetap me-revoke token aktif di family yang sama
+        let revoked = repository.revoke_token_family(&pool, user.id, family, "User revoked session").await.unwrap();
+        let remaining = repository.list_active_sessions(&pool, user.id, None).await.unwrap();
+        sqlx::query!("DELETE FROM users WHERE id = $1", user.id).execute(&pool).await.unwrap();
+
+        assert_eq!(sessions.len(), 1);
+        assert_ne!(sessions[0].id, first_id);
+        assert!(sessions[0].is_current && !sessions[0].is_revoked);
+        assert_eq!(revoked, 1);
+        assert!(remaining.is_empty());
+    }
+
+    /// Butuh database asli (DATABASE_URL)
+    #[tokio::test]
+    async fn test_only_completed_logins_make_device_known() {
+        let pool = test_pool().await;
+
+        let repository = UserRepository::new(b"pepper");
+        let user = repository.create_user(&pool, register_request("device")).await.unwrap();
+
+        let record = |status: &'static str| sqlx::query!(
+            "INSERT INTO login_history (user_id, device_fingerprint, login_status) VALUES ($1, 'laptop', $2)",
+            user.id,
+            status
+        )
+        .execute(&pool);
+
+        record("password_success").await.unwrap();
+        let after_password = repository.is_known_login_device(&pool, user.id, "laptop").await.unwrap();
+        record("success").await.unwrap();
+        let after_login = repository.is_known_login_device(&pool, user.id, "laptop").await.unwrap();
+        let other_device = repository.is_known_login_device(&pool, user.id, "phone").await.unwrap();
+        sqlx::query!("DELETE FROM users WHERE id = $1", user.id).execute(&pool).await.unwrap();
+
+        assert!(!after_password);
+        assert!(after_login);
+        assert!(!other_device);
+    }
+
+    /// Butuh database asli (DATABASE_URL)
+    #[tokio::test]
+    async fn test_unlock_account_resets_lockout() {
+        let pool = test_pool().await;
+
+        let repository = UserRepository::new(b"pepper");
+        let user = repository.create_user(&pool, register_request("unlock")).await.unwrap();
+
+        for _ in 0..ACCOUNT_LOCK_THRESHOLD {
+            sqlx::query!(
+                "INSERT INTO security_events (user_id, event_type, event_data, success) VALUES ($1, 'LOGIN_FAILED', '{}', false)",
+                user.id
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        let locked = repository.get_account_lock_state(&pool, user.id).await.unwrap();
+        let (before, after) = repository.unlock_account(&pool, user.id).await.unwrap();
+        let (again_before, again_after) = repository.unlock_account(&pool, user.id).await.unwrap();
+        let unknown = repository.unlock_account(&pool, Uuid::new_v4()).await;
+        sqlx::query!("DELETE FROM users WHERE id = $1", user.id).execute(&pool).await.unwrap();
+
+        assert!(locked.locked);
+        assert!(before.locked && !after.locked);
+        assert_eq!(after.failed_attempts, 0);
+        assert!(!again_before.locked && !again_after.locked);
+        assert!(matches!(unknown, Err(DatabaseError::UserNotFound)));
+    }
+
+    /// Butuh database asli (DATABASE_URL)
+    #[tokio::test]
+    async fn test_mark_activity_read_is_scoped_to_user() {
+        let pool = test_pool().await;
+
+        let repository = UserRepository::new(b"pepper");
+        let owner = repository.create_user(&pool, register_request("activity")).await.unwrap();
+        let other = repository.create_user(&pool, register_request("activity-other")).await.unwrap();
+        // Event registrasi dari create_user ditandai dulu supaya hitungan mulai dari nol
+        for user_id in [owner.id, other.id] {
+            repository.mark_activity_read(&pool, user_id, None).await.unwrap();
+        }
+
+        let mut event_ids = Vec::new();
+        for user_id in [owner.id, owner.id, owner.id, other.id] {
+            let id = sqlx::query_scalar!(
+                "INSERT INTO security_events (user_id, event_type, event_data, success) VALUES ($1, 'LOGIN_SUCCESS', '{}', true) RETURNING id",
+                user_id
+            )
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+            event_ids.push(id);
+        }
+        let since = Utc::now() - chrono::Duration::days(30);
+
+        let unread_before = repository.count_unread_activity(&pool, owner.id, since).await.unwrap();
+        // ID milik user lain ikut dikirim, tidak boleh berubah
+        let marked = repository.mark_activity_read(&pool, owner.id, Some(&[event_ids[0], event_ids[3]])).await.unwrap();
+        let unread_after = repository.count_unread_activity(&pool, owner.id, since).await.unwrap();
+        let marked_all = repository.mark_activity_read(&pool, owner.id, None).await.unwrap();
+        let other_unread = repository.count_unread_activity(&pool, other.id, since).await.unwrap();
+
+        let user_ids = [owner.id, other.id];
+        sqlx::query!("DELETE FROM security_events WHERE user_id = ANY($1)", &user_ids).execute(&pool).await.unwrap();
+        sqlx::query!("DELETE FROM users WHERE id = ANY($1)", &user_ids).execute(&pool).await.unwrap();
+
+        assert_eq!(unread_before, 3);
+        assert_eq!(marked, 1);
+        assert_eq!(unread_after, 2);
+        assert_eq!(marked_all, 2);
+        assert_eq!(other_unread, 1);
+    }
+}
@@ -8,6 +8,7 @@ use utoipa::{
 use crate::models::{
     RegisterRequest,
     LoginRequest,
+    ResendOtpRequest,
     AuthResponse,
     UserProfile,
     ErrorResponse,
@@ -37,14 +38,17 @@ impl Modify for SecurityAddon {
         // Cuma include yang udah ada #[utoipa::path] di handlers
         crate::api::handlers::auth::register_user,
         crate::api::handlers::auth::login_user,
+        crate::api::handlers::auth::resend_login_otp,
         crate::api::handlers::auth::refresh_access_token,
         crate::api::handlers::auth::logout,
+        crate::api::handlers::auth::get_jwks,
         crate::api::handlers::user::get_profile,
     ),
     components(
         schemas(
             RegisterRequest,
             LoginRequest,
+            ResendOtpRequest,
             AuthResponse,
             UserProfile,
             ErrorResponse,
@@ -10,16 +10,15 @@ mod models;
 mod docs;
 
 use axum::{
-    routing::{get, post, put},
+    routing::{get, post, put, delete},
     Router,
-    http::{Method, HeaderValue, header::{AUTHORIZATION, CONTENT_TYPE, ACCEPT}},
+    http::{Method, HeaderName, HeaderValue, header::{AUTHORIZATION, CONTENT_TYPE, ACCEPT}},
     response::Json,
     middleware as axum_middleware,
 };
 use tower::ServiceBuilder;
 use tower_http::{
     cors::CorsLayer,
-    trace::TraceLayer,
     timeout::TimeoutLayer,
 };
 use sqlx::{postgres::PgPoolOptions, PgPool};
@@ -29,15 +28,23 @@ use tracing::info;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 use docs::ApiDoc;
+use service_common::{
+    concurrency_limit::{ConcurrencyLimit, concurrency_limit_middleware},
+    security_headers::{SecurityHeaders, security_headers_middleware},
+    trace_sampling::{TraceSampling, trace_sampling_middleware},
+};
 
 use crate::{
-    core::JwtService, 
-    services::{ServiceClient, ServiceRegistry, CircuitBreakerManager},
+    core::{JwtService, Clock, SystemClock, CookieSessionConfig, TotpConfig, session_cookie::{CSRF_HEADER, AUTH_MODE_HEADER}}, 
+    services::{ServiceClient, ServiceRegistry, CircuitBreakerManager, GeoVelocityChecker},
     middleware::auth_middleware,
     api::handlers,
-    utils::start_token_cleanup_job, 
+    utils::{start_token_cleanup_job, AdminNotifier, Shutdown, SmtpAlertMailer},
 };
 
+/// Path default yang response-nya tidak boleh di-cache (data akun/admin)
+const NO_STORE_PATHS: &str = "/api/auth,/api/users,/api/admin";
+
 /// State aplikasi dengan semua shared services
 #[derive(Clone)]
 pub struct AppState {
@@ -47,6 +54,34 @@ pub struct AppState {
     pub service_registry: Arc<ServiceRegistry>,
     pub circuit_manager: Arc<CircuitBreakerManager>,
     pub pepper: String,
+    pub clock: Arc<dyn Clock>,
+    pub geo_velocity: Arc<GeoVelocityChecker>,
+    pub cookie_session: Arc<CookieSessionConfig>,
+    pub notifier: Arc<AdminNotifier>,
+    pub totp: Arc<TotpConfig>,
+}
+
+#[cfg(test)]
+impl AppState {
+    /// State untuk test handler/middleware: JwtService test, konfigurasi lain dari env (default dev)
+    pub fn for_tests(db: PgPool) -> Self {
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+        let circuit_manager = Arc::new(CircuitBreakerManager::new());
+
+        Self {
+            db,
+            jwt_service: Arc::new(JwtService::for_tests(clock.clone())),
+            service_client: Arc::new(ServiceClient::new(circuit_manager.clone())),
+            service_registry: Arc::new(ServiceRegistry::new()),
+            circuit_manager,
+            pepper: utils::common::get_pepper(),
+            clock,
+            geo_velocity: Arc::new(GeoVelocityChecker::from_env()),
+            cookie_session: Arc::new(CookieSessionConfig::from_env()),
+            notifier: Arc::new(AdminNotifier::from_env("auth-service", 20)),
+            totp: Arc::new(TotpConfig::from_env().expect("TOTP config test")),
+        }
+    }
 }
 
 #[tokio::main]
@@ -72,20 +107,30 @@ async fn main() {
     let service_registry = Arc::new(ServiceRegistry::new());
     service_registry.init_default_services().await;
 
+    // Alert admin untuk kondisi kritis (ADMIN_ALERT_CHANNEL), threshold default 20 login gagal
+    let notifier = Arc::new(AdminNotifier::from_env("auth-service", 20).with_mailer(Arc::new(SmtpAlertMailer)));
+
     // Initialize circuit breaker manager
-    let circuit_manager = Arc::new(CircuitBreakerManager::new());
+    let circuit_manager = Arc::new(CircuitBreakerManager::new().with_notifier(notifier.clone()));
     
     // Initialize service client untuk inter-service communication
     let service_client = Arc::new(ServiceClient::new(circuit_manager.clone()));
 
+    // Sumber waktu bersama untuk expiry token/OTP dan lockout
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+
     // Initialize JWT service sekali aja
     let jwt_service = Arc::new(
         JwtService::new()
             .expect("Failed to initialize JWT service")
+            .with_clock(clock.clone())
     );
 
+    // Koordinasi shutdown untuk background job
+    let shutdown = Shutdown::new();
+
     // Start token cleanup scheduler
-    match start_token_cleanup_job(pool.clone()).await {
+    match start_token_cleanup_job(pool.clone(), shutdown.clone()).await {
         Ok(_) => info!("✅ Token cleanup scheduler started"),
         Err(e) => tracing::error!("❌ Failed to start scheduler: {}", e),
     }
@@ -101,6 +146,11 @@ async fn main() {
         service_registry,
         circuit_manager,
         pepper,
+        clock,
+        geo_velocity: Arc::new(GeoVelocityChecker::from_env()),
+        cookie_session: Arc::new(CookieSessionConfig::from_env()),
+        notifier,
+        totp: Arc::new(TotpConfig::from_env().expect("Failed to initialize TOTP config")),
     };
 
     // Setup CORS policy
@@ -116,6 +166,9 @@ async fn main() {
         // Health check
         .route("/health", get(health_check))
 
+        // Public key JWT untuk verifikasi lokal (RS256)
+        .route("/.well-known/jwks.json", get(handlers::get_jwks))
+
         // Swagger UI - HARUS PUBLIC!
         .merge(SwaggerUi::new("/swagger-ui")
             .url("/api-docs/openapi.json", openapi.clone()))
@@ -124,9 +177,11 @@ async fn main() {
         .route("/api/auth/register", post(handlers::register_user))
         .route("/api/auth/login", post(handlers::login_user))
         .route("/api/auth/verify-otp", post(handlers::verify_otp))
+        .route("/api/auth/otp/resend", post(handlers::resend_login_otp))
         .route("/api/auth/password-reset/request", post(handlers::request_password_reset))
         .route("/api/auth/password-reset/confirm", post(handlers::reset_password))
         .route("/api/auth/email/verify", post(handlers::verify_email))
+        .route("/api/auth/email/resend-verification", post(handlers::resend_verification_email))
 
         // OAuth endpoints (public)
         .route("/api/auth/oauth/google", post(handlers::start_google_oauth))
@@ -140,16 +195,30 @@ async fn main() {
         .route("/api/auth/refresh", post(handlers::refresh_access_token))
         .route("/api/auth/logout", post(handlers::logout))
         .route("/api/auth/revoke-all", post(handlers::revoke_all_tokens))
+        .route("/api/auth/sessions", get(handlers::list_sessions))
+        .route("/api/auth/sessions/{id}", delete(handlers::revoke_session))
+        .route("/api/auth/trusted-devices", get(handlers::list_trusted_devices))
+        .route("/api/auth/trusted-devices/{id}", delete(handlers::revoke_trusted_device))
         .route("/api/auth/session/validate", get(handlers::validate_session))
         .route("/api/auth/session/validate", post(handlers::validate_session))
+
+        // Personal access tokens
+        .route("/api/auth/tokens", post(handlers::create_personal_token))
+        .route("/api/auth/tokens", get(handlers::list_personal_tokens))
+        .route("/api/auth/tokens/{id}", delete(handlers::revoke_personal_token))
         
         
         // User profile & settings
         .route("/api/auth/profile", get(handlers::get_profile))
         .route("/api/auth/profile", put(handlers::update_profile))
         .route("/api/auth/password/change", post(handlers::change_password))
+        .route("/api/auth/reauthenticate", post(handlers::reauthenticate))
+        .route("/api/auth/2fa/totp/enroll", post(handlers::enroll_totp))
+        .route("/api/auth/2fa/totp/verify", post(handlers::verify_totp_enrollment))
         .route("/api/auth/login-history", get(handlers::get_login_history))
         .route("/api/auth/my-activity", get(handlers::get_my_activity))
+        .route("/api/auth/me/activity/mark-read", post(handlers::mark_my_activity_read))
+        .route("/api/auth/me/notifications", get(handlers::get_my_notification_preferences).put(handlers::update_my_notification_preferences))
         .route("/api/auth/email/send-verification", post(handlers::send_verification_email))
         
         // User data dengan service integration
@@ -162,6 +231,7 @@ async fn main() {
         .route("/api/admin/users/activity", get(handlers::get_admin_activity_feed))
         .route("/api/admin/security/activity", get(handlers::get_security_activity_feed))
         .route("/api/admin/users/{id}/status", put(handlers::admin_update_user_status))
+        .route("/api/admin/users/{id}/unlock", post(handlers::admin_unlock_user))
         
         // Apply auth middleware HANYA untuk protected routes
         .layer(axum_middleware::from_fn_with_state(app_state.clone(), auth_middleware));
@@ -170,7 +240,10 @@ async fn main() {
     let internal_routes = Router::new()
         .route("/api/internal/users/{id}", get(handlers::verify_user_internal))
         .route("/api/internal/users/{id}/payment", get(handlers::get_user_for_payment))
-        .route("/api/internal/validate-token", post(handlers::validate_token_internal));
+        .route("/api/internal/users/{id}/notifications", get(handlers::get_user_notification_preferences_internal))
+        .route("/api/internal/users/batch", post(handlers::get_users_batch_internal))
+        .route("/api/internal/validate-token", post(handlers::validate_token_internal))
+        .route("/api/auth/introspect", post(handlers::introspect_token));
 
     // Combine all routes
     let app = Router::new()
@@ -180,8 +253,19 @@ async fn main() {
         // Apply global middleware (CORS, tracing, timeout)
         .layer(
             ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(TimeoutLayer::new(Duration::from_secs(30)))
+                .layer(axum_middleware::from_fn_with_state(
+                    Arc::new(TraceSampling::from_env()),
+                    trace_sampling_middleware,
+                ))
+                .layer(axum_middleware::from_fn_with_state(
+                    Arc::new(ConcurrencyLimit::from_env()),
+                    concurrency_limit_middleware,
+                ))
+                .layer(axum_middleware::from_fn_with_state(
+                    Arc::new(SecurityHeaders::from_env(NO_STORE_PATHS)),
+                    security_headers_middleware,
+                ))
+                .layer(TimeoutLayer::new(request_timeout()))
                 .layer(cors)
         )
         .with_state(app_state);
@@ -204,9 +288,13 @@ async fn main() {
         listener, 
         app.into_make_service_with_connect_info::<SocketAddr>()
     )
+    .with_graceful_shutdown(shutdown.clone().wait_for_signal())
     .await
     .unwrap_or_else(|e| panic!("Server failed to start: {}", e));
 
+    // Tunggu background job selesai (maks SHUTDOWN_TIMEOUT_SECONDS)
+    shutdown.drain().await;
+
 }
 
 /// Health check endpoint
@@ -284,8 +372,10 @@ fn setup_cors() -> CorsLayer {
         CorsLayer::new()
             .allow_origin(origins)
             .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE, Method::OPTIONS])
-            .allow_headers([AUTHORIZATION, CONTENT_TYPE, ACCEPT])
+            .allow_headers([AUTHORIZATION, CONTENT_TYPE, ACCEPT, HeaderName::from_static(CSRF_HEADER), HeaderName::from_static(AUTH_MODE_HEADER), HeaderName::from_static("x-refresh-token")])
+            .expose_headers([HeaderName::from_static(CSRF_HEADER)])
             .allow_credentials(true)
+            .max_age(cors_max_age())
     } else {
         // Production: More restrictive CORS
         let production_origins = env::var("PRODUCTION_ORIGINS")
@@ -299,11 +389,31 @@ fn setup_cors() -> CorsLayer {
         CorsLayer::new()
             .allow_origin(origins)
             .allow_methods([Method::GET, Method::POST, Method::PUT])
-            .allow_headers([AUTHORIZATION, CONTENT_TYPE, ACCEPT])
+            .allow_headers([AUTHORIZATION, CONTENT_TYPE, ACCEPT, HeaderName::from_static(CSRF_HEADER), HeaderName::from_static(AUTH_MODE_HEADER), HeaderName::from_static("x-refresh-token")])
+            .expose_headers([HeaderName::from_static(CSRF_HEADER)])
             .allow_credentials(true)
+            .max_age(cors_max_age())
     }
 }
 
+/// Timeout global request (REQUEST_TIMEOUT_SECONDS, default 30 detik)
+fn request_timeout() -> Duration {
+    let secs = env::var("REQUEST_TIMEOUT_SECONDS")
+        .unwrap_or_else(|_| "30".to_string())
+        .parse()
+        .unwrap_or(30);
+    Duration::from_secs(secs)
+}
+
+/// Durasi cache preflight CORS (Access-Control-Max-Age)
+fn cors_max_age() -> Duration {
+    let secs = env::var("CORS_MAX_AGE_SECONDS")
+        .unwrap_or_else(|_| "3600".to_string())
+        .parse()
+        .unwrap_or(3600);
+    Duration::from_secs(secs)
+}
+
 /// Mendapatkan server configuration dari environment
 fn get_server_config() -> (String, String) {
     let host = env::var("SERVER_HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
@@ -320,6 +430,7 @@ fn print_startup_info(bind_address: &str) {
     info!("📋 Available endpoints:");
     info!("  Public endpoints:");
     info!("    GET  /health                          - Health check");
+    info!("    GET  /.well-known/jwks.json           - JWT public keys (RS256)");
     info!("    POST /api/auth/register               - User registration");
     info!("    POST /api/auth/login                  - User login");
     info!("    POST /api/auth/password-reset/request - Request password reset");
@@ -330,12 +441,15 @@ fn print_startup_info(bind_address: &str) {
     info!("    PUT  /api/auth/profile                - Update profile");
     info!("    POST /api/auth/refresh                - Refresh token");
     info!("    POST /api/auth/logout                 - Logout");
+    info!("    GET  /api/auth/tokens                 - List personal access tokens");
+    info!("    POST /api/auth/tokens                 - Create personal access token");
     info!("  Admin endpoints (admin JWT required):");
     info!("    GET  /api/admin/users/stats           - User statistics");
     info!("    GET  /api/admin/users                 - User list (paginated)");
     info!("    GET  /api/admin/users/activity        - User activity feed");
     info!("    GET  /api/admin/security/activity     - Security activity feed");
     info!("    PUT  /api/admin/users/:id/status      - Update user status");
+    info!("    POST /api/admin/users/:id/unlock      - Unlock locked account");
     info!("📚 Swagger UI available at: http://localhost:3001/swagger-ui");
     info!("📄 OpenAPI spec at: http://localhost:3001/api-docs/openapi.json");
     
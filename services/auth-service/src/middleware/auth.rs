@@ -2,7 +2,7 @@
 
 use axum::{
     extract::{Request, State},
-    http::StatusCode,
+    http::{Method, StatusCode},
     response::Json,
     middleware::Next,
 };
@@ -10,8 +10,15 @@ use uuid::Uuid;
 
 use crate::{
     AppState,
+    core::jwt::{effective_role, PAT_TOKEN_TYPE},
+    core::session_cookie::{read_cookie, requires_csrf, csrf_valid, ACCESS_COOKIE},
     models::ErrorResponse,
 };
+use super::recent_auth::AuthTime;
+
+/// Scope personal access token di request extensions (None = token login biasa)
+#[derive(Debug, Clone)]
+pub struct PatScopes(pub Option<Vec<String>>);
 
 /// Middleware untuk validasi JWT token pada protected endpoints
 pub async fn auth_middleware(
@@ -26,8 +33,20 @@ pub async fn auth_middleware(
         return Ok(next.run(req).await);
     }
 
-    // Extract dan validate JWT token
-    let token = extract_bearer_token(&req)?;
+    // Token dari Authorization header, atau cookie access_token (mode cookie, wajib CSRF untuk non-GET)
+    let token = match read_cookie(req.headers(), ACCESS_COOKIE) {
+        Some(token) if state.cookie_session.enabled && !req.headers().contains_key("authorization") => {
+            if requires_csrf(req.method()) && !csrf_valid(req.headers()) {
+                tracing::warn!("CSRF token invalid for cookie session: {} {}", req.method(), path);
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    Json(ErrorResponse::new("CSRF token tidak valid", Some("CSRF_TOKEN_INVALID")))
+                ));
+            }
+            token
+        }
+        _ => extract_bearer_token(&req)?,
+    };
     
     // Verify token menggunakan shared JWT service
     let claims = state.jwt_service.verify_token(&token)
@@ -39,8 +58,33 @@ pub async fn auth_middleware(
             )
         })?;
 
+    // Personal access token: cek revocation dan scope
+    if claims.token_type.as_deref() == Some(PAT_TOKEN_TYPE) {
+        state.jwt_service.verify_token_with_blacklist(&token, &state.db).await
+            .map_err(|e| {
+                tracing::warn!("Personal access token rejected: {}", e);
+                (
+                    StatusCode::UNAUTHORIZED,
+                    Json(ErrorResponse::new("Token tidak valid atau sudah di-revoke", Some("INVALID_TOKEN")))
+                )
+            })?;
+
+        let scopes = claims.scopes.clone().unwrap_or_default();
+        if !pat_scope_allows(&scopes, req.method(), &path) {
+            tracing::warn!("PAT for user {} lacks scope for {} {}", claims.sub, req.method(), path);
+            return Err((
+                StatusCode::FORBIDDEN,
+                Json(ErrorResponse::new("Scope token tidak mencukupi", Some("INSUFFICIENT_SCOPE")))
+            ));
+        }
+    }
+
+    let pat_scopes = (claims.token_type.as_deref() == Some(PAT_TOKEN_TYPE))
+        .then(|| claims.scopes.clone().unwrap_or_default());
+    let role = effective_role(&claims.role, pat_scopes.as_deref());
+
     // Check admin access untuk admin routes
-    if path.contains("/admin/") && claims.role != "admin" {
+    if path.contains("/admin/") && role != "admin" {
         tracing::warn!("Non-admin user {} attempted admin access: {}", claims.sub, path);
         return Err((
             StatusCode::FORBIDDEN,
@@ -55,8 +99,17 @@ pub async fn auth_middleware(
             Json(ErrorResponse::new("Invalid user ID in token", Some("INVALID_TOKEN")))
         ))?;
 
+    // PAT tidak pernah dianggap autentikasi baru untuk step-up
+    let auth_time = if claims.token_type.as_deref() == Some(PAT_TOKEN_TYPE) {
+        None
+    } else {
+        Some(claims.auth_time.unwrap_or(claims.iat))
+    };
+
     req.extensions_mut().insert(user_id);
-    req.extensions_mut().insert(claims.role.clone());
+    req.extensions_mut().insert(AuthTime(auth_time));
+    req.extensions_mut().insert(role);
+    req.extensions_mut().insert(PatScopes(pat_scopes));
     req.extensions_mut().insert(token.to_string());
     req.extensions_mut().insert(state.jwt_service.clone());
 
@@ -72,16 +125,51 @@ pub async fn auth_middleware(
 fn is_public_endpoint(path: &str) -> bool {
     let public_paths = [
         "/health",
+        "/.well-known/jwks.json",
         "/api/auth/register",
         "/api/auth/login",
         "/api/auth/password-reset/request",
         "/api/auth/password-reset/confirm",
         "/api/auth/email/verify",
+        "/api/auth/email/resend-verification",
     ];
     
     public_paths.iter().any(|&public_path| path == public_path)
 }
 
+/// Helper untuk cek apakah scope personal access token cukup untuk endpoint
+fn pat_scope_allows(scopes: &[String], method: &Method, path: &str) -> bool {
+    let has = |scope: &str| scopes.iter().any(|s| s == scope);
+
+    // Credential management tidak boleh lewat PAT
+    let interactive_only = [
+        "/api/auth/tokens",
+        "/api/auth/refresh",
+        "/api/auth/revoke-all",
+        "/api/auth/password/change",
+        "/api/auth/reauthenticate",
+        "/api/auth/sessions",
+        "/api/auth/trusted-devices",
+    ];
+    if interactive_only.iter().any(|p| path.starts_with(p)) {
+        return false;
+    }
+
+    if path == "/api/auth/verify" {
+        return true;
+    }
+
+    if path.contains("/admin/") {
+        return has("admin:access");
+    }
+
+    if method == Method::GET {
+        has("profile:read") || has("profile:write")
+    } else {
+        has("profile:write")
+    }
+}
+
 /// Helper untuk extract bearer token dari request header
 fn extract_bearer_token(req: &Request) -> Result<String, (StatusCode, Json<ErrorResponse>)> {
     let auth_header = req
@@ -113,4 +201,66 @@ fn extract_bearer_token(req: &Request) -> Result<String, (StatusCode, Json<Error
 
     let token = auth_header.strip_prefix("Bearer ").unwrap();
     Ok(token.to_string())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::{test_support::{register_request, test_pool}, UserRepository}, utils::hash_token};
+    use axum::{body::Body, middleware::from_fn_with_state, routing::{delete, get}, Router};
+    use tower::ServiceExt;
+
+    /// Butuh database asli (DATABASE_URL)
+    #[tokio::test]
+    async fn test_pat_cannot_manage_sessions_or_trusted_devices() {
+        let pool = test_pool().await;
+
+        let state = AppState::for_tests(pool.clone());
+        let user = UserRepository::new(b"pepper").create_user(&pool, register_request("pat-scope")).await.unwrap();
+
+        // PAT dengan scope tertinggi non-admin, tetap tidak boleh menyentuh sesi/perangkat
+        let token_id = Uuid::new_v4();
+        let scopes = vec!["profile:read".to_string(), "profile:write".to_string()];
+        let expires_at = chrono::Utc::now() + chrono::Duration::days(1);
+        let token = state.jwt_service
+            .generate_personal_access_token(&user, token_id, &scopes, expires_at)
+            .unwrap();
+        sqlx::query!(
+            "INSERT INTO personal_access_tokens (id, user_id, name, token_hash, scopes, expires_at) VALUES ($1, $2, 'test', $3, $4, $5)",
+            token_id, user.id, hash_token(&token), &scopes, expires_at
+        ).execute(&pool).await.unwrap();
+
+        let app = Router::new()
+            .route("/api/auth/profile", get(|| async { "ok" }))
+            .route("/api/auth/sessions", get(|| async { "ok" }))
+            .route("/api/auth/sessions/{id}", delete(|| async { "ok" }))
+            .route("/api/auth/trusted-devices", get(|| async { "ok" }))
+            .route("/api/auth/trusted-devices/{id}", delete(|| async { "ok" }))
+            .layer(from_fn_with_state(state.clone(), auth_middleware))
+            .with_state(state);
+
+        let send = |method: Method, path: String| {
+            let (app, token) = (app.clone(), token.clone());
+            async move {
+                let request = Request::builder()
+                    .method(method)
+                    .uri(path)
+                    .header("authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap();
+                app.oneshot(request).await.unwrap().status()
+            }
+        };
+
+        let profile = send(Method::GET, "/api/auth/profile".to_string()).await;
+        let blocked = [
+            send(Method::GET, "/api/auth/sessions".to_string()).await,
+            send(Method::DELETE, format!("/api/auth/sessions/{}", Uuid::new_v4())).await,
+            send(Method::GET, "/api/auth/trusted-devices".to_string()).await,
+            send(Method::DELETE, format!("/api/auth/trusted-devices/{}", Uuid::new_v4())).await,
+        ];
+        sqlx::query!("DELETE FROM users WHERE id = $1", user.id).execute(&pool).await.unwrap();
+
+        assert_eq!(profile, StatusCode::OK);
+        assert_eq!(blocked, [StatusCode::FORBIDDEN; 4]);
+    }
+}
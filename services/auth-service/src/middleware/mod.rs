@@ -1,5 +1,6 @@
 // /pdf-bookstore/services/auth-service/src/middleware/mod.rs
 
 pub mod auth;
+pub mod recent_auth;
 
 pub use auth::auth_middleware;
\ No newline at end of file
@@ -0,0 +1,76 @@
+// /pdf-bookstore/services/auth-service/src/middleware/recent_auth.rs
+
+use axum::{http::StatusCode, response::Json};
+use chrono::{DateTime, Duration, Utc};
+use std::env;
+
+use crate::models::ErrorResponse;
+
+/// Waktu user terakhir memasukkan kredensial (claim `auth_time`, fallback `iat` untuk token lama)
+/// None untuk personal access token, tidak pernah lolos step-up
+#[derive(Debug, Clone, Copy)]
+pub struct AuthTime(pub Option<usize>);
+
+/// Umur maksimal autentikasi untuk aksi sensitif (REAUTH_MAX_AGE_MINUTES, default 10 menit)
+pub fn reauth_max_age() -> Duration {
+    let minutes = env::var("REAUTH_MAX_AGE_MINUTES")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(10);
+    Duration::minutes(minutes)
+}
+
+/// Autentikasi terakhir sudah terlalu lama untuk aksi sensitif
+#[derive(Debug)]
+pub struct ReauthRequired;
+
+impl From<ReauthRequired> for (StatusCode, Json<ErrorResponse>) {
+    fn from(_: ReauthRequired) -> Self {
+        (
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::new(
+                "Silakan masukkan password Anda lagi untuk melanjutkan",
+                Some("REAUTH_REQUIRED")
+            ))
+        )
+    }
+}
+
+/// Step-up untuk ganti password / setup 2FA: tolak REAUTH_REQUIRED kalau login terakhir sudah terlalu lama,
+/// client diminta memasukkan password lagi lewat POST /api/auth/reauthenticate
+pub fn require_recent_auth(auth_time: AuthTime, now: DateTime<Utc>) -> Result<(), ReauthRequired> {
+    if is_recent(auth_time, now, reauth_max_age()) {
+        Ok(())
+    } else {
+        Err(ReauthRequired)
+    }
+}
+
+fn is_recent(auth_time: AuthTime, now: DateTime<Utc>, max_age: Duration) -> bool {
+    let Some(auth_time) = auth_time.0 else {
+        return false;
+    };
+
+    let age = now.timestamp() - auth_time as i64;
+    // Toleransi clock skew kecil untuk auth_time di masa depan
+    age >= -60 && age <= max_age.num_seconds()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_auth_window() {
+        let now = Utc::now();
+        let at = |offset: Duration| AuthTime(Some((now - offset).timestamp() as usize));
+        let max_age = Duration::minutes(10);
+
+        assert!(is_recent(at(Duration::minutes(2)), now, max_age));
+        assert!(is_recent(at(Duration::minutes(10)), now, max_age));
+        assert!(!is_recent(at(Duration::minutes(11)), now, max_age));
+        assert!(!is_recent(at(Duration::minutes(-5)), now, max_age));
+        assert!(!is_recent(AuthTime(None), now, max_age));
+    }
+}
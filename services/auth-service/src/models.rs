@@ -43,6 +43,13 @@ pub struct Claims {
     pub session_id: Option<String>,
     pub permissions: Option<Vec<String>>,
     pub ip_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+    /// Waktu user terakhir memasukkan kredensial (unix), dipertahankan saat refresh token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<usize>,
 }
 
 // ===== REQUEST MODELS =====
@@ -70,7 +77,7 @@ pub struct LoginRequest {
     #[validate(length(min = 1, message = "Password wajib diisi"))]
     pub password: String,
 
-     #[allow(dead_code)]
+    /// Dipakai saat login langsung dari trusted device (tanpa OTP)
     pub remember_me: Option<bool>,
 
     #[allow(dead_code)]
@@ -78,6 +85,21 @@ pub struct LoginRequest {
 
     #[schema(example = "device-fingerprint-123")]
     pub device_fingerprint: Option<String>,
+
+    /// Token dari verify-otp dengan `trust_device: true`, valid bersama device_fingerprint yang sama
+    pub trusted_device_token: Option<String>,
+
+    /// Token dikirim sebagai cookie HttpOnly, bukan di body response
+    pub use_cookies: Option<bool>,
+}
+
+/// Re-autentikasi (step-up) sebelum aksi sensitif
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ReauthenticateRequest {
+    pub password: String,
+
+    /// Access token baru dikirim sebagai cookie HttpOnly, bukan di body response
+    pub use_cookies: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Validate, ToSchema)]
@@ -90,6 +112,12 @@ pub struct VerifyOtpRequest {
 
     pub remember_me: Option<bool>,
     pub device_fingerprint: Option<String>,
+
+    /// Token dikirim sebagai cookie HttpOnly, bukan di body response
+    pub use_cookies: Option<bool>,
+
+    /// Percayai device ini (butuh device_fingerprint), login berikutnya tidak perlu OTP
+    pub trust_device: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -98,6 +126,12 @@ pub struct PasswordResetRequest {
     pub email: String,
 }
 
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResendVerificationRequest {
+    #[validate(email(message = "Format email tidak valid"))]
+    pub email: String,
+}
+
 #[derive(Debug, Deserialize, Validate)]
 pub struct ResetPasswordRequest {
     pub token: String,
@@ -122,6 +156,52 @@ pub struct UpdateProfileRequest {
     pub avatar_url: Option<String>,
 }
 
+/// Batas panjang field profile, tidak boleh melebihi ukuran kolom DB
+#[derive(Debug, Clone, Copy)]
+pub struct ProfileLengthLimits {
+    pub full_name: usize,
+    pub phone: usize,
+    pub bio: usize,
+    pub avatar_url: usize,
+}
+
+impl ProfileLengthLimits {
+    /// PROFILE_NAME_MAX_LENGTH (default 255), PROFILE_BIO_MAX_LENGTH (default 500),
+    /// PROFILE_AVATAR_URL_MAX_LENGTH (default 500); phone tetap 20 (VARCHAR(20))
+    pub fn from_env() -> Self {
+        let read = |key: &str, default: usize, column_max: usize| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<usize>().ok())
+                .unwrap_or(default)
+                .clamp(1, column_max)
+        };
+
+        Self {
+            full_name: read("PROFILE_NAME_MAX_LENGTH", 255, 255),
+            phone: 20,
+            bio: read("PROFILE_BIO_MAX_LENGTH", 500, 5000),
+            avatar_url: read("PROFILE_AVATAR_URL_MAX_LENGTH", 500, 500),
+        }
+    }
+}
+
+impl UpdateProfileRequest {
+    /// Field yang melebihi batas panjang beserta batasnya
+    pub fn oversized_fields(&self, limits: &ProfileLengthLimits) -> Vec<(&'static str, usize)> {
+        [
+            ("full_name", &self.full_name, limits.full_name),
+            ("phone", &self.phone, limits.phone),
+            ("bio", &self.bio, limits.bio),
+            ("avatar_url", &self.avatar_url, limits.avatar_url),
+        ]
+        .into_iter()
+        .filter(|(_, value, max)| value.as_ref().is_some_and(|v| v.chars().count() > *max))
+        .map(|(field, _, max)| (field, max))
+        .collect()
+    }
+}
+
 #[derive(Debug, Deserialize, Validate)]
 pub struct ChangePasswordRequest {
     pub old_password: String,
@@ -133,6 +213,19 @@ pub struct ChangePasswordRequest {
     pub confirm_password: String,
 }
 
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ResendOtpRequest {
+    #[validate(email(message = "Format email tidak valid"))]
+    pub email: String,
+}
+
+/// Konfirmasi enrollment TOTP dengan kode dari authenticator app
+#[derive(Debug, Deserialize, Validate)]
+pub struct TotpVerifyRequest {
+    #[validate(length(min = 6, max = 6, message = "Kode TOTP harus 6 digit"))]
+    pub code: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct VerifyEmailRequest {
     pub token: String,
@@ -181,6 +274,17 @@ pub struct OAuthLoginRequest {
 
 // ===== RESPONSE MODELS =====
 
+/// Envelope standar response sukses: `{success, message, data, meta}`
+/// `meta` untuk info tambahan (pagination, ringkasan) dan tidak diserialisasi jika kosong
+#[derive(Debug, Serialize)]
+pub struct ApiResponse<T, M = ()> {
+    pub success: bool,
+    pub message: String,
+    pub data: T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<M>,
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct AuthResponse {
     pub success: bool,
@@ -192,6 +296,25 @@ pub struct AuthResponse {
     pub session_id: Option<String>,
     pub requires_verification: Option<bool>,
     pub two_factor_required: Option<bool>,
+    /// Hanya dikirim sekali saat device baru dipercaya, simpan di client untuk login berikutnya
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trusted_device_token: Option<String>,
+    /// Scope personal access token (hanya di /api/auth/verify untuk PAT), dipakai gateway/book-service
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+}
+
+/// Response validasi session, termasuk sisa lifetime supaya client bisa refresh lebih awal
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionValidationResponse {
+    pub success: bool,
+    pub valid: bool,
+    pub message: String,
+    pub user_id: Option<Uuid>,
+    pub role: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub expires_in_seconds: Option<i64>,
+    pub user: Option<UserProfile>,
 }
 
 #[derive(Debug, Serialize, Clone, ToSchema)]
@@ -219,6 +342,54 @@ pub struct EnhancedClaims {
     pub aud: String,
     pub jti: String,
     pub token_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<usize>,
+}
+
+/// Body introspection token (RFC 7662)
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct IntrospectRequest {
+    pub token: String,
+}
+
+/// Hasil introspection, token tidak valid/expired/revoked hanya berisi `active: false`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+}
+
+impl IntrospectionResponse {
+    pub fn inactive() -> Self {
+        Self { active: false, sub: None, role: None, exp: None, iat: None, jti: None, token_type: None }
+    }
+}
+
+impl From<EnhancedClaims> for IntrospectionResponse {
+    fn from(claims: EnhancedClaims) -> Self {
+        Self {
+            active: true,
+            sub: Some(claims.sub),
+            role: Some(claims.role),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            jti: Some(claims.jti),
+            token_type: Some(claims.token_type),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -231,8 +402,83 @@ pub struct TokenPairResponse {
 
 #[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct RefreshTokenRequest {
-    pub refresh_token: String,
+    /// Opsional di mode cookie (diambil dari cookie refresh_token)
+    #[serde(default)]
+    pub refresh_token: Option<String>,
     pub device_fingerprint: Option<String>,
+    pub use_cookies: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchUserLookupRequest {
+    pub user_ids: Vec<Uuid>,
+}
+
+// ===== PERSONAL ACCESS TOKEN MODELS =====
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreatePersonalTokenRequest {
+    #[validate(length(min = 1, max = 100, message = "Nama token harus 1-100 karakter"))]
+    pub name: String,
+
+    #[validate(length(min = 1, message = "Minimal satu scope diperlukan"))]
+    pub scopes: Vec<String>,
+
+    #[validate(range(min = 1, max = 365, message = "Masa berlaku token 1-365 hari"))]
+    pub expires_in_days: Option<i64>,
+}
+
+/// Sesi login aktif (satu refresh token per device)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ActiveSession {
+    pub id: Uuid,
+    pub device_fingerprint: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub is_revoked: bool,
+    /// Sesi milik device yang sedang request (refresh token cocok)
+    pub is_current: bool,
+}
+
+/// Device terpercaya (login tanpa OTP) milik user
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TrustedDevice {
+    pub id: Uuid,
+    pub device_fingerprint: String,
+    pub device_info: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct PersonalAccessTokenInfo {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatePersonalTokenResponse {
+    pub success: bool,
+    pub message: String,
+    /// Token plaintext, hanya ditampilkan sekali
+    pub token: String,
+    pub data: PersonalAccessTokenInfo,
+}
+
+/// Data enrollment TOTP, secret hanya ditampilkan saat enroll
+#[derive(Debug, Serialize)]
+pub struct TotpEnrollment {
+    /// Secret base32 untuk input manual di authenticator app
+    pub secret: String,
+    pub otpauth_uri: String,
+    /// Isi QR code (otpauth URI), dirender menjadi gambar QR di client
+    pub qr_payload: String,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -241,6 +487,57 @@ pub struct LogoutRequest {
     pub access_token_jti: Option<String>,
 }
 
+// ===== NOTIFICATION PREFERENCE MODELS =====
+
+/// Kategori email terhadap preferensi notifikasi user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailCategory {
+    /// OTP login, verifikasi email, reset password: selalu dikirim
+    Mandatory,
+    /// Alert keamanan yang boleh dimatikan user; alert impossible travel dan
+    /// login device baru tidak lewat sini dan selalu dikirim
+    SecurityAlert,
+    OrderReceipt,
+    Marketing,
+}
+
+impl EmailCategory {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "mandatory" => Some(Self::Mandatory),
+            "security_alerts" => Some(Self::SecurityAlert),
+            "order_receipts" => Some(Self::OrderReceipt),
+            "marketing" => Some(Self::Marketing),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct NotificationPreferences {
+    pub security_alerts: bool,
+    pub order_receipts: bool,
+    pub marketing: bool,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Update sebagian preferensi, field kosong tidak diubah
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateNotificationPreferencesRequest {
+    pub security_alerts: Option<bool>,
+    pub order_receipts: Option<bool>,
+    pub marketing: Option<bool>,
+}
+
+/// Tandai aktivitas dibaca: `all: true` atau daftar `ids` (maksimal 200)
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct MarkActivityReadRequest {
+    #[serde(default)]
+    pub all: bool,
+    #[validate(length(min = 1, max = 200, message = "ids harus berisi 1-200 item"))]
+    pub ids: Option<Vec<Uuid>>,
+}
+
 // ===== OAUTH RESPONSE MODELS =====
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -326,9 +623,11 @@ pub struct UserActivity {
     pub metadata: Option<serde_json::Value>,
     pub severity: ActivitySeverity,
     pub location: Option<String>,
+    /// Waktu user menandai event sudah dibaca (None = belum dibaca / bukan event timeline)
+    pub read_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
 pub enum ActivitySeverity {
     Info,
     Warning,
@@ -336,6 +635,66 @@ pub enum ActivitySeverity {
     Security,
 }
 
+impl ActivitySeverity {
+    pub const ALL: [ActivitySeverity; 4] = [Self::Info, Self::Warning, Self::Critical, Self::Security];
+
+    /// Nama lowercase untuk query param dan SQL
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Critical => "critical",
+            Self::Security => "security",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.as_str().eq_ignore_ascii_case(value.trim()))
+    }
+}
+
+/// Jumlah security event per severity dalam periode feed
+#[derive(Debug, Serialize, Default)]
+pub struct SeverityCounts {
+    pub info: i64,
+    pub warning: i64,
+    pub critical: i64,
+    pub security: i64,
+}
+
+impl SeverityCounts {
+    pub fn add(&mut self, severity: ActivitySeverity, count: i64) {
+        match severity {
+            ActivitySeverity::Info => self.info += count,
+            ActivitySeverity::Warning => self.warning += count,
+            ActivitySeverity::Critical => self.critical += count,
+            ActivitySeverity::Security => self.security += count,
+        }
+    }
+
+    pub fn get(&self, severity: ActivitySeverity) -> i64 {
+        match severity {
+            ActivitySeverity::Info => self.info,
+            ActivitySeverity::Warning => self.warning,
+            ActivitySeverity::Critical => self.critical,
+            ActivitySeverity::Security => self.security,
+        }
+    }
+
+    pub fn total(&self) -> i64 {
+        self.info + self.warning + self.critical + self.security
+    }
+}
+
+/// Meta security activity feed admin: pagination + jumlah per severity
+#[derive(Debug, Serialize)]
+pub struct SecurityFeedMeta {
+    pub pagination: AdminPaginationMeta,
+    pub severity_counts: SeverityCounts,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct AdminPaginationMeta {
     pub current_page: u32,
@@ -393,6 +752,9 @@ pub struct ErrorResponse {
     pub request_id: Option<String>,
     pub trace_id: Option<String>,
     pub suggestions: Vec<String>,
+    /// Detik sampai request boleh diulang (response 429)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<i64>,
 }
 
 // ===== OAUTH GOOGLE MODELS =====
@@ -448,6 +810,30 @@ pub struct OAuthProvider {
 
 // ===== IMPLEMENTATIONS =====
 
+impl Default for NotificationPreferences {
+    /// Default untuk user yang belum pernah mengatur preferensi
+    fn default() -> Self {
+        Self {
+            security_alerts: true,
+            order_receipts: true,
+            marketing: false,
+            updated_at: None,
+        }
+    }
+}
+
+impl NotificationPreferences {
+    /// Email kategori ini boleh dikirim ke user
+    pub fn allows(&self, category: EmailCategory) -> bool {
+        match category {
+            EmailCategory::Mandatory => true,
+            EmailCategory::SecurityAlert => self.security_alerts,
+            EmailCategory::OrderReceipt => self.order_receipts,
+            EmailCategory::Marketing => self.marketing,
+        }
+    }
+}
+
 impl From<User> for UserProfile {
     fn from(user: User) -> Self {
         Self {
@@ -477,6 +863,8 @@ impl AuthResponse {
             session_id: None,
             requires_verification: None,
             two_factor_required: None,
+            trusted_device_token: None,
+            scopes: None,
         }
     }
 
@@ -491,6 +879,8 @@ impl AuthResponse {
             session_id: None,
             requires_verification: None,
             two_factor_required: None,
+            trusted_device_token: None,
+            scopes: None,
         }
     }
 
@@ -505,6 +895,36 @@ impl AuthResponse {
             session_id: None,
             requires_verification: None,
             two_factor_required: None,
+            trusted_device_token: None,
+            scopes: None,
+        }
+    }
+}
+
+impl SessionValidationResponse {
+    pub fn valid(user: UserProfile, expires_at: DateTime<Utc>, now: DateTime<Utc>) -> Self {
+        Self {
+            success: true,
+            valid: true,
+            message: "Session valid".to_string(),
+            user_id: Some(user.id),
+            role: Some(user.role.clone()),
+            expires_at: Some(expires_at),
+            expires_in_seconds: Some((expires_at - now).num_seconds().max(0)),
+            user: Some(user),
+        }
+    }
+
+    pub fn invalid(message: &str) -> Self {
+        Self {
+            success: false,
+            valid: false,
+            message: message.to_string(),
+            user_id: None,
+            role: None,
+            expires_at: None,
+            expires_in_seconds: None,
+            user: None,
         }
     }
 }
@@ -524,6 +944,30 @@ impl AdminPaginationMeta {
     }
 }
 
+impl<T> ApiResponse<T> {
+    /// Response sukses tanpa meta
+    pub fn ok(message: impl Into<String>, data: T) -> Self {
+        Self {
+            success: true,
+            message: message.into(),
+            data,
+            meta: None,
+        }
+    }
+}
+
+impl<T, M> ApiResponse<T, M> {
+    /// Tambahkan meta ke response
+    pub fn with_meta<N>(self, meta: N) -> ApiResponse<T, N> {
+        ApiResponse {
+            success: self.success,
+            message: self.message,
+            data: self.data,
+            meta: Some(meta),
+        }
+    }
+}
+
 impl ErrorResponse {
     pub fn new(message: &str, code: Option<&str>) -> Self {
         Self {
@@ -535,9 +979,32 @@ impl ErrorResponse {
             request_id: None,
             trace_id: None,
             suggestions: vec![],
+            retry_after: None,
         }
     }
 
+    /// Error rate limit dengan `retry_after` (detik)
+    pub fn retry_later(message: &str, code: &str, retry_after: i64) -> Self {
+        let mut error = Self::new(message, Some(code));
+        error.retry_after = Some(retry_after.max(1));
+        error
+    }
+
+    /// Error field melebihi batas panjang, detail per field di `details.fields`
+    pub fn input_too_long(fields: &[(&str, usize)]) -> Self {
+        let message = fields.iter()
+            .map(|(field, max)| format!("{}: maksimal {} karakter", field, max))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let details: serde_json::Map<String, serde_json::Value> = fields.iter()
+            .map(|(field, max)| (field.to_string(), serde_json::json!({ "max_length": max })))
+            .collect();
+
+        let mut error = Self::new(&message, Some("INPUT_TOO_LONG"));
+        error.details = Some(serde_json::json!({ "fields": details }));
+        error
+    }
+
     pub fn validation_error(errors: validator::ValidationErrors) -> Self {
         let mut error_messages = Vec::new();
         for (field, field_errors) in errors.field_errors() {
@@ -561,6 +1028,7 @@ impl ErrorResponse {
             request_id: None,
             trace_id: None,
             suggestions: vec![],
+            retry_after: None,
         }
     }
 }
@@ -629,4 +1097,59 @@ fn validate_phone(phone: &str) -> Result<(), ValidationError> {
     } else {
         Err(ValidationError::new("invalid_phone"))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_response_envelope_shape() {
+        let json = serde_json::to_value(
+            ApiResponse::ok("Feed aktivitas berhasil diambil", vec![1, 2])
+                .with_meta(serde_json::json!({ "total": 2 }))
+        ).unwrap();
+
+        assert_eq!(json["success"], true);
+        assert_eq!(json["message"], "Feed aktivitas berhasil diambil");
+        assert_eq!(json["data"], serde_json::json!([1, 2]));
+        assert_eq!(json["meta"]["total"], 2);
+
+        let bare = serde_json::to_value(ApiResponse::ok("ok", ())).unwrap();
+        assert!(bare.get("meta").is_none());
+    }
+
+    #[test]
+    fn test_notification_preferences_never_block_mandatory_email() {
+        let muted = NotificationPreferences {
+            security_alerts: false,
+            order_receipts: false,
+            marketing: false,
+            updated_at: None,
+        };
+
+        assert!(muted.allows(EmailCategory::Mandatory));
+        assert!(!muted.allows(EmailCategory::SecurityAlert));
+        assert!(!NotificationPreferences::default().allows(EmailCategory::Marketing));
+        assert_eq!(EmailCategory::parse("order_receipts"), Some(EmailCategory::OrderReceipt));
+        assert_eq!(EmailCategory::parse("newsletter"), None);
+    }
+
+    #[test]
+    fn test_profile_oversized_fields_reported_per_field() {
+        let limits = ProfileLengthLimits { full_name: 255, phone: 20, bio: 500, avatar_url: 500 };
+        let request = UpdateProfileRequest {
+            full_name: Some("Budi".to_string()),
+            phone: None,
+            bio: Some("x".repeat(501)),
+            avatar_url: None,
+        };
+
+        let oversized = request.oversized_fields(&limits);
+        assert_eq!(oversized, vec![("bio", 500)]);
+
+        let error = ErrorResponse::input_too_long(&oversized);
+        assert_eq!(error.error_code.as_deref(), Some("INPUT_TOO_LONG"));
+        assert_eq!(error.details.unwrap()["fields"]["bio"]["max_length"], 500);
+    }
+}
@@ -4,7 +4,7 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use std::collections::HashMap;
-use crate::utils::{AppError, AppResult};
+use crate::utils::{AdminNotifier, AppError, AppResult};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum CircuitState {
@@ -53,6 +53,7 @@ pub struct CircuitBreaker {
     stats: Arc<RwLock<CircuitStats>>,
     config: CircuitBreakerConfig,
     half_open_calls: Arc<RwLock<u32>>,
+    notifier: Option<Arc<AdminNotifier>>,
 }
 
 impl CircuitBreaker {
@@ -71,6 +72,7 @@ impl CircuitBreaker {
             })),
             config,
             half_open_calls: Arc::new(RwLock::new(0)),
+            notifier: None,
         }
     }
 
@@ -150,6 +152,12 @@ impl CircuitBreaker {
         ))
     }
 
+    /// True jika circuit OPEN dan belum waktunya dicoba lagi (half-open)
+    pub async fn is_open(&self) -> bool {
+        self.get_state().await == CircuitState::Open
+            && self.stats.read().await.state_changed_at.elapsed() < self.config.timeout_duration
+    }
+
     async fn get_state(&self) -> CircuitState {
         self.state.read().await.clone()
     }
@@ -190,6 +198,12 @@ impl CircuitBreaker {
                     "Circuit breaker {} transisi ke OPEN setelah {} failures", 
                     self.name, stats.consecutive_failures
                 );
+                if let Some(notifier) = &self.notifier {
+                    notifier.notify(
+                        &format!("circuit_open:{}", self.name),
+                        format!("Circuit breaker {} OPEN setelah {} kegagalan berturut-turut", self.name, stats.consecutive_failures),
+                    );
+                }
             }
         }
     }
@@ -234,6 +248,7 @@ impl CircuitBreaker {
 pub struct CircuitBreakerManager {
     breakers: Arc<RwLock<HashMap<String, Arc<CircuitBreaker>>>>,
     default_config: CircuitBreakerConfig,
+    notifier: Option<Arc<AdminNotifier>>,
 }
 
 impl CircuitBreakerManager {
@@ -241,9 +256,16 @@ impl CircuitBreakerManager {
         Self {
             breakers: Arc::new(RwLock::new(HashMap::new())),
             default_config: CircuitBreakerConfig::default(),
+            notifier: None,
         }
     }
 
+    /// Alert admin saat circuit breaker mana pun transisi ke OPEN
+    pub fn with_notifier(mut self, notifier: Arc<AdminNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
     pub async fn get_or_create(&self, service_name: &str) -> Arc<CircuitBreaker> {
         let mut breakers = self.breakers.write().await;
         
@@ -266,10 +288,10 @@ impl CircuitBreakerManager {
             _ => self.default_config.clone(),
         };
         
-        let breaker = Arc::new(CircuitBreaker::new(
-            service_name.to_string(),
-            config,
-        ));
+        let breaker = Arc::new(CircuitBreaker {
+            notifier: self.notifier.clone(),
+            ..CircuitBreaker::new(service_name.to_string(), config)
+        });
         
         breakers.insert(service_name.to_string(), breaker.clone());
         breaker
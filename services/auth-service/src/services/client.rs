@@ -1,7 +1,7 @@
 // /pdf-bookstore/services/auth-service/src/services/client.rs
 
 
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
 use std::time::Duration;
 use uuid::Uuid;
 use serde_json::Value;
@@ -11,7 +11,7 @@ use std::sync::Arc;
 use crate:: {
     models::{UserOrderStats, Purchase, BookDetails, DownloadedBook},
     services::CircuitBreakerManager,
-    utils::AppError,
+    utils::{AppError, AppResult},
 };
 
 /// Client untuk komunikasi antar service
@@ -20,8 +20,11 @@ pub struct ServiceClient {
     client: Client,
     book_service_url: String,
     payment_service_url: String,
+    gateway_url: String,
     internal_key: String,
     circuit_manager: Arc<CircuitBreakerManager>, 
+    call_timeout: Duration,
+    max_retries: u32,
 }
 
 impl ServiceClient {
@@ -29,27 +32,113 @@ impl ServiceClient {
     pub fn new(
         circuit_manager: Arc<CircuitBreakerManager>,
     ) -> Self { 
-        let client = Client::builder()
-            .timeout(Duration::from_secs(5))
-            .build()
-            .expect("Failed to create HTTP client");
-        
         let book_service_url = std::env::var("BOOK_SERVICE_URL")
             .unwrap_or_else(|_| "http://localhost:3002".to_string());
         
         let payment_service_url = std::env::var("PAYMENT_SERVICE_URL")
             .unwrap_or_else(|_| "http://localhost:3003".to_string());
         
+        Self::with_urls(circuit_manager, book_service_url, payment_service_url)
+    }
+
+    /// Service client dengan URL downstream eksplisit, API_GATEWAY_URL (default http://localhost:8000)
+    /// SERVICE_CALL_TIMEOUT_MS (default 2000) dan SERVICE_CALL_RETRIES (default 1) berlaku per panggilan
+    pub fn with_urls(
+        circuit_manager: Arc<CircuitBreakerManager>,
+        book_service_url: String,
+        payment_service_url: String,
+    ) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .expect("Failed to create HTTP client");
+        
+        let gateway_url = std::env::var("API_GATEWAY_URL")
+            .unwrap_or_else(|_| "http://localhost:8000".to_string());
+
         let internal_key = std::env::var("INTERNAL_SERVICE_KEY")
             .unwrap_or_else(|_| "internal-service-key-secret".to_string());
+
+        let call_timeout = Duration::from_millis(
+            std::env::var("SERVICE_CALL_TIMEOUT_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2000)
+        );
+
+        let max_retries = std::env::var("SERVICE_CALL_RETRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1);
         
         Self {
             client,
             book_service_url,
             payment_service_url,
+            gateway_url,
             internal_key,
             circuit_manager, 
+            call_timeout,
+            max_retries,
+        }
+    }
+
+    /// GET JSON ke service lain lewat circuit breaker, dengan timeout + retry per panggilan
+    /// Ok(None) = downstream menjawab non-2xx (bukan gangguan), Err = downstream tidak tersedia
+    async fn get_json<F>(&self, service: &str, build_request: F) -> AppResult<Option<Value>>
+    where
+        F: Fn() -> RequestBuilder,
+    {
+        let breaker = self.circuit_manager.get_or_create(service).await;
+        let mut last_error = None;
+
+        for attempt in 0..=self.max_retries {
+            if breaker.is_open().await {
+                return Err(AppError::ExternalService(format!("{} sedang tidak tersedia (circuit open)", service)));
+            }
+
+            if attempt > 0 {
+                tokio::time::sleep(Duration::from_millis(100 * attempt as u64)).await;
+            }
+
+            let request = build_request();
+            let timeout = self.call_timeout;
+
+            let result = breaker.call(async move {
+                tokio::time::timeout(timeout, async move {
+                    let response = request.send().await
+                        .map_err(|e| AppError::ExternalService(format!("{} error: {}", service, e)))?;
+
+                    if response.status().is_server_error() {
+                        return Err(AppError::ExternalService(
+                            format!("{} menjawab {}", service, response.status())
+                        ));
+                    }
+
+                    if !response.status().is_success() {
+                        return Ok(None);
+                    }
+
+                    response.json::<Value>().await
+                        .map(Some)
+                        .map_err(|e| AppError::ExternalService(format!("Parse error dari {}: {}", service, e)))
+                })
+                .await
+                .unwrap_or_else(|_| Err(AppError::ExternalService(
+                    format!("{} timeout setelah {}ms", service, timeout.as_millis())
+                )))
+            }).await;
+
+            match result {
+                Ok(data) => return Ok(data),
+                Err(e) => {
+                    tracing::warn!("Panggilan ke {} gagal (attempt {}): {}", service, attempt + 1, e);
+                    last_error = Some(e);
+                }
+            }
         }
+
+        Err(last_error.unwrap_or_else(|| AppError::ExternalService(format!("{} tidak tersedia", service))))
     }
     
     // ========== PAYMENT SERVICE CALLS ==========
@@ -58,40 +147,21 @@ impl ServiceClient {
     pub async fn get_user_order_stats(
         &self,
         user_id: Uuid
-    ) -> Result<UserOrderStats, Box<dyn std::error::Error>> {
-        let circuit_breaker = self.circuit_manager.get_or_create("payment-service").await;
+    ) -> AppResult<UserOrderStats> {
         let url = format!("{}/api/internal/users/{}/stats", self.payment_service_url, user_id);
-        let client = self.client.clone();
-        let key = self.internal_key.clone();
-        
-        circuit_breaker.call(async move {
-            let response = client
-                .get(&url)
-                .header("X-Service-Key", &key)
-                .send()
-                .await
-                .map_err(|e| AppError::ExternalService(format!("Payment service error: {}", e)))?;
-            
-            if response.status().is_success() {
-                let data: Value = response.json().await
-                    .map_err(|e| AppError::ExternalService(format!("Parse error: {}", e)))?;
-                Ok(UserOrderStats {
-                    total_orders: data["total_orders"].as_i64().unwrap_or(0),
-                    total_spent: data["total_spent"].as_f64().unwrap_or(0.0),
-                    last_purchase: data["last_purchase"]
-                        .as_str()
-                        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
-                        .map(|dt| dt.with_timezone(&Utc)),
-                })
-            } else {
-                Ok(UserOrderStats::default())
-            }
-        }).await.map_err(|e| {
-            Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other, 
-                format!("{:?}", e) // Pake Debug format karena AppError ga implement Display
-            )) as Box<dyn std::error::Error>
-        })
+
+        let data = self.get_json("payment-service", || {
+            self.client.get(&url).header("X-Service-Key", &self.internal_key)
+        }).await?;
+
+        Ok(data.map(|data| UserOrderStats {
+            total_orders: data["total_orders"].as_i64().unwrap_or(0),
+            total_spent: data["total_spent"].as_f64().unwrap_or(0.0),
+            last_purchase: data["last_purchase"]
+                .as_str()
+                .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+                .map(|dt| dt.with_timezone(&Utc)),
+        }).unwrap_or_default())
     }
 
     
@@ -100,23 +170,20 @@ impl ServiceClient {
         &self,
         user_id: Uuid,
         book_id: Uuid
-    ) -> Result<bool, Box<dyn std::error::Error>> {
-        let response = self.client
-            .get(format!("{}/api/internal/ownership/check", self.payment_service_url))
-            .query(&[
-                ("user_id", user_id.to_string()),
-                ("book_id", book_id.to_string()),
-            ])
-            .header("X-Service-Key", &self.internal_key)
-            .send()
-            .await?;
-        
-        if response.status().is_success() {
-            let data: Value = response.json().await?;
-            Ok(data["owns_book"].as_bool().unwrap_or(false))
-        } else {
-            Ok(false)
-        }
+    ) -> AppResult<bool> {
+        let url = format!("{}/api/internal/ownership/check", self.payment_service_url);
+
+        let data = self.get_json("payment-service", || {
+            self.client
+                .get(&url)
+                .query(&[
+                    ("user_id", user_id.to_string()),
+                    ("book_id", book_id.to_string()),
+                ])
+                .header("X-Service-Key", &self.internal_key)
+        }).await?;
+
+        Ok(data.and_then(|data| data["owns_book"].as_bool()).unwrap_or(false))
     }
     
     /// Mendapatkan riwayat pembelian user
@@ -124,22 +191,20 @@ impl ServiceClient {
         &self,
         user_id: Uuid,
         limit: Option<u32>
-    ) -> Result<Vec<Purchase>, Box<dyn std::error::Error>> {
-        let mut request = self.client
-            .get(format!("{}/api/internal/users/{}/purchases", self.payment_service_url, user_id))
-            .header("X-Service-Key", &self.internal_key);
-        
-        if let Some(limit) = limit {
-            request = request.query(&[("limit", limit.to_string())]);
-        }
-        
-        let response = request.send().await?;
-        
-        if response.status().is_success() {
-            let data: Value = response.json().await?;
-            let purchases = data["purchases"].as_array()
-                .unwrap_or(&vec![])
-                .iter()
+    ) -> AppResult<Vec<Purchase>> {
+        let url = format!("{}/api/internal/users/{}/purchases", self.payment_service_url, user_id);
+
+        let data = self.get_json("payment-service", || {
+            let request = self.client.get(&url).header("X-Service-Key", &self.internal_key);
+            match limit {
+                Some(limit) => request.query(&[("limit", limit.to_string())]),
+                None => request,
+            }
+        }).await?;
+
+        let purchases = data.as_ref()
+            .and_then(|data| data["purchases"].as_array())
+            .map(|purchases| purchases.iter()
                 .filter_map(|p| {
                     Some(Purchase {
                         order_id: p["order_id"].as_str()?.parse().ok()?,
@@ -151,55 +216,76 @@ impl ServiceClient {
                             .map(|dt| dt.with_timezone(&Utc)),
                     })
                 })
-                .collect();
-            Ok(purchases)
-        } else {
-            Ok(vec![])
-        }
+                .collect())
+            .unwrap_or_default();
+
+        Ok(purchases)
     }
     
+    // ========== API GATEWAY CALLS ==========
+
+    /// Cabut token di cache verifikasi lokal gateway (JWKS)
+    /// `jti` Some = satu access token (logout), None = semua token user yang terbit sampai `revoked_at`
+    pub async fn invalidate_gateway_tokens(
+        &self,
+        user_id: Uuid,
+        jti: Option<&str>,
+        revoked_at: DateTime<Utc>,
+    ) -> AppResult<()> {
+        let url = format!("{}/api/internal/cache/invalidate-token", self.gateway_url);
+        let body = serde_json::json!({ "user_id": user_id, "jti": jti, "revoked_at": revoked_at.timestamp() });
+
+        let data = self.get_json("api-gateway", || {
+            self.client.post(&url).header("X-Service-Key", &self.internal_key).json(&body)
+        }).await?;
+
+        data.map(|_| ()).ok_or_else(|| AppError::ExternalService("api-gateway menolak invalidasi token".to_string()))
+    }
+
+    /// Invalidasi di background: logout / revoke tetap berhasil walau gateway sedang tidak tersedia
+    pub fn spawn_gateway_invalidation(self: &Arc<Self>, user_id: Uuid, jti: Option<String>, revoked_at: DateTime<Utc>) {
+        let client = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = client.invalidate_gateway_tokens(user_id, jti.as_deref(), revoked_at).await {
+                tracing::warn!("Gagal invalidasi token user {} di api-gateway: {}", user_id, e);
+            }
+        });
+    }
+
     // ========== BOOK SERVICE CALLS ==========
     
-    /// Mendapatkan detail buku dari book service
+    /// Mendapatkan detail buku dari book service (None jika buku tidak ditemukan)
     pub async fn get_book_details(
         &self,
         book_id: Uuid
-    ) -> Result<BookDetails, Box<dyn std::error::Error>> {
-        let response = self.client
-            .get(format!("{}/api/books/{}", self.book_service_url, book_id))
-            .send()
-            .await?;
-        
-        if response.status().is_success() {
-            let data: Value = response.json().await?;
-            Ok(BookDetails {
-                id: book_id,
-                title: data["data"]["book"]["title"].as_str().unwrap_or("").to_string(),
-                author: data["data"]["book"]["author"].as_str().unwrap_or("").to_string(),
-                price: data["data"]["book"]["price"].as_f64().unwrap_or(0.0),
-                is_active: data["data"]["book"]["is_active"].as_bool().unwrap_or(true),
-            })
-        } else {
-            Err("Book not found".into())
-        }
+    ) -> AppResult<Option<BookDetails>> {
+        let url = format!("{}/api/books/{}", self.book_service_url, book_id);
+
+        let data = self.get_json("book-service", || self.client.get(&url)).await?;
+
+        Ok(data.map(|data| BookDetails {
+            id: book_id,
+            title: data["data"]["book"]["title"].as_str().unwrap_or("").to_string(),
+            author: data["data"]["book"]["author"].as_str().unwrap_or("").to_string(),
+            price: data["data"]["book"]["price"].as_f64().unwrap_or(0.0),
+            is_active: data["data"]["book"]["is_active"].as_bool().unwrap_or(true),
+        }))
     }
     
     /// Mendapatkan daftar buku yang sudah didownload user
     pub async fn get_user_downloaded_books(
         &self,
         user_id: Uuid
-    ) -> Result<Vec<DownloadedBook>, Box<dyn std::error::Error>> {
-        let response = self.client
-            .get(format!("{}/api/internal/users/{}/downloads", self.book_service_url, user_id))
-            .header("X-Service-Key", &self.internal_key)
-            .send()
-            .await?;
-        
-        if response.status().is_success() {
-            let data: Value = response.json().await?;
-            let books = data["downloads"].as_array()
-                .unwrap_or(&vec![])
-                .iter()
+    ) -> AppResult<Vec<DownloadedBook>> {
+        let url = format!("{}/api/internal/users/{}/downloads", self.book_service_url, user_id);
+
+        let data = self.get_json("book-service", || {
+            self.client.get(&url).header("X-Service-Key", &self.internal_key)
+        }).await?;
+
+        let books = data.as_ref()
+            .and_then(|data| data["downloads"].as_array())
+            .map(|books| books.iter()
                 .filter_map(|b| {
                     Some(DownloadedBook {
                         book_id: b["book_id"].as_str()?.parse().ok()?,
@@ -210,11 +296,10 @@ impl ServiceClient {
                             .map(|dt| dt.with_timezone(&Utc)),
                     })
                 })
-                .collect();
-            Ok(books)
-        } else {
-            Ok(vec![])
-        }
+                .collect())
+            .unwrap_or_default();
+
+        Ok(books)
     }
     
     /// Mengecek ketersediaan buku
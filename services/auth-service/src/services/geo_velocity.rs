@@ -0,0 +1,252 @@
+// /pdf-bookstore/services/auth-service/src/services/geo_velocity.rs
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{PgPool, Row};
+use std::{collections::HashMap, env, net::IpAddr};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Batas entry cache lookup GeoIP sebelum dikosongkan
+const GEO_CACHE_LIMIT: usize = 10_000;
+
+/// Koordinat hasil lookup GeoIP
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoPoint {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// Dua login dari lokasi berjauhan dalam waktu yang tidak masuk akal
+#[derive(Debug, Clone)]
+pub struct ImpossibleTravel {
+    pub previous_ip: String,
+    pub previous_at: DateTime<Utc>,
+    pub distance_km: f64,
+    pub speed_kmh: f64,
+}
+
+/// Deteksi "impossible travel" dari login_history (opt-in)
+/// GeoIP tidak tersedia / IP private = tidak ada hasil, login tidak pernah diblokir karena check ini
+pub struct GeoVelocityChecker {
+    enabled: bool,
+    lookup_url: String,
+    http: reqwest::Client,
+    max_speed_kmh: f64,
+    min_distance_km: f64,
+    lookback: Duration,
+    cache: RwLock<HashMap<IpAddr, Option<GeoPoint>>>,
+}
+
+impl GeoVelocityChecker {
+    /// GEO_VELOCITY_ENABLED (default false), GEOIP_LOOKUP_URL (template dengan `{ip}`,
+    /// response JSON berisi lat/lon atau latitude/longitude), GEO_VELOCITY_MAX_SPEED_KMH (default 900),
+    /// GEO_VELOCITY_MIN_DISTANCE_KM (default 300), GEO_VELOCITY_LOOKBACK_HOURS (default 24),
+    /// GEOIP_TIMEOUT_MS (default 1500)
+    pub fn from_env() -> Self {
+        let read = |key: &str, default: f64| {
+            env::var(key).ok().and_then(|v| v.parse::<f64>().ok()).unwrap_or(default)
+        };
+
+        let lookup_url = env::var("GEOIP_LOOKUP_URL").unwrap_or_default();
+        let mut enabled = env::var("GEO_VELOCITY_ENABLED")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+
+        if enabled && !lookup_url.contains("{ip}") {
+            tracing::warn!("GEO_VELOCITY_ENABLED aktif tapi GEOIP_LOOKUP_URL tidak berisi {{ip}}, check dinonaktifkan");
+            enabled = false;
+        }
+
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_millis(read("GEOIP_TIMEOUT_MS", 1500.0) as u64))
+            .build()
+            .unwrap_or_default();
+
+        if enabled {
+            tracing::info!("Geo-velocity check aktif");
+        }
+
+        Self {
+            enabled,
+            lookup_url,
+            http,
+            max_speed_kmh: read("GEO_VELOCITY_MAX_SPEED_KMH", 900.0),
+            min_distance_km: read("GEO_VELOCITY_MIN_DISTANCE_KM", 300.0),
+            lookback: Duration::hours(read("GEO_VELOCITY_LOOKBACK_HOURS", 24.0) as i64),
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Bandingkan login sekarang dengan login sukses terakhir user dari IP lain
+    pub async fn check(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        current_ip: IpAddr,
+        now: DateTime<Utc>,
+    ) -> Option<ImpossibleTravel> {
+        if !self.enabled || !is_public_ip(&current_ip) {
+            return None;
+        }
+
+        let current = self.lookup(current_ip).await?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT host(ip_address) AS ip, created_at
+            FROM login_history
+            WHERE user_id = $1
+              AND login_status IN ('password_success', 'success')
+              AND ip_address IS NOT NULL
+              AND ip_address <> $2::inet
+              AND created_at >= $3
+            ORDER BY created_at DESC
+            LIMIT 5
+            "#
+        )
+        .bind(user_id)
+        .bind(current_ip.to_string())
+        .bind(now - self.lookback)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| tracing::warn!("Gagal membaca login_history untuk geo-velocity: {}", e))
+        .ok()?;
+
+        for row in rows {
+            let ip: String = row.get("ip");
+            let previous_at: DateTime<Utc> = row.get("created_at");
+
+            let Some(previous_ip) = ip.parse::<IpAddr>().ok().filter(is_public_ip) else {
+                continue;
+            };
+            let Some(previous) = self.lookup(previous_ip).await else {
+                continue;
+            };
+
+            if let Some((distance_km, speed_kmh)) = self.evaluate(previous, previous_at, current, now) {
+                return Some(ImpossibleTravel { previous_ip: ip, previous_at, distance_km, speed_kmh });
+            }
+        }
+
+        None
+    }
+
+    /// Jarak + kecepatan jika melebihi batas, None jika perjalanan masih wajar
+    fn evaluate(
+        &self,
+        previous: GeoPoint,
+        previous_at: DateTime<Utc>,
+        current: GeoPoint,
+        now: DateTime<Utc>,
+    ) -> Option<(f64, f64)> {
+        let distance_km = haversine_km(previous, current);
+        if distance_km < self.min_distance_km {
+            return None;
+        }
+
+        // Minimal 1 menit supaya login hampir bersamaan tidak membagi dengan nol
+        let hours = ((now - previous_at).num_seconds().max(60) as f64) / 3600.0;
+        let speed_kmh = distance_km / hours;
+
+        (speed_kmh > self.max_speed_kmh).then_some((distance_km, speed_kmh))
+    }
+
+    async fn lookup(&self, ip: IpAddr) -> Option<GeoPoint> {
+        if let Some(cached) = self.cache.read().await.get(&ip) {
+            return *cached;
+        }
+
+        let url = self.lookup_url.replace("{ip}", &ip.to_string());
+        let point = match self.http.get(&url).send().await {
+            Ok(resp) if resp.status().is_success() => resp.json::<serde_json::Value>().await
+                .ok()
+                .and_then(|body| parse_geo_response(&body)),
+            Ok(resp) => {
+                tracing::debug!("GeoIP lookup {} gagal: {}", ip, resp.status());
+                return None;
+            }
+            Err(e) => {
+                // Jangan cache error jaringan, service mungkin hanya sementara down
+                tracing::debug!("GeoIP tidak tersedia: {}", e);
+                return None;
+            }
+        };
+
+        let mut cache = self.cache.write().await;
+        if cache.len() >= GEO_CACHE_LIMIT {
+            cache.clear();
+        }
+        cache.insert(ip, point);
+        point
+    }
+}
+
+/// Format umum service GeoIP: `{lat, lon}` (ip-api) atau `{latitude, longitude}`
+fn parse_geo_response(body: &serde_json::Value) -> Option<GeoPoint> {
+    if body["status"].as_str() == Some("fail") {
+        return None;
+    }
+
+    let lat = body["lat"].as_f64().or_else(|| body["latitude"].as_f64())?;
+    let lon = body["lon"].as_f64().or_else(|| body["longitude"].as_f64())?;
+
+    ((-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon))
+        .then_some(GeoPoint { lat, lon })
+}
+
+/// Jarak great-circle dalam km
+fn haversine_km(a: GeoPoint, b: GeoPoint) -> f64 {
+    const EARTH_RADIUS_KM: f64 = 6371.0;
+
+    let d_lat = (b.lat - a.lat).to_radians();
+    let d_lon = (b.lon - a.lon).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2)
+        + a.lat.to_radians().cos() * b.lat.to_radians().cos() * (d_lon / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS_KM * h.sqrt().asin()
+}
+
+/// IP private/loopback tidak punya lokasi GeoIP yang berarti
+fn is_public_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => !(v4.is_private() || v4.is_loopback() || v4.is_link_local() || v4.is_unspecified()),
+        IpAddr::V6(v6) => !(v6.is_loopback() || v6.is_unspecified() || (v6.segments()[0] & 0xfe00) == 0xfc00),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_impossible_travel_detection() {
+        let checker = GeoVelocityChecker {
+            enabled: true,
+            lookup_url: "http://geoip/{ip}".to_string(),
+            http: reqwest::Client::new(),
+            max_speed_kmh: 900.0,
+            min_distance_km: 300.0,
+            lookback: Duration::hours(24),
+            cache: RwLock::new(HashMap::new()),
+        };
+
+        let jakarta = parse_geo_response(&serde_json::json!({"lat": -6.2, "lon": 106.8})).unwrap();
+        let london = parse_geo_response(&serde_json::json!({"latitude": 51.5, "longitude": -0.1})).unwrap();
+        let bandung = GeoPoint { lat: -6.9, lon: 107.6 };
+        let now = Utc::now();
+
+        let distance = haversine_km(jakarta, london);
+        assert!((11_000.0..12_500.0).contains(&distance));
+
+        // Jakarta -> London dalam 2 jam: mustahil
+        assert!(checker.evaluate(jakarta, now - Duration::hours(2), london, now).is_some());
+        // Jakarta -> London dalam 20 jam: wajar (penerbangan)
+        assert!(checker.evaluate(jakarta, now - Duration::hours(20), london, now).is_none());
+        // Jarak dekat selalu diabaikan
+        assert!(checker.evaluate(jakarta, now - Duration::minutes(1), bandung, now).is_none());
+
+        assert!(parse_geo_response(&serde_json::json!({"status": "fail", "lat": 0.0, "lon": 0.0})).is_none());
+        assert!(!is_public_ip(&"192.168.1.10".parse().unwrap()));
+        assert!(is_public_ip(&"8.8.8.8".parse().unwrap()));
+    }
+}
@@ -4,8 +4,10 @@ pub mod client;
 pub mod circuit_breaker;
 pub mod service_discovery;
 pub mod oauth_service;
+pub mod geo_velocity;
 
 pub use client::ServiceClient;
 pub use circuit_breaker::CircuitBreakerManager;
 pub use service_discovery::ServiceRegistry;
-pub use oauth_service::OAuthService;
\ No newline at end of file
+pub use oauth_service::OAuthService;
+pub use geo_velocity::GeoVelocityChecker;
\ No newline at end of file
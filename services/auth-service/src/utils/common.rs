@@ -2,7 +2,15 @@
 
 use sha2::{Sha256, Digest};
 use axum::http::HeaderMap;
+use lazy_static::lazy_static;
 use std::env;
+use std::net::IpAddr;
+use service_common::client_ip::ClientIpResolver;
+
+lazy_static! {
+    /// Proxy terpercaya dari TRUSTED_PROXIES (comma-separated), parser sama dengan gateway & book-service
+    static ref CLIENT_IP: ClientIpResolver = ClientIpResolver::from_env();
+}
 
 /// Mengambil password pepper dari environment variable
 pub fn get_pepper() -> String {
@@ -10,6 +18,71 @@ pub fn get_pepper() -> String {
         .unwrap_or_else(|_| "default_pepper_change_in_production".to_string())
 }
 
+/// Masa berlaku OTP login (OTP_EXPIRY_MINUTES, default 5 menit)
+pub fn otp_lifetime() -> chrono::Duration {
+    chrono::Duration::minutes(env_positive("OTP_EXPIRY_MINUTES", 5))
+}
+
+/// Jeda minimal antar kirim ulang OTP login (OTP_RESEND_COOLDOWN_SECONDS, default 60 detik)
+pub fn otp_resend_cooldown() -> chrono::Duration {
+    chrono::Duration::seconds(env_positive("OTP_RESEND_COOLDOWN_SECONDS", 60))
+}
+
+/// Batas kirim ulang OTP per window: (OTP_RESEND_MAX, default 3) per (OTP_RESEND_WINDOW_MINUTES, default 15 menit)
+pub fn otp_resend_limit() -> (i32, chrono::Duration) {
+    (
+        env_positive("OTP_RESEND_MAX", 3) as i32,
+        chrono::Duration::minutes(env_positive("OTP_RESEND_WINDOW_MINUTES", 15)),
+    )
+}
+
+/// Batas percobaan verify-otp per kode login (OTP_MAX_ATTEMPTS, default 5)
+pub fn otp_max_attempts() -> i32 {
+    env_positive("OTP_MAX_ATTEMPTS", 5) as i32
+}
+
+/// Masa berlaku link verifikasi email (EMAIL_VERIFICATION_EXPIRY_HOURS, default 24 jam)
+pub fn verification_token_lifetime() -> chrono::Duration {
+    chrono::Duration::hours(env_positive("EMAIL_VERIFICATION_EXPIRY_HOURS", 24))
+}
+
+/// Masa berlaku kode reset password (PASSWORD_RESET_EXPIRY_MINUTES, default 60 menit)
+pub fn password_reset_lifetime() -> chrono::Duration {
+    chrono::Duration::minutes(env_positive("PASSWORD_RESET_EXPIRY_MINUTES", 60))
+}
+
+/// Masa berlaku trusted device / login tanpa OTP (TRUSTED_DEVICE_DAYS, default 30 hari)
+pub fn trusted_device_lifetime() -> chrono::Duration {
+    chrono::Duration::days(env_positive("TRUSTED_DEVICE_DAYS", 30))
+}
+
+/// Email peringatan login dari device baru (LOGIN_ALERTS_ENABLED, default false)
+pub fn login_alerts_enabled() -> bool {
+    env::var("LOGIN_ALERTS_ENABLED")
+        .map(|v| v == "true" || v == "1")
+        .unwrap_or(false)
+}
+
+/// Format durasi untuk teks email, mis. "5 minutes" / "24 hours"
+pub fn describe_duration(duration: chrono::Duration) -> String {
+    let minutes = duration.num_minutes();
+    match minutes {
+        60 => "1 hour".to_string(),
+        m if m % 60 == 0 => format!("{} hours", m / 60),
+        1 => "1 minute".to_string(),
+        m => format!("{} minutes", m),
+    }
+}
+
+/// Baca angka positif dari env, fallback ke default
+fn env_positive(key: &str, default: i64) -> i64 {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v: &i64| *v > 0)
+        .unwrap_or(default)
+}
+
 /// Hash token menggunakan SHA256
 pub fn hash_token(token: &str) -> String {
     let mut hasher = Sha256::new();
@@ -40,4 +113,10 @@ pub fn extract_device_info(headers: &HeaderMap) -> Option<String> {
                 ua.to_string()
             }
         })
-}
\ No newline at end of file
+}
+
+/// Resolve IP client asli dari X-Forwarded-For / X-Real-IP
+/// Header hanya dipercaya kalau peer langsung termasuk TRUSTED_PROXIES
+pub fn resolve_client_ip(peer_ip: IpAddr, headers: &HeaderMap) -> IpAddr {
+    CLIENT_IP.resolve(peer_ip, headers)
+}
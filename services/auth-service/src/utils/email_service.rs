@@ -1,5 +1,6 @@
 // /pdf-bookstore/services/auth-service/src/utils/email_service.rs
 
+use async_trait::async_trait;
 use lettre::{
     Message, 
     AsyncSmtpTransport,
@@ -7,8 +8,11 @@ use lettre::{
     message::header::ContentType,
     transport::smtp::authentication::Credentials,
 };
+use service_common::admin_notifier::AlertMailer;
 use std::env;
 
+use super::common::{describe_duration, otp_lifetime, password_reset_lifetime, verification_token_lifetime};
+
 pub struct EmailService {
     mailer: AsyncSmtpTransport<lettre::Tokio1Executor>,
     from_email: String,
@@ -51,10 +55,10 @@ impl EmailService {
                     Verify Email
                 </a>
                 <p>Or copy this link: {}</p>
-                <p>This link expires in 24 hours.</p>
+                <p>This link expires in {}.</p>
             </body>
             </html>"#,
-            verify_link, verify_link
+            verify_link, verify_link, describe_duration(verification_token_lifetime())
         );
         
         let email = Message::builder()
@@ -81,11 +85,11 @@ impl EmailService {
                 <div style="font-size: 32px; font-weight: bold; padding: 20px; background: #f0f0f0; text-align: center; font-family: monospace; border-radius: 5px;">
                     {}
                 </div>
-                <p>This code expires in 5 minutes.</p>
+                <p>This code expires in {}.</p>
                 <p>If you didn't request this, please ignore this email.</p>
             </body>
             </html>"#,
-            otp
+            otp, describe_duration(otp_lifetime())
         );
         
         let email = Message::builder()
@@ -113,12 +117,12 @@ impl EmailService {
                 <div style="font-size: 32px; font-weight: bold; padding: 20px; background: #f0f0f0; text-align: center; font-family: monospace; border-radius: 5px; letter-spacing: 4px;">
                     {}
                 </div>
-                <p>This code expires in 1 hour.</p>
+                <p>This code expires in {}.</p>
                 <p><strong>Important:</strong> If you didn't request this password reset, please ignore this email and ensure your account is secure.</p>
                 <p style="color: #888; font-size: 12px;">For security reasons, never share this code with anyone.</p>
             </body>
             </html>"#,
-            reset_code
+            reset_code, describe_duration(password_reset_lifetime())
         );
 
         let email = Message::builder()
@@ -131,4 +135,105 @@ impl EmailService {
         self.mailer.send(email).await?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    pub async fn send_security_alert(
+        &self,
+        to: &str,
+        ip_address: &str,
+        login_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let body = format!(
+            r#"<!DOCTYPE html>
+            <html>
+            <body>
+                <h2>Unusual Sign-in Detected</h2>
+                <p>Your account was signed in from a location far from your previous sign-in, in a time that would be impossible to travel.</p>
+                <p><strong>IP address:</strong> {}<br><strong>Time:</strong> {} UTC</p>
+                <p>If this was you, no action is needed. Otherwise, change your password immediately and sign out of all sessions.</p>
+            </body>
+            </html>"#,
+            ip_address, login_at.format("%Y-%m-%d %H:%M")
+        );
+
+        let email = Message::builder()
+            .from(self.from_email.parse()?)
+            .to(to.parse()?)
+            .subject("Security alert: unusual sign-in to your Bookstore account")
+            .header(ContentType::TEXT_HTML)
+            .body(body)?;
+
+        self.mailer.send(email).await?;
+        Ok(())
+    }
+
+    /// Peringatan login dari device yang belum pernah dipakai login sebelumnya
+    pub async fn send_new_device_alert(
+        &self,
+        to: &str,
+        ip_address: &str,
+        login_at: chrono::DateTime<chrono::Utc>,
+        user_agent: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let body = format!(
+            r#"<!DOCTYPE html>
+            <html>
+            <body>
+                <h2>New Device Signed In</h2>
+                <p>Your account was just signed in from a device we haven't seen before.</p>
+                <p><strong>IP address:</strong> {}<br><strong>Time:</strong> around {} UTC<br><strong>Device:</strong> {}</p>
+                <p>If this was you, no action is needed. Otherwise, change your password immediately and sign out of all sessions.</p>
+            </body>
+            </html>"#,
+            ip_address,
+            login_at.format("%Y-%m-%d %H:%M"),
+            escape_html(user_agent.unwrap_or("Unknown device"))
+        );
+
+        let email = Message::builder()
+            .from(self.from_email.parse()?)
+            .to(to.parse()?)
+            .subject("New device signed in to your Bookstore account")
+            .header(ContentType::TEXT_HTML)
+            .body(body)?;
+
+        self.mailer.send(email).await?;
+        Ok(())
+    }
+
+    /// Alert operasional untuk admin (plain text)
+    pub async fn send_admin_alert(
+        &self,
+        to: &str,
+        message: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let email = Message::builder()
+            .from(self.from_email.parse()?)
+            .to(to.parse()?)
+            .subject("[Bookstore] Admin alert")
+            .header(ContentType::TEXT_PLAIN)
+            .body(message.to_string())?;
+
+        self.mailer.send(email).await?;
+        Ok(())
+    }
+}
+
+/// Admin alert (ADMIN_ALERT_CHANNEL=email) lewat SMTP yang sama dengan email user
+/// EmailService dibuat per alert supaya SMTP yang belum siap saat startup tidak menggagalkan notifier
+pub struct SmtpAlertMailer;
+
+#[async_trait]
+impl AlertMailer for SmtpAlertMailer {
+    async fn send_alert(&self, to: &str, message: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        EmailService::new().await?.send_admin_alert(to, message).await
+    }
+}
+
+/// User agent berasal dari client, di-escape sebelum masuk body HTML
+fn escape_html(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -3,9 +3,17 @@
 pub mod error;
 pub mod common;
 pub mod scheduler;
+pub mod retention;
+pub mod token_purge;
 pub mod email_service;
 
 pub use error::{AppError, AppResult};
-pub use common::{get_pepper, hash_token, contains_suspicious_patterns, extract_device_info};
+pub use common::{
+    get_pepper, hash_token, contains_suspicious_patterns, extract_device_info, resolve_client_ip,
+    otp_lifetime, otp_max_attempts, otp_resend_cooldown, otp_resend_limit, verification_token_lifetime, password_reset_lifetime,
+    trusted_device_lifetime, login_alerts_enabled,
+};
 pub use scheduler::start_token_cleanup_job;
-pub use email_service::EmailService;
\ No newline at end of file
+pub use service_common::shutdown::Shutdown;
+pub use email_service::{EmailService, SmtpAlertMailer};
+pub use service_common::admin_notifier::AdminNotifier;
\ No newline at end of file
@@ -0,0 +1,13 @@
+// /pdf-bookstore/services/auth-service/src/utils/retention.rs
+
+use service_common::retention::{RetentionConfig, RetentionPolicy};
+
+/// Tabel log milik auth-service yang di-purge retention job
+/// Set `RETENTION_<TABLE>_DAYS=0` untuk menonaktifkan tabel tertentu
+pub fn retention_config() -> RetentionConfig {
+    RetentionConfig::from_env(vec![
+        RetentionPolicy::from_env("security_events", 180),
+        RetentionPolicy::from_env("login_history", 90),
+        RetentionPolicy::from_env("email_send_log", 7),
+    ])
+}
@@ -2,23 +2,32 @@
 
 use tokio_cron_scheduler::{JobScheduler, Job};
 use sqlx::PgPool;
+use crate::utils::retention::retention_config;
+use service_common::{retention::run_retention, shutdown::Shutdown};
+use crate::utils::token_purge::{purge_auth_tokens, TokenPurgeConfig};
 
-pub async fn start_token_cleanup_job(pool: PgPool) -> Result<(), Box<dyn std::error::Error>> {
-    let scheduler = JobScheduler::new().await?;
+/// Start scheduler cleanup token/session
+/// Saat shutdown, iterasi yang sedang jalan diselesaikan dan tidak ada trigger baru
+pub async fn start_token_cleanup_job(pool: PgPool, shutdown: Shutdown) -> Result<(), Box<dyn std::error::Error>> {
+    let mut scheduler = JobScheduler::new().await?;
 
     // Job 1: Cleanup expired tokens setiap jam
     let pool_clone1 = pool.clone();
+    let shutdown_clone1 = shutdown.clone();
     let cleanup_job = Job::new_async("0 0 * * * *", move |_uuid, _l| {
         let pool = pool_clone1.clone();
+        let shutdown = shutdown_clone1.clone();
         Box::pin(async move {
-            match cleanup_expired_tokens(&pool).await {
-                Ok(result) => {
-                    tracing::info!("Token cleanup result: {}", result);
-                }
-                Err(e) => {
-                    tracing::error!("Token cleanup failed: {}", e);
+            shutdown.run_job(async move {
+                match cleanup_expired_tokens(&pool).await {
+                    Ok(result) => {
+                        tracing::info!("Token cleanup result: {}", result);
+                    }
+                    Err(e) => {
+                        tracing::error!("Token cleanup failed: {}", e);
+                    }
                 }
-            }
+            }).await;
         })
     })?;
 
@@ -26,19 +35,23 @@ pub async fn start_token_cleanup_job(pool: PgPool) -> Result<(), Box<dyn std::er
 
     // Job 2: Cleanup expired sessions setiap 30 menit
     let pool_clone2 = pool.clone();
+    let shutdown_clone2 = shutdown.clone();
     let session_cleanup_job = Job::new_async("0 */30 * * * *", move |_uuid, _l| {
         let pool = pool_clone2.clone();
+        let shutdown = shutdown_clone2.clone();
         Box::pin(async move {
-            match cleanup_expired_sessions(&pool).await {
-                Ok(count) => {
-                    if count > 0 {
-                        tracing::info!("Cleaned up {} expired sessions", count);
+            shutdown.run_job(async move {
+                match cleanup_expired_sessions(&pool).await {
+                    Ok(count) => {
+                        if count > 0 {
+                            tracing::info!("Cleaned up {} expired sessions", count);
+                        }
+                    }
+                    Err(e) => {
+                        tracing::error!("Session cleanup failed: {}", e);
                     }
                 }
-                Err(e) => {
-                    tracing::error!("Session cleanup failed: {}", e);
-                }
-            }
+            }).await;
         })
     })?;
 
@@ -46,26 +59,90 @@ pub async fn start_token_cleanup_job(pool: PgPool) -> Result<(), Box<dyn std::er
 
     // Job 3: Cleanup old inactive sessions (>30 days)
     let pool_clone3 = pool.clone();
+    let shutdown_clone3 = shutdown.clone();
     let old_session_cleanup_job = Job::new_async("0 0 2 * * *", move |_uuid, _l| {
         let pool = pool_clone3.clone();
+        let shutdown = shutdown_clone3.clone();
         Box::pin(async move {
-            match cleanup_old_sessions(&pool).await {
-                Ok(count) => {
-                    if count > 0 {
-                        tracing::info!("Cleaned up {} old sessions", count);
+            shutdown.run_job(async move {
+                match cleanup_old_sessions(&pool).await {
+                    Ok(count) => {
+                        if count > 0 {
+                            tracing::info!("Cleaned up {} old sessions", count);
+                        }
+                    }
+                    Err(e) => {
+                        tracing::error!("Old session cleanup failed: {}", e);
                     }
                 }
-                Err(e) => {
-                    tracing::error!("Old session cleanup failed: {}", e);
-                }
-            }
+            }).await;
         })
     })?;
 
     scheduler.add(old_session_cleanup_job).await?;
 
+    // Job 4: Retention security_events & login_history setiap hari jam 03:00
+    let pool_clone4 = pool.clone();
+    let shutdown_clone4 = shutdown.clone();
+    let retention_config = retention_config();
+    let retention_job = Job::new_async("0 0 3 * * *", move |_uuid, _l| {
+        let pool = pool_clone4.clone();
+        let shutdown = shutdown_clone4.clone();
+        let config = retention_config.clone();
+        Box::pin(async move {
+            shutdown.run_job(async move {
+                match run_retention(&pool, &config).await {
+                    Ok(summary) => {
+                        tracing::info!("Log retention result: {}", summary);
+                    }
+                    Err(e) => {
+                        tracing::error!("Log retention failed: {}", e);
+                    }
+                }
+            }).await;
+        })
+    })?;
+
+    scheduler.add(retention_job).await?;
+
+    // Job 5: Purge OTP login, token verifikasi email & reset password (interval TOKEN_PURGE_INTERVAL_MINUTES)
+    let pool_clone5 = pool.clone();
+    let shutdown_clone5 = shutdown.clone();
+    let purge_config = TokenPurgeConfig::from_env();
+    let purge_job = Job::new_repeated_async(purge_config.interval, move |_uuid, _l| {
+        let pool = pool_clone5.clone();
+        let shutdown = shutdown_clone5.clone();
+        let config = purge_config.clone();
+        Box::pin(async move {
+            shutdown.run_job(async move {
+                match purge_auth_tokens(&pool, &config).await {
+                    Ok(summary) => {
+                        if summary.total() > 0 {
+                            tracing::info!("Purged auth tokens: {}", summary);
+                        }
+                    }
+                    Err(e) => {
+                        tracing::error!("Auth token purge failed: {}", e);
+                    }
+                }
+            }).await;
+        })
+    })?;
+
+    scheduler.add(purge_job).await?;
+
     scheduler.start().await?;
 
+    // Stop scheduler saat shutdown (job yang sedang jalan tetap ditunggu lewat Shutdown)
+    let token = shutdown.token.clone();
+    shutdown.spawn(async move {
+        token.cancelled().await;
+        if let Err(e) = scheduler.shutdown().await {
+            tracing::warn!("Failed to stop cleanup scheduler cleanly: {}", e);
+        }
+        tracing::info!("Token & session cleanup scheduler stopped");
+    });
+
     tracing::info!("✅ Token & session cleanup scheduler started");
     Ok(())
 }
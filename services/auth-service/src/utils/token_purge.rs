@@ -0,0 +1,116 @@
+// /pdf-bookstore/services/auth-service/src/utils/token_purge.rs
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use std::{env, fmt};
+
+/// Konfigurasi purge OTP login, token verifikasi email, dan token reset password
+#[derive(Debug, Clone)]
+pub struct TokenPurgeConfig {
+    pub interval: std::time::Duration,
+    /// Row expired dihapus setelah lewat grace ini
+    pub expired_grace: Duration,
+    /// Row yang sudah dipakai (used_at / verified_at) dihapus setelah retention ini
+    pub used_retention: Duration,
+}
+
+impl TokenPurgeConfig {
+    /// TOKEN_PURGE_INTERVAL_MINUTES (default 30), TOKEN_PURGE_EXPIRED_GRACE_HOURS (default 1),
+    /// TOKEN_PURGE_USED_RETENTION_HOURS (default 24)
+    pub fn from_env() -> Self {
+        let read = |key: &str, default: i64| {
+            env::var(key).ok().and_then(|v| v.parse::<i64>().ok()).filter(|v| *v >= 0).unwrap_or(default)
+        };
+
+        Self {
+            interval: std::time::Duration::from_secs(read("TOKEN_PURGE_INTERVAL_MINUTES", 30).max(1) as u64 * 60),
+            expired_grace: Duration::hours(read("TOKEN_PURGE_EXPIRED_GRACE_HOURS", 1)),
+            used_retention: Duration::hours(read("TOKEN_PURGE_USED_RETENTION_HOURS", 24)),
+        }
+    }
+
+    /// (cutoff expired, cutoff used) relatif terhadap `now`
+    fn cutoffs(&self, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        (now - self.expired_grace, now - self.used_retention)
+    }
+}
+
+/// Jumlah row yang dihapus per tabel
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TokenPurgeSummary {
+    pub login_otps: u64,
+    pub email_verification_tokens: u64,
+    pub password_reset_tokens: u64,
+}
+
+impl TokenPurgeSummary {
+    pub fn total(&self) -> u64 {
+        self.login_otps + self.email_verification_tokens + self.password_reset_tokens
+    }
+}
+
+impl fmt::Display for TokenPurgeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "login_otps={}, email_verification_tokens={}, password_reset_tokens={} (total {})",
+            self.login_otps, self.email_verification_tokens, self.password_reset_tokens, self.total()
+        )
+    }
+}
+
+/// Hapus OTP/token yang sudah expired atau sudah lama dipakai supaya tabel auth tetap kecil
+pub async fn purge_auth_tokens(pool: &PgPool, config: &TokenPurgeConfig) -> Result<TokenPurgeSummary, sqlx::Error> {
+    let (expired_cutoff, used_cutoff) = config.cutoffs(Utc::now());
+
+    let login_otps = sqlx::query!(
+        "DELETE FROM login_otps WHERE expires_at < $1 OR used_at < $2",
+        expired_cutoff,
+        used_cutoff
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    let email_verification_tokens = sqlx::query!(
+        "DELETE FROM email_verification_tokens WHERE expires_at < $1 OR verified_at < $2",
+        expired_cutoff,
+        used_cutoff
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    let password_reset_tokens = sqlx::query!(
+        "DELETE FROM password_reset_tokens WHERE expires_at < $1 OR used_at < $2",
+        expired_cutoff,
+        used_cutoff
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(TokenPurgeSummary { login_otps, email_verification_tokens, password_reset_tokens })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cutoffs_and_summary() {
+        let config = TokenPurgeConfig {
+            interval: std::time::Duration::from_secs(1800),
+            expired_grace: Duration::hours(1),
+            used_retention: Duration::hours(24),
+        };
+        let now = Utc::now();
+        let (expired_cutoff, used_cutoff) = config.cutoffs(now);
+        assert_eq!(now - expired_cutoff, Duration::hours(1));
+        assert_eq!(now - used_cutoff, Duration::hours(24));
+
+        let summary = TokenPurgeSummary { login_otps: 3, email_verification_tokens: 2, password_reset_tokens: 1 };
+        assert_eq!(summary.total(), 6);
+        assert_eq!(summary.to_string(), "login_otps=3, email_verification_tokens=2, password_reset_tokens=1 (total 6)");
+    }
+}
@@ -0,0 +1,109 @@
+// /pdf-bookstore/services/book-service/src/analytics_cache.rs
+
+use std::{
+    collections::HashMap,
+    env,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+type Slot<T> = Arc<tokio::sync::Mutex<Option<(Instant, T)>>>;
+
+/// Cache-aside hasil query analytics admin dengan single-flight per key:
+/// saat cache kosong/expired hanya satu request yang menjalankan query, request lain
+/// dengan key sama menunggu lalu memakai hasilnya (mencegah cache stampede)
+pub struct AnalyticsCache<T> {
+    ttl: Duration,
+    slots: Mutex<HashMap<String, Slot<T>>>,
+}
+
+impl<T: Clone> AnalyticsCache<T> {
+    /// ANALYTICS_CACHE_SECONDS (default 60, 0 = tanpa cache)
+    pub fn from_env() -> Self {
+        let ttl = env::var("ANALYTICS_CACHE_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(60);
+
+        Self::new(Duration::from_secs(ttl))
+    }
+
+    fn new(ttl: Duration) -> Self {
+        Self { ttl, slots: Mutex::new(HashMap::new()) }
+    }
+
+    /// Ambil dari cache atau jalankan `load` sekali per key per TTL
+    /// Error tidak di-cache: request berikutnya yang menunggu akan mencoba query lagi
+    pub async fn get_or_load<F, Fut, E>(&self, key: String, load: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if self.ttl.is_zero() {
+            return load().await;
+        }
+
+        let slot = self.slot(key);
+        let mut entry = slot.lock().await;
+
+        if let Some((cached_at, value)) = entry.as_ref() {
+            if cached_at.elapsed() < self.ttl {
+                return Ok(value.clone());
+            }
+        }
+
+        let value = load().await?;
+        *entry = Some((Instant::now(), value.clone()));
+        Ok(value)
+    }
+
+    /// Slot per key, slot expired yang sedang tidak dipakai dibuang saat key baru masuk
+    fn slot(&self, key: String) -> Slot<T> {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+
+        if !slots.contains_key(&key) {
+            let ttl = self.ttl;
+            slots.retain(|_, slot| match slot.try_lock() {
+                Ok(entry) => entry.as_ref().is_some_and(|(cached_at, _)| cached_at.elapsed() < ttl),
+                Err(_) => true,
+            });
+        }
+
+        slots.entry(key).or_default().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_concurrent_misses_run_query_once() {
+        let cache = Arc::new(AnalyticsCache::<Vec<i64>>::new(Duration::from_secs(60)));
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let callers = (0..20).map(|_| {
+            let (cache, runs) = (cache.clone(), runs.clone());
+            tokio::spawn(async move {
+                cache.get_or_load("sales:30".to_string(), || async {
+                    runs.fetch_add(1, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    Ok::<_, String>(vec![1, 2, 3])
+                }).await
+            })
+        }).collect::<Vec<_>>();
+
+        for caller in callers {
+            assert_eq!(caller.await.unwrap(), Ok(vec![1, 2, 3]));
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        // Key lain dihitung terpisah, error tidak di-cache
+        let failed = cache.get_or_load("sales:7".to_string(), || async { Err::<Vec<i64>, _>("db down".to_string()) }).await;
+        assert!(failed.is_err());
+        let retried = cache.get_or_load("sales:7".to_string(), || async { Ok::<_, String>(vec![7]) }).await;
+        assert_eq!(retried, Ok(vec![7]));
+    }
+}
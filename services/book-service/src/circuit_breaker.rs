@@ -1,6 +1,7 @@
-// /pdf-bookstore/services/auth-service/src/circuit_breaker.rs
+// /pdf-bookstore/services/book-service/src/circuit_breaker.rs
 
 use crate::error::{AppError, AppResult};
+use service_common::admin_notifier::AdminNotifier;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
@@ -31,6 +32,7 @@ pub struct CircuitBreaker {
     state: Arc<RwLock<CircuitState>>,
     stats: Arc<RwLock<CircuitStats>>,
     config: CircuitBreakerConfig,
+    notifier: Option<Arc<AdminNotifier>>,
 }
 
 /// Konfigurasi circuit breaker
@@ -67,6 +69,7 @@ impl CircuitBreaker {
                 state_changed_at: Instant::now(),
             })),
             config,
+            notifier: None,
         }
     }
 
@@ -154,6 +157,12 @@ impl CircuitBreaker {
                     "Circuit breaker {} transisi ke OPEN setelah {} failures berturut-turut", 
                     self.name, stats.consecutive_failures
                 );
+                if let Some(notifier) = &self.notifier {
+                    notifier.notify(
+                        &format!("circuit_open:{}", self.name),
+                        format!("Circuit breaker {} OPEN setelah {} kegagalan berturut-turut", self.name, stats.consecutive_failures),
+                    );
+                }
             }
         }
     }
@@ -191,15 +200,23 @@ impl CircuitBreaker {
 /// Circuit breaker manager untuk manage multiple circuit breakers
 pub struct CircuitBreakerManager {
     breakers: Arc<RwLock<HashMap<String, Arc<CircuitBreaker>>>>,
+    notifier: Option<Arc<AdminNotifier>>,
 }
 
 impl CircuitBreakerManager {
     pub fn new() -> Self {
         Self {
             breakers: Arc::new(RwLock::new(HashMap::new())),
+            notifier: None,
         }
     }
 
+    /// Alert admin saat circuit breaker mana pun transisi ke OPEN
+    pub fn with_notifier(mut self, notifier: Arc<AdminNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
     /// Get atau create circuit breaker untuk service
     pub async fn get_or_create(&self, service_name: &str) -> Arc<CircuitBreaker> {
         let mut breakers = self.breakers.write().await;
@@ -208,10 +225,10 @@ impl CircuitBreakerManager {
             return breaker.clone();
         }
         
-        let breaker = Arc::new(CircuitBreaker::new(
-            service_name.to_string(),
-            CircuitBreakerConfig::default(),
-        ));
+        let breaker = Arc::new(CircuitBreaker {
+            notifier: self.notifier.clone(),
+            ..CircuitBreaker::new(service_name.to_string(), CircuitBreakerConfig::default())
+        });
         
         breakers.insert(service_name.to_string(), breaker.clone());
         breaker
@@ -0,0 +1,132 @@
+// /pdf-bookstore/services/book-service/src/cors.rs
+
+use axum::http::{header, request::Parts, HeaderValue, Method};
+use std::{env, sync::Arc, time::Duration};
+use tower_http::cors::{AllowCredentials, AllowOrigin, CorsLayer};
+
+use service_common::public_routes::PublicRoutePolicy;
+
+/// Origin frontend default (user app, admin panel, dev server)
+const DEFAULT_ORIGINS: &str = "http://localhost:8080,http://localhost:8081,http://localhost:3000";
+
+/// CORS untuk route umum: origin frontend terdaftar boleh semua method + credentials,
+/// GET katalog public boleh dari origin mana saja (tanpa credentials) jika PUBLIC_CORS_ANY_ORIGIN aktif
+pub fn app_cors(public_routes: Arc<PublicRoutePolicy>) -> CorsLayer {
+    let allowed = Arc::new(parse_origins("CORS_ALLOWED_ORIGINS", DEFAULT_ORIGINS));
+    let public_any_origin = env::var("PUBLIC_CORS_ANY_ORIGIN")
+        .map(|v| v == "true" || v == "1")
+        .unwrap_or(true);
+
+    let allowed_for_origin = allowed.clone();
+    let allowed_for_credentials = allowed;
+
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::predicate(move |origin, parts| {
+            allowed_for_origin.contains(origin)
+                || (public_any_origin && is_public_read(parts, &public_routes))
+        }))
+        .allow_credentials(AllowCredentials::predicate(move |origin, _| {
+            allowed_for_credentials.contains(origin)
+        }))
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE, Method::OPTIONS])
+        .allow_headers(allowed_headers())
+        .max_age(cors_max_age())
+}
+
+/// CORS ketat untuk /api/admin/* dan /api/upload/*
+/// ADMIN_CORS_ORIGINS (default: CORS_ALLOWED_ORIGINS), ADMIN_CORS_METHODS (default GET,POST,PUT,DELETE)
+pub fn restricted_cors() -> CorsLayer {
+    let default_origins = env::var("CORS_ALLOWED_ORIGINS").unwrap_or_else(|_| DEFAULT_ORIGINS.to_string());
+    let origins = parse_origins("ADMIN_CORS_ORIGINS", &default_origins);
+
+    let methods = env::var("ADMIN_CORS_METHODS")
+        .unwrap_or_else(|_| "GET,POST,PUT,DELETE".to_string())
+        .split(',')
+        .filter_map(|m| Method::from_bytes(m.trim().to_uppercase().as_bytes()).ok())
+        .chain(std::iter::once(Method::OPTIONS))
+        .collect::<Vec<_>>();
+
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(methods)
+        .allow_headers(allowed_headers())
+        .allow_credentials(true)
+        .max_age(cors_max_age())
+}
+
+/// Request GET ke route public, termasuk preflight yang meminta GET
+fn is_public_read(parts: &Parts, public_routes: &PublicRoutePolicy) -> bool {
+    let method = if parts.method == Method::OPTIONS {
+        parts.headers
+            .get(header::ACCESS_CONTROL_REQUEST_METHOD)
+            .and_then(|m| Method::from_bytes(m.as_bytes()).ok())
+    } else {
+        Some(parts.method.clone())
+    };
+
+    matches!(method, Some(Method::GET) | Some(Method::HEAD))
+        && public_routes.is_public(&Method::GET, parts.uri.path())
+}
+
+fn allowed_headers() -> [header::HeaderName; 4] {
+    [header::CONTENT_TYPE, header::AUTHORIZATION, header::ACCEPT, header::ORIGIN]
+}
+
+fn parse_origins(var: &str, default: &str) -> Vec<HeaderValue> {
+    env::var(var)
+        .unwrap_or_else(|_| default.to_string())
+        .split(',')
+        .filter_map(|origin| {
+            let trimmed = origin.trim();
+            match trimmed.parse::<HeaderValue>() {
+                Ok(value) if !trimmed.is_empty() && trimmed != "*" => Some(value),
+                _ => {
+                    tracing::warn!("Origin CORS tidak valid di {}: '{}'", var, trimmed);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Durasi cache preflight CORS (Access-Control-Max-Age)
+fn cors_max_age() -> Duration {
+    let secs = env::var("CORS_MAX_AGE_SECONDS")
+        .unwrap_or_else(|_| "3600".to_string())
+        .parse()
+        .unwrap_or(3600);
+    Duration::from_secs(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request};
+
+    fn parts(method: Method, path: &str, request_method: Option<&str>) -> Parts {
+        let mut builder = Request::builder().method(method).uri(path);
+        if let Some(m) = request_method {
+            builder = builder.header(header::ACCESS_CONTROL_REQUEST_METHOD, m);
+        }
+        builder.body(Body::empty()).unwrap().into_parts().0
+    }
+
+    #[test]
+    fn test_public_read_detection() {
+        let policy = PublicRoutePolicy::default();
+
+        assert!(is_public_read(&parts(Method::GET, "/api/books", None), &policy));
+        assert!(is_public_read(&parts(Method::OPTIONS, "/api/books/1/reviews", Some("GET")), &policy));
+        assert!(!is_public_read(&parts(Method::OPTIONS, "/api/books", Some("POST")), &policy));
+        assert!(!is_public_read(&parts(Method::GET, "/api/books/my-library", None), &policy));
+    }
+
+    #[test]
+    fn test_layers_are_valid() {
+        // CorsLayer panic saat di-layer jika kombinasi credentials/origin tidak valid
+        let _router: axum::Router = axum::Router::new()
+            .route("/", axum::routing::get(|| async {}))
+            .layer(app_cors(Arc::new(PublicRoutePolicy::default())))
+            .layer(restricted_cors());
+    }
+}
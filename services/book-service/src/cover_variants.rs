@@ -0,0 +1,320 @@
+// /pdf-bookstore/services/book-service/src/cover_variants.rs
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, Uri},
+    middleware::Next,
+    response::Response,
+};
+use image::ImageFormat;
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Folder variant di dalam folder covers: `/covers/{nama}.jpg` -> `/covers/variants/{nama}.jpg.webp`
+const VARIANT_DIR: &str = "variants";
+
+/// Berapa lama variant gagal/tidak lebih kecil tidak dicoba ulang (cover bisa diganti dengan nama sama)
+const UNAVAILABLE_TTL: Duration = Duration::from_secs(600);
+/// Batas entry `unavailable` supaya request ke banyak cover rusak tidak menumpuk memori
+const MAX_UNAVAILABLE: usize = 1024;
+
+/// Format cover yang bisa dinegosiasikan lewat header Accept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariantFormat {
+    Webp,
+}
+
+impl VariantFormat {
+    pub const ALL: &'static [VariantFormat] = &[Self::Webp];
+
+    fn mime(self) -> &'static str {
+        match self {
+            Self::Webp => "image/webp",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Webp => "webp",
+        }
+    }
+
+    fn image_format(self) -> ImageFormat {
+        match self {
+            Self::Webp => ImageFormat::WebP,
+        }
+    }
+}
+
+/// Content negotiation cover di /storage/covers berdasarkan header Accept
+/// Variant dibuat on-demand dari cover asli lalu disimpan di disk, dipakai hanya jika lebih kecil dari aslinya
+pub struct CoverVariants {
+    formats: Vec<VariantFormat>,
+    storage_dir: PathBuf,
+    /// Variant yang gagal dibuat / tidak lebih kecil, supaya tidak di-generate ulang tiap request
+    unavailable: Mutex<HashMap<PathBuf, Instant>>,
+    unavailable_ttl: Duration,
+}
+
+impl CoverVariants {
+    /// COVER_VARIANT_FORMATS: format yang dinegosiasikan, urut prioritas, comma-separated (default "webp", kosong = nonaktif)
+    /// AVIF belum bisa di-encode (crate image tanpa fitur avif) sehingga diabaikan
+    pub fn from_env(storage_dir: impl Into<PathBuf>) -> Self {
+        let mut formats = Vec::new();
+        for name in env::var("COVER_VARIANT_FORMATS").unwrap_or_else(|_| "webp".to_string()).split(',') {
+            match name.trim().to_lowercase().as_str() {
+                "" => {}
+                "webp" => formats.push(VariantFormat::Webp),
+                "avif" => tracing::warn!("COVER_VARIANT_FORMATS: avif belum didukung build ini, diabaikan"),
+                other => tracing::warn!("COVER_VARIANT_FORMATS: format '{}' tidak dikenal, diabaikan", other),
+            }
+        }
+        formats.dedup();
+
+        Self::new(formats, storage_dir.into())
+    }
+
+    fn new(formats: Vec<VariantFormat>, storage_dir: PathBuf) -> Self {
+        Self { formats, storage_dir, unavailable: Mutex::new(HashMap::new()), unavailable_ttl: UNAVAILABLE_TTL }
+    }
+
+    fn is_unavailable(&self, target: &Path) -> bool {
+        let unavailable = self.unavailable.lock().unwrap_or_else(|e| e.into_inner());
+        unavailable.get(target).is_some_and(|marked_at| marked_at.elapsed() < self.unavailable_ttl)
+    }
+
+    /// Tandai variant tidak bisa dipakai; entry expired dibuang dulu, jika masih penuh semua dikosongkan
+    fn mark_unavailable(&self, target: PathBuf) {
+        let mut unavailable = self.unavailable.lock().unwrap_or_else(|e| e.into_inner());
+        if unavailable.len() >= MAX_UNAVAILABLE {
+            let ttl = self.unavailable_ttl;
+            unavailable.retain(|_, marked_at| marked_at.elapsed() < ttl);
+            if unavailable.len() >= MAX_UNAVAILABLE {
+                unavailable.clear();
+            }
+        }
+        unavailable.insert(target, Instant::now());
+    }
+
+    /// Format pertama (urut prioritas config) yang diterima client, wildcard tidak dihitung
+    fn negotiate(&self, accept: &str) -> Option<VariantFormat> {
+        let accepted = accepted_mime_types(accept);
+
+        self.formats.iter().copied().find(|f| accepted.iter().any(|mime| mime == f.mime()))
+    }
+
+    /// Path relatif variant (terhadap /storage) untuk cover JPEG/PNG di folder covers
+    fn variant_path(path: &str, format: VariantFormat) -> Option<String> {
+        let file_name = path.strip_prefix("/covers/")?;
+        if file_name.contains('/') || file_name.contains("..") || file_name.contains('\\') {
+            return None;
+        }
+
+        let ext = file_name.rsplit_once('.')?.1.to_lowercase();
+        if !matches!(ext.as_str(), "jpg" | "jpeg" | "png") {
+            return None;
+        }
+
+        Some(format!("/covers/{}/{}.{}", VARIANT_DIR, file_name, format.extension()))
+    }
+
+    /// Pastikan variant ada di disk, return false jika tidak bisa dipakai (pakai cover asli)
+    async fn ensure_variant(&self, source: PathBuf, target: PathBuf, format: VariantFormat) -> bool {
+        if is_file(&target).await {
+            return true;
+        }
+        if self.is_unavailable(&target) {
+            return false;
+        }
+        // Cover asli tidak ada: biarkan ServeDir menjawab 404, tidak di-cache agar cover yang baru di-upload tetap dapat variant
+        if !is_file(&source).await {
+            return false;
+        }
+
+        let job_target = target.clone();
+        let result = tokio::task::spawn_blocking(move || encode_variant(&source, &job_target, format)).await;
+
+        let usable = match result {
+            Ok(Ok(usable)) => usable,
+            Ok(Err(e)) => {
+                tracing::warn!("Gagal membuat variant cover {}: {}", target.display(), e);
+                false
+            }
+            Err(e) => {
+                tracing::warn!("Task variant cover {} gagal: {}", target.display(), e);
+                false
+            }
+        };
+
+        if !usable {
+            self.mark_unavailable(target);
+        }
+        usable
+    }
+}
+
+pub(crate) async fn is_file(path: &Path) -> bool {
+    tokio::fs::metadata(path).await.is_ok_and(|meta| meta.is_file())
+}
+
+/// Semua path variant yang mungkin ada untuk cover `/storage/covers/...` (cleanup saat cover diganti)
+pub fn variant_storage_paths(cover_path: &str) -> Vec<String> {
+    let Some(inner) = cover_path.strip_prefix("/storage") else {
+        return Vec::new();
+    };
+
+    VariantFormat::ALL.iter()
+        .filter_map(|&format| CoverVariants::variant_path(inner, format))
+        .map(|variant| format!("/storage{}", variant))
+        .collect()
+}
+
+/// Encode cover ke format variant, file tidak disimpan jika hasilnya tidak lebih kecil dari aslinya
+fn encode_variant(source: &Path, target: &Path, format: VariantFormat) -> Result<bool, String> {
+    let original_size = std::fs::metadata(source).map_err(|e| e.to_string())?.len();
+    let decoded = image::open(source).map_err(|e| e.to_string())?;
+
+    let mut encoded = std::io::Cursor::new(Vec::new());
+    decoded.write_to(&mut encoded, format.image_format()).map_err(|e| e.to_string())?;
+    let encoded = encoded.into_inner();
+
+    if encoded.len() as u64 >= original_size {
+        return Ok(false);
+    }
+
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    // Tulis ke file sementara lalu rename supaya request paralel tidak membaca file setengah jadi
+    let temp = target.with_extension(format!("{}.tmp{}", format.extension(), std::process::id()));
+    std::fs::write(&temp, &encoded).map_err(|e| e.to_string())?;
+    std::fs::rename(&temp, target).map_err(|e| e.to_string())?;
+
+    Ok(true)
+}
+
+/// MIME type (lowercase) di header Accept yang tidak ditolak lewat q=0 (termasuk q=0.00)
+/// Dipakai juga oleh negosiasi format halaman preview
+pub(crate) fn accepted_mime_types(accept: &str) -> Vec<String> {
+    accept.split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let mime = parts.next()?.trim().to_lowercase();
+            let quality = parts
+                .filter_map(|p| p.trim().strip_prefix("q=").and_then(|q| q.parse::<f32>().ok()))
+                .next()
+                .unwrap_or(1.0);
+            (!mime.is_empty() && quality > 0.0).then_some(mime)
+        })
+        .collect()
+}
+
+/// Middleware untuk ServeDir /storage: arahkan request cover ke variant yang didukung client
+pub async fn negotiate_cover_format(
+    State(variants): State<Arc<CoverVariants>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let path = req.uri().path().to_string();
+    let is_cover = path.starts_with("/covers/") && !variants.formats.is_empty();
+
+    if is_cover && matches!(*req.method(), Method::GET | Method::HEAD) {
+        let accept = req.headers().get(header::ACCEPT).and_then(|v| v.to_str().ok()).unwrap_or("");
+
+        if let Some(format) = variants.negotiate(accept) {
+            if let Some(variant) = CoverVariants::variant_path(&path, format) {
+                let source = variants.storage_dir.join(path.trim_start_matches('/'));
+                let target = variants.storage_dir.join(variant.trim_start_matches('/'));
+
+                if variants.ensure_variant(source, target, format).await {
+                    let rewritten = match req.uri().query() {
+                        Some(query) => format!("{}?{}", variant, query),
+                        None => variant,
+                    };
+                    if let Ok(uri) = rewritten.parse::<Uri>() {
+                        *req.uri_mut() = uri;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut response = next.run(req).await;
+    if is_cover {
+        response.headers_mut().append(header::VARY, HeaderValue::from_static("accept"));
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_and_variant_path() {
+        let variants = CoverVariants::new(vec![VariantFormat::Webp], PathBuf::from("./storage"));
+
+        assert_eq!(variants.negotiate("image/avif,image/webp,image/apng,*/*;q=0.8"), Some(VariantFormat::Webp));
+        assert_eq!(variants.negotiate("image/webp;q=0"), None);
+        assert_eq!(variants.negotiate("image/webp;q=0.00"), None);
+        assert_eq!(variants.negotiate("*/*"), None);
+        assert_eq!(variants.negotiate(""), None);
+
+        assert_eq!(
+            CoverVariants::variant_path("/covers/abc_123.jpg", VariantFormat::Webp).as_deref(),
+            Some("/covers/variants/abc_123.jpg.webp")
+        );
+        assert_eq!(CoverVariants::variant_path("/covers/abc_123.webp", VariantFormat::Webp), None);
+        assert_eq!(CoverVariants::variant_path("/covers/variants/abc.jpg.webp", VariantFormat::Webp), None);
+        assert_eq!(CoverVariants::variant_path("/books/abc.pdf", VariantFormat::Webp), None);
+
+        assert_eq!(variant_storage_paths("/storage/covers/abc.png"), vec!["/storage/covers/variants/abc.png.webp"]);
+
+        let disabled = CoverVariants::new(Vec::new(), PathBuf::from("./storage"));
+        assert_eq!(disabled.negotiate("image/webp"), None);
+    }
+
+    #[tokio::test]
+    async fn test_variant_generated_once_and_cached() {
+        let dir = env::temp_dir().join(format!("variant-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("covers")).unwrap();
+        image::RgbImage::new(300, 450).save(dir.join("covers/cover.png")).unwrap();
+        std::fs::write(dir.join("covers/broken.png"), b"bukan gambar").unwrap();
+
+        let variants = CoverVariants::new(vec![VariantFormat::Webp], dir.clone());
+        let target = dir.join("covers/variants/cover.png.webp");
+        assert!(variants.ensure_variant(dir.join("covers/cover.png"), target.clone(), VariantFormat::Webp).await);
+        assert_eq!(image::ImageFormat::from_path(&target).unwrap(), ImageFormat::WebP);
+        assert!(image::open(&target).is_ok());
+
+        let broken_target = dir.join("covers/variants/broken.png.webp");
+        assert!(!variants.ensure_variant(dir.join("covers/broken.png"), broken_target.clone(), VariantFormat::Webp).await);
+        assert!(!broken_target.exists());
+        assert!(variants.is_unavailable(&broken_target));
+
+        // Cover asli belum ada tidak di-cache sebagai unavailable
+        let missing_target = dir.join("covers/variants/missing.png.webp");
+        assert!(!variants.ensure_variant(dir.join("covers/missing.png"), missing_target.clone(), VariantFormat::Webp).await);
+        assert!(!variants.unavailable.lock().unwrap().contains_key(&missing_target));
+
+        // Entry unavailable expired sehingga cover yang diganti dicoba lagi
+        let mut expiring = CoverVariants::new(vec![VariantFormat::Webp], dir.clone());
+        expiring.unavailable_ttl = Duration::ZERO;
+        expiring.mark_unavailable(broken_target.clone());
+        assert!(!expiring.is_unavailable(&broken_target));
+
+        for i in 0..MAX_UNAVAILABLE + 10 {
+            variants.mark_unavailable(dir.join(format!("covers/variants/{}.png.webp", i)));
+        }
+        assert!(variants.unavailable.lock().unwrap().len() <= MAX_UNAVAILABLE);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -28,8 +28,38 @@ pub enum DatabaseError {
     ConcurrentModificationError,
 }
 
+/// Deteksi timeout database dari error typed, dipakai mapping error ke response 504
+pub trait DbTimeout {
+    fn is_timeout(&self) -> bool;
+}
+
+impl DbTimeout for sqlx::Error {
+    /// Pool habis (acquire timeout), statement_timeout (57014), lock_timeout (55P03), atau IO timeout
+    fn is_timeout(&self) -> bool {
+        match self {
+            sqlx::Error::PoolTimedOut => true,
+            sqlx::Error::Database(db) => matches!(db.code().as_deref(), Some("57014") | Some("55P03")),
+            sqlx::Error::Io(io) => io.kind() == std::io::ErrorKind::TimedOut,
+            _ => false,
+        }
+    }
+}
+
+impl DbTimeout for DatabaseError {
+    fn is_timeout(&self) -> bool {
+        matches!(self, DatabaseError::Connection(e) if e.is_timeout())
+    }
+}
+
 // ===== FUNGSI HELPER =====
 
+/// Awal window dedup view yang memuat `now` (kelipatan `dedup_minutes` sejak epoch)
+fn view_window_start(now: chrono::DateTime<Utc>, dedup_minutes: i64) -> chrono::DateTime<Utc> {
+    let window_secs = dedup_minutes.max(1) * 60;
+    let start = now.timestamp() - now.timestamp().rem_euclid(window_secs);
+    chrono::DateTime::from_timestamp(start, 0).unwrap_or(now)
+}
+
 /// Membersihkan input pencarian dari karakter berbahaya
 /// Mencegah SQL injection dengan escape karakter khusus
 fn sanitize_search_input(input: &str) -> String {
@@ -67,6 +97,156 @@ impl StringTruncate for String {
     }
 }
 
+// ===== CATALOG SORT PRESETS =====
+
+/// Preset `sort=` untuk list buku, alternatif ringkas dari pasangan sort_by/sort_order
+pub const SORT_PRESETS: &[&str] = &["newest", "oldest", "price_low", "price_high", "popular", "top_rated"];
+
+/// Preset sort katalog, tiap varian menentukan kolom + arah ORDER BY
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortPreset {
+    Newest,
+    Oldest,
+    PriceLow,
+    PriceHigh,
+    /// Jumlah download terbanyak
+    Popular,
+    /// Rata-rata rating review tertinggi (buku tanpa review dianggap 0)
+    TopRated,
+}
+
+impl SortPreset {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "newest" => Some(Self::Newest),
+            "oldest" => Some(Self::Oldest),
+            "price_low" => Some(Self::PriceLow),
+            "price_high" => Some(Self::PriceHigh),
+            "popular" => Some(Self::Popular),
+            "top_rated" => Some(Self::TopRated),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Newest => "newest",
+            Self::Oldest => "oldest",
+            Self::PriceLow => "price_low",
+            Self::PriceHigh => "price_high",
+            Self::Popular => "popular",
+            Self::TopRated => "top_rated",
+        }
+    }
+
+    /// Preset saat client tidak mengirim `sort` maupun `sort_by` (BOOK_DEFAULT_SORT, default newest)
+    pub fn default_from_env() -> Self {
+        let configured = std::env::var("BOOK_DEFAULT_SORT").unwrap_or_else(|_| "newest".to_string());
+
+        Self::from_name(configured.trim()).unwrap_or_else(|| {
+            tracing::warn!("BOOK_DEFAULT_SORT {} tidak dikenal, pakai newest", configured);
+            Self::Newest
+        })
+    }
+
+    fn order_sql(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Newest => ("b.created_at", "DESC"),
+            Self::Oldest => ("b.created_at", "ASC"),
+            Self::PriceLow => ("b.price", "ASC"),
+            Self::PriceHigh => ("b.price", "DESC"),
+            Self::Popular => ("b.download_count", "DESC"),
+            Self::TopRated => ("COALESCE((SELECT AVG(r.rating) FROM book_reviews r WHERE r.book_id = b.id), 0)", "DESC"),
+        }
+    }
+}
+
+/// ORDER BY untuk search_books, selalu diakhiri b.id sebagai tiebreaker
+/// supaya buku dengan nilai sort sama (misal harga sama) urutannya stabil antar halaman OFFSET
+/// Preset `sort` (sudah divalidasi handler) menang atas sort_by/sort_order
+fn search_order_by(params: &BookQueryParams) -> String {
+    let preset = params.sort.as_deref().and_then(SortPreset::from_name);
+    let (sort_column, sort_direction) = match preset {
+        Some(preset) => preset.order_sql(),
+        None => {
+            let column = match params.sort_by.as_deref() {
+                Some("title") => "b.title",
+                Some("author") => "b.author",
+                Some("price") => "b.price",
+                _ => "b.created_at",
+            };
+            let direction = match params.sort_order.as_deref() {
+                Some("asc") => "ASC",
+                _ => "DESC",
+            };
+            (column, direction)
+        }
+    };
+
+    let featured = if params.featured_first.unwrap_or(false) {
+        "b.is_featured DESC, b.featured_order ASC NULLS LAST, "
+    } else {
+        ""
+    };
+
+    format!("{}{} {}, b.id {}", featured, sort_column, sort_direction, sort_direction)
+}
+
+// ===== TOP BOOK METRICS =====
+
+/// Daftar metric yang didukung untuk ranking top books
+pub const TOP_BOOK_METRICS: &[&str] = &["downloads", "sales", "revenue", "recent", "rating"];
+
+/// Metric ranking top books, tiap varian menentukan potongan SQL-nya sendiri
+/// Tambah metric baru cukup dengan varian baru + entry di `TOP_BOOK_METRICS`
+#[derive(Debug, Clone, Copy)]
+enum TopBookMetric {
+    Downloads,
+    Sales,
+    Revenue,
+    Recent,
+    /// Rata-rata rating x100 (mis. 4.35 -> 435)
+    Rating,
+}
+
+impl TopBookMetric {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "downloads" => Some(Self::Downloads),
+            "sales" => Some(Self::Sales),
+            "revenue" => Some(Self::Revenue),
+            "recent" => Some(Self::Recent),
+            "rating" => Some(Self::Rating),
+            _ => None,
+        }
+    }
+
+    fn value_sql(self) -> &'static str {
+        match self {
+            Self::Downloads => "b.download_count::bigint",
+            Self::Sales => "COALESCE(COUNT(o.id), 0)::bigint",
+            Self::Revenue => "COALESCE(SUM(o.amount), 0)::bigint",
+            Self::Recent => "EXTRACT(EPOCH FROM b.created_at)::bigint",
+            Self::Rating => "COALESCE(ROUND(AVG(r.rating) * 100), 0)::bigint",
+        }
+    }
+
+    fn join_sql(self) -> &'static str {
+        match self {
+            Self::Sales | Self::Revenue => "LEFT JOIN orders o ON b.id = o.book_id AND o.status = 'paid'",
+            Self::Rating => "LEFT JOIN book_reviews r ON b.id = r.book_id",
+            Self::Downloads | Self::Recent => "",
+        }
+    }
+
+    fn order_sql(self) -> &'static str {
+        match self {
+            Self::Rating => "metric_value DESC, COUNT(r.id) DESC",
+            _ => "metric_value DESC",
+        }
+    }
+}
+
 // ===== REPOSITORY PATTERN =====
 pub struct BookRepository;
 
@@ -128,6 +308,7 @@ impl BookRepository {
                         updated_at: row.updated_at,
                     },
                     categories: Vec::new(),
+                    thumbnails: Vec::new(),
                 }
             });
         
@@ -155,6 +336,177 @@ impl BookRepository {
         Ok(result)
     }
 
+    /// Mengambil buku aktif berdasarkan daftar ID, urutan mengikuti `book_ids`
+    pub async fn get_active_books_by_ids(
+        pool: &PgPool,
+        book_ids: &[Uuid],
+    ) -> Result<Vec<BookWithCategories>, DatabaseError> {
+        let active_ids: HashSet<Uuid> = sqlx::query_scalar!(
+            "SELECT id FROM books WHERE id = ANY($1) AND is_active = true",
+            book_ids
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .collect();
+
+        let ordered_ids: Vec<Uuid> = book_ids.iter().copied().filter(|id| active_ids.contains(id)).collect();
+        if ordered_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        Self::fetch_books_with_categories(pool, ordered_ids).await
+    }
+
+    /// Featured books aktif sesuai urutan kurasi (featured_order, lalu yang terbaru di-feature)
+    pub async fn get_featured_books(
+        pool: &PgPool,
+        limit: u32,
+    ) -> Result<Vec<FeaturedBook>, DatabaseError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, featured_order, featured_at
+            FROM books
+            WHERE is_featured = true AND is_active = true
+            ORDER BY featured_order ASC NULLS LAST, featured_at DESC NULLS LAST, id
+            LIMIT $1
+            "#,
+            limit as i64
+        )
+        .fetch_all(pool)
+        .await?;
+
+        if rows.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut books: HashMap<Uuid, BookWithCategories> = Self::fetch_books_with_categories(
+            pool,
+            rows.iter().map(|row| row.id).collect(),
+        )
+        .await?
+        .into_iter()
+        .map(|bwc| (bwc.book.id, bwc))
+        .collect();
+
+        Ok(rows.into_iter()
+            .filter_map(|row| books.remove(&row.id).map(|book| FeaturedBook {
+                book,
+                featured_order: row.featured_order,
+                featured_at: row.featured_at,
+            }))
+            .collect())
+    }
+
+    /// Set/unset featured satu buku, unset menghapus urutan
+    pub async fn set_book_featured(
+        pool: &PgPool,
+        book_id: Uuid,
+        featured: bool,
+        featured_order: Option<i32>,
+        actor_user_id: Uuid,
+    ) -> Result<FeaturedStatus, DatabaseError> {
+        let mut tx = pool.begin().await?;
+
+        let status = sqlx::query_as!(
+            FeaturedStatus,
+            r#"
+            UPDATE books
+            SET is_featured = $2,
+                featured_order = CASE WHEN $2 THEN COALESCE($3, featured_order) ELSE NULL END,
+                featured_at = CASE WHEN $2 THEN COALESCE(featured_at, NOW()) ELSE NULL END,
+                updated_at = NOW()
+            WHERE id = $1
+            RETURNING id as book_id, is_featured, featured_order
+            "#,
+            book_id,
+            featured,
+            featured_order
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(DatabaseError::BookNotFound)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_logs (action, resource_type, resource_id, actor_user_id, details)
+            VALUES ($1, 'book', $2, $3, $4)
+            "#,
+            if featured { "BOOK_FEATURED" } else { "BOOK_UNFEATURED" },
+            book_id,
+            actor_user_id,
+            serde_json::json!({ "featured_order": status.featured_order })
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(status)
+    }
+
+    /// Atur ulang urutan featured books sesuai posisi di `book_ids`
+    /// Featured book yang tidak disebut kehilangan urutannya (tampil setelah yang diurutkan)
+    /// Return ID yang bukan featured book; jika ada, tidak ada yang diubah
+    pub async fn reorder_featured_books(
+        pool: &PgPool,
+        book_ids: &[Uuid],
+        actor_user_id: Uuid,
+    ) -> Result<Vec<Uuid>, DatabaseError> {
+        let mut tx = pool.begin().await?;
+
+        let featured: HashSet<Uuid> = sqlx::query_scalar!(
+            "SELECT id FROM books WHERE id = ANY($1) AND is_featured = true FOR UPDATE",
+            book_ids
+        )
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .collect();
+
+        let not_featured: Vec<Uuid> = book_ids.iter().copied().filter(|id| !featured.contains(id)).collect();
+        if !not_featured.is_empty() {
+            tx.rollback().await?;
+            return Ok(not_featured);
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE books
+            SET featured_order = NULL, updated_at = NOW()
+            WHERE is_featured = true AND NOT (id = ANY($1)) AND featured_order IS NOT NULL
+            "#,
+            book_ids
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE books b
+            SET featured_order = o.position::int, updated_at = NOW()
+            FROM UNNEST($1::uuid[]) WITH ORDINALITY AS o(id, position)
+            WHERE b.id = o.id
+            "#,
+            book_ids
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_logs (action, resource_type, actor_user_id, details)
+            VALUES ('FEATURED_BOOKS_REORDERED', 'book', $1, $2)
+            "#,
+            actor_user_id,
+            serde_json::json!({ "book_ids": book_ids })
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Vec::new())
+    }
+
     /// Membuat buku baru dengan validasi lengkap
     /// Menggunakan transaction untuk atomicity
     pub async fn create_book(
@@ -163,6 +515,7 @@ impl BookRepository {
         pdf_path: Option<String>,
         cover_path: Option<String>,
         file_size_mb: Option<BigDecimal>,
+        actor_user_id: Uuid,
     ) -> Result<Book, DatabaseError> {
         let mut tx = pool.begin().await?;
 
@@ -284,10 +637,11 @@ impl BookRepository {
         // Log audit trail untuk tracking
         sqlx::query!(
             r#"
-            INSERT INTO audit_logs (action, resource_type, resource_id, details)
-            VALUES ('BOOK_CREATED', 'book', $1, $2)
+            INSERT INTO audit_logs (action, resource_type, resource_id, actor_user_id, details)
+            VALUES ('BOOK_CREATED', 'book', $1, $2, $3)
             "#,
             book.id,
+            actor_user_id,
             serde_json::json!({
                 "title": book.title,
                 "author": book.author,
@@ -306,6 +660,22 @@ impl BookRepository {
     pub async fn get_book_by_id(
         pool: &PgPool,
         book_id: Uuid,
+    ) -> Result<BookWithCategories, DatabaseError> {
+        Self::fetch_book_with_categories(pool, book_id, false).await
+    }
+
+    /// Mengambil detail buku termasuk yang inactive (untuk internal service)
+    pub async fn get_book_by_id_any_status(
+        pool: &PgPool,
+        book_id: Uuid,
+    ) -> Result<BookWithCategories, DatabaseError> {
+        Self::fetch_book_with_categories(pool, book_id, true).await
+    }
+
+    async fn fetch_book_with_categories(
+        pool: &PgPool,
+        book_id: Uuid,
+        include_inactive: bool,
     ) -> Result<BookWithCategories, DatabaseError> {
         let rows = sqlx::query!(
             r#"
@@ -326,9 +696,10 @@ impl BookRepository {
             FROM books b
             LEFT JOIN book_categories bc ON b.id = bc.book_id
             LEFT JOIN categories c ON bc.category_id = c.id AND c.is_active = true
-            WHERE b.id = $1 AND b.is_active = true
+            WHERE b.id = $1 AND ($2 OR b.is_active = true)
             "#,
-            book_id
+            book_id,
+            include_inactive
         )
         .fetch_all(pool)
         .await?;
@@ -376,7 +747,7 @@ impl BookRepository {
         categories.sort_by(|a, b| a.id.cmp(&b.id));
         categories.dedup_by(|a, b| a.id == b.id);
 
-        Ok(BookWithCategories { book, categories })
+        Ok(BookWithCategories { book, categories, thumbnails: Vec::new() })
     }
 
     /// Pencarian buku dengan filter lengkap dan pagination
@@ -404,9 +775,9 @@ impl BookRepository {
             WHERE b.is_active = true"
         );
         
-        // Build MAIN query untuk fetch book IDs
+        // Build MAIN query untuk fetch book IDs (GROUP BY b.id, bukan DISTINCT, supaya ORDER BY kolom lain valid)
         let mut query_builder = QueryBuilder::<Postgres>::new(
-            "SELECT DISTINCT b.id 
+            "SELECT b.id 
             FROM books b 
             LEFT JOIN book_categories bc ON b.id = bc.book_id 
             LEFT JOIN categories c ON bc.category_id = c.id AND c.is_active = true 
@@ -494,23 +865,8 @@ impl BookRepository {
             .await?;
         let total_items: i64 = count_row.get("total");
             
-        // Sorting
-        let sort_column = match params.sort_by.as_deref() {
-            Some("title") => "b.title",
-            Some("author") => "b.author",
-            Some("price") => "b.price",
-            Some("created_at") | _ => "b.created_at",
-        };
-        
-        let sort_direction = match params.sort_order.as_deref() {
-            Some("asc") => "ASC",
-            _ => "DESC",
-        };
-        
-        query_builder.push(" ORDER BY ");
-        query_builder.push(sort_column);
-        query_builder.push(" ");
-        query_builder.push(sort_direction);
+        query_builder.push(" GROUP BY b.id ORDER BY ");
+        query_builder.push(search_order_by(&params));
         
         query_builder.push(" LIMIT ");
         query_builder.push_bind(limit as i64);
@@ -545,6 +901,7 @@ impl BookRepository {
         pdf_path: Option<String>,
         cover_path: Option<String>,
         file_size_mb: Option<BigDecimal>,
+        actor_user_id: Uuid,
     ) -> Result<(), DatabaseError> {
         let mut tx = pool.begin().await?;
 
@@ -719,10 +1076,11 @@ impl BookRepository {
         // Log audit trail
         sqlx::query!(
             r#"
-            INSERT INTO audit_logs (action, resource_type, resource_id, details)
-            VALUES ('BOOK_UPDATED', 'book', $1, $2)
+            INSERT INTO audit_logs (action, resource_type, resource_id, actor_user_id, details)
+            VALUES ('BOOK_UPDATED', 'book', $1, $2, $3)
             "#,
             book_id,
+            actor_user_id,
             serde_json::json!({
                 "timestamp": Utc::now()
             })
@@ -735,16 +1093,17 @@ impl BookRepository {
     }
 
     /// Soft delete buku (tidak menghapus data fisik)
-    /// Hanya mengubah status is_active menjadi false
+    /// Hanya mengubah status is_active menjadi false, return cover_path untuk cleanup thumbnail
     pub async fn delete_book(
         pool: &PgPool,
         book_id: Uuid,
-    ) -> Result<(), DatabaseError> {
+        actor_user_id: Uuid,
+    ) -> Result<Option<String>, DatabaseError> {
         let mut tx = pool.begin().await?;
 
         // Ambil info buku sebelum dihapus untuk audit
         let book_info = sqlx::query!(
-            "SELECT title, author FROM books WHERE id = $1 AND is_active = true",
+            "SELECT title, author, cover_path FROM books WHERE id = $1 AND is_active = true",
             book_id
         )
         .fetch_optional(&mut *tx)
@@ -774,10 +1133,11 @@ impl BookRepository {
         // Log audit trail untuk tracking
         sqlx::query!(
             r#"
-            INSERT INTO audit_logs (action, resource_type, resource_id, details)
-            VALUES ('BOOK_DELETED', 'book', $1, $2)
+            INSERT INTO audit_logs (action, resource_type, resource_id, actor_user_id, details)
+            VALUES ('BOOK_DELETED', 'book', $1, $2, $3)
             "#,
             book_id,
+            actor_user_id,
             serde_json::json!({
                 "title": book.title,
                 "author": book.author,
@@ -788,7 +1148,45 @@ impl BookRepository {
         .await?;
 
         tx.commit().await?;
-        Ok(())
+        Ok(book.cover_path)
+    }
+
+    /// Hitung data yang mereferensikan buku sebelum soft delete (read-only)
+    /// Buku yang sudah nonaktif tetap bisa dicek
+    pub async fn get_delete_impact(
+        pool: &PgPool,
+        book_id: Uuid,
+    ) -> Result<BookDeleteImpact, DatabaseError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                b.title,
+                b.is_active as "is_active!",
+                (SELECT COUNT(*) FROM orders o
+                    WHERE o.book_id = b.id AND o.status = 'pending'
+                    AND (o.expires_at IS NULL OR o.expires_at > NOW())) as "pending_orders!",
+                (SELECT COUNT(*) FROM user_purchases up WHERE up.book_id = b.id) as "purchases!",
+                (SELECT COUNT(*) FROM book_reviews br WHERE br.book_id = b.id) as "reviews!",
+                (SELECT COUNT(*) FROM reading_progress rp WHERE rp.book_id = b.id) as "active_readers!"
+            FROM books b
+            WHERE b.id = $1
+            "#,
+            book_id
+        )
+        .fetch_optional(pool)
+        .await?
+        .ok_or(DatabaseError::BookNotFound)?;
+
+        Ok(BookDeleteImpact {
+            book_id,
+            title: row.title,
+            is_active: row.is_active,
+            pending_orders: row.pending_orders,
+            purchases: row.purchases,
+            reviews: row.reviews,
+            active_readers: row.active_readers,
+            has_dependencies: row.pending_orders + row.purchases + row.reviews + row.active_readers > 0,
+        })
     }
 
     /// Increment download counter untuk tracking popularitas
@@ -806,6 +1204,158 @@ impl BookRepository {
         Ok(())
     }
 
+    /// ID buku untuk recompute stats (semua buku termasuk nonaktif, atau satu buku)
+    pub async fn get_book_ids_for_recompute(
+        pool: &PgPool,
+        book_id: Option<Uuid>,
+    ) -> Result<Vec<Uuid>, DatabaseError> {
+        let ids = sqlx::query_scalar!(
+            "SELECT id FROM books WHERE $1::uuid IS NULL OR id = $1 ORDER BY id",
+            book_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(ids)
+    }
+
+    /// Samakan book_reviews.helpful_count dengan jumlah vote, hanya row yang berbeda yang diupdate
+    pub async fn recompute_helpful_counts(
+        pool: &PgPool,
+        book_ids: &[Uuid],
+    ) -> Result<u64, DatabaseError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE book_reviews br
+            SET helpful_count = votes.total
+            FROM (
+                SELECT r.id, COUNT(v.id)::int AS total
+                FROM book_reviews r
+                LEFT JOIN review_helpful_votes v ON v.review_id = r.id
+                WHERE r.book_id = ANY($1)
+                GROUP BY r.id
+            ) votes
+            WHERE br.id = votes.id
+            AND br.helpful_count IS DISTINCT FROM votes.total
+            "#,
+            book_ids
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Naikkan books.download_count yang lebih kecil dari jumlah order paid
+    /// Download tidak dicatat per event, jadi counter hanya bisa diperbaiki ke batas bawah ini
+    /// Dihitung dari order, bukan audit BOOK_PURCHASED yang bisa tercatat ganda (replay, log lama)
+    pub async fn repair_download_counts(
+        pool: &PgPool,
+        book_ids: &[Uuid],
+    ) -> Result<u64, DatabaseError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE books b
+            SET download_count = purchases.total
+            FROM (
+                SELECT book_id, COUNT(DISTINCT id)::int AS total
+                FROM orders
+                WHERE status = 'paid' AND book_id = ANY($1)
+                GROUP BY book_id
+            ) purchases
+            WHERE b.id = purchases.book_id
+            AND COALESCE(b.download_count, 0) < purchases.total
+            "#,
+            book_ids
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Jalankan side effect payment-success (download count + audit BOOK_PURCHASED) satu kali per order
+    /// Return false jika order sudah pernah diproses (tidak double-increment)
+    pub async fn apply_purchase_side_effects(
+        pool: &PgPool,
+        book_id: Uuid,
+        user_id: Option<Uuid>,
+        order_id: Option<&str>,
+        source: &str,
+        actor_id: Option<Uuid>,
+    ) -> Result<bool, DatabaseError> {
+        let mut tx = pool.begin().await?;
+
+        if let Some(order_id) = order_id {
+            // Serialize webhook & replay untuk order yang sama
+            sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+                .bind(order_id)
+                .execute(&mut *tx)
+                .await?;
+
+            let already_applied = sqlx::query_scalar!(
+                r#"
+                SELECT EXISTS(
+                    SELECT 1 FROM audit_logs
+                    WHERE action = 'BOOK_PURCHASED' AND resource_id = $1 AND details->>'order_id' = $2
+                ) as "exists!"
+                "#,
+                book_id,
+                order_id
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if already_applied {
+                return Ok(false);
+            }
+        }
+
+        sqlx::query!(
+            "UPDATE books SET download_count = download_count + 1, updated_at = NOW() WHERE id = $1",
+            book_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_logs (action, resource_type, resource_id, user_id, actor_user_id, details)
+            VALUES ('BOOK_PURCHASED', 'book', $1, $2, $3, $4)
+            "#,
+            book_id,
+            user_id,
+            actor_id,
+            serde_json::json!({
+                "order_id": order_id.unwrap_or("unknown"),
+                "source": source,
+                "timestamp": chrono::Utc::now()
+            })
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Order untuk replay webhook admin, dicari lewat id atau order_number
+    pub async fn find_replay_order(pool: &PgPool, order_id: &str) -> Result<Option<ReplayOrder>, DatabaseError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT user_id, book_id, status as "status!"
+            FROM orders
+            WHERE id::text = $1 OR order_number = $1
+            LIMIT 1
+            "#,
+            order_id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(|r| ReplayOrder { user_id: r.user_id, book_id: r.book_id, status: r.status }))
+    }
+
     /// Mengambil semua kategori yang aktif
     pub async fn get_all_categories(
         pool: &PgPool,
@@ -837,9 +1387,130 @@ impl BookRepository {
         }).collect())
     }
 
-    pub async fn get_user_library(
+    /// Mengambil kategori yang terhubung ke satu buku
+    pub async fn get_book_categories(
         pool: &PgPool,
-        user_id: Uuid,
+        book_id: Uuid,
+    ) -> Result<Vec<Category>, DatabaseError> {
+        let exists = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM books WHERE id = $1)",
+            book_id
+        )
+        .fetch_one(pool)
+        .await?
+        .unwrap_or(false);
+
+        if !exists {
+            return Err(DatabaseError::BookNotFound);
+        }
+
+        let categories = sqlx::query!(
+            r#"
+            SELECT 
+                c.id, 
+                c.name, 
+                c.slug, 
+                c.description, 
+                c.is_active as "is_active!", 
+                c.created_at as "created_at!"
+            FROM book_categories bc
+            JOIN categories c ON bc.category_id = c.id
+            WHERE bc.book_id = $1
+            ORDER BY c.name
+            "#,
+            book_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(categories.into_iter().map(|row| Category {
+            id: row.id,
+            name: row.name,
+            slug: row.slug,
+            description: row.description,
+            is_active: row.is_active,
+            created_at: row.created_at,
+        }).collect())
+    }
+
+    /// Mengganti seluruh kategori buku tanpa menyentuh field buku lainnya
+    pub async fn replace_book_categories(
+        pool: &PgPool,
+        book_id: Uuid,
+        category_ids: &[Uuid],
+    ) -> Result<Vec<Category>, DatabaseError> {
+        let category_ids: Vec<Uuid> = category_ids.iter()
+            .copied()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+
+        let mut tx = pool.begin().await?;
+
+        // Lock row buku supaya replace kategori tidak balapan dengan update lain
+        let book = sqlx::query!("SELECT id FROM books WHERE id = $1 FOR UPDATE", book_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        if book.is_none() {
+            tx.rollback().await?;
+            return Err(DatabaseError::BookNotFound);
+        }
+
+        if !category_ids.is_empty() {
+            // Validasi semua kategori ada dan aktif
+            let category_count = sqlx::query!(
+                "SELECT COUNT(*) as count FROM categories WHERE id = ANY($1) AND is_active = true",
+                &category_ids
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if category_count.count.unwrap_or(0) != category_ids.len() as i64 {
+                tx.rollback().await?;
+                return Err(DatabaseError::CategoryNotFound);
+            }
+        }
+
+        sqlx::query!("DELETE FROM book_categories WHERE book_id = $1", book_id)
+            .execute(&mut *tx)
+            .await?;
+
+        if !category_ids.is_empty() {
+            let mut query_builder = QueryBuilder::new(
+                "INSERT INTO book_categories (book_id, category_id) "
+            );
+
+            query_builder.push_values(category_ids.iter(), |mut b, category_id| {
+                b.push_bind(book_id).push_bind(category_id);
+            });
+
+            query_builder.build().execute(&mut *tx).await?;
+        }
+
+        // Log audit trail
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_logs (action, resource_type, resource_id, details)
+            VALUES ('BOOK_CATEGORIES_UPDATED', 'book', $1, $2)
+            "#,
+            book_id,
+            serde_json::json!({
+                "category_ids": category_ids,
+                "timestamp": Utc::now()
+            })
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Self::get_book_categories(pool, book_id).await
+    }
+
+    pub async fn get_user_library(
+        pool: &PgPool,
+        user_id: Uuid,
     ) -> Result<Vec<PurchasedBook>, DatabaseError> {
         let rows = sqlx::query!(
             r#"
@@ -852,6 +1523,9 @@ impl BookRepository {
                 up.purchased_at as "purchase_date!",
                 up.download_count as "user_download_count!",
                 up.last_downloaded_at,
+                rp.current_page as "progress_page?",
+                rp.position as "progress_position?",
+                rp.updated_at as "progress_updated_at?",
                 c.id as "category_id?",
                 c.name as "category_name?",
                 c.slug as "category_slug?",
@@ -860,6 +1534,7 @@ impl BookRepository {
                 c.created_at as category_created_at
             FROM user_purchases up
             INNER JOIN books b ON up.book_id = b.id
+            LEFT JOIN reading_progress rp ON rp.user_id = up.user_id AND rp.book_id = b.id
             LEFT JOIN book_categories bc ON b.id = bc.book_id
             LEFT JOIN categories c ON bc.category_id = c.id AND c.is_active = true
             WHERE up.user_id = $1 AND b.is_active = true
@@ -897,6 +1572,12 @@ impl BookRepository {
                     download_count: row.user_download_count,
                     last_downloaded_at: row.last_downloaded_at,
                     categories: Vec::new(),
+                    reading_progress: match (row.progress_page, row.progress_updated_at) {
+                        (Some(page), Some(updated_at)) => Some(ReadingProgress::new(
+                            row.id, page, row.total_pages, row.progress_position.clone(), updated_at,
+                        )),
+                        _ => None,
+                    },
                 }
             });
 
@@ -917,6 +1598,60 @@ impl BookRepository {
         Ok(books_map.into_values().collect())
     }
 
+    // ===== READING PROGRESS METHODS =====
+
+    /// Mengambil posisi baca terakhir user untuk buku
+    pub async fn get_reading_progress(
+        pool: &PgPool,
+        user_id: Uuid,
+        book_id: Uuid,
+    ) -> Result<Option<ReadingProgress>, DatabaseError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT rp.current_page, rp.position, rp.updated_at, b.total_pages
+            FROM reading_progress rp
+            JOIN books b ON b.id = rp.book_id
+            WHERE rp.user_id = $1 AND rp.book_id = $2
+            "#,
+            user_id,
+            book_id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(|r| ReadingProgress::new(book_id, r.current_page, r.total_pages, r.position, r.updated_at)))
+    }
+
+    /// Simpan posisi baca (upsert per user + buku)
+    pub async fn upsert_reading_progress(
+        pool: &PgPool,
+        user_id: Uuid,
+        book_id: Uuid,
+        current_page: i32,
+        position: Option<String>,
+        total_pages: Option<i32>,
+    ) -> Result<ReadingProgress, DatabaseError> {
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO reading_progress (user_id, book_id, current_page, position, updated_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            ON CONFLICT (user_id, book_id) DO UPDATE
+            SET current_page = EXCLUDED.current_page,
+                position = EXCLUDED.position,
+                updated_at = NOW()
+            RETURNING current_page, position, updated_at
+            "#,
+            user_id,
+            book_id,
+            current_page,
+            position
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(ReadingProgress::new(book_id, row.current_page, total_pages, row.position, row.updated_at))
+    }
+
     // ===== PREVIEW METHODS =====
     
     /// Mengambil preview data untuk buku
@@ -949,15 +1684,107 @@ impl BookRepository {
         }
     }
 
+    // ===== VIEW TRACKING METHODS =====
+
+    /// Catat view buku, di-skip jika viewer yang sama sudah tercatat di window dedup yang sama
+    /// (window tetap per `dedup_minutes`, bukan sliding dari view terakhir)
+    /// user_id hanya disimpan jika user-nya ada (header gateway tidak dipercaya penuh)
+    pub async fn record_book_view(
+        pool: &PgPool,
+        book_id: Uuid,
+        user_id: Option<Uuid>,
+        viewer_hash: &str,
+        source: &str,
+        dedup_minutes: i64,
+    ) -> Result<bool, DatabaseError> {
+        let view_window = view_window_start(Utc::now(), dedup_minutes);
+
+        // Unique (book_id, viewer_hash, source, view_window): request paralel tetap satu row
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO book_views (book_id, user_id, viewer_hash, source, view_window)
+            SELECT $1, u.id, $3::varchar, $4::varchar, $5
+            FROM (SELECT 1) AS one
+            LEFT JOIN users u ON u.id = $2
+            ON CONFLICT (book_id, viewer_hash, source, view_window) DO NOTHING
+            "#,
+            book_id,
+            user_id,
+            viewer_hash,
+            source,
+            view_window
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Mengambil statistik view dan konversi (views -> purchases) untuk satu buku
+    pub async fn get_book_view_stats(
+        pool: &PgPool,
+        book_id: Uuid,
+    ) -> Result<BookViewStats, DatabaseError> {
+        let exists = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM books WHERE id = $1)",
+            book_id
+        )
+        .fetch_one(pool)
+        .await?
+        .unwrap_or(false);
+
+        if !exists {
+            return Err(DatabaseError::BookNotFound);
+        }
+
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                COUNT(*) as "total_views!",
+                COUNT(DISTINCT viewer_hash) as "unique_viewers!",
+                COUNT(*) FILTER (WHERE source = 'detail') as "detail_views!",
+                COUNT(*) FILTER (WHERE source = 'preview') as "preview_views!",
+                COUNT(*) FILTER (WHERE created_at >= NOW() - INTERVAL '7 days') as "views_last_7_days!",
+                (SELECT COUNT(*) FROM user_purchases WHERE book_id = $1) as "purchases!"
+            FROM book_views
+            WHERE book_id = $1
+            "#,
+            book_id
+        )
+        .fetch_one(pool)
+        .await?;
+
+        let conversion_rate = if row.unique_viewers > 0 {
+            (row.purchases as f64 / row.unique_viewers as f64) * 100.0
+        } else {
+            0.0
+        };
+
+        Ok(BookViewStats {
+            book_id,
+            total_views: row.total_views,
+            unique_viewers: row.unique_viewers,
+            detail_views: row.detail_views,
+            preview_views: row.preview_views,
+            views_last_7_days: row.views_last_7_days,
+            purchases: row.purchases,
+            conversion_rate,
+        })
+    }
+
     // ===== RELATED BOOKS METHODS =====
     
     /// Mengambil buku terkait berdasarkan kategori yang sama
+    /// Default urut download_count lalu created_at, `by_relevance` mendahulukan jumlah kategori yang sama
     pub async fn get_related_books(
         pool: &PgPool,
         book_id: Uuid,
+        page: u32,
         limit: u32,
-    ) -> Result<Vec<BookWithCategories>, DatabaseError> {
-        let limit = std::cmp::min(limit, 20) as i64;
+        by_relevance: bool,
+    ) -> Result<(Vec<RelatedBook>, i64), DatabaseError> {
+        let limit = std::cmp::min(limit, 20);
+        let offset = (page.saturating_sub(1) as i64) * limit as i64;
 
         let category_ids: Vec<Uuid> = sqlx::query_scalar!(
             "SELECT category_id FROM book_categories WHERE book_id = $1",
@@ -967,44 +1794,114 @@ impl BookRepository {
         .await?;
 
         if category_ids.is_empty() {
-            return Ok(Vec::new());
+            return Ok((Vec::new(), 0));
         }
 
-        let book_ids: Vec<Uuid> = sqlx::query!(
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(DISTINCT b.id) as "total!"
+            FROM books b
+            INNER JOIN book_categories bc ON b.id = bc.book_id
+            WHERE bc.category_id = ANY($1)
+            AND b.id != $2
+            AND b.is_active = true
+            AND b.pdf_path IS NOT NULL
+            "#,
+            &category_ids[..],
+            book_id
+        )
+        .fetch_one(pool)
+        .await?;
+
+        let rows = sqlx::query!(
             r#"
-            SELECT DISTINCT ON (b.id) 
+            SELECT
                 b.id,
-                b.download_count,
-                b.created_at
+                COUNT(*) as "shared_categories!",
+                b.download_count
             FROM books b
             INNER JOIN book_categories bc ON b.id = bc.book_id
             WHERE bc.category_id = ANY($1)
             AND b.id != $2
             AND b.is_active = true
             AND b.pdf_path IS NOT NULL
-            ORDER BY b.id, b.download_count DESC, b.created_at DESC
-            LIMIT $3
+            GROUP BY b.id
+            ORDER BY
+                CASE WHEN $5 THEN COUNT(*) ELSE 0 END DESC,
+                b.download_count DESC,
+                b.created_at DESC,
+                b.id
+            LIMIT $3 OFFSET $4
             "#,
             &category_ids[..],
             book_id,
-            limit
+            limit as i64,
+            offset,
+            by_relevance
         )
         .fetch_all(pool)
-        .await?
-        .into_iter()
-        .map(|row| row.id)
-        .collect();
+        .await?;
 
-        if book_ids.is_empty() {
-            return Ok(Vec::new());
+        if rows.is_empty() {
+            return Ok((Vec::new(), total));
         }
 
-        Self::fetch_books_with_categories(pool, book_ids).await
+        let mut scores: HashMap<Uuid, RelatedScore> = rows.iter()
+            .map(|row| (row.id, RelatedScore {
+                shared_categories: row.shared_categories,
+                download_count: row.download_count.unwrap_or(0),
+            }))
+            .collect();
+
+        let books = Self::fetch_books_with_categories(pool, rows.iter().map(|row| row.id).collect()).await?
+            .into_iter()
+            .filter_map(|book| scores.remove(&book.book.id).map(|relevance| RelatedBook { book, relevance }))
+            .collect();
+
+        Ok((books, total))
     }
 
     // ===== REVIEW METHODS =====
     
     /// Mengambil semua review untuk buku dengan info user
+    /// Satu batch review untuk export, keyset pagination (created_at, id) supaya tidak load semua sekaligus
+    pub async fn get_review_export_batch(
+        pool: &PgPool,
+        book_id: Uuid,
+        after: Option<(chrono::DateTime<Utc>, Uuid)>,
+        limit: i64,
+    ) -> Result<Vec<ReviewExportRecord>, DatabaseError> {
+        let (after_created, after_id) = after.unzip();
+
+        let rows = sqlx::query_as!(
+            ReviewExportRecord,
+            r#"
+            SELECT
+                id as "id!",
+                user_id as "user_id!",
+                rating as "rating!",
+                comment as "comment!",
+                COALESCE(helpful_count, 0) as "helpful_count!",
+                COALESCE(created_at, 'epoch'::timestamptz) as "created_at!"
+            FROM book_reviews
+            WHERE book_id = $1
+              AND ($2::timestamptz IS NULL
+                   OR (COALESCE(created_at, 'epoch'::timestamptz), id) > ($2::timestamptz, $3::uuid))
+            ORDER BY COALESCE(created_at, 'epoch'::timestamptz), id
+            LIMIT $4
+            "#,
+            book_id,
+            after_created,
+            after_id,
+            limit
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// user_name / user_email kosong, diisi handler lewat UserDirectory (tabel users milik auth-service)
     pub async fn get_book_reviews(
         pool: &PgPool,
         book_id: Uuid,
@@ -1020,11 +1917,8 @@ impl BookRepository {
                 br.comment as "comment!",
                 br.helpful_count as "helpful_count!",
                 br.created_at as "created_at!",
-                br.updated_at as "updated_at!",
-                u.full_name as "user_name!",
-                u.email as "user_email!"
+                br.updated_at as "updated_at!"
             FROM book_reviews br
-            INNER JOIN users u ON br.user_id = u.id
             WHERE br.book_id = $1
             ORDER BY br.helpful_count DESC, br.created_at DESC
             "#,
@@ -1053,8 +1947,8 @@ impl BookRepository {
                 id: row.id,
                 book_id: row.book_id,
                 user_id: row.user_id,
-                user_name: row.user_name,
-                user_email: row.user_email,
+                user_name: String::new(),
+                user_email: String::new(),
                 rating: row.rating,
                 comment: row.comment,
                 helpful_count: row.helpful_count,
@@ -1070,8 +1964,8 @@ impl BookRepository {
         Ok((reviews, stats))
     }
 
-    /// Menghitung statistik review untuk buku
-    async fn calculate_review_stats(
+    /// Menghitung statistik review untuk buku (tanpa load review rows)
+    pub async fn calculate_review_stats(
         pool: &PgPool,
         book_id: Uuid,
     ) -> Result<ReviewStats, DatabaseError> {
@@ -1106,7 +2000,145 @@ impl BookRepository {
         })
     }
 
-    /// Membuat review baru untuk buku
+    /// Hitungan mentah untuk badge popularitas buku aktif:
+    /// total download, pembelian `window_days` terakhir, dan pembelian window sebelumnya
+    pub async fn get_popularity_counts(
+        pool: &PgPool,
+        book_id: Uuid,
+        window_days: i64,
+    ) -> Result<(i64, i64, i64), DatabaseError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                COALESCE(b.download_count, 0)::bigint as "total_downloads!",
+                COUNT(up.id) FILTER (
+                    WHERE up.purchased_at >= NOW() - make_interval(days => $2::int)
+                ) as "recent!",
+                COUNT(up.id) FILTER (
+                    WHERE up.purchased_at >= NOW() - make_interval(days => $2::int * 2)
+                    AND up.purchased_at < NOW() - make_interval(days => $2::int)
+                ) as "previous!"
+            FROM books b
+            LEFT JOIN user_purchases up ON up.book_id = b.id
+                AND up.purchased_at >= NOW() - make_interval(days => $2::int * 2)
+            WHERE b.id = $1 AND b.is_active = true
+            GROUP BY b.id
+            "#,
+            book_id,
+            window_days as i32
+        )
+        .fetch_optional(pool)
+        .await?
+        .ok_or(DatabaseError::BookNotFound)?;
+
+        Ok((row.total_downloads, row.recent, row.previous))
+    }
+
+    /// Rata-rata rating dan jumlah review untuk banyak buku dalam satu query
+    /// Buku tanpa review tetap dikembalikan dengan nilai 0, urutan mengikuti `book_ids`
+    pub async fn get_rating_summaries(
+        pool: &PgPool,
+        book_ids: &[Uuid],
+    ) -> Result<Vec<BookRatingSummary>, DatabaseError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                book_id as "book_id!",
+                COUNT(*) as "total!",
+                COALESCE(AVG(rating)::double precision, 0.0) as "avg_rating!"
+            FROM book_reviews
+            WHERE book_id = ANY($1)
+            GROUP BY book_id
+            "#,
+            book_ids
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let stats: HashMap<Uuid, (i64, f64)> = rows.into_iter()
+            .map(|row| (row.book_id, (row.total, row.avg_rating)))
+            .collect();
+
+        Ok(book_ids.iter()
+            .map(|&book_id| {
+                let (total_reviews, average_rating) = stats.get(&book_id).copied().unwrap_or((0, 0.0));
+                BookRatingSummary { book_id, total_reviews, average_rating }
+            })
+            .collect())
+    }
+
+    /// Review yang ditulis user beserta info buku dan rata-rata rating buku saat ini
+    /// Buku nonaktif tetap ditampilkan karena review-nya masih milik user
+    pub async fn get_user_reviews(
+        pool: &PgPool,
+        user_id: Uuid,
+        page: u32,
+        limit: u32,
+    ) -> Result<(Vec<UserReviewWithBook>, i64), DatabaseError> {
+        let offset = ((page - 1) * limit) as i64;
+
+        let total = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM book_reviews WHERE user_id = $1",
+            user_id
+        )
+        .fetch_one(pool)
+        .await?
+        .unwrap_or(0);
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                br.id,
+                br.book_id,
+                b.title as book_title,
+                b.author as book_author,
+                b.cover_path as book_cover_path,
+                stats.avg_rating as "avg_rating!",
+                stats.total_reviews as "total_reviews!",
+                br.rating,
+                br.comment,
+                br.helpful_count as "helpful_count!",
+                br.created_at as "created_at!",
+                br.updated_at as "updated_at!"
+            FROM book_reviews br
+            INNER JOIN books b ON b.id = br.book_id
+            CROSS JOIN LATERAL (
+                SELECT
+                    COALESCE(AVG(r.rating)::double precision, 0.0) as avg_rating,
+                    COUNT(*) as total_reviews
+                FROM book_reviews r
+                WHERE r.book_id = br.book_id
+            ) stats
+            WHERE br.user_id = $1
+            ORDER BY br.updated_at DESC, br.id
+            LIMIT $2 OFFSET $3
+            "#,
+            user_id,
+            limit as i64,
+            offset
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let reviews = rows.into_iter().map(|row| UserReviewWithBook {
+            id: row.id,
+            book_id: row.book_id,
+            book_title: row.book_title,
+            book_author: row.book_author,
+            book_cover_url: row.book_cover_path,
+            book_average_rating: row.avg_rating,
+            book_total_reviews: row.total_reviews,
+            rating: row.rating,
+            comment: row.comment,
+            helpful_count: row.helpful_count,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }).collect();
+
+        Ok((reviews, total))
+    }
+
+    /// Membuat review baru untuk buku, atau update review user yang sudah ada
     pub async fn create_book_review(
         pool: &PgPool,
         book_id: Uuid,
@@ -1128,83 +2160,71 @@ impl BookRepository {
             return Err(DatabaseError::InvalidQuery);
         }
 
-        let existing = sqlx::query!(
-            "SELECT id FROM book_reviews WHERE book_id = $1 AND user_id = $2",
+        // Upsert atomik: submit bersamaan tidak bisa membuat duplikat
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO book_reviews (book_id, user_id, rating, comment)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (book_id, user_id) DO UPDATE
+            SET rating = EXCLUDED.rating, comment = EXCLUDED.comment, updated_at = NOW()
+            RETURNING 
+                id, book_id, user_id,
+                rating as "rating!",
+                comment as "comment!",
+                helpful_count as "helpful_count!",
+                created_at as "created_at!",
+                updated_at as "updated_at!"
+            "#,
             book_id,
-            user_id
+            user_id,
+            rating,
+            comment
         )
-        .fetch_optional(pool)
+        .fetch_one(pool)
         .await?;
 
-        let review = if let Some(existing_review) = existing {
-            // Update existing review
-            let row = sqlx::query!(
-                r#"
-                UPDATE book_reviews
-                SET rating = $1, comment = $2, updated_at = NOW()
-                WHERE id = $3
-                RETURNING 
-                    id, book_id, user_id, 
-                    rating as "rating!",
-                    comment as "comment!",
-                    helpful_count as "helpful_count!",
-                    created_at as "created_at!",
-                    updated_at as "updated_at!"
-                "#,
-                rating,
-                comment,
-                existing_review.id
-            )
-            .fetch_one(pool)
-            .await?;
-
-            BookReview {
-                id: row.id,
-                book_id: row.book_id,
-                user_id: row.user_id,
-                rating: row.rating,
-                comment: row.comment,
-                helpful_count: row.helpful_count,
-                created_at: row.created_at,
-                updated_at: row.updated_at,
-            }
-        } else {
-            // Insert new review
-            let row = sqlx::query!(
-                r#"
-                INSERT INTO book_reviews (book_id, user_id, rating, comment)
-                VALUES ($1, $2, $3, $4)
-                RETURNING 
-                    id, book_id, user_id,
-                    rating as "rating!",
-                    comment as "comment!",
-                    helpful_count as "helpful_count!",
-                    created_at as "created_at!",
-                    updated_at as "updated_at!"
-                "#,
-                book_id,
-                user_id,
-                rating,
-                comment
-            )
-            .fetch_one(pool)
-            .await?;
-
-            BookReview {
-                id: row.id,
-                book_id: row.book_id,
-                user_id: row.user_id,
-                rating: row.rating,
-                comment: row.comment,
-                helpful_count: row.helpful_count,
-                created_at: row.created_at,
-                updated_at: row.updated_at,
-            }
+        let review = BookReview {
+            id: row.id,
+            book_id: row.book_id,
+            user_id: row.user_id,
+            rating: row.rating,
+            comment: row.comment,
+            helpful_count: row.helpful_count,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
         };
 
         Ok(review)
     }
 
+    /// Jumlah buku lain yang di-review user dengan teks identik (case/spasi diabaikan) sejak waktu tertentu
+    pub async fn count_identical_user_reviews(
+        pool: &PgPool,
+        user_id: Uuid,
+        exclude_book_id: Uuid,
+        comment: &str,
+        since: chrono::DateTime<Utc>,
+    ) -> Result<i64, DatabaseError> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!"
+            FROM book_reviews
+            WHERE user_id = $1
+              AND book_id <> $2
+              AND lower(btrim(comment)) = lower(btrim($3))
+              AND updated_at >= $4
+            "#,
+            user_id,
+            exclude_book_id,
+            comment,
+            since
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count)
+    }
+
     /// Check apakah user sudah membeli buku
     pub async fn check_user_purchased_book(
         pool: &PgPool,
@@ -1341,76 +2361,74 @@ impl BookRepository {
     }
 
     /// Mengambil top books berdasarkan metric tertentu
-    /// Metric: downloads, sales, revenue, recent
+    /// Metric: lihat `TOP_BOOK_METRICS`
     pub async fn get_top_books_by_metric(
         pool: &PgPool,
         metric_type: &str,
         limit: u32,
     ) -> Result<Vec<TopBook>, DatabaseError> {
+        let (books, _) = Self::get_top_books_page(pool, metric_type, limit, 0, None).await?;
+        Ok(books)
+    }
+
+    /// Mengambil top books dengan pagination (offset/limit) dan filter category slug
+    /// Return (books, total_items)
+    pub async fn get_top_books_page(
+        pool: &PgPool,
+        metric_type: &str,
+        limit: u32,
+        offset: u32,
+        category: Option<&str>,
+    ) -> Result<(Vec<TopBook>, i64), DatabaseError> {
         let limit = std::cmp::min(limit, 50) as i64;
+        let metric = TopBookMetric::from_name(metric_type)
+            .ok_or(DatabaseError::InvalidMetricType)?;
+        let category = category.map(str::trim).filter(|c| !c.is_empty() && c.len() <= 100);
+
+        let category_filter = r#"
+            ($1::text IS NULL OR EXISTS (
+                SELECT 1 FROM book_categories bc
+                JOIN categories c ON bc.category_id = c.id AND c.is_active = true
+                WHERE bc.book_id = b.id AND c.slug = $1
+            ))
+        "#;
 
-        let query = match metric_type {
-            "downloads" => {
-                r#"
-                SELECT 
-                    b.id, b.title, b.author, b.cover_path, 
-                    b.download_count, b.price, b.created_at,
-                    b.download_count::bigint as metric_value
-                FROM books b
-                WHERE b.is_active = true
-                ORDER BY b.download_count DESC
-                LIMIT $1
-                "#
-            },
-            "sales" => {
-                r#"
-                SELECT 
-                    b.id, b.title, b.author, b.cover_path, 
-                    b.download_count, b.price, b.created_at,
-                    COALESCE(COUNT(o.id), 0)::bigint as metric_value
-                FROM books b
-                LEFT JOIN orders o ON b.id = o.book_id AND o.status = 'paid'
-                WHERE b.is_active = true
-                GROUP BY b.id
-                ORDER BY metric_value DESC
-                LIMIT $1
-                "#
-            },
-            "revenue" => {
-                r#"
-                SELECT 
-                    b.id, b.title, b.author, b.cover_path, 
-                    b.download_count, b.price, b.created_at,
-                    COALESCE(SUM(o.amount), 0)::bigint as metric_value
-                FROM books b
-                LEFT JOIN orders o ON b.id = o.book_id AND o.status = 'paid'
-                WHERE b.is_active = true
-                GROUP BY b.id
-                ORDER BY metric_value DESC
-                LIMIT $1
-                "#
-            },
-            "recent" => {
-                r#"
-                SELECT 
-                    b.id, b.title, b.author, b.cover_path, 
-                    b.download_count, b.price, b.created_at,
-                    EXTRACT(EPOCH FROM b.created_at)::bigint as metric_value
-                FROM books b
-                WHERE b.is_active = true
-                ORDER BY b.created_at DESC
-                LIMIT $1
-                "#
-            },
-            _ => return Err(DatabaseError::InvalidMetricType),
-        };
+        let total_items: i64 = sqlx::query(&format!(
+            "SELECT COUNT(*) FROM books b WHERE b.is_active = true AND {}",
+            category_filter
+        ))
+        .bind(category)
+        .fetch_one(pool)
+        .await?
+        .get(0);
 
-        let rows = sqlx::query(query)
+        let query = format!(
+            r#"
+            SELECT 
+                b.id, b.title, b.author, b.cover_path, 
+                b.download_count, b.price, b.created_at,
+                {value} as metric_value
+            FROM books b
+            {join}
+            WHERE b.is_active = true AND {filter}
+            GROUP BY b.id
+            ORDER BY {order}, b.id
+            LIMIT $2 OFFSET $3
+            "#,
+            value = metric.value_sql(),
+            join = metric.join_sql(),
+            filter = category_filter,
+            order = metric.order_sql(),
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(category)
             .bind(limit)
+            .bind(offset as i64)
             .fetch_all(pool)
             .await?;
 
-        Ok(rows.into_iter().map(|row| TopBook {
+        let books = rows.into_iter().map(|row| TopBook {
             id: row.get("id"),
             title: row.get("title"),
             author: row.get("author"),
@@ -1420,7 +2438,9 @@ impl BookRepository {
             created_at: row.get("created_at"),
             metric_value: row.get("metric_value"),
             metric_type: metric_type.to_string(),
-        }).collect())
+        }).collect();
+
+        Ok((books, total_items))
     }
 
     /// Mengambil analytics penjualan untuk chart
@@ -1499,8 +2519,13 @@ impl BookRepository {
     }
 
     /// Mengambil analytics per kategori
+    /// Revenue di-scope ke periode `from`..`to` (waktu bayar order), tanpa range = all-time
+    /// Download count tetap all-time karena tidak ada log download per waktu
     pub async fn get_category_analytics(
         pool: &PgPool,
+        from: Option<chrono::DateTime<Utc>>,
+        to: Option<chrono::DateTime<Utc>>,
+        limit: i64,
     ) -> Result<Vec<CategoryAnalytics>, DatabaseError> {
         let query = r#"
             SELECT 
@@ -1519,15 +2544,20 @@ impl BookRepository {
                     SUM(amount) as total_revenue
                 FROM orders 
                 WHERE status = 'paid'
+                AND ($1::timestamptz IS NULL OR COALESCE(paid_at, created_at) >= $1)
+                AND ($2::timestamptz IS NULL OR COALESCE(paid_at, created_at) < $2)
                 GROUP BY book_id
             ) o ON b.id = o.book_id
             WHERE c.is_active = true AND b.is_active = true
             GROUP BY c.id, c.name, c.slug
             ORDER BY total_revenue DESC, book_count DESC
-            LIMIT 20
+            LIMIT $3
         "#;
         
         let rows = sqlx::query(query)
+            .bind(from)
+            .bind(to)
+            .bind(limit)
             .fetch_all(pool)
             .await?;
 
@@ -1558,9 +2588,13 @@ impl BookRepository {
                 al.details,
                 al.created_at as "timestamp!",
                 b.title as "title?",        
-                b.author as "author?"       
+                b.author as "author?",
+                al.actor_user_id,
+                u.full_name as "actor_name?",
+                u.email as "actor_email?"
             FROM audit_logs al
             LEFT JOIN books b ON al.resource_id = b.id
+            LEFT JOIN users u ON al.actor_user_id = u.id
             WHERE al.resource_type = 'book'
             AND al.created_at >= CURRENT_DATE - INTERVAL '7 days'
             ORDER BY al.created_at DESC
@@ -1597,6 +2631,11 @@ impl BookRepository {
                 "author": row.author,      
                 "timestamp": row.timestamp,
                 "details": row.details,
+                "actor": row.actor_user_id.map(|id| serde_json::json!({
+                    "user_id": id,
+                    "name": row.actor_name,
+                    "email": row.actor_email
+                })),
                 "icon": match row.action.as_str() {
                     "BOOK_CREATED" => "plus-circle",
                     "BOOK_UPDATED" => "edit",
@@ -1606,4 +2645,307 @@ impl BookRepository {
             })
         }).collect())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use service_common::db_connect::test_pool;
+
+    /// User dummy dengan email unik, dihapus lewat `cleanup_fixtures`
+    async fn insert_test_user(pool: &PgPool) -> Uuid {
+        sqlx::query_scalar("INSERT INTO users (email, password_hash, full_name) VALUES ($1, 'x', 'Test User') RETURNING id")
+            .bind(format!("book-test-{}@example.com", Uuid::new_v4()))
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn insert_test_book(pool: &PgPool, author: &str, price: i32) -> Uuid {
+        sqlx::query_scalar("INSERT INTO books (title, author, price) VALUES ('Test Book', $1, $2) RETURNING id")
+            .bind(author)
+            .bind(BigDecimal::from(price))
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn cleanup_fixtures(pool: &PgPool, user_ids: &[Uuid], book_ids: &[Uuid]) {
+        sqlx::query("DELETE FROM books WHERE id = ANY($1)").bind(book_ids).execute(pool).await.unwrap();
+        sqlx::query("DELETE FROM users WHERE id = ANY($1)").bind(user_ids).execute(pool).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_parallel_review_submissions_leave_one_row() {
+        let pool = test_pool().await;
+        let user_id = insert_test_user(&pool).await;
+        let book_id = insert_test_book(&pool, "Review Race", 10_000).await;
+        sqlx::query("INSERT INTO user_purchases (user_id, book_id) VALUES ($1, $2)")
+            .bind(user_id)
+            .bind(book_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let submissions = (1..=5).map(|rating| {
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                BookRepository::create_book_review(&pool, book_id, user_id, rating, format!("Ulasan paralel nomor {}", rating)).await
+            })
+        }).collect::<Vec<_>>();
+
+        let mut review_ids = HashSet::new();
+        for submission in submissions {
+            review_ids.insert(submission.await.unwrap().expect("upsert review").id);
+        }
+        let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM book_reviews WHERE book_id = $1 AND user_id = $2")
+            .bind(book_id)
+            .bind(user_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        cleanup_fixtures(&pool, &[user_id], &[book_id]).await;
+        assert_eq!(rows, 1);
+        assert_eq!(review_ids.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_paging_identical_prices_has_no_duplicates_or_gaps() {
+        let pool = test_pool().await;
+        let author = format!("Paging{}", Uuid::new_v4().simple());
+        let mut created = HashSet::new();
+        for _ in 0..7 {
+            created.insert(insert_test_book(&pool, &author, 50_000).await);
+        }
+
+        let mut seen = Vec::new();
+        for page in 1..=3 {
+            let params = BookQueryParams {
+                page: Some(page),
+                limit: Some(3),
+                author: Some(author.clone()),
+                sort_by: Some("price".to_string()),
+                sort_order: Some("asc".to_string()),
+                ..Default::default()
+            };
+            let (books, pagination) = BookRepository::search_books(&pool, params).await.unwrap();
+            assert_eq!(pagination.total_items, 7);
+            seen.extend(books.into_iter().map(|b| b.book.id));
+        }
+
+        cleanup_fixtures(&pool, &[], &created.iter().copied().collect::<Vec<_>>()).await;
+        assert_eq!(seen.len(), 7);
+        assert_eq!(seen.into_iter().collect::<HashSet<_>>(), created);
+    }
+
+    #[tokio::test]
+    async fn test_parallel_views_recorded_once_per_window() {
+        let pool = test_pool().await;
+        let book_id = insert_test_book(&pool, "View Dedup", 20_000).await;
+        let viewer_hash = format!("{:064x}", Uuid::new_v4().as_u128());
+
+        let views = (0..5).map(|_| {
+            let (pool, viewer_hash) = (pool.clone(), viewer_hash.clone());
+            tokio::spawn(async move {
+                BookRepository::record_book_view(&pool, book_id, None, &viewer_hash, "detail", 30).await
+            })
+        }).collect::<Vec<_>>();
+
+        let mut recorded = 0;
+        for view in views {
+            recorded += view.await.unwrap().unwrap() as i32;
+        }
+        let preview = BookRepository::record_book_view(&pool, book_id, None, &viewer_hash, "preview", 30).await.unwrap();
+
+        cleanup_fixtures(&pool, &[], &[book_id]).await;
+        assert_eq!(recorded, 1);
+        assert!(preview);
+    }
+
+    #[tokio::test]
+    async fn test_replay_order_lookup_and_admin_actor_recorded() {
+        let pool = test_pool().await;
+        let (buyer, admin) = (insert_test_user(&pool).await, insert_test_user(&pool).await);
+        let book_id = insert_test_book(&pool, "Replay Order", 30_000).await;
+        let order_number = format!("ORD-REPLAY-{}", Uuid::new_v4().simple());
+        let order_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO orders (user_id, book_id, order_number, amount, status) VALUES ($1, $2, $3, 30000, 'paid') RETURNING id"
+        )
+        .bind(buyer)
+        .bind(book_id)
+        .bind(&order_number)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let by_number = BookRepository::find_replay_order(&pool, &order_number).await.unwrap().unwrap();
+        let by_id = BookRepository::find_replay_order(&pool, &order_id.to_string()).await.unwrap().unwrap();
+        let missing = BookRepository::find_replay_order(&pool, "ORD-TIDAK-ADA").await.unwrap();
+
+        let applied = BookRepository::apply_purchase_side_effects(
+            &pool, book_id, Some(buyer), Some(&order_number), "admin_replay", Some(admin),
+        ).await.unwrap();
+        let (user_id, actor_id): (Option<Uuid>, Option<Uuid>) = sqlx::query_as(
+            "SELECT user_id, actor_user_id FROM audit_logs WHERE action = 'BOOK_PURCHASED' AND resource_id = $1"
+        )
+        .bind(book_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        sqlx::query("DELETE FROM audit_logs WHERE resource_id = $1").bind(book_id).execute(&pool).await.unwrap();
+        sqlx::query("DELETE FROM orders WHERE id = $1").bind(order_id).execute(&pool).await.unwrap();
+        cleanup_fixtures(&pool, &[buyer, admin], &[book_id]).await;
+
+        assert_eq!((by_number.status.as_str(), by_number.user_id, by_number.book_id), ("paid", Some(buyer), Some(book_id)));
+        assert_eq!(by_id.user_id, Some(buyer));
+        assert!(missing.is_none());
+        assert!(applied);
+        assert_eq!((user_id, actor_id), (Some(buyer), Some(admin)));
+    }
+
+    #[tokio::test]
+    async fn test_reorder_at_request_limit_reads_back_every_book() {
+        use validator::Validate;
+
+        let too_many = ReorderFeaturedBooksRequest { book_ids: vec![Uuid::new_v4(); MAX_FEATURED_REORDER as usize + 1] };
+        assert!(too_many.validate().is_err());
+
+        let pool = test_pool().await;
+        let admin = insert_test_user(&pool).await;
+        // Reorder mengosongkan featured_order buku lain, disimpan dulu lalu dikembalikan
+        let previous: Vec<(Uuid, Option<i32>)> = sqlx::query_as("SELECT id, featured_order FROM books WHERE is_featured = true")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+
+        let mut book_ids = Vec::new();
+        for _ in 0..MAX_FEATURED_REORDER {
+            book_ids.push(insert_test_book(&pool, "Featured Limit", 10_000).await);
+        }
+        sqlx::query("UPDATE books SET is_featured = true, featured_at = NOW() WHERE id = ANY($1)")
+            .bind(&book_ids)
+            .execute(&pool)
+            .await
+            .unwrap();
+        book_ids.reverse();
+
+        let request = ReorderFeaturedBooksRequest { book_ids: book_ids.clone() };
+        let not_featured = BookRepository::reorder_featured_books(&pool, &request.book_ids, admin).await.unwrap();
+        let featured = BookRepository::get_featured_books(&pool, MAX_FEATURED_REORDER as u32).await.unwrap();
+
+        let (ids, orders): (Vec<Uuid>, Vec<Option<i32>>) = previous.into_iter().unzip();
+        sqlx::query("UPDATE books b SET featured_order = p.featured_order FROM UNNEST($1::uuid[], $2::int[]) AS p(id, featured_order) WHERE b.id = p.id")
+            .bind(&ids)
+            .bind(&orders)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM audit_logs WHERE actor_user_id = $1").bind(admin).execute(&pool).await.unwrap();
+        cleanup_fixtures(&pool, &[admin], &book_ids).await;
+
+        assert!(request.validate().is_ok());
+        assert!(not_featured.is_empty());
+        assert_eq!(featured.iter().map(|f| f.book.book.id).collect::<Vec<_>>(), book_ids);
+    }
+
+    #[tokio::test]
+    async fn test_download_floor_counts_distinct_paid_orders() {
+        let pool = test_pool().await;
+        let buyer = insert_test_user(&pool).await;
+        let repaired = insert_test_book(&pool, "Download Floor", 10_000).await;
+        let ahead = insert_test_book(&pool, "Download Floor", 10_000).await;
+        sqlx::query("UPDATE books SET download_count = CASE WHEN id = $1 THEN 0 ELSE 5 END WHERE id = ANY($2)")
+            .bind(repaired)
+            .bind([repaired, ahead])
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        for (book_id, status) in [(repaired, "paid"), (repaired, "paid"), (repaired, "pending"), (repaired, "refunded"), (ahead, "paid")] {
+            sqlx::query("INSERT INTO orders (user_id, book_id, order_number, amount, status) VALUES ($1, $2, $3, 10000, $4)")
+                .bind(buyer)
+                .bind(book_id)
+                .bind(format!("ORD-FLOOR-{}", Uuid::new_v4().simple()))
+                .bind(status)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        // Audit pembelian tercatat ganda (replay) tidak ikut menaikkan batas bawah
+        for _ in 0..4 {
+            sqlx::query("INSERT INTO audit_logs (action, resource_type, resource_id, details) VALUES ('BOOK_PURCHASED', 'book', $1, '{}')")
+                .bind(repaired)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let updated = BookRepository::repair_download_counts(&pool, &[repaired, ahead]).await.unwrap();
+        let counts: Vec<(Uuid, Option<i32>)> = sqlx::query_as("SELECT id, download_count FROM books WHERE id = ANY($1) ORDER BY id")
+            .bind([repaired, ahead])
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM audit_logs WHERE resource_id = $1").bind(repaired).execute(&pool).await.unwrap();
+        sqlx::query("DELETE FROM orders WHERE user_id = $1").bind(buyer).execute(&pool).await.unwrap();
+        cleanup_fixtures(&pool, &[buyer], &[repaired, ahead]).await;
+
+        assert_eq!(updated, 1);
+        let count = |id: Uuid| counts.iter().find(|(book_id, _)| *book_id == id).unwrap().1;
+        assert_eq!(count(repaired), Some(2));
+        assert_eq!(count(ahead), Some(5));
+    }
+
+    #[test]
+    fn test_view_window_start() {
+        let at = |s: &str| chrono::DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        assert_eq!(view_window_start(at("2026-10-14T10:47:12Z"), 30), at("2026-10-14T10:30:00Z"));
+        assert_eq!(view_window_start(at("2026-10-14T10:30:00Z"), 30), at("2026-10-14T10:30:00Z"));
+        assert_eq!(view_window_start(at("2026-10-14T10:47:12Z"), 0), at("2026-10-14T10:47:00Z"));
+    }
+
+    #[test]
+    fn test_search_order_by_always_has_id_tiebreaker() {
+        let params = |sort_by: Option<&str>, sort_order: Option<&str>| BookQueryParams {
+            sort_by: sort_by.map(String::from),
+            sort_order: sort_order.map(String::from),
+            ..Default::default()
+        };
+
+        assert_eq!(search_order_by(&params(Some("price"), Some("desc"))), "b.price DESC, b.id DESC");
+        assert_eq!(search_order_by(&params(Some("title"), Some("asc"))), "b.title ASC, b.id ASC");
+        assert_eq!(search_order_by(&params(None, None)), "b.created_at DESC, b.id DESC");
+
+        for sort_by in ["title", "author", "price", "created_at", "unknown"] {
+            assert!(search_order_by(&params(Some(sort_by), None)).ends_with(", b.id DESC"));
+        }
+
+        let featured = BookQueryParams { featured_first: Some(true), ..params(Some("price"), Some("asc")) };
+        assert_eq!(
+            search_order_by(&featured),
+            "b.is_featured DESC, b.featured_order ASC NULLS LAST, b.price ASC, b.id ASC"
+        );
+    }
+
+    #[test]
+    fn test_sort_preset_overrides_granular_params() {
+        for name in SORT_PRESETS {
+            assert_eq!(SortPreset::from_name(name).map(SortPreset::name), Some(*name));
+        }
+        assert_eq!(SortPreset::from_name("cheapest"), None);
+
+        let preset = |sort: &str| BookQueryParams {
+            sort: Some(sort.to_string()),
+            sort_by: Some("title".to_string()),
+            sort_order: Some("asc".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(search_order_by(&preset("price_low")), "b.price ASC, b.id ASC");
+        assert_eq!(search_order_by(&preset("oldest")), "b.created_at ASC, b.id ASC");
+        assert_eq!(search_order_by(&preset("popular")), "b.download_count DESC, b.id DESC");
+        assert!(search_order_by(&preset("top_rated")).starts_with("COALESCE((SELECT AVG(r.rating)"));
+    }
+}
@@ -0,0 +1,116 @@
+// /pdf-bookstore/services/book-service/src/download_limiter.rs
+
+use std::{
+    env,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::{
+    fs::File,
+    io::{AsyncRead, ReadBuf},
+    sync::{OwnedSemaphorePermit, Semaphore},
+};
+
+/// Batas download PDF bersamaan per instance (terpisah dari rate limit per user)
+/// Slot dipegang selama body response di-stream, bukan hanya selama handler berjalan
+pub struct DownloadLimiter {
+    semaphore: Option<Arc<Semaphore>>,
+    max_concurrent: usize,
+    queue_wait: Duration,
+    pub retry_after_secs: u64,
+}
+
+impl DownloadLimiter {
+    /// MAX_CONCURRENT_DOWNLOADS (default 50, 0 = tanpa batas), DOWNLOAD_QUEUE_WAIT_MS (default 2000),
+    /// DOWNLOAD_RETRY_AFTER_SECONDS (default 5)
+    pub fn from_env() -> Self {
+        let read = |key: &str, default: u64| {
+            env::var(key).ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(default)
+        };
+
+        Self::new(
+            read("MAX_CONCURRENT_DOWNLOADS", 50) as usize,
+            Duration::from_millis(read("DOWNLOAD_QUEUE_WAIT_MS", 2000)),
+            read("DOWNLOAD_RETRY_AFTER_SECONDS", 5).max(1),
+        )
+    }
+
+    fn new(max_concurrent: usize, queue_wait: Duration, retry_after_secs: u64) -> Self {
+        Self {
+            semaphore: (max_concurrent > 0).then(|| Arc::new(Semaphore::new(max_concurrent))),
+            max_concurrent,
+            queue_wait,
+            retry_after_secs,
+        }
+    }
+
+    /// Tunggu slot maksimal DOWNLOAD_QUEUE_WAIT_MS, None jika tetap penuh
+    pub async fn acquire(&self) -> Option<DownloadSlot> {
+        let Some(semaphore) = &self.semaphore else {
+            return Some(DownloadSlot { _permit: None });
+        };
+
+        match tokio::time::timeout(self.queue_wait, semaphore.clone().acquire_owned()).await {
+            Ok(Ok(permit)) => Some(DownloadSlot { _permit: Some(permit) }),
+            _ => None,
+        }
+    }
+
+    /// Jumlah download yang sedang di-stream
+    pub fn in_flight(&self) -> usize {
+        self.semaphore.as_ref()
+            .map(|s| self.max_concurrent - s.available_permits())
+            .unwrap_or(0)
+    }
+
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
+}
+
+/// Slot download, dilepas saat di-drop (tanpa permit jika limit nonaktif)
+pub struct DownloadSlot {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+/// File PDF yang melepas slot download saat stream selesai atau client putus
+pub struct LimitedFile {
+    file: File,
+    _slot: DownloadSlot,
+}
+
+impl LimitedFile {
+    pub fn new(file: File, slot: DownloadSlot) -> Self {
+        Self { file, _slot: slot }
+    }
+}
+
+impl AsyncRead for LimitedFile {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.file).poll_read(cx, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_download_slots_released_on_drop() {
+        let limiter = DownloadLimiter::new(1, Duration::from_millis(20), 5);
+
+        let slot = limiter.acquire().await.expect("slot pertama tersedia");
+        assert_eq!(limiter.in_flight(), 1);
+        assert!(limiter.acquire().await.is_none());
+
+        drop(slot);
+        assert_eq!(limiter.in_flight(), 0);
+        assert!(limiter.acquire().await.is_some());
+
+        let unlimited = DownloadLimiter::new(0, Duration::from_millis(20), 5);
+        assert!(unlimited.acquire().await.is_some());
+        assert_eq!(unlimited.in_flight(), 0);
+    }
+}
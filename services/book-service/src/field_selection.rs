@@ -0,0 +1,77 @@
+// /pdf-bookstore/services/book-service/src/field_selection.rs
+
+use axum::response::{IntoResponse, Json, Response};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Field buku yang boleh dipilih lewat `fields=` (key hasil serialize BookWithCategories)
+pub const BOOK_FIELDS: &[&str] = &[
+    "id", "title", "author", "description", "isbn", "price", "pdf_path", "cover_path",
+    "file_size_mb", "total_pages", "language", "is_active", "download_count",
+    "created_at", "updated_at", "categories", "thumbnails",
+];
+
+/// Sparse fieldset dari query `fields=title,price,cover_path`, `id` selalu ikut
+#[derive(Debug, PartialEq, Eq)]
+pub struct FieldSelection(Vec<&'static str>);
+
+impl FieldSelection {
+    /// None = response penuh (param tidak ada / tidak ada field valid), field di luar allowlist diabaikan
+    pub fn parse(fields: Option<&str>, allowlist: &'static [&'static str]) -> Option<Self> {
+        let mut selected = vec!["id"];
+        for name in fields?.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            match allowlist.iter().find(|allowed| **allowed == name) {
+                Some(allowed) if !selected.contains(allowed) => selected.push(allowed),
+                Some(_) => {}
+                None => tracing::debug!("Field tidak dikenal diabaikan: {}", name),
+            }
+        }
+
+        (selected.len() > 1).then_some(Self(selected))
+    }
+
+    /// Buang key object yang tidak dipilih
+    fn project(&self, value: Value) -> Value {
+        match value {
+            Value::Object(object) => Value::Object(
+                object.into_iter().filter(|(key, _)| self.0.contains(&key.as_str())).collect::<Map<_, _>>(),
+            ),
+            other => other,
+        }
+    }
+}
+
+/// Serialize response API lalu proyeksikan `data` (object atau array object) sesuai selection
+pub fn sparse_json<T: Serialize>(body: T, selection: Option<&FieldSelection>) -> Response {
+    let Some(selection) = selection else {
+        return Json(body).into_response();
+    };
+
+    let mut value = serde_json::to_value(body).unwrap_or(Value::Null);
+    if let Some(data) = value.get_mut("data") {
+        *data = match data.take() {
+            Value::Array(items) => Value::Array(items.into_iter().map(|item| selection.project(item)).collect()),
+            other => selection.project(other),
+        };
+    }
+    Json(value).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_and_project_fields() {
+        assert_eq!(FieldSelection::parse(None, BOOK_FIELDS), None);
+        assert_eq!(FieldSelection::parse(Some(" , unknown"), BOOK_FIELDS), None);
+
+        let selection = FieldSelection::parse(Some("title, price,secret,title"), BOOK_FIELDS).unwrap();
+        assert_eq!(selection, FieldSelection(vec!["id", "title", "price"]));
+
+        let book = json!({"id": "b1", "title": "Rust", "price": "10", "description": "panjang", "categories": []});
+        assert_eq!(selection.project(book), json!({"id": "b1", "title": "Rust", "price": "10"}));
+        assert_eq!(selection.project(Value::Null), Value::Null);
+    }
+}
@@ -1,31 +1,64 @@
 // /pdf-bookstore/services/book-service/src/handlers.rs
 
 use axum::{
-    extract::{State, Path, Query, Multipart},
-    http::StatusCode,
-    response::{Json, Response},
+    extract::{State, Path, Query, Multipart, ConnectInfo},
+    http::{StatusCode, HeaderMap},
+    response::{IntoResponse, Json, Response},
     Extension,
 };
 
 use crate::models::*;
 
-use crate::database::{BookRepository, DatabaseError};
+use crate::database::{BookRepository, DatabaseError, DbTimeout, SortPreset, SORT_PRESETS, TOP_BOOK_METRICS};
 use crate::upload::FileUploader;
 use crate::AppState;
+use crate::stats_recompute::{RecomputeJob, StatsRecomputer};
+use crate::download_limiter::LimitedFile;
+use crate::review_export::{self, ReviewExportFormat};
+use crate::preview_pages::{PageFormat, PageRenderError};
+use crate::field_selection::{self, FieldSelection, BOOK_FIELDS};
+use crate::cover_upload_url::{CoverUploadError, CoverUploadTarget, MAX_COVER_SIZE_BYTES};
+use service_common::client_ip::ClientIpResolver;
+use crate::review_limiter::ReviewRateLimited;
+use crate::user_directory;
 use uuid::Uuid;
 use validator::Validate;
 use tokio_util::io::ReaderStream;
 use tokio::fs::File;
 use std::env;
+use std::net::IpAddr;
 use bigdecimal::BigDecimal;
 use tokio::time::timeout;
 use std::time::Duration;
+use sha2::{Sha256, Digest};
 
 // Handler untuk mendapatkan daftar buku dengan pagination dan filter
+// Kedalaman OFFSET dibatasi MAX_SEARCH_OFFSET supaya page sangat jauh tidak memaksa scan besar
+// `fields=` membatasi field tiap buku di response (default object penuh)
 pub async fn get_books(
     State(state): State<AppState>,                 
     Query(params): Query<BookQueryParams>,
-) -> Result<Json<PaginatedBooksResponse>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let selection = FieldSelection::parse(params.fields.as_deref(), BOOK_FIELDS);
+
+    // Preset sort: eksplisit harus valid, tanpa sort/sort_by pakai default BOOK_DEFAULT_SORT
+    let sort = match params.sort.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        Some(name) if SortPreset::from_name(name).is_some() => Some(name.to_string()),
+        Some(_) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    success: false,
+                    message: format!("Preset sort tidak valid. Opsi valid: {}", SORT_PRESETS.join(", ")),
+                    error_code: Some("INVALID_SORT".to_string()),
+                    details: None,
+                })
+            ));
+        }
+        None if params.sort_by.is_none() => Some(SortPreset::default_from_env().name().to_string()),
+        None => None,
+    };
+
     // Input validation
     let validated_params = BookQueryParams {
         search: params.search.filter(|s| !s.trim().is_empty() && s.len() <= 255),
@@ -38,20 +71,39 @@ pub async fn get_books(
         limit: Some(params.limit.unwrap_or(12).min(100).max(1)),
         sort_by: params.sort_by,
         sort_order: params.sort_order,
+        sort,
+        featured_first: params.featured_first,
+        fields: None,
     };
+
+    let (page, limit) = (validated_params.page.unwrap_or(1), validated_params.limit.unwrap_or(12));
+    if let Err(max_page) = check_search_depth(page, limit, max_search_offset()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                success: false,
+                message: format!(
+                    "Page maksimal {} untuk limit {}, persempit pencarian dengan filter (search/category/author)",
+                    max_page, limit
+                ),
+                error_code: Some("SEARCH_DEPTH_EXCEEDED".to_string()),
+                details: None,
+            })
+        ));
+    }
     
     match BookRepository::search_books(&state.db, validated_params).await {
-        Ok((books, pagination)) => { 
+        Ok((mut books, pagination)) => { 
             let base_url = env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3002".to_string());
             
-            let books_with_fixed_urls = books.into_iter().map(|mut bwc| {
-                if let Some(ref cover_path) = bwc.book.cover_path {
-                    bwc.book.cover_path = Some(format!("{}{}", base_url, cover_path));
-                }
-                bwc
-            }).collect();
+            for bwc in books.iter_mut() {
+                apply_cover_urls(&state, bwc, &base_url).await;
+            }
             
-            Ok(Json(PaginatedBooksResponse::success(books_with_fixed_urls, pagination)))
+            Ok(field_selection::sparse_json(
+                PaginatedBooksResponse::success(books, pagination),
+                selection.as_ref(),
+            ))
         }
         Err(DatabaseError::InvalidQuery) => Err((
             StatusCode::BAD_REQUEST,
@@ -59,43 +111,96 @@ pub async fn get_books(
                 success: false,
                 message: "Parameter query tidak valid".to_string(),
                 error_code: Some("INVALID_QUERY".to_string()),
+                details: None,
             })
         )),
-        Err(DatabaseError::Connection(ref e)) if e.to_string().contains("timeout") => Err((
+        Err(e) => {
+            tracing::error!("Database error in get_books: {}", e);
+            Err(db_error(&e, "Terjadi kesalahan saat mengambil data buku", "INTERNAL_ERROR"))
+        }
+    }
+}
+
+/// Batas OFFSET list buku (MAX_SEARCH_OFFSET, default 10000, 0 = tanpa batas)
+fn max_search_offset() -> u64 {
+    env::var("MAX_SEARCH_OFFSET")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(10_000)
+}
+
+/// Err(page terakhir yang diizinkan) jika OFFSET page melebihi batas
+fn check_search_depth(page: u32, limit: u32, max_offset: u64) -> Result<(), u64> {
+    let offset = (page.saturating_sub(1) as u64) * limit as u64;
+
+    if max_offset == 0 || offset <= max_offset {
+        Ok(())
+    } else {
+        Err(max_offset / limit.max(1) as u64 + 1)
+    }
+}
+
+/// Batas panjang comment review (REVIEW_COMMENT_MAX_LENGTH, default 1000, maksimal 1000 sesuai CHECK di DB)
+fn review_comment_max_length() -> usize {
+    env::var("REVIEW_COMMENT_MAX_LENGTH")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(1000)
+        .clamp(10, 1000)
+}
+
+/// Mapping error database ke response: timeout jadi 504 DB_TIMEOUT supaya client tahu boleh retry,
+/// error lain 500 dengan pesan dan kode milik endpoint
+fn db_error(error: &impl DbTimeout, message: impl Into<String>, code: &str) -> (StatusCode, Json<ErrorResponse>) {
+    if error.is_timeout() {
+        return (
             StatusCode::GATEWAY_TIMEOUT,
             Json(ErrorResponse {
                 success: false,
                 message: "Database timeout, silakan coba lagi".to_string(),
                 error_code: Some("DB_TIMEOUT".to_string()),
+                details: None,
             })
-        )),
-        Err(e) => {
-            tracing::error!("Database error in get_books: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    success: false,
-                    message: "Terjadi kesalahan saat mengambil data buku".to_string(),
-                    error_code: Some("INTERNAL_ERROR".to_string()),
-                })
-            ))
-        }
+        );
+    }
+
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ErrorResponse {
+            success: false,
+            message: message.into(),
+            error_code: Some(code.to_string()),
+            details: None,
+        })
+    )
+}
+
+/// Tolak comment melebihi batas sebelum validasi lain dan query DB
+fn check_comment_length(comment: &str, max_length: usize) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    if comment.chars().count() <= max_length {
+        return Ok(());
     }
+
+    Err((StatusCode::BAD_REQUEST, Json(ErrorResponse::input_too_long(&[("comment", max_length)]))))
 }
 
-// Handler untuk mendapatkan detail buku berdasarkan ID
+// Handler untuk mendapatkan detail buku berdasarkan ID, `fields=` seperti list buku
 pub async fn get_book_by_id(
     State(state): State<AppState>,                 
     Path(book_id): Path<Uuid>,                    
-) -> Result<Json<BookResponse>, (StatusCode, Json<ErrorResponse>)> {
+    Query(params): Query<BookFieldsParams>,
+    ConnectInfo(peer): ConnectInfo<std::net::SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     match BookRepository::get_book_by_id(&state.db, book_id).await {
         Ok(mut book_with_categories) => {
+            track_book_view(&state, book_id, peer.ip(), &headers, "detail");
+
             // Tambahkan base URL ke cover path
-            if let Some(ref cover_path) = book_with_categories.book.cover_path {
-                let base_url = env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3002".to_string());
-                book_with_categories.book.cover_path = Some(format!("{}{}", base_url, cover_path));
-            }
-            Ok(Json(BookResponse::success(book_with_categories)))
+            let base_url = env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3002".to_string());
+            apply_cover_urls(&state, &mut book_with_categories, &base_url).await;
+            let selection = FieldSelection::parse(params.fields.as_deref(), BOOK_FIELDS);
+            Ok(field_selection::sparse_json(BookResponse::success(book_with_categories), selection.as_ref()))
         }
         Err(DatabaseError::BookNotFound) => {      
             Err((
@@ -104,23 +209,110 @@ pub async fn get_book_by_id(
                     success: false,
                     message: "Book tidak ditemukan".to_string(),
                     error_code: Some("BOOK_NOT_FOUND".to_string()),
+                    details: None,
                 })
             ))
         }
         Err(e) => {                                
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    success: false,
-                    message: format!("Gagal mengambil book: {}", e),
-                    error_code: Some("DATABASE_ERROR".to_string()),
-                })
-            ))
+            Err(db_error(&e, format!("Gagal mengambil book: {}", e), "DATABASE_ERROR"))
         }
     }
 }
 
+/// Handler untuk metadata banyak buku sekaligus (hydrate cart/wishlist tanpa N request)
+/// POST /api/books/batch
+/// Buku tidak ditemukan/nonaktif tidak dikembalikan, ID-nya dicantumkan di meta.missing_ids
+pub async fn get_books_batch(
+    State(state): State<AppState>,
+    Json(request): Json<BatchBooksRequest>,
+) -> Result<Json<BatchBooksResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if let Err(errors) = request.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                success: false,
+                message: format!("Error validasi: {:?}", errors),
+                error_code: Some("VALIDATION_ERROR".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    let book_ids = parse_batch_book_ids(&request.book_ids)?;
+
+    let mut books = BookRepository::get_active_books_by_ids(&state.db, &book_ids).await
+        .map_err(|e| {
+            tracing::error!("Failed to fetch batch books: {}", e);
+            db_error(&e, format!("Gagal mengambil buku: {}", e), "DATABASE_ERROR")
+        })?;
+
+    let base_url = env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3002".to_string());
+    for book in books.iter_mut() {
+        apply_cover_urls(&state, book, &base_url).await;
+    }
+
+    let found: std::collections::HashSet<Uuid> = books.iter().map(|b| b.book.id).collect();
+    let missing_ids = book_ids.iter().copied().filter(|id| !found.contains(id)).collect();
+
+    Ok(Json(BatchBooksResponse::success(books, book_ids.len(), missing_ids)))
+}
+
+// helper view tracking: tulis async supaya tidak memperlambat response
+fn track_book_view(state: &AppState, book_id: Uuid, peer_ip: IpAddr, headers: &HeaderMap, source: &'static str) {
+    let viewer_hash = book_viewer(&state.client_ip, peer_ip, headers);
+
+    let dedup_minutes = env::var("BOOK_VIEW_DEDUP_MINUTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(30);
+
+    let db = state.db.clone();
+    tokio::spawn(async move {
+        // Route detail/preview public: tidak ada user terverifikasi, view selalu dicatat sebagai guest
+        if let Err(e) = BookRepository::record_book_view(
+            &db, book_id, None, &viewer_hash, source, dedup_minutes
+        ).await {
+            tracing::warn!("Gagal mencatat view buku {}: {}", book_id, e);
+        }
+    });
+}
+
+// helper: viewer di-identifikasi dari hash IP + user agent, header identitas (X-User-Id) diabaikan
+// karena route public tidak diverifikasi. IP dari header forwarded hanya dipakai jika peer
+// termasuk TRUSTED_PROXIES (sama dengan webhook)
+fn book_viewer(client_ip: &ClientIpResolver, peer_ip: IpAddr, headers: &HeaderMap) -> String {
+    let user_agent = headers.get("User-Agent").and_then(|h| h.to_str().ok()).unwrap_or("");
+
+    let mut hasher = Sha256::new();
+    hasher.update(client_ip.resolve(peer_ip, headers).to_string().as_bytes());
+    hasher.update(user_agent.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
 // helper create
+// helper: prefix BASE_URL ke cover dan lampirkan thumbnail yang sudah di-generate
+async fn apply_cover_urls(state: &AppState, bwc: &mut BookWithCategories, base_url: &str) {
+    if let Some(ref cover_path) = bwc.book.cover_path {
+        if !is_absolute_url(cover_path) {
+            bwc.thumbnails = state.thumbnails.available(cover_path, base_url).await;
+        }
+        bwc.book.cover_path = Some(public_cover_url(base_url, cover_path));
+    }
+}
+
+// helper: cover dari bucket S3 sudah berupa URL penuh, cover lokal (/storage/...) diberi BASE_URL
+fn public_cover_url(base_url: &str, cover_path: &str) -> String {
+    if is_absolute_url(cover_path) {
+        cover_path.to_string()
+    } else {
+        format!("{}{}", base_url, cover_path)
+    }
+}
+
+fn is_absolute_url(path: &str) -> bool {
+    path.starts_with("https://") || path.starts_with("http://")
+}
+
 async fn cleanup_uploaded_files(pdf_path: Option<&str>, cover_path: Option<&str>) {
     if let Some(pdf) = pdf_path {
         if let Err(e) = FileUploader::delete_file(pdf).await {
@@ -150,6 +342,7 @@ pub async fn create_book(
                 success: false,
                 message: "Akses admin diperlukan".to_string(),
                 error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
             }),
         ));
     }
@@ -167,6 +360,7 @@ pub async fn create_book(
                 success: false,
                 message: "Request timeout saat upload file".to_string(),
                 error_code: Some("UPLOAD_TIMEOUT".to_string()),
+                details: None,
             }),
         ))??;
 
@@ -179,20 +373,23 @@ pub async fn create_book(
                 success: false,
                 message: error_msg,
                 error_code: Some("VALIDATION_ERROR".to_string()),
+                details: None,
             }),
         ));
     }
 
     // Simpan buku ke database
-    match BookRepository::create_book(&state.db, book_request, pdf_path.clone(), cover_path.clone(), file_size_mb).await {
+    match BookRepository::create_book(&state.db, book_request, pdf_path.clone(), cover_path.clone(), file_size_mb, user_id).await {
         Ok(book) => {
+            if let Some(ref cover) = cover_path {
+                state.thumbnails.generate(cover).await;
+            }
+
             // Ambil data lengkap buku dengan kategori
             match BookRepository::get_book_by_id(&state.db, book.id).await {
                 Ok(mut book_with_categories) => {
-                    if let Some(ref cover_path) = book_with_categories.book.cover_path {
-                        let base_url = env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3002".to_string());
-                        book_with_categories.book.cover_path = Some(format!("{}{}", base_url, cover_path));
-                    }
+                    let base_url = env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3002".to_string());
+                    apply_cover_urls(&state, &mut book_with_categories, &base_url).await;
                     Ok(Json(BookResponse::success(book_with_categories)))
                 }
                 Err(_) => {
@@ -214,6 +411,7 @@ pub async fn create_book(
                         success: false,
                         message: "ISBN sudah ada".to_string(),
                         error_code: Some("ISBN_EXISTS".to_string()),
+                        details: None,
                     }),
                 )),
                 DatabaseError::CategoryNotFound => Err((
@@ -222,16 +420,10 @@ pub async fn create_book(
                         success: false,
                         message: "Satu atau lebih kategori tidak ditemukan".to_string(),
                         error_code: Some("CATEGORY_NOT_FOUND".to_string()),
+                        details: None,
                     }),
                 )),
-                _ => Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(ErrorResponse {
-                        success: false,
-                        message: format!("Gagal membuat book: {}", e),
-                        error_code: Some("DATABASE_ERROR".to_string()),
-                    }),
-                )),
+                _ => Err(db_error(&e, format!("Gagal membuat book: {}", e), "DATABASE_ERROR")),
             }
         }
     }
@@ -265,6 +457,7 @@ async fn parse_create_book_multipart(
             success: false,
             message: format!("Gagal parse multipart data: {}", e),
             error_code: Some("MULTIPART_ERROR".to_string()),
+            details: None,
         })
     ))? {
         let name = field.name().unwrap_or("").to_string();
@@ -278,6 +471,7 @@ async fn parse_create_book_multipart(
                     success: false,
                     message: "Gagal membaca field chunk".to_string(),
                     error_code: Some("CHUNK_READ_ERROR".to_string()),
+                    details: None,
                 })
             ))? {
                 if buffer.len() + chunk.len() > MAX_TEXT_FIELD_SIZE {
@@ -287,6 +481,7 @@ async fn parse_create_book_multipart(
                             success: false,
                             message: format!("Field {} terlalu besar (max 10KB)", name),
                             error_code: Some("FIELD_TOO_LARGE".to_string()),
+                            details: None,
                         })
                     ));
                 }
@@ -299,6 +494,7 @@ async fn parse_create_book_multipart(
                     success: false,
                     message: "Encoding UTF-8 tidak valid".to_string(),
                     error_code: Some("INVALID_ENCODING".to_string()),
+                    details: None,
                 })
             ))?;
 
@@ -330,6 +526,7 @@ async fn parse_create_book_multipart(
                             success: false,
                             message: "Gagal baca field price".to_string(),
                             error_code: Some("FIELD_READ_ERROR".to_string()),
+                            details: None,
                         })
                     ))?;
                     price = Some(text.parse::<BigDecimal>().map_err(|_| (
@@ -338,6 +535,7 @@ async fn parse_create_book_multipart(
                             success: false,
                             message: "Format price tidak valid".to_string(),
                             error_code: Some("INVALID_PRICE".to_string()),
+                            details: None,
                         })
                     ))?);
                 }
@@ -348,6 +546,7 @@ async fn parse_create_book_multipart(
                             success: false,
                             message: "Gagal baca field language".to_string(),
                             error_code: Some("FIELD_READ_ERROR".to_string()),
+                            details: None,
                         })
                     ))?);
                 }
@@ -358,6 +557,7 @@ async fn parse_create_book_multipart(
                             success: false,
                             message: "Gagal baca field category_ids".to_string(),
                             error_code: Some("FIELD_READ_ERROR".to_string()),
+                            details: None,
                         })
                     ))?;
                     let ids: Result<Vec<Uuid>, _> = text.split(',')
@@ -372,6 +572,7 @@ async fn parse_create_book_multipart(
                             success: false,
                             message: "Format category_ids tidak valid".to_string(),
                             error_code: Some("INVALID_CATEGORY_ID".to_string()),
+                            details: None,
                         })
                     ))?);
                 }
@@ -382,6 +583,7 @@ async fn parse_create_book_multipart(
                             success: false,
                             message: "Gagal baca field total_pages".to_string(),
                             error_code: Some("FIELD_READ_ERROR".to_string()),
+                            details: None,
                         })
                     ))?;
                     if !text.trim().is_empty() {
@@ -391,6 +593,7 @@ async fn parse_create_book_multipart(
                                 success: false,
                                 message: "Format total_pages tidak valid".to_string(),
                                 error_code: Some("INVALID_PAGES".to_string()),
+                                details: None,
                             })
                         ))?);
                     }
@@ -425,6 +628,7 @@ async fn parse_create_book_multipart(
             success: false,
             message: "Title diperlukan".to_string(),
             error_code: Some("MISSING_TITLE".to_string()),
+            details: None,
         })
     ))?;
 
@@ -434,6 +638,7 @@ async fn parse_create_book_multipart(
             success: false,
             message: "Author diperlukan".to_string(),
             error_code: Some("MISSING_AUTHOR".to_string()),
+            details: None,
         })
     ))?;
 
@@ -443,13 +648,14 @@ async fn parse_create_book_multipart(
             success: false,
             message: "Price diperlukan".to_string(),
             error_code: Some("MISSING_PRICE".to_string()),
+            details: None,
         })
     ))?;
 
     let book_request = CreateBookRequest {
-        title,
-        author,
-        description,
+        title: sanitize_book_text(&title),
+        author: sanitize_book_text(&author),
+        description: sanitize_optional_book_text(description),
         isbn,
         price,
         language,
@@ -465,6 +671,7 @@ async fn parse_create_book_multipart(
                 success: false,
                 message: e,
                 error_code: Some("VALIDATION_ERROR".to_string()),
+                details: None,
             })
         ));
     }
@@ -488,6 +695,7 @@ pub async fn update_book(
                 success: false,
                 message: "Akses admin diperlukan".to_string(),
                 error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
             })
         ));
     }
@@ -505,6 +713,7 @@ pub async fn update_book(
                 success: false,
                 message: "Request timeout saat upload file".to_string(),
                 error_code: Some("UPLOAD_TIMEOUT".to_string()),
+                details: None,
             })
         ))??;
 
@@ -516,20 +725,56 @@ pub async fn update_book(
             Json(ErrorResponse {
                 success: false,
                 message: error_msg,
-                error_code: Some("VALIDATION_ERROR".to_string())
+                error_code: Some("VALIDATION_ERROR".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    // Business rules dijalankan terhadap state akhir (data lama + field yang di-update)
+    let current = match BookRepository::get_book_by_id_any_status(&state.db, book_id).await {
+        Ok(current) => current,
+        Err(DatabaseError::BookNotFound) => return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                success: false,
+                message: "Book tidak ditemukan".to_string(),
+                error_code: Some("BOOK_NOT_FOUND".to_string()),
+                details: None,
+            })
+        )),
+        Err(e) => return Err(db_error(&e, format!("Gagal mengambil book: {}", e), "DATABASE_ERROR")),
+    };
+
+    if let Err(e) = update_request.effective_state(&current).validate_business_rules() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                success: false,
+                message: e,
+                error_code: Some("VALIDATION_ERROR".to_string()),
+                details: None,
             })
         ));
     }
 
     // Update buku di database
-    match BookRepository::update_book(&state.db, book_id, update_request, pdf_path, cover_path, file_size_mb).await {
+    match BookRepository::update_book(&state.db, book_id, update_request, pdf_path, cover_path.clone(), file_size_mb, user_id).await {
         Ok(_) => {
+            state.internal_book_cache.write().await.remove(&book_id);
+
+            // Cover diganti: generate ulang thumbnail, buang thumbnail cover lama
+            if let Some(ref new_cover) = cover_path {
+                state.thumbnails.generate(new_cover).await;
+                if let Some(old_cover) = current.book.cover_path.as_deref().filter(|old| *old != new_cover) {
+                    state.thumbnails.remove(old_cover).await;
+                }
+            }
+
             match BookRepository::get_book_by_id(&state.db, book_id).await {
                 Ok(mut book_with_categories) => {
-                    if let Some(ref cover_path) = book_with_categories.book.cover_path {
-                        let base_url = env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3002".to_string());
-                        book_with_categories.book.cover_path = Some(format!("{}{}", base_url, cover_path));
-                    }
+                    let base_url = env::var("BASE_URL").unwrap_or_else(|_| "http://localhost:3002".to_string());
+                    apply_cover_urls(&state, &mut book_with_categories, &base_url).await;
                     Ok(Json(BookResponse::success(book_with_categories)))
                 }
                 Err(_) => {
@@ -544,6 +789,7 @@ pub async fn update_book(
                     success: false,
                     message: "Book tidak ditemukan".to_string(),
                     error_code: Some("BOOK_NOT_FOUND".to_string()),
+                    details: None,
                 })
             ))
         }
@@ -554,6 +800,7 @@ pub async fn update_book(
                     success: false,
                     message: "ISBN sudah ada".to_string(),
                     error_code: Some("ISBN_EXISTS".to_string()),
+                    details: None,
                 })
             ))
         }
@@ -564,18 +811,12 @@ pub async fn update_book(
                     success: false,
                     message: "Satu atau lebih kategori tidak ditemukan".to_string(),
                     error_code: Some("CATEGORY_NOT_FOUND".to_string()),
+                    details: None,
                 })
             ))
         }
         Err(e) => {
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    success: false,
-                    message: format!("Gagal update book: {}", e),
-                    error_code: Some("DATABASE_ERROR".to_string()),
-                })
-            ))
+            Err(db_error(&e, format!("Gagal update book: {}", e), "DATABASE_ERROR"))
         }
     }
 }
@@ -605,6 +846,7 @@ async fn parse_update_book_multipart(
                 success: false,
                 message: format!("Gagal parse multipart data: {}", e),
                 error_code: Some("MULTIPART_ERROR".to_string()),
+                details: None,
             })
         ))? {
         
@@ -618,6 +860,7 @@ async fn parse_update_book_multipart(
                         success: false,
                         message: "Gagal baca field title".to_string(),
                         error_code: Some("FIELD_READ_ERROR".to_string()),
+                        details: None,
                     })
                 ))?;
                 if !text.trim().is_empty() {
@@ -631,6 +874,7 @@ async fn parse_update_book_multipart(
                         success: false,
                         message: "Gagal baca field author".to_string(),
                         error_code: Some("FIELD_READ_ERROR".to_string()),
+                        details: None,
                     })
                 ))?;
                 if !text.trim().is_empty() {
@@ -644,6 +888,7 @@ async fn parse_update_book_multipart(
                         success: false,
                         message: "Gagal baca field description".to_string(),
                         error_code: Some("FIELD_READ_ERROR".to_string()),
+                        details: None,
                     })
                 ))?;
                 if !text.trim().is_empty() {
@@ -659,6 +904,7 @@ async fn parse_update_book_multipart(
                         success: false,
                         message: "Gagal baca field ISBN".to_string(),
                         error_code: Some("FIELD_READ_ERROR".to_string()),
+                        details: None,
                     })
                 ))?;
                 if !text.trim().is_empty() {
@@ -674,6 +920,7 @@ async fn parse_update_book_multipart(
                         success: false,
                         message: "Gagal baca field price".to_string(),
                         error_code: Some("FIELD_READ_ERROR".to_string()),
+                        details: None,
                     })
                 ))?;
                 if !text.trim().is_empty() {
@@ -683,6 +930,7 @@ async fn parse_update_book_multipart(
                             success: false,
                             message: "Format price tidak valid".to_string(),
                             error_code: Some("INVALID_PRICE".to_string()),
+                            details: None,
                         })
                     ))?);
                 }
@@ -694,6 +942,7 @@ async fn parse_update_book_multipart(
                         success: false,
                         message: "Gagal baca field language".to_string(),
                         error_code: Some("FIELD_READ_ERROR".to_string()),
+                        details: None,
                     })
                 ))?;
                 if !text.trim().is_empty() {
@@ -707,6 +956,7 @@ async fn parse_update_book_multipart(
                         success: false,
                         message: "Gagal baca field is_active".to_string(),
                         error_code: Some("FIELD_READ_ERROR".to_string()),
+                        details: None,
                     })
                 ))?;
                 if !text.trim().is_empty() {
@@ -720,6 +970,7 @@ async fn parse_update_book_multipart(
                         success: false,
                         message: "Gagal baca field category_ids".to_string(),
                         error_code: Some("FIELD_READ_ERROR".to_string()),
+                        details: None,
                     })
                 ))?;
                 
@@ -737,6 +988,7 @@ async fn parse_update_book_multipart(
                             success: false,
                             message: "Format ID kategori tidak valid".to_string(),
                             error_code: Some("INVALID_CATEGORY_ID".to_string()),
+                            details: None,
                         })
                     ))?);
                 }
@@ -748,6 +1000,7 @@ async fn parse_update_book_multipart(
                         success: false,
                         message: "Gagal baca field total_pages".to_string(),
                         error_code: Some("FIELD_READ_ERROR".to_string()),
+                        details: None,
                     })
                 ))?;
                 if !text.trim().is_empty() {
@@ -757,6 +1010,7 @@ async fn parse_update_book_multipart(
                             success: false,
                             message: "Format total_pages tidak valid".to_string(),
                             error_code: Some("INVALID_PAGES".to_string()),
+                            details: None,
                         })
                     ))?);
                 }
@@ -783,9 +1037,9 @@ async fn parse_update_book_multipart(
     }
 
     let update_request = UpdateBookRequest {
-        title,
-        author,
-        description,
+        title: title.map(|t| sanitize_book_text(&t)),
+        author: author.map(|a| sanitize_book_text(&a)),
+        description: sanitize_optional_book_text(description),
         isbn,
         price,
         language,
@@ -801,8 +1055,9 @@ async fn parse_update_book_multipart(
 pub async fn delete_book(
     State(state): State<AppState>,                 
     Path(book_id): Path<Uuid>,                    
-    Extension(user_role): Extension<String>,      
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    Extension(user_role): Extension<String>,
+    Extension(user_id): Extension<Uuid>,      
+) -> Result<Json<ApiResponse<DeletedBook>>, (StatusCode, Json<ErrorResponse>)> {
     // Validasi akses admin
     if user_role != "admin" {                     
         return Err((
@@ -811,17 +1066,22 @@ pub async fn delete_book(
                 success: false,
                 message: "Akses admin diperlukan".to_string(),
                 error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
             })
         ));
     }
 
     // Hapus buku (soft delete di database)
-    match BookRepository::delete_book(&state.db, book_id).await {
-        Ok(()) => {                                
-            Ok(Json(serde_json::json!({
-                "success": true,
-                "message": "Book berhasil dihapus"
-            })))
+    match BookRepository::delete_book(&state.db, book_id, user_id).await {
+        Ok(cover_path) => {                                
+            state.internal_book_cache.write().await.remove(&book_id);
+
+            // Thumbnail dan variant WebP bisa di-generate ulang, cover asli tetap disimpan
+            if let Some(cover_path) = cover_path.filter(|path| !is_absolute_url(path)) {
+                state.thumbnails.remove(&cover_path).await;
+            }
+
+            Ok(Json(ApiResponse::ok("Book berhasil dihapus", DeletedBook { book_id })))
         }
         Err(DatabaseError::BookNotFound) => {
             Err((
@@ -830,39 +1090,96 @@ pub async fn delete_book(
                     success: false,
                     message: "Book tidak ditemukan".to_string(),
                     error_code: Some("BOOK_NOT_FOUND".to_string()),
+                    details: None,
                 })
             ))
         }
         Err(e) => {
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    success: false,
-                    message: format!("Gagal hapus book: {}", e),
-                    error_code: Some("DATABASE_ERROR".to_string()),
-                })
-            ))
+            Err(db_error(&e, format!("Gagal hapus book: {}", e), "DATABASE_ERROR"))
         }
     }
 }
 
 // Handler untuk health check endpoint
-pub async fn health_check() -> Json<serde_json::Value> {
+pub async fn health_check(State(state): State<AppState>) -> Json<serde_json::Value> {
     Json(serde_json::json!({
         "service": "book-service",
         "status": "healthy",
         "timestamp": chrono::Utc::now(),
-        "version": "1.0.0"
+        "version": "1.0.0",
+        "metrics": {
+            "downloads_in_flight": state.download_limiter.in_flight(),
+            "max_concurrent_downloads": state.download_limiter.max_concurrent(),
+        }
     }))
 }
 
+/// Nama file download dari template DOWNLOAD_FILENAME_TEMPLATE
+/// Placeholder: {title}, {author}, {isbn}. Default: "{title}_by_{author}"
+fn download_filename(book: &Book) -> String {
+    let template = env::var("DOWNLOAD_FILENAME_TEMPLATE")
+        .unwrap_or_else(|_| "{title}_by_{author}".to_string());
+
+    let name = template
+        .replace("{title}", &book.title)
+        .replace("{author}", &book.author)
+        .replace("{isbn}", book.isbn.as_deref().unwrap_or(""));
+
+    // Buang control char dan karakter yang tidak aman untuk nama file / header
+    let sanitized: String = name
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| match c {
+            ' ' => '_',
+            '/' | '\\' | '"' | ';' | ':' | '*' | '?' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect();
+
+    let trimmed = sanitized.trim_matches(|c| c == '_' || c == '.');
+    let base = if trimmed.is_empty() { "book" } else { trimmed };
+
+    format!("{}.pdf", base.chars().take(150).collect::<String>())
+}
+
+/// Header Content-Disposition attachment dengan filename ASCII + filename*=UTF-8''
+fn content_disposition_attachment(filename: &str) -> axum::http::HeaderValue {
+    let ascii_fallback: String = filename
+        .chars()
+        .map(|c| if c.is_ascii() { c } else { '_' })
+        .collect();
+
+    // RFC 5987 attr-char tidak perlu di-encode, sisanya percent-encode per byte UTF-8
+    let encoded: String = filename
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9'
+            | b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+
+    format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", ascii_fallback, encoded)
+        .parse()
+        .unwrap_or_else(|_| axum::http::HeaderValue::from_static("attachment; filename=\"book.pdf\""))
+}
+
 // Handler untuk download file PDF (memerlukan autentikasi)
+// Non-admin wajib memiliki buku: row user_purchases, atau konfirmasi payment-service (PurchaseVerifier)
 pub async fn download_book_pdf(
     State(state): State<AppState>,                 
     Path(book_id): Path<Uuid>,                     
-    Extension(_user_id): Extension<Uuid>,          
+    Extension(user_id): Extension<Uuid>,          
+    Extension(user_role): Extension<String>,
 ) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
 
+    // Hanya pembeli (atau admin) yang boleh download
+    if user_role != "admin" {
+        ensure_book_owned(&state, user_id, book_id).await?;
+    }
+
     // Ambil data buku untuk mendapatkan path PDF
     let book = match BookRepository::get_book_by_id(&state.db, book_id).await {
         Ok(book_with_categories) => book_with_categories.book, 
@@ -873,23 +1190,17 @@ pub async fn download_book_pdf(
                     success: false,
                     message: "Book tidak ditemukan".to_string(),
                     error_code: Some("BOOK_NOT_FOUND".to_string()),
+                    details: None,
                 })
             ));
         }
         Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    success: false,
-                    message: format!("Gagal mengambil book: {}", e),
-                    error_code: Some("DATABASE_ERROR".to_string()),
-                })
-            ));
+            return Err(db_error(&e, format!("Gagal mengambil book: {}", e), "DATABASE_ERROR"));
         }
     };
 
     // Cek apakah file PDF tersedia
-    let pdf_path = match book.pdf_path {           
+    let pdf_path = match book.pdf_path.clone() {           
         Some(path) => path,                        
         None => {                                  
             return Err((
@@ -898,6 +1209,7 @@ pub async fn download_book_pdf(
                     success: false,
                     message: "File PDF tidak tersedia".to_string(),
                     error_code: Some("PDF_NOT_AVAILABLE".to_string()),
+                    details: None,
                 })
             ));
         }
@@ -911,6 +1223,24 @@ pub async fn download_book_pdf(
         format!("{}/{}", upload_dir, pdf_path)     
     };
 
+    // Batas download bersamaan per instance: tunggu sebentar, lalu 503 + Retry-After jika tetap penuh
+    let Some(slot) = state.download_limiter.acquire().await else {
+        tracing::warn!(
+            "Download buku {} ditolak: {} download sedang berjalan",
+            book_id, state.download_limiter.in_flight()
+        );
+        return Ok((
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(axum::http::header::RETRY_AFTER, state.download_limiter.retry_after_secs.to_string())],
+            Json(ErrorResponse {
+                success: false,
+                message: "Server sedang sibuk melayani download, silakan coba lagi".to_string(),
+                error_code: Some("DOWNLOAD_CAPACITY_EXCEEDED".to_string()),
+                details: None,
+            }),
+        ).into_response());
+    };
+
     // Buka file PDF untuk streaming
     let file = match File::open(&absolute_path).await {
         Ok(file) => file,                          
@@ -921,6 +1251,7 @@ pub async fn download_book_pdf(
                     success: false,
                     message: "File PDF tidak ditemukan di server".to_string(),
                     error_code: Some("FILE_NOT_FOUND".to_string()),
+                    details: None,
                 })
             ));
         }
@@ -929,8 +1260,8 @@ pub async fn download_book_pdf(
     // Update counter download
     let _ = BookRepository::increment_download_count(&state.db, book_id).await;
 
-    // Streaming file untuk download
-    let stream = ReaderStream::new(file);          
+    // Streaming file untuk download, slot dilepas saat stream selesai
+    let stream = ReaderStream::new(LimitedFile::new(file, slot));
     let body = axum::body::Body::from_stream(stream); 
 
     // Setup response headers
@@ -940,13 +1271,9 @@ pub async fn download_book_pdf(
     // Set content type untuk PDF
     headers.insert("content-type", "application/pdf".parse().unwrap());
 
-    // Set content disposition dengan nama file
-    let filename = format!("{}_by_{}.pdf", 
-        book.title.replace(" ", "_"),              
-        book.author.replace(" ", "_")             
-    );
-    headers.insert("content-disposition", 
-        format!("attachment; filename=\"{}\"", filename).parse().unwrap());
+    // Set content disposition dengan nama file (ASCII fallback + RFC 5987 untuk UTF-8)
+    let filename = download_filename(&book);
+    headers.insert("content-disposition", content_disposition_attachment(&filename));
 
     // Set cache control
     headers.insert("cache-control", "private, max-age=3600".parse().unwrap());
@@ -957,30 +1284,119 @@ pub async fn download_book_pdf(
 // Handler untuk mendapatkan semua kategori
 pub async fn get_categories(
     State(state): State<AppState>,                 
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<Json<ApiResponse<Vec<Category>>>, (StatusCode, Json<ErrorResponse>)> {
     match BookRepository::get_all_categories(&state.db).await {
         Ok(categories) => {                       
-            Ok(Json(serde_json::json!({
-                "success": true,
-                "message": "Kategori berhasil diambil",
-                "data": categories
-            })))
+            Ok(Json(ApiResponse::ok("Kategori berhasil diambil", categories)))
         }
         Err(e) => {                                
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    success: false,
-                    message: format!("Gagal mengambil kategori: {}", e),
-                    error_code: Some("DATABASE_ERROR".to_string()),
-                })
-            ))
+            Err(db_error(&e, format!("Gagal mengambil kategori: {}", e), "DATABASE_ERROR"))
+        }
+    }
+}
+
+// Handler untuk melihat kategori satu buku (Admin only)
+// GET /api/admin/books/{id}/categories
+pub async fn get_admin_book_categories(
+    State(state): State<AppState>,
+    Extension(user_role): Extension<String>,
+    Path(book_id): Path<Uuid>,
+) -> Result<Json<ApiResponse<Vec<Category>>>, (StatusCode, Json<ErrorResponse>)> {
+    // Validasi akses admin
+    if user_role != "admin" {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                success: false,
+                message: "Akses admin diperlukan".to_string(),
+                error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    match BookRepository::get_book_categories(&state.db, book_id).await {
+        Ok(categories) => {
+            Ok(Json(ApiResponse::ok("Kategori buku berhasil diambil", categories)))
+        }
+        Err(e) => Err(book_categories_error(e)),
+    }
+}
+
+// Handler untuk mengganti kategori buku tanpa update field lain (Admin only)
+// PUT /api/admin/books/{id}/categories
+pub async fn update_admin_book_categories(
+    State(state): State<AppState>,
+    Extension(user_role): Extension<String>,
+    Path(book_id): Path<Uuid>,
+    Json(request): Json<UpdateBookCategoriesRequest>,
+) -> Result<Json<ApiResponse<Vec<Category>>>, (StatusCode, Json<ErrorResponse>)> {
+    // Validasi akses admin
+    if user_role != "admin" {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                success: false,
+                message: "Akses admin diperlukan".to_string(),
+                error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    if let Err(errors) = request.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                success: false,
+                message: format!("Validation error: {:?}", errors),
+                error_code: Some("VALIDATION_ERROR".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    match BookRepository::replace_book_categories(&state.db, book_id, &request.category_ids).await {
+        Ok(categories) => {
+            state.internal_book_cache.write().await.remove(&book_id);
+            tracing::info!("Kategori buku {} diganti: {} kategori", book_id, categories.len());
+            Ok(Json(ApiResponse::ok("Kategori buku berhasil diperbarui", categories)))
+        }
+        Err(e) => Err(book_categories_error(e)),
+    }
+}
+
+// helper mapping error endpoint kategori buku
+fn book_categories_error(error: DatabaseError) -> (StatusCode, Json<ErrorResponse>) {
+    match error {
+        DatabaseError::BookNotFound => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                success: false,
+                message: "Book tidak ditemukan".to_string(),
+                error_code: Some("BOOK_NOT_FOUND".to_string()),
+                details: None,
+            })
+        ),
+        DatabaseError::CategoryNotFound => (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                success: false,
+                message: "Satu atau lebih kategori tidak ditemukan atau tidak aktif".to_string(),
+                error_code: Some("CATEGORY_NOT_FOUND".to_string()),
+                details: None,
+            })
+        ),
+        e => {
+            tracing::error!("Gagal memproses kategori buku: {}", e);
+            db_error(&e, format!("Gagal memproses kategori buku: {}", e), "DATABASE_ERROR")
         }
     }
 }
 
 // Handler untuk upload file PDF saja (Admin only)
 pub async fn upload_pdf_only(
+    State(state): State<AppState>,
     Extension(user_role): Extension<String>,
     Extension(user_id): Extension<Uuid>,       
     multipart: Multipart,                          
@@ -993,18 +1409,20 @@ pub async fn upload_pdf_only(
                 success: false,
                 message: "Akses admin diperlukan".to_string(),
                 error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
             })
         ));
     }
 
     // Inisialisasi uploader dan upload PDF
-    let file_uploader = FileUploader::new()
+    let file_uploader = FileUploader::new(state.upload_tracker.clone())
         .map_err(|e| (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
                 success: false,
                 message: format!("Gagal inisialisasi uploader: {}", e),
                 error_code: Some("UPLOADER_INIT_ERROR".to_string()),
+                details: None,
             })
         ))?;
 
@@ -1020,6 +1438,7 @@ pub async fn upload_pdf_only(
 
 // Handler untuk upload cover image saja (Admin only)
 pub async fn upload_cover_only(
+    State(state): State<AppState>,
     Extension(user_role): Extension<String>,
     Extension(_user_id): Extension<Uuid>,       
     multipart: Multipart,                          
@@ -1032,23 +1451,26 @@ pub async fn upload_cover_only(
                 success: false,
                 message: "Akses admin diperlukan".to_string(),
                 error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
             })
         ));
     }
 
     // Inisialisasi uploader dan upload cover
-    let file_uploader = FileUploader::new()
+    let file_uploader = FileUploader::new(state.upload_tracker.clone())
         .map_err(|e| (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
                 success: false,
                 message: format!("Gagal inisialisasi uploader: {}", e),
                 error_code: Some("UPLOADER_INIT_ERROR".to_string()),
+                details: None,
             })
         ))?;
 
     match file_uploader.upload_cover_image(multipart, &_user_id.to_string()).await {
         Ok(file_path) => {                         
+            state.thumbnails.generate(&file_path).await;
             Ok(Json(FileUploadResponse::success(file_path, None)))
          
         }
@@ -1058,17 +1480,143 @@ pub async fn upload_cover_only(
     }
 }
 
-/// Handler untuk validasi buku sebelum order Memastikan buku exists, active, dan punya PDF
-pub async fn validate_book_for_order(
+/// Handler untuk URL upload cover langsung ke object storage (admin only)
+/// POST /api/admin/upload/cover-url
+/// Backend local mengembalikan target endpoint multipart /api/upload/cover
+/// Presigned PUT hanya membatasi content type dan ukuran; cek dimensi/aspect ratio dan thumbnail
+/// baru jalan di POST /api/admin/upload/cover-url/confirm, cover_path jangan disimpan sebelum confirm sukses
+pub async fn create_cover_upload_url(
     State(state): State<AppState>,
-    Path(book_id): Path<Uuid>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
-    // Ambil data buku
-    match BookRepository::get_book_by_id(&state.db, book_id).await {
-        Ok(book_with_categories) => {
-            let book = &book_with_categories.book;
-            
-            // Validasi buku bisa diorder
+    Extension(user_role): Extension<String>,
+    Json(request): Json<CoverUploadUrlRequest>,
+) -> Result<Json<ApiResponse<CoverUploadTarget>>, (StatusCode, Json<ErrorResponse>)> {
+    if user_role != "admin" {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                success: false,
+                message: "Akses admin diperlukan".to_string(),
+                error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    let target = state.cover_uploads
+        .cover_target(&request.content_type, request.content_length, chrono::Utc::now())
+        .map_err(|e| {
+            let (message, code) = match e {
+                CoverUploadError::InvalidContentType => (
+                    "content_type harus image/jpeg, image/png, atau image/webp".to_string(),
+                    "INVALID_IMAGE_TYPE",
+                ),
+                CoverUploadError::InvalidSize => (
+                    format!("content_length harus 1-{} byte", MAX_COVER_SIZE_BYTES),
+                    "FILE_TOO_LARGE",
+                ),
+                // Hanya dikembalikan fetch_cover / put_object
+                CoverUploadError::NotSupported | CoverUploadError::NotFound | CoverUploadError::Storage(_) => (
+                    "Target upload cover tidak bisa dibuat".to_string(),
+                    "COVER_UPLOAD_ERROR",
+                ),
+            };
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    success: false,
+                    message,
+                    error_code: Some(code.to_string()),
+                    details: None,
+                })
+            )
+        })?;
+
+    Ok(Json(ApiResponse::ok("URL upload cover dibuat", target)))
+}
+
+/// Handler konfirmasi cover yang di-upload lewat presigned URL (admin only)
+/// POST /api/admin/upload/cover-url/confirm
+/// HEAD object untuk cek content type + ukuran, download lalu cek dimensi/aspect ratio seperti
+/// upload multipart; cover tidak valid dihapus dari bucket, cover valid dibuatkan thumbnail di background
+pub async fn confirm_cover_upload(
+    State(state): State<AppState>,
+    Extension(user_role): Extension<String>,
+    Json(request): Json<ConfirmCoverUploadRequest>,
+) -> Result<Json<ApiResponse<ConfirmedCoverUpload>>, (StatusCode, Json<ErrorResponse>)> {
+    let error = |status: StatusCode, message: String, code: &str| (
+        status,
+        Json(ErrorResponse {
+            success: false,
+            message,
+            error_code: Some(code.to_string()),
+            details: None,
+        })
+    );
+
+    if user_role != "admin" {
+        return Err(error(StatusCode::FORBIDDEN, "Akses admin diperlukan".to_string(), "INSUFFICIENT_PRIVILEGES"));
+    }
+
+    let object_key = request.object_key.trim();
+    let fetched = state.cover_uploads
+        .fetch_cover(&state.http_client, object_key, chrono::Utc::now())
+        .await;
+    let (content_type, data, cover_path) = match fetched {
+        Ok(cover) => cover,
+        Err(CoverUploadError::NotSupported) => return Err(error(
+            StatusCode::BAD_REQUEST,
+            "Storage local tidak memakai presigned URL, upload cover lewat /api/upload/cover".to_string(),
+            "PRESIGNED_UPLOAD_DISABLED",
+        )),
+        Err(CoverUploadError::NotFound) => return Err(error(
+            StatusCode::NOT_FOUND,
+            "Object cover tidak ditemukan".to_string(),
+            "COVER_NOT_FOUND",
+        )),
+        Err(CoverUploadError::Storage(e)) => {
+            tracing::error!("Gagal membaca object cover {}: {}", object_key, e);
+            return Err(error(StatusCode::BAD_GATEWAY, "Object storage tidak bisa diakses".to_string(), "STORAGE_ERROR"));
+        }
+        Err(invalid) => {
+            state.cover_uploads.delete_object(&state.http_client, object_key, chrono::Utc::now()).await;
+            let (message, code) = match invalid {
+                CoverUploadError::InvalidContentType => ("Content type cover tidak sesuai ekstensi object".to_string(), "INVALID_IMAGE_TYPE"),
+                _ => (format!("Ukuran cover harus 1-{} byte", MAX_COVER_SIZE_BYTES), "FILE_TOO_LARGE"),
+            };
+            return Err(error(StatusCode::BAD_REQUEST, message, code));
+        }
+    };
+
+    if let Err(rejected) = FileUploader::check_cover_dimensions(&data) {
+        state.cover_uploads.delete_object(&state.http_client, object_key, chrono::Utc::now()).await;
+        return Err(rejected);
+    }
+
+    // Thumbnail ditulis ke bucket dengan naming convention yang sama ({key}_w{lebar}.{ext})
+    let (thumbnails, cover_uploads, client) = (state.thumbnails.clone(), state.cover_uploads.clone(), state.http_client.clone());
+    let key = object_key.to_string();
+    tokio::spawn(async move {
+        for (thumbnail_key, bytes) in thumbnails.render(&key, data).await {
+            if let Err(e) = cover_uploads.put_object(&client, &thumbnail_key, &content_type, bytes, chrono::Utc::now()).await {
+                tracing::warn!("Gagal upload thumbnail {}: {:?}", thumbnail_key, e);
+            }
+        }
+    });
+
+    Ok(Json(ApiResponse::ok("Cover valid", ConfirmedCoverUpload { cover_path })))
+}
+
+/// Handler untuk validasi buku sebelum order Memastikan buku exists, active, dan punya PDF
+pub async fn validate_book_for_order(
+    State(state): State<AppState>,
+    Path(book_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    // Ambil data buku
+    match BookRepository::get_book_by_id(&state.db, book_id).await {
+        Ok(book_with_categories) => {
+            let book = &book_with_categories.book;
+            
+            // Validasi buku bisa diorder
             if !book.is_active {
                 return Err((
                     StatusCode::BAD_REQUEST,
@@ -1076,6 +1624,7 @@ pub async fn validate_book_for_order(
                         success: false,
                         message: "Buku tidak tersedia untuk pembelian".to_string(),
                         error_code: Some("BOOK_INACTIVE".to_string()),
+                        details: None,
                     })
                 ));
             }
@@ -1087,6 +1636,7 @@ pub async fn validate_book_for_order(
                         success: false,
                         message: "File PDF belum tersedia".to_string(),
                         error_code: Some("PDF_NOT_AVAILABLE".to_string()),
+                        details: None,
                     })
                 ));
             }
@@ -1111,18 +1661,12 @@ pub async fn validate_book_for_order(
                     success: false,
                     message: "Buku tidak ditemukan".to_string(),
                     error_code: Some("BOOK_NOT_FOUND".to_string()),
+                    details: None,
                 })
             ))
         }
         Err(e) => {
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    success: false,
-                    message: format!("Error validasi buku: {}", e),
-                    error_code: Some("VALIDATION_ERROR".to_string()),
-                })
-            ))
+            Err(db_error(&e, format!("Error validasi buku: {}", e), "VALIDATION_ERROR"))
         }
     }
 }
@@ -1130,9 +1674,25 @@ pub async fn validate_book_for_order(
 /// Handler untuk webhook setelah payment success Update download count dan catat transaksi
 pub async fn handle_payment_success_webhook(
     State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<std::net::SocketAddr>,
     headers: axum::http::HeaderMap,
     Json(payload): Json<serde_json::Value>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    // Allowlist IP sumber (opsional) dicek sebelum signature
+    let source_ip = state.client_ip.resolve(peer.ip(), &headers);
+    if !state.webhook_sources.is_allowed(source_ip) {
+        tracing::warn!("Webhook payment ditolak dari IP tidak terdaftar: {}", source_ip);
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                success: false,
+                message: "Sumber webhook tidak diizinkan".to_string(),
+                error_code: Some("WEBHOOK_SOURCE_FORBIDDEN".to_string()),
+                details: None,
+            })
+        ));
+    }
+
     // Validasi webhook signature untuk security
     let webhook_secret = env::var("WEBHOOK_SECRET").unwrap_or_else(|_| "default_secret".to_string());
     
@@ -1146,6 +1706,7 @@ pub async fn handle_payment_success_webhook(
                 success: false,
                 message: "Missing webhook signature".to_string(),
                 error_code: Some("MISSING_SIGNATURE".to_string()),
+                details: None,
             })
         ))?;
     
@@ -1159,6 +1720,7 @@ pub async fn handle_payment_success_webhook(
                 success: false,
                 message: "Invalid webhook secret".to_string(),
                 error_code: Some("INVALID_SECRET".to_string()),
+                details: None,
             })
         ))?;
     
@@ -1174,6 +1736,7 @@ pub async fn handle_payment_success_webhook(
                 success: false,
                 message: "Invalid webhook signature".to_string(),
                 error_code: Some("INVALID_SIGNATURE".to_string()),
+                details: None,
             })
         ));
     }
@@ -1188,40 +1751,23 @@ pub async fn handle_payment_success_webhook(
                 success: false,
                 message: "Invalid book_id dalam payload".to_string(),
                 error_code: Some("INVALID_PAYLOAD".to_string()),
+                details: None,
             })
         ))?;
     
     let order_id = payload.get("order_id")
-        .and_then(|id| id.as_str())
-        .unwrap_or("unknown");
+        .and_then(|id| id.as_str());
     
     let user_id = payload.get("user_id")
         .and_then(|id| id.as_str())
         .and_then(|id| Uuid::parse_str(id).ok());
     
-    // Increment download counter
-    if let Err(e) = BookRepository::increment_download_count(&state.db, book_id).await {
-        tracing::error!("Gagal update download count untuk book {}: {}", book_id, e);
-    }
-    
-    // Log transaksi untuk audit trail
-    if let Some(uid) = user_id {
-        sqlx::query!(
-            r#"
-            INSERT INTO audit_logs (action, resource_type, resource_id, user_id, details)
-            VALUES ('BOOK_PURCHASED', 'book', $1, $2, $3)
-            "#,
-            book_id,
-            uid,
-            serde_json::json!({
-                "order_id": order_id,
-                "timestamp": chrono::Utc::now()
-            })
-        )
-        .execute(&state.db)
-        .await
-        .ok(); 
+    // Increment download counter + audit trail (idempotent per order)
+    if let Err(e) = BookRepository::apply_purchase_side_effects(&state.db, book_id, user_id, order_id, "webhook", None).await {
+        tracing::error!("Gagal memproses side effect pembayaran untuk book {}: {}", book_id, e);
     }
+
+    let order_id = order_id.unwrap_or("unknown");
     
     tracing::info!("Payment webhook processed: book={}, order={}", book_id, order_id);
     
@@ -1233,6 +1779,196 @@ pub async fn handle_payment_success_webhook(
     })))
 }
 
+/// Handler admin untuk replay side effect payment-success yang gagal (webhook terlewat)
+pub async fn replay_payment_webhook(
+    State(state): State<AppState>,
+    Extension(user_role): Extension<String>,
+    Extension(admin_id): Extension<Uuid>,
+    Json(request): Json<WebhookReplayRequest>,
+) -> Result<Json<ApiResponse<WebhookReplayResult>>, (StatusCode, Json<ErrorResponse>)> {
+    if user_role != "admin" {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                success: false,
+                message: "Akses admin diperlukan".to_string(),
+                error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    if let Err(errors) = request.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                success: false,
+                message: format!("Error validasi: {:?}", errors),
+                error_code: Some("VALIDATION_ERROR".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    match BookRepository::get_book_by_id_any_status(&state.db, request.book_id).await {
+        Ok(_) => {}
+        Err(DatabaseError::BookNotFound) => return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                success: false,
+                message: "Book tidak ditemukan".to_string(),
+                error_code: Some("BOOK_NOT_FOUND".to_string()),
+                details: None,
+            })
+        )),
+        Err(e) => return Err(db_error(&e, format!("Gagal mengambil book: {}", e), "DATABASE_ERROR")),
+    }
+
+    let order = BookRepository::find_replay_order(&state.db, &request.order_id).await
+        .map_err(|e| db_error(&e, format!("Gagal mengambil order: {}", e), "DATABASE_ERROR"))?;
+    let buyer_id = check_replay_order(order.as_ref(), &request)?;
+
+    // user_id = pembeli dari order, admin dicatat sebagai actor
+    let applied = BookRepository::apply_purchase_side_effects(
+        &state.db,
+        request.book_id,
+        Some(buyer_id),
+        Some(&request.order_id),
+        "admin_replay",
+        Some(admin_id),
+    )
+    .await
+    .map_err(|e| db_error(&e, format!("Gagal replay webhook: {}", e), "DATABASE_ERROR"))?;
+
+    tracing::info!(
+        "Webhook replay oleh admin {}: order={}, book={}, applied={}",
+        admin_id, request.order_id, request.book_id, applied
+    );
+
+    let message = if applied {
+        "Side effect pembayaran berhasil diproses ulang"
+    } else {
+        "Order sudah pernah diproses, tidak ada perubahan"
+    };
+
+    Ok(Json(ApiResponse::ok(message, WebhookReplayResult {
+        applied,
+        book_id: request.book_id,
+        order_id: request.order_id,
+    })))
+}
+
+/// Replay hanya untuk order paid milik buku (dan user, jika diisi) di request, return id pembeli
+fn check_replay_order(
+    order: Option<&ReplayOrder>,
+    request: &WebhookReplayRequest,
+) -> Result<Uuid, (StatusCode, Json<ErrorResponse>)> {
+    let reject = |status: StatusCode, message: &str, code: &str| (
+        status,
+        Json(ErrorResponse {
+            success: false,
+            message: message.to_string(),
+            error_code: Some(code.to_string()),
+            details: None,
+        })
+    );
+
+    let Some(order) = order else {
+        return Err(reject(StatusCode::NOT_FOUND, "Order tidak ditemukan", "ORDER_NOT_FOUND"));
+    };
+    if order.status != "paid" {
+        return Err(reject(StatusCode::CONFLICT, "Order belum dibayar, side effect tidak bisa di-replay", "ORDER_NOT_PAID"));
+    }
+    if order.book_id != Some(request.book_id) {
+        return Err(reject(StatusCode::BAD_REQUEST, "book_id tidak sesuai dengan order", "ORDER_MISMATCH"));
+    }
+
+    match (order.user_id, request.user_id) {
+        (Some(buyer), Some(requested)) if buyer != requested => {
+            Err(reject(StatusCode::BAD_REQUEST, "user_id tidak sesuai dengan order", "ORDER_MISMATCH"))
+        }
+        (Some(buyer), _) => Ok(buyer),
+        (None, _) => Err(reject(StatusCode::CONFLICT, "Order tidak memiliki user", "ORDER_MISMATCH")),
+    }
+}
+
+/// Handler untuk hitung ulang stats denormalisasi (helpful_count, download_count) di background
+/// POST /api/admin/books/recompute-stats?book_id=
+pub async fn recompute_book_stats(
+    State(state): State<AppState>,
+    Extension(user_role): Extension<String>,
+    Extension(admin_id): Extension<Uuid>,
+    Query(params): Query<RecomputeStatsParams>,
+) -> Result<(StatusCode, Json<ApiResponse<RecomputeJob>>), (StatusCode, Json<ErrorResponse>)> {
+    if user_role != "admin" {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                success: false,
+                message: "Akses admin diperlukan".to_string(),
+                error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    if let Some(book_id) = params.book_id {
+        match BookRepository::get_book_by_id_any_status(&state.db, book_id).await {
+            Ok(_) => {}
+            Err(DatabaseError::BookNotFound) => return Err((
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    success: false,
+                    message: "Book tidak ditemukan".to_string(),
+                    error_code: Some("BOOK_NOT_FOUND".to_string()),
+                    details: None,
+                })
+            )),
+            Err(e) => return Err(db_error(&e, format!("Gagal mengambil book: {}", e), "DATABASE_ERROR")),
+        }
+    }
+
+    match StatsRecomputer::start(&state, params.book_id, admin_id).await {
+        Ok(job) => {
+            tracing::info!("Recompute stats {} dimulai oleh admin {} (book={:?})", job.job_id, admin_id, job.book_id);
+            Ok((StatusCode::ACCEPTED, Json(ApiResponse::ok("Recompute stats dimulai", job))))
+        }
+        Err(running) => Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                success: false,
+                message: format!("Recompute stats {} masih berjalan ({}/{} buku)", running.job_id, running.processed_books, running.total_books),
+                error_code: Some("RECOMPUTE_IN_PROGRESS".to_string()),
+                details: None,
+            })
+        )),
+    }
+}
+
+/// Handler untuk progress job recompute stats terakhir
+/// GET /api/admin/books/recompute-stats
+pub async fn get_recompute_stats_status(
+    State(state): State<AppState>,
+    Extension(user_role): Extension<String>,
+) -> Result<Json<ApiResponse<Option<RecomputeJob>>>, (StatusCode, Json<ErrorResponse>)> {
+    if user_role != "admin" {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                success: false,
+                message: "Akses admin diperlukan".to_string(),
+                error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    let job = state.stats_recompute.current().await;
+    let message = if job.is_some() { "Status recompute stats" } else { "Belum ada job recompute stats" };
+
+    Ok(Json(ApiResponse::ok(message, job)))
+}
+
 /// Handler untuk mendapatkan stock info 
 pub async fn get_book_stock(
     State(state): State<AppState>,
@@ -1270,6 +2006,7 @@ pub async fn get_book_stock(
                     success: false,
                     message: "Buku tidak ditemukan".to_string(),
                     error_code: Some("BOOK_NOT_FOUND".to_string()),
+                    details: None,
                 })
             ))
         }
@@ -1292,7 +2029,7 @@ pub async fn get_my_library(
             
             let books_with_fixed_urls = books.into_iter().map(|mut pb| {
                 if let Some(ref cover_path) = pb.book.cover_path {
-                    pb.book.cover_path = Some(format!("{}{}", base_url, cover_path));
+                    pb.book.cover_path = Some(public_cover_url(&base_url, cover_path));
                 }
                 pb
             }).collect();
@@ -1302,14 +2039,149 @@ pub async fn get_my_library(
         }
         Err(e) => {
             tracing::error!("Failed to fetch library for user {}: {}", user_id, e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
+            Err(db_error(&e, format!("Gagal mengambil library: {}", e), "LIBRARY_ERROR"))
+        }
+    }
+}
+
+/// Handler untuk daftar review yang ditulis user login
+/// GET /api/books/my-reviews?page=&limit=
+pub async fn get_my_reviews(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Query(params): Query<PageParams>,
+) -> Result<Json<UserReviewsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let page = params.page.unwrap_or(1).max(1);
+    let limit = params.limit.unwrap_or(10).clamp(1, 50);
+
+    match BookRepository::get_user_reviews(&state.db, user_id, page, limit).await {
+        Ok((reviews, total)) => {
+            let base_url = env::var("BASE_URL")
+                .unwrap_or_else(|_| "http://localhost:3002".to_string());
+
+            let reviews = reviews.into_iter().map(|mut review| {
+                review.book_cover_url = review.book_cover_url.map(|path| format!("{}{}", base_url, path));
+                review
+            }).collect();
+
+            Ok(Json(ApiResponse::ok("Review user berhasil diambil", reviews)
+                .with_meta(PaginationMeta::new(page, limit, total))))
+        }
+        Err(e) => {
+            tracing::error!("Failed to fetch reviews for user {}: {}", user_id, e);
+            Err(db_error(&e, format!("Gagal mengambil review: {}", e), "REVIEW_ERROR"))
+        }
+    }
+}
+
+// ========================= READING PROGRESS HANDLERS =========================
+
+/// Pastikan user memiliki buku sebelum akses reading progress / download
+/// Jika row user_purchases belum ada, konfirmasi ke payment-service (lihat PurchaseVerifier)
+async fn ensure_book_owned(
+    state: &AppState,
+    user_id: Uuid,
+    book_id: Uuid,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    match BookRepository::check_user_purchased_book(&state.db, user_id, book_id).await {
+        Ok(true) => Ok(()),
+        Ok(false) if state.purchase_verifier
+            .confirm(&state.http_client, user_id, book_id)
+            .await => Ok(()),
+        Ok(false) => Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                success: false,
+                message: "Anda belum membeli buku ini".to_string(),
+                error_code: Some("NOT_PURCHASED".to_string()),
+                details: None,
+            })
+        )),
+        Err(e) => {
+            tracing::error!("Failed to check purchase: {}", e);
+            Err(db_error(&e, "Gagal verifikasi pembelian".to_string(), "DATABASE_ERROR"))
+        }
+    }
+}
+
+/// Handler untuk mengambil posisi baca terakhir
+/// GET /api/books/{id}/progress
+pub async fn get_reading_progress(
+    State(state): State<AppState>,
+    Path(book_id): Path<Uuid>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<ReadingProgressResponse>, (StatusCode, Json<ErrorResponse>)> {
+    ensure_book_owned(&state, user_id, book_id).await?;
+
+    match BookRepository::get_reading_progress(&state.db, user_id, book_id).await {
+        Ok(progress) => Ok(Json(ReadingProgressResponse::success(progress, "Reading progress berhasil diambil"))),
+        Err(e) => {
+            tracing::error!("Failed to fetch reading progress for {}: {}", book_id, e);
+            Err(db_error(&e, format!("Gagal mengambil reading progress: {}", e), "READING_PROGRESS_ERROR"))
+        }
+    }
+}
+
+/// Handler untuk menyimpan posisi baca
+/// PUT /api/books/{id}/progress
+pub async fn update_reading_progress(
+    State(state): State<AppState>,
+    Path(book_id): Path<Uuid>,
+    Extension(user_id): Extension<Uuid>,
+    Json(request): Json<UpdateReadingProgressRequest>,
+) -> Result<Json<ReadingProgressResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if let Err(errors) = request.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                success: false,
+                message: format!("Validation error: {:?}", errors),
+                error_code: Some("VALIDATION_ERROR".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    ensure_book_owned(&state, user_id, book_id).await?;
+
+    let total_pages = match BookRepository::get_book_by_id(&state.db, book_id).await {
+        Ok(book_with_categories) => book_with_categories.book.total_pages,
+        Err(DatabaseError::BookNotFound) => return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                success: false,
+                message: "Buku tidak ditemukan".to_string(),
+                error_code: Some("BOOK_NOT_FOUND".to_string()),
+                details: None,
+            })
+        )),
+        Err(e) => return Err(db_error(&e, format!("Error: {}", e), "DATABASE_ERROR")),
+    };
+
+    if let Some(total) = total_pages {
+        if request.current_page > total {
+            return Err((
+                StatusCode::BAD_REQUEST,
                 Json(ErrorResponse {
                     success: false,
-                    message: format!("Gagal mengambil library: {}", e),
-                    error_code: Some("LIBRARY_ERROR".to_string()),
+                    message: format!("Halaman melebihi total halaman buku ({})", total),
+                    error_code: Some("VALIDATION_ERROR".to_string()),
+                    details: None,
                 })
-            ))
+            ));
+        }
+    }
+
+    match BookRepository::upsert_reading_progress(
+        &state.db, user_id, book_id, request.current_page, request.position, total_pages,
+    ).await {
+        Ok(progress) => {
+            tracing::debug!("Reading progress user {} book {}: page {}", user_id, book_id, progress.current_page);
+            Ok(Json(ReadingProgressResponse::success(Some(progress), "Reading progress tersimpan")))
+        }
+        Err(e) => {
+            tracing::error!("Failed to save reading progress for {}: {}", book_id, e);
+            Err(db_error(&e, format!("Gagal menyimpan reading progress: {}", e), "READING_PROGRESS_ERROR"))
         }
     }
 }
@@ -1321,10 +2193,13 @@ pub async fn get_my_library(
 pub async fn get_book_preview(
     State(state): State<AppState>,
     Path(book_id): Path<Uuid>,
+    ConnectInfo(peer): ConnectInfo<std::net::SocketAddr>,
+    headers: HeaderMap,
 ) -> Result<Json<BookPreviewResponse>, (StatusCode, Json<ErrorResponse>)> {
     match BookRepository::get_book_preview(&state.db, book_id).await {
         Ok(Some(preview_data)) => {
             if preview_data.has_preview {
+                track_book_view(&state, book_id, peer.ip(), &headers, "preview");
                 let mut fixed_data = preview_data;
                 if let Some(ref preview_url) = fixed_data.preview_url {
                     let base_url = env::var("BASE_URL")
@@ -1342,65 +2217,258 @@ pub async fn get_book_preview(
                 success: false,
                 message: "Buku tidak ditemukan".to_string(),
                 error_code: Some("BOOK_NOT_FOUND".to_string()),
+                details: None,
             })
         )),
         Err(e) => {
             tracing::error!("Failed to fetch preview for book {}: {}", book_id, e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    success: false,
-                    message: format!("Gagal mengambil preview: {}", e),
-                    error_code: Some("PREVIEW_ERROR".to_string()),
-                })
-            ))
+            Err(db_error(&e, format!("Gagal mengambil preview: {}", e), "PREVIEW_ERROR"))
         }
     }
 }
 
+/// Handler untuk gambar satu halaman preview (PNG, atau WebP jika Accept mengizinkan)
+/// GET /api/books/{id}/preview/pages/{n}, n dibatasi ketat ke preview_pages
+pub async fn get_book_preview_page(
+    State(state): State<AppState>,
+    Path((book_id, page)): Path<(Uuid, u32)>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let error = |status: StatusCode, message: String, code: &str| (
+        status,
+        Json(ErrorResponse {
+            success: false,
+            message,
+            error_code: Some(code.to_string()),
+            details: None,
+        })
+    );
+
+    let preview = match BookRepository::get_book_preview(&state.db, book_id).await {
+        Ok(Some(preview)) => preview,
+        Ok(None) => return Err(error(StatusCode::NOT_FOUND, "Buku tidak ditemukan".to_string(), "BOOK_NOT_FOUND")),
+        Err(e) => {
+            tracing::error!("Failed to fetch preview for book {}: {}", book_id, e);
+            return Err(db_error(&e, format!("Gagal mengambil preview: {}", e), "PREVIEW_ERROR"));
+        }
+    };
+
+    let preview_url = match preview.preview_url.as_deref() {
+        Some(url) if preview.has_preview => url,
+        _ => return Err(error(StatusCode::NOT_FOUND, "Preview tidak tersedia untuk buku ini".to_string(), "PREVIEW_NOT_AVAILABLE")),
+    };
+
+    // Batas halaman = preview_pages, tidak pernah melebihi total halaman buku
+    let allowed_pages = preview.total_pages
+        .map_or(preview.preview_pages, |total| preview.preview_pages.min(total))
+        .max(0) as u32;
+
+    let format = PageFormat::negotiate(
+        headers.get("accept").and_then(|h| h.to_str().ok()).unwrap_or(""),
+    );
+
+    let bytes = state.preview_pages
+        .page_image(book_id, preview_url, page, allowed_pages, format)
+        .await
+        .map_err(|e| match e {
+            PageRenderError::PageOutOfRange => error(
+                StatusCode::NOT_FOUND,
+                format!("Halaman preview harus antara 1 dan {}", allowed_pages),
+                "PREVIEW_PAGE_OUT_OF_RANGE",
+            ),
+            PageRenderError::Busy => error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Render preview sedang sibuk, silakan coba lagi".to_string(),
+                "PREVIEW_RENDER_BUSY",
+            ),
+            PageRenderError::Failed(reason) => {
+                tracing::error!("Failed to render preview page {} for book {}: {}", page, book_id, reason);
+                error(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Gagal merender halaman preview".to_string(),
+                    "PREVIEW_RENDER_FAILED",
+                )
+            }
+        })?;
+
+    let mut response = bytes.into_response();
+    let response_headers = response.headers_mut();
+    response_headers.insert("content-type", format.mime().parse().unwrap());
+    response_headers.insert("cache-control", "public, max-age=86400".parse().unwrap());
+    response_headers.insert("vary", "accept".parse().unwrap());
+
+    Ok(response)
+}
+
 // ========================= RELATED BOOKS HANDLERS =========================
 
 /// Handler untuk mendapatkan buku terkait
-/// GET /api/books/{id}/related
+/// GET /api/books/{id}/related?page=&limit=&sort=popular|relevance
 pub async fn get_related_books(
     State(state): State<AppState>,
     Path(book_id): Path<Uuid>,
-    Query(params): Query<std::collections::HashMap<String, String>>,
+    Query(params): Query<RelatedBooksParams>,
 ) -> Result<Json<RelatedBooksResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let limit = params.get("limit")
-        .and_then(|l| l.parse::<u32>().ok())
-        .unwrap_or(6)
-        .min(20);
+    let page = params.page.unwrap_or(1).max(1);
+    let limit = params.limit.unwrap_or(6).clamp(1, 20);
+    let by_relevance = params.sort.as_deref() == Some("relevance");
 
-    match BookRepository::get_related_books(&state.db, book_id, limit).await {
-        Ok(books) => {
+    match BookRepository::get_related_books(&state.db, book_id, page, limit, by_relevance).await {
+        Ok((mut books, total)) => {
             let base_url = env::var("BASE_URL")
                 .unwrap_or_else(|_| "http://localhost:3002".to_string());
             
-            let books_with_fixed_urls: Vec<BookWithCategories> = books.into_iter().map(|mut bwc| {
-                if let Some(ref cover_path) = bwc.book.cover_path {
-                    bwc.book.cover_path = Some(format!("{}{}", base_url, cover_path));
-                }
-                bwc
-            }).collect();
+            for related in books.iter_mut() {
+                apply_cover_urls(&state, &mut related.book, &base_url).await;
+            }
             
-            tracing::info!("Related books for {} fetched: {} books", book_id, books_with_fixed_urls.len());
+            tracing::info!("Related books for {} fetched: {} books", book_id, books.len());
             Ok(Json(RelatedBooksResponse::success(
-                books_with_fixed_urls,
-                "same_category".to_string()
+                books,
+                "same_category".to_string(),
+                book_id,
+                if by_relevance { "relevance" } else { "popular" }.to_string(),
+                PaginationMeta::new(page, limit, total),
             )))
         }
         Err(e) => {
             tracing::error!("Failed to fetch related books for {}: {}", book_id, e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    success: false,
-                    message: format!("Gagal mengambil related books: {}", e),
-                    error_code: Some("RELATED_BOOKS_ERROR".to_string()),
-                })
-            ))
+            Err(db_error(&e, format!("Gagal mengambil related books: {}", e), "RELATED_BOOKS_ERROR"))
+        }
+    }
+}
+
+/// Handler untuk featured books hasil kurasi admin
+/// GET /api/books/featured
+pub async fn get_featured_books(
+    State(state): State<AppState>,
+    Query(params): Query<FeaturedBooksParams>,
+) -> Result<Json<FeaturedBooksResponse>, (StatusCode, Json<ErrorResponse>)> {
+    featured_books_response(&state, params.limit.unwrap_or(12).clamp(1, 50)).await
+}
+
+/// Featured books dengan cover URL, dipakai list publik dan response reorder admin
+async fn featured_books_response(
+    state: &AppState,
+    limit: u32,
+) -> Result<Json<FeaturedBooksResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match BookRepository::get_featured_books(&state.db, limit).await {
+        Ok(mut books) => {
+            let base_url = env::var("BASE_URL")
+                .unwrap_or_else(|_| "http://localhost:3002".to_string());
+
+            for featured in books.iter_mut() {
+                apply_cover_urls(state, &mut featured.book, &base_url).await;
+            }
+
+            let count = books.len();
+            Ok(Json(ApiResponse::ok("Featured books berhasil diambil", books).with_meta(CountMeta { count })))
+        }
+        Err(e) => {
+            tracing::error!("Failed to fetch featured books: {}", e);
+            Err(db_error(&e, format!("Gagal mengambil featured books: {}", e), "FEATURED_BOOKS_ERROR"))
+        }
+    }
+}
+
+/// Handler set/unset featured satu buku (Admin only)
+/// PUT /api/admin/books/{id}/featured
+pub async fn set_featured_book(
+    State(state): State<AppState>,
+    Extension(user_role): Extension<String>,
+    Extension(user_id): Extension<Uuid>,
+    Path(book_id): Path<Uuid>,
+    Json(request): Json<SetFeaturedBookRequest>,
+) -> Result<Json<ApiResponse<FeaturedStatus>>, (StatusCode, Json<ErrorResponse>)> {
+    if user_role != "admin" {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                success: false,
+                message: "Akses admin diperlukan".to_string(),
+                error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    if let Err(errors) = request.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                success: false,
+                message: format!("Validation error: {:?}", errors),
+                error_code: Some("VALIDATION_ERROR".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    match BookRepository::set_book_featured(&state.db, book_id, request.featured, request.featured_order, user_id).await {
+        Ok(status) => {
+            tracing::info!("Featured buku {} diubah: {} oleh {}", book_id, status.is_featured, user_id);
+            let message = if status.is_featured { "Buku ditandai featured" } else { "Buku dihapus dari featured" };
+            Ok(Json(ApiResponse::ok(message, status)))
+        }
+        Err(e) => Err(book_categories_error(e)),
+    }
+}
+
+/// Handler atur ulang urutan featured books (Admin only)
+/// PUT /api/admin/books/featured/order
+pub async fn reorder_featured_books(
+    State(state): State<AppState>,
+    Extension(user_role): Extension<String>,
+    Extension(user_id): Extension<Uuid>,
+    Json(request): Json<ReorderFeaturedBooksRequest>,
+) -> Result<Json<FeaturedBooksResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if user_role != "admin" {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                success: false,
+                message: "Akses admin diperlukan".to_string(),
+                error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    if let Err(errors) = request.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                success: false,
+                message: format!("Validation error: {:?}", errors),
+                error_code: Some("VALIDATION_ERROR".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    // Duplikat dibuang, posisi pertama yang dipakai
+    let mut seen = std::collections::HashSet::new();
+    let book_ids: Vec<Uuid> = request.book_ids.into_iter().filter(|id| seen.insert(*id)).collect();
+
+    match BookRepository::reorder_featured_books(&state.db, &book_ids, user_id).await {
+        Ok(not_featured) if !not_featured.is_empty() => Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                success: false,
+                message: format!(
+                    "Buku berikut bukan featured: {}",
+                    not_featured.iter().map(Uuid::to_string).collect::<Vec<_>>().join(", ")
+                ),
+                error_code: Some("BOOK_NOT_FEATURED".to_string()),
+                details: None,
+            })
+        )),
+        Ok(_) => {
+            tracing::info!("Urutan featured books diubah oleh {}: {} buku", user_id, book_ids.len());
+            // Baca ulang sebanyak batas request supaya semua buku yang baru diurutkan ikut kembali
+            featured_books_response(&state, MAX_FEATURED_REORDER as u32).await
         }
+        Err(e) => Err(book_categories_error(e)),
     }
 }
 
@@ -1419,7 +2487,8 @@ pub async fn get_book_reviews(
     match BookRepository::get_book_by_id(&state.db, book_id).await {
         Ok(_) => {
             match BookRepository::get_book_reviews(&state.db, book_id, user_id).await {
-                Ok((reviews, stats)) => {
+                Ok((mut reviews, stats)) => {
+                    user_directory::enrich_reviews(&state.user_directory, &mut reviews).await;
                     tracing::info!(
                         "Reviews for book {} fetched: {} reviews, avg: {:.1}",
                         book_id, reviews.len(), stats.average_rating
@@ -1428,14 +2497,7 @@ pub async fn get_book_reviews(
                 }
                 Err(e) => {
                     tracing::error!("Failed to fetch reviews for {}: {}", book_id, e);
-                    Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(ErrorResponse {
-                            success: false,
-                            message: format!("Gagal mengambil reviews: {}", e),
-                            error_code: Some("REVIEWS_ERROR".to_string()),
-                        })
-                    ))
+                    Err(db_error(&e, format!("Gagal mengambil reviews: {}", e), "REVIEWS_ERROR"))
                 }
             }
         }
@@ -1443,19 +2505,337 @@ pub async fn get_book_reviews(
             StatusCode::NOT_FOUND,
             Json(ErrorResponse {
                 success: false,
-                message: "Buku tidak ditemukan".to_string(),
-                error_code: Some("BOOK_NOT_FOUND".to_string()),
+                message: "Buku tidak ditemukan".to_string(),
+                error_code: Some("BOOK_NOT_FOUND".to_string()),
+                details: None,
+            })
+        )),
+        Err(e) => Err(db_error(&e, format!("Error: {}", e), "DATABASE_ERROR"))
+    }
+}
+
+// ========================= INTERNAL HANDLERS =========================
+
+/// Helper untuk verifikasi X-Service-Key pada internal endpoint
+fn is_valid_service_key(headers: &HeaderMap) -> bool {
+    let service_key = headers
+        .get("X-Service-Key")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("");
+
+    let expected_key = env::var("INTERNAL_SERVICE_KEY")
+        .unwrap_or_else(|_| "internal-service-key-secret".to_string());
+
+    service_key == expected_key
+}
+
+/// TTL cache detail buku internal (detik)
+fn internal_book_cache_ttl() -> Duration {
+    Duration::from_secs(
+        env::var("INTERNAL_BOOK_CACHE_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30)
+    )
+}
+
+/// Handler detail buku lengkap untuk service internal (payment-service)
+/// Termasuk buku inactive supaya caller bisa memutuskan sendiri
+/// GET /api/internal/books/{id}
+pub async fn get_book_internal(
+    State(state): State<AppState>,
+    Path(book_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    if !is_valid_service_key(&headers) {
+        tracing::warn!("Invalid service key untuk internal book lookup {}", book_id);
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                success: false,
+                message: "Unauthorized service call".to_string(),
+                error_code: Some("INVALID_SERVICE_KEY".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    let ttl = internal_book_cache_ttl();
+
+    if let Some((cached_at, data)) = state.internal_book_cache.read().await.get(&book_id) {
+        if cached_at.elapsed() < ttl {
+            return Ok(Json(data.clone()));
+        }
+    }
+
+    let book_with_categories = BookRepository::get_book_by_id_any_status(&state.db, book_id).await
+        .map_err(|e| match e {
+            DatabaseError::BookNotFound => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    success: false,
+                    message: "Buku tidak ditemukan".to_string(),
+                    error_code: Some("BOOK_NOT_FOUND".to_string()),
+                    details: None,
+                })
+            ),
+            e => db_error(&e, format!("Error: {}", e), "DATABASE_ERROR"),
+        })?;
+
+    let data = serde_json::json!({
+        "success": true,
+        "data": {
+            "book": book_with_categories.book,
+            "categories": book_with_categories.categories,
+            "pdf_available": book_with_categories.book.pdf_path.is_some(),
+        }
+    });
+
+    cache_insert(&mut *state.internal_book_cache.write().await, book_id, data.clone(), ttl, book_cache_max_entries());
+
+    Ok(Json(data))
+}
+
+/// Buang konfirmasi kepemilikan yang di-cache setelah akses dicabut / order di-refund (payment-service)
+/// POST /api/internal/purchases/{user_id}/{book_id}/evict
+pub async fn evict_purchase_confirmation(
+    State(state): State<AppState>,
+    Path((user_id, book_id)): Path<(Uuid, Uuid)>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    if !is_valid_service_key(&headers) {
+        tracing::warn!("Invalid service key untuk evict konfirmasi pembelian user {} buku {}", user_id, book_id);
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                success: false,
+                message: "Unauthorized service call".to_string(),
+                error_code: Some("INVALID_SERVICE_KEY".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    let evicted = state.purchase_verifier.evict(user_id, book_id).await;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "Cache konfirmasi pembelian dibersihkan",
+        "data": { "user_id": user_id, "book_id": book_id, "evicted": evicted },
+    })))
+}
+
+/// Batas entry cache per buku (rating, popularitas, internal), BOOK_CACHE_MAX_ENTRIES (default 10000)
+fn book_cache_max_entries() -> usize {
+    env::var("BOOK_CACHE_MAX_ENTRIES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(10_000)
+        .max(1)
+}
+
+/// Simpan ke cache per buku: entry expired dibuang, jika masih penuh entry paling lama ikut dibuang
+fn cache_insert<V>(
+    cache: &mut std::collections::HashMap<Uuid, (std::time::Instant, V)>,
+    book_id: Uuid,
+    value: V,
+    ttl: Duration,
+    max_entries: usize,
+) {
+    cache.retain(|_, (cached_at, _)| cached_at.elapsed() < ttl);
+
+    while cache.len() >= max_entries && !cache.contains_key(&book_id) {
+        let Some(oldest) = cache.iter().min_by_key(|(_, (cached_at, _))| *cached_at).map(|(id, _)| *id) else {
+            break;
+        };
+        cache.remove(&oldest);
+    }
+
+    cache.insert(book_id, (std::time::Instant::now(), value));
+}
+
+/// TTL cache rating summary per buku (detik)
+fn rating_cache_ttl() -> Duration {
+    Duration::from_secs(
+        env::var("RATING_CACHE_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(60)
+    )
+}
+
+/// Handler untuk rating summary buku: average, total, distribusi bintang (public)
+/// GET /api/books/{id}/rating
+pub async fn get_book_rating(
+    State(state): State<AppState>,
+    Path(book_id): Path<Uuid>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let ttl = rating_cache_ttl();
+
+    let cached = state.rating_cache.read().await
+        .get(&book_id)
+        .filter(|(cached_at, _)| cached_at.elapsed() < ttl)
+        .map(|(_, stats)| stats.clone());
+
+    let stats = match cached {
+        Some(stats) => stats,
+        None => {
+            match BookRepository::get_book_by_id(&state.db, book_id).await {
+                Ok(_) => {}
+                Err(DatabaseError::BookNotFound) => return Err((
+                    StatusCode::NOT_FOUND,
+                    Json(ErrorResponse {
+                        success: false,
+                        message: "Buku tidak ditemukan".to_string(),
+                        error_code: Some("BOOK_NOT_FOUND".to_string()),
+                        details: None,
+                    })
+                )),
+                Err(e) => return Err(db_error(&e, format!("Error: {}", e), "DATABASE_ERROR")),
+            }
+
+            let stats = BookRepository::calculate_review_stats(&state.db, book_id).await
+                .map_err(|e| {
+                    tracing::error!("Failed to fetch rating for {}: {}", book_id, e);
+                    db_error(&e, format!("Gagal mengambil rating: {}", e), "RATING_ERROR")
+                })?;
+
+            cache_insert(&mut *state.rating_cache.write().await, book_id, stats.clone(), ttl, book_cache_max_entries());
+            stats
+        }
+    };
+
+    let mut response = Json(BookRatingResponse::success(stats)).into_response();
+    response.headers_mut().insert(
+        "cache-control",
+        format!("public, max-age={}", ttl.as_secs()).parse().unwrap(),
+    );
+
+    Ok(response)
+}
+
+/// Window pembelian untuk badge popularitas ("... bulan ini")
+const POPULARITY_WINDOW_DAYS: i64 = 30;
+
+/// TTL cache popularitas per buku (detik), angka kasar jadi boleh lebih lama dari rating
+fn popularity_cache_ttl() -> Duration {
+    Duration::from_secs(
+        env::var("POPULARITY_CACHE_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(900)
+    )
+}
+
+/// Handler untuk badge popularitas buku: bucket download dan arah tren (public)
+/// Angka pasti penjualan/revenue tetap hanya di analytics admin
+/// GET /api/books/{id}/popularity
+pub async fn get_book_popularity(
+    State(state): State<AppState>,
+    Path(book_id): Path<Uuid>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let ttl = popularity_cache_ttl();
+
+    let cached = state.popularity_cache.read().await
+        .get(&book_id)
+        .filter(|(cached_at, _)| cached_at.elapsed() < ttl)
+        .map(|(_, popularity)| popularity.clone());
+
+    let popularity = match cached {
+        Some(popularity) => popularity,
+        None => {
+            let (total, recent, previous) = BookRepository::get_popularity_counts(&state.db, book_id, POPULARITY_WINDOW_DAYS).await
+                .map_err(|e| match e {
+                    DatabaseError::BookNotFound => (
+                        StatusCode::NOT_FOUND,
+                        Json(ErrorResponse {
+                            success: false,
+                            message: "Buku tidak ditemukan".to_string(),
+                            error_code: Some("BOOK_NOT_FOUND".to_string()),
+                            details: None,
+                        })
+                    ),
+                    e => {
+                        tracing::error!("Failed to fetch popularity for {}: {}", book_id, e);
+                        db_error(&e, format!("Gagal mengambil popularitas: {}", e), "POPULARITY_ERROR")
+                    }
+                })?;
+
+            let popularity = BookPopularity::from_counts(book_id, total, recent, previous, POPULARITY_WINDOW_DAYS);
+
+            cache_insert(&mut *state.popularity_cache.write().await, book_id, popularity.clone(), ttl, book_cache_max_entries());
+            popularity
+        }
+    };
+
+    let mut response = Json(BookPopularityResponse::success(popularity)).into_response();
+    response.headers_mut().insert(
+        "cache-control",
+        format!("public, max-age={}", ttl.as_secs()).parse().unwrap(),
+    );
+
+    Ok(response)
+}
+
+/// Parse daftar book ID dari request batch: semua harus UUID valid, duplikat dibuang (urutan dipertahankan)
+fn parse_batch_book_ids(raw_ids: &[String]) -> Result<Vec<Uuid>, (StatusCode, Json<ErrorResponse>)> {
+    let invalid_ids = raw_ids.iter()
+        .filter(|id| Uuid::parse_str(id.trim()).is_err())
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+
+    if !invalid_ids.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                success: false,
+                message: format!("Book ID tidak valid: {}", invalid_ids.join(", ")),
+                error_code: Some("INVALID_BOOK_ID".to_string()),
+                details: None,
             })
-        )),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
+        ));
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    Ok(raw_ids.iter()
+        .filter_map(|id| Uuid::parse_str(id.trim()).ok())
+        .filter(|id| seen.insert(*id))
+        .collect())
+}
+
+/// Handler untuk rating banyak buku sekaligus (hindari N+1 di list katalog)
+/// POST /api/books/ratings/batch
+pub async fn get_book_ratings_batch(
+    State(state): State<AppState>,
+    Json(request): Json<BatchRatingsRequest>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    if let Err(errors) = request.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
                 success: false,
-                message: format!("Error: {}", e),
-                error_code: Some("DATABASE_ERROR".to_string()),
+                message: format!("Error validasi: {:?}", errors),
+                error_code: Some("VALIDATION_ERROR".to_string()),
+                details: None,
             })
-        ))
+        ));
     }
+
+    let book_ids = parse_batch_book_ids(&request.book_ids)?;
+
+    let summaries = BookRepository::get_rating_summaries(&state.db, &book_ids).await
+        .map_err(|e| {
+            tracing::error!("Failed to fetch batch ratings: {}", e);
+            db_error(&e, format!("Gagal mengambil rating: {}", e), "RATING_ERROR")
+        })?;
+
+    let mut response = Json(BatchRatingsResponse::success(summaries)).into_response();
+    response.headers_mut().insert(
+        "cache-control",
+        format!("public, max-age={}", rating_cache_ttl().as_secs()).parse().unwrap(),
+    );
+
+    Ok(response)
 }
 
 /// Handler untuk membuat review buku
@@ -1465,7 +2845,9 @@ pub async fn create_book_review(
     Path(book_id): Path<Uuid>,
     Extension(user_id): Extension<Uuid>,
     Json(review_request): Json<CreateReviewRequest>,
-) -> Result<Json<ReviewResponse>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    check_comment_length(&review_request.comment, review_comment_max_length())?;
+
     if let Err(errors) = review_request.validate() {
         let error_msg = format!("Validation error: {:?}", errors);
         return Err((
@@ -1474,10 +2856,28 @@ pub async fn create_book_review(
                 success: false,
                 message: error_msg,
                 error_code: Some("VALIDATION_ERROR".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    if let Err(reason) = state.review_filter.check(&review_request.comment) {
+        tracing::info!("Review ditolak: user={}, book={}, alasan={}", user_id, book_id, reason);
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ErrorResponse {
+                success: false,
+                message: reason,
+                error_code: Some("REVIEW_REJECTED".to_string()),
+                details: None,
             })
         ));
     }
 
+    if let Err(rejected) = ensure_review_not_abusive(&state, user_id, book_id, &review_request.comment).await {
+        return Ok(rejected);
+    }
+
     match BookRepository::check_user_purchased_book(&state.db, user_id, book_id).await {
         Ok(true) => {
             match BookRepository::create_book_review(
@@ -1485,27 +2885,15 @@ pub async fn create_book_review(
                 review_request.rating, review_request.comment,
             ).await {
                 Ok(review) => {
-                    let user_info = sqlx::query!(
-                        "SELECT full_name, email FROM users WHERE id = $1",
-                        user_id
-                    )
-                    .fetch_one(&state.db)
-                    .await
-                    .map_err(|e| (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(ErrorResponse {
-                            success: false,
-                            message: format!("Failed to fetch user: {}", e),
-                            error_code: Some("DATABASE_ERROR".to_string()),
-                        })
-                    ))?;
+                    // Rating summary berubah, buang cache lama
+                    state.rating_cache.write().await.remove(&book_id);
 
-                    let review_with_user = BookReviewWithUser {
+                    let mut review_with_user = BookReviewWithUser {
                         id: review.id,
                         book_id: review.book_id,
                         user_id: review.user_id,
-                        user_name: user_info.full_name,
-                        user_email: user_info.email,
+                        user_name: String::new(),
+                        user_email: String::new(),
                         rating: review.rating,
                         comment: review.comment,
                         helpful_count: review.helpful_count,
@@ -1514,22 +2902,17 @@ pub async fn create_book_review(
                         can_edit: true,
                         has_voted_helpful: false,
                     };
+                    user_directory::enrich_reviews(&state.user_directory, std::slice::from_mut(&mut review_with_user))
+                        .await;
 
                     tracing::info!("Review created: user={}, book={}, rating={}", 
                         user_id, book_id, review.rating);
 
-                    Ok(Json(ReviewResponse::success(review_with_user)))
+                    Ok(Json(ReviewResponse::success(review_with_user)).into_response())
                 }
                 Err(e) => {
                     tracing::error!("Failed to create review: {}", e);
-                    Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(ErrorResponse {
-                            success: false,
-                            message: format!("Gagal membuat review: {}", e),
-                            error_code: Some("REVIEW_CREATE_ERROR".to_string()),
-                        })
-                    ))
+                    Err(db_error(&e, format!("Gagal membuat review: {}", e), "REVIEW_CREATE_ERROR"))
                 }
             }
         }
@@ -1539,22 +2922,79 @@ pub async fn create_book_review(
                 success: false,
                 message: "Anda harus membeli buku ini untuk membuat review".to_string(),
                 error_code: Some("NOT_PURCHASED".to_string()),
+                details: None,
             })
         )),
         Err(e) => {
             tracing::error!("Failed to check purchase: {}", e);
+            Err(db_error(&e, "Gagal memverifikasi pembelian".to_string(), "PURCHASE_CHECK_ERROR"))
+        }
+    }
+}
+
+/// Rate limit edit review + deteksi teks identik di banyak buku (429)
+async fn ensure_review_not_abusive(
+    state: &AppState,
+    user_id: Uuid,
+    book_id: Uuid,
+    comment: &str,
+) -> Result<(), Response> {
+    let limiter = &state.review_limiter;
+
+    if let Err(limited) = limiter.check(user_id, book_id).await {
+        tracing::warn!("Review rate limit: user={}, book={}", user_id, book_id);
+        return Err(review_rate_limited_response(limited));
+    }
+
+    if limiter.duplicate_max_books == 0 || comment.trim().chars().count() < limiter.duplicate_min_length {
+        return Ok(());
+    }
+
+    let since = chrono::Utc::now() - chrono::Duration::from_std(limiter.duplicate_window).unwrap_or_default();
+
+    match BookRepository::count_identical_user_reviews(&state.db, user_id, book_id, comment, since).await {
+        Ok(count) if count >= limiter.duplicate_max_books => {
+            tracing::warn!("Review duplikat terdeteksi: user={}, book={}, buku lain={}", user_id, book_id, count);
             Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::TOO_MANY_REQUESTS,
                 Json(ErrorResponse {
                     success: false,
-                    message: "Gagal memverifikasi pembelian".to_string(),
-                    error_code: Some("PURCHASE_CHECK_ERROR".to_string()),
+                    message: "Review dengan teks yang sama sudah dikirim ke terlalu banyak buku".to_string(),
+                    error_code: Some("REVIEW_DUPLICATE_SPAM".to_string()),
+                    details: None,
                 })
-            ))
+            ).into_response())
+        }
+        Ok(_) => Ok(()),
+        Err(e) => {
+            // Jangan blokir review hanya karena pengecekan spam gagal
+            tracing::error!("Gagal cek review duplikat: {}", e);
+            Ok(())
         }
     }
 }
 
+// helper: 429 review dengan Retry-After + X-RateLimit-* seperti rate limiter lain
+fn review_rate_limited_response(limited: ReviewRateLimited) -> Response {
+    let reset_at = chrono::Utc::now().timestamp() + limited.retry_after_secs as i64;
+
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [
+            (axum::http::header::RETRY_AFTER.as_str(), limited.retry_after_secs.to_string()),
+            ("X-RateLimit-Limit", limited.limit.to_string()),
+            ("X-RateLimit-Remaining", "0".to_string()),
+            ("X-RateLimit-Reset", reset_at.to_string()),
+        ],
+        Json(ErrorResponse {
+            success: false,
+            message: format!("Terlalu banyak perubahan review, coba lagi dalam {} detik", limited.retry_after_secs),
+            error_code: Some("REVIEW_RATE_LIMITED".to_string()),
+            details: None,
+        }),
+    ).into_response()
+}
+
 // ========================= HANDLER ADMIN ANALYTICS =========================
 
 // Handler untuk statistik buku admin dashboard
@@ -1570,6 +3010,7 @@ pub async fn get_admin_book_stats(
                 success: false,
                 message: "Akses admin diperlukan".to_string(),
                 error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
             })
         ));
     }
@@ -1584,19 +3025,13 @@ pub async fn get_admin_book_stats(
         }
         Err(e) => {
             tracing::error!("Gagal mengambil admin book stats: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    success: false,
-                    message: format!("Gagal mengambil statistik book: {}", e),
-                    error_code: Some("STATS_ERROR".to_string()),
-                })
-            ))
+            Err(db_error(&e, format!("Gagal mengambil statistik book: {}", e), "STATS_ERROR"))
         }
     }
 }
 
 // Handler untuk top books berdasarkan metrik tertentu
+// GET /api/admin/books/top?metric=&limit=&offset=&category=
 pub async fn get_top_books(
     State(state): State<AppState>,
     Extension(user_role): Extension<String>,
@@ -1610,6 +3045,7 @@ pub async fn get_top_books(
                 success: false,
                 message: "Akses admin diperlukan".to_string(),
                 error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
             })
         ));
     }
@@ -1622,28 +3058,37 @@ pub async fn get_top_books(
     let limit = params.get("limit")
         .and_then(|l| l.parse::<u32>().ok())
         .unwrap_or(10)
-        .min(50);
+        .clamp(1, 50);
+
+    let offset = params.get("offset")
+        .and_then(|o| o.parse::<u32>().ok())
+        .unwrap_or(0);
+
+    let category = params.get("category").map(|c| c.as_str());
 
     // Validasi tipe metrik
-    let valid_metrics = vec!["downloads", "sales", "revenue", "recent"];
-    if !valid_metrics.contains(&metric_type) {
+    if !TOP_BOOK_METRICS.contains(&metric_type) {
         return Err((
             StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
                 success: false,
-                message: format!("Tipe metrik tidak valid. Opsi valid: {}", valid_metrics.join(", ")),
+                message: format!("Tipe metrik tidak valid. Opsi valid: {}", TOP_BOOK_METRICS.join(", ")),
                 error_code: Some("INVALID_METRIC_TYPE".to_string()),
+                details: None,
             })
         ));
     }
 
     // Ambil data top books
-    match BookRepository::get_top_books_by_metric(&state.db, metric_type, limit).await {
-        Ok(top_books) => {
-            tracing::info!("Admin top books berhasil diambil: {} books berdasarkan {}", 
-                top_books.len(), metric_type);
+    match BookRepository::get_top_books_page(&state.db, metric_type, limit, offset, category).await {
+        Ok((top_books, total_items)) => {
+            tracing::info!("Admin top books berhasil diambil: {} books berdasarkan {} (offset {})", 
+                top_books.len(), metric_type, offset);
             
-            Ok(Json(AdminTopBooksResponse::success(top_books)))
+            Ok(Json(AdminTopBooksResponse::success(
+                top_books,
+                TopBooksPagination::new(offset, limit, total_items),
+            )))
         }
         Err(DatabaseError::InvalidMetricType) => {
             Err((
@@ -1652,21 +3097,159 @@ pub async fn get_top_books(
                     success: false,
                     message: format!("Tipe metrik tidak valid: {}", metric_type),
                     error_code: Some("INVALID_METRIC_TYPE".to_string()),
+                    details: None,
                 })
             ))
         }
         Err(e) => {
             tracing::error!("Gagal mengambil top books: {}", e);
+            Err(db_error(&e, format!("Gagal mengambil top books: {}", e), "TOP_BOOKS_ERROR"))
+        }
+    }
+}
+
+// Handler untuk preview dampak soft delete buku (tidak menghapus apa pun)
+// GET /api/admin/books/{id}/delete-impact
+pub async fn get_book_delete_impact(
+    State(state): State<AppState>,
+    Extension(user_role): Extension<String>,
+    Path(book_id): Path<Uuid>,
+) -> Result<Json<AdminDeleteImpactResponse>, (StatusCode, Json<ErrorResponse>)> {
+    // Validasi akses admin
+    if user_role != "admin" {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                success: false,
+                message: "Akses admin diperlukan".to_string(),
+                error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    match BookRepository::get_delete_impact(&state.db, book_id).await {
+        Ok(impact) => {
+            let message = if impact.pending_orders > 0 {
+                format!("Buku ini memiliki {} order pending", impact.pending_orders)
+            } else {
+                "Dampak penghapusan buku berhasil dihitung".to_string()
+            };
+            Ok(Json(ApiResponse::ok(message, impact)))
+        }
+        Err(DatabaseError::BookNotFound) => {
+            Err((
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    success: false,
+                    message: "Buku tidak ditemukan".to_string(),
+                    error_code: Some("BOOK_NOT_FOUND".to_string()),
+                    details: None,
+                })
+            ))
+        }
+        Err(e) => {
+            tracing::error!("Gagal menghitung dampak hapus buku {}: {}", book_id, e);
+            Err(db_error(&e, format!("Gagal menghitung dampak penghapusan: {}", e), "DELETE_IMPACT_ERROR"))
+        }
+    }
+}
+
+// Handler untuk statistik view dan konversi buku
+// GET /api/admin/books/{id}/views
+pub async fn get_book_views(
+    State(state): State<AppState>,
+    Extension(user_role): Extension<String>,
+    Path(book_id): Path<Uuid>,
+) -> Result<Json<AdminBookViewsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    // Validasi akses admin
+    if user_role != "admin" {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                success: false,
+                message: "Akses admin diperlukan".to_string(),
+                error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    match BookRepository::get_book_view_stats(&state.db, book_id).await {
+        Ok(stats) => Ok(Json(AdminBookViewsResponse::success(stats))),
+        Err(DatabaseError::BookNotFound) => {
             Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::NOT_FOUND,
                 Json(ErrorResponse {
                     success: false,
-                    message: format!("Gagal mengambil top books: {}", e),
-                    error_code: Some("TOP_BOOKS_ERROR".to_string()),
+                    message: "Buku tidak ditemukan".to_string(),
+                    error_code: Some("BOOK_NOT_FOUND".to_string()),
+                    details: None,
                 })
             ))
         }
+        Err(e) => {
+            tracing::error!("Gagal mengambil statistik view buku {}: {}", book_id, e);
+            Err(db_error(&e, format!("Gagal mengambil statistik view: {}", e), "BOOK_VIEWS_ERROR"))
+        }
+    }
+}
+
+// Handler untuk export semua review buku (publisher), reviewer dianonimkan
+// GET /api/admin/books/{id}/reviews/export?format=csv|json (default csv)
+pub async fn export_book_reviews(
+    State(state): State<AppState>,
+    Extension(user_role): Extension<String>,
+    Path(book_id): Path<Uuid>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    // Validasi akses admin
+    if user_role != "admin" {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                success: false,
+                message: "Akses admin diperlukan".to_string(),
+                error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
+            })
+        ));
+    }
+
+    let format = ReviewExportFormat::parse(params.get("format").map(String::as_str)).ok_or((
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse {
+            success: false,
+            message: "Parameter format harus csv atau json".to_string(),
+            error_code: Some("INVALID_EXPORT_FORMAT".to_string()),
+            details: None,
+        })
+    ))?;
+
+    match BookRepository::get_book_by_id(&state.db, book_id).await {
+        Ok(_) => {}
+        Err(DatabaseError::BookNotFound) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    success: false,
+                    message: "Buku tidak ditemukan".to_string(),
+                    error_code: Some("BOOK_NOT_FOUND".to_string()),
+                    details: None,
+                })
+            ));
+        }
+        Err(e) => return Err(db_error(&e, format!("Error: {}", e), "DATABASE_ERROR")),
     }
+
+    let mut response = Response::new(review_export::stream_reviews(state.db.clone(), book_id, format));
+    let headers = response.headers_mut();
+    headers.insert("content-type", format.content_type().parse().unwrap());
+    headers.insert("content-disposition",
+        format!("attachment; filename=\"book_{}_reviews.{}\"", book_id, format.extension()).parse().unwrap());
+    headers.insert("cache-control", "no-store".parse().unwrap());
+
+    Ok(response)
 }
 
 // Handler untuk analytics penjualan
@@ -1683,6 +3266,7 @@ pub async fn get_sales_analytics(
                 success: false,
                 message: "Akses admin diperlukan".to_string(),
                 error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
             })
         ));
     }
@@ -1693,8 +3277,11 @@ pub async fn get_sales_analytics(
         .unwrap_or(30)
         .min(365);
 
-    // Ambil sales analytics
-    match BookRepository::get_sales_analytics(&state.db, days).await {
+    // Ambil sales analytics (cache + single-flight per jumlah hari)
+    let analytics = state.sales_analytics_cache
+        .get_or_load(days.to_string(), || BookRepository::get_sales_analytics(&state.db, days))
+        .await;
+    match analytics {
         Ok(analytics) => {
             tracing::info!("Admin sales analytics berhasil diambil: {} hari data", days);
             
@@ -1702,14 +3289,7 @@ pub async fn get_sales_analytics(
         }
         Err(e) => {
             tracing::error!("Gagal mengambil sales analytics: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    success: false,
-                    message: format!("Gagal mengambil sales analytics: {}", e),
-                    error_code: Some("ANALYTICS_ERROR".to_string()),
-                })
-            ))
+            Err(db_error(&e, format!("Gagal mengambil sales analytics: {}", e), "ANALYTICS_ERROR"))
         }
     }
 }
@@ -1728,6 +3308,7 @@ pub async fn get_popular_books_chart_data(
                 success: false,
                 message: "Akses admin diperlukan".to_string(),
                 error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
             })
         ));
     }
@@ -1738,8 +3319,11 @@ pub async fn get_popular_books_chart_data(
         .unwrap_or(10)
         .min(15);
 
-    // Ambil data chart
-    match BookRepository::get_popular_books_chart_data(&state.db, limit).await {
+    // Ambil data chart (cache + single-flight per limit)
+    let chart = state.popular_books_cache
+        .get_or_load(limit.to_string(), || BookRepository::get_popular_books_chart_data(&state.db, limit))
+        .await;
+    match chart {
         Ok(chart_data) => {
             tracing::info!("Popular books chart data berhasil diambil: {} books", 
                 chart_data.labels.len());
@@ -1748,23 +3332,18 @@ pub async fn get_popular_books_chart_data(
         }
         Err(e) => {
             tracing::error!("Gagal mengambil popular books chart data: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    success: false,
-                    message: format!("Gagal mengambil chart data: {}", e),
-                    error_code: Some("CHART_DATA_ERROR".to_string()),
-                })
-            ))
+            Err(db_error(&e, format!("Gagal mengambil chart data: {}", e), "CHART_DATA_ERROR"))
         }
     }
 }
 
 // Handler untuk analytics per kategori
+// Query: from/to (YYYY-MM-DD, inklusif), limit (default 20, max 100), format=csv untuk export
 pub async fn get_category_analytics(
     State(state): State<AppState>,
     Extension(user_role): Extension<String>,
-) -> Result<Json<AdminCategoryAnalyticsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     // Validasi akses admin
     if user_role != "admin" {
         return Err((
@@ -1773,31 +3352,106 @@ pub async fn get_category_analytics(
                 success: false,
                 message: "Akses admin diperlukan".to_string(),
                 error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
             })
         ));
     }
 
-    // Ambil analytics kategori
-    match BookRepository::get_category_analytics(&state.db).await {
+    let parse_date = |key: &str| -> Result<Option<chrono::NaiveDate>, (StatusCode, Json<ErrorResponse>)> {
+        match params.get(key).filter(|v| !v.trim().is_empty()) {
+            None => Ok(None),
+            Some(value) => chrono::NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+                .map(Some)
+                .map_err(|_| (
+                    StatusCode::BAD_REQUEST,
+                    Json(ErrorResponse {
+                        success: false,
+                        message: format!("Parameter {} harus format YYYY-MM-DD", key),
+                        error_code: Some("INVALID_DATE_RANGE".to_string()),
+                        details: None,
+                    })
+                )),
+        }
+    };
+
+    let from = parse_date("from")?;
+    let to = parse_date("to")?;
+
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    success: false,
+                    message: "Parameter from tidak boleh setelah to".to_string(),
+                    error_code: Some("INVALID_DATE_RANGE".to_string()),
+                    details: None,
+                })
+            ));
+        }
+    }
+
+    let limit = params.get("limit")
+        .and_then(|l| l.parse::<i64>().ok())
+        .unwrap_or(20)
+        .clamp(1, 100);
+
+    let as_csv = params.get("format").map(|f| f.eq_ignore_ascii_case("csv")).unwrap_or(false);
+
+    // `to` inklusif: batas atas = awal hari berikutnya
+    let from_ts = from.and_then(|d| d.and_hms_opt(0, 0, 0)).map(|dt| dt.and_utc());
+    let to_ts = to
+        .and_then(|d| d.succ_opt())
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc());
+
+    // Ambil analytics kategori (cache + single-flight per range + limit, JSON dan CSV berbagi hasil)
+    let cache_key = format!("{:?}:{:?}:{}", from, to, limit);
+    let analytics = state.category_analytics_cache
+        .get_or_load(cache_key, || BookRepository::get_category_analytics(&state.db, from_ts, to_ts, limit))
+        .await;
+    match analytics {
         Ok(analytics) => {
             tracing::info!("Category analytics berhasil diambil: {} kategori", analytics.len());
-            
-            Ok(Json(AdminCategoryAnalyticsResponse::success(analytics)))
+
+            if as_csv {
+                return Ok(category_analytics_csv(&analytics));
+            }
+
+            Ok(Json(AdminCategoryAnalyticsResponse::success(analytics, from, to)).into_response())
         }
         Err(e) => {
             tracing::error!("Gagal mengambil category analytics: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    success: false,
-                    message: format!("Gagal mengambil category analytics: {}", e),
-                    error_code: Some("CATEGORY_ANALYTICS_ERROR".to_string()),
-                })
-            ))
+            Err(db_error(&e, format!("Gagal mengambil category analytics: {}", e), "CATEGORY_ANALYTICS_ERROR"))
         }
     }
 }
 
+/// Render category analytics sebagai CSV attachment
+fn category_analytics_csv(analytics: &[CategoryAnalytics]) -> Response {
+    // Escaping sama dengan export review: quote koma/quote/CR/LF, prefix ' untuk formula spreadsheet
+    let mut csv = String::from("category_name,category_slug,book_count,total_downloads,total_revenue,avg_price\n");
+    for row in analytics {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            review_export::csv_field(&row.category_name),
+            review_export::csv_field(&row.category_slug),
+            row.book_count,
+            row.total_downloads,
+            row.total_revenue,
+            row.avg_price.as_ref().map(|p| p.to_string()).unwrap_or_default(),
+        ));
+    }
+
+    let mut response = Response::new(axum::body::Body::from(csv));
+    let headers = response.headers_mut();
+    headers.insert("content-type", "text/csv; charset=utf-8".parse().unwrap());
+    headers.insert("content-disposition",
+        "attachment; filename=\"category_analytics.csv\"".parse().unwrap());
+
+    response
+}
+
 // Handler untuk dashboard metrics gabungan
 pub async fn get_dashboard_metrics(
     State(state): State<AppState>,
@@ -1811,6 +3465,7 @@ pub async fn get_dashboard_metrics(
                 success: false,
                 message: "Akses admin diperlukan".to_string(),
                 error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
             })
         ));
     }
@@ -1852,7 +3507,7 @@ pub async fn get_recent_activity(
     State(state): State<AppState>,
     Extension(user_role): Extension<String>,
     Query(params): Query<std::collections::HashMap<String, String>>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<Json<ApiResponse<Vec<serde_json::Value>, CountMeta>>, (StatusCode, Json<ErrorResponse>)> {
     // Validasi akses admin
     if user_role != "admin" {
         return Err((
@@ -1861,6 +3516,7 @@ pub async fn get_recent_activity(
                 success: false,
                 message: "Akses admin diperlukan".to_string(),
                 error_code: Some("INSUFFICIENT_PRIVILEGES".to_string()),
+                details: None,
             })
         ));
     }
@@ -1876,23 +3532,194 @@ pub async fn get_recent_activity(
         Ok(activities) => {
             tracing::info!("Recent activity berhasil diambil: {} items", activities.len());
             
-            Ok(Json(serde_json::json!({
-                "success": true,
-                "message": "Aktivitas buku terbaru berhasil diambil",
-                "data": activities,
-                "count": activities.len()
-            })))
+            let count = activities.len();
+            Ok(Json(ApiResponse::ok("Aktivitas buku terbaru berhasil diambil", activities)
+                .with_meta(CountMeta { count })))
         }
         Err(e) => {
             tracing::error!("Gagal mengambil recent activity: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    success: false,
-                    message: format!("Gagal mengambil aktivitas: {}", e),
-                    error_code: Some("ACTIVITY_ERROR".to_string()),
-                })
-            ))
+            Err(db_error(&e, format!("Gagal mengambil aktivitas: {}", e), "ACTIVITY_ERROR"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_category_analytics_csv_escapes_formulas_and_line_breaks() {
+        let row = |name: &str| CategoryAnalytics {
+            category_name: name.to_string(),
+            category_slug: "fiksi".to_string(),
+            book_count: 2,
+            total_downloads: 5,
+            total_revenue: BigDecimal::from(10000),
+            avg_price: None,
+        };
+        let response = category_analytics_csv(&[row("=SUM(A1:A9)"), row("Fiksi\rRemaja"), row("-Sains")]);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let lines: Vec<&str> = std::str::from_utf8(&body).unwrap().split('\n').collect();
+
+        assert_eq!(lines[1], "'=SUM(A1:A9),fiksi,2,5,10000,");
+        assert_eq!(lines[2], "\"Fiksi\rRemaja\",fiksi,2,5,10000,");
+        assert_eq!(lines[3], "'-Sains,fiksi,2,5,10000,");
+    }
+
+    #[test]
+    fn test_book_cache_capped_and_expired_entries_evicted() {
+        let mut cache = std::collections::HashMap::new();
+        let ttl = Duration::from_secs(60);
+        let (first, second, third) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        cache_insert(&mut cache, first, 1, ttl, 2);
+        std::thread::sleep(Duration::from_millis(2));
+        cache_insert(&mut cache, second, 2, ttl, 2);
+        // Key yang sudah ada diperbarui tanpa membuang entry lain
+        cache_insert(&mut cache, second, 3, ttl, 2);
+        assert_eq!(cache.len(), 2);
+
+        // Penuh: entry paling lama dibuang
+        cache_insert(&mut cache, third, 4, ttl, 2);
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.contains_key(&first));
+        assert_eq!(cache[&second].1, 3);
+
+        // TTL 0: entry lama semuanya expired
+        cache_insert(&mut cache, first, 5, Duration::ZERO, 2);
+        assert_eq!(cache.keys().collect::<Vec<_>>(), vec![&first]);
+    }
+
+    #[test]
+    fn test_content_disposition_encodes_non_ascii_filename() {
+        let header = content_disposition_attachment("Café_by_José.pdf");
+        let value = header.to_str().unwrap();
+
+        assert!(value.contains("filename=\"Caf__by_Jos_.pdf\""));
+        assert!(value.contains("filename*=UTF-8''Caf%C3%A9_by_Jos%C3%A9.pdf"));
+    }
+
+    #[test]
+    fn test_search_depth_cap() {
+        assert!(check_search_depth(1, 12, 10_000).is_ok());
+        assert!(check_search_depth(834, 12, 10_000).is_ok());
+        assert_eq!(check_search_depth(835, 12, 10_000), Err(834));
+        assert!(check_search_depth(100_000, 100, 0).is_ok());
+    }
+
+    #[test]
+    fn test_db_timeout_maps_to_gateway_timeout() {
+        let (status, Json(body)) = db_error(&DatabaseError::Connection(sqlx::Error::PoolTimedOut), "gagal", "DATABASE_ERROR");
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(body.error_code.as_deref(), Some("DB_TIMEOUT"));
+
+        let (status, Json(body)) = db_error(&DatabaseError::Connection(sqlx::Error::RowNotFound), "gagal", "DATABASE_ERROR");
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body.error_code.as_deref(), Some("DATABASE_ERROR"));
+        assert!(!DatabaseError::BookNotFound.is_timeout());
+    }
+
+    #[test]
+    fn test_raw_sqlx_timeout_maps_to_gateway_timeout() {
+        // Handler yang memanggil pool langsung meneruskan sqlx::Error tanpa DatabaseError
+        let (status, Json(body)) = db_error(&sqlx::Error::PoolTimedOut, "gagal", "REVIEW_ERROR");
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(body.error_code.as_deref(), Some("DB_TIMEOUT"));
+
+        let io_timeout = sqlx::Error::Io(std::io::Error::from(std::io::ErrorKind::TimedOut));
+        assert_eq!(db_error(&io_timeout, "gagal", "REVIEW_ERROR").0, StatusCode::GATEWAY_TIMEOUT);
+
+        let (status, Json(body)) = db_error(&sqlx::Error::PoolClosed, "gagal", "REVIEW_ERROR");
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body.error_code.as_deref(), Some("REVIEW_ERROR"));
+
+        // DatabaseError selain timeout koneksi tetap memakai kode 500 milik endpoint
+        for error in [DatabaseError::BookNotFound, DatabaseError::InvalidQuery, DatabaseError::ConcurrentModificationError] {
+            let (status, Json(body)) = db_error(&error, "gagal", "BOOK_ERROR");
+            assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+            assert_eq!(body.error_code.as_deref(), Some("BOOK_ERROR"));
+        }
+    }
+
+    #[test]
+    fn test_guest_view_hash_ignores_spoofed_forwarded_for() {
+        let client_ip = ClientIpResolver::new(&["10.0.0.0/8"]);
+        let peer: IpAddr = "8.8.8.8".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("User-Agent", "browser".parse().unwrap());
+        let direct = book_viewer(&client_ip, peer, &headers);
+
+        // Peer bukan proxy terpercaya: rotasi X-Forwarded-For tidak membuat viewer baru
+        headers.insert("X-Forwarded-For", "1.2.3.4".parse().unwrap());
+        assert_eq!(book_viewer(&client_ip, peer, &headers), direct);
+
+        // Lewat proxy terpercaya: client asli dari hop paling kanan yang bukan proxy
+        headers.insert("X-Forwarded-For", "1.2.3.4, 8.8.8.8, 10.0.0.9".parse().unwrap());
+        assert_eq!(book_viewer(&client_ip, "10.0.0.2".parse().unwrap(), &headers), direct);
+
+        // Header identitas palsu di route public tidak membuat viewer baru
+        for _ in 0..3 {
+            headers.insert("X-Gateway-Request", "1".parse().unwrap());
+            headers.insert("X-User-Id", Uuid::new_v4().to_string().parse().unwrap());
+            assert_eq!(book_viewer(&client_ip, peer, &headers), direct);
         }
+
+        // User agent berbeda tetap viewer berbeda
+        headers.insert("User-Agent", "other".parse().unwrap());
+        assert_ne!(book_viewer(&client_ip, peer, &headers), direct);
+    }
+
+    #[test]
+    fn test_replay_requires_paid_order_for_same_book_and_user() {
+        let (buyer, book_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let request = |user_id: Option<Uuid>| WebhookReplayRequest { order_id: "ORD-1".to_string(), book_id, user_id };
+        let order = |status: &str, book: Uuid| ReplayOrder { user_id: Some(buyer), book_id: Some(book), status: status.to_string() };
+        let code = |result: Result<Uuid, (StatusCode, Json<ErrorResponse>)>| {
+            let (status, Json(body)) = result.unwrap_err();
+            (status, body.error_code.unwrap())
+        };
+
+        assert_eq!(check_replay_order(Some(&order("paid", book_id)), &request(None)).unwrap(), buyer);
+        assert_eq!(check_replay_order(Some(&order("paid", book_id)), &request(Some(buyer))).unwrap(), buyer);
+        assert_eq!(code(check_replay_order(None, &request(None))), (StatusCode::NOT_FOUND, "ORDER_NOT_FOUND".to_string()));
+        assert_eq!(code(check_replay_order(Some(&order("pending", book_id)), &request(None))), (StatusCode::CONFLICT, "ORDER_NOT_PAID".to_string()));
+        assert_eq!(code(check_replay_order(Some(&order("paid", Uuid::new_v4())), &request(None))).1, "ORDER_MISMATCH");
+        assert_eq!(code(check_replay_order(Some(&order("paid", book_id)), &request(Some(Uuid::new_v4())))).1, "ORDER_MISMATCH");
+    }
+
+    #[test]
+    fn test_review_rate_limit_sets_retry_and_limit_headers() {
+        let response = review_rate_limited_response(ReviewRateLimited { limit: 5, retry_after_secs: 42 });
+        let headers = response.headers();
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(headers["retry-after"], "42");
+        assert_eq!(headers["x-ratelimit-limit"], "5");
+        assert_eq!(headers["x-ratelimit-remaining"], "0");
+        let reset: i64 = headers["x-ratelimit-reset"].to_str().unwrap().parse().unwrap();
+        assert!(reset >= chrono::Utc::now().timestamp() + 41);
+    }
+
+    #[test]
+    fn test_bucket_cover_url_not_prefixed() {
+        let base = "http://localhost:3002";
+        assert_eq!(public_cover_url(base, "/storage/covers/a.jpg"), "http://localhost:3002/storage/covers/a.jpg");
+        assert_eq!(
+            public_cover_url(base, "https://covers.s3.amazonaws.com/covers/a.jpg"),
+            "https://covers.s3.amazonaws.com/covers/a.jpg"
+        );
+    }
+
+    #[test]
+    fn test_overlong_review_comment_rejected_with_field_error() {
+        assert!(check_comment_length(&"a".repeat(1000), 1000).is_ok());
+        // Batas dihitung per karakter, bukan byte
+        assert!(check_comment_length(&"é".repeat(1000), 1000).is_ok());
+
+        let (status, Json(body)) = check_comment_length(&"a".repeat(1001), 1000).unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body.error_code.as_deref(), Some("INPUT_TOO_LONG"));
+        assert!(body.message.starts_with("comment:"));
+        assert_eq!(body.details.unwrap()["fields"]["comment"]["max_length"], 1000);
     }
-}
\ No newline at end of file
+}
@@ -3,8 +3,10 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use validator::Validate;
+use regex::Regex;
+use std::sync::LazyLock;
 use bigdecimal::{BigDecimal, Zero};
 
 
@@ -48,6 +50,15 @@ pub struct BookWithCategories {
     #[serde(flatten)]
     pub book: Book,
     pub categories: Vec<Category>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub thumbnails: Vec<CoverThumbnail>,
+}
+
+/// Thumbnail cover hasil pre-generate saat upload
+#[derive(Debug, Clone, Serialize)]
+pub struct CoverThumbnail {
+    pub width: u32,
+    pub url: String,
 }
 
 // ===== REQUEST MODELS =====
@@ -87,6 +98,79 @@ pub struct UpdateBookRequest {
     pub total_pages: Option<i32>,
 }
 
+/// Request untuk mengganti kategori buku (admin)
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateBookCategoriesRequest {
+    #[validate(length(max = 20, message = "Maksimal 20 kategori per buku"))]
+    pub category_ids: Vec<Uuid>,
+}
+
+/// Query recompute stats, tanpa book_id = semua buku
+#[derive(Debug, Deserialize)]
+pub struct RecomputeStatsParams {
+    pub book_id: Option<Uuid>,
+}
+
+/// Request replay side effect payment-success (admin recovery)
+#[derive(Debug, Deserialize, Validate)]
+pub struct WebhookReplayRequest {
+    #[validate(length(min = 1, max = 100, message = "order_id harus 1-100 karakter"))]
+    pub order_id: String,
+    pub book_id: Uuid,
+    pub user_id: Option<Uuid>,
+}
+
+/// Request rating banyak buku sekaligus (list/rekomendasi katalog)
+/// book_ids diterima sebagai string agar UUID invalid bisa dilaporkan per item
+#[derive(Debug, Deserialize, Validate)]
+pub struct BatchRatingsRequest {
+    #[validate(length(min = 1, max = 100, message = "book_ids harus berisi 1-100 ID"))]
+    pub book_ids: Vec<String>,
+}
+
+/// Request metadata banyak buku sekaligus (cart/wishlist)
+#[derive(Debug, Deserialize, Validate)]
+pub struct BatchBooksRequest {
+    #[validate(length(min = 1, max = 100, message = "book_ids harus berisi 1-100 ID"))]
+    pub book_ids: Vec<String>,
+}
+
+/// Request presigned URL upload cover langsung ke object storage
+#[derive(Debug, Deserialize)]
+pub struct CoverUploadUrlRequest {
+    pub content_type: String,
+    /// Ukuran file (byte), ikut ditandatangani sehingga upload dengan ukuran lain ditolak storage
+    pub content_length: u64,
+}
+
+/// Konfirmasi cover yang sudah di-PUT ke presigned URL
+#[derive(Debug, Deserialize)]
+pub struct ConfirmCoverUploadRequest {
+    pub object_key: String,
+}
+
+/// Cover hasil presigned upload yang lolos validasi, cover_path siap disimpan di buku
+#[derive(Debug, Serialize)]
+pub struct ConfirmedCoverUpload {
+    pub cover_path: String,
+}
+
+/// Order yang di-replay (tabel orders milik payment-service), dicek sebelum side effect dijalankan
+#[derive(Debug, Clone)]
+pub struct ReplayOrder {
+    pub user_id: Option<Uuid>,
+    pub book_id: Option<Uuid>,
+    pub status: String,
+}
+
+/// Hasil replay side effect payment-success
+#[derive(Debug, Serialize)]
+pub struct WebhookReplayResult {
+    pub applied: bool,
+    pub book_id: Uuid,
+    pub order_id: String,
+}
+
 /// Parameter query untuk pencarian dan filter buku
 #[derive(Debug, Deserialize)]
 pub struct BookQueryParams {
@@ -100,6 +184,18 @@ pub struct BookQueryParams {
     pub max_price: Option<BigDecimal>,  
     pub sort_by: Option<String>,
     pub sort_order: Option<String>,
+    /// Preset sort (newest, oldest, price_low, price_high, popular, top_rated), menang atas sort_by/sort_order
+    pub sort: Option<String>,
+    /// Buku unggulan (featured) ditaruh di depan hasil
+    pub featured_first: Option<bool>,
+    /// Sparse fieldset, mis. `fields=title,price,cover_path` (lihat field_selection::BOOK_FIELDS)
+    pub fields: Option<String>,
+}
+
+/// Parameter query `fields=` untuk detail buku
+#[derive(Debug, Deserialize)]
+pub struct BookFieldsParams {
+    pub fields: Option<String>,
 }
 
 /// Metadata pagination untuk response list
@@ -115,23 +211,58 @@ pub struct PaginationMeta {
 
 // ===== RESPONSE MODELS =====
 
-/// Response untuk list buku dengan pagination
+/// Envelope standar response sukses: `{success, message, data, meta}`
+/// `meta` untuk info tambahan (pagination, filter aktif) dan tidak diserialisasi jika kosong
 #[derive(Debug, Serialize)]
-pub struct PaginatedBooksResponse {
+pub struct ApiResponse<T, M = ()> {
     pub success: bool,
     pub message: String,
-    pub data: Vec<BookWithCategories>,
-    pub pagination: PaginationMeta,
+    pub data: T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<M>,
 }
 
+impl<T> ApiResponse<T> {
+    /// Response sukses tanpa meta
+    pub fn ok(message: impl Into<String>, data: T) -> Self {
+        Self {
+            success: true,
+            message: message.into(),
+            data,
+            meta: None,
+        }
+    }
+}
+
+impl<T, M> ApiResponse<T, M> {
+    /// Tambahkan meta ke response
+    pub fn with_meta<N>(self, meta: N) -> ApiResponse<T, N> {
+        ApiResponse {
+            success: self.success,
+            message: self.message,
+            data: self.data,
+            meta: Some(meta),
+        }
+    }
+}
+
+/// Response untuk list buku dengan pagination di meta
+pub type PaginatedBooksResponse = ApiResponse<Vec<BookWithCategories>, PaginationMeta>;
+
 /// Response untuk single buku
+pub type BookResponse = ApiResponse<Option<BookWithCategories>>;
+
+/// Meta batch buku: ID yang diminta tapi tidak ditemukan/nonaktif
 #[derive(Debug, Serialize)]
-pub struct BookResponse {
-    pub success: bool,
-    pub message: String,
-    pub data: Option<BookWithCategories>,
+pub struct BatchBooksMeta {
+    pub requested: usize,
+    pub found: usize,
+    pub missing_ids: Vec<Uuid>,
 }
 
+/// Response batch buku (urutan mengikuti request, tanpa duplikat)
+pub type BatchBooksResponse = ApiResponse<Vec<BookWithCategories>, BatchBooksMeta>;
+
 /// Response untuk file upload
 #[derive(Debug, Serialize)]
 pub struct FileUploadResponse {
@@ -147,6 +278,41 @@ pub struct ErrorResponse {
     pub success: bool,
     pub message: String,
     pub error_code: Option<String>,
+    /// Detail terstruktur (mis. `fields` untuk INPUT_TOO_LONG), sama dengan auth-service
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ErrorResponse {
+    /// Error field melebihi batas panjang, detail per field di `details.fields`
+    pub fn input_too_long(fields: &[(&str, usize)]) -> Self {
+        let message = fields.iter()
+            .map(|(field, max)| format!("{}: maksimal {} karakter", field, max))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let details: serde_json::Map<String, serde_json::Value> = fields.iter()
+            .map(|(field, max)| (field.to_string(), serde_json::json!({ "max_length": max })))
+            .collect();
+
+        Self {
+            success: false,
+            message,
+            error_code: Some("INPUT_TOO_LONG".to_string()),
+            details: Some(serde_json::json!({ "fields": details })),
+        }
+    }
+}
+
+/// Data response hapus buku
+#[derive(Debug, Serialize)]
+pub struct DeletedBook {
+    pub book_id: Uuid,
+}
+
+/// Meta jumlah item untuk list tanpa pagination
+#[derive(Debug, Serialize)]
+pub struct CountMeta {
+    pub count: usize,
 }
 
 // ===== REVIEW MODELS =====
@@ -164,6 +330,17 @@ pub struct BookReview {
     pub updated_at: DateTime<Utc>,
 }
 
+/// Row review untuk export admin (user_id dianonimkan sebelum keluar dari service)
+#[derive(Debug)]
+pub struct ReviewExportRecord {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub rating: i32,
+    pub comment: String,
+    pub helpful_count: i32,
+    pub created_at: DateTime<Utc>,
+}
+
 /// Review dengan informasi user untuk response
 #[derive(Debug, Serialize)]
 pub struct BookReviewWithUser {
@@ -181,6 +358,30 @@ pub struct BookReviewWithUser {
     pub has_voted_helpful: bool,
 }
 
+/// Review milik user beserta metadata buku (halaman "buku yang saya review")
+#[derive(Debug, Serialize)]
+pub struct UserReviewWithBook {
+    pub id: Uuid,
+    pub book_id: Uuid,
+    pub book_title: String,
+    pub book_author: String,
+    pub book_cover_url: Option<String>,
+    pub book_average_rating: f64,
+    pub book_total_reviews: i64,
+    pub rating: i32,
+    pub comment: String,
+    pub helpful_count: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Parameter pagination sederhana
+#[derive(Debug, Deserialize)]
+pub struct PageParams {
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+}
+
 /// Request untuk membuat review baru
 #[derive(Debug, Deserialize, Validate)]
 pub struct CreateReviewRequest {
@@ -191,8 +392,19 @@ pub struct CreateReviewRequest {
     pub comment: String,
 }
 
+/// Request untuk sync posisi baca
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateReadingProgressRequest {
+    #[validate(range(min = 1, message = "Halaman minimal 1"))]
+    pub current_page: i32,
+
+    /// Posisi detail dari reader (misal offset/CFI), opsional
+    #[validate(length(max = 500, message = "Position maksimal 500 karakter"))]
+    pub position: Option<String>,
+}
+
 /// Statistik review untuk buku
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ReviewStats {
     pub total_reviews: i64,
     pub average_rating: f64,
@@ -200,7 +412,7 @@ pub struct ReviewStats {
 }
 
 /// Distribusi rating 1-5
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct RatingDistribution {
     pub five_star: i64,
     pub four_star: i64,
@@ -209,23 +421,112 @@ pub struct RatingDistribution {
     pub one_star: i64,
 }
 
-/// Response wrapper untuk list reviews
+/// Meta list reviews: statistik rating buku
 #[derive(Debug, Serialize)]
-pub struct BookReviewsResponse {
-    pub success: bool,
-    pub message: String,
-    pub data: Vec<BookReviewWithUser>,
+pub struct ReviewListMeta {
     pub stats: ReviewStats,
 }
 
-/// Response wrapper untuk single review
-#[derive(Debug, Serialize)]
-pub struct ReviewResponse {
-    pub success: bool,
-    pub message: String,
-    pub data: Option<BookReviewWithUser>,
+/// Response wrapper untuk list reviews
+pub type BookReviewsResponse = ApiResponse<Vec<BookReviewWithUser>, ReviewListMeta>;
+
+/// Response wrapper untuk rating summary buku (tanpa list review)
+pub type BookRatingResponse = ApiResponse<ReviewStats>;
+
+/// Ringkasan rating satu buku di response batch
+#[derive(Debug, Clone, Serialize)]
+pub struct BookRatingSummary {
+    pub book_id: Uuid,
+    pub total_reviews: i64,
+    pub average_rating: f64,
+}
+
+/// Response wrapper untuk rating batch (urutan mengikuti request, tanpa duplikat)
+pub type BatchRatingsResponse = ApiResponse<Vec<BookRatingSummary>>;
+
+/// Batas bawah bucket download untuk badge popularitas public (angka pasti tidak pernah dikirim)
+const POPULARITY_BUCKETS: &[i64] = &[10, 50, 100, 500, 1_000, 5_000, 10_000, 50_000, 100_000];
+
+/// Minimal pembelian dalam dua window terakhir sebelum arah tren ditampilkan
+const POPULARITY_MIN_TREND_SAMPLE: i64 = 10;
+
+/// Arah tren pembelian window terakhir dibanding window sebelumnya
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PopularityTrend {
+    Up,
+    Down,
+    Steady,
+}
+
+/// Metrik popularitas public: hanya bucket kasar dan arah tren, tanpa jumlah penjualan/revenue
+#[derive(Debug, Clone, Serialize)]
+pub struct BookPopularity {
+    pub book_id: Uuid,
+    /// Batas bawah bucket total download (0 = belum mencapai bucket terkecil)
+    pub downloads_bucket: i64,
+    /// Label siap tampil, misal "500+", None jika di bawah bucket terkecil
+    pub downloads_label: Option<String>,
+    /// Bucket download dalam window terakhir ("500+ bulan ini")
+    pub recent_downloads_bucket: i64,
+    pub recent_downloads_label: Option<String>,
+    pub trend: PopularityTrend,
+    pub window_days: i64,
+}
+
+impl BookPopularity {
+    /// Bangun metrik dari hitungan mentah: total download, pembelian window terakhir dan sebelumnya
+    pub fn from_counts(book_id: Uuid, total_downloads: i64, recent: i64, previous: i64, window_days: i64) -> Self {
+        let downloads_bucket = popularity_bucket(total_downloads);
+        let recent_downloads_bucket = popularity_bucket(recent);
+
+        Self {
+            book_id,
+            downloads_bucket,
+            downloads_label: popularity_label(downloads_bucket),
+            recent_downloads_bucket,
+            recent_downloads_label: popularity_label(recent_downloads_bucket),
+            trend: popularity_trend(recent, previous),
+            window_days,
+        }
+    }
+}
+
+fn popularity_bucket(count: i64) -> i64 {
+    POPULARITY_BUCKETS.iter().rev().copied().find(|bucket| count >= *bucket).unwrap_or(0)
+}
+
+fn popularity_label(bucket: i64) -> Option<String> {
+    (bucket > 0).then(|| format!("{}+", bucket))
+}
+
+/// Selisih di bawah 20% dianggap stabil, sampel kecil selalu stabil supaya tidak membocorkan angka
+fn popularity_trend(recent: i64, previous: i64) -> PopularityTrend {
+    if recent + previous < POPULARITY_MIN_TREND_SAMPLE {
+        return PopularityTrend::Steady;
+    }
+
+    if recent * 5 >= previous * 6 {
+        PopularityTrend::Up
+    } else if recent * 5 <= previous * 4 {
+        PopularityTrend::Down
+    } else {
+        PopularityTrend::Steady
+    }
 }
 
+/// Response wrapper untuk popularitas buku (public)
+pub type BookPopularityResponse = ApiResponse<BookPopularity>;
+
+/// Response wrapper untuk reading progress
+pub type ReadingProgressResponse = ApiResponse<Option<ReadingProgress>>;
+
+/// Response wrapper untuk review milik user
+pub type UserReviewsResponse = ApiResponse<Vec<UserReviewWithBook>, PaginationMeta>;
+
+/// Response wrapper untuk single review
+pub type ReviewResponse = ApiResponse<Option<BookReviewWithUser>>;
+
 // ===== LIBRARY & PURCHASED BOOKS MODELS =====
 
 /// Buku yang sudah dibeli user dengan purchase info
@@ -237,28 +538,129 @@ pub struct PurchasedBook {
     pub download_count: i32,
     pub last_downloaded_at: Option<DateTime<Utc>>,
     pub categories: Vec<Category>,
+    pub reading_progress: Option<ReadingProgress>,
 }
 
-/// Response untuk library books
+/// Posisi baca terakhir user untuk satu buku
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadingProgress {
+    pub book_id: Uuid,
+    pub current_page: i32,
+    pub total_pages: Option<i32>,
+    pub position: Option<String>,
+    pub percent_complete: Option<f64>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ReadingProgress {
+    pub fn new(
+        book_id: Uuid,
+        current_page: i32,
+        total_pages: Option<i32>,
+        position: Option<String>,
+        updated_at: DateTime<Utc>,
+    ) -> Self {
+        let percent_complete = total_pages
+            .filter(|total| *total > 0)
+            .map(|total| ((current_page as f64 / total as f64) * 100.0).min(100.0));
+
+        Self { book_id, current_page, total_pages, position, percent_complete, updated_at }
+    }
+}
+
+/// Meta library: total buku yang dimiliki user
 #[derive(Debug, Serialize)]
-pub struct LibraryBooksResponse {
-    pub success: bool,
-    pub message: String,
-    pub data: Vec<PurchasedBook>,
+pub struct LibraryMeta {
     pub total_books: i64,
 }
 
+/// Response untuk library books
+pub type LibraryBooksResponse = ApiResponse<Vec<PurchasedBook>, LibraryMeta>;
+
 // ===== RELATED BOOKS MODELS =====
 
-/// Response untuk related books
+/// Query related books: `sort=relevance` mengurutkan berdasarkan jumlah kategori yang sama dulu
+#[derive(Debug, Deserialize)]
+pub struct RelatedBooksParams {
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+    pub sort: Option<String>,
+}
+
+/// Sinyal relevansi buku terkait terhadap buku sumber
+#[derive(Debug, Clone, Serialize)]
+pub struct RelatedScore {
+    pub shared_categories: i64,
+    pub download_count: i32,
+}
+
+/// Buku terkait beserta alasan relasinya
 #[derive(Debug, Serialize)]
-pub struct RelatedBooksResponse {
-    pub success: bool,
-    pub message: String,
-    pub data: Vec<BookWithCategories>,
+pub struct RelatedBook {
+    #[serde(flatten)]
+    pub book: BookWithCategories,
+    pub relevance: RelatedScore,
+}
+
+/// Meta related books: dasar relasi yang dipakai (category/author/popular)
+#[derive(Debug, Serialize)]
+pub struct RelatedBooksMeta {
     pub relation_type: String,
+    pub source_book_id: Uuid,
+    pub sort: String,
+    pub pagination: PaginationMeta,
+}
+
+/// Response untuk related books
+pub type RelatedBooksResponse = ApiResponse<Vec<RelatedBook>, RelatedBooksMeta>;
+
+// ===== FEATURED BOOK MODELS =====
+
+/// Buku unggulan hasil kurasi admin
+#[derive(Debug, Serialize)]
+pub struct FeaturedBook {
+    #[serde(flatten)]
+    pub book: BookWithCategories,
+    pub featured_order: Option<i32>,
+    pub featured_at: Option<DateTime<Utc>>,
+}
+
+/// Query params list featured books
+#[derive(Debug, Deserialize)]
+pub struct FeaturedBooksParams {
+    pub limit: Option<u32>,
+}
+
+/// Request set/unset featured satu buku
+#[derive(Debug, Deserialize, Validate)]
+pub struct SetFeaturedBookRequest {
+    pub featured: bool,
+
+    #[validate(range(min = 1, max = 10000, message = "featured_order harus 1-10000"))]
+    pub featured_order: Option<i32>,
 }
 
+/// Batas buku per reorder featured, response reorder membaca ulang sebanyak ini juga
+pub const MAX_FEATURED_REORDER: u64 = 100;
+
+/// Request urutan baru featured books (posisi di array = featured_order)
+#[derive(Debug, Deserialize, Validate)]
+pub struct ReorderFeaturedBooksRequest {
+    #[validate(length(min = 1, max = MAX_FEATURED_REORDER, message = "book_ids harus 1-100 item"))]
+    pub book_ids: Vec<Uuid>,
+}
+
+/// Status featured satu buku setelah diubah admin
+#[derive(Debug, Serialize)]
+pub struct FeaturedStatus {
+    pub book_id: Uuid,
+    pub is_featured: bool,
+    pub featured_order: Option<i32>,
+}
+
+/// Response untuk featured books
+pub type FeaturedBooksResponse = ApiResponse<Vec<FeaturedBook>, CountMeta>;
+
 // ===== PREVIEW MODELS =====
 
 /// Data preview buku
@@ -273,12 +675,7 @@ pub struct BookPreviewData {
 }
 
 /// Response untuk book preview
-#[derive(Debug, Serialize)]
-pub struct BookPreviewResponse {
-    pub success: bool,
-    pub message: String,
-    pub data: Option<BookPreviewData>,
-}
+pub type BookPreviewResponse = ApiResponse<Option<BookPreviewData>>;
 
 
 // ===== ADMIN ANALYTICS MODELS =====
@@ -307,6 +704,19 @@ pub struct LanguageStats {
     pub percentage: f64,
 }
 
+/// Statistik view dan konversi satu buku
+#[derive(Debug, Serialize)]
+pub struct BookViewStats {
+    pub book_id: Uuid,
+    pub total_views: i64,
+    pub unique_viewers: i64,
+    pub detail_views: i64,
+    pub preview_views: i64,
+    pub views_last_7_days: i64,
+    pub purchases: i64,
+    pub conversion_rate: f64,
+}
+
 /// Top buku berdasarkan metric
 #[derive(Debug, Serialize)]
 pub struct TopBook {
@@ -322,7 +732,7 @@ pub struct TopBook {
 }
 
 /// Data analytics penjualan
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SalesAnalytics {
     pub date: String,
     pub sales_count: i64,
@@ -331,7 +741,7 @@ pub struct SalesAnalytics {
 }
 
 /// Data chart buku populer
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PopularBooksChart {
     pub labels: Vec<String>,
     pub data: Vec<i64>,
@@ -339,7 +749,7 @@ pub struct PopularBooksChart {
 }
 
 /// Analytics per kategori
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct CategoryAnalytics {
     pub category_name: String,
     pub category_slug: String,
@@ -349,43 +759,52 @@ pub struct CategoryAnalytics {
     pub avg_price: Option<BigDecimal>,
 }
 
-// ===== ADMIN RESPONSE WRAPPERS =====
-
+/// Data yang terdampak jika buku di-soft-delete (preview sebelum hapus)
+/// Wishlist belum ada di schema sehingga tidak dihitung
 #[derive(Debug, Serialize)]
-pub struct AdminBookStatsResponse {
-    pub success: bool,
-    pub message: String,
-    pub data: AdminBookStats,
+pub struct BookDeleteImpact {
+    pub book_id: Uuid,
+    pub title: String,
+    pub is_active: bool,
+    pub pending_orders: i64,
+    pub purchases: i64,
+    pub reviews: i64,
+    pub active_readers: i64,
+    pub has_dependencies: bool,
 }
 
-#[derive(Debug, Serialize)]
-pub struct AdminTopBooksResponse {
-    pub success: bool,
-    pub message: String,
-    pub data: Vec<TopBook>,
-}
+// ===== ADMIN RESPONSE WRAPPERS =====
 
-#[derive(Debug, Serialize)]
-pub struct AdminSalesAnalyticsResponse {
-    pub success: bool,
-    pub message: String,
-    pub data: Vec<SalesAnalytics>,
-}
+pub type AdminBookStatsResponse = ApiResponse<AdminBookStats>;
+
+pub type AdminTopBooksResponse = ApiResponse<Vec<TopBook>, TopBooksPagination>;
 
+/// Metadata pagination offset-based untuk ranking top books
 #[derive(Debug, Serialize)]
-pub struct AdminPopularBooksChartResponse {
-    pub success: bool,
-    pub message: String,
-    pub data: PopularBooksChart,
+pub struct TopBooksPagination {
+    pub offset: u32,
+    pub limit: u32,
+    pub total_items: i64,
+    pub has_next: bool,
 }
 
+pub type AdminSalesAnalyticsResponse = ApiResponse<Vec<SalesAnalytics>>;
+
+pub type AdminPopularBooksChartResponse = ApiResponse<PopularBooksChart>;
+
+pub type AdminBookViewsResponse = ApiResponse<BookViewStats>;
+
+pub type AdminDeleteImpactResponse = ApiResponse<BookDeleteImpact>;
+
+/// Meta rentang tanggal filter analytics
 #[derive(Debug, Serialize)]
-pub struct AdminCategoryAnalyticsResponse {
-    pub success: bool,
-    pub message: String,
-    pub data: Vec<CategoryAnalytics>,
+pub struct DateRangeMeta {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
 }
 
+pub type AdminCategoryAnalyticsResponse = ApiResponse<Vec<CategoryAnalytics>, DateRangeMeta>;
+
 // ===== IMPLEMENTATIONS =====
 
 impl Default for BookQueryParams {
@@ -401,6 +820,9 @@ impl Default for BookQueryParams {
             max_price: None,
             sort_by: Some("created_at".to_string()),
             sort_order: Some("desc".to_string()),
+            sort: None,
+            featured_first: None,
+            fields: None,
         }
     }
 }
@@ -421,11 +843,56 @@ impl PaginationMeta {
     }
 }
 
+/// Batas panjang description default (karakter), override lewat BOOK_DESCRIPTION_MAX_LENGTH
+const DEFAULT_DESCRIPTION_MAX_LENGTH: usize = 5000;
+
+static SCRIPT_BLOCK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?is)<script\b.*?</script\s*>|<style\b.*?</style\s*>").expect("regex script block valid")
+});
+static HTML_TAG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)<[!/?a-zA-Z][^>]*>").expect("regex html tag valid")
+});
+
+/// Bersihkan free-text buku (title/author/description) sebelum disimpan untuk mencegah stored XSS:
+/// blok script/style dibuang beserta isinya, tag HTML lain di-strip, sisa `<` `>` di-escape
+/// Idempotent, jadi aman dijalankan ulang pada teks yang sudah bersih
+pub fn sanitize_book_text(text: &str) -> String {
+    let without_scripts = SCRIPT_BLOCK.replace_all(text, "");
+    let without_tags = HTML_TAG.replace_all(&without_scripts, "");
+    without_tags.replace('<', "&lt;").replace('>', "&gt;").trim().to_string()
+}
+
+/// Sanitasi field opsional multipart (`Some(None)` = dikosongkan), teks yang kosong setelah sanitasi jadi `Some(None)`
+pub fn sanitize_optional_book_text(value: Option<Option<String>>) -> Option<Option<String>> {
+    value.map(|text| text.map(|t| sanitize_book_text(&t)).filter(|t| !t.is_empty()))
+}
+
+/// Panjang maksimal description (karakter) dari BOOK_DESCRIPTION_MAX_LENGTH
+pub fn description_max_length() -> usize {
+    std::env::var("BOOK_DESCRIPTION_MAX_LENGTH")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|max| *max > 0)
+        .unwrap_or(DEFAULT_DESCRIPTION_MAX_LENGTH)
+}
+
 impl CreateBookRequest {
     /// Validasi custom untuk business rules
     pub fn validate_business_rules(&self) -> Result<(), String> {
+        if self.title.trim().is_empty() || self.author.trim().is_empty() {
+            return Err("Title dan author tidak boleh kosong".to_string());
+        }
+
+        // Validasi panjang description (dihitung setelah sanitasi)
+        if let Some(Some(ref description)) = &self.description {
+            let max = description_max_length();
+            if description.chars().count() > max {
+                return Err(format!("Description maksimal {} karakter", max));
+            }
+        }
+
         // Validasi price range
-        if self.price.is_zero() || self.price < Zero::zero() {
+        if self.price.is_zero() || self.price < BigDecimal::zero() {
             return Err("Harga harus lebih dari 0".to_string());
         }
         
@@ -450,26 +917,37 @@ impl CreateBookRequest {
     }
 }
 
+impl UpdateBookRequest {
+    /// Gabungkan update dengan data buku saat ini menjadi state akhir
+    /// Dipakai untuk menjalankan business rules yang sama dengan create
+    pub fn effective_state(&self, current: &BookWithCategories) -> CreateBookRequest {
+        let book = &current.book;
+
+        CreateBookRequest {
+            title: self.title.clone().unwrap_or_else(|| book.title.clone()),
+            author: self.author.clone().unwrap_or_else(|| book.author.clone()),
+            description: Some(self.description.clone().unwrap_or_else(|| book.description.clone())),
+            isbn: Some(self.isbn.clone().unwrap_or_else(|| book.isbn.clone())),
+            price: self.price.clone().unwrap_or_else(|| book.price.clone()),
+            language: Some(self.language.clone().unwrap_or_else(|| book.language.clone())),
+            category_ids: self.category_ids.clone()
+                .unwrap_or_else(|| current.categories.iter().map(|c| c.id).collect()),
+            total_pages: self.total_pages.or(book.total_pages),
+        }
+    }
+}
+
 impl PaginatedBooksResponse {
     /// Helper untuk membuat response sukses
     pub fn success(data: Vec<BookWithCategories>, pagination: PaginationMeta) -> Self {
-        Self {
-            success: true,
-            message: "Buku berhasil diambil".to_string(),
-            data,
-            pagination,
-        }
+        ApiResponse::ok("Buku berhasil diambil", data).with_meta(pagination)
     }
 }
 
 impl BookResponse {
     /// Helper untuk membuat response sukses dengan data buku
     pub fn success(book: BookWithCategories) -> Self {
-        Self {
-            success: true,
-            message: "Buku berhasil diambil".to_string(),
-            data: Some(book),
-        }
+        Self::ok("Buku berhasil diambil", Some(book))
     }
 
     /// Helper untuk membuat response error
@@ -478,6 +956,7 @@ impl BookResponse {
             success: false,
             message: message.to_string(),
             data: None,
+            meta: None,
         }
     }
 }
@@ -497,58 +976,73 @@ impl FileUploadResponse {
 impl BookReviewsResponse {
     /// Helper untuk membuat response reviews sukses
     pub fn success(reviews: Vec<BookReviewWithUser>, stats: ReviewStats) -> Self {
-        Self {
-            success: true,
-            message: "Reviews berhasil diambil".to_string(),
-            data: reviews,
-            stats,
-        }
+        ApiResponse::ok("Reviews berhasil diambil", reviews).with_meta(ReviewListMeta { stats })
+    }
+}
+
+impl BookRatingResponse {
+    /// Helper untuk membuat response rating summary sukses
+    pub fn success(stats: ReviewStats) -> Self {
+        Self::ok("Rating buku berhasil diambil", stats)
+    }
+}
+
+impl BookPopularityResponse {
+    /// Helper untuk membuat response popularitas sukses
+    pub fn success(popularity: BookPopularity) -> Self {
+        Self::ok("Popularitas buku berhasil diambil", popularity)
+    }
+}
+
+impl BatchBooksResponse {
+    /// Helper untuk membuat response batch buku sukses
+    pub fn success(data: Vec<BookWithCategories>, requested: usize, missing_ids: Vec<Uuid>) -> Self {
+        let found = data.len();
+        ApiResponse::ok(format!("{} buku berhasil diambil", found), data)
+            .with_meta(BatchBooksMeta { requested, found, missing_ids })
+    }
+}
+
+impl BatchRatingsResponse {
+    /// Helper untuk membuat response rating batch sukses
+    pub fn success(data: Vec<BookRatingSummary>) -> Self {
+        Self::ok(format!("Rating {} buku berhasil diambil", data.len()), data)
+    }
+}
+
+impl ReadingProgressResponse {
+    /// Helper untuk membuat response reading progress
+    pub fn success(progress: Option<ReadingProgress>, message: &str) -> Self {
+        Self::ok(message, progress)
     }
 }
 
 impl ReviewResponse {
     /// Helper untuk membuat response review sukses
     pub fn success(review: BookReviewWithUser) -> Self {
-        Self {
-            success: true,
-            message: "Review berhasil ditambahkan".to_string(),
-            data: Some(review),
-        }
+        Self::ok("Review berhasil ditambahkan", Some(review))
     }
 }
 
 impl LibraryBooksResponse {
     /// Helper untuk membuat response library sukses
     pub fn success(books: Vec<PurchasedBook>, total: i64) -> Self {
-        Self {
-            success: true,
-            message: "Library berhasil diambil".to_string(),
-            data: books,
-            total_books: total,
-        }
+        ApiResponse::ok("Library berhasil diambil", books).with_meta(LibraryMeta { total_books: total })
     }
 }
 
 impl RelatedBooksResponse {
     /// Helper untuk membuat response related books sukses
-    pub fn success(books: Vec<BookWithCategories>, relation_type: String) -> Self {
-        Self {
-            success: true,
-            message: "Related books berhasil diambil".to_string(),
-            data: books,
-            relation_type,
-        }
+    pub fn success(books: Vec<RelatedBook>, relation_type: String, source_book_id: Uuid, sort: String, pagination: PaginationMeta) -> Self {
+        ApiResponse::ok("Related books berhasil diambil", books)
+            .with_meta(RelatedBooksMeta { relation_type, source_book_id, sort, pagination })
     }
 }
 
 impl BookPreviewResponse {
     /// Helper untuk membuat response preview sukses
     pub fn success(data: BookPreviewData) -> Self {
-        Self {
-            success: true,
-            message: "Preview data berhasil diambil".to_string(),
-            data: Some(data),
-        }
+        Self::ok("Preview data berhasil diambil", Some(data))
     }
     
     /// Helper untuk membuat response preview tidak tersedia
@@ -557,6 +1051,7 @@ impl BookPreviewResponse {
             success: false,
             message: "Preview tidak tersedia untuk buku ini".to_string(),
             data: None,
+            meta: None,
         }
     }
 }
@@ -564,54 +1059,189 @@ impl BookPreviewResponse {
 impl AdminBookStatsResponse {
     /// Helper untuk membuat response statistik admin
     pub fn success(stats: AdminBookStats) -> Self {
-        Self {
-            success: true,
-            message: "Statistik buku berhasil diambil".to_string(),
-            data: stats,
-        }
+        Self::ok("Statistik buku berhasil diambil", stats)
     }
 }
 
 impl AdminTopBooksResponse {
     /// Helper untuk membuat response top books
-    pub fn success(books: Vec<TopBook>) -> Self {
+    pub fn success(books: Vec<TopBook>, pagination: TopBooksPagination) -> Self {
+        ApiResponse::ok("Top buku berhasil diambil", books).with_meta(pagination)
+    }
+}
+
+impl TopBooksPagination {
+    /// Membuat metadata pagination dari offset, limit, dan total items
+    pub fn new(offset: u32, limit: u32, total_items: i64) -> Self {
         Self {
-            success: true,
-            message: "Top buku berhasil diambil".to_string(),
-            data: books,
+            offset,
+            limit,
+            total_items,
+            has_next: (offset as i64 + limit as i64) < total_items,
         }
     }
 }
 
+impl AdminBookViewsResponse {
+    /// Helper untuk membuat response statistik view buku
+    pub fn success(stats: BookViewStats) -> Self {
+        Self::ok("Statistik view buku berhasil diambil", stats)
+    }
+}
+
 impl AdminSalesAnalyticsResponse {
     /// Helper untuk membuat response sales analytics
     pub fn success(analytics: Vec<SalesAnalytics>) -> Self {
-        Self {
-            success: true,
-            message: "Analytics penjualan berhasil diambil".to_string(),
-            data: analytics,
-        }
+        Self::ok("Analytics penjualan berhasil diambil", analytics)
     }
 }
 
 impl AdminPopularBooksChartResponse {
     /// Helper untuk membuat response popular books chart
     pub fn success(chart_data: PopularBooksChart) -> Self {
-        Self {
-            success: true,
-            message: "Data chart buku populer berhasil diambil".to_string(),
-            data: chart_data,
-        }
+        Self::ok("Data chart buku populer berhasil diambil", chart_data)
     }
 }
 
 impl AdminCategoryAnalyticsResponse {
     /// Helper untuk membuat response category analytics
-    pub fn success(analytics: Vec<CategoryAnalytics>) -> Self {
-        Self {
-            success: true,
-            message: "Analytics kategori berhasil diambil".to_string(),
-            data: analytics,
+    pub fn success(
+        analytics: Vec<CategoryAnalytics>,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Self {
+        ApiResponse::ok("Analytics kategori berhasil diambil", analytics).with_meta(DateRangeMeta { from, to })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn current_book() -> BookWithCategories {
+        BookWithCategories {
+            book: Book {
+                id: Uuid::new_v4(),
+                title: "Rust Basics".to_string(),
+                author: "Ferris".to_string(),
+                description: None,
+                isbn: Some("9781234567897".to_string()),
+                price: BigDecimal::from(50_000),
+                pdf_path: None,
+                cover_path: None,
+                file_size_mb: None,
+                total_pages: Some(200),
+                language: "id".to_string(),
+                is_active: true,
+                download_count: 0,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            },
+            categories: vec![Category {
+                id: Uuid::new_v4(),
+                name: "Programming".to_string(),
+                slug: "programming".to_string(),
+                description: None,
+                is_active: true,
+                created_at: Utc::now(),
+            }],
+            thumbnails: Vec::new(),
+        }
+    }
+
+    fn empty_update() -> UpdateBookRequest {
+        UpdateBookRequest {
+            title: None,
+            author: None,
+            description: None,
+            isbn: None,
+            price: None,
+            language: None,
+            category_ids: None,
+            is_active: None,
+            total_pages: None,
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_effective_state_keeps_current_values() {
+        let current = current_book();
+        let state = empty_update().effective_state(&current);
+
+        assert_eq!(state.price, current.book.price);
+        assert_eq!(state.category_ids, vec![current.categories[0].id]);
+        assert!(state.validate_business_rules().is_ok());
+    }
+
+    #[test]
+    fn test_effective_state_rejects_invalid_update() {
+        let current = current_book();
+
+        let mut zero_price = empty_update();
+        zero_price.price = Some(BigDecimal::from(0));
+        assert!(zero_price.effective_state(&current).validate_business_rules().is_err());
+
+        let mut no_categories = empty_update();
+        no_categories.category_ids = Some(Vec::new());
+        assert!(no_categories.effective_state(&current).validate_business_rules().is_err());
+    }
+
+    #[test]
+    fn test_book_popularity_is_bucketed() {
+        let popularity = BookPopularity::from_counts(Uuid::new_v4(), 734, 120, 60, 30);
+        assert_eq!(popularity.downloads_bucket, 500);
+        assert_eq!(popularity.downloads_label.as_deref(), Some("500+"));
+        assert_eq!(popularity.recent_downloads_label.as_deref(), Some("100+"));
+        assert_eq!(popularity.trend, PopularityTrend::Up);
+
+        let quiet = BookPopularity::from_counts(Uuid::new_v4(), 7, 3, 0, 30);
+        assert_eq!(quiet.downloads_bucket, 0);
+        assert!(quiet.downloads_label.is_none());
+        assert_eq!(quiet.trend, PopularityTrend::Steady);
+
+        assert_eq!(popularity_trend(40, 60), PopularityTrend::Down);
+        assert_eq!(popularity_trend(55, 50), PopularityTrend::Steady);
+    }
+
+    #[test]
+    fn test_sanitize_book_text_and_description_limit() {
+        assert_eq!(sanitize_book_text("Seru <script>alert(1)</script>dibaca"), "Seru dibaca");
+        assert_eq!(sanitize_book_text("<b>Tebal</b> <img src=x onerror=alert(1)>"), "Tebal");
+        assert_eq!(sanitize_book_text("a < b && c > d"), "a &lt; b && c &gt; d");
+        assert_eq!(sanitize_book_text("<img src=x onerror=alert(1)"), "&lt;img src=x onerror=alert(1)");
+        assert_eq!(sanitize_book_text(&sanitize_book_text("a < b")), "a &lt; b");
+        assert_eq!(sanitize_optional_book_text(Some(Some("<p></p>".to_string()))), Some(None));
+
+        let mut too_long = empty_update();
+        too_long.description = Some(Some("x".repeat(description_max_length() + 1)));
+        assert!(too_long.effective_state(&current_book()).validate_business_rules().is_err());
+
+        let mut empty_title = empty_update();
+        empty_title.title = Some(sanitize_book_text("<i></i>"));
+        assert!(empty_title.effective_state(&current_book()).validate_business_rules().is_err());
+    }
+
+    #[test]
+    fn test_api_response_envelope_shape() {
+        let single = serde_json::to_value(BookResponse::success(current_book())).unwrap();
+        assert_eq!(single["success"], true);
+        assert!(single["data"]["title"].is_string());
+        assert!(single.get("meta").is_none());
+
+        let related_book = RelatedBook {
+            book: current_book(),
+            relevance: RelatedScore { shared_categories: 2, download_count: 10 },
+        };
+        let related = serde_json::to_value(RelatedBooksResponse::success(
+            vec![related_book],
+            "category".to_string(),
+            Uuid::new_v4(),
+            "popular".to_string(),
+            PaginationMeta::new(1, 6, 1),
+        )).unwrap();
+        assert_eq!(related["meta"]["relation_type"], "category");
+        assert_eq!(related["meta"]["pagination"]["total_items"], 1);
+        assert!(related["data"][0]["title"].is_string());
+        assert_eq!(related["data"][0]["relevance"]["shared_categories"], 2);
+        assert!(related.get("relation_type").is_none());
+    }
+}
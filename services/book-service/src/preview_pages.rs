@@ -0,0 +1,253 @@
+// /pdf-bookstore/services/book-service/src/preview_pages.rs
+
+use image::ImageFormat;
+use sha2::{Digest, Sha256};
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{process::Command, sync::Semaphore};
+use uuid::Uuid;
+
+use crate::cover_variants::accepted_mime_types;
+
+/// Folder cache halaman preview di dalam storage: `/previews/pages/{book_id}/{versi}/{n}.{ext}`
+const PAGES_DIR: &str = "previews/pages";
+
+/// Format gambar halaman preview, dinegosiasikan lewat header Accept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageFormat {
+    Png,
+    Webp,
+}
+
+impl PageFormat {
+    /// WebP jika client menerimanya secara eksplisit, selain itu PNG
+    pub fn negotiate(accept: &str) -> Self {
+        let webp = accepted_mime_types(accept).iter().any(|mime| mime == "image/webp");
+
+        if webp { Self::Webp } else { Self::Png }
+    }
+
+    pub fn mime(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Webp => "image/webp",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Webp => "webp",
+        }
+    }
+}
+
+/// Error render halaman preview
+#[derive(Debug)]
+pub enum PageRenderError {
+    /// Halaman di luar rentang preview (atau tidak ada di PDF preview)
+    PageOutOfRange,
+    /// Semua slot render sedang dipakai
+    Busy,
+    Failed(String),
+}
+
+/// Render halaman PDF preview ke gambar lewat renderer eksternal (poppler `pdftoppm`),
+/// hasil di-cache di disk supaya tiap halaman hanya dirender sekali
+pub struct PreviewPageRenderer {
+    command: String,
+    dpi: u32,
+    timeout: Duration,
+    storage_dir: PathBuf,
+    slots: Arc<Semaphore>,
+}
+
+impl PreviewPageRenderer {
+    /// PREVIEW_RENDER_COMMAND (default "pdftoppm"), PREVIEW_RENDER_DPI (default 110, 50-300),
+    /// PREVIEW_RENDER_TIMEOUT_SECONDS (default 20), PREVIEW_RENDER_CONCURRENCY (default 2)
+    pub fn from_env(storage_dir: impl Into<PathBuf>) -> Self {
+        let read = |key: &str, default: u64| {
+            env::var(key).ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(default)
+        };
+
+        Self {
+            command: env::var("PREVIEW_RENDER_COMMAND")
+                .ok()
+                .filter(|v| !v.trim().is_empty())
+                .unwrap_or_else(|| "pdftoppm".to_string()),
+            dpi: read("PREVIEW_RENDER_DPI", 110).clamp(50, 300) as u32,
+            timeout: Duration::from_secs(read("PREVIEW_RENDER_TIMEOUT_SECONDS", 20).max(1)),
+            storage_dir: storage_dir.into(),
+            slots: Arc::new(Semaphore::new(read("PREVIEW_RENDER_CONCURRENCY", 2).max(1) as usize)),
+        }
+    }
+
+    /// Path file PDF preview di disk dari `preview_url` (`/storage/...`), None jika di luar storage
+    pub fn source_path(&self, preview_url: &str) -> Option<PathBuf> {
+        let relative = preview_url.strip_prefix("/storage/")?;
+        if relative.contains("..") || relative.contains('\\') || !relative.to_lowercase().ends_with(".pdf") {
+            return None;
+        }
+        Some(self.storage_dir.join(relative))
+    }
+
+    /// Path cache halaman, versi diambil dari preview_url supaya preview baru tidak memakai cache lama
+    fn cache_path(&self, book_id: Uuid, preview_url: &str, page: u32, format: PageFormat) -> PathBuf {
+        let version = hex::encode(&Sha256::digest(preview_url.as_bytes())[..8]);
+        self.storage_dir
+            .join(PAGES_DIR)
+            .join(book_id.to_string())
+            .join(version)
+            .join(format!("{}.{}", page, format.extension()))
+    }
+
+    /// Ambil gambar halaman `page` (1-based), dirender saat pertama diminta
+    /// `allowed_pages` = batas preview buku, halaman di luar batas tidak pernah dirender
+    pub async fn page_image(
+        &self,
+        book_id: Uuid,
+        preview_url: &str,
+        page: u32,
+        allowed_pages: u32,
+        format: PageFormat,
+    ) -> Result<Vec<u8>, PageRenderError> {
+        if page == 0 || page > allowed_pages {
+            return Err(PageRenderError::PageOutOfRange);
+        }
+
+        let source = self.source_path(preview_url)
+            .ok_or_else(|| PageRenderError::Failed(format!("preview_url tidak valid: {}", preview_url)))?;
+        let target = self.cache_path(book_id, preview_url, page, format);
+
+        if let Ok(bytes) = tokio::fs::read(&target).await {
+            return Ok(bytes);
+        }
+
+        let _slot = tokio::time::timeout(self.timeout, self.slots.clone().acquire_owned())
+            .await
+            .map_err(|_| PageRenderError::Busy)?
+            .map_err(|_| PageRenderError::Busy)?;
+
+        // Request paralel untuk halaman yang sama mungkin sudah selesai merender selama menunggu slot
+        if let Ok(bytes) = tokio::fs::read(&target).await {
+            return Ok(bytes);
+        }
+
+        let png = self.render_png(&source, page).await?;
+        let bytes = match format {
+            PageFormat::Png => png,
+            PageFormat::Webp => tokio::task::spawn_blocking(move || encode_webp(&png))
+                .await
+                .map_err(|e| PageRenderError::Failed(e.to_string()))??,
+        };
+
+        if let Err(e) = write_atomic(&target, &bytes).await {
+            tracing::warn!("Gagal menyimpan cache halaman preview {}: {}", target.display(), e);
+        }
+        Ok(bytes)
+    }
+
+    /// `pdftoppm -png -r DPI -f n -l n -singlefile` hanya untuk satu halaman
+    async fn render_png(&self, source: &Path, page: u32) -> Result<Vec<u8>, PageRenderError> {
+        if !source.is_file() {
+            return Err(PageRenderError::Failed(format!("file preview tidak ada: {}", source.display())));
+        }
+
+        let work_dir = env::temp_dir().join(format!("preview-render-{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&work_dir).await.map_err(|e| PageRenderError::Failed(e.to_string()))?;
+        let output_prefix = work_dir.join("page");
+
+        let run = Command::new(&self.command)
+            .arg("-png")
+            .arg("-r").arg(self.dpi.to_string())
+            .arg("-f").arg(page.to_string())
+            .arg("-l").arg(page.to_string())
+            .arg("-singlefile")
+            .arg(source)
+            .arg(&output_prefix)
+            .kill_on_drop(true)
+            .output();
+
+        let result = tokio::time::timeout(self.timeout, run).await;
+        let rendered = tokio::fs::read(output_prefix.with_extension("png")).await;
+        let _ = tokio::fs::remove_dir_all(&work_dir).await;
+
+        match result {
+            Err(_) => Err(PageRenderError::Failed(format!("render halaman {} timeout", page))),
+            Ok(Err(e)) => Err(PageRenderError::Failed(format!("{} tidak bisa dijalankan: {}", self.command, e))),
+            // pdftoppm tidak menghasilkan file jika halaman melebihi jumlah halaman PDF preview
+            Ok(Ok(output)) if output.status.success() => rendered.map_err(|_| PageRenderError::PageOutOfRange),
+            Ok(Ok(output)) => Err(PageRenderError::Failed(format!(
+                "{} gagal ({}): {}",
+                self.command,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))),
+        }
+    }
+}
+
+fn encode_webp(png: &[u8]) -> Result<Vec<u8>, PageRenderError> {
+    let decoded = image::load_from_memory_with_format(png, ImageFormat::Png)
+        .map_err(|e| PageRenderError::Failed(e.to_string()))?;
+
+    let mut encoded = std::io::Cursor::new(Vec::new());
+    decoded.write_to(&mut encoded, ImageFormat::WebP)
+        .map_err(|e| PageRenderError::Failed(e.to_string()))?;
+    Ok(encoded.into_inner())
+}
+
+/// Tulis ke file sementara lalu rename supaya request paralel tidak membaca file setengah jadi
+async fn write_atomic(target: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = target.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let temp = target.with_extension(format!("tmp{}", Uuid::new_v4().simple()));
+    tokio::fs::write(&temp, bytes).await?;
+    tokio::fs::rename(&temp, target).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_page_bounds_and_cache_lookup() {
+        let dir = env::temp_dir().join(format!("preview-pages-test-{}", Uuid::new_v4()));
+        let renderer = PreviewPageRenderer::from_env(dir.clone());
+        let book_id = Uuid::new_v4();
+        let preview_url = "/storage/previews/sample_preview.pdf";
+
+        assert!(matches!(
+            renderer.page_image(book_id, preview_url, 0, 5, PageFormat::Png).await,
+            Err(PageRenderError::PageOutOfRange)
+        ));
+        assert!(matches!(
+            renderer.page_image(book_id, preview_url, 6, 5, PageFormat::Png).await,
+            Err(PageRenderError::PageOutOfRange)
+        ));
+
+        assert_eq!(renderer.source_path("/storage/../secret.pdf"), None);
+        assert_eq!(renderer.source_path("/etc/passwd"), None);
+        assert_eq!(renderer.source_path(preview_url), Some(dir.join("previews/sample_preview.pdf")));
+
+        // Halaman yang sudah ada di cache tidak dirender ulang
+        let cached = renderer.cache_path(book_id, preview_url, 2, PageFormat::Png);
+        write_atomic(&cached, b"cached-page").await.unwrap();
+        let bytes = renderer.page_image(book_id, preview_url, 2, 5, PageFormat::Png).await.unwrap();
+        assert_eq!(bytes, b"cached-page");
+        assert_ne!(cached, renderer.cache_path(book_id, "/storage/previews/new_preview.pdf", 2, PageFormat::Png));
+
+        assert_eq!(PageFormat::negotiate("image/avif,image/webp,*/*;q=0.8"), PageFormat::Webp);
+        assert_eq!(PageFormat::negotiate("image/webp;q=0"), PageFormat::Png);
+        assert_eq!(PageFormat::negotiate("image/webp;q=0.00"), PageFormat::Png);
+        assert_eq!(PageFormat::negotiate("IMAGE/WEBP;q=0.5"), PageFormat::Webp);
+        assert_eq!(PageFormat::negotiate("*/*"), PageFormat::Png);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
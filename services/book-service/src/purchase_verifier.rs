@@ -0,0 +1,159 @@
+// /pdf-bookstore/services/book-service/src/purchase_verifier.rs
+
+use std::{collections::HashMap, env, time::{Duration, Instant}};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Fallback verifikasi kepemilikan ke payment-service saat row user_purchases belum ada
+/// (misal webhook pembayaran terlambat), hanya hasil positif yang di-cache dan
+/// payment-service meng-evict cache saat order di-refund atau akses dicabut admin
+pub struct PurchaseVerifier {
+    enabled: bool,
+    payment_service_url: String,
+    service_key: String,
+    timeout: Duration,
+    cache_ttl: Duration,
+    confirmed: RwLock<HashMap<(Uuid, Uuid), Instant>>,
+}
+
+impl PurchaseVerifier {
+    /// PURCHASE_FALLBACK_ENABLED (default true), PAYMENT_SERVICE_URL,
+    /// PURCHASE_FALLBACK_TIMEOUT_MS (default 2000), PURCHASE_CONFIRM_CACHE_SECONDS (default 300)
+    pub fn from_env() -> Self {
+        let enabled = env::var("PURCHASE_FALLBACK_ENABLED")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(true);
+
+        let timeout_ms = env::var("PURCHASE_FALLBACK_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(2000);
+
+        let cache_secs = env::var("PURCHASE_CONFIRM_CACHE_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(300);
+
+        Self {
+            enabled,
+            payment_service_url: env::var("PAYMENT_SERVICE_URL")
+                .unwrap_or_else(|_| "http://payment-service:3003".to_string()),
+            service_key: env::var("INTERNAL_SERVICE_KEY")
+                .unwrap_or_else(|_| "internal-service-key-secret".to_string()),
+            timeout: Duration::from_millis(timeout_ms),
+            cache_ttl: Duration::from_secs(cache_secs),
+            confirmed: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Konfirmasi kepemilikan ke payment-service (pemilik data order, termasuk refund / akses dicabut)
+    /// Error jaringan/payment-service down dianggap tidak terkonfirmasi (fail closed)
+    pub async fn confirm(
+        &self,
+        http_client: &reqwest::Client,
+        user_id: Uuid,
+        book_id: Uuid,
+    ) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        if let Some(confirmed_at) = self.confirmed.read().await.get(&(user_id, book_id)) {
+            if confirmed_at.elapsed() < self.cache_ttl {
+                return true;
+            }
+        }
+
+        if !self.ownership_confirmed(http_client, user_id, book_id).await {
+            return false;
+        }
+
+        tracing::info!(
+            "Kepemilikan user {} buku {} dikonfirmasi payment-service (user_purchases belum ada)",
+            user_id, book_id
+        );
+
+        let mut confirmed = self.confirmed.write().await;
+        confirmed.retain(|_, confirmed_at| confirmed_at.elapsed() < self.cache_ttl);
+        confirmed.insert((user_id, book_id), Instant::now());
+        true
+    }
+
+    /// Hapus konfirmasi yang di-cache (akses dicabut / order di-refund), return true jika ada entry
+    pub async fn evict(&self, user_id: Uuid, book_id: Uuid) -> bool {
+        self.confirmed.write().await.remove(&(user_id, book_id)).is_some()
+    }
+
+    async fn ownership_confirmed(&self, http_client: &reqwest::Client, user_id: Uuid, book_id: Uuid) -> bool {
+        let response = http_client
+            .get(format!(
+                "{}/api/internal/users/{}/books/{}/ownership",
+                self.payment_service_url, user_id, book_id
+            ))
+            .header("X-Service-Key", &self.service_key)
+            .timeout(self.timeout)
+            .send()
+            .await;
+
+        let body = match response {
+            Ok(resp) if resp.status().is_success() => resp.json::<serde_json::Value>().await.ok(),
+            Ok(resp) => {
+                tracing::debug!("Payment-service menolak lookup kepemilikan user {} buku {}: {}", user_id, book_id, resp.status());
+                None
+            }
+            Err(e) => {
+                tracing::warn!("Payment-service tidak bisa dihubungi untuk kepemilikan user {} buku {}: {}", user_id, book_id, e);
+                None
+            }
+        };
+
+        body.is_some_and(|body| is_confirmed_ownership(&body["data"], user_id, book_id))
+    }
+}
+
+/// Respons payment-service harus `owned: true` untuk user + buku yang sama
+fn is_confirmed_ownership(data: &serde_json::Value, user_id: Uuid, book_id: Uuid) -> bool {
+    let matches = |field: &str, expected: Uuid| {
+        data[field].as_str().and_then(|v| Uuid::parse_str(v).ok()) == Some(expected)
+    };
+
+    data["owned"].as_bool() == Some(true) && matches("user_id", user_id) && matches("book_id", book_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ownership_must_match_user_and_book() {
+        let user_id = Uuid::new_v4();
+        let book_id = Uuid::new_v4();
+        let data = |owned: bool, user: Uuid| serde_json::json!({
+            "owned": owned,
+            "user_id": user.to_string(),
+            "book_id": book_id.to_string(),
+        });
+
+        assert!(is_confirmed_ownership(&data(true, user_id), user_id, book_id));
+        assert!(!is_confirmed_ownership(&data(false, user_id), user_id, book_id));
+        assert!(!is_confirmed_ownership(&data(true, Uuid::new_v4()), user_id, book_id));
+        assert!(!is_confirmed_ownership(&serde_json::Value::Null, user_id, book_id));
+    }
+
+    #[tokio::test]
+    async fn test_evicted_confirmation_is_not_reused() {
+        let mut verifier = PurchaseVerifier::from_env();
+        // Port 9 (discard) di localhost -> connection refused, payment-service dianggap down
+        verifier.payment_service_url = "http://127.0.0.1:9".to_string();
+        let http_client = reqwest::Client::new();
+        let (user_id, book_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        verifier.confirmed.write().await.insert((user_id, book_id), Instant::now());
+        assert!(verifier.confirm(&http_client, user_id, book_id).await);
+
+        // Refund / revoke di payment-service meng-evict cache, konfirmasi berikutnya fail closed
+        assert!(verifier.evict(user_id, book_id).await);
+        assert!(!verifier.confirm(&http_client, user_id, book_id).await);
+        assert!(!verifier.evict(user_id, book_id).await);
+    }
+}
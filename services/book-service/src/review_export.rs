@@ -0,0 +1,219 @@
+// /pdf-bookstore/services/book-service/src/review_export.rs
+
+use axum::body::Body;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::PgPool;
+use std::env;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use uuid::Uuid;
+
+use crate::database::BookRepository;
+use crate::models::ReviewExportRecord;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const CSV_HEADER: &str = "review_id,reviewer,rating,comment,helpful_count,created_at\n";
+
+/// Format export review untuk publisher
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReviewExportFormat {
+    Csv,
+    Json,
+}
+
+impl ReviewExportFormat {
+    /// Query `format` (csv/json), default csv
+    pub fn parse(value: Option<&str>) -> Option<Self> {
+        match value.map(|v| v.trim().to_lowercase()).as_deref() {
+            None | Some("") | Some("csv") => Some(Self::Csv),
+            Some("json") => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Json => "application/json",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+
+    fn header(self, book_id: Uuid) -> String {
+        match self {
+            Self::Csv => CSV_HEADER.to_string(),
+            Self::Json => format!("{{\"success\":true,\"book_id\":\"{}\",\"reviews\":[", book_id),
+        }
+    }
+
+    fn footer(self) -> &'static str {
+        match self {
+            Self::Csv => "",
+            Self::Json => "]}",
+        }
+    }
+
+    fn push_row(self, out: &mut String, row: &ReviewExportRecord, reviewer: &str, first: bool) {
+        match self {
+            Self::Csv => out.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                row.id,
+                reviewer,
+                row.rating,
+                csv_field(&row.comment),
+                row.helpful_count,
+                row.created_at.to_rfc3339(),
+            )),
+            Self::Json => {
+                if !first {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::json!({
+                    "review_id": row.id,
+                    "reviewer": reviewer,
+                    "rating": row.rating,
+                    "comment": row.comment,
+                    "helpful_count": row.helpful_count,
+                    "created_at": row.created_at,
+                }).to_string());
+            }
+        }
+    }
+}
+
+/// ID reviewer anonim: HMAC-SHA256(key, book_id + user_id), 16 byte pertama dalam hex
+/// Stabil antar export buku yang sama, tapi tidak bisa di-link ke user atau ke export buku lain
+pub fn anonymize_reviewer(key: &[u8], book_id: Uuid, user_id: Uuid) -> String {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC menerima key sepanjang apapun");
+    mac.update(book_id.as_bytes());
+    mac.update(user_id.as_bytes());
+    hex::encode(&mac.finalize().into_bytes()[..16])
+}
+
+/// Escape field CSV, prefix `'` untuk nilai yang bisa dieksekusi sebagai formula spreadsheet
+pub(crate) fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+/// Stream semua review buku per batch (REVIEW_EXPORT_BATCH_SIZE, default 500)
+/// Batch berikutnya baru diambil setelah client membaca batch sebelumnya
+/// REVIEW_EXPORT_ANONYMIZE_KEY: key HMAC untuk anonimisasi reviewer
+pub fn stream_reviews(pool: PgPool, book_id: Uuid, format: ReviewExportFormat) -> Body {
+    let batch_size = env::var("REVIEW_EXPORT_BATCH_SIZE")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(500)
+        .clamp(50, 5000);
+
+    let key = env::var("REVIEW_EXPORT_ANONYMIZE_KEY")
+        .unwrap_or_else(|_| "review-export-anonymize-key-change-me".to_string());
+
+    let (tx, rx) = mpsc::channel::<Result<String, std::io::Error>>(2);
+
+    tokio::spawn(async move {
+        if tx.send(Ok(format.header(book_id))).await.is_err() {
+            return;
+        }
+
+        let mut cursor = None;
+        let mut exported = 0usize;
+
+        loop {
+            let rows = match BookRepository::get_review_export_batch(&pool, book_id, cursor, batch_size).await {
+                Ok(rows) => rows,
+                Err(e) => {
+                    tracing::error!("Export review buku {} gagal setelah {} review: {}", book_id, exported, e);
+                    // Body diputus supaya client tahu file tidak lengkap
+                    let _ = tx.send(Err(std::io::Error::other("export review gagal"))).await;
+                    return;
+                }
+            };
+
+            let mut chunk = String::new();
+            for row in &rows {
+                let reviewer = anonymize_reviewer(key.as_bytes(), book_id, row.user_id);
+                format.push_row(&mut chunk, row, &reviewer, exported == 0);
+                exported += 1;
+            }
+
+            if !chunk.is_empty() && tx.send(Ok(chunk)).await.is_err() {
+                tracing::info!("Client memutus export review buku {}", book_id);
+                return;
+            }
+
+            if (rows.len() as i64) < batch_size {
+                break;
+            }
+            cursor = rows.last().map(|r| (r.created_at, r.id));
+        }
+
+        let _ = tx.send(Ok(format.footer().to_string())).await;
+        tracing::info!("Export review buku {} selesai: {} review", book_id, exported);
+    });
+
+    Body::from_stream(ReceiverStream::new(rx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_export_rows_anonymized_and_escaped() {
+        let book_a = Uuid::new_v4();
+        let book_b = Uuid::new_v4();
+        let user = Uuid::new_v4();
+
+        let reviewer = anonymize_reviewer(b"key", book_a, user);
+        assert_eq!(reviewer.len(), 32);
+        assert_eq!(reviewer, anonymize_reviewer(b"key", book_a, user));
+        assert_ne!(reviewer, anonymize_reviewer(b"key", book_b, user));
+        assert!(!reviewer.contains(&user.simple().to_string()));
+
+        assert_eq!(csv_field("Bagus sekali"), "Bagus sekali");
+        assert_eq!(csv_field("Bagus, \"mantap\""), "\"Bagus, \"\"mantap\"\"\"");
+        assert_eq!(csv_field("=HYPERLINK(\"x\")"), "\"'=HYPERLINK(\"\"x\"\")\"");
+
+        let row = ReviewExportRecord {
+            id: Uuid::new_v4(),
+            user_id: user,
+            rating: 5,
+            comment: "Isi buku sangat membantu".to_string(),
+            helpful_count: 3,
+            created_at: Utc::now(),
+        };
+
+        let mut json = ReviewExportFormat::Json.header(book_a);
+        ReviewExportFormat::Json.push_row(&mut json, &row, &reviewer, true);
+        ReviewExportFormat::Json.push_row(&mut json, &row, &reviewer, false);
+        json.push_str(ReviewExportFormat::Json.footer());
+
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["reviews"].as_array().unwrap().len(), 2);
+        assert_eq!(parsed["reviews"][0]["reviewer"], reviewer);
+        assert!(parsed["reviews"][0].get("user_id").is_none());
+
+        assert_eq!(ReviewExportFormat::parse(None), Some(ReviewExportFormat::Csv));
+        assert_eq!(ReviewExportFormat::parse(Some("JSON")), Some(ReviewExportFormat::Json));
+        assert_eq!(ReviewExportFormat::parse(Some("xml")), None);
+    }
+}
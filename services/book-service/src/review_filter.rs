@@ -0,0 +1,180 @@
+// /pdf-bookstore/services/book-service/src/review_filter.rs
+
+use std::{collections::HashSet, env, fs};
+
+/// Batas keras panjang comment (sesuai CHECK constraint tabel book_reviews)
+const HARD_MAX_COMMENT_LENGTH: usize = 1000;
+
+/// Filter konten review: batas panjang + profanity/spam (opsional)
+/// Dimuat sekali saat startup dan disimpan di AppState
+#[derive(Debug, Clone)]
+pub struct ReviewFilter {
+    pub enabled: bool,
+    pub max_comment_length: usize,
+    /// Kata tunggal, dicek per kata
+    blocked_words: HashSet<String>,
+    /// Entry multi-kata ("kata kasar"), dicek sebagai urutan kata utuh
+    blocked_phrases: Vec<String>,
+    max_links: usize,
+}
+
+impl ReviewFilter {
+    /// Load konfigurasi filter dari env
+    /// REVIEW_BLOCKLIST_PATH: file word list (satu kata / frasa per baris, `#` untuk komentar)
+    /// REVIEW_BLOCKLIST: tambahan kata / frasa, comma-separated
+    pub fn from_env() -> Self {
+        let enabled = env::var("REVIEW_FILTER_ENABLED")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+
+        let max_comment_length = env::var("REVIEW_MAX_LENGTH")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(HARD_MAX_COMMENT_LENGTH)
+            .clamp(10, HARD_MAX_COMMENT_LENGTH);
+
+        let max_links = env::var("REVIEW_MAX_LINKS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1);
+
+        let mut entries = Vec::new();
+
+        if let Ok(path) = env::var("REVIEW_BLOCKLIST_PATH") {
+            match fs::read_to_string(&path) {
+                Ok(content) => entries.extend(
+                    content.lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                        .map(String::from)
+                ),
+                Err(e) => tracing::warn!("Gagal membaca review blocklist {}: {}", path, e),
+            }
+        }
+
+        if let Ok(words) = env::var("REVIEW_BLOCKLIST") {
+            entries.extend(words.split(',').map(String::from));
+        }
+
+        let filter = Self::new(enabled, max_comment_length, &entries, max_links);
+        if enabled {
+            tracing::info!("Review filter aktif: {} kata/frasa diblokir, max {} karakter",
+                filter.blocked_words.len() + filter.blocked_phrases.len(), max_comment_length);
+        }
+
+        filter
+    }
+
+    /// Entry blocklist dinormalisasi dengan tokenisasi yang sama dengan comment
+    fn new(enabled: bool, max_comment_length: usize, entries: &[String], max_links: usize) -> Self {
+        let mut blocked_words = HashSet::new();
+        let mut blocked_phrases = Vec::new();
+
+        for entry in entries {
+            let normalized = words(&entry.to_lowercase()).join(" ");
+            if normalized.is_empty() {
+                continue;
+            }
+            if normalized.contains(' ') {
+                blocked_phrases.push(normalized);
+            } else {
+                blocked_words.insert(normalized);
+            }
+        }
+
+        Self { enabled, max_comment_length, blocked_words, blocked_phrases, max_links }
+    }
+
+    /// Cek comment review, return alasan penolakan jika tidak lolos
+    pub fn check(&self, comment: &str) -> Result<(), String> {
+        if comment.chars().count() > self.max_comment_length {
+            return Err(format!("Comment maksimal {} karakter", self.max_comment_length));
+        }
+
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let lowered = comment.to_lowercase();
+
+        let comment_words = words(&lowered);
+        let joined = format!(" {} ", comment_words.join(" "));
+
+        let has_blocked_word = comment_words.iter().any(|word| self.blocked_words.contains(*word))
+            || self.blocked_phrases.iter().any(|phrase| joined.contains(&format!(" {} ", phrase)));
+        if has_blocked_word {
+            return Err("Review mengandung kata yang tidak diizinkan".to_string());
+        }
+
+        if count_links(&lowered) > self.max_links {
+            return Err("Review mengandung terlalu banyak link".to_string());
+        }
+
+        if has_repeated_run(comment, 10) {
+            return Err("Review terdeteksi sebagai spam".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// Kata alfanumerik dari teks (lowercase), pemisah apa pun dianggap batas kata
+fn words(text: &str) -> Vec<&str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Hitung link: tiap "http(s)://" satu link, "www." hanya dihitung jika tidak didahului skema
+fn count_links(lowered: &str) -> usize {
+    let with_scheme = lowered.matches("http://").count() + lowered.matches("https://").count();
+    let bare_www = lowered.match_indices("www.")
+        .filter(|(index, _)| !lowered[..*index].ends_with("://"))
+        .count();
+
+    with_scheme + bare_www
+}
+
+/// Deteksi karakter yang sama berulang >= `limit` kali berturut-turut
+fn has_repeated_run(text: &str, limit: usize) -> bool {
+    let mut previous = None;
+    let mut run = 0;
+
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        if Some(c) == previous {
+            run += 1;
+            if run >= limit {
+                return true;
+            }
+        } else {
+            previous = Some(c);
+            run = 1;
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_links_counted_once_and_phrases_blocked() {
+        let entries = ["Spam", "kata  kasar", "beli-sekarang", " "].map(String::from);
+        let filter = ReviewFilter::new(true, 1000, &entries, 1);
+
+        assert_eq!(count_links("lihat https://www.example.com"), 1);
+        assert_eq!(count_links("http://a.com dan www.b.com"), 2);
+        assert_eq!(count_links("tanpa link"), 0);
+        assert!(filter.check("Bagus, detail di https://www.example.com/review").is_ok());
+        assert!(filter.check("https://www.a.com lalu https://www.b.com").is_err());
+
+        assert!(filter.check("Buku ini SPAM sekali").is_err());
+        assert!(filter.check("Isinya penuh kata kasar, kecewa").is_err());
+        assert!(filter.check("Ayo BELI sekarang juga!").is_err());
+        // Frasa hanya cocok sebagai kata utuh berurutan
+        assert!(filter.check("kata kasarnya tidak ada di sini").is_ok());
+        assert!(filter.check("kasar kata, urutan berbeda").is_ok());
+    }
+}
@@ -0,0 +1,157 @@
+// /pdf-bookstore/services/book-service/src/review_limiter.rs
+
+use std::{collections::{HashMap, VecDeque}, env, time::{Duration, Instant}};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Jumlah key maksimal sebelum entry kadaluarsa dibersihkan
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Penolakan rate limit review: limit window yang penuh dan detik sampai slot berikutnya
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReviewRateLimited {
+    pub limit: usize,
+    pub retry_after_secs: u64,
+}
+
+/// Rate limit create/update review (upsert) per user
+/// Dua window: edit per (user, buku) dan total review per user, keduanya per jam
+pub struct ReviewRateLimiter {
+    per_book: RwLock<SlidingWindow<(Uuid, Uuid)>>,
+    per_user: RwLock<SlidingWindow<Uuid>>,
+    /// Review dengan teks identik di >= N buku lain dianggap spam (0 = nonaktif)
+    pub duplicate_max_books: i64,
+    /// Comment lebih pendek dari ini tidak dicek duplikat ("Bagus!" wajar berulang)
+    pub duplicate_min_length: usize,
+    /// Rentang waktu pengecekan duplikat
+    pub duplicate_window: Duration,
+}
+
+impl ReviewRateLimiter {
+    /// REVIEW_EDITS_PER_BOOK_PER_HOUR (default 5), REVIEW_MAX_PER_USER_PER_HOUR (default 20),
+    /// REVIEW_DUPLICATE_MAX_BOOKS (default 3), REVIEW_DUPLICATE_MIN_LENGTH (default 20),
+    /// REVIEW_DUPLICATE_WINDOW_HOURS (default 24)
+    pub fn from_env() -> Self {
+        let read = |key: &str, default: u64| {
+            env::var(key).ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(default)
+        };
+
+        let hour = Duration::from_secs(3600);
+
+        Self {
+            per_book: RwLock::new(SlidingWindow::new(read("REVIEW_EDITS_PER_BOOK_PER_HOUR", 5) as usize, hour)),
+            per_user: RwLock::new(SlidingWindow::new(read("REVIEW_MAX_PER_USER_PER_HOUR", 20) as usize, hour)),
+            duplicate_max_books: read("REVIEW_DUPLICATE_MAX_BOOKS", 3) as i64,
+            duplicate_min_length: read("REVIEW_DUPLICATE_MIN_LENGTH", 20) as usize,
+            duplicate_window: Duration::from_secs(read("REVIEW_DUPLICATE_WINDOW_HOURS", 24) * 3600),
+        }
+    }
+
+    /// Catat percobaan review, return window yang penuh (tunggu terlama) jika limit terlampaui
+    /// Percobaan yang ditolak tidak ikut dihitung
+    pub async fn check(&self, user_id: Uuid, book_id: Uuid) -> Result<(), ReviewRateLimited> {
+        let now = Instant::now();
+        let mut per_book = self.per_book.write().await;
+        let mut per_user = self.per_user.write().await;
+
+        let limited = [
+            per_book.retry_after(&(user_id, book_id), now).map(|wait| (wait, per_book.limit)),
+            per_user.retry_after(&user_id, now).map(|wait| (wait, per_user.limit)),
+        ]
+        .into_iter()
+        .flatten()
+        .max();
+
+        if let Some((wait, limit)) = limited {
+            return Err(ReviewRateLimited { limit, retry_after_secs: wait.as_secs().max(1) });
+        }
+
+        per_book.record((user_id, book_id), now);
+        per_user.record(user_id, now);
+        Ok(())
+    }
+}
+
+/// Sliding window log: simpan timestamp hit per key dalam window
+struct SlidingWindow<K> {
+    limit: usize,
+    window: Duration,
+    hits: HashMap<K, VecDeque<Instant>>,
+}
+
+impl<K: std::hash::Hash + Eq> SlidingWindow<K> {
+    fn new(limit: usize, window: Duration) -> Self {
+        Self { limit, window, hits: HashMap::new() }
+    }
+
+    /// None jika masih boleh, Some(durasi) sampai hit tertua keluar window
+    fn retry_after(&mut self, key: &K, now: Instant) -> Option<Duration> {
+        if self.limit == 0 {
+            return None;
+        }
+
+        let hits = self.hits.get_mut(key)?;
+        while hits.front().is_some_and(|t| now.duration_since(*t) >= self.window) {
+            hits.pop_front();
+        }
+
+        if hits.len() < self.limit {
+            return None;
+        }
+
+        hits.front().map(|oldest| self.window.saturating_sub(now.duration_since(*oldest)))
+    }
+
+    fn record(&mut self, key: K, now: Instant) {
+        if self.limit == 0 {
+            return;
+        }
+
+        if self.hits.len() >= PRUNE_THRESHOLD {
+            let window = self.window;
+            self.hits.retain(|_, hits| hits.back().is_some_and(|t| now.duration_since(*t) < window));
+        }
+
+        self.hits.entry(key).or_default().push_back(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sliding_window_limits_and_expires() {
+        let mut window = SlidingWindow::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert!(window.retry_after(&"a", start).is_none());
+        window.record("a", start);
+        window.record("a", start + Duration::from_secs(10));
+
+        let wait = window.retry_after(&"a", start + Duration::from_secs(20)).unwrap();
+        assert_eq!(wait, Duration::from_secs(40));
+        assert!(window.retry_after(&"b", start).is_none());
+
+        // Hit pertama keluar window
+        assert!(window.retry_after(&"a", start + Duration::from_secs(61)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rejection_reports_full_window_limit() {
+        let limiter = ReviewRateLimiter {
+            per_book: RwLock::new(SlidingWindow::new(2, Duration::from_secs(3600))),
+            per_user: RwLock::new(SlidingWindow::new(20, Duration::from_secs(3600))),
+            duplicate_max_books: 0,
+            duplicate_min_length: 20,
+            duplicate_window: Duration::from_secs(3600),
+        };
+        let (user_id, book_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assert!(limiter.check(user_id, book_id).await.is_ok());
+        assert!(limiter.check(user_id, book_id).await.is_ok());
+        let limited = limiter.check(user_id, book_id).await.unwrap_err();
+        assert_eq!(limited.limit, 2);
+        assert!(limited.retry_after_secs > 3500 && limited.retry_after_secs <= 3600);
+    }
+}
@@ -0,0 +1,172 @@
+// /pdf-bookstore/services/book-service/src/stats_recompute.rs
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::{database::BookRepository, AppState};
+use service_common::shutdown::Shutdown;
+
+/// Jumlah buku per batch update, progress diperbarui tiap batch
+const RECOMPUTE_BATCH_SIZE: usize = 200;
+
+/// Status job recompute stats (satu job berjalan dalam satu waktu)
+#[derive(Debug, Clone, Serialize)]
+pub struct RecomputeJob {
+    pub job_id: Uuid,
+    pub status: &'static str,
+    pub book_id: Option<Uuid>,
+    pub total_books: usize,
+    pub processed_books: usize,
+    pub helpful_counts_updated: u64,
+    pub download_counts_updated: u64,
+    pub rows_updated: u64,
+    pub started_by: Uuid,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+impl RecomputeJob {
+    pub fn is_running(&self) -> bool {
+        self.status == "running"
+    }
+}
+
+/// Hitung ulang field denormalisasi dari tabel sumber di background:
+/// - book_reviews.helpful_count dari review_helpful_votes
+/// - books.download_count minimal sejumlah order paid (distinct), karena tiap pembelian menambah counter
+///
+/// Cache rating & internal book dibersihkan setelah job selesai
+pub struct StatsRecomputer {
+    shutdown: Shutdown,
+    job: RwLock<Option<RecomputeJob>>,
+}
+
+impl StatsRecomputer {
+    pub fn new(shutdown: &Shutdown) -> Self {
+        Self {
+            shutdown: shutdown.clone(),
+            job: RwLock::new(None),
+        }
+    }
+
+    /// Job terakhir (sedang berjalan atau sudah selesai)
+    pub async fn current(&self) -> Option<RecomputeJob> {
+        self.job.read().await.clone()
+    }
+
+    /// Mulai job baru, Err(job berjalan) jika masih ada job lain
+    pub async fn start(state: &AppState, book_id: Option<Uuid>, started_by: Uuid) -> Result<RecomputeJob, RecomputeJob> {
+        let recomputer = state.stats_recompute.clone();
+
+        let job = {
+            let mut current = recomputer.job.write().await;
+            if let Some(running) = current.as_ref().filter(|job| job.is_running()) {
+                return Err(running.clone());
+            }
+
+            let job = RecomputeJob {
+                job_id: Uuid::new_v4(),
+                status: "running",
+                book_id,
+                total_books: 0,
+                processed_books: 0,
+                helpful_counts_updated: 0,
+                download_counts_updated: 0,
+                rows_updated: 0,
+                started_by,
+                started_at: Utc::now(),
+                finished_at: None,
+                error: None,
+            };
+            *current = Some(job.clone());
+            job
+        };
+
+        let state = state.clone();
+        let started = recomputer.shutdown.spawn(async move {
+            let result = run_job(&state, book_id).await;
+            let recomputer = &state.stats_recompute;
+
+            let mut current = recomputer.job.write().await;
+            if let Some(job) = current.as_mut() {
+                job.finished_at = Some(Utc::now());
+                match result {
+                    Ok(true) => job.status = "completed",
+                    Ok(false) => job.status = "cancelled",
+                    Err(e) => {
+                        tracing::error!("Recompute stats gagal: {}", e);
+                        job.status = "failed";
+                        job.error = Some(e);
+                    }
+                }
+
+                tracing::info!(
+                    "Recompute stats {} {}: {} buku, {} row diperbarui",
+                    job.job_id, job.status, job.processed_books, job.rows_updated
+                );
+            }
+        });
+
+        // Service sedang shutdown: job tidak dijalankan, jangan tertinggal berstatus running
+        if !started {
+            recomputer.update(|job| {
+                job.status = "cancelled";
+                job.finished_at = Some(Utc::now());
+            }).await;
+            return Ok(RecomputeJob { status: "cancelled", finished_at: Some(Utc::now()), ..job });
+        }
+
+        Ok(job)
+    }
+
+    async fn update(&self, apply: impl FnOnce(&mut RecomputeJob)) {
+        if let Some(job) = self.job.write().await.as_mut() {
+            apply(job);
+        }
+    }
+}
+
+/// Ok(false) jika dihentikan karena shutdown
+async fn run_job(state: &AppState, book_id: Option<Uuid>) -> Result<bool, String> {
+    let recomputer = &state.stats_recompute;
+
+    let book_ids = BookRepository::get_book_ids_for_recompute(&state.db, book_id).await
+        .map_err(|e| e.to_string())?;
+    recomputer.update(|job| job.total_books = book_ids.len()).await;
+
+    for batch in book_ids.chunks(RECOMPUTE_BATCH_SIZE) {
+        if recomputer.shutdown.token.is_cancelled() {
+            return Ok(false);
+        }
+
+        let helpful = BookRepository::recompute_helpful_counts(&state.db, batch).await
+            .map_err(|e| e.to_string())?;
+        let downloads = BookRepository::repair_download_counts(&state.db, batch).await
+            .map_err(|e| e.to_string())?;
+
+        recomputer.update(|job| {
+            job.processed_books += batch.len();
+            job.helpful_counts_updated += helpful;
+            job.download_counts_updated += downloads;
+            job.rows_updated += helpful + downloads;
+        }).await;
+    }
+
+    match book_id {
+        Some(id) => {
+            state.rating_cache.write().await.remove(&id);
+            state.popularity_cache.write().await.remove(&id);
+            state.internal_book_cache.write().await.remove(&id);
+        }
+        None => {
+            state.rating_cache.write().await.clear();
+            state.popularity_cache.write().await.clear();
+            state.internal_book_cache.write().await.clear();
+        }
+    }
+
+    Ok(true)
+}
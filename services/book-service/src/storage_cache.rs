@@ -0,0 +1,90 @@
+// /pdf-bookstore/services/book-service/src/storage_cache.rs
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::{env, sync::Arc};
+
+/// Cache-Control untuk file di /storage
+/// Cover/preview memakai nama file unik (UUID + timestamp) sehingga aman di-cache immutable,
+/// file lain (misal PDF) tidak boleh di-cache shared cache
+#[derive(Debug, Clone)]
+pub struct StorageCachePolicy {
+    immutable_dirs: Vec<String>,
+    immutable_value: HeaderValue,
+}
+
+impl StorageCachePolicy {
+    /// STORAGE_CACHE_MAX_AGE_SECONDS (default 1 tahun, 0 = tanpa cache)
+    /// STORAGE_IMMUTABLE_DIRS: sub-folder content-addressed, comma-separated (default "covers,previews")
+    pub fn from_env() -> Self {
+        let max_age = env::var("STORAGE_CACHE_MAX_AGE_SECONDS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(31_536_000);
+
+        let immutable_dirs = env::var("STORAGE_IMMUTABLE_DIRS")
+            .unwrap_or_else(|_| "covers,previews".to_string())
+            .split(',')
+            .map(|d| d.trim().trim_matches('/').to_string())
+            .filter(|d| !d.is_empty())
+            .collect();
+
+        Self::new(immutable_dirs, max_age)
+    }
+
+    fn new(immutable_dirs: Vec<String>, max_age: u64) -> Self {
+        let immutable_value = if max_age == 0 {
+            HeaderValue::from_static("no-cache")
+        } else {
+            HeaderValue::from_str(&format!("public, max-age={}, immutable", max_age))
+                .unwrap_or_else(|_| HeaderValue::from_static("no-cache"))
+        };
+
+        Self { immutable_dirs, immutable_value }
+    }
+
+    /// Path relatif terhadap /storage (nest_service sudah strip prefix)
+    fn cache_control_for(&self, path: &str) -> HeaderValue {
+        let dir = path.trim_start_matches('/').split('/').next().unwrap_or("");
+
+        if self.immutable_dirs.iter().any(|d| d == dir) {
+            self.immutable_value.clone()
+        } else {
+            HeaderValue::from_static("private, no-store")
+        }
+    }
+}
+
+/// Middleware untuk ServeDir /storage
+pub async fn storage_cache_headers(
+    State(policy): State<Arc<StorageCachePolicy>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let cache_control = policy.cache_control_for(req.uri().path());
+    let mut response = next.run(req).await;
+
+    if response.status().is_success() || response.status() == axum::http::StatusCode::NOT_MODIFIED {
+        response.headers_mut().insert(header::CACHE_CONTROL, cache_control);
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_control_per_directory() {
+        let policy = StorageCachePolicy::new(vec!["covers".to_string()], 600);
+
+        assert_eq!(policy.cache_control_for("/covers/abc_123.jpg"), "public, max-age=600, immutable");
+        assert_eq!(policy.cache_control_for("/books/abc_123.pdf"), "private, no-store");
+        assert_eq!(policy.cache_control_for("/covers-old/x.jpg"), "private, no-store");
+    }
+}
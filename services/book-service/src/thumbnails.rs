@@ -0,0 +1,260 @@
+// /pdf-bookstore/services/book-service/src/thumbnails.rs
+
+use image::{imageops::FilterType, ImageFormat};
+use std::{env, path::{Path, PathBuf}};
+
+use crate::cover_variants::{is_file, variant_storage_paths};
+use crate::models::CoverThumbnail;
+
+/// Batas lebar thumbnail yang boleh dikonfigurasi
+const MIN_THUMBNAIL_WIDTH: u32 = 16;
+const MAX_THUMBNAIL_WIDTH: u32 = 2000;
+
+/// Konfigurasi thumbnail cover yang di-generate saat upload
+/// Naming convention: `/storage/covers/{nama}.jpg` -> `/storage/covers/{nama}_w{lebar}.jpg`
+#[derive(Debug, Clone)]
+pub struct ThumbnailConfig {
+    pub widths: Vec<u32>,
+    upload_dir: PathBuf,
+}
+
+impl ThumbnailConfig {
+    /// Load dari env
+    /// THUMBNAIL_SIZES: daftar lebar px, comma-separated (default "100,200,400", kosong = nonaktif)
+    pub fn from_env() -> Self {
+        let mut widths = env::var("THUMBNAIL_SIZES")
+            .unwrap_or_else(|_| "100,200,400".to_string())
+            .split(',')
+            .filter_map(|w| w.trim().parse::<u32>().ok())
+            .map(|w| w.clamp(MIN_THUMBNAIL_WIDTH, MAX_THUMBNAIL_WIDTH))
+            .collect::<Vec<_>>();
+        widths.sort_unstable();
+        widths.dedup();
+
+        let upload_dir = PathBuf::from(
+            env::var("UPLOAD_DIR").unwrap_or_else(|_| "./storage".to_string())
+        );
+
+        if !widths.is_empty() {
+            tracing::info!("Thumbnail cover aktif untuk lebar {:?}", widths);
+        }
+
+        Self { widths, upload_dir }
+    }
+
+    /// Path relatif thumbnail untuk cover dan lebar tertentu
+    pub fn thumbnail_path(cover_path: &str, width: u32) -> String {
+        match cover_path.rsplit_once('.') {
+            Some((stem, ext)) if !ext.contains('/') => format!("{}_w{}.{}", stem, width, ext),
+            _ => format!("{}_w{}", cover_path, width),
+        }
+    }
+
+    /// Generate semua ukuran thumbnail untuk cover yang baru di-upload
+    /// Gagal generate tidak menggagalkan upload, cukup di-log (read path fallback ke cover asli)
+    pub async fn generate(&self, cover_path: &str) {
+        if self.widths.is_empty() {
+            return;
+        }
+
+        let Some(source) = self.resolve(cover_path) else {
+            tracing::warn!("Path cover tidak valid untuk thumbnail: {}", cover_path);
+            return;
+        };
+
+        let targets = self.widths.iter()
+            .filter_map(|&w| self.resolve(&Self::thumbnail_path(cover_path, w)).map(|path| (w, path)))
+            .collect::<Vec<_>>();
+
+        let result = tokio::task::spawn_blocking(move || generate_blocking(&source, &targets)).await;
+
+        match result {
+            Ok(Ok(count)) => tracing::debug!("{} thumbnail dibuat untuk {}", count, cover_path),
+            Ok(Err(e)) => tracing::warn!("Gagal membuat thumbnail {}: {}", cover_path, e),
+            Err(e) => tracing::warn!("Task thumbnail {} gagal: {}", cover_path, e),
+        }
+    }
+
+    /// Render thumbnail dari isi cover yang tidak ada di disk lokal (cover di object storage)
+    /// Return (path thumbnail sesuai naming convention, isi file); gagal decode = kosong + di-log
+    pub async fn render(&self, cover_path: &str, data: Vec<u8>) -> Vec<(String, Vec<u8>)> {
+        if self.widths.is_empty() {
+            return Vec::new();
+        }
+
+        let widths = self.widths.clone();
+        let result = tokio::task::spawn_blocking(move || render_blocking(&data, &widths)).await;
+
+        match result {
+            Ok(Ok(rendered)) => rendered.into_iter()
+                .map(|(width, bytes)| (Self::thumbnail_path(cover_path, width), bytes))
+                .collect(),
+            Ok(Err(e)) => {
+                tracing::warn!("Gagal membuat thumbnail {}: {}", cover_path, e);
+                Vec::new()
+            }
+            Err(e) => {
+                tracing::warn!("Task thumbnail {} gagal: {}", cover_path, e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Hapus thumbnail dan variant format (WebP) milik cover (dipanggil saat cover diganti atau buku dihapus)
+    pub async fn remove(&self, cover_path: &str) {
+        let thumbnails = self.widths.iter().map(|&width| Self::thumbnail_path(cover_path, width));
+        let variants = std::iter::once(cover_path.to_string())
+            .chain(thumbnails.clone())
+            .flat_map(|path| variant_storage_paths(&path));
+
+        for relative in thumbnails.chain(variants) {
+            if let Some(path) = self.resolve(&relative) {
+                if let Err(e) = tokio::fs::remove_file(&path).await {
+                    if e.kind() != std::io::ErrorKind::NotFound {
+                        tracing::warn!("Gagal menghapus file turunan cover {}: {}", path.display(), e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Thumbnail yang tersedia di disk untuk cover (cover lama tanpa thumbnail = kosong)
+    pub async fn available(&self, cover_path: &str, base_url: &str) -> Vec<CoverThumbnail> {
+        let mut thumbnails = Vec::with_capacity(self.widths.len());
+        for &width in &self.widths {
+            let relative = Self::thumbnail_path(cover_path, width);
+            if let Some(path) = self.resolve(&relative) {
+                if is_file(&path).await {
+                    thumbnails.push(CoverThumbnail { width, url: format!("{}{}", base_url, relative) });
+                }
+            }
+        }
+        thumbnails
+    }
+
+    /// Map `/storage/...` ke path di UPLOAD_DIR, tolak path traversal
+    fn resolve(&self, relative_path: &str) -> Option<PathBuf> {
+        let inner = relative_path.strip_prefix("/storage/")?;
+        if inner.contains("..") || inner.contains("//") || inner.contains('\\') {
+            return None;
+        }
+        Some(self.upload_dir.join(inner))
+    }
+}
+
+/// Decode cover sekali lalu resize ke tiap lebar (aspect ratio dijaga, tidak upscale)
+fn generate_blocking(source: &Path, targets: &[(u32, PathBuf)]) -> Result<usize, String> {
+    let format = ImageFormat::from_path(source).map_err(|e| e.to_string())?;
+    let original = image::open(source).map_err(|e| e.to_string())?;
+
+    for (width, target) in targets {
+        resize(&original, *width, format)
+            .save_with_format(target, format)
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(targets.len())
+}
+
+/// Sama dengan generate_blocking tapi dari bytes ke bytes, format mengikuti isi cover
+fn render_blocking(data: &[u8], widths: &[u32]) -> Result<Vec<(u32, Vec<u8>)>, String> {
+    let format = image::guess_format(data).map_err(|e| e.to_string())?;
+    let original = image::load_from_memory_with_format(data, format).map_err(|e| e.to_string())?;
+
+    widths.iter()
+        .map(|&width| {
+            let mut bytes = std::io::Cursor::new(Vec::new());
+            resize(&original, width, format)
+                .write_to(&mut bytes, format)
+                .map_err(|e| e.to_string())?;
+            Ok((width, bytes.into_inner()))
+        })
+        .collect()
+}
+
+/// Resize ke lebar tertentu (aspect ratio dijaga, tidak upscale)
+fn resize(original: &image::DynamicImage, width: u32, format: ImageFormat) -> image::DynamicImage {
+    let thumbnail = if original.width() > width {
+        let height = (original.height() as u64 * width as u64 / original.width() as u64).max(1) as u32;
+        original.resize_exact(width, height, FilterType::Triangle)
+    } else {
+        original.clone()
+    };
+
+    // JPEG tidak support alpha channel
+    if format == ImageFormat::Jpeg {
+        image::DynamicImage::ImageRgb8(thumbnail.to_rgb8())
+    } else {
+        thumbnail
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thumbnail_path_naming() {
+        assert_eq!(
+            ThumbnailConfig::thumbnail_path("/storage/covers/abc_123.jpg", 200),
+            "/storage/covers/abc_123_w200.jpg"
+        );
+        assert_eq!(
+            ThumbnailConfig::thumbnail_path("/storage/covers/noext", 100),
+            "/storage/covers/noext_w100"
+        );
+    }
+
+    #[test]
+    fn test_generate_resizes_without_upscaling() {
+        let dir = env::temp_dir().join(format!("thumb-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("covers")).unwrap();
+        image::RgbImage::new(300, 450).save(dir.join("covers/cover.png")).unwrap();
+
+        let config = ThumbnailConfig { widths: vec![100, 400], upload_dir: dir.clone() };
+        let targets = config.widths.iter()
+            .map(|&w| (w, config.resolve(&ThumbnailConfig::thumbnail_path("/storage/covers/cover.png", w)).unwrap()))
+            .collect::<Vec<_>>();
+
+        generate_blocking(&dir.join("covers/cover.png"), &targets).unwrap();
+
+        assert_eq!(image::image_dimensions(dir.join("covers/cover_w100.png")).unwrap(), (100, 150));
+        assert_eq!(image::image_dimensions(dir.join("covers/cover_w400.png")).unwrap(), (300, 450));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_render_from_bytes_for_object_storage() {
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbImage::new(300, 450).write_to(&mut png, ImageFormat::Png).unwrap();
+
+        let config = ThumbnailConfig { widths: vec![100, 400], upload_dir: PathBuf::from("./storage") };
+        let rendered = config.render("covers/cover.png", png.into_inner()).await;
+
+        assert_eq!(rendered.iter().map(|(path, _)| path.as_str()).collect::<Vec<_>>(), ["covers/cover_w100.png", "covers/cover_w400.png"]);
+        assert_eq!(image::load_from_memory(&rendered[0].1).unwrap().width(), 100);
+        assert!(config.render("covers/broken.png", b"bukan image".to_vec()).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_remove_cleans_thumbnails_and_variants() {
+        let dir = env::temp_dir().join(format!("thumb-remove-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("covers/variants")).unwrap();
+        for file in ["covers/cover.png", "covers/cover_w100.png", "covers/cover_w400.png", "covers/variants/cover.png.webp", "covers/variants/cover_w100.png.webp"] {
+            std::fs::write(dir.join(file), b"x").unwrap();
+        }
+
+        let config = ThumbnailConfig { widths: vec![100, 400], upload_dir: dir.clone() };
+        assert_eq!(config.available("/storage/covers/cover.png", "http://x").await.len(), 2);
+
+        config.remove("/storage/covers/cover.png").await;
+
+        assert!(config.available("/storage/covers/cover.png", "http://x").await.is_empty());
+        assert!(!dir.join("covers/variants/cover.png.webp").exists());
+        assert!(!dir.join("covers/variants/cover_w100.png.webp").exists());
+        // Cover asli tetap ada (soft delete buku bisa dipulihkan)
+        assert!(dir.join("covers/cover.png").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
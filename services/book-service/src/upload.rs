@@ -16,6 +16,7 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use rand::Rng;
+use service_common::shutdown::Shutdown;
 
 // ===== ERROR HANDLING =====
 #[derive(Error, Debug)]
@@ -72,6 +73,7 @@ const ALLOWED_FILE_TYPES: &[FileTypeValidator] = &[
 // ===== CONCURRENT UPLOAD TRACKING =====
 
 // Tracker untuk membatasi upload concurrent per user dengan auto cleanup
+// Satu instance dibagi lewat AppState supaya limit berlaku lintas request
 #[derive(Clone)]
 pub struct UploadTracker {
     active_uploads: Arc<RwLock<HashMap<String, chrono::DateTime<chrono::Utc>>>>,
@@ -79,19 +81,23 @@ pub struct UploadTracker {
 }
 
 impl UploadTracker {
-    pub fn new(max_concurrent: usize) -> Self {
+    pub fn new(max_concurrent: usize, shutdown: &Shutdown) -> Self {
         let tracker = Self {
             active_uploads: Arc::new(RwLock::new(HashMap::new())),
             max_concurrent,
         };
         
-        // Spawn periodic cleanup task
+        // Spawn periodic cleanup task, berhenti saat shutdown
         let uploads_clone = tracker.active_uploads.clone();
-        tokio::spawn(async move {
+        let token = shutdown.token.clone();
+        shutdown.spawn(async move {
             let mut interval = tokio::time::interval(std::time::Duration::from_secs(300));
             
             loop {
-                interval.tick().await;
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = interval.tick() => {}
+                }
                 
                 let mut uploads = uploads_clone.write().await;
                 let cutoff = chrono::Utc::now() - chrono::Duration::minutes(5);
@@ -142,7 +148,7 @@ pub struct FileUploader {
 
 impl FileUploader {
     // Initialize uploader dengan secure directories dan concurrent limits
-    pub fn new() -> Result<Self, UploadError> {
+    pub fn new(upload_tracker: UploadTracker) -> Result<Self, UploadError> {
         let upload_dir = PathBuf::from(env::var("UPLOAD_DIR").unwrap_or_else(|_| "./storage".to_string()));
         let temp_dir = upload_dir.join("temp");
 
@@ -151,15 +157,10 @@ impl FileUploader {
         Self::create_secure_directory(&upload_dir.join("books"))?;
         Self::create_secure_directory(&upload_dir.join("covers"))?;
 
-        let max_concurrent = env::var("MAX_CONCURRENT_UPLOADS")
-            .unwrap_or_else(|_| "5".to_string())
-            .parse()
-            .unwrap_or(5);
-
         Ok(Self {
             upload_dir,
             temp_dir,
-            upload_tracker: UploadTracker::new(max_concurrent),
+            upload_tracker,
         })
     }
 
@@ -178,6 +179,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Batas upload terlampaui: {}", e),
                     error_code: Some("CONCURRENT_UPLOAD_LIMIT".to_string()),
+                    details: None,
                 })
             ))?;
 
@@ -198,6 +200,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Error parsing multipart: {}", e),
                     error_code: Some("MULTIPART_ERROR".to_string()),
+                    details: None,
                 })
             ))? {
             
@@ -214,6 +217,7 @@ impl FileUploader {
                 success: false,
                 message: "File PDF tidak ditemukan dalam upload".to_string(),
                 error_code: Some("NO_FILE_FOUND".to_string()),
+                details: None,
             })
         ))
     }
@@ -232,6 +236,7 @@ impl FileUploader {
                     success: false,
                     message: "Gagal membuat direktori upload".to_string(),
                     error_code: Some("DIRECTORY_ERROR".to_string()),
+                    details: None,
                 })
             ));
         }
@@ -245,6 +250,7 @@ impl FileUploader {
                     success: false,
                     message: "Hanya file PDF yang diizinkan".to_string(),
                     error_code: Some("INVALID_FILE_TYPE".to_string()),
+                    details: None,
                 })
             ));
         }
@@ -269,6 +275,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Gagal membaca data file: {}", e),
                     error_code: Some("FILE_READ_ERROR".to_string()),
+                    details: None,
                 })
             ))?;
 
@@ -289,6 +296,7 @@ impl FileUploader {
                     message: format!("File terlalu besar: {:.2}MB (maks: {}MB)", 
                         file_size_mb, max_size_mb),
                     error_code: Some("FILE_TOO_LARGE".to_string()),
+                    details: None,
                 })
             ));
         }
@@ -300,10 +308,13 @@ impl FileUploader {
                     success: false,
                     message: "Format file PDF tidak valid".to_string(),
                     error_code: Some("INVALID_PDF".to_string()),
+                    details: None,
                 })
             ));
         }
 
+        Self::check_pdf_structure(&data)?;
+
         let mut file = fs::File::create(&file_path).await
             .map_err(|e| (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -311,6 +322,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Gagal membuat file: {}", e),
                     error_code: Some("FILE_CREATE_ERROR".to_string()),
+                    details: None,
                 })
             ))?;
 
@@ -321,6 +333,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Gagal menulis file: {}", e),
                     error_code: Some("FILE_WRITE_ERROR".to_string()),
+                    details: None,
                 })
             ))?;
 
@@ -331,6 +344,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Gagal flush file: {}", e),
                     error_code: Some("FILE_FLUSH_ERROR".to_string()),
+                    details: None,
                 })
             ))?;
 
@@ -355,6 +369,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Batas upload terlampaui: {}", e),
                     error_code: Some("CONCURRENT_UPLOAD_LIMIT".to_string()),
+                    details: None,
                 })
             ))?;
 
@@ -375,6 +390,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Error parsing multipart: {}", e),
                     error_code: Some("MULTIPART_ERROR".to_string()),
+                    details: None,
                 })
             ))? {
             
@@ -392,6 +408,7 @@ impl FileUploader {
                 success: false,
                 message: "Cover image tidak ditemukan dalam upload".to_string(),
                 error_code: Some("NO_IMAGE_FOUND".to_string()),
+                details: None,
             })
         ))
     }
@@ -410,6 +427,7 @@ impl FileUploader {
                     success: false,
                     message: "Gagal membuat direktori upload".to_string(),
                     error_code: Some("DIRECTORY_ERROR".to_string()),
+                    details: None,
                 })
             ));
         }
@@ -431,6 +449,7 @@ impl FileUploader {
                     message: format!("Tipe image tidak valid. Diizinkan: {}", 
                         allowed_extensions.join(", ")),
                     error_code: Some("INVALID_IMAGE_TYPE".to_string()),
+                    details: None,
                 })
             ));
         }
@@ -450,6 +469,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Gagal membaca data image: {}", e),
                     error_code: Some("FILE_READ_ERROR".to_string()),
+                    details: None,
                 })
             ))?;
 
@@ -466,6 +486,7 @@ impl FileUploader {
                     message: format!("Image terlalu besar: {:.2}MB (maks: {}MB)", 
                         file_size_mb, max_size_mb),
                     error_code: Some("IMAGE_TOO_LARGE".to_string()),
+                    details: None,
                 })
             ));
         }
@@ -477,10 +498,13 @@ impl FileUploader {
                     success: false,
                     message: "Format file image tidak valid".to_string(),
                     error_code: Some("INVALID_IMAGE_FORMAT".to_string()),
+                    details: None,
                 })
             ));
         }
 
+        Self::check_cover_dimensions(&data)?;
+
         let mut file = fs::File::create(&file_path).await
             .map_err(|e| (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -488,6 +512,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Gagal membuat file image: {}", e),
                     error_code: Some("FILE_CREATE_ERROR".to_string()),
+                    details: None,
                 })
             ))?;
 
@@ -498,6 +523,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Gagal menulis file image: {}", e),
                     error_code: Some("FILE_WRITE_ERROR".to_string()),
+                    details: None,
                 })
             ))?;
 
@@ -508,6 +534,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Gagal flush file image: {}", e),
                     error_code: Some("FILE_FLUSH_ERROR".to_string()),
+                    details: None,
                 })
             ))?;
 
@@ -538,6 +565,7 @@ impl FileUploader {
                     success: false,
                     message: "Nama file tidak aman terdeteksi".to_string(),
                     error_code: Some("INVALID_FILENAME".to_string()),
+                    details: None,
                 })
             ));
         }
@@ -566,6 +594,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Gagal memindahkan file: {}", e),
                     error_code: Some("FILE_MOVE_ERROR".to_string()),
+                    details: None,
                 })
             ))?;
 
@@ -634,6 +663,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Gagal membaca chunk file: {}", e),
                     error_code: Some("FILE_READ_ERROR".to_string()),
+                    details: None,
                 })
             ))? {
             
@@ -644,6 +674,7 @@ impl FileUploader {
                         success: false,
                         message: "Ukuran chunk terlalu besar".to_string(),
                         error_code: Some("CHUNK_TOO_LARGE".to_string()),
+                        details: None,
                     })
                 ));
             }
@@ -655,6 +686,7 @@ impl FileUploader {
                         success: false,
                         message: "File terlalu besar".to_string(),
                         error_code: Some("FILE_TOO_LARGE".to_string()),
+                        details: None,
                     })
                 ));
             }
@@ -669,6 +701,7 @@ impl FileUploader {
                     success: false,
                     message: "File kosong tidak diizinkan".to_string(),
                     error_code: Some("EMPTY_FILE".to_string()),
+                    details: None,
                 })
             ));
         }
@@ -715,6 +748,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Tipe file tidak diizinkan: {}", extension),
                     error_code: Some("INVALID_FILE_TYPE".to_string()),
+                    details: None,
                 })
             ));
         }
@@ -730,6 +764,7 @@ impl FileUploader {
                     message: format!("File terlalu besar: {:.2}MB (maks: {}MB)", 
                         file_size_mb, applicable_validator.max_size_mb),
                     error_code: Some("FILE_TOO_LARGE".to_string()),
+                    details: None,
                 })
             ));
         }
@@ -741,10 +776,17 @@ impl FileUploader {
                     success: false,
                     message: "Konten file tidak sesuai dengan tipe file".to_string(),
                     error_code: Some("INVALID_FILE_CONTENT".to_string()),
+                    details: None,
                 })
             ));
         }
 
+        if applicable_validator.mime_type == "application/pdf" {
+            Self::check_pdf_structure(data)?;
+        } else if file_category == "image" {
+            Self::check_cover_dimensions(data)?;
+        }
+
         self.perform_security_scans(data, applicable_validator)?;
 
         Ok(applicable_validator)
@@ -792,6 +834,7 @@ impl FileUploader {
                             success: false,
                             message: "Konten berpotensi berbahaya terdeteksi".to_string(),
                             error_code: Some("MALICIOUS_CONTENT".to_string()),
+                            details: None,
                         })
                     ));
                 }
@@ -805,6 +848,7 @@ impl FileUploader {
                     success: false,
                     message: "File mengandung metadata berlebihan".to_string(),
                     error_code: Some("EXCESSIVE_METADATA".to_string()),
+                    details: None,
                 })
             ));
         }
@@ -878,6 +922,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Gagal membuat file sementara: {}", e),
                     error_code: Some("FILE_CREATE_ERROR".to_string()),
+                    details: None,
                 })
             ))?;
 
@@ -888,6 +933,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Gagal menulis file: {}", e),
                     error_code: Some("FILE_WRITE_ERROR".to_string()),
+                    details: None,
                 })
             ))?;
 
@@ -898,6 +944,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Gagal flush file: {}", e),
                     error_code: Some("FILE_FLUSH_ERROR".to_string()),
+                    details: None,
                 })
             ))?;
 
@@ -908,6 +955,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Gagal finalisasi file: {}", e),
                     error_code: Some("FILE_FINALIZE_ERROR".to_string()),
+                    details: None,
                 })
             ))?;
 
@@ -929,6 +977,7 @@ impl FileUploader {
                         success: false,
                         message: format!("Gagal mendapatkan permission file: {}", e),
                         error_code: Some("PERMISSION_ERROR".to_string()),
+                        details: None,
                     })
                 ))?
                 .permissions();
@@ -941,6 +990,7 @@ impl FileUploader {
                         success: false,
                         message: format!("Gagal set permission file: {}", e),
                         error_code: Some("PERMISSION_ERROR".to_string()),
+                        details: None,
                     })
                 ))?;
         }
@@ -976,6 +1026,7 @@ impl FileUploader {
                     success: false,
                     message: format!("Gagal membaca file untuk scan: {}", e),
                     error_code: Some("SCAN_READ_ERROR".to_string()),
+                    details: None,
                 })
             ))?;
 
@@ -994,6 +1045,7 @@ impl FileUploader {
                         success: false,
                         message: "Malware terdeteksi dalam file".to_string(),
                         error_code: Some("MALWARE_DETECTED".to_string()),
+                        details: None,
                     })
                 ));
             }
@@ -1083,6 +1135,36 @@ impl FileUploader {
         data.len() >= 5 && &data[0..5] == b"%PDF-"
     }
 
+    // Cek struktur PDF sebelum disimpan: file terpotong/rusak dan PDF terenkripsi
+    // akan gagal di render dan preview generation, jadi ditolak sejak upload
+    fn check_pdf_structure(data: &[u8]) -> Result<(), (StatusCode, axum::Json<ErrorResponse>)> {
+        let issue = match pdf_structure_issue(data) {
+            Some(issue) => issue,
+            None => return Ok(()),
+        };
+
+        let (message, error_code) = match issue {
+            PdfIssue::Corrupt => (
+                "File PDF rusak atau tidak lengkap (trailer/%%EOF tidak ditemukan)",
+                "PDF_CORRUPT",
+            ),
+            PdfIssue::Encrypted => (
+                "File PDF terproteksi password/terenkripsi, upload versi tanpa enkripsi",
+                "PDF_ENCRYPTED",
+            ),
+        };
+
+        Err((
+            StatusCode::BAD_REQUEST,
+            axum::Json(ErrorResponse {
+                success: false,
+                message: message.to_string(),
+                error_code: Some(error_code.to_string()),
+                details: None,
+            })
+        ))
+    }
+
     // Image validation dengan magic bytes per format
     fn is_valid_image(data: &[u8], extension: &str) -> bool {
         if data.len() < 8 {
@@ -1105,4 +1187,217 @@ impl FileUploader {
             _ => false,
         }
     }
-}
\ No newline at end of file
+
+    // Cek dimensi dan aspect ratio cover sebelum disimpan supaya grid katalog tetap rapi,
+    // dimensi dibaca dari header image tanpa decode pixel penuh
+    pub(crate) fn check_cover_dimensions(data: &[u8]) -> Result<(), (StatusCode, axum::Json<ErrorResponse>)> {
+        let dimensions = image::ImageReader::new(std::io::Cursor::new(data))
+            .with_guessed_format()
+            .ok()
+            .and_then(|reader| reader.into_dimensions().ok());
+
+        let (message, error_code) = match dimensions {
+            None => ("Image cover tidak bisa dibaca".to_string(), "INVALID_IMAGE_FORMAT"),
+            Some((width, height)) => match CoverDimensionRules::from_env().issue(width, height) {
+                Some(message) => (message, "INVALID_IMAGE_DIMENSIONS"),
+                None => return Ok(()),
+            },
+        };
+
+        Err((
+            StatusCode::BAD_REQUEST,
+            axum::Json(ErrorResponse {
+                success: false,
+                message,
+                error_code: Some(error_code.to_string()),
+                details: None,
+            })
+        ))
+    }
+}
+
+// ===== COVER DIMENSION CHECK =====
+
+// Batas dimensi cover, aspect ratio = lebar / tinggi (cover buku umumnya 2:3 = 0.67)
+#[derive(Debug, Clone)]
+struct CoverDimensionRules {
+    min_width: u32,
+    min_height: u32,
+    max_width: u32,
+    max_height: u32,
+    min_aspect: f64,
+    max_aspect: f64,
+}
+
+impl CoverDimensionRules {
+    // COVER_MIN_WIDTH/COVER_MIN_HEIGHT (default 200), COVER_MAX_WIDTH/COVER_MAX_HEIGHT (default 6000),
+    // COVER_MIN_ASPECT_RATIO (default 0.5), COVER_MAX_ASPECT_RATIO (default 1.0)
+    fn from_env() -> Self {
+        let size = |key: &str, default: u32| {
+            env::var(key).ok().and_then(|v| v.parse::<u32>().ok()).unwrap_or(default)
+        };
+        let ratio = |key: &str, default: f64| {
+            env::var(key).ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|r| r.is_finite() && *r > 0.0)
+                .unwrap_or(default)
+        };
+
+        Self {
+            min_width: size("COVER_MIN_WIDTH", 200),
+            min_height: size("COVER_MIN_HEIGHT", 200),
+            max_width: size("COVER_MAX_WIDTH", 6000),
+            max_height: size("COVER_MAX_HEIGHT", 6000),
+            min_aspect: ratio("COVER_MIN_ASPECT_RATIO", 0.5),
+            max_aspect: ratio("COVER_MAX_ASPECT_RATIO", 1.0),
+        }
+    }
+
+    // Pesan error jika dimensi di luar batas, None jika valid
+    fn issue(&self, width: u32, height: u32) -> Option<String> {
+        if width < self.min_width || height < self.min_height {
+            return Some(format!(
+                "Cover terlalu kecil: {}x{} (minimal {}x{})",
+                width, height, self.min_width, self.min_height
+            ));
+        }
+
+        if width > self.max_width || height > self.max_height {
+            return Some(format!(
+                "Cover terlalu besar: {}x{} (maksimal {}x{})",
+                width, height, self.max_width, self.max_height
+            ));
+        }
+
+        let aspect = width as f64 / height as f64;
+        if aspect < self.min_aspect || aspect > self.max_aspect {
+            return Some(format!(
+                "Aspect ratio cover {:.2} di luar rentang {:.2}-{:.2} (lebar/tinggi)",
+                aspect, self.min_aspect, self.max_aspect
+            ));
+        }
+
+        None
+    }
+}
+
+// ===== PDF STRUCTURE CHECK =====
+
+// Batas pencarian marker di akhir file (reader PDF umumnya toleransi 1024 byte sampah setelah %%EOF)
+const PDF_TAIL_WINDOW: usize = 1024;
+
+#[derive(Debug, PartialEq)]
+enum PdfIssue {
+    Corrupt,
+    Encrypted,
+}
+
+// Validasi ringan tanpa parse penuh: %%EOF dan startxref harus ada di akhir file,
+// offset xref harus di dalam file, dan trailer (atau dictionary xref stream) tidak boleh punya /Encrypt
+fn pdf_structure_issue(data: &[u8]) -> Option<PdfIssue> {
+    let tail_start = data.len().saturating_sub(PDF_TAIL_WINDOW);
+    let tail = &data[tail_start..];
+
+    if find_last(tail, b"%%EOF").is_none() {
+        return Some(PdfIssue::Corrupt);
+    }
+
+    let startxref_pos = match find_last(tail, b"startxref") {
+        Some(pos) => tail_start + pos,
+        None => return Some(PdfIssue::Corrupt),
+    };
+
+    let xref_offset = data[startxref_pos + b"startxref".len()..]
+        .iter()
+        .skip_while(|b| b.is_ascii_whitespace())
+        .take_while(|b| b.is_ascii_digit())
+        .try_fold(0usize, |acc, b| acc.checked_mul(10)?.checked_add((b - b'0') as usize));
+
+    let xref_offset = match xref_offset {
+        Some(offset) if offset > 0 && offset < startxref_pos => offset,
+        _ => return Some(PdfIssue::Corrupt),
+    };
+
+    // Section dari xref terakhir sampai akhir file berisi trailer dictionary
+    // (xref table klasik) atau dictionary xref stream (PDF 1.5+)
+    if has_name_key(&data[xref_offset..], b"/Encrypt") {
+        return Some(PdfIssue::Encrypted);
+    }
+
+    None
+}
+
+fn find_last(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|window| window == needle)
+}
+
+// Cocokkan name object persis (misal /Encrypt tapi bukan /EncryptMetadata)
+fn has_name_key(haystack: &[u8], name: &[u8]) -> bool {
+    haystack.windows(name.len())
+        .enumerate()
+        .any(|(i, window)| {
+            window == name && haystack.get(i + name.len()).is_none_or(|next| {
+                next.is_ascii_whitespace() || b"/<>[]()%".contains(next)
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pdf_with_trailer(trailer: &str) -> Vec<u8> {
+        let body = "%PDF-1.4\n1 0 obj\n<< /Type /Catalog >>\nendobj\n";
+        let xref_offset = body.len();
+        format!(
+            "{}xref\n0 2\n0000000000 65535 f \n0000000009 00000 n \ntrailer\n{}\nstartxref\n{}\n%%EOF\n",
+            body, trailer, xref_offset
+        ).into_bytes()
+    }
+
+    #[test]
+    fn test_pdf_structure_valid() {
+        let pdf = pdf_with_trailer("<< /Size 2 /Root 1 0 R >>");
+        assert_eq!(pdf_structure_issue(&pdf), None);
+    }
+
+    #[test]
+    fn test_pdf_structure_encrypted() {
+        let pdf = pdf_with_trailer("<< /Size 2 /Root 1 0 R /Encrypt 3 0 R >>");
+        assert_eq!(pdf_structure_issue(&pdf), Some(PdfIssue::Encrypted));
+    }
+
+    #[test]
+    fn test_pdf_structure_truncated() {
+        let pdf = pdf_with_trailer("<< /Size 2 /Root 1 0 R >>");
+        assert_eq!(pdf_structure_issue(&pdf[..pdf.len() / 2]), Some(PdfIssue::Corrupt));
+
+        let mut bad_offset = pdf_with_trailer("<< /Size 2 /Root 1 0 R >>");
+        let pos = find_last(&bad_offset, b"startxref").unwrap() + "startxref\n".len();
+        bad_offset[pos..pos + 2].copy_from_slice(b"00");
+        assert_eq!(pdf_structure_issue(&bad_offset), Some(PdfIssue::Corrupt));
+    }
+
+    #[test]
+    fn test_cover_dimensions_checked_from_header() {
+        let png = |width: u32, height: u32| {
+            let mut buf = std::io::Cursor::new(Vec::new());
+            image::RgbImage::new(width, height).write_to(&mut buf, image::ImageFormat::Png).unwrap();
+            buf.into_inner()
+        };
+
+        assert!(FileUploader::check_cover_dimensions(&png(300, 450)).is_ok());
+
+        let (status, body) = FileUploader::check_cover_dimensions(&png(450, 300)).unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body.error_code.as_deref(), Some("INVALID_IMAGE_DIMENSIONS"));
+
+        let (_, body) = FileUploader::check_cover_dimensions(b"\x89PNG\r\n\x1a\nbroken").unwrap_err();
+        assert_eq!(body.error_code.as_deref(), Some("INVALID_IMAGE_FORMAT"));
+
+        let rules = CoverDimensionRules::from_env();
+        assert!(rules.issue(100, 150).unwrap().contains("terlalu kecil"));
+        assert!(rules.issue(4000, 8000).unwrap().contains("terlalu besar"));
+        assert_eq!(rules.issue(200, 200), None);
+    }
+}
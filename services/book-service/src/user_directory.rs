@@ -0,0 +1,73 @@
+// /pdf-bookstore/services/book-service/src/user_directory.rs
+
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::models::BookReviewWithUser;
+
+pub use service_common::user_directory::{UserDirectory, UserProfile};
+
+/// Nama yang ditampilkan jika profil reviewer tidak bisa diambil
+const UNKNOWN_USER_NAME: &str = "Pengguna";
+
+/// Isi user_name / user_email review, reviewer yang tidak ditemukan tampil sebagai "Pengguna"
+pub async fn enrich_reviews(directory: &UserDirectory, reviews: &mut [BookReviewWithUser]) {
+    if reviews.is_empty() {
+        return;
+    }
+
+    let user_ids: Vec<Uuid> = reviews.iter().map(|r| r.user_id).collect();
+    let profiles = directory.lookup(&user_ids).await;
+    apply_profiles(reviews, &profiles);
+}
+
+fn apply_profiles(reviews: &mut [BookReviewWithUser], profiles: &HashMap<Uuid, UserProfile>) {
+    for review in reviews.iter_mut() {
+        match profiles.get(&review.user_id) {
+            Some(profile) => {
+                review.user_name = profile.full_name.clone();
+                review.user_email = profile.email.clone();
+            }
+            None => {
+                review.user_name = UNKNOWN_USER_NAME.to_string();
+                review.user_email = String::new();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn review(user_id: Uuid) -> BookReviewWithUser {
+        BookReviewWithUser {
+            id: Uuid::new_v4(),
+            book_id: Uuid::new_v4(),
+            user_id,
+            user_name: String::new(),
+            user_email: String::new(),
+            rating: 5,
+            comment: "Bagus".to_string(),
+            helpful_count: 0,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+            can_edit: false,
+            has_voted_helpful: false,
+        }
+    }
+
+    #[test]
+    fn test_profiles_applied_to_reviews() {
+        let (known, missing) = (Uuid::new_v4(), Uuid::new_v4());
+        let profiles = HashMap::from([(known, UserProfile {
+            email: "ani@example.com".to_string(),
+            full_name: "Ani".to_string(),
+        })]);
+
+        let mut reviews = vec![review(known), review(missing)];
+        apply_profiles(&mut reviews, &profiles);
+        assert_eq!((reviews[0].user_name.as_str(), reviews[0].user_email.as_str()), ("Ani", "ani@example.com"));
+        assert_eq!((reviews[1].user_name.as_str(), reviews[1].user_email.as_str()), (UNKNOWN_USER_NAME, ""));
+    }
+}
@@ -0,0 +1,50 @@
+// /pdf-bookstore/services/book-service/src/webhook_source.rs
+
+use service_common::client_ip::{cidr_list_contains, parse_cidr_list};
+use std::{env, net::IpAddr};
+
+/// Allowlist IP sumber webhook pembayaran (lapisan tambahan di samping HMAC)
+/// Kosong = semua sumber diterima (default dev)
+pub struct WebhookSourcePolicy {
+    allowed: Vec<(IpAddr, u8)>,
+}
+
+impl WebhookSourcePolicy {
+    /// WEBHOOK_ALLOWED_SOURCE_IPS: CIDR / IP comma-separated (payment-service, range IP Midtrans)
+    pub fn from_env() -> Self {
+        let raw = env::var("WEBHOOK_ALLOWED_SOURCE_IPS").unwrap_or_default();
+        let policy = Self { allowed: parse_cidr_list("WEBHOOK_ALLOWED_SOURCE_IPS", &raw) };
+
+        if policy.allowed.is_empty() {
+            tracing::info!("WEBHOOK_ALLOWED_SOURCE_IPS kosong, webhook diterima dari semua IP (hanya HMAC)");
+        }
+        policy
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.allowed.is_empty()
+    }
+
+    /// Cek IP sumber webhook (setelah resolve header forwarded lewat ClientIpResolver)
+    pub fn is_allowed(&self, source_ip: IpAddr) -> bool {
+        !self.is_enabled() || cidr_list_contains(&self.allowed, source_ip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowlist_matches_cidr_and_single_ip() {
+        let policy = WebhookSourcePolicy { allowed: parse_cidr_list("WEBHOOK_ALLOWED_SOURCE_IPS", "103.208.23.0/24, 172.18.0.5") };
+
+        assert!(policy.is_allowed("103.208.23.14".parse().unwrap()));
+        assert!(policy.is_allowed("172.18.0.5".parse().unwrap()));
+        assert!(!policy.is_allowed("172.18.0.6".parse().unwrap()));
+        assert!(!policy.is_allowed("::1".parse().unwrap()));
+
+        let open = WebhookSourcePolicy { allowed: Vec::new() };
+        assert!(open.is_allowed("8.8.8.8".parse().unwrap()));
+    }
+}
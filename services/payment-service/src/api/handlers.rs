@@ -2,8 +2,8 @@
 
 use axum::{
     extract::{State, Path, Query},
-    http::StatusCode,
-    response::Json,
+    http::{header, HeaderMap, HeaderValue},
+    response::{Json, Response},
     Extension,
 };
 
@@ -16,12 +16,14 @@ use std::sync::Arc;
 use crate::{
     models::*,
     AppState,
+    core::invoice::{InvoiceGenerator, INVOICE_PAGE_SIZE},
     utils::{
         error::{AppError, AppResult},
         validator as utils_validator,
         validator::validate_positive_amount,
         scheduler::trigger_maintenance_job,
         cache::CacheManager,
+        user_directory,
         {DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE},
         scheduler::SchedulerMetrics,
     },
@@ -44,8 +46,9 @@ pub async fn create_order(
     let book_id = utils_validator::validate_uuid(&payload.book_id, "book_id")?;
     let book_details = get_book_details(&state, book_id).await?;
 
-    // Validasi payment method
+    // Validasi payment method (dikenal dan aktif di allowlist)
     utils_validator::validate_payment_method(&payload.payment_method)?;
+    state.midtrans_service.ensure_method_enabled(&payload.payment_method)?;
     validate_positive_amount(&book_details.price, "book price")?;
     
     // Validasi idempotency key jika ada
@@ -59,11 +62,7 @@ pub async fn create_order(
             .await? 
         {
             tracing::info!("Idempotent order request detected for key: {}", idempotency_key);
-            return Ok(Json(OrderResponse {
-                success: true,
-                message: "Order sudah ada (idempotent)".to_string(),
-                data: Some(existing_order),
-            }));
+            return Ok(Json(OrderResponse::ok("Order sudah ada (idempotent)", Some(existing_order))));
         }
     }
     
@@ -79,11 +78,7 @@ pub async fn create_order(
     
     tracing::info!("New order created: {} for user: {}", order.order.order_number, user_id);
     
-    Ok(Json(OrderResponse {
-        success: true,
-        message: "Order berhasil dibuat".to_string(),
-        data: Some(order),
-    }))
+    Ok(Json(OrderResponse::ok("Order berhasil dibuat", Some(order))))
 }
 
 // helper function untuk mendapatkan detail book
@@ -92,10 +87,14 @@ async fn get_book_details(
     book_id: Uuid,
 ) -> AppResult<BookDetails> {
     let response = state.http_client
-        .get(format!("{}/api/books/{}", 
+        .get(format!("{}/api/internal/books/{}", 
             std::env::var("BOOK_SERVICE_URL").unwrap_or_else(|_| "http://book-service:3002".to_string()),
             book_id
         ))
+        .header(
+            "X-Service-Key",
+            std::env::var("INTERNAL_SERVICE_KEY").unwrap_or_else(|_| "internal-service-key-secret".to_string()),
+        )
         .send()
         .await?;
     
@@ -107,6 +106,10 @@ async fn get_book_details(
     
     let book_obj = data["data"]["book"].as_object()
         .ok_or_else(|| AppError::ExternalService("Invalid book response format".to_string()))?;
+
+    if !book_obj["is_active"].as_bool().unwrap_or(false) {
+        return Err(AppError::BadRequest("Buku tidak tersedia untuk pembelian".to_string()));
+    }
     
     Ok(BookDetails {
         title: book_obj["title"].as_str().unwrap_or("Unknown").to_string(),
@@ -117,6 +120,28 @@ async fn get_book_details(
     })
 }
 
+/// Minta book-service membuang konfirmasi kepemilikan yang di-cache (best effort, gagal hanya di-log)
+async fn evict_book_purchase_cache(state: &AppState, user_id: Uuid, book_id: Uuid) {
+    let result = state.http_client
+        .post(format!("{}/api/internal/purchases/{}/{}/evict",
+            std::env::var("BOOK_SERVICE_URL").unwrap_or_else(|_| "http://book-service:3002".to_string()),
+            user_id,
+            book_id
+        ))
+        .header(
+            "X-Service-Key",
+            std::env::var("INTERNAL_SERVICE_KEY").unwrap_or_else(|_| "internal-service-key-secret".to_string()),
+        )
+        .timeout(std::time::Duration::from_secs(5))
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status());
+
+    if let Err(e) = result {
+        tracing::warn!("Gagal evict cache kepemilikan book-service user {} buku {}: {}", user_id, book_id, e);
+    }
+}
+
 /// Handler untuk mendapatkan detail order
 /// GET /api/orders/{id}
 pub async fn get_order(
@@ -132,11 +157,7 @@ pub async fn get_order(
         .get::<OrderWithDetails>(&cache_key).await 
     {
         tracing::debug!("Order {} retrieved from cache", order_id);
-        return Ok(Json(OrderResponse {
-            success: true,
-            message: "Order berhasil diambil (cached)".to_string(),
-            data: Some(cached_order),
-        }));
+        return Ok(Json(OrderResponse::ok("Order berhasil diambil (cached)", Some(cached_order))));
     }
     
     // Jika tidak ada di cache, ambil dari database
@@ -181,11 +202,7 @@ pub async fn get_order(
         );
     }
     
-    Ok(Json(OrderResponse {
-        success: true,
-        message: "Order berhasil diambil".to_string(),
-        data: Some(order),
-    }))
+    Ok(Json(OrderResponse::ok("Order berhasil diambil", Some(order))))
 }
 
 /// Handler untuk list orders user
@@ -212,31 +229,78 @@ pub async fn list_orders(
     }
     
     // Get orders dari repository
-    let (orders, total) = state.repository
+    let (mut orders, total) = state.repository
         .order()
         .find_by_user(user_id, validated_page, validated_limit, params)
         .await?;
+    user_directory::enrich_orders(&state.user_directory, &mut orders).await;
     
     let pagination = PaginationMeta::new(validated_page, validated_limit, total);
     
     tracing::debug!("Listed {} orders for user {} (page {}/{})", 
         orders.len(), user_id, validated_page, pagination.total_pages);
     
-    Ok(Json(OrdersListResponse {
-        success: true,
-        message: "Orders berhasil diambil".to_string(),
-        data: orders,
-        pagination: Some(pagination),
-    }))
+    Ok(Json(ApiResponse::ok("Orders berhasil diambil", orders).with_meta(pagination)))
+}
+
+/// Handler untuk download semua invoice user dalam rentang tanggal (ZIP)
+/// GET /api/orders/invoices?from=YYYY-MM-DD&to=YYYY-MM-DD
+pub async fn download_invoices_bulk(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<Uuid>,
+    Query(params): Query<InvoiceRangeParams>,
+) -> AppResult<Response> {
+    // Default: 1 tahun terakhir
+    let to = params.to.unwrap_or_else(|| chrono::Utc::now().date_naive());
+    let from = params.from.unwrap_or_else(|| to - chrono::Duration::days(365));
+    utils_validator::validate_invoice_range(from, to)?;
+
+    // Batas atas inklusif sampai akhir hari 'to'
+    let from_ts = from.and_time(chrono::NaiveTime::MIN).and_utc();
+    let to_ts = (to + chrono::Duration::days(1)).and_time(chrono::NaiveTime::MIN).and_utc();
+
+    let first_page = state.repository
+        .order()
+        .find_paid_by_user_in_range(user_id, from_ts, to_ts, None, INVOICE_PAGE_SIZE)
+        .await?;
+
+    if first_page.is_empty() {
+        return Err(AppError::NotFound("Tidak ada order paid pada rentang tanggal tersebut".to_string()));
+    }
+
+    tracing::info!("User {} downloading invoices ({} - {})", user_id, from, to);
+
+    let repository = state.repository.clone();
+    let body = InvoiceGenerator::stream_zip(first_page, INVOICE_PAGE_SIZE, move |cursor| {
+        let repository = repository.clone();
+        async move {
+            repository.order()
+                .find_paid_by_user_in_range(user_id, from_ts, to_ts, Some(cursor), INVOICE_PAGE_SIZE)
+                .await
+        }
+    });
+
+    let filename = format!("invoices_{}_{}.zip", from, to);
+    let mut response = Response::new(body);
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/zip"));
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!("attachment; filename=\"{}\"", filename))
+            .map_err(|e| AppError::Internal(e.to_string()))?,
+    );
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("private, no-store"));
+
+    Ok(response)
 }
 
-/// Handler untuk cancel order
-/// PUT /api/orders/{id}/cancel
+/// Handler untuk cancel order pending milik user
+/// POST /api/orders/{id}/cancel (PUT tetap didukung)
 pub async fn cancel_order(
     State(state): State<AppState>,
     Path(order_id): Path<Uuid>,
     Extension(user_id): Extension<Uuid>,
-) -> AppResult<Json<serde_json::Value>> {
+) -> AppResult<Json<ApiResponse<serde_json::Value>>> {
     // Get order untuk verify ownership dan status
     let order = state.repository
         .order()
@@ -253,7 +317,7 @@ pub async fn cancel_order(
     
     // Verify status - hanya pending yang bisa dibatalkan
     if order.order.status != "pending" {
-        return Err(AppError::BadRequest(
+        return Err(AppError::Conflict(
             format!("Order dengan status '{}' tidak bisa dibatalkan", order.order.status)
         ));
     }
@@ -274,12 +338,26 @@ pub async fn cancel_order(
     
     tracing::info!("Order {} cancelled by user {}", order.order.order_number, user_id);
     
-    Ok(Json(serde_json::json!({
-        "success": true,
-        "message": "Order berhasil dibatalkan",
+    Ok(Json(ApiResponse::ok("Order berhasil dibatalkan", serde_json::json!({
         "order_id": order_id,
         "cancelled_at": chrono::Utc::now()
-    })))
+    }))))
+}
+
+/// Handler untuk membuat ulang link pembayaran order pending (token Midtrans expired)
+/// POST /api/orders/{id}/payment-link
+pub async fn regenerate_payment_link(
+    State(state): State<AppState>,
+    Path(order_id): Path<Uuid>,
+    Extension(user_id): Extension<Uuid>,
+) -> AppResult<Json<OrderResponse>> {
+    let order = state.payment_service
+        .regenerate_payment_link(order_id, user_id)
+        .await?;
+
+    invalidate_order_cache(&state.cache_manager, user_id, order_id).await;
+
+    Ok(Json(OrderResponse::ok("Link pembayaran baru berhasil dibuat", Some(order))))
 }
 
 // fungsi untuk invalidate cache saat order berubah
@@ -307,7 +385,7 @@ pub async fn request_refund(
     Path(order_id): Path<Uuid>,
     Extension(user_id): Extension<Uuid>,
     Json(payload): Json<RefundRequest>,
-) -> AppResult<Json<serde_json::Value>> {
+) -> AppResult<Json<ApiResponse<serde_json::Value>>> {
     // Validate refund request
     payload.validate()
         .map_err(|e| AppError::ValidationError(e.to_string()))?;
@@ -335,25 +413,26 @@ pub async fn request_refund(
         ));
     }
     
-    // Check apakah sudah pernah di-refund
-    let existing_refund = state.repository
-        .payment()
-        .get_refund_by_order_id(order_id)
+    // Partial refund boleh berulang selama total tidak melebihi nilai order
+    let already_refunded = state.repository
+        .order()
+        .get_refunded_amount(order_id)
         .await?;
-    
-    if existing_refund.is_some() {
-        return Err(AppError::Conflict("Order sudah pernah di-refund".to_string()));
-    }
+    let remaining = &order.order.amount - &already_refunded;
 
-    let refund_amount = if let Some(requested_amount) = payload.amount {
-        if requested_amount > order.order.amount {
-            return Err(AppError::BadRequest("Jumlah refund melebihi nilai order".to_string()));
-        }
-        requested_amount
-    } else {
-        order.order.amount.clone() 
+    let refund_amount = match payload.amount {
+        Some(requested_amount) => requested_amount,
+        None => remaining.clone(),
     };
 
+    utils_validator::validate_positive_amount(&refund_amount, "Jumlah refund")?;
+
+    if refund_amount > remaining {
+        return Err(AppError::BadRequest(
+            format!("Jumlah refund melebihi sisa nilai order ({})", remaining)
+        ));
+    }
+
     if let Some(ref bank_acc) = payload.bank_account {
         tracing::info!("Refund akan dikirim ke rekening: {}", bank_acc);
     }
@@ -361,15 +440,44 @@ pub async fn request_refund(
     let refund_reason = payload.reason.as_ref()
         .map(|r| r.as_str())
         .unwrap_or("Customer request");
+
+    // Reserve jumlah refund secara atomic sebelum ke Midtrans (cegah over-refund paralel)
+    let (total_refunded, fully_refunded) = state.repository
+        .order()
+        .reserve_refund_amount(order_id, &refund_amount)
+        .await?
+        .ok_or_else(|| AppError::Conflict("Order sudah di-refund atau jumlah melebihi sisa".to_string()))?;
     
     // Process refund melalui Midtrans
-    let refund_result = state.midtrans_service
+    let refund_result = match state.midtrans_service
         .process_refund(
             &order.order.midtrans_order_id.unwrap_or_default(),
             refund_amount.clone(), 
             refund_reason
         )
-        .await?;
+        .await
+    {
+        Ok(result) => result,
+        Err(e) => {
+            match state.repository
+                .order()
+                .release_refund_amount(order_id, &refund_amount)
+                .await
+            {
+                // Refund paralel sempat mencabut akses, order kembali paid: akses dipulihkan
+                Ok(true) => {
+                    if let (Some(buyer_id), Some(book_id)) = (order.order.user_id, order.order.book_id) {
+                        evict_book_purchase_cache(&state, buyer_id, book_id).await;
+                    }
+                }
+                Ok(false) => {}
+                Err(release_err) => {
+                    tracing::error!("Failed to release refund reservation for {}: {}", order_id, release_err);
+                }
+            }
+            return Err(e);
+        }
+    };
     
     // Simpan refund record
     let refund_id = state.repository
@@ -382,28 +490,43 @@ pub async fn request_refund(
             refund_result.refund_id
         )
         .await?;
+
+    // Akses buku hanya dicabut kalau order sudah full refund
+    if fully_refunded {
+        let revoked = state.repository
+            .order()
+            .revoke_purchase_for_order(order_id)
+            .await?;
+        tracing::info!("Access revoked for order {} ({} purchases)", order_id, revoked);
+
+        if let (Some(buyer_id), Some(book_id)) = (order.order.user_id, order.order.book_id) {
+            evict_book_purchase_cache(&state, buyer_id, book_id).await;
+        }
+    }
+
+    let refunds = state.repository
+        .payment()
+        .get_refunds_by_order_id(order_id)
+        .await
+        .unwrap_or_default();
     
-    // Update order status
-    state.repository
-        .order()
-        .update_status_simple(order_id, "refunded")
-        .await?;
-    
-    tracing::info!("Refund processed for order {} by user {}", order_id, user_id);
+    tracing::info!("Refund processed for order {} by user {} (full: {})", order_id, user_id, fully_refunded);
     
-    Ok(Json(serde_json::json!({
-        "success": true,
-        "message": "Refund berhasil diproses",
+    Ok(Json(ApiResponse::ok("Refund berhasil diproses", serde_json::json!({
         "refund_id": refund_id,
         "status": "processing",
         "estimated_days": "3-7", 
-        "refund_amount": refund_amount.to_string() 
-    })))
+        "refund_amount": refund_amount.to_string(),
+        "total_refunded": total_refunded.to_string(),
+        "remaining_refundable": (&order.order.amount - &total_refunded).to_string(),
+        "fully_refunded": fully_refunded,
+        "refunds": refunds
+    }))))
 }
 
 pub async fn get_scheduler_status(
     Extension(user_role): Extension<String>,
-) -> AppResult<Json<serde_json::Value>> {
+) -> AppResult<Json<ApiResponse<serde_json::Value>>> {
     // Admin only
     if user_role != "admin" {
         return Err(AppError::Forbidden("Admin access required".to_string()));
@@ -412,10 +535,7 @@ pub async fn get_scheduler_status(
     let metrics = SchedulerMetrics::new();
     let status = metrics.get_status().await;
     
-    Ok(Json(serde_json::json!({
-        "success": true,
-        "data": status
-    })))
+    Ok(Json(ApiResponse::ok("Status scheduler berhasil diambil", status)))
 }
 
 /// Get Midtrans client key untuk frontend
@@ -423,13 +543,24 @@ pub async fn get_scheduler_status(
 pub async fn get_payment_config(
     State(state): State<AppState>,
     Extension(_user_id): Extension<Uuid>,
-) -> AppResult<Json<serde_json::Value>> {
-    Ok(Json(serde_json::json!({
-        "success": true,
+) -> AppResult<Json<ApiResponse<serde_json::Value>>> {
+    Ok(Json(ApiResponse::ok("Konfigurasi payment berhasil diambil", serde_json::json!({
         "client_key": state.midtrans_service.get_client_key(),
-        "is_production": std::env::var("MIDTRANS_IS_PRODUCTION")
-            .unwrap_or_else(|_| "false".to_string()) == "true"
-    })))
+        "environment": state.midtrans_service.environment().as_str(),
+        "is_production": state.midtrans_service.environment().is_production()
+    }))))
+}
+
+/// Handler daftar metode pembayaran yang aktif untuk checkout
+/// GET /api/payment-methods
+pub async fn list_payment_methods(
+    State(state): State<AppState>,
+    Extension(_user_id): Extension<Uuid>,
+) -> AppResult<Json<ApiResponse<Vec<PaymentMethodOption>>>> {
+    Ok(Json(ApiResponse::ok(
+        "Metode pembayaran berhasil diambil",
+        state.midtrans_service.enabled_methods(),
+    )))
 }
 
 // ========================= WEBHOOK HANDLERS =========================
@@ -457,10 +588,17 @@ pub async fn handle_midtrans_webhook(
     tracing::info!("Processing webhook for transaction: {} with status: {}", 
         payload.transaction_id, payload.transaction_status);
     
-    // Process webhook melalui service
-    state.payment_service
+    // Process webhook melalui service, kegagalan berulang memicu alert admin
+    if let Err(e) = state.payment_service
         .process_webhook(&payload, signature_key)
-        .await?;
+        .await
+    {
+        state.notifier.record("webhook_failure", |count| format!(
+            "{} webhook Midtrans gagal diproses dalam window alert (terakhir order {}: {})",
+            count, payload.order_id, e
+        ));
+        return Err(e);
+    }
     
     tracing::info!("Webhook processed successfully for transaction: {}", payload.transaction_id);
     
@@ -480,7 +618,7 @@ pub async fn check_purchase_status(
     State(state): State<AppState>,
     Path(book_id): Path<Uuid>,
     Extension(user_id): Extension<Uuid>,
-) -> AppResult<Json<serde_json::Value>> {
+) -> AppResult<Json<ApiResponse<serde_json::Value>>> {
     // Check purchase status
     let has_purchased = state.repository
         .payment()
@@ -490,18 +628,51 @@ pub async fn check_purchase_status(
     tracing::debug!("Purchase status check: user {} book {} = {}", 
         user_id, book_id, has_purchased);
     
-    Ok(Json(serde_json::json!({
-        "success": true,
+    let message = if has_purchased {
+        "Book sudah dibeli dan tersedia untuk download"
+    } else {
+        "Book belum dibeli"
+    };
+
+    Ok(Json(ApiResponse::ok(message, serde_json::json!({
         "user_id": user_id,
         "book_id": book_id,
         "has_purchased": has_purchased,
-        "message": if has_purchased {
-            "Book sudah dibeli dan tersedia untuk download"
-        } else {
-            "Book belum dibeli"
-        },
         "checked_at": chrono::Utc::now()
-    })))
+    }))))
+}
+
+// ========================= INTERNAL HANDLERS =========================
+
+/// Kepemilikan buku untuk fallback verifikasi book-service, diverifikasi via X-Service-Key
+/// GET /api/internal/users/{user_id}/books/{book_id}/ownership
+pub async fn get_book_ownership_internal(
+    State(state): State<AppState>,
+    Path((user_id, book_id)): Path<(Uuid, Uuid)>,
+    headers: HeaderMap,
+) -> AppResult<Json<ApiResponse<serde_json::Value>>> {
+    let service_key = headers
+        .get("X-Service-Key")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("");
+    let expected_key = std::env::var("INTERNAL_SERVICE_KEY")
+        .unwrap_or_else(|_| "internal-service-key-secret".to_string());
+
+    if service_key != expected_key {
+        tracing::warn!("Invalid service key untuk internal ownership lookup user {} buku {}", user_id, book_id);
+        return Err(AppError::Unauthorized("Unauthorized service call".to_string()));
+    }
+
+    let owned = state.repository
+        .payment()
+        .has_book_access(user_id, book_id)
+        .await?;
+
+    Ok(Json(ApiResponse::ok("Status kepemilikan berhasil diambil", serde_json::json!({
+        "user_id": user_id,
+        "book_id": book_id,
+        "owned": owned,
+    }))))
 }
 
 /// Trigger maintenance job manually
@@ -509,7 +680,7 @@ pub async fn check_purchase_status(
 pub async fn trigger_maintenance(
     State(state): State<AppState>,
     Extension(user_role): Extension<String>,
-) -> AppResult<Json<serde_json::Value>> {
+) -> AppResult<Json<ApiResponse<serde_json::Value>>> {
     if user_role != "admin" {
         return Err(AppError::Forbidden("Admin access required".to_string()));
     }
@@ -517,11 +688,9 @@ pub async fn trigger_maintenance(
     // Trigger maintenance
     trigger_maintenance_job(state.repository.clone()).await?;
     
-    Ok(Json(serde_json::json!({
-        "success": true,
-        "message": "Maintenance job triggered successfully",
+    Ok(Json(ApiResponse::ok("Maintenance job triggered successfully", serde_json::json!({
         "timestamp": chrono::Utc::now()
-    })))
+    }))))
 }
 // ========================= ADMIN HANDLERS =========================
 
@@ -555,12 +724,10 @@ pub async fn get_admin_order_stats(
     tracing::info!("Admin stats requested: {} total orders, {} revenue", 
         stats.total_orders, stats.total_revenue);
     
-    let response = serde_json::json!({
-        "success": true,
-        "message": "Order statistics berhasil diambil",
-        "data": stats,
-        "generated_at": chrono::Utc::now()
-    });
+    let response = serde_json::to_value(
+        ApiResponse::ok("Order statistics berhasil diambil", stats)
+            .with_meta(serde_json::json!({ "generated_at": chrono::Utc::now() }))
+    ).map_err(|e| AppError::Internal(format!("Gagal serialize stats: {}", e)))?;
     
     // CACHE admin stats selama 15 menit
     if let Err(e) = state.cache_manager
@@ -580,7 +747,7 @@ pub async fn admin_update_order_status(
     Extension(user_role): Extension<String>,
     Extension(admin_id): Extension<Uuid>,
     Json(payload): Json<UpdateOrderRequest>,
-) -> AppResult<Json<serde_json::Value>> {
+) -> AppResult<Json<ApiResponse<serde_json::Value>>> {
     // Verify admin role
     if user_role != "admin" {
         return Err(AppError::Forbidden("Akses admin diperlukan".to_string()));
@@ -646,15 +813,106 @@ pub async fn admin_update_order_status(
         tracing::warn!("Failed to invalidate order cache: {}", e);
     }
     
-    Ok(Json(serde_json::json!({
-        "success": true,
-        "message": "Order berhasil diupdate",
+    Ok(Json(ApiResponse::ok("Order berhasil diupdate", serde_json::json!({
         "order_id": order_id,
         "updated_status": payload.status,
         "notes": payload.notes,
         "updated_by": admin_id,
         "updated_at": chrono::Utc::now()
-    })))
+    }))))
+}
+
+/// Handler untuk memulihkan akses buku user secara manual (Admin only), idempotent
+/// POST /api/admin/users/{user_id}/books/{book_id}/grant
+pub async fn admin_grant_book_access(
+    State(state): State<AppState>,
+    Path((user_id, book_id)): Path<(Uuid, Uuid)>,
+    Extension(user_role): Extension<String>,
+    Extension(admin_id): Extension<Uuid>,
+    Json(payload): Json<BookAccessChangeRequest>,
+) -> AppResult<Json<ApiResponse<serde_json::Value>>> {
+    if user_role != "admin" {
+        return Err(AppError::Forbidden("Akses admin diperlukan".to_string()));
+    }
+    payload.validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    let mut tx = state.repository.begin_transaction().await?;
+    let (granted, order_id) = state.repository
+        .payment()
+        .grant_book_access(&mut tx, user_id, book_id)
+        .await?;
+
+    // Grant ulang untuk user yang sudah punya akses tidak dicatat sebagai perubahan
+    if granted {
+        state.repository
+            .audit()
+            .log_book_access_changed(&mut tx, admin_id, "BOOK_ACCESS_GRANTED", user_id, book_id, serde_json::json!({
+                "reason": payload.reason,
+                "order_id": order_id,
+            }))
+            .await?;
+    }
+    tx.commit().await?;
+
+    tracing::info!("Admin {} granted book {} to user {} (changed: {})", admin_id, book_id, user_id, granted);
+
+    let message = if granted { "Akses buku berhasil dipulihkan" } else { "User sudah memiliki akses buku" };
+    Ok(Json(ApiResponse::ok(message, serde_json::json!({
+        "user_id": user_id,
+        "book_id": book_id,
+        "order_id": order_id,
+        "changed": granted,
+        "has_access": true,
+        "updated_by": admin_id,
+    }))))
+}
+
+/// Handler untuk mencabut akses buku user secara manual (Admin only), idempotent
+/// POST /api/admin/users/{user_id}/books/{book_id}/revoke
+pub async fn admin_revoke_book_access(
+    State(state): State<AppState>,
+    Path((user_id, book_id)): Path<(Uuid, Uuid)>,
+    Extension(user_role): Extension<String>,
+    Extension(admin_id): Extension<Uuid>,
+    Json(payload): Json<BookAccessChangeRequest>,
+) -> AppResult<Json<ApiResponse<serde_json::Value>>> {
+    if user_role != "admin" {
+        return Err(AppError::Forbidden("Akses admin diperlukan".to_string()));
+    }
+    payload.validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    let mut tx = state.repository.begin_transaction().await?;
+    let revoked = state.repository
+        .payment()
+        .revoke_book_access(&mut tx, user_id, book_id)
+        .await?;
+
+    if revoked {
+        state.repository
+            .audit()
+            .log_book_access_changed(&mut tx, admin_id, "BOOK_ACCESS_REVOKED", user_id, book_id, serde_json::json!({
+                "reason": payload.reason,
+            }))
+            .await?;
+    }
+    tx.commit().await?;
+
+    tracing::info!("Admin {} revoked book {} from user {} (changed: {})", admin_id, book_id, user_id, revoked);
+
+    if revoked {
+        evict_book_purchase_cache(&state, user_id, book_id).await;
+    }
+
+    let message = if revoked { "Akses buku berhasil dicabut" } else { "User tidak memiliki akses buku" };
+    Ok(Json(ApiResponse::ok(message, serde_json::json!({
+        "user_id": user_id,
+        "book_id": book_id,
+        "changed": revoked,
+        "has_access": false,
+        "updated_by": admin_id,
+    }))))
 }
 
 // Helper function untuk validate status transition
@@ -687,7 +945,7 @@ pub async fn get_revenue_analytics(
     State(state): State<AppState>,
     Extension(user_role): Extension<String>,
     Query(params): Query<std::collections::HashMap<String, String>>,
-) -> AppResult<Json<serde_json::Value>> {
+) -> AppResult<Json<ApiResponse<RevenueAnalytics, serde_json::Value>>> {
     // Verify admin role
     if user_role != "admin" {
         return Err(AppError::Forbidden("Akses admin diperlukan".to_string()));
@@ -714,16 +972,13 @@ pub async fn get_revenue_analytics(
     
     tracing::info!("Revenue analytics requested: {} period, {} days", period, validated_days);
     
-    Ok(Json(serde_json::json!({
-        "success": true,
-        "message": "Revenue analytics berhasil diambil",
-        "data": analytics,
+    Ok(Json(ApiResponse::ok("Revenue analytics berhasil diambil", analytics).with_meta(serde_json::json!({
         "parameters": {
             "period": period,
             "days": validated_days
         },
         "generated_at": chrono::Utc::now()
-    })))
+    }))))
 }
 
 /// Handler untuk recent orders (admin) dengan flexible filtering
@@ -753,10 +1008,11 @@ pub async fn get_recent_orders_admin(
     }
     
     // Get orders dari repository
-    let orders = state.repository
+    let mut orders = state.repository
         .order()
         .get_recent_orders(limit, status_filter)
         .await?;
+    user_directory::enrich_orders(&state.user_directory, &mut orders).await;
     
     tracing::info!("Recent orders requested by admin: {} orders, filter: {:?}", 
         orders.len(), status_filter);
@@ -765,7 +1021,7 @@ pub async fn get_recent_orders_admin(
         success: true,
         message: "Recent orders berhasil diambil".to_string(),
         data: orders,
-        pagination: None, // No pagination for recent orders
+        meta: None, // No pagination for recent orders
     }))
 }
 
@@ -774,7 +1030,7 @@ pub async fn get_recent_orders_admin(
 pub async fn get_system_health(
     State(state): State<AppState>,
     Extension(user_role): Extension<String>,
-) -> AppResult<Json<serde_json::Value>> {
+) -> AppResult<Json<ApiResponse<serde_json::Value>>> {
     // Verify admin role
     if user_role != "admin" {
         return Err(AppError::Forbidden("Akses admin diperlukan".to_string()));
@@ -798,22 +1054,23 @@ pub async fn get_system_health(
     // Get cache status
     let cache_stats = state.cache_manager.get_stats().await;
     
-    Ok(Json(serde_json::json!({
-        "success": true,
-        "data": {
-            "database_connected": database_connected,
-            "cache_status": cache_stats,
-            "order_stats": stats,
-            "timestamp": chrono::Utc::now(),
-            "service_version": env!("CARGO_PKG_VERSION"),
-        }
-    })))
+    Ok(Json(ApiResponse::ok("System health berhasil diambil", serde_json::json!({
+        "database_connected": database_connected,
+        "cache_status": cache_stats,
+        "rate_limiter": {
+            "tracked_keys": state.rate_limiter.tracked_keys().await,
+            "max_tracked_keys": state.rate_limiter.max_tracked_keys(),
+        },
+        "order_stats": stats,
+        "timestamp": chrono::Utc::now(),
+        "service_version": env!("CARGO_PKG_VERSION"),
+    }))))
 }
 
 // Handler untuk comprehensive health check
 pub async fn comprehensive_health_check_handler(
     State(state): State<AppState>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+) -> AppResult<Json<serde_json::Value>> {
     let health_result = crate::utils::health::comprehensive_health_check(
         &state.repository,
         &state.cache_manager,
@@ -821,5 +1078,8 @@ pub async fn comprehensive_health_check_handler(
         &state.service_registry,
     ).await;
     
-    Ok(Json(serde_json::to_value(health_result).unwrap()))
+    let body = serde_json::to_value(health_result)
+        .map_err(|e| AppError::Internal(format!("Gagal serialize health check: {}", e)))?;
+
+    Ok(Json(body))
 }
@@ -15,16 +15,24 @@ pub fn create_routes() -> Router<AppState> {
         // Order management routes
         .route("/api/orders", post(handlers::create_order))
         .route("/api/orders", get(handlers::list_orders))
+        .route("/api/orders/invoices", get(handlers::download_invoices_bulk))
         
         // Order detail dan actions
         .route("/api/orders/{id}", get(handlers::get_order))
-        .route("/api/orders/{id}/cancel", put(handlers::cancel_order))
+        .route("/api/orders/{id}/cancel", post(handlers::cancel_order).put(handlers::cancel_order))
+        .route("/api/orders/{id}/payment-link", post(handlers::regenerate_payment_link))
 
         // Route untuk refund
         .route("/api/orders/{id}/refund", post(handlers::request_refund))  
         // Purchase verification
         .route("/api/purchases/{book_id}", get(handlers::check_purchase_status))
+
+        // Metode pembayaran aktif untuk checkout
+        .route("/api/payment-methods", get(handlers::list_payment_methods))
         
+        // Internal service routes (X-Service-Key)
+        .route("/api/internal/users/{user_id}/books/{book_id}/ownership", get(handlers::get_book_ownership_internal))
+
         // Webhook endpoint (public, no auth)
         .route("/api/webhook/midtrans", post(handlers::handle_midtrans_webhook))
         
@@ -38,6 +46,8 @@ pub fn create_routes() -> Router<AppState> {
         .route("/api/admin/analytics/revenue", get(handlers::get_revenue_analytics))
         .route("/api/admin/orders/recent", get(handlers::get_recent_orders_admin))
         .route("/api/admin/orders/{id}/status", put(handlers::admin_update_order_status))
+        .route("/api/admin/users/{user_id}/books/{book_id}/grant", post(handlers::admin_grant_book_access))
+        .route("/api/admin/users/{user_id}/books/{book_id}/revoke", post(handlers::admin_revoke_book_access))
         // Maintenance endpoint (admin only)
         .route("/api/admin/maintenance/trigger", post(handlers::trigger_maintenance))
         .route("/api/admin/system/health", get(handlers::get_system_health))
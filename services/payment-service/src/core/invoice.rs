@@ -0,0 +1,355 @@
+// /pdf-bookstore/services/payment-service/src/core/invoice.rs
+
+use axum::body::{Body, Bytes};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use std::future::Future;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use uuid::Uuid;
+
+use crate::{models::OrderWithDetails, utils::error::AppResult};
+
+/// Jumlah order yang diambil dari database per halaman saat streaming ZIP
+pub const INVOICE_PAGE_SIZE: i64 = 100;
+
+/// Cursor keyset halaman invoice: (waktu bayar, id) order terakhir
+pub type InvoiceCursor = (DateTime<Utc>, Uuid);
+
+/// Generator invoice PDF untuk order yang sudah dibayar
+pub struct InvoiceGenerator;
+
+impl InvoiceGenerator {
+    /// Nama file invoice di dalam arsip
+    pub fn filename(order: &OrderWithDetails) -> String {
+        format!("invoice-{}.pdf", sanitize_filename(&order.order.order_number))
+    }
+
+    /// Render satu invoice menjadi dokumen PDF sederhana (1 halaman, font Helvetica)
+    pub fn render_pdf(order: &OrderWithDetails) -> Vec<u8> {
+        let paid_at = order.order.paid_at.unwrap_or(order.order.created_at);
+        let lines = [
+            "INVOICE - PDF Bookstore".to_string(),
+            String::new(),
+            format!("No. Order     : {}", order.order.order_number),
+            format!("Tanggal Bayar : {}", paid_at.format("%Y-%m-%d %H:%M UTC")),
+            format!("Pelanggan     : {}", order.user_name.as_deref().unwrap_or("-")),
+            format!("Email         : {}", order.user_email.as_deref().unwrap_or("-")),
+            String::new(),
+            format!("Buku          : {}", order.book_title.as_deref().unwrap_or("-")),
+            format!("Penulis       : {}", order.book_author.as_deref().unwrap_or("-")),
+            format!("Metode Bayar  : {}", order.order.payment_method.as_deref().unwrap_or("-")),
+            format!("Status        : {}", order.order.status),
+            String::new(),
+            format!("Total         : Rp {}", order.order.amount.with_scale(2)),
+        ];
+
+        let mut content = String::from("BT\n/F1 12 Tf\n14 TL\n50 780 Td\n");
+        for line in &lines {
+            content.push_str(&format!("({}) Tj T*\n", escape_pdf_text(line)));
+        }
+        content.push_str("ET\n");
+
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 595 842] \
+             /Resources << /Font << /F1 4 0 R >> >> /Contents 5 0 R >>".to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+            format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content),
+        ];
+
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", index + 1, object).as_bytes());
+        }
+
+        let xref_offset = pdf.len();
+        pdf.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+        for offset in offsets {
+            pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        pdf.extend_from_slice(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+                objects.len() + 1,
+                xref_offset
+            )
+            .as_bytes(),
+        );
+
+        pdf
+    }
+
+    /// Cursor untuk halaman berikutnya setelah order ini
+    pub fn cursor(order: &OrderWithDetails) -> InvoiceCursor {
+        (order.order.paid_at.unwrap_or(order.order.created_at), order.order.id)
+    }
+
+    /// Stream arsip ZIP berisi invoice semua order, diambil per halaman lewat `next_page`
+    /// Hanya satu halaman order dan satu PDF yang ada di memory; jika halaman berikutnya gagal
+    /// diambil stream diputus dengan error (client menerima ZIP terpotong, bukan arsip yang tampak lengkap)
+    pub fn stream_zip<F, Fut>(first_page: Vec<OrderWithDetails>, page_size: i64, mut next_page: F) -> Body
+    where
+        F: FnMut(InvoiceCursor) -> Fut + Send + 'static,
+        Fut: Future<Output = AppResult<Vec<OrderWithDetails>>> + Send,
+    {
+        let (tx, rx) = mpsc::channel::<Result<Bytes, std::io::Error>>(4);
+
+        tokio::spawn(async move {
+            let mut zip = ZipStreamWriter::default();
+            let mut page = first_page;
+
+            loop {
+                for order in &page {
+                    let chunk = zip.add_file(&InvoiceGenerator::filename(order), &InvoiceGenerator::render_pdf(order));
+                    if tx.send(Ok(Bytes::from(chunk))).await.is_err() {
+                        tracing::debug!("Client menutup koneksi saat download invoice");
+                        return;
+                    }
+                }
+
+                let Some(last) = page.last().filter(|_| page.len() as i64 >= page_size) else {
+                    break;
+                };
+                page = match next_page(InvoiceGenerator::cursor(last)).await {
+                    Ok(next) => next,
+                    Err(e) => {
+                        tracing::error!("Gagal mengambil halaman invoice: {}", e);
+                        let _ = tx.send(Err(std::io::Error::other(e.to_string()))).await;
+                        return;
+                    }
+                };
+            }
+
+            let _ = tx.send(Ok(Bytes::from(zip.finish()))).await;
+        });
+
+        Body::from_stream(ReceiverStream::new(rx))
+    }
+}
+
+/// ZIP writer minimal (metode STORED) yang menghasilkan arsip secara incremental
+#[derive(Default)]
+struct ZipStreamWriter {
+    offset: u32,
+    central_directory: Vec<u8>,
+    entries: u16,
+}
+
+impl ZipStreamWriter {
+    /// Tambah file ke arsip, return bytes local header + data
+    fn add_file(&mut self, name: &str, data: &[u8]) -> Vec<u8> {
+        let crc = crc32fast::hash(data);
+        let size = data.len() as u32;
+        let (dos_time, dos_date) = dos_datetime();
+        let name = name.as_bytes();
+
+        let mut local = Vec::with_capacity(30 + name.len() + data.len());
+        local.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        local.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        local.extend_from_slice(&0u16.to_le_bytes()); // flags
+        local.extend_from_slice(&0u16.to_le_bytes()); // method: stored
+        local.extend_from_slice(&dos_time.to_le_bytes());
+        local.extend_from_slice(&dos_date.to_le_bytes());
+        local.extend_from_slice(&crc.to_le_bytes());
+        local.extend_from_slice(&size.to_le_bytes());
+        local.extend_from_slice(&size.to_le_bytes());
+        local.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        local.extend_from_slice(&0u16.to_le_bytes()); // extra length
+        local.extend_from_slice(name);
+        local.extend_from_slice(data);
+
+        let central = &mut self.central_directory;
+        central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        central.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        central.extend_from_slice(&0u16.to_le_bytes());
+        central.extend_from_slice(&0u16.to_le_bytes());
+        central.extend_from_slice(&dos_time.to_le_bytes());
+        central.extend_from_slice(&dos_date.to_le_bytes());
+        central.extend_from_slice(&crc.to_le_bytes());
+        central.extend_from_slice(&size.to_le_bytes());
+        central.extend_from_slice(&size.to_le_bytes());
+        central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        central.extend_from_slice(&0u16.to_le_bytes()); // extra length
+        central.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        central.extend_from_slice(&0u16.to_le_bytes()); // disk number
+        central.extend_from_slice(&0u16.to_le_bytes()); // internal attrs
+        central.extend_from_slice(&0u32.to_le_bytes()); // external attrs
+        central.extend_from_slice(&self.offset.to_le_bytes());
+        central.extend_from_slice(name);
+
+        self.offset += local.len() as u32;
+        self.entries += 1;
+        local
+    }
+
+    /// Tutup arsip, return central directory + end record
+    fn finish(self) -> Vec<u8> {
+        let mut tail = self.central_directory;
+        let central_size = tail.len() as u32;
+
+        tail.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        tail.extend_from_slice(&0u16.to_le_bytes());
+        tail.extend_from_slice(&0u16.to_le_bytes());
+        tail.extend_from_slice(&self.entries.to_le_bytes());
+        tail.extend_from_slice(&self.entries.to_le_bytes());
+        tail.extend_from_slice(&central_size.to_le_bytes());
+        tail.extend_from_slice(&self.offset.to_le_bytes());
+        tail.extend_from_slice(&0u16.to_le_bytes());
+        tail
+    }
+}
+
+/// Timestamp format MS-DOS untuk header ZIP
+fn dos_datetime() -> (u16, u16) {
+    let now = Utc::now();
+    let time = ((now.hour() as u16) << 11) | ((now.minute() as u16) << 5) | (now.second() as u16 / 2);
+    let date = (((now.year() - 1980).max(0) as u16) << 9) | ((now.month() as u16) << 5) | now.day() as u16;
+    (time, date)
+}
+
+/// Escape karakter khusus PDF, karakter non-ASCII diganti '?'
+fn escape_pdf_text(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '(' | ')' | '\\' => format!("\\{}", c),
+            c if c.is_ascii() && !c.is_ascii_control() => c.to_string(),
+            _ => "?".to_string(),
+        })
+        .collect()
+}
+
+fn sanitize_filename(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Order;
+    use bigdecimal::BigDecimal;
+    use std::sync::{Arc, Mutex};
+
+    fn paid_order(number: usize) -> OrderWithDetails {
+        let paid_at = Utc::now();
+        OrderWithDetails {
+            order: Order {
+                id: Uuid::new_v4(),
+                user_id: Some(Uuid::new_v4()),
+                book_id: Some(Uuid::new_v4()),
+                order_number: format!("ORD-{:04}", number),
+                amount: BigDecimal::from(75_000),
+                status: "paid".to_string(),
+                payment_method: Some("qris".to_string()),
+                midtrans_order_id: None,
+                payment_url: None,
+                paid_at: Some(paid_at),
+                expires_at: None,
+                created_at: paid_at,
+                updated_at: paid_at,
+            },
+            book_title: Some("Belajar (Rust) \\ Lanjut".to_string()),
+            book_author: Some("José".to_string()),
+            book_cover_path: None,
+            user_email: Some("user@example.com".to_string()),
+            user_name: Some("User".to_string()),
+        }
+    }
+
+    fn u16_at(bytes: &[u8], at: usize) -> usize {
+        u16::from_le_bytes([bytes[at], bytes[at + 1]]) as usize
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_pdf_xref_offsets_and_stream_length() {
+        let pdf = InvoiceGenerator::render_pdf(&paid_order(1));
+        let text = String::from_utf8(pdf.clone()).expect("PDF hanya berisi ASCII");
+
+        assert!(text.starts_with("%PDF-1.4\n"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("(Buku          : Belajar \\(Rust\\) \\\\ Lanjut) Tj"));
+        assert!(text.contains("Jos?"));
+
+        let startxref: usize = text.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+        assert!(text[startxref..].starts_with("xref\n0 6\n"));
+
+        // Tiap entry xref menunjuk tepat ke awal "N 0 obj"
+        let entries = text[startxref..].lines().skip(3).take(5).collect::<Vec<_>>();
+        for (index, entry) in entries.iter().enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(text[offset..].starts_with(&format!("{} 0 obj\n", index + 1)), "xref object {}", index + 1);
+        }
+
+        let length_at = text.find("/Length ").unwrap() + "/Length ".len();
+        let length: usize = text[length_at..].split_whitespace().next().unwrap().trim_end_matches(">>").parse().unwrap();
+        let stream_start = text.find("stream\n").unwrap() + "stream\n".len();
+        assert_eq!(&text[stream_start + length..stream_start + length + "endstream".len()], "endstream");
+    }
+
+    #[test]
+    fn test_zip_headers_and_central_directory() {
+        let files = [("invoice-a.pdf", b"%PDF-a".to_vec()), ("invoice-b.pdf", b"%PDF-bb".to_vec())];
+        let mut zip = ZipStreamWriter::default();
+        let mut archive = Vec::new();
+        for (name, data) in &files {
+            archive.extend(zip.add_file(name, data));
+        }
+        archive.extend(zip.finish());
+
+        // End of central directory di 22 byte terakhir
+        let eocd = archive.len() - 22;
+        assert_eq!(u32_at(&archive, eocd), 0x0605_4b50);
+        assert_eq!(u16_at(&archive, eocd + 10), files.len());
+        let central_size = u32_at(&archive, eocd + 12) as usize;
+        let central_offset = u32_at(&archive, eocd + 16) as usize;
+        assert_eq!(central_offset + central_size, eocd);
+
+        let mut central = central_offset;
+        for (name, data) in &files {
+            assert_eq!(u32_at(&archive, central), 0x0201_4b50);
+            assert_eq!(u32_at(&archive, central + 16), crc32fast::hash(data));
+            assert_eq!(u32_at(&archive, central + 20) as usize, data.len());
+            let name_len = u16_at(&archive, central + 28);
+            assert_eq!(&archive[central + 46..central + 46 + name_len], name.as_bytes());
+
+            let local = u32_at(&archive, central + 42) as usize;
+            assert_eq!(u32_at(&archive, local), 0x0403_4b50);
+            assert_eq!(u16_at(&archive, local + 8), 0, "metode STORED");
+            assert_eq!(u32_at(&archive, local + 14), crc32fast::hash(data));
+            let data_start = local + 30 + u16_at(&archive, local + 26);
+            assert_eq!(&archive[data_start..data_start + data.len()], data.as_slice());
+
+            central += 46 + name_len;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_zip_fetches_every_page() {
+        let remaining = Arc::new(Mutex::new((2..=5).map(paid_order).collect::<Vec<_>>()));
+        let cursors = Arc::new(Mutex::new(Vec::new()));
+
+        let (pages, seen) = (remaining.clone(), cursors.clone());
+        let body = InvoiceGenerator::stream_zip(vec![paid_order(0), paid_order(1)], 2, move |cursor| {
+            seen.lock().unwrap().push(cursor);
+            let mut pages = pages.lock().unwrap();
+            let take = pages.len().min(2);
+            let page = pages.drain(..take).collect::<Vec<_>>();
+            async move { Ok(page) }
+        });
+
+        let archive = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let eocd = archive.len() - 22;
+        assert_eq!(u16_at(&archive, eocd + 10), 6);
+        // Halaman 2 dan 3 penuh, halaman 4 kosong menutup arsip
+        assert_eq!(cursors.lock().unwrap().len(), 3);
+    }
+}
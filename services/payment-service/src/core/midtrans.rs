@@ -12,35 +12,183 @@ use crate::{
     utils::error::{AppError, AppResult},
 };
 
+/// Prefix key Midtrans sandbox ("SB-Mid-server-...", "SB-Mid-client-...")
+const SANDBOX_KEY_PREFIX: &str = "SB-";
+/// Prefix key Midtrans production ("Mid-server-...", "Mid-client-...")
+const PRODUCTION_KEY_PREFIX: &str = "Mid-";
+/// Pemisah order_number dan suffix pada order_id Midtrans untuk link pembayaran ulang
+const PAYMENT_REFERENCE_SEPARATOR: char = '~';
+
+/// Environment Midtrans yang menentukan base URL API dan Snap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidtransEnvironment {
+    Sandbox,
+    Production,
+}
+
+impl MidtransEnvironment {
+    /// MIDTRANS_ENVIRONMENT=sandbox|production
+    /// Fallback ke MIDTRANS_IS_PRODUCTION (legacy) jika tidak di-set, default sandbox
+    pub fn from_env() -> AppResult<Self> {
+        match env::var("MIDTRANS_ENVIRONMENT") {
+            Ok(value) => Self::parse(&value),
+            Err(_) => {
+                let is_production = env::var("MIDTRANS_IS_PRODUCTION")
+                    .map(|v| v.trim() == "true")
+                    .unwrap_or(false);
+                Ok(if is_production { Self::Production } else { Self::Sandbox })
+            }
+        }
+    }
+
+    fn parse(value: &str) -> AppResult<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "sandbox" => Ok(Self::Sandbox),
+            "production" => Ok(Self::Production),
+            other => Err(AppError::Configuration(format!(
+                "MIDTRANS_ENVIRONMENT tidak valid: '{}' (gunakan sandbox atau production)", other
+            ))),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sandbox => "sandbox",
+            Self::Production => "production",
+        }
+    }
+
+    pub fn is_production(&self) -> bool {
+        *self == Self::Production
+    }
+
+    /// Base URL Core API (charge, status, cancel, refund)
+    pub fn api_base_url(&self) -> &'static str {
+        match self {
+            Self::Sandbox => "https://api.sandbox.midtrans.com/v2",
+            Self::Production => "https://api.midtrans.com/v2",
+        }
+    }
+
+    /// Base URL Snap (halaman pembayaran)
+    pub fn snap_base_url(&self) -> &'static str {
+        match self {
+            Self::Sandbox => "https://app.sandbox.midtrans.com/snap/v1",
+            Self::Production => "https://app.midtrans.com/snap/v1",
+        }
+    }
+
+    /// Tolak key sandbox di production dan sebaliknya berdasarkan prefix key
+    /// Key dengan format tidak dikenal hanya di-warn
+    fn validate_key(&self, name: &str, key: &str) -> AppResult<()> {
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(AppError::Configuration(format!("{} kosong", name)));
+        }
+
+        let mismatch = match self {
+            Self::Production => key.starts_with(SANDBOX_KEY_PREFIX),
+            Self::Sandbox => key.starts_with(PRODUCTION_KEY_PREFIX),
+        };
+
+        if mismatch {
+            return Err(AppError::Configuration(format!(
+                "{} tidak cocok dengan MIDTRANS_ENVIRONMENT={} (cek prefix key)", name, self.as_str()
+            )));
+        }
+
+        if !key.starts_with(SANDBOX_KEY_PREFIX) && !key.starts_with(PRODUCTION_KEY_PREFIX) {
+            tracing::warn!("Format {} tidak dikenal, prefix tidak bisa divalidasi", name);
+        }
+
+        Ok(())
+    }
+}
+
+/// Konfigurasi Midtrans yang sudah divalidasi
+#[derive(Debug, Clone)]
+pub struct MidtransConfig {
+    pub environment: MidtransEnvironment,
+    server_key: String,
+    client_key: String,
+    /// Channel Midtrans yang diizinkan, None = semua channel
+    enabled_payments: Option<Vec<String>>,
+}
+
+impl MidtransConfig {
+    /// Load dari env: MIDTRANS_ENVIRONMENT, MIDTRANS_SERVER_KEY, MIDTRANS_CLIENT_KEY,
+    /// MIDTRANS_ENABLED_PAYMENTS (channel comma-separated, misal `qris,gopay,bca_va`; kosong = semua)
+    pub fn from_env() -> AppResult<Self> {
+        let server_key = env::var("MIDTRANS_SERVER_KEY")
+            .map_err(|_| AppError::Configuration("MIDTRANS_SERVER_KEY not set".to_string()))?;
+
+        let client_key = env::var("MIDTRANS_CLIENT_KEY")
+            .map_err(|_| AppError::Configuration("MIDTRANS_CLIENT_KEY not set".to_string()))?;
+
+        let config = Self::new(MidtransEnvironment::from_env()?, server_key, client_key)?;
+        config.with_enabled_payments(&env::var("MIDTRANS_ENABLED_PAYMENTS").unwrap_or_default())
+    }
+
+    pub fn new(environment: MidtransEnvironment, server_key: String, client_key: String) -> AppResult<Self> {
+        environment.validate_key("MIDTRANS_SERVER_KEY", &server_key)?;
+        environment.validate_key("MIDTRANS_CLIENT_KEY", &client_key)?;
+
+        Ok(Self {
+            environment,
+            server_key: server_key.trim().to_string(),
+            client_key: client_key.trim().to_string(),
+            enabled_payments: None,
+        })
+    }
+
+    /// Batasi channel pembayaran, channel tidak dikenal atau allowlist yang
+    /// tidak menyisakan satu metode pun ditolak saat startup
+    pub fn with_enabled_payments(mut self, value: &str) -> AppResult<Self> {
+        let channels: Vec<String> = value
+            .split(',')
+            .map(|c| c.trim().to_lowercase())
+            .filter(|c| !c.is_empty())
+            .collect();
+
+        if channels.is_empty() {
+            self.enabled_payments = None;
+            return Ok(self);
+        }
+
+        let known: Vec<String> = PaymentMethod::ALL.iter()
+            .flat_map(|method| method.get_enabled_payments())
+            .collect();
+
+        if let Some(unknown) = channels.iter().find(|c| !known.contains(c)) {
+            return Err(AppError::Configuration(format!(
+                "MIDTRANS_ENABLED_PAYMENTS berisi channel tidak dikenal: '{}' (valid: {})",
+                unknown, known.join(", ")
+            )));
+        }
+
+        self.enabled_payments = Some(channels);
+        Ok(self)
+    }
+}
+
 /// Client untuk integrasi dengan Midtrans payment gateway
 pub struct MidtransClient {
     client: Client,
     server_key: String,
     client_key: String,
-    is_production: bool,
+    environment: MidtransEnvironment,
     base_url: String,
+    enabled_payments: Option<Vec<String>>,
 }
 
 impl MidtransClient {
-    /// Initialize Midtrans client
+    /// Initialize Midtrans client dari env
     pub fn new() -> AppResult<Self> {
-        let server_key = env::var("MIDTRANS_SERVER_KEY")
-            .map_err(|_| AppError::Configuration("MIDTRANS_SERVER_KEY not set".to_string()))?;
-        
-        let client_key = env::var("MIDTRANS_CLIENT_KEY")
-            .map_err(|_| AppError::Configuration("MIDTRANS_CLIENT_KEY not set".to_string()))?;
-        
-        let is_production = env::var("MIDTRANS_IS_PRODUCTION")
-            .unwrap_or_else(|_| "false".to_string())
-            .parse::<bool>()
-            .unwrap_or(false);
-        
-        let base_url = if is_production {
-            "https://api.midtrans.com/v2".to_string()
-        } else {
-            "https://api.sandbox.midtrans.com/v2".to_string()
-        };
-        
+        Self::from_config(MidtransConfig::from_env()?)
+    }
+
+    /// Initialize Midtrans client dari konfigurasi yang sudah divalidasi
+    pub fn from_config(config: MidtransConfig) -> AppResult<Self> {
         let client = Client::builder()
             .timeout(std::time::Duration::from_secs(30))
             .build()
@@ -48,15 +196,70 @@ impl MidtransClient {
         
         Ok(Self {
             client,
-            server_key,
-            client_key,
-            is_production,
-            base_url,
+            server_key: config.server_key,
+            client_key: config.client_key,
+            environment: config.environment,
+            base_url: config.environment.api_base_url().to_string(),
+            enabled_payments: config.enabled_payments,
         })
     }
+
+    /// Environment Midtrans yang aktif
+    pub fn environment(&self) -> MidtransEnvironment {
+        self.environment
+    }
     
+    /// order_id Midtrans baru untuk link pembayaran ulang, Midtrans menolak order_id yang sudah dipakai
+    pub fn new_payment_reference(order_number: &str) -> String {
+        format!("{}{}{}", order_number, PAYMENT_REFERENCE_SEPARATOR, chrono::Utc::now().timestamp_millis())
+    }
+
+    /// order_number asli dari order_id Midtrans (dengan atau tanpa suffix link ulang)
+    pub fn order_number_from_reference(reference: &str) -> &str {
+        reference.split(PAYMENT_REFERENCE_SEPARATOR).next().unwrap_or(reference)
+    }
+
+    /// Channel Midtrans metode ini yang lolos allowlist (kosong = metode nonaktif)
+    pub fn enabled_channels(&self, method: &PaymentMethod) -> Vec<String> {
+        method.get_enabled_payments()
+            .into_iter()
+            .filter(|channel| self.is_channel_enabled(channel))
+            .collect()
+    }
+
+    /// Metode pembayaran yang punya minimal satu channel aktif
+    pub fn enabled_methods(&self) -> Vec<PaymentMethodOption> {
+        PaymentMethod::ALL.iter()
+            .map(|method| PaymentMethodOption {
+                code: method.code(),
+                display_name: method.display_name(),
+                channels: self.enabled_channels(method),
+            })
+            .filter(|option| !option.channels.is_empty())
+            .collect()
+    }
+
+    /// Validasi metode yang diminta client terhadap allowlist sebelum charge
+    pub fn ensure_method_enabled(&self, code: &str) -> AppResult<PaymentMethod> {
+        PaymentMethod::from_code(code)
+            .filter(|method| !self.enabled_channels(method).is_empty())
+            .ok_or_else(|| AppError::BadRequest(format!(
+                "Metode pembayaran '{}' tidak tersedia", code
+            )))
+    }
+
+    fn is_channel_enabled(&self, channel: &str) -> bool {
+        self.enabled_payments.as_ref().is_none_or(|allowed| allowed.iter().any(|c| c == channel))
+    }
+
     /// Create payment transaction
     pub async fn create_payment(&self, request: &MidtransPaymentRequest) -> AppResult<MidtransPaymentResponse> {
+        if request.enabled_payments.is_empty()
+            || !request.enabled_payments.iter().all(|channel| self.is_channel_enabled(channel))
+        {
+            return Err(AppError::BadRequest("Channel pembayaran tidak diizinkan".to_string()));
+        }
+
         let auth_header = format!(
             "Basic {}",
             base64::engine::general_purpose::STANDARD.encode(format!("{}:", self.server_key))
@@ -68,15 +271,16 @@ impl MidtransClient {
             .header("Content-Type", "application/json")
             .json(request)
             .send()
-            .await?;
+            .await
+            .map_err(|e| AppError::PaymentGateway(format!("Midtrans request failed: {}", e)))?;
         
         if !response.status().is_success() {
             let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AppError::ExternalService(format!("Midtrans error: {}", error_text)));
+            return Err(AppError::PaymentGateway(format!("Midtrans error: {}", error_text)));
         }
         
         let payment_response: MidtransPaymentResponse = response.json().await
-            .map_err(|e| AppError::ExternalService(format!("Failed to parse Midtrans response: {}", e)))?;
+            .map_err(|e| AppError::PaymentGateway(format!("Failed to parse Midtrans response: {}", e)))?;
         
         Ok(payment_response)
     }
@@ -129,11 +333,12 @@ impl MidtransClient {
             .post(format!("{}/{}/cancel", self.base_url, order_id))
             .header("Authorization", auth_header)
             .send()
-            .await?;
+            .await
+            .map_err(|e| AppError::PaymentGateway(format!("Midtrans request failed: {}", e)))?;
         
         if !response.status().is_success() {
             let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AppError::ExternalService(format!("Failed to cancel payment: {}", error_text)));
+            return Err(AppError::PaymentGateway(format!("Failed to cancel payment: {}", error_text)));
         }
         
         Ok(())
@@ -141,11 +346,7 @@ impl MidtransClient {
     
     /// Create payment URL
     pub fn create_payment_url(&self, transaction_id: &str) -> String {
-        if self.is_production {
-            format!("https://app.midtrans.com/snap/v1/transactions/{}/pay", transaction_id)
-        } else {
-            format!("https://app.sandbox.midtrans.com/snap/v1/transactions/{}/pay", transaction_id)
-        }
+        format!("{}/transactions/{}/pay", self.environment.snap_base_url(), transaction_id)
     }
 
     /// Process refund melalui Midtrans
@@ -172,15 +373,16 @@ impl MidtransClient {
             .header("Content-Type", "application/json")
             .json(&refund_request)
             .send()
-            .await?;
+            .await
+            .map_err(|e| AppError::PaymentGateway(format!("Midtrans request failed: {}", e)))?;
         
         if !response.status().is_success() {
             let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AppError::ExternalService(format!("Midtrans refund error: {}", error_text)));
+            return Err(AppError::PaymentGateway(format!("Midtrans refund error: {}", error_text)));
         }
         
         let refund_response: RefundResponse = response.json().await
-            .map_err(|e| AppError::ExternalService(format!("Failed to parse refund response: {}", e)))?;
+            .map_err(|e| AppError::PaymentGateway(format!("Failed to parse refund response: {}", e)))?;
         
         Ok(refund_response)
     }
@@ -199,3 +401,74 @@ impl MidtransClient {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_environment_selects_urls() {
+        let production = MidtransEnvironment::parse("Production").unwrap();
+        assert_eq!(production.api_base_url(), "https://api.midtrans.com/v2");
+        assert_eq!(MidtransEnvironment::Sandbox.api_base_url(), "https://api.sandbox.midtrans.com/v2");
+        assert!(MidtransEnvironment::parse("staging").is_err());
+    }
+
+    #[test]
+    fn test_key_prefix_must_match_environment() {
+        let sandbox = MidtransConfig::new(
+            MidtransEnvironment::Sandbox,
+            "SB-Mid-server-abc".to_string(),
+            "SB-Mid-client-abc".to_string(),
+        );
+        assert!(sandbox.is_ok());
+
+        let sandbox_keys_in_production = MidtransConfig::new(
+            MidtransEnvironment::Production,
+            "SB-Mid-server-abc".to_string(),
+            "SB-Mid-client-abc".to_string(),
+        );
+        assert!(sandbox_keys_in_production.is_err());
+
+        let production_key_in_sandbox = MidtransConfig::new(
+            MidtransEnvironment::Sandbox,
+            "Mid-server-abc".to_string(),
+            "SB-Mid-client-abc".to_string(),
+        );
+        assert!(production_key_in_sandbox.is_err());
+
+        let empty_key = MidtransConfig::new(MidtransEnvironment::Sandbox, " ".to_string(), "SB-x".to_string());
+        assert!(empty_key.is_err());
+    }
+
+    #[test]
+    fn test_enabled_payments_allowlist() {
+        let config = || MidtransConfig::new(
+            MidtransEnvironment::Sandbox,
+            "SB-Mid-server-abc".to_string(),
+            "SB-Mid-client-abc".to_string(),
+        ).unwrap();
+
+        let all = MidtransClient::from_config(config().with_enabled_payments("").unwrap()).unwrap();
+        assert_eq!(all.enabled_methods().len(), PaymentMethod::ALL.len());
+
+        let client = MidtransClient::from_config(config().with_enabled_payments("qris, BCA_VA").unwrap()).unwrap();
+        let codes: Vec<&str> = client.enabled_methods().iter().map(|m| m.code).collect();
+        assert_eq!(codes, vec!["qris", "bank_transfer"]);
+        assert_eq!(client.enabled_channels(&PaymentMethod::BankTransfer), vec!["bca_va".to_string()]);
+
+        assert!(client.ensure_method_enabled("qris").is_ok());
+        assert!(client.ensure_method_enabled("e_wallet").is_err());
+        assert!(client.ensure_method_enabled("unknown").is_err());
+
+        assert!(config().with_enabled_payments("qris,paypal").is_err());
+    }
+
+    #[test]
+    fn test_payment_reference_round_trip() {
+        let reference = MidtransClient::new_payment_reference("ORD-20250101-ABC123");
+        assert!(reference.starts_with("ORD-20250101-ABC123~"));
+        assert!(reference.len() <= 64);
+        assert_eq!(MidtransClient::order_number_from_reference(&reference), "ORD-20250101-ABC123");
+        assert_eq!(MidtransClient::order_number_from_reference("ORD-20250101-ABC123"), "ORD-20250101-ABC123");
+    }
+}
@@ -2,6 +2,7 @@
 
 pub mod payment;
 pub mod midtrans;
+pub mod invoice;
 
 // Re-export untuk kemudahan akses
 pub mod services {
@@ -15,6 +15,7 @@ use crate::{
     utils::service_discovery::ServiceRegistry,
     utils::cache::CacheManager,
 };
+use service_common::admin_notifier::AdminNotifier;
 
 use super::midtrans::MidtransClient;
 
@@ -27,6 +28,9 @@ pub struct PaymentService {
     cache_manager: Arc<CacheManager>,
     auth_service_url: String,
     http_client: reqwest::Client,
+    notifier: Arc<AdminNotifier>,
+    /// REQUIRE_EMAIL_VERIFIED_TO_PURCHASE: tolak order dari user yang email-nya belum terverifikasi
+    require_email_verified: bool,
 }
 
 impl PaymentService {
@@ -34,6 +38,7 @@ impl PaymentService {
     pub async fn new(
         repository: Arc<Repository>,
         cache_manager: Arc<CacheManager>, 
+        notifier: Arc<AdminNotifier>,
     ) -> AppResult<Self> {
         let midtrans_client = Arc::new(
             MidtransClient::new()
@@ -45,10 +50,14 @@ impl PaymentService {
         service_registry.init_default_services().await;
         
         // Initialize circuit breaker manager
-        let circuit_manager = Arc::new(CircuitBreakerManager::new());
+        let circuit_manager = Arc::new(CircuitBreakerManager::new().with_notifier(notifier.clone()));
         
         let auth_service_url = std::env::var("AUTH_SERVICE_URL")
             .unwrap_or_else(|_| "http://auth-service:3001".to_string());
+
+        let require_email_verified = std::env::var("REQUIRE_EMAIL_VERIFIED_TO_PURCHASE")
+            .map(|v| matches!(v.trim().to_lowercase().as_str(), "true" | "1" | "yes"))
+            .unwrap_or(false);
         
         // Create HTTP client dengan proper configuration
         let http_client = reqwest::Client::builder()
@@ -65,6 +74,8 @@ impl PaymentService {
             cache_manager,
             auth_service_url,
             http_client,
+            notifier,
+            require_email_verified,
         })
     }
     
@@ -76,6 +87,10 @@ impl PaymentService {
         payment_method: String,
         idempotency_key: Option<String>,
     ) -> AppResult<OrderWithDetails> {
+        if self.require_email_verified {
+            self.ensure_email_verified(user_id).await?;
+        }
+
         // Get book details dari book service dengan enhanced error handling
         let book_details = self.get_book_details(book_id).await?;
         
@@ -172,7 +187,25 @@ impl PaymentService {
             }
         }
         
-        // Cancel di Midtrans jika ada transaction ID
+        // Update status di database (row ter-lock sampai commit, webhook paid menunggu)
+        let cancelled = self.repository.order()
+            .cancel_pending(&mut tx, order_id, user_id)
+            .await?;
+        
+        if !cancelled {
+            return Err(AppError::Conflict("Order sudah tidak dalam status pending".to_string()));
+        }
+        
+        // Log audit
+        self.repository.audit()
+            .log_order_cancelled(&mut tx, user_id, order_id)
+            .await?;
+        
+        // Commit dulu: lock order tidak ditahan selama HTTP ke Midtrans
+        tx.commit().await
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        
+        // Cancel di Midtrans jika ada transaction ID, gagal di sini hanya di-log (order sudah cancelled)
         if let Some(midtrans_order_id) = &order.order.midtrans_order_id {
             match self.midtrans_client.cancel_payment(midtrans_order_id).await {
                 Ok(_) => {
@@ -180,28 +213,97 @@ impl PaymentService {
                 }
                 Err(e) => {
                     tracing::warn!("Failed to cancel payment {} in Midtrans: {}", midtrans_order_id, e);
-                    // Continue dengan cancel di database meskipun Midtrans fail
                 }
             }
         }
         
-        // Update status di database
+        tracing::info!("Order {} cancelled by user {}", order.order.order_number, user_id);
+        
+        Ok(())
+    }
+    
+    /// Buat ulang link pembayaran untuk order pending milik user (token Midtrans lama expired)
+    /// Order dan nominal tetap, hanya transaksi Midtrans yang diganti dengan order_id baru
+    pub async fn regenerate_payment_link(
+        &self,
+        order_id: Uuid,
+        user_id: Uuid,
+    ) -> AppResult<OrderWithDetails> {
+        let order = self.repository.order()
+            .find_by_id(order_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Order tidak ditemukan".to_string()))?
+            .order;
+
+        if order.user_id != Some(user_id) {
+            tracing::warn!("User {} attempted to regenerate payment link for order {} owned by {:?}",
+                user_id, order_id, order.user_id);
+            return Err(AppError::Forbidden("Akses ditolak untuk order ini".to_string()));
+        }
+        ensure_link_regenerable(&order)?;
+
+        let payment_method = order.payment_method.clone().unwrap_or_else(|| "qris".to_string());
+        self.midtrans_client.ensure_method_enabled(&payment_method)?;
+
+        let book_id = order.book_id
+            .ok_or_else(|| AppError::BadRequest("Order tidak memiliki buku".to_string()))?;
+        let book_details = self.get_book_details(book_id).await?;
+        let user_details = self.get_user_details(user_id).await?;
+
+        // Semua panggilan Midtrans di luar row lock supaya webhook / cancel order tidak ikut menunggu
+        // Transaksi lama dibatalkan dulu agar link lama tidak bisa dibayar setelah diganti
+        if let Some(previous) = &order.midtrans_order_id {
+            if let Err(e) = self.midtrans_client.cancel_payment(previous).await {
+                tracing::warn!("Failed to cancel superseded payment {} in Midtrans: {}", previous, e);
+            }
+        }
+
+        let reference = MidtransClient::new_payment_reference(&order.order_number);
+        let mut payment_request = self.build_payment_request(&order, &user_details, &book_details, payment_method);
+        payment_request.transaction_details.order_id = reference.clone();
+
+        let midtrans_response = self.create_payment_with_retry(&payment_request, 3).await?;
+        let payment_url = self.midtrans_client
+            .create_payment_url(&midtrans_response.transaction_id);
+
+        // Lock singkat hanya untuk menyimpan link baru, status dicek ulang karena bisa berubah selama HTTP
+        let mut tx = self.repository.begin_transaction().await?;
+        let current = self.repository.order()
+            .lock_by_id(&mut tx, order.id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Order tidak ditemukan".to_string()))?;
+
+        if let Err(e) = ensure_link_regenerable(&current).and_then(|_| {
+            if current.midtrans_order_id == order.midtrans_order_id {
+                Ok(())
+            } else {
+                Err(AppError::Conflict("Link pembayaran sedang dibuat ulang, coba lagi".to_string()))
+            }
+        }) {
+            drop(tx);
+            if let Err(cancel_err) = self.midtrans_client.cancel_payment(&midtrans_response.transaction_id).await {
+                tracing::warn!("Failed to cancel unused payment {} in Midtrans: {}", midtrans_response.transaction_id, cancel_err);
+            }
+            return Err(e);
+        }
+
         self.repository.order()
-            .update_status(&mut tx, order_id, PaymentStatus::Cancelled, None)
+            .update_payment_link(&mut tx, order.id, &midtrans_response.transaction_id, &payment_url, &reference)
             .await?;
-        
-        // Log audit
+
         self.repository.audit()
-            .log_order_cancelled(&mut tx, user_id, order_id)
+            .log_payment_link_regenerated(&mut tx, user_id, order.id, &reference)
             .await?;
-        
-        // Commit
+
         tx.commit().await
             .map_err(|e| AppError::Database(e.to_string()))?;
-        
-        tracing::info!("Order {} cancelled by user {}", order.order.order_number, user_id);
-        
-        Ok(())
+
+        tracing::info!("Payment link regenerated for order {} ({})", order.order_number, reference);
+
+        self.repository.order()
+            .find_by_id(order.id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Order tidak ditemukan".to_string()))
     }
     
     /// Process webhook dari Midtrans dengan enhanced security dan idempotency
@@ -240,9 +342,9 @@ impl PaymentService {
             )
             .await?;
         
-        // Get order dengan validation
+        // Get order dengan validation (order_id link ulang = order_number + suffix)
         let order = self.repository.order()
-            .find_by_order_number(&payload.order_id)
+            .find_by_order_number(MidtransClient::order_number_from_reference(&payload.order_id))
             .await?
             .ok_or_else(|| {
                 tracing::error!("Webhook received for unknown order: {}", payload.order_id);
@@ -252,6 +354,21 @@ impl PaymentService {
         // Process payment status dari webhook
         let payment_status = self.midtrans_client
             .process_webhook_notification(payload)?;
+
+        // Status non-paid dari link lama yang sudah diganti diabaikan supaya order tetap pending,
+        // pembayaran sukses lewat link lama tetap diproses
+        if !matches!(payment_status, PaymentStatus::Paid) {
+            let active_reference = self.repository.order()
+                .active_payment_reference(order.id)
+                .await?
+                .unwrap_or_else(|| order.order_number.clone());
+
+            if payload.order_id != active_reference {
+                tracing::info!("Ignoring {:?} webhook for superseded payment link {} (active: {})",
+                    payment_status, payload.order_id, active_reference);
+                return Ok(());
+            }
+        }
         
         // Start transaction
         let mut tx = self.repository.begin_transaction().await?;
@@ -275,10 +392,15 @@ impl PaymentService {
         
         // If paid, create user purchase record menggunakan atomic function
         if matches!(payment_status, PaymentStatus::Paid) {
+            // Transaksi yang benar-benar dibayar (bisa link lama) jadi acuan refund / cancel
+            self.repository.order()
+                .record_paid_transaction(&mut tx, order.id, &payload.transaction_id, &payload.order_id)
+                .await?;
+
             self.repository.payment()
                 .complete_payment_atomic(
                     &mut tx,
-                    &payload.order_id,
+                    &order.order_number,
                     &payload.transaction_id,
                     serde_json::to_value(payload).ok(),
                 )
@@ -345,7 +467,10 @@ impl PaymentService {
             .await;
         
         // Clone variables yang dibutuhkan untuk move ke async block
-        let url = format!("{}/api/books/{}", book_service.get_url(), book_id);
+        // Endpoint internal book-service: data buku authoritative (termasuk status aktif)
+        let url = format!("{}/api/internal/books/{}", book_service.get_url(), book_id);
+        let service_key = std::env::var("INTERNAL_SERVICE_KEY")
+            .unwrap_or_else(|_| "internal-service-key-secret".to_string());
         let client = self.http_client.clone();
         let cache_manager = self.cache_manager.clone();  
         let cache_key_copy = cache_key.clone();
@@ -354,6 +479,7 @@ impl PaymentService {
         circuit_breaker.call(async move {
             let response = client
                 .get(&url)
+                .header("X-Service-Key", service_key)
                 .timeout(Duration::from_secs(5))
                 .send()
                 .await
@@ -368,6 +494,10 @@ impl PaymentService {
             
             let book_obj = data["data"]["book"].as_object()
                 .ok_or_else(|| AppError::ExternalService("Invalid book response format".to_string()))?;
+
+            if !book_obj["is_active"].as_bool().unwrap_or(false) {
+                return Err(AppError::BadRequest("Buku tidak tersedia untuk pembelian".to_string()));
+            }
             
             let book_details = BookDetails {
                 title: book_obj["title"].as_str().unwrap_or("Unknown").to_string(),
@@ -383,6 +513,41 @@ impl PaymentService {
         }).await
     }
     
+    /// Cek status verifikasi email via endpoint internal auth-service
+    /// Fail closed: jika status tidak bisa dipastikan, order ditolak
+    async fn ensure_email_verified(&self, user_id: Uuid) -> AppResult<()> {
+        let service_key = std::env::var("INTERNAL_SERVICE_KEY")
+            .unwrap_or_else(|_| "internal-service-key-secret".to_string());
+
+        let response = self.http_client
+            .get(format!("{}/api/internal/users/{}/payment", self.auth_service_url, user_id))
+            .header("X-Service-Key", service_key)
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to verify email status for user {}: {}", user_id, e);
+                AppError::ExternalService(format!("Auth service error: {}", e))
+            })?;
+
+        if !response.status().is_success() {
+            tracing::warn!("Auth service returned {} saat cek verifikasi email user {}", response.status(), user_id);
+            return Err(AppError::ExternalService("Status verifikasi email tidak dapat dipastikan".to_string()));
+        }
+
+        let data: serde_json::Value = response.json().await
+            .map_err(|e| AppError::ExternalService(format!("Parse auth response error: {}", e)))?;
+
+        if !data["user"]["email_verified"].as_bool().unwrap_or(false) {
+            tracing::warn!("User {} dengan email belum terverifikasi mencoba membuat order", user_id);
+            return Err(AppError::EmailNotVerified(
+                "Verifikasi email Anda terlebih dahulu sebelum melakukan pembelian".to_string()
+            ));
+        }
+
+        Ok(())
+    }
+
     /// Get user details - simplified implementation untuk sekarang
     async fn get_user_details(&self, user_id: Uuid) -> AppResult<UserDetails> {
         // Request ke auth service
@@ -473,9 +638,15 @@ impl PaymentService {
             }
         }
         
-        Err(last_error.unwrap_or_else(|| 
-            AppError::ExternalService("All payment attempts failed".to_string())
-        ))
+        let error = last_error.unwrap_or_else(||
+            AppError::PaymentGateway("All payment attempts failed".to_string())
+        );
+        self.notifier.record("midtrans_error", |count| format!(
+            "{} pembuatan transaksi Midtrans gagal dalam window alert (terakhir order {}: {})",
+            count, request.transaction_details.order_id, error
+        ));
+
+        Err(error)
     }
     
     /// Build payment request untuk Midtrans dengan proper validation
@@ -499,14 +670,8 @@ impl PaymentService {
             "".to_string()
         };
         
-        let payment_method_enum = match payment_method.as_str() {
-            "credit_card" => PaymentMethod::CreditCard,
-            "bank_transfer" => PaymentMethod::BankTransfer,
-            "e_wallet" => PaymentMethod::EWallet,
-            "qris" => PaymentMethod::Qris,
-            "convenience_store" => PaymentMethod::ConvenienceStore,
-            _ => PaymentMethod::Qris, // Default fallback
-        };
+        let payment_method_enum = PaymentMethod::from_code(&payment_method)
+            .unwrap_or(PaymentMethod::Qris); // Default fallback
         
         MidtransPaymentRequest {
             transaction_details: TransactionDetails {
@@ -533,7 +698,7 @@ impl PaymentService {
                 brand: Some("PDF Bookstore".to_string()),
                 category: Some("Digital Book".to_string()),
             }],
-            enabled_payments: payment_method_enum.get_enabled_payments(),
+            enabled_payments: self.midtrans_client.enabled_channels(&payment_method_enum),
             callbacks: Some(CallbackUrls {
                 finish: format!("{}/payment/finish", std::env::var("FRONTEND_BASE_URL").unwrap_or_default()),
                 unfinish: format!("{}/payment/unfinish", std::env::var("FRONTEND_BASE_URL").unwrap_or_default()),
@@ -544,3 +709,18 @@ impl PaymentService {
 }
 
 
+
+/// Link pembayaran hanya bisa dibuat ulang untuk order pending yang belum expired
+fn ensure_link_regenerable(order: &Order) -> AppResult<()> {
+    if order.status != "pending" {
+        return Err(AppError::Conflict(
+            format!("Order dengan status '{}' tidak bisa dibuatkan link pembayaran baru", order.status)
+        ));
+    }
+
+    if order.expires_at.is_some_and(|expires_at| expires_at < Utc::now()) {
+        return Err(AppError::BadRequest("Order sudah expired, silakan buat order baru".to_string()));
+    }
+
+    Ok(())
+}
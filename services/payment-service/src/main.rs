@@ -12,13 +12,18 @@ use axum::{
     middleware as axum_middleware,
 };
 use tower::ServiceBuilder;
-use tower_http::{
-    trace::TraceLayer,
-    timeout::TimeoutLayer,
-};
+use tower_http::timeout::TimeoutLayer;
 use sqlx::postgres::PgPoolOptions;
 use std::{env, sync::Arc, time::Duration};
 use tracing::info; 
+use service_common::{
+    admin_notifier::AdminNotifier,
+    concurrency_limit::{ConcurrencyLimit, concurrency_limit_middleware},
+    db_connect::{connect_with_retry, DbConnectRetry},
+    security_headers::{SecurityHeaders, security_headers_middleware},
+    shutdown::Shutdown,
+    trace_sampling::{TraceSampling, trace_sampling_middleware},
+};
 use crate::{
     api::routes,
     core::services::*,
@@ -29,12 +34,16 @@ use crate::{
     },
     utils::{
         scheduler::start_background_jobs,
-        cache::CacheManager,
+        user_directory::UserDirectory,
+        cache::{CacheManager, RedisRetryConfig},
         circuit_breaker::CircuitBreakerManager,  
         service_discovery::ServiceRegistry,      
     },
 };
 
+/// Path default yang response-nya tidak boleh di-cache (data order/admin)
+const NO_STORE_PATHS: &str = "/api/orders,/api/admin";
+
 #[derive(Clone)]
 pub struct AppState {
     pub repository: Arc<Repository>,
@@ -45,6 +54,8 @@ pub struct AppState {
     pub circuit_manager: Arc<CircuitBreakerManager>,  
     pub service_registry: Arc<ServiceRegistry>,       
     pub http_client: reqwest::Client,
+    pub notifier: Arc<AdminNotifier>,
+    pub user_directory: Arc<UserDirectory>,
 }
 
 #[tokio::main]
@@ -59,7 +70,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let database_url = env::var("DATABASE_URL")
         .expect("DATABASE_URL harus di-set di environment");
     
-    let pool = PgPoolOptions::new()
+    // Tunggu PostgreSQL siap (retry dengan backoff) supaya tidak crash loop saat startup bersamaan
+    let pool_options = PgPoolOptions::new()
         .max_connections(
             env::var("DATABASE_MAX_CONNECTIONS")
                 .unwrap_or_else(|_| "10".to_string())
@@ -71,43 +83,33 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .unwrap_or_else(|_| "3".to_string())
                 .parse()
                 .unwrap_or(3)
-        ))
-        .connect(&database_url)
-        .await?;
-    
-    // Test database connection
-    sqlx::query("SELECT 1")
-        .fetch_one(&pool)
-        .await
-        .expect("Gagal ping database");
+        ));
+    let pool = connect_with_retry(pool_options, &database_url, &DbConnectRetry::from_env()).await?;
     
     info!("✅ Database berhasil terkoneksi");
     
     // Initialize repository layer
     let repository = Arc::new(Repository::new(pool.clone()));
     
-    // Cache manager dengan fallback 
+    // Cache manager dengan retry + fallback dummy (reconnect di background setelah shutdown siap)
+    let redis_url = env::var("REDIS_URL").unwrap_or_else(|_| "redis://localhost:6379".to_string());
+    let redis_retry = RedisRetryConfig::from_env();
     let cache_manager = Arc::new(
-        match CacheManager::new(
-            &env::var("REDIS_URL").unwrap_or_else(|_| "redis://localhost:6379".to_string()),
-            "payment_service"
-        ).await {
-            Ok(cache) => {
-                tracing::info!("✅ Redis cache berhasil terkoneksi");
-                cache
-            }
-            Err(e) => {
-                tracing::warn!("⚠️ Redis tidak tersedia, menggunakan dummy cache: {}", e);
-                CacheManager::new_dummy("payment_service")
-            }
-        }
+        CacheManager::connect_with_retry(&redis_url, "payment_service", &redis_retry).await
     );
+    if cache_manager.is_using_redis() {
+        tracing::info!("✅ Redis cache berhasil terkoneksi");
+    }
     
+    // Alert admin untuk kondisi kritis (ADMIN_ALERT_CHANNEL)
+    let notifier = Arc::new(AdminNotifier::from_env("payment-service", 5));
+
     // Initialize payment service
     let payment_service = Arc::new(
         PaymentService::new(
             repository.clone(),
             cache_manager.clone(),
+            notifier.clone(),
         ).await
         .expect("Failed to initialize payment service")
     );
@@ -115,8 +117,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize Midtrans client
     let midtrans_service = Arc::new(
         MidtransClient::new()
-            .expect("Gagal initialize Midtrans client")
+            .unwrap_or_else(|e| panic!("Konfigurasi Midtrans tidak valid: {}", e))
     );
+    tracing::info!("Midtrans environment aktif: {}", midtrans_service.environment().as_str());
 
     
     
@@ -132,25 +135,50 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .parse()
                 .unwrap_or(60)
         )
+        .with_max_tracked_keys(
+            env::var("RATE_LIMIT_MAX_TRACKED_KEYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(100_000)
+        )
     );
 
     // Initialize circuit breaker manager
-    let circuit_manager = Arc::new(CircuitBreakerManager::new());
+    let circuit_manager = Arc::new(CircuitBreakerManager::new().with_notifier(notifier.clone()));
 
     // Initialize service discovery
     let service_registry = Arc::new(ServiceRegistry::new());
     service_registry.init_default_services().await;
 
+    // Koordinasi shutdown untuk semua background job
+    let shutdown = Shutdown::new();
+
+    // Swap dummy cache ke Redis begitu Redis tersedia
+    cache_manager.spawn_reconnect(redis_url, &redis_retry, &shutdown);
+
     // Start health check background job
-    start_health_check_job(service_registry.clone());
+    start_health_check_job(service_registry.clone(), &shutdown);
+
+    // Cleanup key rate limiter yang idle (RATE_LIMIT_CLEANUP_INTERVAL_SECONDS, default 60)
+    rate_limiter.spawn_cleanup(
+        Duration::from_secs(
+            env::var("RATE_LIMIT_CLEANUP_INTERVAL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|secs| *secs > 0)
+                .unwrap_or(60)
+        ),
+        &shutdown,
+    );
 
     let http_client = reqwest::Client::builder()
         .timeout(Duration::from_secs(5))
         .pool_max_idle_per_host(10)
         .build()?;
+    let user_directory = Arc::new(UserDirectory::from_env(http_client.clone()));
     
     // Start background jobs
-    start_background_jobs(repository.clone()).await?;
+    start_background_jobs(repository.clone(), shutdown.clone()).await?;
     
     // Create application state
     let app_state = AppState {
@@ -162,6 +190,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         circuit_manager,
         service_registry,
         http_client,
+        notifier,
+        user_directory,
     };
     
     // Setup CORS
@@ -179,15 +209,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .layer(
             ServiceBuilder::new()
                 // Request tracing (paling luar)
-                .layer(TraceLayer::new_for_http())
+                .layer(axum_middleware::from_fn_with_state(
+                    Arc::new(TraceSampling::from_env()),
+                    trace_sampling_middleware,
+                ))
+                // Batas request in-flight (load shedding)
+                .layer(axum_middleware::from_fn_with_state(
+                    Arc::new(ConcurrencyLimit::from_env()),
+                    concurrency_limit_middleware,
+                ))
                 // Timeout protection
-                .layer(TimeoutLayer::new(Duration::from_secs(30)))
+                .layer(TimeoutLayer::new(request_timeout()))
                 // CORS handling
                 .layer(cors)
         )
         // Security headers middleware (langsung panggil function)
-        .layer(axum_middleware::from_fn(
-            middleware::security::security_headers_middleware
+        .layer(axum_middleware::from_fn_with_state(
+            Arc::new(SecurityHeaders::from_env(NO_STORE_PATHS)),
+            security_headers_middleware
         ))
         // Rate limiting sebagai middleware terpisah
         .layer(axum_middleware::from_fn_with_state(
@@ -213,9 +252,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let listener = tokio::net::TcpListener::bind(&bind_address).await?;
     info!("🚀 Payment Service berjalan di {}", bind_address);
     
-    axum::serve(listener, app)
-        .await
-        .map_err(|e| e.into())
+    let result = axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown.clone().wait_for_signal())
+        .await;
+
+    // Tunggu background job selesai (maks SHUTDOWN_TIMEOUT_SECONDS)
+    shutdown.drain().await;
+
+    result.map_err(|e| e.into())
+}
+
+/// Timeout global request (REQUEST_TIMEOUT_SECONDS, default 30 detik)
+fn request_timeout() -> Duration {
+    let secs = env::var("REQUEST_TIMEOUT_SECONDS")
+        .unwrap_or_else(|_| "30".to_string())
+        .parse()
+        .unwrap_or(30);
+    Duration::from_secs(secs)
 }
 
 // Health check endpoint
@@ -240,13 +293,16 @@ async fn health_check() -> axum::Json<serde_json::Value> {
     }))
 }
 
-fn start_health_check_job(registry: Arc<ServiceRegistry>) {
-    tokio::spawn(async move {
+fn start_health_check_job(registry: Arc<ServiceRegistry>, shutdown: &Shutdown) {
+    let token = shutdown.token.clone();
+    shutdown.spawn(async move {
         let mut interval = tokio::time::interval(Duration::from_secs(30));
         
         loop {
-            interval.tick().await;
-            registry.health_check_all().await;
+            tokio::select! {
+                _ = token.cancelled() => break,
+                _ = interval.tick() => registry.health_check_all().await,
+            }
         }
     });
 }
\ No newline at end of file
@@ -2,7 +2,7 @@
 
 use axum::{
     extract::{Request, State},
-    http::StatusCode,
+    http::{Method, StatusCode},
     middleware::Next,
     response::{Json, Response},
 };
@@ -13,6 +13,7 @@ use std::sync::Arc;
 use std::collections::HashMap;
 use tokio::sync::RwLock;
 use std::time::{Duration, Instant};
+use service_common::token_scopes::{scope_allows, scopes_from_headers, scopes_from_verify};
 
 use crate::{
     AppState,
@@ -61,6 +62,8 @@ pub async fn auth_middleware(
                     tracing::debug!("✓ Gateway auth: user={}, role={}, path={}", 
                         user_id, user_role, path);
 
+                    ensure_scope(scopes_from_headers(req.headers()).as_deref(), req.method(), &path)?;
+
                     // Check admin access
                     if path.contains("/admin/") && user_role != "admin" {
                         tracing::warn!("Non-admin user {} mencoba akases admin via gateway: {}", user_id, path);
@@ -129,6 +132,8 @@ pub async fn auth_middleware(
         if let Some(cached) = cache.get(&token_hash) {
             if cached.expires > Instant::now() {
                 tracing::debug!("✓ Using cached token for user: {}", cached.user_id);
+
+                ensure_scope(cached.scopes.as_deref(), req.method(), &path)?;
                 
                 // Use cached data
                 req.extensions_mut().insert(cached.user_id);
@@ -254,6 +259,9 @@ pub async fn auth_middleware(
         .unwrap_or("")
         .to_string();
 
+    // Personal access token: scope dari /api/auth/verify, aturan sama dengan gateway
+    let scopes = scopes_from_verify(&auth_data);
+
     tracing::debug!("✓ Token verified: user={}, role={}", user_id, user_role);
 
     // Insert ke cache untuk performance
@@ -263,12 +271,15 @@ pub async fn auth_middleware(
             user_id,
             role: user_role.clone(),
             email: user_email.clone(),
+            scopes: scopes.clone(),
             expires: Instant::now() + Duration::from_secs(300), // 5 minutes cache
         });
         
         tracing::debug!("Token cached: {} (expires in 5 minutes)", &token_hash[..8]);
     }
 
+    ensure_scope(scopes.as_deref(), req.method(), &path)?;
+
     // Check admin access
     if path.contains("/admin/") && user_role != "admin" {
         tracing::warn!("Non-admin user {} mencoba akses admin: {}", user_id, path);
@@ -301,6 +312,25 @@ pub async fn auth_middleware(
     Ok(next.run(req).await)
 }
 
+/// Personal access token hanya boleh mengakses route sesuai scope-nya, JWT sesi (tanpa scope) selalu lolos
+fn ensure_scope(scopes: Option<&[String]>, method: &Method, path: &str) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    match scopes {
+        Some(scopes) if !scope_allows(scopes, method, path) => {
+            tracing::warn!("Personal access token tanpa scope untuk {} {}", method, path);
+            Err((
+                StatusCode::FORBIDDEN,
+                Json(ErrorResponse {
+                    success: false,
+                    message: "Scope token tidak mengizinkan request ini".to_string(),
+                    error_code: Some("INSUFFICIENT_SCOPE".to_string()),
+                    details: None,
+                })
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
 /// Log admin access ke database untuk audit trail
 async fn log_admin_access(
     state: &AppState,
@@ -328,6 +358,8 @@ fn is_public_endpoint(path: &str) -> bool {
         "/api/webhook",
         "/api/webhooks",
         "/api/csrf-token", 
+        // Internal endpoint diverifikasi via X-Service-Key di handler
+        "/api/internal/",
     ];
     
     public_paths.iter().any(|&public_path| path.starts_with(public_path))
@@ -339,4 +371,27 @@ pub async fn clean_token_cache() {
     let now = Instant::now();
     cache.retain(|_, token| token.expires > now);
     tracing::debug!("Token cache cleaned, remaining: {}", cache.len());
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pat_scopes_enforced_on_payment_routes() {
+        let books_only = vec!["books:read".to_string()];
+        let orders_read = vec!["orders:read".to_string()];
+        let code = |result: Result<(), (StatusCode, Json<ErrorResponse>)>| {
+            result.map_err(|(status, body)| (status, body.0.error_code))
+        };
+
+        // JWT sesi biasa (tanpa scope) tidak dibatasi
+        assert!(ensure_scope(None, &Method::POST, "/api/orders").is_ok());
+
+        let forbidden = Err((StatusCode::FORBIDDEN, Some("INSUFFICIENT_SCOPE".to_string())));
+        assert_eq!(code(ensure_scope(Some(&books_only), &Method::GET, "/api/orders")), forbidden);
+        assert_eq!(code(ensure_scope(Some(&orders_read), &Method::POST, "/api/orders")), forbidden);
+        assert_eq!(code(ensure_scope(Some(&orders_read), &Method::GET, "/api/admin/orders/stats")), forbidden);
+        assert!(ensure_scope(Some(&orders_read), &Method::GET, "/api/orders/42").is_ok());
+    }
+}
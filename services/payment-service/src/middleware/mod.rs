@@ -1,5 +1,4 @@
 // /pdf-bookstore/services/payment-service/src/middleware/mod.rs
 
 pub mod auth;
-pub mod rate_limit;
-pub mod security;
\ No newline at end of file
+pub mod rate_limit;
\ No newline at end of file
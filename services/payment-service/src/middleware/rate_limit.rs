@@ -1,15 +1,19 @@
 // /pdf-bookstore/services/payment-service/src/middleware/rate_limit.rs
 use axum::{
     extract::{Request, State},
-    http::StatusCode,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     middleware::Next,
-    response::{Json, Response},
+    response::{IntoResponse, Json, Response},
 };
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use chrono::{Utc, Duration};
 use crate::{AppState, models::ErrorResponse};
+use service_common::shutdown::Shutdown;
+
+/// Default batas jumlah key yang dilacak sebelum eviction LRU
+const DEFAULT_MAX_TRACKED_KEYS: usize = 100_000;
 
 /// Rate limiter dengan token bucket algorithm
 #[derive(Clone)]
@@ -17,54 +21,117 @@ pub struct RateLimiter {
     buckets: Arc<RwLock<HashMap<String, TokenBucket>>>,
     max_requests: u32,
     window_seconds: i64,
+    max_tracked_keys: usize,
 }
 
 #[derive(Debug, Clone)]
 struct TokenBucket {
     tokens: u32,
     last_refill: chrono::DateTime<Utc>,
+    last_seen: chrono::DateTime<Utc>,
 }
 
 impl RateLimiter {
     /// Create rate limiter baru
     pub fn new(max_requests: u32, window_seconds: i64) -> Self {
-        let limiter = Self {
+        Self {
             buckets: Arc::new(RwLock::new(HashMap::new())),
             max_requests,
             window_seconds,
-        };
-        
-        // Cleanup task
-        let buckets_clone = limiter.buckets.clone();
-        let window = window_seconds;
-        
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(
-                std::time::Duration::from_secs(window as u64 * 2)
-            );
-            
+            max_tracked_keys: DEFAULT_MAX_TRACKED_KEYS,
+        }
+    }
+
+    /// Batas key yang dilacak (RATE_LIMIT_MAX_TRACKED_KEYS), key paling lama tidak dipakai di-evict saat penuh
+    pub fn with_max_tracked_keys(mut self, max_tracked_keys: usize) -> Self {
+        self.max_tracked_keys = max_tracked_keys.max(1);
+        self
+    }
+
+    /// Cleanup periodik bucket yang sudah lewat satu window (bucket itu toh akan terisi penuh lagi),
+    /// berhenti saat shutdown
+    pub fn spawn_cleanup(&self, every: std::time::Duration, shutdown: &Shutdown) {
+        let limiter = self.clone();
+        let token = shutdown.token.clone();
+
+        shutdown.spawn(async move {
+            let mut interval = tokio::time::interval(every);
+
             loop {
-                interval.tick().await;
-                let mut buckets = buckets_clone.write().await;
-                let cutoff = Utc::now() - Duration::seconds(window * 2);
-                buckets.retain(|_, bucket| bucket.last_refill > cutoff);
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = interval.tick() => {}
+                }
+
+                let removed = limiter.evict_expired().await;
+                if removed > 0 {
+                    tracing::debug!("Rate limiter cleanup: removed {} idle keys", removed);
+                }
             }
         });
-        
-        limiter
+    }
+
+    /// Jumlah key (IP) yang sedang dilacak
+    pub async fn tracked_keys(&self) -> usize {
+        self.buckets.read().await.len()
+    }
+
+    pub fn max_tracked_keys(&self) -> usize {
+        self.max_tracked_keys
+    }
+
+    async fn evict_expired(&self) -> usize {
+        let mut buckets = self.buckets.write().await;
+        let before = buckets.len();
+        let cutoff = Utc::now() - Duration::seconds(self.window_seconds);
+        buckets.retain(|_, bucket| bucket.last_seen > cutoff);
+        before - buckets.len()
+    }
+
+    /// Backstop saat map penuh: buang bucket expired, lalu ~10% key yang paling lama tidak dipakai
+    fn make_room(&self, buckets: &mut HashMap<String, TokenBucket>, now: chrono::DateTime<Utc>) {
+        let cutoff = now - Duration::seconds(self.window_seconds);
+        buckets.retain(|_, bucket| bucket.last_seen > cutoff);
+
+        if buckets.len() < self.max_tracked_keys {
+            return;
+        }
+
+        let evict_count = (self.max_tracked_keys / 10).max(1).min(buckets.len());
+        let mut last_seen: Vec<_> = buckets.values().map(|bucket| bucket.last_seen).collect();
+        let (_, threshold, _) = last_seen.select_nth_unstable(evict_count - 1);
+        let threshold = *threshold;
+
+        let mut evicted = 0;
+        buckets.retain(|_, bucket| {
+            if evicted < evict_count && bucket.last_seen <= threshold {
+                evicted += 1;
+                false
+            } else {
+                true
+            }
+        });
+
+        tracing::warn!("Rate limiter penuh ({} key), {} key LRU di-evict", self.max_tracked_keys, evicted);
     }
     
-    /// Check rate limit
-    pub async fn check_rate_limit(&self, identifier: &str) -> bool {
+    /// Check rate limit, return keputusan beserta info window untuk header
+    pub async fn check_rate_limit(&self, identifier: &str) -> RateLimitDecision {
         let mut buckets = self.buckets.write().await;
         let now = Utc::now();
+
+        if !buckets.contains_key(identifier) && buckets.len() >= self.max_tracked_keys {
+            self.make_room(&mut buckets, now);
+        }
         
         let bucket = buckets.entry(identifier.to_string()).or_insert_with(|| {
             TokenBucket {
                 tokens: self.max_requests,
                 last_refill: now,
+                last_seen: now,
             }
         });
+        bucket.last_seen = now;
         
         // Refill tokens
         let elapsed = (now - bucket.last_refill).num_seconds();
@@ -75,14 +142,60 @@ impl RateLimiter {
             // Gradual refill untuk smooth rate limiting
             let refill_rate = self.max_requests as f64 / self.window_seconds as f64;
             let tokens_to_add = (elapsed as f64 * refill_rate) as u32;
-            bucket.tokens = (bucket.tokens + tokens_to_add).min(self.max_requests);
+            if tokens_to_add > 0 {
+                bucket.tokens = (bucket.tokens + tokens_to_add).min(self.max_requests);
+                // Majukan last_refill sesuai token yang sudah ditambahkan supaya tidak dihitung ulang
+                let consumed = (tokens_to_add as f64 / refill_rate).ceil() as i64;
+                bucket.last_refill += Duration::seconds(consumed.min(elapsed));
+            }
         }
         
-        if bucket.tokens > 0 {
+        let allowed = if bucket.tokens > 0 {
             bucket.tokens -= 1;
             true
         } else {
             false
+        };
+
+        // Window reset = saat bucket terisi penuh kembali
+        let since_refill = (now - bucket.last_refill).num_seconds().max(0);
+        let reset_after_seconds = (self.window_seconds - since_refill).max(1);
+
+        // Perkiraan waktu sampai token berikutnya tersedia
+        let seconds_per_token = (self.window_seconds as f64 / self.max_requests.max(1) as f64).ceil() as i64;
+        let retry_after_seconds = (seconds_per_token - since_refill).clamp(1, reset_after_seconds);
+
+        RateLimitDecision {
+            allowed,
+            limit: self.max_requests,
+            remaining: bucket.tokens,
+            reset_after_seconds,
+            retry_after_seconds,
+        }
+    }
+}
+
+/// Hasil pengecekan rate limit untuk satu request
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    pub reset_after_seconds: i64,
+    pub retry_after_seconds: i64,
+}
+
+impl RateLimitDecision {
+    /// Pasang header X-RateLimit-* (dan Retry-After jika ditolak) ke response
+    pub fn apply_headers(&self, headers: &mut HeaderMap) {
+        let reset_at = Utc::now().timestamp() + self.reset_after_seconds;
+
+        headers.insert("X-RateLimit-Limit", HeaderValue::from(self.limit));
+        headers.insert("X-RateLimit-Remaining", HeaderValue::from(self.remaining));
+        headers.insert("X-RateLimit-Reset", HeaderValue::from(reset_at));
+
+        if !self.allowed {
+            headers.insert(header::RETRY_AFTER, HeaderValue::from(self.retry_after_seconds));
         }
     }
 }
@@ -113,22 +226,92 @@ pub async fn rate_limit_middleware(
         .to_string();
     
     // Check rate limit menggunakan STATE
-    if !state.rate_limiter.check_rate_limit(&identifier).await {
+    let decision = state.rate_limiter.check_rate_limit(&identifier).await;
+
+    if !decision.allowed {
         tracing::warn!("Rate limit terlampaui untuk: {}", identifier);
         
-        return Err((
+        let mut response = (
             StatusCode::TOO_MANY_REQUESTS,
             Json(ErrorResponse {
                 success: false,
                 message: "Terlalu banyak request. Silakan coba lagi nanti.".to_string(),
                 error_code: Some("RATE_LIMIT_EXCEEDED".to_string()),
                 details: Some(serde_json::json!({
-                    "retry_after_seconds": 60,
-                    "limit": "100 requests per menit"
+                    "retry_after_seconds": decision.retry_after_seconds,
+                    "limit": decision.limit,
+                    "reset_after_seconds": decision.reset_after_seconds
                 })),
             })
-        ));
+        ).into_response();
+        decision.apply_headers(response.headers_mut());
+
+        return Ok(response);
     }
     
-    Ok(next.run(req).await)
+    let mut response = next.run(req).await;
+    decision.apply_headers(response.headers_mut());
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rate_limit_decision_exposes_window_info() {
+        let limiter = RateLimiter::new(2, 60);
+
+        let first = limiter.check_rate_limit("10.0.0.1").await;
+        assert!(first.allowed);
+        assert_eq!(first.limit, 2);
+        assert_eq!(first.remaining, 1);
+
+        let second = limiter.check_rate_limit("10.0.0.1").await;
+        assert!(second.allowed);
+        assert_eq!(second.remaining, 0);
+
+        let denied = limiter.check_rate_limit("10.0.0.1").await;
+        assert!(!denied.allowed);
+        assert!(denied.retry_after_seconds >= 1);
+        assert!(denied.retry_after_seconds <= denied.reset_after_seconds);
+        assert!(denied.reset_after_seconds <= 60);
+
+        let mut headers = HeaderMap::new();
+        denied.apply_headers(&mut headers);
+        assert_eq!(headers.get("X-RateLimit-Remaining").unwrap(), "0");
+        assert!(headers.contains_key(header::RETRY_AFTER));
+    }
+
+    #[tokio::test]
+    async fn test_tracked_keys_capped_with_lru_eviction() {
+        let limiter = RateLimiter::new(1, 60).with_max_tracked_keys(10);
+
+        for i in 0..10 {
+            limiter.check_rate_limit(&format!("10.0.0.{}", i)).await;
+        }
+        assert_eq!(limiter.tracked_keys().await, 10);
+
+        // Key yang baru dipakai tidak ikut di-evict, limit-nya tetap berlaku
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        assert!(!limiter.check_rate_limit("10.0.0.9").await.allowed);
+
+        limiter.check_rate_limit("10.0.1.1").await;
+        assert_eq!(limiter.tracked_keys().await, 10);
+        assert!(!limiter.check_rate_limit("10.0.0.9").await.allowed);
+        assert!(limiter.buckets.read().await.contains_key("10.0.1.1"));
+        assert!(!limiter.buckets.read().await.contains_key("10.0.0.0"));
+
+        assert_eq!(limiter.evict_expired().await, 0);
+
+        // Cleanup periodik hanya membuang bucket yang idle satu window penuh
+        let idle = Utc::now() - Duration::seconds(61);
+        for key in ["10.0.0.1", "10.0.0.2"] {
+            limiter.buckets.write().await.get_mut(key).unwrap().last_seen = idle;
+        }
+        assert_eq!(limiter.evict_expired().await, 2);
+        assert_eq!(limiter.tracked_keys().await, 8);
+        assert!(!limiter.check_rate_limit("10.0.0.9").await.allowed);
+    }
 }
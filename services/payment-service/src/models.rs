@@ -64,6 +64,8 @@ pub struct CachedToken {
     pub user_id: Uuid,
     pub role: String,
     pub email: String,
+    /// Scope personal access token, None = JWT sesi biasa
+    pub scopes: Option<Vec<String>>,
     pub expires: Instant
 }
 
@@ -120,6 +122,13 @@ pub struct UpdateOrderRequest {
     pub notes: Option<String>,
 }
 
+/// Request grant/revoke akses buku manual oleh admin (misal setelah sengketa refund selesai)
+#[derive(Debug, Deserialize, Validate)]
+pub struct BookAccessChangeRequest {
+    #[validate(length(min = 1, max = 500, message = "Alasan wajib diisi (maksimal 500 karakter)"))]
+    pub reason: String,
+}
+
 /// Query parameters untuk list orders
 #[derive(Debug, Deserialize)]
 pub struct OrderQueryParams {
@@ -131,6 +140,13 @@ pub struct OrderQueryParams {
     pub sort_order: Option<String>,
 }
 
+/// Query parameters untuk bulk download invoice
+#[derive(Debug, Deserialize)]
+pub struct InvoiceRangeParams {
+    pub from: Option<chrono::NaiveDate>,
+    pub to: Option<chrono::NaiveDate>,
+}
+
 // ========================= RESPONSE DTOs =========================
 
 /// Order dengan detail tambahan untuk response
@@ -145,22 +161,22 @@ pub struct OrderWithDetails {
     pub user_name: Option<String>,
 }
 
-/// Response wrapper untuk single order
+/// Envelope standar response sukses: `{success, message, data, meta}`
+/// `meta` untuk info tambahan (pagination, parameter query) dan tidak diserialisasi jika kosong
 #[derive(Debug, Serialize)]
-pub struct OrderResponse {
+pub struct ApiResponse<T, M = ()> {
     pub success: bool,
     pub message: String,
-    pub data: Option<OrderWithDetails>,
+    pub data: T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<M>,
 }
 
-/// Response wrapper untuk list orders
-#[derive(Debug, Serialize)]
-pub struct OrdersListResponse {
-    pub success: bool,
-    pub message: String,
-    pub data: Vec<OrderWithDetails>,
-    pub pagination: Option<PaginationMeta>,
-}
+/// Response wrapper untuk single order
+pub type OrderResponse = ApiResponse<Option<OrderWithDetails>>;
+
+/// Response wrapper untuk list orders, pagination di meta
+pub type OrdersListResponse = ApiResponse<Vec<OrderWithDetails>, PaginationMeta>;
 
 /// Metadata untuk pagination
 #[derive(Debug, Serialize, Clone)]
@@ -360,6 +376,30 @@ pub enum PaymentMethod {
 }
 
 impl PaymentMethod {
+    /// Semua metode pembayaran yang dikenal, urutan tampilan di checkout
+    pub const ALL: [PaymentMethod; 5] = [
+        PaymentMethod::Qris,
+        PaymentMethod::EWallet,
+        PaymentMethod::BankTransfer,
+        PaymentMethod::CreditCard,
+        PaymentMethod::ConvenienceStore,
+    ];
+
+    /// Kode metode yang dikirim client di CreateOrderRequest.payment_method
+    pub fn code(&self) -> &'static str {
+        match self {
+            PaymentMethod::CreditCard => "credit_card",
+            PaymentMethod::BankTransfer => "bank_transfer",
+            PaymentMethod::EWallet => "e_wallet",
+            PaymentMethod::Qris => "qris",
+            PaymentMethod::ConvenienceStore => "convenience_store",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|method| method.code() == code)
+    }
+
     /// Get display name untuk UI
     pub fn display_name(&self) -> &'static str {
         match self {
@@ -394,6 +434,15 @@ impl PaymentMethod {
     }
 }
 
+/// Metode pembayaran yang aktif untuk ditampilkan di checkout
+#[derive(Debug, Serialize)]
+pub struct PaymentMethodOption {
+    pub code: &'static str,
+    pub display_name: &'static str,
+    /// Channel Midtrans yang aktif untuk metode ini (misal bca_va, gopay)
+    pub channels: Vec<String>,
+}
+
 // ========================= ADMIN MODELS =========================
 
 /// Statistik order untuk admin dashboard
@@ -443,6 +492,30 @@ pub struct RevenueDataPoint {
 
 // ========================= HELPER IMPLEMENTATIONS =========================
 
+impl<T> ApiResponse<T> {
+    /// Response sukses tanpa meta
+    pub fn ok(message: impl Into<String>, data: T) -> Self {
+        Self {
+            success: true,
+            message: message.into(),
+            data,
+            meta: None,
+        }
+    }
+}
+
+impl<T, M> ApiResponse<T, M> {
+    /// Tambahkan meta ke response
+    pub fn with_meta<N>(self, meta: N) -> ApiResponse<T, N> {
+        ApiResponse {
+            success: self.success,
+            message: self.message,
+            data: self.data,
+            meta: Some(meta),
+        }
+    }
+}
+
 impl PaginationMeta {
     /// Create pagination metadata dari hasil query
     pub fn new(current_page: u32, per_page: u32, total_items: i64) -> Self {
@@ -470,4 +543,26 @@ impl Default for OrderQueryParams {
             sort_order: Some("desc".to_string()),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_response_envelope_shape() {
+        let list: OrdersListResponse = ApiResponse::ok("Orders berhasil diambil", vec![])
+            .with_meta(PaginationMeta::new(2, 10, 25));
+        let json = serde_json::to_value(list).unwrap();
+
+        assert_eq!(json["success"], true);
+        assert!(json["data"].is_array());
+        assert_eq!(json["meta"]["current_page"], 2);
+        assert_eq!(json["meta"]["total_pages"], 3);
+        assert!(json.get("pagination").is_none());
+
+        let single = serde_json::to_value(OrderResponse::ok("Order berhasil diambil", None)).unwrap();
+        assert!(single["data"].is_null());
+        assert!(single.get("meta").is_none());
+    }
+}
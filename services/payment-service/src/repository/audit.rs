@@ -60,6 +60,55 @@ impl AuditRepository {
         Ok(())
     }
     
+    /// Log link pembayaran dibuat ulang
+    pub async fn log_payment_link_regenerated(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+        order_id: Uuid,
+        payment_reference: &str,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_logs (user_id, action, resource_type, resource_id, details)
+            VALUES ($1, 'PAYMENT_LINK_REGENERATED', 'order', $2, $3)
+            "#,
+            user_id,
+            order_id,
+            serde_json::json!({ "payment_reference": payment_reference })
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Log grant/revoke akses buku manual oleh admin (action BOOK_ACCESS_GRANTED / BOOK_ACCESS_REVOKED)
+    pub async fn log_book_access_changed(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        admin_id: Uuid,
+        action: &str,
+        user_id: Uuid,
+        book_id: Uuid,
+        details: serde_json::Value,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_logs (user_id, action, resource_type, resource_id, details)
+            VALUES ($1, $2, 'book', $3, $4)
+            "#,
+            admin_id,
+            action,
+            book_id,
+            serde_json::json!({ "target_user_id": user_id, "change": details })
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
     /// Log webhook processed
     pub async fn log_webhook_processed(
         &self,
@@ -142,6 +142,36 @@ impl OrderRepository {
         Ok(order)
     }
 
+    /// Lock order untuk update (dipakai saat membuat ulang link pembayaran)
+    pub async fn lock_by_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        order_id: Uuid,
+    ) -> AppResult<Option<Order>> {
+        let order = sqlx::query_as::<_, Order>(
+            "SELECT * FROM orders WHERE id = $1 FOR UPDATE"
+        )
+        .bind(order_id)
+        .fetch_optional(&mut **tx)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        Ok(order)
+    }
+
+    /// order_id Midtrans yang aktif (None = masih order_number asli)
+    pub async fn active_payment_reference(&self, order_id: Uuid) -> AppResult<Option<String>> {
+        let reference = sqlx::query_scalar!(
+            "SELECT payment_reference FROM orders WHERE id = $1",
+            order_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .flatten();
+
+        Ok(reference)
+    }
+
     /// Find order by idempotency key
     pub async fn find_by_idempotency_key(
         &self,
@@ -187,6 +217,7 @@ impl OrderRepository {
     }
 
     /// Find orders by user dengan pagination 
+    /// user_email / user_name kosong, diisi handler lewat UserDirectory (tabel users milik auth-service)
     pub async fn find_by_user(
         &self,
         user_id: Uuid,
@@ -246,12 +277,9 @@ impl OrderRepository {
                 o.*,
                 b.title as book_title,
                 b.author as book_author,
-                b.cover_path as book_cover_path,
-                u.email as user_email,
-                u.full_name as user_name
+                b.cover_path as book_cover_path
             FROM orders o
             LEFT JOIN books b ON o.book_id = b.id
-            LEFT JOIN users u ON o.user_id = u.id
             WHERE {}
             ORDER BY {} {}
             LIMIT {} OFFSET {}
@@ -277,6 +305,52 @@ impl OrderRepository {
         Ok((orders, total))
     }
 
+    /// Ambil satu halaman order paid milik user dalam rentang tanggal (untuk invoice), urut (waktu bayar, id)
+    /// `after` = cursor (waktu bayar, id) order terakhir di halaman sebelumnya
+    pub async fn find_paid_by_user_in_range(
+        &self,
+        user_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        after: Option<(DateTime<Utc>, Uuid)>,
+        limit: i64,
+    ) -> AppResult<Vec<OrderWithDetails>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT 
+                o.*,
+                b.title as book_title,
+                b.author as book_author,
+                b.cover_path as book_cover_path,
+                u.email as user_email,
+                u.full_name as user_name
+            FROM orders o
+            LEFT JOIN books b ON o.book_id = b.id
+            LEFT JOIN users u ON o.user_id = u.id
+            WHERE o.user_id = $1
+              AND o.status = 'paid'
+              AND COALESCE(o.paid_at, o.created_at) >= $2
+              AND COALESCE(o.paid_at, o.created_at) < $3
+              AND ($4::timestamptz IS NULL OR (COALESCE(o.paid_at, o.created_at), o.id) > ($4, $5))
+            ORDER BY COALESCE(o.paid_at, o.created_at) ASC, o.id ASC
+            LIMIT $6
+            "#
+        )
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .bind(after.map(|(paid_at, _)| paid_at))
+        .bind(after.map(|(_, id)| id))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+        Ok(rows.into_iter()
+            .map(|r| self.map_row_to_order_with_details(r))
+            .collect())
+    }
+
     /// Update order status
     pub async fn update_status(
         &self,
@@ -307,6 +381,29 @@ impl OrderRepository {
         Ok(())
     }
 
+    /// Cancel order pending milik user secara atomic
+    /// Return false jika order sudah tidak pending (paid/expired/cancelled duluan)
+    pub async fn cancel_pending(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        order_id: Uuid,
+        user_id: Uuid,
+    ) -> AppResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE orders
+            SET status = 'cancelled', updated_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND status = 'pending'
+            "#,
+            order_id,
+            user_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     // Tambahkan method simple untuk update status tanpa transaction
     pub async fn update_status_simple(
         &self,
@@ -333,6 +430,129 @@ impl OrderRepository {
         Ok(())
     }
 
+    /// Jumlah yang sudah di-refund untuk order
+    pub async fn get_refunded_amount(&self, order_id: Uuid) -> AppResult<BigDecimal> {
+        let row = sqlx::query!(
+            "SELECT refunded_amount FROM orders WHERE id = $1",
+            order_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Order tidak ditemukan".to_string()))?;
+
+        Ok(row.refunded_amount)
+    }
+
+    /// Reserve jumlah refund secara atomic supaya partial refund tidak melebihi nilai order
+    /// Return (total refunded, full refund?) atau None jika melebihi sisa / status bukan paid
+    pub async fn reserve_refund_amount(
+        &self,
+        order_id: Uuid,
+        amount: &BigDecimal,
+    ) -> AppResult<Option<(BigDecimal, bool)>> {
+        let row = sqlx::query!(
+            r#"
+            UPDATE orders
+            SET refunded_amount = refunded_amount + $2,
+                status = CASE WHEN refunded_amount + $2 >= amount THEN 'refunded' ELSE status END,
+                updated_at = NOW()
+            WHERE id = $1
+            AND status = 'paid'
+            AND refunded_amount + $2 <= amount
+            RETURNING refunded_amount, amount
+            "#,
+            order_id,
+            amount
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| {
+            let fully_refunded = r.refunded_amount >= r.amount;
+            (r.refunded_amount, fully_refunded)
+        }))
+    }
+
+    /// Kembalikan reservasi refund jika proses ke payment gateway gagal
+    /// Status dihitung ulang dari refunded_amount baru: refund paralel lain bisa saja sudah menutup order.
+    /// Jika order kembali paid, akses buku yang sempat dicabut oleh full refund dipulihkan
+    /// (kecuali dicabut manual oleh admin). Return true jika akses dipulihkan
+    pub async fn release_refund_amount(
+        &self,
+        order_id: Uuid,
+        amount: &BigDecimal,
+    ) -> AppResult<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let order = sqlx::query!(
+            r#"
+            UPDATE orders
+            SET refunded_amount = GREATEST(refunded_amount - $2, 0),
+                status = CASE WHEN GREATEST(refunded_amount - $2, 0) >= amount THEN 'refunded' ELSE 'paid' END,
+                updated_at = NOW()
+            WHERE id = $1 AND status IN ('paid', 'refunded')
+            RETURNING status, user_id, book_id, access_revoked_at
+            "#,
+            order_id,
+            amount
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let mut restored = false;
+        if let Some(order) = order {
+            if let (Some("paid"), Some(user_id), Some(book_id), None) =
+                (order.status.as_deref(), order.user_id, order.book_id, order.access_revoked_at)
+            {
+                restored = sqlx::query!(
+                    r#"
+                    INSERT INTO user_purchases (user_id, book_id, order_id, purchased_at, download_count)
+                    VALUES ($1, $2, $3, NOW(), 0)
+                    ON CONFLICT (user_id, book_id) DO NOTHING
+                    "#,
+                    user_id,
+                    book_id,
+                    order_id
+                )
+                .execute(&mut *tx)
+                .await?
+                .rows_affected() > 0;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(restored)
+    }
+
+    /// Cabut akses buku dari order yang sudah full refund
+    /// Row order dikunci supaya tidak balapan dengan release_refund_amount: jika refund paralel
+    /// yang gagal sudah mengembalikan order ke paid, akses tidak dicabut
+    pub async fn revoke_purchase_for_order(&self, order_id: Uuid) -> AppResult<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        let status = sqlx::query_scalar!(
+            "SELECT status FROM orders WHERE id = $1 FOR UPDATE",
+            order_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .flatten();
+
+        if status.as_deref() != Some("refunded") {
+            return Ok(0);
+        }
+
+        let result = sqlx::query!(
+            "DELETE FROM user_purchases WHERE order_id = $1",
+            order_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+
     /// Update Midtrans data
     pub async fn update_midtrans_data(
         &self,
@@ -363,6 +583,56 @@ impl OrderRepository {
         Ok(())
     }
     
+    /// Simpan transaksi Midtrans pengganti untuk link pembayaran ulang
+    pub async fn update_payment_link(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        order_id: Uuid,
+        midtrans_order_id: &str,
+        payment_url: &str,
+        payment_reference: &str,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            UPDATE orders
+            SET midtrans_order_id = $1, payment_url = $2, payment_reference = $3, updated_at = NOW()
+            WHERE id = $4
+            "#,
+            midtrans_order_id,
+            payment_url,
+            payment_reference,
+            order_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+    
+    /// Simpan transaksi Midtrans yang dibayar (bisa dari link lama) sebagai acuan refund / cancel
+    pub async fn record_paid_transaction(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        order_id: Uuid,
+        midtrans_order_id: &str,
+        payment_reference: &str,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            UPDATE orders
+            SET midtrans_order_id = $1, payment_reference = $2, updated_at = NOW()
+            WHERE id = $3
+            "#,
+            midtrans_order_id,
+            payment_reference,
+            order_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
     /// Get admin statistics dengan enhanced error handling
     pub async fn get_admin_stats(&self) -> AppResult<AdminOrderStats> {
         let now = Utc::now();
@@ -559,6 +829,7 @@ impl OrderRepository {
     }
     
     /// Get recent orders untuk admin dengan flexible filtering
+    /// user_email / user_name diisi handler lewat UserDirectory
     pub async fn get_recent_orders(
         &self,
         limit: u32,
@@ -573,12 +844,9 @@ impl OrderRepository {
                     o.*,
                     b.title as book_title,
                     b.author as book_author,
-                    b.cover_path as book_cover_path,
-                    u.email as user_email,
-                    u.full_name as user_name
+                    b.cover_path as book_cover_path
                 FROM orders o
                 LEFT JOIN books b ON o.book_id = b.id
-                LEFT JOIN users u ON o.user_id = u.id
                 WHERE o.status = $1
                 ORDER BY o.created_at DESC
                 LIMIT $2
@@ -593,12 +861,9 @@ impl OrderRepository {
                     o.*,
                     b.title as book_title,
                     b.author as book_author,
-                    b.cover_path as book_cover_path,
-                    u.email as user_email,
-                    u.full_name as user_name
+                    b.cover_path as book_cover_path
                 FROM orders o
                 LEFT JOIN books b ON o.book_id = b.id
-                LEFT JOIN users u ON o.user_id = u.id
                 ORDER BY o.created_at DESC
                 LIMIT $1
                 "#
@@ -668,4 +933,123 @@ impl OrderRepository {
             user_name: row.try_get("user_name").ok(),
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use service_common::db_connect::test_pool;
+
+    #[tokio::test]
+    async fn test_parallel_partial_refunds_never_exceed_order_amount() {
+        let pool = test_pool().await;
+        let repository = OrderRepository::new(pool.clone());
+
+        let user_id = sqlx::query_scalar!(
+            "INSERT INTO users (email, password_hash, full_name) VALUES ($1, 'x', 'Refund Test') RETURNING id",
+            format!("refund-{}@example.com", Uuid::new_v4())
+        )
+        .fetch_one(&pool).await.unwrap();
+        let book_id = sqlx::query_scalar!(
+            "INSERT INTO books (title, author, price) VALUES ('Refund Test', 'Tester', 10000) RETURNING id"
+        )
+        .fetch_one(&pool).await.unwrap();
+        let order_id = sqlx::query_scalar!(
+            "INSERT INTO orders (user_id, book_id, order_number, amount, status) VALUES ($1, $2, $3, 10000, 'paid') RETURNING id",
+            user_id, book_id, format!("ORD-REFUND-{}", Uuid::new_v4().simple())
+        )
+        .fetch_one(&pool).await.unwrap();
+
+        // 5 partial refund 3000 paralel untuk order 10000: hanya 3 yang boleh lolos
+        let partial = BigDecimal::from(3000);
+        let mut refunds = tokio::task::JoinSet::new();
+        for _ in 0..5 {
+            let (repository, amount) = (OrderRepository::new(pool.clone()), partial.clone());
+            refunds.spawn(async move { repository.reserve_refund_amount(order_id, &amount).await.unwrap() });
+        }
+        let reserved = refunds.join_all().await.into_iter().flatten().collect::<Vec<_>>();
+        let after_parallel = repository.get_refunded_amount(order_id).await.unwrap();
+
+        // Sisa 1000 menutup order (full refund), refund tambahan ditolak
+        let last = repository.reserve_refund_amount(order_id, &BigDecimal::from(1000)).await.unwrap();
+        let over = repository.reserve_refund_amount(order_id, &BigDecimal::from(1)).await.unwrap();
+
+        // Gateway gagal untuk salah satu partial: reservasi dikembalikan, order kembali paid
+        repository.release_refund_amount(order_id, &partial).await.unwrap();
+        let status = sqlx::query_scalar!("SELECT status FROM orders WHERE id = $1", order_id)
+            .fetch_one(&pool).await.unwrap();
+        let after_release = repository.get_refunded_amount(order_id).await.unwrap();
+
+        sqlx::query!("DELETE FROM orders WHERE id = $1", order_id).execute(&pool).await.unwrap();
+        sqlx::query!("DELETE FROM books WHERE id = $1", book_id).execute(&pool).await.unwrap();
+        sqlx::query!("DELETE FROM users WHERE id = $1", user_id).execute(&pool).await.unwrap();
+
+        assert_eq!(reserved.len(), 3);
+        assert!(reserved.iter().all(|(_, full)| !full));
+        assert_eq!(after_parallel, BigDecimal::from(9000));
+        assert_eq!(last, Some((BigDecimal::from(10000), true)));
+        assert_eq!(over, None);
+        assert_eq!(status.as_deref(), Some("paid"));
+        assert_eq!(after_release, BigDecimal::from(7000));
+    }
+
+    #[tokio::test]
+    async fn test_failed_partial_refund_after_full_refund_restores_access() {
+        let pool = test_pool().await;
+        let repository = OrderRepository::new(pool.clone());
+
+        let user_id = sqlx::query_scalar!(
+            "INSERT INTO users (email, password_hash, full_name) VALUES ($1, 'x', 'Refund Race') RETURNING id",
+            format!("refund-race-{}@example.com", Uuid::new_v4())
+        )
+        .fetch_one(&pool).await.unwrap();
+        let book_id = sqlx::query_scalar!(
+            "INSERT INTO books (title, author, price) VALUES ('Refund Race', 'Tester', 10000) RETURNING id"
+        )
+        .fetch_one(&pool).await.unwrap();
+        let order_id = sqlx::query_scalar!(
+            "INSERT INTO orders (user_id, book_id, order_number, amount, status) VALUES ($1, $2, $3, 10000, 'paid') RETURNING id",
+            user_id, book_id, format!("ORD-RACE-{}", Uuid::new_v4().simple())
+        )
+        .fetch_one(&pool).await.unwrap();
+        sqlx::query!(
+            "INSERT INTO user_purchases (user_id, book_id, order_id) VALUES ($1, $2, $3)",
+            user_id, book_id, order_id
+        )
+        .execute(&pool).await.unwrap();
+
+        let state = || async {
+            let status = sqlx::query_scalar!("SELECT status FROM orders WHERE id = $1", order_id)
+                .fetch_one(&pool).await.unwrap();
+            let owned = sqlx::query_scalar!("SELECT COUNT(*) FROM user_purchases WHERE order_id = $1", order_id)
+                .fetch_one(&pool).await.unwrap();
+            (status.unwrap_or_default(), owned.unwrap_or(0))
+        };
+
+        // Partial A (3000) in-flight, partial B (7000) menutup order lalu akses dicabut,
+        // kemudian Midtrans gagal untuk A: order kembali paid dan akses dipulihkan
+        let a = BigDecimal::from(3000);
+        repository.reserve_refund_amount(order_id, &a).await.unwrap().unwrap();
+        let full = repository.reserve_refund_amount(order_id, &BigDecimal::from(7000)).await.unwrap();
+        let revoked = repository.revoke_purchase_for_order(order_id).await.unwrap();
+        let restored = repository.release_refund_amount(order_id, &a).await.unwrap();
+        let after_release = state().await;
+
+        // Urutan sebaliknya: A sudah di-release sebelum B mencabut akses -> akses tidak dicabut
+        repository.reserve_refund_amount(order_id, &a).await.unwrap().unwrap();
+        repository.release_refund_amount(order_id, &a).await.unwrap();
+        let late_revoke = repository.revoke_purchase_for_order(order_id).await.unwrap();
+        let after_late_revoke = state().await;
+
+        sqlx::query!("DELETE FROM user_purchases WHERE order_id = $1", order_id).execute(&pool).await.unwrap();
+        sqlx::query!("DELETE FROM orders WHERE id = $1", order_id).execute(&pool).await.unwrap();
+        sqlx::query!("DELETE FROM books WHERE id = $1", book_id).execute(&pool).await.unwrap();
+        sqlx::query!("DELETE FROM users WHERE id = $1", user_id).execute(&pool).await.unwrap();
+
+        assert_eq!(full, Some((BigDecimal::from(10000), true)));
+        assert_eq!(revoked, 1);
+        assert!(restored);
+        assert_eq!(after_release, ("paid".to_string(), 1));
+        assert_eq!(late_revoke, 0);
+        assert_eq!(after_late_revoke, ("paid".to_string(), 1));
+    }
+}
@@ -33,6 +33,121 @@ impl PaymentRepository {
         Ok(result.count.unwrap_or(0) > 0)
     }
     
+    /// Kepemilikan authoritative untuk book-service: row user_purchases, atau order paid
+    /// yang belum di-refund / dicabut admin (webhook pembayaran belum sempat membuat row)
+    pub async fn has_book_access(&self, user_id: Uuid, book_id: Uuid) -> AppResult<bool> {
+        let owned = sqlx::query_scalar!(
+            r#"
+            SELECT
+                EXISTS(SELECT 1 FROM user_purchases WHERE user_id = $1 AND book_id = $2)
+                OR EXISTS(
+                    SELECT 1 FROM orders
+                    WHERE user_id = $1 AND book_id = $2 AND status = 'paid' AND access_revoked_at IS NULL
+                ) AS "owned!"
+            "#,
+            user_id,
+            book_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(owned)
+    }
+
+    /// Pulihkan akses buku secara manual (idempotent), dikaitkan ke order paid/refunded terakhir jika ada
+    /// Return false jika user sudah punya akses
+    pub async fn grant_book_access(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+        book_id: Uuid,
+    ) -> AppResult<(bool, Option<Uuid>)> {
+        let exists = sqlx::query!(
+            r#"
+            SELECT
+                EXISTS(SELECT 1 FROM users WHERE id = $1) as "user_exists!",
+                EXISTS(SELECT 1 FROM books WHERE id = $2) as "book_exists!"
+            "#,
+            user_id,
+            book_id
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        if !exists.user_exists {
+            return Err(AppError::NotFound("User tidak ditemukan".to_string()));
+        }
+        if !exists.book_exists {
+            return Err(AppError::NotFound("Buku tidak ditemukan".to_string()));
+        }
+
+        let order_id = sqlx::query_scalar!(
+            r#"
+            SELECT id FROM orders
+            WHERE user_id = $1 AND book_id = $2 AND status IN ('paid', 'refunded')
+            ORDER BY created_at DESC
+            LIMIT 1
+            "#,
+            user_id,
+            book_id
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_purchases (user_id, book_id, order_id, purchased_at, download_count)
+            VALUES ($1, $2, $3, NOW(), 0)
+            ON CONFLICT (user_id, book_id) DO NOTHING
+            "#,
+            user_id,
+            book_id,
+            order_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE orders SET access_revoked_at = NULL WHERE user_id = $1 AND book_id = $2 AND access_revoked_at IS NOT NULL",
+            user_id,
+            book_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok((result.rows_affected() > 0, order_id))
+    }
+
+    /// Cabut akses buku secara manual, return false jika user memang tidak punya akses
+    /// Order paid ditandai access_revoked_at agar fallback verifikasi book-service tidak memulihkan akses
+    pub async fn revoke_book_access(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+        book_id: Uuid,
+    ) -> AppResult<bool> {
+        let purchases = sqlx::query!(
+            "DELETE FROM user_purchases WHERE user_id = $1 AND book_id = $2",
+            user_id,
+            book_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        let orders = sqlx::query!(
+            r#"
+            UPDATE orders SET access_revoked_at = NOW()
+            WHERE user_id = $1 AND book_id = $2 AND status = 'paid' AND access_revoked_at IS NULL
+            "#,
+            user_id,
+            book_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(purchases.rows_affected() > 0 || orders.rows_affected() > 0)
+    }
+
     /// Complete payment dengan atomic function
     pub async fn complete_payment_atomic(
         &self,
@@ -174,31 +289,32 @@ impl PaymentRepository {
         Ok(())
     }
 
-    /// Get refund by order ID
-    pub async fn get_refund_by_order_id(
+    /// Get semua refund (termasuk partial) untuk order
+    pub async fn get_refunds_by_order_id(
         &self,
         order_id: Uuid,
-    ) -> AppResult<Option<serde_json::Value>> {
-        let result = sqlx::query!(
+    ) -> AppResult<Vec<serde_json::Value>> {
+        let rows = sqlx::query!(
             r#"
             SELECT 
                 id, refund_id, amount, reason, status, created_at
             FROM refunds 
             WHERE order_id = $1
+            ORDER BY created_at ASC
             "#,
             order_id
         )
-        .fetch_optional(&self.pool)
+        .fetch_all(&self.pool)
         .await?;
         
-        Ok(result.map(|r| serde_json::json!({
+        Ok(rows.into_iter().map(|r| serde_json::json!({
             "id": r.id,
             "refund_id": r.refund_id,
             "amount": r.amount,
             "reason": r.reason,
             "status": r.status,
             "created_at": r.created_at
-        })))
+        })).collect())
     }
 
     /// Create refund record
@@ -230,4 +346,54 @@ impl PaymentRepository {
         tracing::info!("Refund created: {} for order {}", id, order_id);
         Ok(id)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use service_common::db_connect::test_pool;
+
+    /// Butuh database asli (DATABASE_URL)
+    #[tokio::test]
+    async fn test_revoke_blocks_paid_order_fallback() {
+        let pool = test_pool().await;
+        let repository = PaymentRepository::new(pool.clone());
+
+        let user_id = sqlx::query_scalar!(
+            "INSERT INTO users (email, password_hash, full_name) VALUES ($1, 'x', 'Revoke Test') RETURNING id",
+            format!("revoke-{}@example.com", Uuid::new_v4())
+        )
+        .fetch_one(&pool).await.unwrap();
+        let book_id = sqlx::query_scalar!(
+            "INSERT INTO books (title, author, price) VALUES ('Revoke Test', 'Tester', 10000) RETURNING id"
+        )
+        .fetch_one(&pool).await.unwrap();
+        // Order paid tanpa row user_purchases (webhook terlambat)
+        sqlx::query!(
+            "INSERT INTO orders (user_id, book_id, order_number, amount, status) VALUES ($1, $2, $3, 10000, 'paid')",
+            user_id, book_id, format!("ORD-REVOKE-{}", Uuid::new_v4().simple())
+        )
+        .execute(&pool).await.unwrap();
+
+        let before = repository.has_book_access(user_id, book_id).await.unwrap();
+
+        let mut tx = pool.begin().await.unwrap();
+        let revoked = repository.revoke_book_access(&mut tx, user_id, book_id).await.unwrap();
+        tx.commit().await.unwrap();
+        let after_revoke = repository.has_book_access(user_id, book_id).await.unwrap();
+
+        let mut tx = pool.begin().await.unwrap();
+        repository.grant_book_access(&mut tx, user_id, book_id).await.unwrap();
+        tx.commit().await.unwrap();
+        let after_grant = repository.has_book_access(user_id, book_id).await.unwrap();
+
+        sqlx::query!("DELETE FROM user_purchases WHERE user_id = $1", user_id).execute(&pool).await.unwrap();
+        sqlx::query!("DELETE FROM orders WHERE user_id = $1", user_id).execute(&pool).await.unwrap();
+        sqlx::query!("DELETE FROM books WHERE id = $1", book_id).execute(&pool).await.unwrap();
+        sqlx::query!("DELETE FROM users WHERE id = $1", user_id).execute(&pool).await.unwrap();
+
+        assert!(before);
+        assert!(revoked, "order paid tanpa user_purchases tetap dihitung sebagai perubahan");
+        assert!(!after_revoke, "download ditolak setelah akses dicabut");
+        assert!(after_grant);
+    }
+}
@@ -15,9 +15,9 @@ pub fn print_startup_banner(bind_address: &str) {
     tracing::info!("    POST /api/webhook/midtrans     - Payment webhook");
     tracing::info!("  Protected:");
     tracing::info!("    POST /api/orders                - Create order");
-    tracing::info!("    GET  /api/orders                - List orders");
+    tracing::info!("    GET  /api/orders?status=pending - List orders");
     tracing::info!("    GET  /api/orders/:id            - Get order");
-    tracing::info!("    PUT  /api/orders/:id/cancel     - Cancel order");
+    tracing::info!("    POST /api/orders/:id/cancel     - Cancel pending order");
     tracing::info!("  Admin:");
     tracing::info!("    GET  /api/admin/orders/stats    - Order statistics");
     tracing::info!("    GET  /api/admin/analytics/*     - Analytics endpoints");
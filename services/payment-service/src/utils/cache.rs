@@ -2,48 +2,146 @@
 
 use redis::{aio::ConnectionManager, AsyncCommands, Client, RedisError};
 use serde::{Serialize, Deserialize};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock as StdRwLock};
 use std::collections::HashMap;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
+use service_common::shutdown::Shutdown;
+
 
 /// Redis cache manager dengan async support
+/// Koneksi dibagi antar clone sehingga reconnect di background langsung terlihat di semua handler
 #[derive(Clone)]
 pub struct CacheManager {
-    conn_manager: Option<Arc<ConnectionManager>>,
+    conn_manager: Arc<StdRwLock<Option<ConnectionManager>>>,
     namespace: String,
     dummy_cache: Arc<RwLock<HashMap<String, (String, chrono::DateTime<chrono::Utc>)>>>,
-    is_dummy: bool,
+}
+
+/// Retry koneksi Redis saat startup + interval reconnect selama masih dummy
+#[derive(Debug, Clone)]
+pub struct RedisRetryConfig {
+    pub attempts: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub reconnect_interval: Duration,
+}
+
+impl RedisRetryConfig {
+    /// REDIS_CONNECT_ATTEMPTS (default 5), REDIS_CONNECT_DELAY_MS (default 500, dobel tiap attempt),
+    /// REDIS_CONNECT_MAX_DELAY_MS (default 5000), REDIS_RECONNECT_INTERVAL_SECONDS (default 30, 0 = nonaktif)
+    pub fn from_env() -> Self {
+        let read = |key: &str, default: u64| {
+            std::env::var(key).ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(default)
+        };
+
+        Self {
+            attempts: read("REDIS_CONNECT_ATTEMPTS", 5).max(1) as u32,
+            initial_delay: Duration::from_millis(read("REDIS_CONNECT_DELAY_MS", 500)),
+            max_delay: Duration::from_millis(read("REDIS_CONNECT_MAX_DELAY_MS", 5000)),
+            reconnect_interval: Duration::from_secs(read("REDIS_RECONNECT_INTERVAL_SECONDS", 30)),
+        }
+    }
+
+    /// Delay sebelum attempt ke-n (mulai 1), exponential backoff dengan batas atas
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_delay.saturating_mul(factor).min(self.max_delay)
+    }
 }
 
 
 impl CacheManager {
     /// Create cache manager baru dengan async connection
     pub async fn new(redis_url: &str, namespace: &str) -> Result<Self, RedisError> {
-        let client = Client::open(redis_url)?;
-        let conn_manager = ConnectionManager::new(client).await?;
+        let conn_manager = Self::connect(redis_url).await?;
         
         tracing::info!("Redis cache manager berhasil terhubung");
         
-        Ok(Self {
-            conn_manager: Some(Arc::new(conn_manager)),
-            namespace: namespace.to_string(),
-            dummy_cache: Arc::new(RwLock::new(HashMap::new())),
-            is_dummy: false,
-        })
+        Ok(Self::with_connection(Some(conn_manager), namespace))
+    }
+
+    /// Coba konek Redis beberapa kali dengan backoff, fallback ke dummy cache jika tetap gagal
+    pub async fn connect_with_retry(redis_url: &str, namespace: &str, config: &RedisRetryConfig) -> Self {
+        for attempt in 1..=config.attempts {
+            match Self::new(redis_url, namespace).await {
+                Ok(cache) => return cache,
+                Err(e) if attempt < config.attempts => {
+                    let delay = config.backoff(attempt);
+                    tracing::warn!("Redis belum tersedia (attempt {}/{}): {}, retry dalam {:?}",
+                        attempt, config.attempts, e, delay);
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => tracing::warn!("⚠️ Redis tidak tersedia setelah {} attempt: {}", config.attempts, e),
+            }
+        }
+
+        Self::new_dummy(namespace)
     }
 
     // Create dummy cache untuk fallback ketika Redis tidak tersedia
     pub fn new_dummy(namespace: &str) -> Self {
         tracing::warn!("⚠️ Menggunakan dummy in-memory cache (Redis tidak tersedia)");
         
+        Self::with_connection(None, namespace)
+    }
+
+    fn with_connection(conn_manager: Option<ConnectionManager>, namespace: &str) -> Self {
         Self {
-            conn_manager: None,
+            conn_manager: Arc::new(StdRwLock::new(conn_manager)),
             namespace: namespace.to_string(),
             dummy_cache: Arc::new(RwLock::new(HashMap::new())),
-            is_dummy: true,
         }
     }
+
+    async fn connect(redis_url: &str) -> Result<ConnectionManager, RedisError> {
+        let client = Client::open(redis_url)?;
+        ConnectionManager::new(client).await
+    }
+
+    /// Background task: selama masih dummy, coba konek ulang tiap `reconnect_interval`
+    /// lalu swap ke Redis. Task berhenti setelah terhubung atau saat shutdown
+    pub fn spawn_reconnect(&self, redis_url: String, config: &RedisRetryConfig, shutdown: &Shutdown) {
+        if self.is_using_redis() || config.reconnect_interval.is_zero() {
+            return;
+        }
+
+        let cache = self.clone();
+        let interval = config.reconnect_interval;
+        let token = shutdown.token.clone();
+
+        shutdown.spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = tokio::time::sleep(interval) => {}
+                }
+
+                match Self::connect(&redis_url).await {
+                    Ok(conn_manager) => {
+                        cache.swap_connection(conn_manager).await;
+                        tracing::info!("✅ Redis terhubung kembali, dummy cache diganti Redis");
+                        break;
+                    }
+                    Err(e) => tracing::debug!("Reconnect Redis gagal: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Pasang koneksi Redis dan buang isi dummy cache (data lama tidak dipindahkan)
+    async fn swap_connection(&self, conn_manager: ConnectionManager) {
+        if let Ok(mut conn) = self.conn_manager.write() {
+            *conn = Some(conn_manager);
+        }
+        self.dummy_cache.write().await.clear();
+    }
+
+    /// Clone koneksi Redis aktif, None = mode dummy
+    fn connection(&self) -> Option<ConnectionManager> {
+        self.conn_manager.read().ok().and_then(|conn| conn.clone())
+    }
     
     /// Generate cache key dengan namespace
     fn make_key(&self, key: &str) -> String {
@@ -57,7 +155,7 @@ impl CacheManager {
         value: &T,
         ttl_seconds: u64,
     ) -> Result<(), RedisError> {
-        if self.is_dummy {
+        let Some(mut conn) = self.connection() else {
             // Gunakan dummy cache
             let serialized = serde_json::to_string(value)
                 .map_err(|e| RedisError::from((redis::ErrorKind::TypeError,
@@ -69,31 +167,23 @@ impl CacheManager {
             
             tracing::debug!("Dummy cache set: key={}, ttl={}s", key, ttl_seconds);
             return Ok(());
-        }
+        };
         
-        // Handle Option dengan pattern matching
-        match &self.conn_manager {
-            Some(conn_manager) => {
-                let serialized = serde_json::to_string(value)
-                    .map_err(|e| RedisError::from((
-                        redis::ErrorKind::TypeError,
-                        "Serialization failed",
-                        e.to_string()
-                    )))?;
-                
-                let mut conn = conn_manager.as_ref().clone();
+        let serialized = serde_json::to_string(value)
+            .map_err(|e| RedisError::from((
+                redis::ErrorKind::TypeError,
+                "Serialization failed",
+                e.to_string()
+            )))?;
 
-                conn.set_ex::<_, _, ()>(
-                    self.make_key(key), 
-                    serialized, 
-                    ttl_seconds
-                ).await?;
-                
-                tracing::debug!("Cache set: key={}, ttl={}s", key, ttl_seconds);
-                Ok(())
-            }
-            None => Ok(()) 
-        }
+        conn.set_ex::<_, _, ()>(
+            self.make_key(key), 
+            serialized, 
+            ttl_seconds
+        ).await?;
+        
+        tracing::debug!("Cache set: key={}, ttl={}s", key, ttl_seconds);
+        Ok(())
     }
     
     /// Get value dari cache
@@ -101,7 +191,7 @@ impl CacheManager {
         &self,
         key: &str,
     ) -> Result<Option<T>, RedisError> {
-        if self.is_dummy {
+        let Some(mut conn) = self.connection() else {
             // Gunakan dummy cache
             let mut cache = self.dummy_cache.write().await;
             let now = chrono::Utc::now();
@@ -121,31 +211,20 @@ impl CacheManager {
             
             tracing::debug!("Dummy cache miss: key={}", key);
             return Ok(None);
-        }
+        };
         
-        // Handle Option dengan pattern matching
-        match &self.conn_manager {
-            Some(conn_manager) => {
-                // Clone connection dari Arc
-                let mut conn = conn_manager.as_ref().clone();
-                let result: Option<String> = conn.get(self.make_key(key)).await?;
-                
-                match result {
-                    Some(data) => {
-                        let deserialized = serde_json::from_str(&data)
-                            .map_err(|e| RedisError::from((redis::ErrorKind::TypeError,
-                                "Deserialization gagal", e.to_string())))?;
-                        tracing::debug!("Redis cache hit: key={}", key);
-                        Ok(Some(deserialized))
-                    }
-                    None => {
-                        tracing::debug!("Redis cache miss: key={}", key);
-                        Ok(None)
-                    }
-                }
+        let result: Option<String> = conn.get(self.make_key(key)).await?;
+        
+        match result {
+            Some(data) => {
+                let deserialized = serde_json::from_str(&data)
+                    .map_err(|e| RedisError::from((redis::ErrorKind::TypeError,
+                        "Deserialization gagal", e.to_string())))?;
+                tracing::debug!("Redis cache hit: key={}", key);
+                Ok(Some(deserialized))
             }
             None => {
-                // Fallback: cache miss jika conn_manager None
+                tracing::debug!("Redis cache miss: key={}", key);
                 Ok(None)
             }
         }
@@ -153,34 +232,23 @@ impl CacheManager {
     
     /// Delete key dari cache
     pub async fn delete(&self, key: &str) -> Result<(), RedisError> {
-        if self.is_dummy {
+        let Some(mut conn) = self.connection() else {
             // Gunakan dummy cache
             let mut cache = self.dummy_cache.write().await;
             cache.remove(&self.make_key(key));
             tracing::debug!("Dummy cache delete: key={}", key);
             return Ok(());
-        }
+        };
         
-        // Handle Option dengan pattern matching
-        match &self.conn_manager {
-            Some(conn_manager) => {
-                // Clone connection dari Arc
-                let mut conn = conn_manager.as_ref().clone();
-                conn.del::<_, ()>(self.make_key(key)).await?;
-                
-                tracing::debug!("Redis cache delete: key={}", key);
-                Ok(())
-            }
-            None => {
-                // Silently succeed jika conn_manager None
-                Ok(())
-            }
-        }
+        conn.del::<_, ()>(self.make_key(key)).await?;
+        
+        tracing::debug!("Redis cache delete: key={}", key);
+        Ok(())
     }
     
     /// Invalidate pattern - hapus semua key yang cocok dengan pola
     pub async fn invalidate_pattern(&self, pattern: &str) -> Result<u64, RedisError> {
-        if self.is_dummy {
+        let Some(mut conn) = self.connection() else {
             // Gunakan dummy cache
             let mut cache = self.dummy_cache.write().await;
             let pattern_key = format!("{}:{}*", self.namespace, pattern);
@@ -197,43 +265,33 @@ impl CacheManager {
             
             tracing::debug!("Dummy cache invalidate pattern: pattern={}, deleted={}", pattern, deleted);
             return Ok(deleted);
-        }
+        };
         
-        // Handle Option dengan pattern matching
-        match &self.conn_manager {
-            Some(conn_manager) => {
-                // Clone connection dari Arc
-                let mut conn = conn_manager.as_ref().clone();
-                let pattern_key = format!("{}:{}*", self.namespace, pattern);
-                
-                // Gunakan SCAN instead of KEYS untuk production (lebih aman)
-                let keys: Vec<String> = redis::cmd("KEYS")
-                    .arg(&pattern_key)
-                    .query_async(&mut conn)
-                    .await?;
-                
-                if keys.is_empty() {
-                    return Ok(0);
-                }
-                
-                let deleted: u64 = conn.del(keys).await?;
-                tracing::debug!("Redis cache invalidate pattern: pattern={}, deleted={}", pattern, deleted);
-                Ok(deleted)
-            }
-            None => {
-                Ok(0)
-            }
+        let pattern_key = format!("{}:{}*", self.namespace, pattern);
+        
+        // Gunakan SCAN instead of KEYS untuk production (lebih aman)
+        let keys: Vec<String> = redis::cmd("KEYS")
+            .arg(&pattern_key)
+            .query_async(&mut conn)
+            .await?;
+        
+        if keys.is_empty() {
+            return Ok(0);
         }
+        
+        let deleted: u64 = conn.del(keys).await?;
+        tracing::debug!("Redis cache invalidate pattern: pattern={}, deleted={}", pattern, deleted);
+        Ok(deleted)
     }
 
     /// Check apakah cache menggunakan Redis atau dummy
     pub fn is_using_redis(&self) -> bool {
-        !self.is_dummy
+        self.conn_manager.read().map(|conn| conn.is_some()).unwrap_or(false)
     }
     
     /// Get cache stats untuk monitoring
     pub async fn get_stats(&self) -> serde_json::Value {
-        if self.is_dummy {
+        if !self.is_using_redis() {
             let cache = self.dummy_cache.read().await;
             let now = chrono::Utc::now();
             let active_entries = cache.values()
@@ -250,9 +308,30 @@ impl CacheManager {
         } else {
             serde_json::json!({
                 "type": "redis",
-                "connected": self.conn_manager.is_some(),
+                "connected": true,
                 "namespace": self.namespace,
             })
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_until_max() {
+        let config = RedisRetryConfig {
+            attempts: 5,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_millis(3000),
+            reconnect_interval: Duration::from_secs(30),
+        };
+
+        assert_eq!(config.backoff(1), Duration::from_millis(500));
+        assert_eq!(config.backoff(2), Duration::from_millis(1000));
+        assert_eq!(config.backoff(3), Duration::from_millis(2000));
+        assert_eq!(config.backoff(4), Duration::from_millis(3000));
+        assert_eq!(config.backoff(40), Duration::from_millis(3000));
+    }
+}
@@ -5,6 +5,7 @@ use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use std::collections::HashMap;
 use crate::utils::error::{AppError, AppResult};
+use service_common::admin_notifier::AdminNotifier;
 
 /// State dari circuit breaker
 #[derive(Debug, Clone, PartialEq)]
@@ -30,6 +31,7 @@ pub struct CircuitBreaker {
     state: Arc<RwLock<CircuitState>>,
     stats: Arc<RwLock<CircuitStats>>,
     config: CircuitBreakerConfig,
+    notifier: Option<Arc<AdminNotifier>>,
 }
 
 /// Konfigurasi circuit breaker
@@ -66,6 +68,7 @@ impl CircuitBreaker {
                 state_changed_at: Instant::now(),
             })),
             config,
+            notifier: None,
         }
     }
 
@@ -153,6 +156,12 @@ impl CircuitBreaker {
                     "Circuit breaker {} transisi ke OPEN setelah {} failures berturut-turut", 
                     self.name, stats.consecutive_failures
                 );
+                if let Some(notifier) = &self.notifier {
+                    notifier.notify(
+                        &format!("circuit_open:{}", self.name),
+                        format!("Circuit breaker {} OPEN setelah {} kegagalan berturut-turut", self.name, stats.consecutive_failures),
+                    );
+                }
             }
         }
     }
@@ -190,15 +199,23 @@ impl CircuitBreaker {
 /// Circuit breaker manager untuk manage multiple circuit breakers
 pub struct CircuitBreakerManager {
     breakers: Arc<RwLock<HashMap<String, Arc<CircuitBreaker>>>>,
+    notifier: Option<Arc<AdminNotifier>>,
 }
 
 impl CircuitBreakerManager {
     pub fn new() -> Self {
         Self {
             breakers: Arc::new(RwLock::new(HashMap::new())),
+            notifier: None,
         }
     }
 
+    /// Alert admin saat circuit breaker mana pun transisi ke OPEN
+    pub fn with_notifier(mut self, notifier: Arc<AdminNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
     /// Get atau create circuit breaker untuk service
     pub async fn get_or_create(&self, service_name: &str) -> Arc<CircuitBreaker> {
         let mut breakers = self.breakers.write().await;
@@ -207,10 +224,10 @@ impl CircuitBreakerManager {
             return breaker.clone();
         }
         
-        let breaker = Arc::new(CircuitBreaker::new(
-            service_name.to_string(),
-            CircuitBreakerConfig::default(),
-        ));
+        let breaker = Arc::new(CircuitBreaker {
+            notifier: self.notifier.clone(),
+            ..CircuitBreaker::new(service_name.to_string(), CircuitBreakerConfig::default())
+        });
         
         breakers.insert(service_name.to_string(), breaker.clone());
         breaker
@@ -3,6 +3,7 @@
 use tower_http::cors::CorsLayer;
 use axum::http::{header, Method, HeaderValue};
 use std::env;
+use std::time::Duration;
 
 /// Setup CORS layer untuk payment service
 pub fn create_cors_layer() -> CorsLayer {
@@ -23,7 +24,7 @@ fn build_development_cors() -> CorsLayer {
         .allow_methods(get_allowed_methods())
         .allow_headers(get_allowed_headers())
         .allow_credentials(true)
-        .max_age(std::time::Duration::from_secs(3600))
+        .max_age(get_max_age(3600))
 }
 
 /// Build CORS configuration untuk production dengan security ketat
@@ -35,8 +36,15 @@ fn build_production_cors() -> CorsLayer {
         .allow_methods(get_production_methods())
         .allow_headers(get_allowed_headers())
         .allow_credentials(true)
-        .expose_headers([header::CONTENT_LENGTH, header::CONTENT_TYPE])
-        .max_age(std::time::Duration::from_secs(86400))
+        .expose_headers([
+            header::CONTENT_LENGTH,
+            header::CONTENT_TYPE,
+            header::RETRY_AFTER,
+            header::HeaderName::from_static("x-ratelimit-limit"),
+            header::HeaderName::from_static("x-ratelimit-remaining"),
+            header::HeaderName::from_static("x-ratelimit-reset"),
+        ])
+        .max_age(get_max_age(86400))
 }
 
 /// Parse origins dari environment variable
@@ -114,6 +122,15 @@ fn get_allowed_headers() -> Vec<header::HeaderName> {
     ]
 }
 
+/// Durasi cache preflight (Access-Control-Max-Age), override via CORS_MAX_AGE_SECONDS
+fn get_max_age(default_secs: u64) -> Duration {
+    let secs = env::var("CORS_MAX_AGE_SECONDS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default_secs);
+    Duration::from_secs(secs)
+}
+
 /// Ambil environment mode dari env variable
 fn get_environment() -> String {
     env::var("ENVIRONMENT")
@@ -35,9 +35,15 @@ pub enum AppError {
     
     #[error("Forbidden: {0}")]
     Forbidden(String),
+
+    #[error("Email not verified: {0}")]
+    EmailNotVerified(String),
     
     #[error("External service error: {0}")]
     ExternalService(String),
+
+    #[error("Payment gateway error: {0}")]
+    PaymentGateway(String),
     
     #[error("Configuration error: {0}")]
     Configuration(String),
@@ -88,6 +94,11 @@ impl IntoResponse for AppError {
                 "FORBIDDEN",
                 msg.clone(),
             ),
+            AppError::EmailNotVerified(msg) => (
+                StatusCode::FORBIDDEN,
+                "EMAIL_NOT_VERIFIED",
+                msg.clone(),
+            ),
             AppError::ExternalService(msg) => {
                 tracing::error!("External service error: {}", msg);
                 (
@@ -96,6 +107,14 @@ impl IntoResponse for AppError {
                     "External service unavailable".to_string(),
                 )
             }
+            AppError::PaymentGateway(msg) => {
+                tracing::error!("Payment gateway error: {}", msg);
+                (
+                    StatusCode::BAD_GATEWAY,
+                    "PAYMENT_GATEWAY_ERROR",
+                    "Payment gateway gagal memproses request".to_string(),
+                )
+            }
             AppError::Configuration(msg) => {
                 tracing::error!("Configuration error: {}", msg);
                 (
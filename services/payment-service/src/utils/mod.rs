@@ -5,10 +5,12 @@ pub mod constants;
 pub mod logger;
 pub mod cors;
 pub mod scheduler;
+pub mod retention;
 pub mod banner;
 pub mod cache;
 pub mod circuit_breaker;
 pub mod service_discovery;
 pub mod health;
+pub mod user_directory;
 
 pub use constants::constants::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
\ No newline at end of file
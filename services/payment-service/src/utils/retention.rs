@@ -0,0 +1,11 @@
+// /pdf-bookstore/services/payment-service/src/utils/retention.rs
+
+use service_common::retention::{RetentionConfig, RetentionPolicy};
+
+/// Tabel log milik payment-service yang di-purge retention job
+/// Set `RETENTION_<TABLE>_DAYS=0` untuk menonaktifkan tabel tertentu
+pub fn retention_config() -> RetentionConfig {
+    RetentionConfig::from_env(vec![
+        RetentionPolicy::from_env("audit_logs", 365),
+    ])
+}
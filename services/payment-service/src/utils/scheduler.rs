@@ -10,7 +10,9 @@ use tokio_cron_scheduler::{JobScheduler, Job};
 use crate::{
     repository::Repository,
     utils::error::AppResult,
+    utils::retention::retention_config,
 };
+use service_common::{retention::run_retention, shutdown::Shutdown};
 
 // Scheduler metrics for monitoring
 pub struct SchedulerMetrics {
@@ -43,21 +45,26 @@ impl SchedulerMetrics {
 }
 
 /// Start background jobs untuk maintenance tasks
-pub async fn start_background_jobs(repository: Arc<Repository>) -> AppResult<()> {
-    let scheduler = JobScheduler::new().await
+/// Saat shutdown, iterasi yang sedang jalan diselesaikan dan tidak ada trigger baru
+pub async fn start_background_jobs(repository: Arc<Repository>, shutdown: Shutdown) -> AppResult<()> {
+    let mut scheduler = JobScheduler::new().await
         .map_err(|e| crate::utils::error::AppError::Configuration(
             format!("Failed to create scheduler: {}", e)
         ))?;
     
     // Job 1: Cleanup expired orders setiap 1 jam
     let repo_clone = repository.clone();
+    let shutdown_clone = shutdown.clone();
     let cleanup_job = Job::new_async("0 0 */1 * * *", move |_uuid, _l| {
         let repo = repo_clone.clone();
+        let shutdown = shutdown_clone.clone();
         Box::pin(async move {
-            if let Err(e) = cleanup_expired_orders_job(repo, SchedulerMetrics::new()).await {
-                tracing::error!("Failed to cleanup expired orders: {}", e);
-            }
-            tracing::info!("Cleanup expired orders job completed");
+            shutdown.run_job(async move {
+                if let Err(e) = cleanup_expired_orders_job(repo, SchedulerMetrics::new()).await {
+                    tracing::error!("Failed to cleanup expired orders: {}", e);
+                }
+                tracing::info!("Cleanup expired orders job completed");
+            }).await;
         })
     })
     .map_err(|e| crate::utils::error::AppError::Configuration(
@@ -71,12 +78,16 @@ pub async fn start_background_jobs(repository: Arc<Repository>) -> AppResult<()>
     
     // Job 2: Log statistics setiap hari jam 00:00
     let repo_clone2 = repository.clone();
+    let shutdown_clone2 = shutdown.clone();
     let stats_job = Job::new_async("0 0 0 * * *", move |_uuid, _l| {
         let repo = repo_clone2.clone();
+        let shutdown = shutdown_clone2.clone();
         Box::pin(async move {
-            if let Err(e) = daily_stats_job(repo).await {
-                tracing::error!("Failed to log daily stats: {}", e);
-            }
+            shutdown.run_job(async move {
+                if let Err(e) = daily_stats_job(repo).await {
+                    tracing::error!("Failed to log daily stats: {}", e);
+                }
+            }).await;
         })
     })
     .map_err(|e| crate::utils::error::AppError::Configuration(
@@ -89,10 +100,14 @@ pub async fn start_background_jobs(repository: Arc<Repository>) -> AppResult<()>
         ))?;
 
     // Job 3: Clean token cache setiap 30 menit
+    let shutdown_clone3 = shutdown.clone();
     let cache_cleanup_job = Job::new_async("0 */30 * * * *", move |_uuid, _l| {
+        let shutdown = shutdown_clone3.clone();
         Box::pin(async move {
-            crate::middleware::auth::clean_token_cache().await;
-            tracing::debug!("Token cache cleanup completed");
+            shutdown.run_job(async {
+                crate::middleware::auth::clean_token_cache().await;
+                tracing::debug!("Token cache cleanup completed");
+            }).await;
         })
     })
     .map_err(|e| crate::utils::error::AppError::Configuration(
@@ -104,6 +119,32 @@ pub async fn start_background_jobs(repository: Arc<Repository>) -> AppResult<()>
             format!("Failed to add cache cleanup job: {}", e)
         ))?;
 
+    // Job 4: Retention audit_logs setiap hari jam 03:00
+    let repo_clone4 = repository.clone();
+    let retention_config = retention_config();
+    let shutdown_clone4 = shutdown.clone();
+    let retention_job = Job::new_async("0 0 3 * * *", move |_uuid, _l| {
+        let repo = repo_clone4.clone();
+        let config = retention_config.clone();
+        let shutdown = shutdown_clone4.clone();
+        Box::pin(async move {
+            shutdown.run_job(async move {
+                match run_retention(&repo.pool, &config).await {
+                    Ok(summary) => tracing::info!("Log retention result: {}", summary),
+                    Err(e) => tracing::error!("Log retention failed: {}", e),
+                }
+            }).await;
+        })
+    })
+    .map_err(|e| crate::utils::error::AppError::Configuration(
+        format!("Failed to create retention job: {}", e)
+    ))?;
+
+    scheduler.add(retention_job).await
+        .map_err(|e| crate::utils::error::AppError::Configuration(
+            format!("Failed to add retention job: {}", e)
+        ))?;
+
     // Start scheduler
     scheduler.start().await
         .map_err(|e| crate::utils::error::AppError::Configuration(
@@ -112,11 +153,14 @@ pub async fn start_background_jobs(repository: Arc<Repository>) -> AppResult<()>
     
     tracing::info!("✅ Background jobs scheduler started");
     
-    // Keep scheduler running in background
-    tokio::spawn(async move {
-        loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+    // Stop scheduler saat shutdown (job yang sedang jalan tetap ditunggu lewat Shutdown)
+    let token = shutdown.token.clone();
+    shutdown.spawn(async move {
+        token.cancelled().await;
+        if let Err(e) = scheduler.shutdown().await {
+            tracing::warn!("Failed to stop scheduler cleanly: {}", e);
         }
+        tracing::info!("Background jobs scheduler stopped");
     });
     
     Ok(())
@@ -0,0 +1,23 @@
+// /pdf-bookstore/services/payment-service/src/utils/user_directory.rs
+
+use uuid::Uuid;
+
+use crate::models::OrderWithDetails;
+
+pub use service_common::user_directory::UserDirectory;
+
+/// Isi user_email / user_name order dari auth-service
+pub async fn enrich_orders(directory: &UserDirectory, orders: &mut [OrderWithDetails]) {
+    let user_ids: Vec<Uuid> = orders.iter().filter_map(|o| o.order.user_id).collect();
+    if user_ids.is_empty() {
+        return;
+    }
+
+    let profiles = directory.lookup(&user_ids).await;
+    for order in orders.iter_mut() {
+        if let Some(profile) = order.order.user_id.and_then(|id| profiles.get(&id)) {
+            order.user_email = Some(profile.email.clone());
+            order.user_name = Some(profile.full_name.clone());
+        }
+    }
+}
@@ -2,6 +2,7 @@
 
 use uuid::Uuid;
 use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
 use crate::utils::error::{AppError, AppResult};
 
 /// Validasi UUID format
@@ -164,6 +165,19 @@ pub fn validate_days_range(days: u32) -> AppResult<u32> {
     Ok(days)
 }
 
+/// Validasi rentang tanggal invoice (maksimal 1 tahun)
+pub fn validate_invoice_range(from: NaiveDate, to: NaiveDate) -> AppResult<()> {
+    if from > to {
+        return Err(AppError::BadRequest("Tanggal 'from' harus sebelum 'to'".to_string()));
+    }
+
+    if (to - from).num_days() > 366 {
+        return Err(AppError::BadRequest("Rentang invoice maksimal 1 tahun".to_string()));
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -194,4 +208,12 @@ mod tests {
         assert!(validate_pagination(1, 0).is_err());
         assert!(validate_pagination(1, 101).is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_validate_invoice_range() {
+        let from = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        assert!(validate_invoice_range(from, NaiveDate::from_ymd_opt(2025, 12, 31).unwrap()).is_ok());
+        assert!(validate_invoice_range(from, NaiveDate::from_ymd_opt(2026, 3, 1).unwrap()).is_err());
+        assert!(validate_invoice_range(from, NaiveDate::from_ymd_opt(2024, 12, 1).unwrap()).is_err());
+    }
+}